@@ -1,9 +1,71 @@
+use std::{env, fs, path::Path};
+
 fn main() {
-    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    // Printing a rerun-if-env-changed directive disables the default of
+    // rerunning the build script when any file changes, so the files from which
+    // the header is generated are listed explicitly.
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Maximum number of systematic packets of an image. By default it is the
+    // maximum supported by the SSDV FEC code.
+    println!("cargo:rerun-if-env-changed=SSDV_FEC_MAX_K");
+    let max_k = match env::var("SSDV_FEC_MAX_K") {
+        Ok(value) => value
+            .parse::<u16>()
+            .ok()
+            .filter(|&k| k > 0)
+            .unwrap_or_else(|| panic!("SSDV_FEC_MAX_K must be between 1 and 65535, not {value}")),
+        Err(_) => u16::MAX,
+    };
+    fs::write(
+        Path::new(&out_dir).join("config.rs"),
+        format!("const MAX_K: usize = {max_k};\n"),
+    )
+    .unwrap();
+
+    // The ssdv_fec_cs_* functions are declared in the header only if the
+    // library is built with them.
+    let critical_section = if env::var_os("CARGO_FEATURE_CRITICAL_SECTION").is_some() {
+        "\n\n/**\n * Defined if the library is built with the critical-section feature\n */\n#define SSDV_FEC_CRITICAL_SECTION"
+    } else {
+        ""
+    };
+
+    let mut config = cbindgen::Config::default();
+    config.language = cbindgen::Language::C;
+    // Lengths in the ssdv_fec2_* API are size_t
+    config.usize_is_size_t = true;
+    config.after_includes = Some(format!(
+        "
+/**
+ * Maximum number of systematic packets of an image, configured with the
+ * SSDV_FEC_MAX_K environment variable when the library is built
+ */
+#define SSDV_FEC_MAX_K {max_k}
+
+/**
+ * Length in bytes of a buffer that can hold the systematic packets of any image
+ */
+#define SSDV_FEC_MAX_IMAGE_LEN (SSDV_FEC_MAX_K * SSDV_FEC_MAX_PACKET_LEN)
+
+/**
+ * Length in bytes of a bitmap with one bit for each systematic packet of any
+ * image
+ */
+#define SSDV_FEC_MAX_BITMAP_LEN ((SSDV_FEC_MAX_K + 7) / 8){critical_section}"
+    ));
+    config.defines.insert(
+        "feature = critical-section".to_string(),
+        "SSDV_FEC_CRITICAL_SECTION".to_string(),
+    );
 
     cbindgen::Builder::new()
         .with_crate(crate_dir)
-        .with_language(cbindgen::Language::C)
+        .with_config(config)
         .generate()
         .expect("Unable to generate bindings")
         .write_to_file("erminaz_ssdv_fec.h");
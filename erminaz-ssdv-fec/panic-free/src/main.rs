@@ -0,0 +1,74 @@
+//! Link-time check that the encoder and decoder cannot panic.
+//!
+//! This program calls the encoding and decoding functions of the `ssdv_fec`
+//! crate with the `panic-free` feature on inputs that the compiler cannot see.
+//! The panic handler calls a function that does not exist, so the program only
+//! links if the compiler has removed all the panicking paths, and with them the
+//! panic formatting machinery. The program is never run.
+
+#![no_std]
+#![no_main]
+
+use core::{hint::black_box, panic::PanicInfo, ptr, slice};
+use ssdv_fec::{
+    ChunkScratch, Decoder, DecoderOptions, Encoder, Packet, ReadOnlyEncoder, SSDVNoFecPacket,
+    SSDVPacket, StreamingDecoder,
+};
+
+extern "C" {
+    // This function is not defined anywhere.
+    fn ssdv_fec_panic_is_possible() -> !;
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    // SAFETY: this call is never linked.
+    unsafe { ssdv_fec_panic_is_possible() }
+}
+
+// Returns a slice that the compiler cannot see through.
+fn opaque<'a, T>() -> &'a mut [T] {
+    // SAFETY: the program is never run.
+    unsafe { slice::from_raw_parts_mut(black_box(ptr::dangling_mut()), black_box(0)) }
+}
+
+// Returns a reference that the compiler cannot see through.
+fn opaque_ref<'a, T>() -> &'a mut T {
+    // SAFETY: the program is never run.
+    unsafe { &mut *black_box(ptr::dangling_mut()) }
+}
+
+fn check<P: Packet>() {
+    if let Ok(encoder) = Encoder::<P>::new(opaque()) {
+        encoder.encode(black_box(0), opaque_ref::<P>());
+        let _ = black_box(encoder.try_encode_range(black_box(0), opaque()));
+        let mut chunks = encoder.encode_chunks(black_box(0));
+        chunks.next_chunk(opaque());
+        chunks.next_chunk_with_scratch(opaque(), &mut ChunkScratch::new());
+        let mut words = encoder.encode_words(black_box(0));
+        black_box(words.next_word());
+        black_box(words.next_byte());
+        let _ = black_box(encoder.encode_into(black_box(0), opaque()));
+        encoder.release(black_box(true));
+    }
+    if let Ok(encoder) = ReadOnlyEncoder::<P>::new(opaque(), opaque()) {
+        encoder.encode(black_box(0), opaque_ref::<P>());
+    }
+    let _ = black_box(Decoder::decode_with_stats::<P>(
+        opaque(),
+        opaque(),
+        &DecoderOptions::new().lenient(black_box(false)),
+    ));
+    let _ = black_box(Decoder::decode_from::<P>(opaque(), opaque()));
+    let mut decoder = StreamingDecoder::<P>::new(opaque());
+    black_box(decoder.push(opaque_ref::<P>()));
+    let _ = black_box(decoder.try_decode_partial(opaque(), opaque()));
+    let _ = black_box(decoder.decode_with_stats(opaque()));
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    check::<SSDVPacket>();
+    check::<SSDVNoFecPacket>();
+    loop {}
+}
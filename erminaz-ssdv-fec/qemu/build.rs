@@ -0,0 +1,11 @@
+use std::{env, fs, path::Path};
+
+fn main() {
+    // Put memory.x where the linker script of cortex-m-rt can find it.
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::copy("memory.x", Path::new(&out_dir).join("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={out_dir}");
+    println!("cargo:rustc-link-arg=-Tlink.x");
+    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,8 @@
+/* Memory of the Arm MPS2 AN386 board emulated by QEMU */
+MEMORY
+{
+  /* SSRAM1, where QEMU loads the program */
+  FLASH : ORIGIN = 0x00000000, LENGTH = 4M
+  /* SSRAM2 and SSRAM3 */
+  RAM : ORIGIN = 0x20000000, LENGTH = 4M
+}
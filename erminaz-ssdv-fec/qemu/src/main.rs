@@ -0,0 +1,145 @@
+//! Tests of the C API on the Cortex-M4 under QEMU.
+//!
+//! This program runs encode and decode round trips through the C API of the
+//! library built for `thumbv7em-none-eabi`, on the Cortex-M4 of the Arm MPS2
+//! AN386 board emulated by QEMU, so that regressions in the `no_std` paths and
+//! in the code generated for the target are caught before testing on the
+//! hardware. The result is reported through semihosting, and the program exits
+//! QEMU with a failure status if any check fails or if the library reports a
+//! fault.
+
+#![no_std]
+#![no_main]
+
+use core::ffi::{c_int, c_void};
+use cortex_m_rt::entry;
+use cortex_m_semihosting::{debug, hprintln};
+use erminaz_ssdv_fec::{
+    ssdv_fec_chunks_next, ssdv_fec_chunks_t, ssdv_fec_decoder_decode_packets,
+    ssdv_fec_decoder_init, ssdv_fec_decoder_t, ssdv_fec_encoder_encode_chunks,
+    ssdv_fec_encoder_encode_packet, ssdv_fec_encoder_encode_range, ssdv_fec_encoder_encode_words,
+    ssdv_fec_encoder_init, ssdv_fec_encoder_release, ssdv_fec_encoder_t, ssdv_fec_format_t,
+    ssdv_fec_init, ssdv_fec_words_next, ssdv_fec_words_t,
+};
+
+// Functions of the C API that are defined in private modules of the library.
+extern "C" {
+    fn ssdv_fec_selftest(scratch: *mut c_void, len: usize) -> c_int;
+    fn ssdv_fec_set_fault_handler(handler: Option<unsafe extern "C" fn()>);
+}
+
+static IMG_230_SSDV: &[u8; IMAGE_LEN] =
+    include_bytes!("../../../ssdv-fec/src/test_data/img_230.ssdv");
+
+const LEN: usize = 218;
+const K: usize = 65;
+const IMAGE_LEN: usize = K * LEN;
+
+const FORMAT: ssdv_fec_format_t = ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2;
+
+// Reports a failed check and exits QEMU with a failure status.
+macro_rules! check {
+    ($cond:expr, $what:expr) => {
+        if !$cond {
+            hprintln!("FAILED: {}", $what);
+            debug::exit(debug::EXIT_FAILURE);
+        }
+    };
+}
+
+unsafe extern "C" fn fault() {
+    hprintln!("FAILED: fault reported by the library");
+    debug::exit(debug::EXIT_FAILURE);
+}
+
+fn selftest() {
+    let mut scratch = [0u8; 3072];
+    let ret = unsafe { ssdv_fec_selftest(scratch.as_mut_ptr().cast(), scratch.len()) };
+    check!(ret == 0, "selftest");
+}
+
+fn encode_decode() {
+    let mut encoder_buffer = *IMG_230_SSDV;
+    // Contexts are usually zero-initialized static variables in C.
+    let mut encoder: ssdv_fec_encoder_t = unsafe { core::mem::zeroed() };
+    let ret = unsafe {
+        ssdv_fec_encoder_init(
+            &mut encoder,
+            FORMAT,
+            encoder_buffer.as_mut_ptr().cast(),
+            K as c_int,
+        )
+    };
+    check!(ret == 0, "encoder init");
+
+    // The image is decoded from the last systematic packet and FEC packets.
+    let mut input = [0u8; IMAGE_LEN];
+    let ret = unsafe {
+        ssdv_fec_encoder_encode_range(&encoder, K as u16, K as c_int, input.as_mut_ptr().cast())
+    };
+    check!(ret == K as c_int, "encode range");
+    input[..LEN].copy_from_slice(&IMG_230_SSDV[(K - 1) * LEN..]);
+
+    // The packets generated in chunks and word by word are the same as the
+    // packets generated in a buffer.
+    let packet_id = 2 * K as u16;
+    let mut packet = [0u8; LEN];
+    unsafe {
+        ssdv_fec_encoder_encode_packet(&encoder, c_int::from(packet_id), packet.as_mut_ptr().cast())
+    };
+    let mut chunks: ssdv_fec_chunks_t = unsafe { core::mem::zeroed() };
+    let ret = unsafe { ssdv_fec_encoder_encode_chunks(&encoder, packet_id, &mut chunks) };
+    check!(ret == 0, "encode chunks");
+    let mut chunked = [0u8; LEN];
+    for chunk in chunked.chunks_mut(50) {
+        let ret = unsafe {
+            ssdv_fec_chunks_next(&mut chunks, chunk.as_mut_ptr().cast(), chunk.len() as c_int)
+        };
+        check!(ret == chunk.len() as c_int, "chunks next");
+    }
+    check!(chunked == packet, "packet generated in chunks");
+    let mut words: ssdv_fec_words_t = unsafe { core::mem::zeroed() };
+    let ret = unsafe { ssdv_fec_encoder_encode_words(&encoder, packet_id, &mut words) };
+    check!(ret == 0, "encode words");
+    let mut worded = [0u8; LEN];
+    for word in worded.chunks_mut(2) {
+        let ret = unsafe { ssdv_fec_words_next(&mut words, word.as_mut_ptr().cast()) };
+        check!(ret == 2, "words next");
+    }
+    check!(worded == packet, "packet generated word by word");
+
+    let mut decoder: ssdv_fec_decoder_t = unsafe { core::mem::zeroed() };
+    let mut output = [0u8; IMAGE_LEN];
+    let ret =
+        unsafe { ssdv_fec_decoder_init(&mut decoder, FORMAT, core::ptr::null_mut(), 0, false) };
+    check!(ret == 0, "decoder init");
+    let ret = unsafe {
+        ssdv_fec_decoder_decode_packets(
+            &decoder,
+            input.as_mut_ptr().cast(),
+            K as c_int,
+            output.as_mut_ptr().cast(),
+            K as c_int,
+        )
+    };
+    check!(ret == K as c_int, "decode");
+    check!(output == *IMG_230_SSDV, "decoded image");
+
+    let ret = unsafe { ssdv_fec_encoder_release(&mut encoder, true) };
+    check!(ret == 0, "encoder release");
+    check!(encoder_buffer == *IMG_230_SSDV, "restored image");
+}
+
+#[entry]
+fn main() -> ! {
+    unsafe { ssdv_fec_set_fault_handler(Some(fault)) };
+    ssdv_fec_init();
+    selftest();
+    encode_decode();
+    hprintln!("PASSED");
+    debug::exit(debug::EXIT_SUCCESS);
+    // QEMU has exited, unless semihosting is not enabled
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -0,0 +1,307 @@
+//! Critical-section protected C API.
+//!
+//! The `ssdv_fec_cs_*` functions are built when the `critical-section` feature
+//! is enabled. They work as the corresponding `ssdv_fec_*` functions, but run
+//! inside a critical section of the [`critical_section`] crate, so that
+//! calls on the same context from different interrupt priorities or tasks are
+//! serialized and cannot corrupt its state. On the Cortex-M4 the critical
+//! section is implemented by disabling interrupts, so interrupts are delayed
+//! for the whole duration of the call. Pushing packets and querying the
+//! decoder status are short, but decoding an image takes much longer.
+//!
+//! The unprotected functions are still available, and they must not be used
+//! concurrently with the protected functions on the same context.
+
+use super::*;
+
+// The critical section implementation for single-core Cortex-M
+// microcontrollers is provided by the cortex-m crate.
+#[cfg(target_arch = "arm")]
+use cortex_m as _;
+
+/// Prepares an SSDV FEC encoder in a critical section.
+///
+/// This works as [`ssdv_fec_encoder_init`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_init` apply, except that the
+/// encoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_encoder_init(
+    encoder: *mut ssdv_fec_encoder_t,
+    format: ssdv_fec_format_t,
+    ssdv_packets: *mut c_char,
+    num_ssdv_packets: c_int,
+) -> c_int {
+    critical_section::with(|_| {
+        ssdv_fec_encoder_init(encoder, format, ssdv_packets, num_ssdv_packets)
+    })
+}
+
+/// Releases an SSDV FEC encoder in a critical section.
+///
+/// This works as [`ssdv_fec_encoder_release`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_release` apply, except that
+/// the encoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_encoder_release(
+    encoder: *mut ssdv_fec_encoder_t,
+    restore: bool,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_encoder_release(encoder, restore))
+}
+
+/// Generates a FEC encoded packet in a critical section.
+///
+/// This works as [`ssdv_fec_encoder_encode_packet`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_encode_packet` apply, except
+/// that the encoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_encoder_encode_packet(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: c_int,
+    output: *mut c_char,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_encoder_encode_packet(encoder, packet_id, output))
+}
+
+/// Generates a range of FEC encoded packets in a critical section.
+///
+/// This works as [`ssdv_fec_encoder_encode_range`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_encode_range` apply, except
+/// that the encoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_encoder_encode_range(
+    encoder: *const ssdv_fec_encoder_t,
+    first_id: u16,
+    count: c_int,
+    output: *mut c_char,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_encoder_encode_range(encoder, first_id, count, output))
+}
+
+/// Returns the status of an SSDV FEC encoder in a critical section.
+///
+/// This works as [`ssdv_fec_encoder_status`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_status` apply, except that
+/// the encoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_encoder_status(
+    encoder: *const ssdv_fec_encoder_t,
+) -> ssdv_fec_encoder_status_t {
+    critical_section::with(|_| ssdv_fec_encoder_status(encoder))
+}
+
+/// Prepares an SSDV FEC decoder in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_init`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_init` apply, except that the
+/// decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_init(
+    decoder: *mut ssdv_fec_decoder_t,
+    format: ssdv_fec_format_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    critical_section::with(|_| {
+        ssdv_fec_decoder_init(decoder, format, buffer, num_buffer_packets, lenient)
+    })
+}
+
+/// Prepares an SSDV FEC decoder with an output buffer in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_setup`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_setup` apply, except that
+/// the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_setup(
+    decoder: *mut ssdv_fec_decoder_t,
+    format: ssdv_fec_format_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    critical_section::with(|_| {
+        ssdv_fec_decoder_setup(
+            decoder,
+            format,
+            buffer,
+            num_buffer_packets,
+            output,
+            num_output_packets,
+            lenient,
+        )
+    })
+}
+
+/// Pushes a received packet into an SSDV FEC decoder in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_push`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_push` apply, except that the
+/// decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_push(
+    decoder: *mut ssdv_fec_decoder_t,
+    packet: *const c_char,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_decoder_push(decoder, packet))
+}
+
+/// Returns the status of an SSDV FEC decoder in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_status`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_status` apply, except that
+/// the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_status(
+    decoder: *const ssdv_fec_decoder_t,
+) -> ssdv_fec_decoder_status_t {
+    critical_section::with(|_| ssdv_fec_decoder_status(decoder))
+}
+
+/// Returns the statistics of an SSDV FEC decoder in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_stats`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_stats` apply, except that
+/// the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_stats(
+    decoder: *const ssdv_fec_decoder_t,
+    stats: *mut ssdv_fec_stats_t,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_decoder_stats(decoder, stats))
+}
+
+/// Returns the IDs of the systematic packets missing from an SSDV FEC decoder
+/// in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_missing_ids`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_missing_ids` apply, except
+/// that the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_missing_ids(
+    decoder: *const ssdv_fec_decoder_t,
+    ids: *mut u16,
+    max_ids: c_int,
+    num_needed: *mut c_int,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_decoder_missing_ids(decoder, ids, max_ids, num_needed))
+}
+
+/// Decodes the SSDV image from the packets pushed into an SSDV FEC decoder in
+/// a critical section.
+///
+/// This works as [`ssdv_fec_decoder_decode_pushed`]. Interrupts are delayed
+/// while the image is decoded.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_decode_pushed` apply, except
+/// that the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_decode_pushed(
+    decoder: *mut ssdv_fec_decoder_t,
+    output: *mut c_char,
+    num_output_packets: c_int,
+) -> c_int {
+    critical_section::with(|_| ssdv_fec_decoder_decode_pushed(decoder, output, num_output_packets))
+}
+
+/// Partially decodes the SSDV image from the packets pushed into an SSDV FEC
+/// decoder in a critical section.
+///
+/// This works as [`ssdv_fec_decoder_decode_partial`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_decode_partial` apply,
+/// except that the decoder context can be used concurrently by other
+/// `ssdv_fec_cs_*` functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_decode_partial(
+    decoder: *const ssdv_fec_decoder_t,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    valid: *mut u8,
+) -> c_int {
+    critical_section::with(|_| {
+        ssdv_fec_decoder_decode_partial(decoder, output, num_output_packets, valid)
+    })
+}
+
+/// Removes all the packets pushed into an SSDV FEC decoder in a critical
+/// section.
+///
+/// This works as [`ssdv_fec_decoder_reset`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_reset` apply, except that
+/// the decoder context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_decoder_reset(decoder: *mut ssdv_fec_decoder_t) -> c_int {
+    critical_section::with(|_| ssdv_fec_decoder_reset(decoder))
+}
+
+/// Returns the next packet ID to transmit from an SSDV FEC transmission
+/// scheduler in a critical section.
+///
+/// This works as [`ssdv_fec_scheduler_next`].
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_scheduler_next` apply, except that
+/// the scheduler context can be used concurrently by other `ssdv_fec_cs_*`
+/// functions.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_cs_scheduler_next(scheduler: *mut ssdv_fec_scheduler_t) -> u16 {
+    critical_section::with(|_| ssdv_fec_scheduler_next(scheduler))
+}
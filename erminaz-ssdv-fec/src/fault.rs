@@ -0,0 +1,58 @@
+//! Fault handler.
+//!
+//! The library is designed so that none of its functions panic when they are
+//! called according to their safety requirements. The arguments that would
+//! make the `ssdv_fec` crate panic, such as out of range packet IDs, are
+//! checked and rejected with an error code by the C API. If a panic happens
+//! nevertheless because of a bug, the panic handler notifies the fault
+//! management system of the flight software through a registered fault handler
+//! and then halts, instead of halting silently.
+
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// Fault handler.
+#[allow(non_camel_case_types)]
+pub type ssdv_fec_fault_handler_t = Option<unsafe extern "C" fn()>;
+
+// Handler registered with `ssdv_fec_set_fault_handler`, stored as a data
+// pointer because there are no atomic function pointers. NULL if there is no
+// handler.
+static HANDLER: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers a fault handler.
+///
+/// The library calls `handler` if an internal error happens, which can only be
+/// caused by a bug in the library or by calling its functions in a way that
+/// breaks their safety requirements. The handler should notify the fault
+/// management system of the flight software, for instance by logging the fault
+/// and resetting the MCU. If the handler returns, or if there is no handler,
+/// the library halts in an infinite loop, so the MCU is eventually reset by the
+/// watchdog. Passing `NULL` removes the handler.
+///
+/// The handler is called from the thread or interrupt handler that calls the
+/// library function where the error happens, so it must be safe to call from
+/// all of them. It must not call functions of this library.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_set_fault_handler(handler: ssdv_fec_fault_handler_t) {
+    let handler = handler.map_or(ptr::null_mut(), |f| f as *mut c_void);
+    HANDLER.store(handler, Ordering::Release);
+}
+
+// The host tests link with std, which provides its own panic handler.
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    let handler = HANDLER.load(Ordering::Acquire);
+    if !handler.is_null() {
+        // SAFETY: the pointer was obtained from a handler in
+        // ssdv_fec_set_fault_handler.
+        unsafe { core::mem::transmute::<*mut c_void, unsafe extern "C" fn()>(handler)() };
+    }
+    loop {
+        core::sync::atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,252 @@
+//! Per-format C API.
+//!
+//! The `ssdv_fec_lj2_*` and `ssdv_fec_nofec_*` functions are versions of the
+//! functions that take an `ssdv_fec_format_t` parameter with the format fixed
+//! by their name, so that software handling a single format, such as the
+//! flight software and the parts of the ground software that handle test
+//! transmissions in the standard format, does not need to pass the format
+//! around. The functions that take an encoder or decoder context do not have
+//! per-format versions, since the context records the format it was prepared
+//! with. Both symbol families are always included in the library.
+
+use super::*;
+
+/// Prepares an SSDV FEC encoder for the Longjiang-2 format.
+///
+/// This works as [`ssdv_fec_encoder_init`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_init` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_encoder_init(
+    encoder: *mut ssdv_fec_encoder_t,
+    ssdv_packets: *mut c_char,
+    num_ssdv_packets: c_int,
+) -> c_int {
+    ssdv_fec_encoder_init(
+        encoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2,
+        ssdv_packets,
+        num_ssdv_packets,
+    )
+}
+
+/// Prepares an SSDV FEC decoder for the Longjiang-2 format.
+///
+/// This works as [`ssdv_fec_decoder_init`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_init` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_decoder_init(
+    decoder: *mut ssdv_fec_decoder_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    ssdv_fec_decoder_init(
+        decoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2,
+        buffer,
+        num_buffer_packets,
+        lenient,
+    )
+}
+
+/// Prepares an SSDV FEC decoder with an output buffer for the Longjiang-2
+/// format.
+///
+/// This works as [`ssdv_fec_decoder_setup`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_setup` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_decoder_setup(
+    decoder: *mut ssdv_fec_decoder_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    ssdv_fec_decoder_setup(
+        decoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2,
+        buffer,
+        num_buffer_packets,
+        output,
+        num_output_packets,
+        lenient,
+    )
+}
+
+/// Checks the CRC-32 of an SSDV packet in the Longjiang-2 format.
+///
+/// This works as [`ssdv_fec_packet_crc_ok`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the Longjiang-2
+/// format.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_packet_crc_ok(packet: *const c_char) -> bool {
+    ssdv_fec_packet_crc_ok(ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2, packet)
+}
+
+/// Validates a single SSDV packet in the Longjiang-2 format.
+///
+/// This works as [`ssdv_fec_packet_validate`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the Longjiang-2
+/// format.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_packet_validate(packet: *const c_char) -> c_int {
+    ssdv_fec_packet_validate(ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2, packet)
+}
+
+/// Parses the header of an SSDV packet in the Longjiang-2 format.
+///
+/// This works as [`ssdv_fec_packet_info`] with the `SSDV_FEC_FORMAT_LJ2`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the Longjiang-2
+/// format, and the `info` parameter must point to valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_lj2_packet_info(
+    packet: *const c_char,
+    info: *mut ssdv_fec_packet_info_t,
+) -> c_int {
+    ssdv_fec_packet_info(ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2, packet, info)
+}
+
+/// Prepares an SSDV FEC encoder for the standard no-FEC format.
+///
+/// This works as [`ssdv_fec_encoder_init`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_encoder_init` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_encoder_init(
+    encoder: *mut ssdv_fec_encoder_t,
+    ssdv_packets: *mut c_char,
+    num_ssdv_packets: c_int,
+) -> c_int {
+    ssdv_fec_encoder_init(
+        encoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC,
+        ssdv_packets,
+        num_ssdv_packets,
+    )
+}
+
+/// Prepares an SSDV FEC decoder for the standard no-FEC format.
+///
+/// This works as [`ssdv_fec_decoder_init`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_init` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_decoder_init(
+    decoder: *mut ssdv_fec_decoder_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    ssdv_fec_decoder_init(
+        decoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC,
+        buffer,
+        num_buffer_packets,
+        lenient,
+    )
+}
+
+/// Prepares an SSDV FEC decoder with an output buffer for the standard no-FEC
+/// format.
+///
+/// This works as [`ssdv_fec_decoder_setup`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The safety considerations of `ssdv_fec_decoder_setup` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_decoder_setup(
+    decoder: *mut ssdv_fec_decoder_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    ssdv_fec_decoder_setup(
+        decoder,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC,
+        buffer,
+        num_buffer_packets,
+        output,
+        num_output_packets,
+        lenient,
+    )
+}
+
+/// Checks the CRC-32 of an SSDV packet in the standard no-FEC format.
+///
+/// This works as [`ssdv_fec_packet_crc_ok`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the standard no-FEC
+/// format.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_packet_crc_ok(packet: *const c_char) -> bool {
+    ssdv_fec_packet_crc_ok(ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC, packet)
+}
+
+/// Validates a single SSDV packet in the standard no-FEC format.
+///
+/// This works as [`ssdv_fec_packet_validate`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the standard no-FEC
+/// format.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_packet_validate(packet: *const c_char) -> c_int {
+    ssdv_fec_packet_validate(ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC, packet)
+}
+
+/// Parses the header of an SSDV packet in the standard no-FEC format.
+///
+/// This works as [`ssdv_fec_packet_info`] with the `SSDV_FEC_FORMAT_NO_FEC`
+/// format.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to an SSDV packet in the standard no-FEC
+/// format, and the `info` parameter must point to valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_nofec_packet_info(
+    packet: *const c_char,
+    info: *mut ssdv_fec_packet_info_t,
+) -> c_int {
+    ssdv_fec_packet_info(ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC, packet, info)
+}
@@ -5,89 +5,1734 @@
 //! a static library for an ARM Cortex-M4 using the `thumbv7em-none-eabi`
 //! target, and a C header is generated using `cbindgen`.
 
-#![no_std]
-
-extern crate panic_halt;
+#![cfg_attr(not(test), no_std)]
 
 use core::{
-    ffi::{c_char, c_int},
-    mem::MaybeUninit,
-    slice,
+    ffi::{c_char, c_int, c_void, CStr},
+    mem::{align_of, size_of},
+    ptr, slice,
+};
+use log::{log, SSDV_FEC_LOG_DEBUG, SSDV_FEC_LOG_ERROR, SSDV_FEC_LOG_INFO, SSDV_FEC_LOG_WARNING};
+use ssdv_fec::{
+    crc32, DecodeStats, Decoder, DecoderError, DecoderOptions, Encoder, EncoderError,
+    FountainScheduler, Packet, PacketChunks, PacketError, PacketWords, PushStatus, SSDVNoFecPacket,
+    SSDVPacket, StreamingDecoder, SSDV_DATA_LEN, SSDV_NO_FEC_DATA_LEN, SSDV_NO_FEC_PACKET_LEN,
+    SSDV_PACKET_LEN,
 };
-use ssdv_fec::{Decoder, DecoderError, Encoder, EncoderError, SSDVPacket};
 
-static mut SSDV_FEC_ENCODER: MaybeUninit<Encoder> = MaybeUninit::uninit();
+/// SSDV packet format.
+///
+/// The packet format is selected when an encoder or decoder context is
+/// initialized. All the packets given to and obtained from the context use
+/// this format.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ssdv_fec_format_t {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields
+    SSDV_FEC_FORMAT_LJ2 = 0,
+    /// Standard 256-byte no-FEC SSDV format
+    SSDV_FEC_FORMAT_NO_FEC = 1,
+}
+
+// Evaluates an expression that uses the packet type `$packet` corresponding to
+// a format.
+macro_rules! with_format {
+    ($format:expr, $packet:ident => $body:expr) => {
+        match $format {
+            ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2 => {
+                type $packet = SSDVPacket;
+                $body
+            }
+            ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC => {
+                type $packet = SSDVNoFecPacket;
+                $body
+            }
+        }
+    };
+}
+
+#[cfg(feature = "critical-section")]
+mod cs;
+mod fault;
+mod formats;
+mod log;
+mod progress;
+mod selftest;
+// The hardened API is declared after `with_format!` so that it can use it.
+mod v2;
+
+#[cfg(test)]
+mod panic_free;
+#[cfg(test)]
+mod qemu;
+#[cfg(test)]
+mod stack_usage;
+#[cfg(test)]
+mod test;
+
+/// SSDV FEC encoder context.
+///
+/// The storage for the context is provided by the caller, for instance as a
+/// static variable or in the stack of the task that uses it, and it is
+/// initialized with [`ssdv_fec_encoder_init`]. The contents of the context are
+/// private. The context does not own any resources, so it does not need to be
+/// freed.
+///
+/// Different contexts can be used concurrently from different threads or
+/// tasks.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_encoder_t {
+    // Storage for an `Encoder`, which contains a slice. It is an array of
+    // pointers to get the same size and alignment.
+    storage: [*mut c_void; 2],
+    format: ssdv_fec_format_t,
+    // Set by `ssdv_fec_encoder_init` and cleared by `ssdv_fec_encoder_release`.
+    initialized: bool,
+}
+
+const _: () = assert!(
+    size_of::<Encoder<SSDVPacket>>() <= size_of::<[*mut c_void; 2]>()
+        && align_of::<Encoder<SSDVPacket>>() <= align_of::<[*mut c_void; 2]>()
+        && size_of::<Encoder<SSDVNoFecPacket>>() <= size_of::<[*mut c_void; 2]>()
+        && align_of::<Encoder<SSDVNoFecPacket>>() <= align_of::<[*mut c_void; 2]>()
+);
+
+impl ssdv_fec_encoder_t {
+    // The encoder must have been initialized with `ssdv_fec_encoder_init`
+    // using the format corresponding to `P`.
+    unsafe fn encoder<P: Packet>(&self) -> &Encoder<'static, P> {
+        &*self.storage.as_ptr().cast::<Encoder<P>>()
+    }
+}
+
+/// SSDV FEC packet chunks context.
+///
+/// The storage for the context is provided by the caller, and it is
+/// initialized with [`ssdv_fec_encoder_encode_chunks`] to generate a packet in
+/// chunks. The contents of the context are private. The context does not own
+/// any resources, so it does not need to be freed.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_chunks_t {
+    // Storage for a `PacketChunks`, which contains a reference to the encoder.
+    storage: [*mut c_void; 6],
+    format: ssdv_fec_format_t,
+}
+
+const _: () = assert!(
+    size_of::<PacketChunks<SSDVPacket>>() <= size_of::<[*mut c_void; 6]>()
+        && align_of::<PacketChunks<SSDVPacket>>() <= align_of::<[*mut c_void; 6]>()
+        && size_of::<PacketChunks<SSDVNoFecPacket>>() <= size_of::<[*mut c_void; 6]>()
+        && align_of::<PacketChunks<SSDVNoFecPacket>>() <= align_of::<[*mut c_void; 6]>()
+);
+
+impl ssdv_fec_chunks_t {
+    // The context must have been initialized with
+    // `ssdv_fec_encoder_encode_chunks` using the format corresponding to `P`.
+    unsafe fn chunks<P: Packet>(&self) -> &PacketChunks<'static, P> {
+        &*self.storage.as_ptr().cast::<PacketChunks<P>>()
+    }
+
+    // The context must have been initialized with
+    // `ssdv_fec_encoder_encode_chunks` using the format corresponding to `P`.
+    unsafe fn chunks_mut<P: Packet>(&mut self) -> &mut PacketChunks<'static, P> {
+        &mut *self.storage.as_mut_ptr().cast::<PacketChunks<P>>()
+    }
+}
+
+/// SSDV FEC packet words context.
+///
+/// The storage for the context is provided by the caller, and it is
+/// initialized with [`ssdv_fec_encoder_encode_words`] to generate a packet word
+/// by word. The contents of the context are private. The context does not own
+/// any resources, so it does not need to be freed.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_words_t {
+    // Storage for a `PacketWords`, which contains a reference to the encoder.
+    storage: [*mut c_void; 8],
+    format: ssdv_fec_format_t,
+}
+
+const _: () = assert!(
+    size_of::<PacketWords<SSDVPacket>>() <= size_of::<[*mut c_void; 8]>()
+        && align_of::<PacketWords<SSDVPacket>>() <= align_of::<[*mut c_void; 8]>()
+        && size_of::<PacketWords<SSDVNoFecPacket>>() <= size_of::<[*mut c_void; 8]>()
+        && align_of::<PacketWords<SSDVNoFecPacket>>() <= align_of::<[*mut c_void; 8]>()
+);
+
+impl ssdv_fec_words_t {
+    // The context must have been initialized with
+    // `ssdv_fec_encoder_encode_words` using the format corresponding to `P`.
+    unsafe fn words<P: Packet>(&self) -> &PacketWords<'static, P> {
+        &*self.storage.as_ptr().cast::<PacketWords<P>>()
+    }
+
+    // The context must have been initialized with
+    // `ssdv_fec_encoder_encode_words` using the format corresponding to `P`.
+    unsafe fn words_mut<P: Packet>(&mut self) -> &mut PacketWords<'static, P> {
+        &mut *self.storage.as_mut_ptr().cast::<PacketWords<P>>()
+    }
+}
+
+/// SSDV FEC decoder context.
+///
+/// The storage for the context is provided by the caller, and it is
+/// initialized with [`ssdv_fec_decoder_init`]. The contents of the context are
+/// private. The context does not own any resources, so it does not need to be
+/// freed.
+///
+/// Different contexts can be used concurrently from different threads or
+/// tasks.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_decoder_t {
+    // Storage for a `StreamingDecoder`.
+    storage: [*mut c_void; 8],
+    format: ssdv_fec_format_t,
+    // Output buffer given to `ssdv_fec_decoder_setup`, or NULL.
+    output: *mut c_char,
+    num_output_packets: c_int,
+    // Scratch memory given to `ssdv_fec_decoder_set_scratch`, or NULL.
+    scratch: *mut c_void,
+    scratch_len: usize,
+    // Returned by `ssdv_fec_decoder_stats`.
+    stats: ssdv_fec_stats_t,
+}
+
+const _: () = assert!(
+    size_of::<StreamingDecoder<SSDVPacket>>() <= size_of::<[*mut c_void; 8]>()
+        && align_of::<StreamingDecoder<SSDVPacket>>() <= align_of::<[*mut c_void; 8]>()
+        && size_of::<StreamingDecoder<SSDVNoFecPacket>>() <= size_of::<[*mut c_void; 8]>()
+        && align_of::<StreamingDecoder<SSDVNoFecPacket>>() <= align_of::<[*mut c_void; 8]>()
+);
+
+impl ssdv_fec_decoder_t {
+    // The decoder must have been initialized with `ssdv_fec_decoder_init`
+    // using the format corresponding to `P`.
+    unsafe fn decoder<P: Packet>(&self) -> &StreamingDecoder<'static, P> {
+        &*self.storage.as_ptr().cast::<StreamingDecoder<P>>()
+    }
+
+    // The decoder must have been initialized with `ssdv_fec_decoder_init`
+    // using the format corresponding to `P`.
+    unsafe fn decoder_mut<P: Packet>(&mut self) -> &mut StreamingDecoder<'static, P> {
+        &mut *self.storage.as_mut_ptr().cast::<StreamingDecoder<P>>()
+    }
+
+    // Returns the scratch memory as packets, if it has been set.
+    unsafe fn scratch<P: Packet + 'static>(&self) -> Option<&'static mut [P]> {
+        if self.scratch.is_null() {
+            None
+        } else {
+            Some(slice::from_raw_parts_mut(
+                self.scratch.cast::<P>(),
+                self.scratch_len / P::LEN,
+            ))
+        }
+    }
+
+    // Decodes the packets stored in the decoder. If there is scratch memory,
+    // decoding is done on a copy of the stored packets, which are only
+    // consumed if decoding succeeds.
+    unsafe fn decode_stored<P: Packet + 'static>(
+        &mut self,
+        output: &mut [P],
+    ) -> Result<usize, c_int> {
+        let scratch = self.scratch::<P>();
+        let decoder = self.decoder_mut::<P>();
+        let result = match scratch.filter(|_| decoder.is_ready()) {
+            Some(scratch) => {
+                let packets = decoder.packets();
+                let Some(copy) = scratch.get_mut(..packets.len()) else {
+                    return Err(scratch_too_short());
+                };
+                copy.copy_from_slice(packets);
+                let result =
+                    decode_result(Decoder::decode_with_stats(copy, output, decoder.options()));
+                if result.is_ok() {
+                    decoder.reset();
+                }
+                result
+            }
+            None => decode_result(decoder.decode_with_stats(output)),
+        };
+        result.map(|(len, stats)| {
+            self.stats.fec_packets_used = stats.fec_packets_used as u32;
+            self.stats.packets_recovered = stats.packets_recovered as u32;
+            len
+        })
+    }
+
+    // Decodes the packets in `input`. If there is scratch memory, decoding is
+    // done on a copy of `input`, which is not modified.
+    unsafe fn decode_input<P: Packet + 'static>(
+        &self,
+        input: &mut [P],
+        output: &mut [P],
+    ) -> Result<usize, c_int> {
+        let input = match self.scratch::<P>() {
+            Some(scratch) => {
+                let Some(copy) = scratch.get_mut(..input.len()) else {
+                    return Err(scratch_too_short());
+                };
+                copy.copy_from_slice(input);
+                copy
+            }
+            None => input,
+        };
+        decode_result(Decoder::decode_with_stats(
+            input,
+            output,
+            self.decoder::<P>().options(),
+        ))
+        .map(|(len, _)| len)
+    }
+}
+
+/// SSDV FEC transmission scheduler context.
+///
+/// The storage for the context is provided by the caller, and it is
+/// initialized with [`ssdv_fec_scheduler_init`]. The contents of the context
+/// are private. The context does not own any resources, so it does not need to
+/// be freed.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_scheduler_t {
+    // Storage for a `FountainScheduler`.
+    storage: [u32; 3],
+}
+
+const _: () = assert!(
+    size_of::<FountainScheduler>() <= size_of::<[u32; 3]>()
+        && align_of::<FountainScheduler>() <= align_of::<[u32; 3]>()
+);
+
+impl ssdv_fec_scheduler_t {
+    // The scheduler must have been initialized with `ssdv_fec_scheduler_init`.
+    unsafe fn scheduler(&self) -> &FountainScheduler {
+        &*self.storage.as_ptr().cast::<FountainScheduler>()
+    }
+
+    // The scheduler must have been initialized with `ssdv_fec_scheduler_init`.
+    unsafe fn scheduler_mut(&mut self) -> &mut FountainScheduler {
+        &mut *self.storage.as_mut_ptr().cast::<FountainScheduler>()
+    }
+}
+
+/// Status of an SSDV FEC encoder.
+///
+/// This is returned by [`ssdv_fec_encoder_status`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_encoder_status_t {
+    /// Buffer given to [`ssdv_fec_encoder_init`], or `NULL` if the encoder is
+    /// not initialized.
+    pub buffer: *const c_char,
+    /// Number of systematic packets of the image, or -1 if the encoder is not
+    /// initialized.
+    pub num_systematic: c_int,
+    /// Image ID of the image, or -1 if the encoder is not initialized.
+    pub image_id: c_int,
+    /// Whether the encoder is initialized and can be used to encode packets.
+    pub ready: bool,
+}
+
+/// Status of an SSDV FEC decoder.
+///
+/// This is returned by [`ssdv_fec_decoder_status`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_decoder_status_t {
+    /// Number of distinct valid packets stored in the decoder.
+    pub num_packets: c_int,
+    /// Number of systematic packets of the image, or -1 if it is not known
+    /// yet.
+    pub num_systematic: c_int,
+    /// Image ID of the stored packets, or -1 if no packets have been stored.
+    pub image_id: c_int,
+    /// Whether enough packets have been stored to decode the image.
+    pub ready: bool,
+}
+
+/// Statistics of an SSDV FEC decoder.
+///
+/// This is filled by [`ssdv_fec_decoder_stats`]. The counters of dropped
+/// packets count since the decoder was initialized, and wrap around on
+/// overflow. The other fields describe the last image decoded with
+/// [`ssdv_fec_decoder_decode_pushed`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Default, Copy, Clone)]
+pub struct ssdv_fec_stats_t {
+    /// Number of pushed packets dropped because of a wrong CRC.
+    pub wrong_crc: u32,
+    /// Number of pushed packets dropped because they were duplicates.
+    pub duplicates: u32,
+    /// Number of FEC packets used to decode the last image.
+    pub fec_packets_used: u32,
+    /// Number of systematic packets missing from the last image that were
+    /// recovered with the FEC packets.
+    pub packets_recovered: u32,
+}
+
+/// Header fields of an SSDV packet.
+///
+/// This is filled by [`ssdv_fec_packet_info`].
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct ssdv_fec_packet_info_t {
+    /// Image ID.
+    pub image_id: u8,
+    /// Packet ID.
+    pub packet_id: u16,
+    /// Flags field, including the EOI and FEC packet flags.
+    pub flags: u8,
+    /// Whether the packet is a FEC packet.
+    pub fec_packet: bool,
+    /// Whether the packet has the EOI flag set.
+    pub eoi: bool,
+    /// Width field (in units of 16 pixels), or -1 for FEC packets.
+    pub width: c_int,
+    /// Height field (in units of 16 pixels), or -1 for FEC packets.
+    pub height: c_int,
+    /// Number of systematic packets, or -1 for systematic packets.
+    pub num_systematic: c_int,
+    /// Whether the CRC-32 of the packet is correct.
+    pub crc_ok: bool,
+}
+
+/// Prepares an SSDV FEC encoder.
+///
+/// The `encoder` parameter should point to the storage for the encoder
+/// context. The `format` parameter selects the format of the SSDV packets. The
+/// `ssdv_packets` parameter should point to an array that contains the
+/// concatenation of the SSDV systematic packets corresponding to a single
+/// image. The number of packets in this array is indicated in the
+/// `num_ssdv_packets` parameter.
+///
+/// The function returns zero on success, or a negative error code if there is
+/// an error. Images with more than `SSDV_FEC_MAX_K` systematic packets are
+/// rejected with `SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT`.
+/// `SSDV_FEC_ERR_NULL_POINTER` is returned if `encoder` is `NULL` or
+/// `ssdv_packets` is `NULL` and `num_ssdv_packets` is not zero, and
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `num_ssdv_packets` is negative.
+/// `SSDV_FEC_ERR_BAD_BUFFER` is returned if `encoder` is misaligned or
+/// `ssdv_packets` overlaps it. The encoder context is left uninitialized if
+/// there is an error, unless it is `NULL` or misaligned.
+///
+/// This function modifies the contents of the `ssdv_packets` array.
+///
+/// # Safety
+///
+/// The `encoder` parameter must be `NULL` or point to valid storage for an
+/// encoder context. The `format` parameter must be one of the values of
+/// `ssdv_fec_format_t`. The buffer pointed to by `ssdv_packets` must have
+/// allocated storage for at least `num_ssdv_packets` SSDV packets and must
+/// outlive all the usage of the encoder context until it is initialized again
+/// with a new buffer. The encoder context must not be used concurrently by
+/// other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_init(
+    encoder: *mut ssdv_fec_encoder_t,
+    format: ssdv_fec_format_t,
+    ssdv_packets: *mut c_char,
+    num_ssdv_packets: c_int,
+) -> c_int {
+    if encoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_ssdv_packets = match packet_count(ssdv_packets, num_ssdv_packets) {
+        Ok(count) => count,
+        Err(err) => {
+            (*encoder).initialized = false;
+            return err;
+        }
+    };
+    with_format!(format, P => {
+        if overlaps_context(encoder, ssdv_packets, num_ssdv_packets.saturating_mul(P::LEN)) {
+            (*encoder).initialized = false;
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let ssdv_packets = packets_mut::<P, _>(ssdv_packets, num_ssdv_packets);
+        let result = if ssdv_packets.len() > MAX_K {
+            Err(EncoderError::TooLongInput)
+        } else {
+            Encoder::new(ssdv_packets)
+        };
+        let new_encoder = match result {
+            Ok(encoder) => encoder,
+            Err(err) => {
+                (*encoder).initialized = false;
+                let err = encoder_error(err);
+                log(
+                    SSDV_FEC_LOG_ERROR,
+                    format_args!("encoder initialization failed: {}", describe(err)),
+                );
+                return err;
+            }
+        };
+        log(
+            SSDV_FEC_LOG_INFO,
+            format_args!(
+                "encoder initialized: image {}, {} systematic packets",
+                new_encoder.image_id(),
+                new_encoder.num_systematic()
+            ),
+        );
+        (*encoder)
+            .storage
+            .as_mut_ptr()
+            .cast::<Encoder<P>>()
+            .write(new_encoder);
+    });
+    (*encoder).format = format;
+    (*encoder).initialized = true;
+    0
+}
+
+/// Releases an SSDV FEC encoder.
+///
+/// This function marks the encoder context as uninitialized, so that the
+/// buffer given to [`ssdv_fec_encoder_init`] is no longer used by the library
+/// and can be reclaimed by the caller. The encoder modifies the contents of
+/// this buffer. If `restore` is true, the original contents of the buffer,
+/// which are the systematic packets of the image, are restored. This takes
+/// about as long as `ssdv_fec_encoder_init`.
+///
+/// The function returns zero on success, or
+/// `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` if the encoder context was not
+/// initialized or has already been released, or `SSDV_FEC_ERR_BAD_BUFFER` if
+/// `encoder` is misaligned. After the encoder is released, it must be
+/// initialized again before it is used with any other function.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init`. The encoder context must not be used
+/// concurrently by other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_release(
+    encoder: *mut ssdv_fec_encoder_t,
+    restore: bool,
+) -> c_int {
+    if encoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    (*encoder).initialized = false;
+    with_format!((*encoder).format, P => {
+        let released = (*encoder).storage.as_ptr().cast::<Encoder<P>>().read();
+        released.release(restore);
+    });
+    0
+}
+
+/// Generates a FEC encoded packet.
+///
+/// This function generates a systematic or FEC SSDV packet using an encoder
+/// context previously prepared by a call to [`ssdv_fec_encoder_init`]. The
+/// `packet_id` parameter corresponds to the SSDV packet ID. The `output`
+/// parameter should point to an array of size at least the size of an SSDV
+/// packet. The encoded packet is written to this array.
+///
+/// The function returns zero on success,
+/// `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` if the encoder is not initialized,
+/// or `SSDV_FEC_ERR_BAD_BUFFER` if `encoder` is misaligned or `output`
+/// overlaps it. If there is an error, `output` is not modified.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. The
+/// context can be used concurrently by several threads to encode packets. The
+/// `packet_id` parameter must be non-negative and smaller than `2**16 - 1`.
+/// The `output` buffer must have allocated storage for at least one SSDV
+/// packet. All the safety considerations of `ssdv_fec_encoder_init` also
+/// apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_packet(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: c_int,
+    output: *mut c_char,
+) -> c_int {
+    if encoder.is_null() || output.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    with_format!((*encoder).format, P => {
+        if overlaps_context(encoder, output, P::LEN) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let output = &mut *output.cast::<P>();
+        (*encoder).encoder::<P>().encode(packet_id as u16, output);
+    });
+    0
+}
+
+/// Generates a range of FEC encoded packets.
+///
+/// This function generates `count` systematic or FEC SSDV packets with
+/// consecutive packet IDs starting at `first_id`, using an encoder context
+/// previously prepared by a call to [`ssdv_fec_encoder_init`]. The packets are
+/// written one after another to the `output` array. This is equivalent to
+/// calling [`ssdv_fec_encoder_encode_packet`] for each packet.
+///
+/// The function returns the number of packets generated,
+/// `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` if the encoder is not initialized,
+/// `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if `count` is negative or the last
+/// packet ID would not be smaller than `2**16 - 1`, or
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `encoder` is misaligned or `output` overlaps
+/// it.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. The
+/// context can be used concurrently by several threads to encode packets. The
+/// `output` buffer must have allocated storage for at least `count` SSDV
+/// packets. All the safety considerations of `ssdv_fec_encoder_init` also
+/// apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_range(
+    encoder: *const ssdv_fec_encoder_t,
+    first_id: u16,
+    count: c_int,
+    output: *mut c_char,
+) -> c_int {
+    if encoder.is_null() || (output.is_null() && count > 0) {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    // Encoder::try_encode_range accepts packet ID 0xffff, but the C API does
+    // not.
+    if count < 0 || i64::from(first_id) + i64::from(count) > i64::from(u16::MAX) {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    with_format!((*encoder).format, P => {
+        if overlaps_context(encoder, output, packets_len::<P>(count)) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let output = slice::from_raw_parts_mut(output.cast::<P>(), count as usize);
+        if let Err(err) = (*encoder).encoder::<P>().try_encode_range(first_id, output) {
+            return encoder_error(err);
+        }
+    });
+    count
+}
+
+/// Generates a range of FEC encoded packets into strided frames.
+///
+/// This function works as [`ssdv_fec_encoder_encode_range`], but the packets
+/// are written to `output` at intervals of `stride` bytes instead of one after
+/// another. This allows writing the packets directly in place into a buffer of
+/// radio frames, where each packet is stored inside a larger frame. The bytes
+/// between packets are not modified.
+///
+/// The function returns the number of packets generated,
+/// `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` if the encoder is not initialized,
+/// `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if `count` is negative or the last
+/// packet ID would not be smaller than `2**16 - 1`,
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `stride` is smaller than the packet size,
+/// or `SSDV_FEC_ERR_BAD_BUFFER` if `encoder` is misaligned or one of the
+/// packets would overlap it.
+///
+/// # Safety
+///
+/// The `output` buffer must have allocated storage for at least
+/// `(count - 1) * stride` bytes plus the size of an SSDV packet. All the safety
+/// considerations of `ssdv_fec_encoder_encode_range` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_range_strided(
+    encoder: *const ssdv_fec_encoder_t,
+    first_id: u16,
+    count: c_int,
+    output: *mut c_char,
+    stride: c_int,
+) -> c_int {
+    if encoder.is_null() || (output.is_null() && count > 0) {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    if count < 0 || i64::from(first_id) + i64::from(count) > i64::from(u16::MAX) {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    with_format!((*encoder).format, P => {
+        if stride < P::LEN as c_int {
+            return SSDV_FEC_ERR_INVALID_LENGTH;
+        }
+        let len = if count == 0 {
+            0
+        } else {
+            (count as usize - 1)
+                .saturating_mul(stride as usize)
+                .saturating_add(P::LEN)
+        };
+        if overlaps_context(encoder, output, len) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let encoder = (*encoder).encoder::<P>();
+        for j in 0..count as usize {
+            let packet = &mut *output.add(j * stride as usize).cast::<P>();
+            encoder.encode(first_id + j as u16, packet);
+        }
+    });
+    count
+}
+
+/// Generates a FEC encoded packet prefixed by its length.
+///
+/// This function works as [`ssdv_fec_encoder_encode_packet`], but the packet is
+/// preceded in `output` by its length in bytes, written as a 16-bit big-endian
+/// word of `SSDV_FEC_LENGTH_PREFIX_LEN` bytes. This allows link-layer code that
+/// frames variable-size payloads to send the output as is, without knowing the
+/// packet size of each format. The capacity of `output` in bytes is given by
+/// `output_len`. A buffer of `SSDV_FEC_LENGTH_PREFIX_LEN +
+/// SSDV_FEC_MAX_PACKET_LEN` bytes is large enough for any format.
+///
+/// The function returns the number of bytes written, which is
+/// `SSDV_FEC_LENGTH_PREFIX_LEN` plus the packet size, or a negative error code.
+/// `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` is returned if the encoder is not
+/// initialized, `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if `packet_id` is
+/// `0xffff`, `SSDV_FEC_ERR_OUTPUT_TOO_SHORT` if the length and the packet do
+/// not fit in `output`, and `SSDV_FEC_ERR_BAD_BUFFER` if `encoder` is
+/// misaligned or `output` overlaps it.
+///
+/// # Safety
+///
+/// The `output` buffer must have allocated storage for at least `output_len`
+/// bytes. All the safety considerations of `ssdv_fec_encoder_encode_packet`
+/// also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_prefixed(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: u16,
+    output: *mut c_char,
+    output_len: c_int,
+) -> c_int {
+    if encoder.is_null() || output.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    if packet_id == u16::MAX {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    with_format!((*encoder).format, P => {
+        let len = SSDV_FEC_LENGTH_PREFIX_LEN + P::LEN;
+        if output_len < len as c_int {
+            return SSDV_FEC_ERR_OUTPUT_TOO_SHORT;
+        }
+        if overlaps_context(encoder, output, len) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let output = slice::from_raw_parts_mut(output.cast::<u8>(), len);
+        let (prefix, packet) = output.split_at_mut(SSDV_FEC_LENGTH_PREFIX_LEN);
+        prefix.copy_from_slice(&(P::LEN as u16).to_be_bytes());
+        (*encoder)
+            .encoder::<P>()
+            .encode(packet_id, &mut *packet.as_mut_ptr().cast::<P>());
+        len as c_int
+    })
+}
+
+/// Starts generating a FEC encoded packet in chunks.
+///
+/// This function prepares the `chunks` context to generate the packet with
+/// `packet_id` using an encoder context previously prepared by a call to
+/// [`ssdv_fec_encoder_init`]. The bytes of the packet are then obtained in
+/// chunks of any size with [`ssdv_fec_chunks_next`]. This allows writing the
+/// packet directly into a radio FIFO or a chain of DMA descriptors, without
+/// holding a buffer for the whole packet in each slot of the transmit queue.
+/// The packet is the same as the one generated by
+/// [`ssdv_fec_encoder_encode_packet`].
+///
+/// The function returns zero on success, `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED`
+/// if the encoder is not initialized, `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if
+/// `packet_id` is `0xffff`, or `SSDV_FEC_ERR_BAD_BUFFER` if one of the contexts
+/// is misaligned or they overlap.
+///
+/// # Safety
+///
+/// The `chunks` parameter must point to valid storage for a chunks context. The
+/// chunks context refers to the encoder context, so the encoder context must
+/// not be moved, released or initialized again while the chunks context is
+/// used. The encoder context can be used concurrently by several threads to
+/// encode packets. All the safety considerations of `ssdv_fec_encoder_init`
+/// also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_chunks(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: u16,
+    chunks: *mut ssdv_fec_chunks_t,
+) -> c_int {
+    if encoder.is_null() || chunks.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned()
+        || !chunks.is_aligned()
+        || overlaps_context(encoder, chunks, size_of::<ssdv_fec_chunks_t>())
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    if packet_id == u16::MAX {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    let format = (*encoder).format;
+    with_format!(format, P => {
+        (*chunks)
+            .storage
+            .as_mut_ptr()
+            .cast::<PacketChunks<P>>()
+            .write((*encoder).encoder::<P>().encode_chunks(packet_id));
+    });
+    (*chunks).format = format;
+    0
+}
+
+/// Writes the next chunk of a packet generated in chunks.
+///
+/// This function writes the next bytes of the packet being generated by a
+/// chunks context prepared with [`ssdv_fec_encoder_encode_chunks`] to the
+/// `chunk` array, whose length is given by `len`. The chunks can have any
+/// length, but chunks that are a multiple of 64 bytes are the most efficient
+/// for FEC packets.
+///
+/// The function returns the number of bytes written, which is `len` unless the
+/// end of the packet is reached, zero once the whole packet has been written,
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `len` is negative, or
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `chunks` is misaligned or `chunk` overlaps it.
+///
+/// # Safety
+///
+/// The `chunks` parameter must point to a chunks context that has been
+/// initialized. The `chunk` buffer must have allocated storage for at least
+/// `len` bytes. The chunks context must not be used concurrently by other
+/// threads while this function runs. All the safety considerations of
+/// `ssdv_fec_encoder_encode_chunks` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_chunks_next(
+    chunks: *mut ssdv_fec_chunks_t,
+    chunk: *mut c_char,
+    len: c_int,
+) -> c_int {
+    if len < 0 {
+        return SSDV_FEC_ERR_INVALID_LENGTH;
+    }
+    if chunks.is_null() || (chunk.is_null() && len > 0) {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !chunks.is_aligned() || overlaps_context(chunks, chunk, len as usize) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let chunk = slice::from_raw_parts_mut(chunk.cast::<u8>(), len as usize);
+    with_format!((*chunks).format, P => (*chunks).chunks_mut::<P>().next_chunk(chunk) as c_int)
+}
+
+/// Returns the number of bytes of a packet generated in chunks that have not
+/// been written yet.
+///
+/// The function returns `SSDV_FEC_ERR_BAD_BUFFER` if `chunks` is misaligned.
+///
+/// # Safety
+///
+/// The `chunks` parameter must point to a chunks context that has been
+/// initialized.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_chunks_remaining(chunks: *const ssdv_fec_chunks_t) -> c_int {
+    if chunks.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !chunks.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*chunks).format, P => (*chunks).chunks::<P>().remaining() as c_int)
+}
+
+/// Starts generating a FEC encoded packet word by word.
+///
+/// This function prepares the `words` context to generate the packet with
+/// `packet_id` using an encoder context previously prepared by a call to
+/// [`ssdv_fec_encoder_init`]. The packet is then obtained 2 bytes at a time
+/// with [`ssdv_fec_words_next`], which computes each word on demand. This is
+/// intended for radio interrupt handlers that transmit packets without any
+/// buffer for them. The packet is the same as the one generated by
+/// [`ssdv_fec_encoder_encode_packet`].
+///
+/// Each word of the data field of a FEC packet takes one GF(2¹⁶) division per
+/// systematic packet, so generating a whole packet in this way is slower than
+/// with [`ssdv_fec_encoder_encode_chunks`].
+///
+/// The function returns zero on success, `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED`
+/// if the encoder is not initialized, `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if
+/// `packet_id` is `0xffff`, or `SSDV_FEC_ERR_BAD_BUFFER` if one of the contexts
+/// is misaligned or they overlap.
+///
+/// # Safety
+///
+/// The `words` parameter must point to valid storage for a words context. The
+/// words context refers to the encoder context, so the encoder context must not
+/// be moved, released or initialized again while the words context is used.
+/// The encoder context can be used concurrently by several threads to encode
+/// packets. All the safety considerations of `ssdv_fec_encoder_init` also
+/// apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_encode_words(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: u16,
+    words: *mut ssdv_fec_words_t,
+) -> c_int {
+    if encoder.is_null() || words.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned()
+        || !words.is_aligned()
+        || overlaps_context(encoder, words, size_of::<ssdv_fec_words_t>())
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    if packet_id == u16::MAX {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    let format = (*encoder).format;
+    with_format!(format, P => {
+        (*words)
+            .storage
+            .as_mut_ptr()
+            .cast::<PacketWords<P>>()
+            .write((*encoder).encoder::<P>().encode_words(packet_id));
+    });
+    (*words).format = format;
+    0
+}
+
+/// Writes the next word of a packet generated word by word.
+///
+/// This function writes the next 2 bytes of the packet being generated by a
+/// words context prepared with [`ssdv_fec_encoder_encode_words`] to `word`.
+///
+/// The function returns 2 if the word has been written, zero once the whole
+/// packet has been written, or `SSDV_FEC_ERR_BAD_BUFFER` if `words` is
+/// misaligned or `word` overlaps it.
+///
+/// # Safety
+///
+/// The `words` parameter must point to a words context that has been
+/// initialized. The `word` buffer must have allocated storage for at least 2
+/// bytes. The words context must not be used concurrently by other threads
+/// while this function runs. All the safety considerations of
+/// `ssdv_fec_encoder_encode_words` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_words_next(
+    words: *mut ssdv_fec_words_t,
+    word: *mut c_char,
+) -> c_int {
+    if words.is_null() || word.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !words.is_aligned() || overlaps_context(words, word, 2) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*words).format, P => match (*words).words_mut::<P>().next_word() {
+        Some(bytes) => {
+            word.cast::<[u8; 2]>().write_unaligned(bytes);
+            2
+        }
+        None => 0,
+    })
+}
+
+/// Returns the number of bytes of a packet generated word by word that have
+/// not been written yet.
+///
+/// The function returns `SSDV_FEC_ERR_BAD_BUFFER` if `words` is misaligned.
+///
+/// # Safety
+///
+/// The `words` parameter must point to a words context that has been
+/// initialized.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_words_remaining(words: *const ssdv_fec_words_t) -> c_int {
+    if words.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !words.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*words).format, P => (*words).words::<P>().remaining() as c_int)
+}
+
+/// Checks whether an SSDV FEC encoder is ready to encode packets.
+///
+/// The function returns true if the encoder context has been initialized with
+/// [`ssdv_fec_encoder_init`] and has not been released, so that it owns the
+/// buffer given to `ssdv_fec_encoder_init`. This can be used, for instance
+/// after a task restart, to detect whether a context still holds an image
+/// before calling the encoding functions. It returns false if `encoder` is
+/// `NULL` or misaligned.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed, as is
+/// the case for static variables. The encoder context must not be used
+/// concurrently by other threads that modify it while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_is_ready(encoder: *const ssdv_fec_encoder_t) -> bool {
+    !encoder.is_null() && encoder.is_aligned() && (*encoder).initialized
+}
+
+/// Returns the status of an SSDV FEC encoder.
+///
+/// The status indicates whether the encoder is ready, as
+/// [`ssdv_fec_encoder_is_ready`] does, and if so, which buffer it owns and the
+/// image ID and number of systematic packets of the image. This allows C code
+/// that manages several transmission sessions to check which image each
+/// encoder context holds.
+///
+/// # Safety
+///
+/// The same safety considerations as for `ssdv_fec_encoder_is_ready` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_status(
+    encoder: *const ssdv_fec_encoder_t,
+) -> ssdv_fec_encoder_status_t {
+    if !ssdv_fec_encoder_is_ready(encoder) {
+        return ssdv_fec_encoder_status_t {
+            buffer: ptr::null(),
+            num_systematic: -1,
+            image_id: -1,
+            ready: false,
+        };
+    }
+    with_format!((*encoder).format, P => {
+        let encoder = (*encoder).encoder::<P>();
+        ssdv_fec_encoder_status_t {
+            buffer: encoder.as_ptr().cast(),
+            num_systematic: c_int::from(encoder.num_systematic()),
+            image_id: c_int::from(encoder.image_id()),
+            ready: true,
+        }
+    })
+}
+
+/// Returns the number of systematic packets of the image of an SSDV FEC
+/// encoder.
+///
+/// This function and the other getters of the image metadata return -1 if the
+/// encoder is not initialized or `encoder` is `NULL` or misaligned.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. All the
+/// safety considerations of `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_num_systematic(
+    encoder: *const ssdv_fec_encoder_t,
+) -> c_int {
+    if encoder.is_null() || !encoder.is_aligned() || !(*encoder).initialized {
+        return -1;
+    }
+    with_format!((*encoder).format, P => c_int::from((*encoder).encoder::<P>().num_systematic()))
+}
+
+/// Returns the image ID of the image of an SSDV FEC encoder.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. All the
+/// safety considerations of `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_image_id(encoder: *const ssdv_fec_encoder_t) -> c_int {
+    if encoder.is_null() || !encoder.is_aligned() || !(*encoder).initialized {
+        return -1;
+    }
+    with_format!((*encoder).format, P => c_int::from((*encoder).encoder::<P>().image_id()))
+}
+
+/// Returns the width of the image of an SSDV FEC encoder.
+///
+/// As in the SSDV header, the width is measured in units of 16 pixels.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. All the
+/// safety considerations of `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_width(encoder: *const ssdv_fec_encoder_t) -> c_int {
+    if encoder.is_null() || !encoder.is_aligned() || !(*encoder).initialized {
+        return -1;
+    }
+    with_format!((*encoder).format, P => c_int::from((*encoder).encoder::<P>().image_width()))
+}
+
+/// Returns the height of the image of an SSDV FEC encoder.
+///
+/// As in the SSDV header, the height is measured in units of 16 pixels.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. All the
+/// safety considerations of `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_height(encoder: *const ssdv_fec_encoder_t) -> c_int {
+    if encoder.is_null() || !encoder.is_aligned() || !(*encoder).initialized {
+        return -1;
+    }
+    with_format!((*encoder).format, P => c_int::from((*encoder).encoder::<P>().image_height()))
+}
+
+/// Returns the flags field of the image of an SSDV FEC encoder.
+///
+/// # Safety
+///
+/// The `encoder` parameter must point to an encoder context that has been
+/// passed to `ssdv_fec_encoder_init` or whose storage has been zeroed. All the
+/// safety considerations of `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_encoder_flags(encoder: *const ssdv_fec_encoder_t) -> c_int {
+    if encoder.is_null() || !encoder.is_aligned() || !(*encoder).initialized {
+        return -1;
+    }
+    with_format!((*encoder).format, P => c_int::from((*encoder).encoder::<P>().flags()))
+}
+
+/// Prepares an SSDV FEC decoder.
+///
+/// The `decoder` parameter should point to the storage for the decoder
+/// context. The `format` parameter selects the format of the SSDV packets. The
+/// `buffer` parameter should point to an array where the packets pushed with
+/// [`ssdv_fec_decoder_push`] are stored. The length of this array,
+/// measured in number of SSDV packets, is indicated by the `num_buffer_packets`
+/// parameter. The array should be large enough to hold the systematic packets
+/// of the largest image that is expected. If the decoder is only used with
+/// [`ssdv_fec_decoder_decode_packets`], `buffer` can be `NULL` and
+/// `num_buffer_packets` can be zero.
+///
+/// If `lenient` is false, decoding fails if the headers of the input packets
+/// are inconsistent. If `lenient` is true, the inconsistencies are resolved by
+/// majority vote, discarding the packets that disagree with the majority.
+///
+/// The function returns zero on success, or a negative error code if there is
+/// an error. `SSDV_FEC_ERR_NULL_POINTER` is returned if `decoder` is `NULL`, or
+/// `buffer` is `NULL` and `num_buffer_packets` is not zero,
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `num_buffer_packets` is negative, and
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is misaligned or `buffer` overlaps
+/// it. The decoder context is not modified if there is an error.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to valid storage for a
+/// decoder context.
+/// The `format` parameter must be one of the values of `ssdv_fec_format_t`.
+/// The buffer pointed to by `buffer` must have allocated storage for at least
+/// `num_buffer_packets` SSDV packets and must outlive all the usage of the
+/// decoder context until it is initialized again with a new buffer. The
+/// decoder context must not be used concurrently by other threads while this
+/// function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_init(
+    decoder: *mut ssdv_fec_decoder_t,
+    format: ssdv_fec_format_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_buffer_packets = match packet_count(buffer, num_buffer_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    with_format!(format, P => {
+        if overlaps_context(decoder, buffer, num_buffer_packets.saturating_mul(P::LEN)) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let buffer = packets_mut::<P, _>(buffer, num_buffer_packets);
+        (*decoder)
+            .storage
+            .as_mut_ptr()
+            .cast::<StreamingDecoder<P>>()
+            .write(StreamingDecoder::with_options(
+                buffer,
+                DecoderOptions::new().lenient(lenient),
+            ));
+    });
+    (*decoder).format = format;
+    (*decoder).output = ptr::null_mut();
+    (*decoder).num_output_packets = 0;
+    (*decoder).scratch = ptr::null_mut();
+    (*decoder).scratch_len = 0;
+    (*decoder).stats = ssdv_fec_stats_t::default();
+    0
+}
+
+/// Prepares an SSDV FEC decoder with an output buffer.
+///
+/// This function works as [`ssdv_fec_decoder_init`], but additionally gives
+/// the decoder an `output` array, of length `num_output_packets` measured in
+/// number of SSDV packets, where the decoded images are written by
+/// [`ssdv_fec_decoder_decode_pushed`] when it is called with a `NULL` output.
+/// This allows the buffers to be allocated once, for instance statically at
+/// boot, and given to the decoder, so that they do not need to be passed in
+/// each call.
+///
+/// The function returns the same errors as `ssdv_fec_decoder_init`, which
+/// also apply to `output` and `num_output_packets`. Besides,
+/// `SSDV_FEC_ERR_BAD_BUFFER` is returned if `output` overlaps `buffer`. The
+/// decoder context is not modified if there is an error.
+///
+/// # Safety
+///
+/// All the safety considerations of `ssdv_fec_decoder_init` apply. Besides,
+/// the buffer pointed to by `output` must have allocated storage for at least
+/// `num_output_packets` SSDV packets and must outlive all the usage of the
+/// decoder context until it is initialized again.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_setup(
+    decoder: *mut ssdv_fec_decoder_t,
+    format: ssdv_fec_format_t,
+    buffer: *mut c_char,
+    num_buffer_packets: c_int,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    lenient: bool,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    let output_len = match packet_count(output, num_output_packets) {
+        Ok(count) => count.saturating_mul(ssdv_fec_packet_len(format) as usize),
+        Err(err) => return err,
+    };
+    let buffer_len = with_format!(format, P => packets_len::<P>(num_buffer_packets));
+    if overlaps_context(decoder, output, output_len)
+        || overlap(buffer.cast(), buffer_len, output.cast(), output_len)
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let ret = ssdv_fec_decoder_init(decoder, format, buffer, num_buffer_packets, lenient);
+    if ret != 0 {
+        return ret;
+    }
+    (*decoder).output = output;
+    (*decoder).num_output_packets = num_output_packets;
+    0
+}
+
+/// Pushes a received packet into an SSDV FEC decoder.
+///
+/// The `packet` parameter should point to a received SSDV packet. The packet
+/// is copied into the buffer of the decoder unless it has a wrong CRC, it is a
+/// duplicate of a packet already stored, it belongs to a different image than
+/// the packets already stored (only for strict decoders), it belongs to an
+/// image with more than `SSDV_FEC_MAX_K` systematic packets, or the buffer is
+/// full. The function returns one of the `SSDV_FEC_PUSH_*` values to indicate
+/// the outcome, or `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is misaligned or
+/// `packet` overlaps it.
+///
+/// This function is fast, so it can be called as packets arrive (for instance
+/// from the interrupt handler of the radio), while decoding with
+/// [`ssdv_fec_decoder_decode_pushed`] is only done when
+/// [`ssdv_fec_decoder_status`] indicates that the decoder is ready.
+///
+/// # Safety
+///
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The `packet` parameter must point to at least the size of an
+/// SSDV packet. The decoder context must not be used concurrently by other
+/// threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_push(
+    decoder: *mut ssdv_fec_decoder_t,
+    packet: *const c_char,
+) -> c_int {
+    if decoder.is_null() || packet.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let status = with_format!((*decoder).format, P => {
+        if overlaps_context(decoder, packet, P::LEN) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let decoder = (*decoder).decoder_mut::<P>();
+        let packet = &*packet.cast::<P>();
+        // Only packets with a correct CRC are reported as too large. The
+        // others are reported as having a wrong CRC by the decoder.
+        if exceeds_max_k(packet) && packet.compute_crc32() == packet.crc32() {
+            log(
+                SSDV_FEC_LOG_WARNING,
+                format_args!(
+                    "dropped packet {}: image larger than {MAX_K} packets",
+                    packet.packet_id()
+                ),
+            );
+            return SSDV_FEC_PUSH_TOO_LARGE;
+        }
+        let was_ready = decoder.is_ready();
+        let status = decoder.push(packet);
+        log_push(decoder, packet, status);
+        if !was_ready && decoder.is_ready() {
+            log(
+                SSDV_FEC_LOG_INFO,
+                format_args!(
+                    "decoder ready: image {}, {} packets",
+                    packet.image_id(),
+                    decoder.num_packets()
+                ),
+            );
+        }
+        status
+    });
+    let stats = &mut (*decoder).stats;
+    match status {
+        PushStatus::Stored => SSDV_FEC_PUSH_STORED,
+        PushStatus::Duplicate => {
+            stats.duplicates = stats.duplicates.wrapping_add(1);
+            SSDV_FEC_PUSH_DUPLICATE
+        }
+        PushStatus::WrongCrc => {
+            stats.wrong_crc = stats.wrong_crc.wrapping_add(1);
+            SSDV_FEC_PUSH_WRONG_CRC
+        }
+        PushStatus::OtherImage => SSDV_FEC_PUSH_OTHER_IMAGE,
+        PushStatus::BufferFull => SSDV_FEC_PUSH_BUFFER_FULL,
+    }
+}
+
+/// Pushes several received packets stored in strided frames into an SSDV FEC
+/// decoder.
+///
+/// This function works as calling [`ssdv_fec_decoder_push`] for each of the
+/// `num_packets` packets in `packets`, which are located at intervals of
+/// `stride` bytes. This allows pushing packets directly from a buffer of radio
+/// frames, where each packet is stored inside a larger frame, without
+/// compacting them first.
+///
+/// The function returns the number of packets that have been stored,
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `stride` is smaller than the packet size,
+/// or `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is misaligned. Packets that
+/// overlap the decoder context are not stored.
+/// The outcome of pushing each packet is reported to the log callback.
+///
+/// # Safety
+///
+/// The `packets` buffer must have allocated storage for at least
+/// `(num_packets - 1) * stride` bytes plus the size of an SSDV packet. All the
+/// safety considerations of `ssdv_fec_decoder_push` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_push_strided(
+    decoder: *mut ssdv_fec_decoder_t,
+    packets: *const c_char,
+    num_packets: c_int,
+    stride: c_int,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_packets = match packet_count(packets, num_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    if stride < ssdv_fec_packet_len((*decoder).format) {
+        return SSDV_FEC_ERR_INVALID_LENGTH;
+    }
+    let mut stored = 0;
+    for j in 0..num_packets {
+        let packet = packets.add(j * stride as usize);
+        if ssdv_fec_decoder_push(decoder, packet) == SSDV_FEC_PUSH_STORED {
+            stored += 1;
+        }
+    }
+    stored
+}
+
+/// Returns the status of an SSDV FEC decoder.
+///
+/// If `decoder` is `NULL` or misaligned, the status returned is not ready and
+/// its other fields are -1.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_status(
+    decoder: *const ssdv_fec_decoder_t,
+) -> ssdv_fec_decoder_status_t {
+    if decoder.is_null() || !decoder.is_aligned() {
+        return ssdv_fec_decoder_status_t {
+            num_packets: -1,
+            num_systematic: -1,
+            image_id: -1,
+            ready: false,
+        };
+    }
+    with_format!((*decoder).format, P => {
+        let decoder = (*decoder).decoder::<P>();
+        ssdv_fec_decoder_status_t {
+            num_packets: decoder.num_packets() as c_int,
+            num_systematic: decoder.num_systematic().map_or(-1, c_int::from),
+            image_id: decoder.image_id().map_or(-1, c_int::from),
+            ready: decoder.is_ready(),
+        }
+    })
+}
 
-/// Prepares the SSDV FEC encoder.
+/// Returns the statistics of an SSDV FEC decoder.
 ///
-/// The `ssdv_packets` parameter should point to an array that contains the
-/// concatenation of the SSDV systematic packets corresponding to a single
-/// image. The number of packets in this array is indicated in the
-/// `num_ssdv_packets` parameter.
+/// The statistics are written to `stats`. They are intended to be reported in
+/// telemetry, so that the number of packets dropped by
+/// [`ssdv_fec_decoder_push`] and how much the last image decoded by
+/// [`ssdv_fec_decoder_decode_pushed`] relied on the FEC packets do not need to
+/// be computed by the caller. Decoding with [`ssdv_fec_decoder_decode_packets`]
+/// does not update the statistics.
 ///
-/// The function returns zero on success, or a negative error code if there is
-/// an error.
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if
+/// `decoder` or `stats` are `NULL`, or `SSDV_FEC_ERR_BAD_BUFFER` if they are
+/// misaligned.
 ///
-/// This function modifies the contents of the `ssdv_packets` array.
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized. The `stats` parameter must be `NULL` or point to
+/// valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_stats(
+    decoder: *const ssdv_fec_decoder_t,
+    stats: *mut ssdv_fec_stats_t,
+) -> c_int {
+    if decoder.is_null() || stats.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() || !stats.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    *stats = (*decoder).stats;
+    0
+}
+
+/// Returns the IDs of the systematic packets missing from an SSDV FEC
+/// decoder.
+///
+/// The IDs of the systematic packets that have not been pushed into the
+/// decoder are written in increasing order to the `ids` array, whose length is
+/// indicated by `max_ids`. If there are more than `max_ids` missing packets,
+/// only the first `max_ids` are written. If `num_needed` is not `NULL`, the
+/// number of additional distinct packets (systematic or FEC) needed to decode
+/// the image is written to it.
+///
+/// The function returns the total number of missing systematic packets, which
+/// can be larger than `max_ids`, or
+/// `SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC` if the number of systematic
+/// packets of the image is not known yet because no FEC packet or systematic
+/// packet with the EOI flag has been pushed. `SSDV_FEC_ERR_BAD_BUFFER` is
+/// returned if `decoder`, `ids` or `num_needed` are misaligned, or `ids`
+/// overlaps the decoder context.
 ///
 /// # Safety
 ///
-/// This function is not thread safe, since it uses static mutable storage. The
-/// buffer pointed to by `ssdv_packets` must have allocated storage for at least
-/// `num_ssdv_packets` SSDV packets and must outlive all the usage of the FEC
-/// encoder until this function is called again with a new buffer.
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The `ids` buffer must have allocated storage for at least
+/// `max_ids` elements. The `num_needed` parameter must be `NULL` or point to
+/// valid storage.
 #[no_mangle]
-pub unsafe extern "C" fn ssdv_fec_encoder_setup(
-    ssdv_packets: *mut c_char,
-    num_ssdv_packets: c_int,
+pub unsafe extern "C" fn ssdv_fec_decoder_missing_ids(
+    decoder: *const ssdv_fec_decoder_t,
+    ids: *mut u16,
+    max_ids: c_int,
+    num_needed: *mut c_int,
 ) -> c_int {
-    let ssdv_packets =
-        slice::from_raw_parts_mut(ssdv_packets.cast::<SSDVPacket>(), num_ssdv_packets as usize);
-    let encoder = match Encoder::new(ssdv_packets) {
-        Ok(encoder) => encoder,
-        Err(err) => {
-            return match err {
-                EncoderError::EmptyInput => SSDV_FEC_ENCODER_ERR_EMPTY_INPUT,
-                EncoderError::TooLongInput => SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT,
-                EncoderError::NonSystematicInput => SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT,
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    let max_ids = match packet_count(ids, max_ids) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    if !decoder.is_aligned()
+        || !ids.is_aligned()
+        || !num_needed.is_aligned()
+        || overlaps_context(decoder, ids, max_ids.saturating_mul(size_of::<u16>()))
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*decoder).format, P => {
+        let decoder = (*decoder).decoder::<P>();
+        let Some(needed) = decoder.num_packets_needed() else {
+            return SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC;
+        };
+        if !num_needed.is_null() {
+            *num_needed = needed as c_int;
+        }
+        let mut count = 0;
+        for id in decoder.missing_ids() {
+            if count < max_ids {
+                *ids.add(count) = id;
             }
+            count += 1;
+        }
+        count as c_int
+    })
+}
+
+/// Decodes the SSDV image from the packets pushed into an SSDV FEC decoder.
+///
+/// The `output` parameter should point to an array where the decoded SSDV
+/// packets can be written to. The `num_output_packets` indicates the length of
+/// this array, measured in number of SSDV packets. If `output` is `NULL`, the
+/// output buffer given to [`ssdv_fec_decoder_setup`] is used instead and
+/// `num_output_packets` is ignored.
+///
+/// The function returns the length of the decoded SSDV image, measured in
+/// number of SSDV packets, if decoding is successful, or a negative error code
+/// otherwise. If the decoder is not ready, the packets stored in the decoder
+/// are kept. Otherwise, they are consumed by the decoder and the decoder is
+/// reset, so that it can receive a new image. If scratch memory has been given
+/// to the decoder with [`ssdv_fec_decoder_set_scratch`], decoding is done on a
+/// copy of the stored packets in the scratch memory, and the stored packets
+/// are kept if decoding fails, so that more packets can be pushed.
+/// `SSDV_FEC_ERR_BAD_BUFFER` is returned if `decoder` is misaligned or `output`
+/// overlaps it.
+///
+/// # Safety
+///
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The `output` buffer should be valid allocated storage of size
+/// at least `num_output_packets`. The decoder context must not be used
+/// concurrently by other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_decode_pushed(
+    decoder: *mut ssdv_fec_decoder_t,
+    output: *mut c_char,
+    num_output_packets: c_int,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let (output, num_output_packets) = if output.is_null() {
+        ((*decoder).output, (*decoder).num_output_packets)
+    } else {
+        (output, num_output_packets)
+    };
+    let num_output_packets = match packet_count(output, num_output_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    with_format!((*decoder).format, P => {
+        if overlaps_context(decoder, output, num_output_packets.saturating_mul(P::LEN)) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
         }
+        let output = packets_mut::<P, _>(output, num_output_packets);
+        (*decoder)
+            .decode_stored(output)
+            .map_or_else(|err| err, |len| len as c_int)
+    })
+}
+
+/// Partially decodes the SSDV image from the packets pushed into an SSDV FEC
+/// decoder.
+///
+/// This can be used to display an incomplete image while packets are being
+/// received. The systematic packets pushed so far are written to their slots in
+/// the `output` array, whose length is indicated by `num_output_packets`,
+/// measured in number of SSDV packets. The packet with packet ID `i` is written
+/// to slot `i`. If `output` is `NULL`, the output buffer given to
+/// [`ssdv_fec_decoder_setup`] is used instead and `num_output_packets` is
+/// ignored.
+///
+/// The `valid` bitmap indicates which slots have been written. Bit `i % 8`
+/// (the least significant bit being bit 0) of `valid[i / 8]` is set if slot `i`
+/// is valid, and cleared otherwise. The other slots are not modified.
+///
+/// The function returns the number of valid slots, or `SSDV_FEC_ERR_BAD_BUFFER`
+/// if `decoder` is misaligned or `output` or `valid` overlap it. Missing
+/// systematic packets cannot be recovered until enough packets have been
+/// pushed, so FEC packets are not used. The packets stored in the decoder are
+/// kept. Once [`ssdv_fec_decoder_status`] indicates that the decoder is ready,
+/// [`ssdv_fec_decoder_decode_pushed`] should be used to obtain the complete
+/// image.
+///
+/// # Safety
+///
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The `output` buffer should be valid allocated storage of size
+/// at least `num_output_packets`. The `valid` buffer must have allocated
+/// storage for at least `(num_output_packets + 7) / 8` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_decode_partial(
+    decoder: *const ssdv_fec_decoder_t,
+    output: *mut c_char,
+    num_output_packets: c_int,
+    valid: *mut u8,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let (output, num_output_packets) = if output.is_null() {
+        ((*decoder).output, (*decoder).num_output_packets)
+    } else {
+        (output, num_output_packets)
+    };
+    let num_output_packets = match packet_count(output, num_output_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
     };
-    SSDV_FEC_ENCODER.write(encoder);
+    let valid_len = num_output_packets.div_ceil(8);
+    if valid.is_null() && valid_len != 0 {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    with_format!((*decoder).format, P => {
+        if overlaps_context(decoder, output, num_output_packets.saturating_mul(P::LEN))
+            || overlaps_context(decoder, valid, valid_len)
+        {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let output = packets_mut::<P, _>(output, num_output_packets);
+        let valid = if valid_len == 0 {
+            &mut []
+        } else {
+            slice::from_raw_parts_mut(valid, valid_len)
+        };
+        (*decoder)
+            .decoder::<P>()
+            .try_decode_partial(output, valid)
+            .map_or_else(decoder_error, |num_valid| num_valid as c_int)
+    })
+}
+
+/// Gives scratch memory to an SSDV FEC decoder.
+///
+/// The `mem` parameter should point to a memory region of `len` bytes, for
+/// instance a static array, that the decoder uses as working memory instead of
+/// modifying its inputs in place. Decoding with
+/// [`ssdv_fec_decoder_decode_pushed`] then keeps the stored packets if it
+/// fails, and [`ssdv_fec_decoder_decode_packets`] does not modify its input.
+/// The scratch memory must be able to hold as many packets as are decoded, so
+/// `SSDV_FEC_MAX_IMAGE_LEN` bytes are enough for any image. If it is too short,
+/// decoding fails with `SSDV_FEC_ERR_SCRATCH_TOO_SHORT`. If `mem` is `NULL`,
+/// the scratch memory is removed and decoding is done in place.
+///
+/// The library never allocates memory, so the scratch memory is the only
+/// working memory used by the decoder besides the stack. It is removed when the
+/// decoder is initialized, so this function must be called after
+/// [`ssdv_fec_decoder_init`].
+///
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if
+/// `decoder` is `NULL`, or `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is
+/// misaligned or `mem` overlaps it.
+///
+/// # Safety
+///
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The memory pointed to by `mem` must have allocated storage for
+/// at least `len` bytes, must not overlap the other buffers used with the
+/// decoder, and must outlive all the usage of the decoder context until it is
+/// initialized again. The decoder context must not be used concurrently by
+/// other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_set_scratch(
+    decoder: *mut ssdv_fec_decoder_t,
+    mem: *mut c_void,
+    len: usize,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() || (!mem.is_null() && overlaps_context(decoder, mem, len)) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    (*decoder).scratch = mem;
+    (*decoder).scratch_len = if mem.is_null() { 0 } else { len };
     0
 }
 
-/// Generates a FEC encoded packet.
+/// Removes all the packets pushed into an SSDV FEC decoder.
 ///
-/// This function generates a systematic or FEC SSDV packet using the encoder
-/// previously prepared by a call to [`ssdv_fec_encoder_setup`]. The `packet_id`
-/// parameter corresponds to the SSDV packet ID. The `output` parameter should
-/// point to an array of size at least the size of an SSDV packet. The encoded
-/// packet is written to this array.
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if
+/// `decoder` is `NULL`, or `SSDV_FEC_ERR_BAD_BUFFER` if it is misaligned.
 ///
 /// # Safety
 ///
-/// This function is not thread safe. The `packet_id` parameter must be
-/// non-negative and smaller than `2**16 - 1`. The `output` buffer must have
-/// allocated storage for at least one SSDV packet. All the safety
-/// considerations of `ssdv_fec_encoder_setup` also apply.
+/// The `decoder` parameter must point to a decoder context that has been
+/// initialized. The decoder context must not be used concurrently by other
+/// threads while this function runs.
 #[no_mangle]
-pub unsafe extern "C" fn ssdv_fec_encoder_encode(packet_id: c_int, output: *mut c_char) {
-    let output = output.cast::<SSDVPacket>();
-    let output = &mut *output;
-    SSDV_FEC_ENCODER
-        .assume_init_mut()
-        .encode(packet_id as u16, output);
+pub unsafe extern "C" fn ssdv_fec_decoder_reset(decoder: *mut ssdv_fec_decoder_t) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*decoder).format, P => (*decoder).decoder_mut::<P>().reset());
+    0
 }
 
 /// Decodes a FEC encoded SSDV image.
 ///
 /// This function decodes an SSDV image from a series of FEC encoded SSDV
-/// packets if there are enough packets to recover the original image. The
-/// `input` parameter should point to an array that contains the FEC encoded
-/// SSDV packets. The number of packets in this array is indicated by the
-/// `num_input_packets` parameter. The `output` parameter should point to an
-/// array where the decoded SSDV packets can be written to. The
+/// packets if there are enough packets to recover the original image, using a
+/// decoder context previously prepared by a call to [`ssdv_fec_decoder_init`].
+/// The `input` parameter should point to an array that contains the FEC
+/// encoded SSDV packets. The number of packets in this array is indicated by
+/// the `num_input_packets` parameter. The `output` parameter should point to
+/// an array where the decoded SSDV packets can be written to. The
 /// `num_output_packets` indicates the length of this array, measured in number
 /// of SSDV packets.
 ///
@@ -95,43 +1740,759 @@ pub unsafe extern "C" fn ssdv_fec_encoder_encode(packet_id: c_int, output: *mut
 /// number of SSDV packets, if decoding is successful. After the function
 /// successfully returns, the beginning of the `output` array contains the
 /// decoded SSDV image. If decoding is not possible, the function returns a
-/// negative error code.
+/// negative error code. `SSDV_FEC_ERR_INVALID_LENGTH` is returned if
+/// `num_input_packets` or `num_output_packets` are negative, and
+/// `SSDV_FEC_ERR_NULL_POINTER` if `decoder` is `NULL` or `input` or `output`
+/// are `NULL` and their number of packets is not zero. These checks are done
+/// before the buffers are used. `SSDV_FEC_ERR_BAD_BUFFER` is returned if
+/// `decoder` is misaligned, `input` and `output` overlap, or one of them
+/// overlaps the decoder context.
 ///
-/// The function modifies the contents of the `input` array.
+/// The function modifies the contents of the `input` array, unless scratch
+/// memory has been given to the decoder with [`ssdv_fec_decoder_set_scratch`],
+/// in which case decoding is done on a copy of the input in the scratch memory.
 ///
 /// # Safety
 ///
-/// The `input` and `output` buffers should be valid allocated storage of size
-/// at least as indicated by their corresponding `num_*_packets` parameters.
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized. The `input` and `output` buffers should be valid
+/// allocated storage of size at least as indicated by their corresponding
+/// `num_*_packets` parameters.
 #[no_mangle]
-pub unsafe extern "C" fn ssdv_fec_decoder_decode(
+pub unsafe extern "C" fn ssdv_fec_decoder_decode_packets(
+    decoder: *const ssdv_fec_decoder_t,
     input: *mut c_char,
     num_input_packets: c_int,
     output: *mut c_char,
     num_output_packets: c_int,
 ) -> c_int {
-    let input = slice::from_raw_parts_mut(input.cast::<SSDVPacket>(), num_input_packets as usize);
-    let output =
-        slice::from_raw_parts_mut(output.cast::<SSDVPacket>(), num_output_packets as usize);
-    match Decoder::decode(input, output) {
-        Ok(packets) => packets.len() as c_int,
-        Err(err) => match err {
-            DecoderError::EoiOnFecPacket => SSDV_FEC_DECODER_ERR_EOI_ON_FEC_PACKET,
-            DecoderError::DuplicatedEoi => SSDV_FEC_DECODER_ERR_DUPLICATED_EOI,
-            DecoderError::NumSystematicMismatch => SSDV_FEC_DECODER_ERR_NUM_SYSTEMATIC_MISMATCH,
-            DecoderError::UnknownNumSystematic => SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC,
-            DecoderError::EoiFecMismatch => SSDV_FEC_DECODER_ERR_EOI_FEC_MISMATCH,
-            DecoderError::NotEnoughInput => SSDV_FEC_DECODER_ERR_NOT_ENOUGH_INPUT,
-            DecoderError::OutputTooShort => SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT,
-            DecoderError::WrongSystematicId => SSDV_FEC_DECODER_ERR_WRONG_SYSTEMATIC_ID,
-            DecoderError::MultipleImageIds => SSDV_FEC_DECODER_ERR_MULTIPLE_IMAGE_IDS,
-            DecoderError::InconsistentFlags => SSDV_FEC_DECODER_ERR_INCONSISTENT_FLAGS,
-            DecoderError::DimensionsMismatch => SSDV_FEC_DECODER_ERR_DIMENSIONS_MISMATCH,
-            DecoderError::NoSystematic => SSDV_FEC_DECODER_ERR_NO_SYSTEMATIC,
-        },
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_input_packets = match packet_count(input, num_input_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    let num_output_packets = match packet_count(output, num_output_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    with_format!((*decoder).format, P => {
+        let input_len = num_input_packets.saturating_mul(P::LEN);
+        let output_len = num_output_packets.saturating_mul(P::LEN);
+        if overlap(input.cast(), input_len, output.cast(), output_len)
+            || overlaps_context(decoder, input, input_len)
+            || overlaps_context(decoder, output, output_len)
+        {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let input = packets_mut::<P, _>(input, num_input_packets);
+        let output = packets_mut::<P, _>(output, num_output_packets);
+        (*decoder)
+            .decode_input(input, output)
+            .map_or_else(|err| err, |len| len as c_int)
+    })
+}
+
+/// Decodes a FEC encoded SSDV image stored in strided frames.
+///
+/// This function works as [`ssdv_fec_decoder_decode_packets`], but the
+/// `num_input_packets` input packets are located in `input` at intervals of
+/// `stride` bytes. This allows decoding directly from a buffer of radio frames,
+/// where each packet is stored inside a larger frame. The input packets are
+/// copied to the scratch memory given to the decoder with
+/// [`ssdv_fec_decoder_set_scratch`], where decoding is done, so `input` is not
+/// modified. The decoded image is written to the `output` array, whose length
+/// is indicated by `num_output_packets`, measured in number of SSDV packets.
+///
+/// The function returns the length of the decoded SSDV image, measured in
+/// number of SSDV packets, if decoding is successful, or a negative error code
+/// otherwise. `SSDV_FEC_ERR_INVALID_LENGTH` is returned if `stride` is smaller
+/// than the packet size, `SSDV_FEC_ERR_SCRATCH_TOO_SHORT` if the decoder has
+/// no scratch memory or it cannot hold `num_input_packets` packets, and
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is misaligned or `output` overlaps
+/// it. Negative numbers of packets and `NULL` pointers are rejected as in
+/// `ssdv_fec_decoder_decode_packets`.
+///
+/// # Safety
+///
+/// The `input` buffer must have allocated storage for at least
+/// `(num_input_packets - 1) * stride` bytes plus the size of an SSDV packet.
+/// All the safety considerations of `ssdv_fec_decoder_decode_packets` also
+/// apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_decoder_decode_strided(
+    decoder: *const ssdv_fec_decoder_t,
+    input: *const c_char,
+    num_input_packets: c_int,
+    stride: c_int,
+    output: *mut c_char,
+    num_output_packets: c_int,
+) -> c_int {
+    if decoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_input_packets = match packet_count(input, num_input_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    let num_output_packets = match packet_count(output, num_output_packets) {
+        Ok(count) => count,
+        Err(err) => return err,
+    };
+    with_format!((*decoder).format, P => {
+        if stride < P::LEN as c_int {
+            return SSDV_FEC_ERR_INVALID_LENGTH;
+        }
+        if overlaps_context(decoder, output, num_output_packets.saturating_mul(P::LEN)) {
+            return SSDV_FEC_ERR_BAD_BUFFER;
+        }
+        let Some(copy) = (*decoder)
+            .scratch::<P>()
+            .and_then(|scratch| scratch.get_mut(..num_input_packets))
+        else {
+            return scratch_too_short();
+        };
+        for (j, packet) in copy.iter_mut().enumerate() {
+            *packet = *input.add(j * stride as usize).cast::<P>();
+        }
+        let output = packets_mut::<P, _>(output, num_output_packets);
+        let result = Decoder::decode_with_stats(copy, output, (*decoder).decoder::<P>().options());
+        decode_result(result).map_or_else(|err| err, |(len, _)| len as c_int)
+    })
+}
+
+/// Prepares an SSDV FEC transmission scheduler.
+///
+/// The scheduler generates the sequence of packet IDs to encode and transmit
+/// for an image of `num_systematic` systematic packets in a carousel. The
+/// sequence is formed by rounds, in which all the systematic packets are sent,
+/// followed by `overhead_percent` percent of FEC packets (rounded up) that have
+/// not been sent in previous rounds. The sequence is the same as the one
+/// generated by the `FountainScheduler` of the `ssdv_fec` crate and the
+/// `carousel` command of the `ssdv-fec` tool with the same parameters, so the
+/// flight software and the ground tools agree on which packets are sent. The
+/// `seed` parameter selects the first FEC packet.
+///
+/// The function returns zero on success, `SSDV_FEC_ENCODER_ERR_EMPTY_INPUT` if
+/// `num_systematic` is zero, `SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT` if it is
+/// larger than `SSDV_FEC_MAX_K`, or `SSDV_FEC_ERR_BAD_BUFFER` if `scheduler` is
+/// misaligned.
+///
+/// # Safety
+///
+/// The `scheduler` parameter must point to valid storage for a scheduler
+/// context. The scheduler context must not be used concurrently by other
+/// threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_scheduler_init(
+    scheduler: *mut ssdv_fec_scheduler_t,
+    seed: u32,
+    num_systematic: u16,
+    overhead_percent: u16,
+) -> c_int {
+    if scheduler.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !scheduler.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if num_systematic == 0 {
+        // FountainScheduler panics if there are no systematic packets.
+        return SSDV_FEC_ENCODER_ERR_EMPTY_INPUT;
+    }
+    if usize::from(num_systematic) > MAX_K {
+        return SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT;
+    }
+    (*scheduler)
+        .storage
+        .as_mut_ptr()
+        .cast::<FountainScheduler>()
+        .write(FountainScheduler::with_overhead_percent(
+            num_systematic,
+            overhead_percent,
+            seed,
+        ));
+    0
+}
+
+/// Returns the next packet ID to transmit from an SSDV FEC transmission
+/// scheduler.
+///
+/// The packet can be generated with [`ssdv_fec_encoder_encode_packet`]. The
+/// scheduler returns packet IDs forever, repeating the rounds. If `scheduler`
+/// is `NULL` or misaligned, the function returns `0xffff`, which is never a
+/// valid packet ID.
+///
+/// # Safety
+///
+/// The `scheduler` parameter must point to a scheduler context that has been
+/// initialized. The scheduler context must not be used concurrently by other
+/// threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_scheduler_next(scheduler: *mut ssdv_fec_scheduler_t) -> u16 {
+    if scheduler.is_null() || !scheduler.is_aligned() {
+        return u16::MAX;
+    }
+    (*scheduler).scheduler_mut().next_packet_id()
+}
+
+/// Returns the number of packets in each round of an SSDV FEC transmission
+/// scheduler.
+///
+/// The function returns zero if `scheduler` is `NULL` or misaligned.
+///
+/// # Safety
+///
+/// The `scheduler` parameter must point to a scheduler context that has been
+/// initialized.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_scheduler_packets_per_round(
+    scheduler: *const ssdv_fec_scheduler_t,
+) -> u32 {
+    if scheduler.is_null() || !scheduler.is_aligned() {
+        return 0;
+    }
+    (*scheduler).scheduler().packets_per_round()
+}
+
+/// Computes the CRC-32 used by SSDV packets in the Longjiang-2 format.
+///
+/// The `data` parameter should point to an array of length `len`. This is the
+/// CRC-32 of standard SSDV packets, but initialized as if the packet type and
+/// callsign fields omitted by the Longjiang-2 format had been processed first.
+/// It is also known as the DSLWP CRC-32. If `data` is `NULL` or `len` is not
+/// positive, the CRC-32 of an empty array is returned.
+///
+/// # Safety
+///
+/// The `data` buffer must be `NULL` or have allocated storage for at least
+/// `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_crc32(data: *const c_char, len: c_int) -> u32 {
+    let data = if len > 0 && !data.is_null() {
+        slice::from_raw_parts(data.cast::<u8>(), len as usize)
+    } else {
+        &[]
+    };
+    crc32(data.iter())
+}
+
+/// Checks the CRC-32 of an SSDV packet.
+///
+/// The `packet` parameter should point to an SSDV packet in the format given by
+/// `format`. The function returns true if the CRC-32 of the packet is correct,
+/// and false if it is wrong or `packet` is `NULL`.
+///
+/// # Safety
+///
+/// The `format` parameter must be one of the values of `ssdv_fec_format_t`.
+/// The `packet` parameter must point to at least the size of an SSDV packet.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_packet_crc_ok(
+    format: ssdv_fec_format_t,
+    packet: *const c_char,
+) -> bool {
+    if packet.is_null() {
+        return false;
+    }
+    with_format!(format, P => {
+        let packet = &*packet.cast::<P>();
+        packet.compute_crc32() == packet.crc32()
+    })
+}
+
+/// Returns the length in bytes of an SSDV packet in a format.
+///
+/// This can be used to size buffers when the format is selected at runtime.
+/// The `SSDV_FEC_*_PACKET_LEN` constants give the same values at compile time.
+///
+/// # Safety
+///
+/// The `format` parameter must be one of the values of `ssdv_fec_format_t`.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_packet_len(format: ssdv_fec_format_t) -> c_int {
+    with_format!(format, P => P::LEN as c_int)
+}
+
+/// Validates a single SSDV packet.
+///
+/// The `packet` parameter should point to an SSDV packet in the format given by
+/// `format`. The function checks the CRC-32 of the packet, the fields that
+/// have a fixed value in the packet format, and the consistency of the header
+/// fields that can be checked without other packets of the image. It returns
+/// zero if the packet is valid, or a negative error code otherwise.
+/// `SSDV_FEC_ERR_NULL_POINTER` is returned if `packet` is `NULL`.
+///
+/// # Safety
+///
+/// The `format` parameter must be one of the values of `ssdv_fec_format_t`.
+/// The `packet` parameter must point to at least the size of an SSDV packet.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_packet_validate(
+    format: ssdv_fec_format_t,
+    packet: *const c_char,
+) -> c_int {
+    if packet.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    let result = with_format!(format, P => (*packet.cast::<P>()).validate());
+    match result {
+        Ok(()) => 0,
+        Err(PacketError::WrongCrc) => SSDV_FEC_PACKET_ERR_WRONG_CRC,
+        Err(PacketError::WrongFixedField) => SSDV_FEC_PACKET_ERR_WRONG_FIXED_FIELD,
+        Err(PacketError::EoiOnFecPacket) => SSDV_FEC_PACKET_ERR_EOI_ON_FEC_PACKET,
+        Err(PacketError::WrongFecId) => SSDV_FEC_PACKET_ERR_WRONG_FEC_ID,
+    }
+}
+
+/// Parses the header of an SSDV packet.
+///
+/// The `packet` parameter should point to an SSDV packet in the format given by
+/// `format`. The header fields of the packet are written to `info`. The fields
+/// are filled even if the CRC-32 of the packet is wrong, in which case they
+/// might be corrupted.
+///
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if
+/// `packet` or `info` are `NULL`, or `SSDV_FEC_ERR_BAD_BUFFER` if `info` is
+/// misaligned.
+///
+/// # Safety
+///
+/// The `format` parameter must be one of the values of `ssdv_fec_format_t`.
+/// The `packet` parameter must point to at least the size of an SSDV packet.
+/// The `info` parameter must point to valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_packet_info(
+    format: ssdv_fec_format_t,
+    packet: *const c_char,
+    info: *mut ssdv_fec_packet_info_t,
+) -> c_int {
+    if packet.is_null() || info.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !info.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    *info = with_format!(format, P => {
+        let packet = &*packet.cast::<P>();
+        ssdv_fec_packet_info_t {
+            image_id: packet.image_id(),
+            packet_id: packet.packet_id(),
+            flags: packet.flags(),
+            fec_packet: packet.is_fec_packet(),
+            eoi: packet.is_eoi(),
+            width: packet.width().map_or(-1, c_int::from),
+            height: packet.height().map_or(-1, c_int::from),
+            num_systematic: packet.number_systematic_packets().map_or(-1, c_int::from),
+            crc_ok: packet.compute_crc32() == packet.crc32(),
+        }
+    });
+    0
+}
+
+/// Returns the version of the library.
+///
+/// The function returns a pointer to a static NUL-terminated string containing
+/// the version of the library, such as `"0.1.0"`, which must not be modified or
+/// freed.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Returns the maximum number of systematic packets of an image.
+///
+/// This is the value of `SSDV_FEC_MAX_K` when the library was built, and can be
+/// used to check at runtime that the header matches the library.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_max_k() -> c_int {
+    MAX_K as c_int
+}
+
+/// Returns the capabilities of the library.
+///
+/// The function returns a bitmask formed by the `SSDV_FEC_CAP_*` values of the
+/// formats and features supported by the library. Bits that are not defined
+/// are reserved for future use, so they should be ignored.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_capabilities() -> u32 {
+    SSDV_FEC_CAP_FORMAT_LJ2
+        | SSDV_FEC_CAP_FORMAT_NO_FEC
+        | SSDV_FEC_CAP_LENIENT_DECODER
+        | SSDV_FEC_CAP_INCREMENTAL_DECODER
+        | SSDV_FEC_CAP_ENCODE_RANGE
+        | SSDV_FEC_CAP_API_V2
+        | SSDV_FEC_CAP_PARTIAL_DECODE
+        | SSDV_FEC_CAP_LOG_CALLBACK
+        | SSDV_FEC_CAP_SCRATCH
+        | SSDV_FEC_CAP_FAULT_HANDLER
+        | SSDV_FEC_CAP_STRIDED_BUFFERS
+        | SSDV_FEC_CAP_SCHEDULER
+        | SSDV_FEC_CAP_FORMAT_SYMBOLS
+        | SSDV_FEC_CAP_STATS
+        | SSDV_FEC_CAP_BUFFER_CHECKS
+        | SSDV_FEC_CAP_LENGTH_PREFIX
+        | SSDV_FEC_CAP_ENCODER_STATUS
+        | SSDV_FEC_CAP_SELFTEST
+        | SSDV_FEC_CAP_CHUNKS
+        | SSDV_FEC_CAP_PROGRESS_HOOK
+        | SSDV_FEC_CAP_WORDS
+        | if cfg!(feature = "critical-section") {
+            SSDV_FEC_CAP_CRITICAL_SECTION
+        } else {
+            0
+        }
+        | if cfg!(feature = "ram-tables") {
+            SSDV_FEC_CAP_RAM_TABLES
+        } else {
+            0
+        }
+}
+
+/// Initializes the library.
+///
+/// When the library is built with the `ram-tables` feature, the tables used by
+/// the encoder and decoder are generated in RAM by this function instead of
+/// being stored in flash, so it must be called once at startup, before any
+/// other function of the library. Otherwise it does nothing. Calling it again
+/// does nothing.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_init() {
+    #[cfg(feature = "ram-tables")]
+    ssdv_fec::init();
+}
+
+/// Returns a description of an error code.
+///
+/// The `err` parameter should be one of the negative error codes returned by
+/// the functions of this library. The function returns a pointer to a static
+/// NUL-terminated string describing the error, which must not be modified or
+/// freed. Unknown error codes are described as `"unknown error"`.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_strerror(err: c_int) -> *const c_char {
+    let description: &[u8] = match err {
+        0 => b"success\0",
+        SSDV_FEC_ENCODER_ERR_EMPTY_INPUT => b"encoder input is empty\0",
+        SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT => b"encoder input is too long\0",
+        SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT => b"non-systematic packet in encoder input\0",
+        SSDV_FEC_ENCODER_ERR_INVALID_RANGE => b"packet ID range out of bounds\0",
+        SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED => b"encoder not initialized\0",
+        SSDV_FEC_DECODER_ERR_EOI_ON_FEC_PACKET => b"EOI set on FEC packet\0",
+        SSDV_FEC_DECODER_ERR_DUPLICATED_EOI => b"EOI set on several different packets\0",
+        SSDV_FEC_DECODER_ERR_NUM_SYSTEMATIC_MISMATCH => {
+            b"mismatched number of systematic packets on different FEC packets\0"
+        }
+        SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC => {
+            b"could not determine number of systematic packets\0"
+        }
+        SSDV_FEC_DECODER_ERR_EOI_FEC_MISMATCH => {
+            b"mismatch between EOI and number of systematic packets\0"
+        }
+        SSDV_FEC_DECODER_ERR_NOT_ENOUGH_INPUT => b"not enough input packets\0",
+        SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT => b"output buffer is too short\0",
+        SSDV_FEC_DECODER_ERR_WRONG_SYSTEMATIC_ID => b"wrong packet ID on systematic packet\0",
+        SSDV_FEC_DECODER_ERR_MULTIPLE_IMAGE_IDS => b"multiple image IDs\0",
+        SSDV_FEC_DECODER_ERR_INCONSISTENT_FLAGS => b"inconsistent flags on different packets\0",
+        SSDV_FEC_DECODER_ERR_DIMENSIONS_MISMATCH => {
+            b"mismatched width or height on different systematic packets\0"
+        }
+        SSDV_FEC_DECODER_ERR_NO_SYSTEMATIC => b"no systematic packets\0",
+        SSDV_FEC_PACKET_ERR_WRONG_CRC => b"wrong CRC-32\0",
+        SSDV_FEC_PACKET_ERR_WRONG_FIXED_FIELD => b"wrong value in fixed field\0",
+        SSDV_FEC_PACKET_ERR_EOI_ON_FEC_PACKET => b"EOI set on FEC packet\0",
+        SSDV_FEC_PACKET_ERR_WRONG_FEC_ID => {
+            b"FEC packet ID inconsistent with the number of systematic packets\0"
+        }
+        SSDV_FEC_ERR_NULL_POINTER => b"NULL pointer argument\0",
+        SSDV_FEC_ERR_INVALID_LENGTH => b"invalid buffer length\0",
+        SSDV_FEC_ERR_OUTPUT_TOO_SHORT => b"output buffer is too short\0",
+        SSDV_FEC_ERR_SCRATCH_TOO_SHORT => b"scratch memory is too short\0",
+        SSDV_FEC_ERR_BAD_BUFFER => b"misaligned or overlapping buffer\0",
+        SSDV_FEC_ERR_SELFTEST_FAILED => b"self-test failed\0",
+        SSDV_FEC_ERR_INVALID_FORMAT => b"invalid packet format\0",
+        _ => b"unknown error\0",
+    };
+    description.as_ptr().cast()
+}
+
+// Checks whether the byte ranges starting at `a` and `b` overlap.
+fn overlap(a: *const u8, a_len: usize, b: *const u8, b_len: usize) -> bool {
+    let (a, b) = (a as usize, b as usize);
+    a < b.saturating_add(b_len) && b < a.saturating_add(a_len)
+}
+
+// Checks whether a buffer overlaps a context.
+fn overlaps_context<T, U>(context: *const T, buffer: *const U, len: usize) -> bool {
+    overlap(context.cast(), size_of::<T>(), buffer.cast(), len)
+}
+
+// Converts a number of packets given by the caller into a `usize`. Negative
+// numbers are rejected with SSDV_FEC_ERR_INVALID_LENGTH, and a NULL buffer is
+// only accepted if it holds no packets.
+fn packet_count<T>(buffer: *const T, count: c_int) -> Result<usize, c_int> {
+    let Ok(count) = usize::try_from(count) else {
+        return Err(SSDV_FEC_ERR_INVALID_LENGTH);
+    };
+    if buffer.is_null() && count != 0 {
+        return Err(SSDV_FEC_ERR_NULL_POINTER);
+    }
+    Ok(count)
+}
+
+// Returns a mutable slice of packets, which can be empty even if `ptr` is
+// NULL.
+//
+// `ptr` must be NULL or point to at least `num_packets` packets.
+unsafe fn packets_mut<'a, P: Packet, T>(ptr: *mut T, num_packets: usize) -> &'a mut [P] {
+    if num_packets == 0 {
+        &mut []
+    } else {
+        slice::from_raw_parts_mut(ptr.cast::<P>(), num_packets)
+    }
+}
+
+// Returns the length in bytes of `count` packets, which is zero if `count` is
+// negative.
+fn packets_len<P: Packet>(count: c_int) -> usize {
+    usize::try_from(count).map_or(0, |count| count.saturating_mul(P::LEN))
+}
+
+// Returns whether a packet belongs to an image with more than MAX_K systematic
+// packets.
+fn exceeds_max_k<P: Packet>(packet: &P) -> bool {
+    match packet.number_systematic_packets() {
+        Some(k) => usize::from(k) > MAX_K,
+        None => usize::from(packet.packet_id()) >= MAX_K,
+    }
+}
+
+// Describes an error code in log messages.
+fn describe(err: c_int) -> &'static str {
+    // SAFETY: ssdv_fec_strerror returns static NUL-terminated strings.
+    unsafe { CStr::from_ptr(ssdv_fec_strerror(err)) }
+        .to_str()
+        .unwrap_or("")
+}
+
+// Reports the outcome of pushing a packet into a decoder to the log callback.
+fn log_push<P: Packet>(decoder: &StreamingDecoder<P>, packet: &P, status: PushStatus) {
+    let id = packet.packet_id();
+    match status {
+        PushStatus::Stored => log(
+            SSDV_FEC_LOG_DEBUG,
+            format_args!("stored packet {id} ({} packets)", decoder.num_packets()),
+        ),
+        PushStatus::Duplicate => log(
+            SSDV_FEC_LOG_DEBUG,
+            format_args!("dropped packet {id}: duplicate"),
+        ),
+        PushStatus::WrongCrc => log(
+            SSDV_FEC_LOG_WARNING,
+            format_args!("dropped packet: wrong CRC-32"),
+        ),
+        PushStatus::OtherImage => log(
+            SSDV_FEC_LOG_INFO,
+            format_args!(
+                "dropped packet {id} of image {}: receiving image {}",
+                packet.image_id(),
+                decoder.image_id().unwrap_or_default()
+            ),
+        ),
+        PushStatus::BufferFull => log(
+            SSDV_FEC_LOG_WARNING,
+            format_args!("dropped packet {id}: buffer full"),
+        ),
+    }
+}
+
+// Converts the result of decoding to the length of the decoded image or an
+// error code, and reports it to the log callback.
+fn decode_result<P: Packet>(
+    result: Result<(&mut [P], DecodeStats), DecoderError>,
+) -> Result<(usize, DecodeStats), c_int> {
+    match result {
+        Ok((packets, stats)) => {
+            log(
+                SSDV_FEC_LOG_INFO,
+                format_args!(
+                    "decoded image {}: {} packets, {} recovered, {} header overrides",
+                    packets.first().map_or(0, |packet| packet.image_id()),
+                    packets.len(),
+                    stats.packets_recovered,
+                    stats.overrides.total()
+                ),
+            );
+            Ok((packets.len(), stats))
+        }
+        Err(err) => {
+            let err = decoder_error(err);
+            log(
+                SSDV_FEC_LOG_ERROR,
+                format_args!("decoding failed: {}", describe(err)),
+            );
+            Err(err)
+        }
+    }
+}
+
+// Reports that the scratch memory of a decoder is too short.
+fn scratch_too_short() -> c_int {
+    log(
+        SSDV_FEC_LOG_ERROR,
+        format_args!("decoding failed: scratch memory is too short"),
+    );
+    SSDV_FEC_ERR_SCRATCH_TOO_SHORT
+}
+
+fn encoder_error(err: EncoderError) -> c_int {
+    match err {
+        EncoderError::EmptyInput => SSDV_FEC_ENCODER_ERR_EMPTY_INPUT,
+        EncoderError::TooLongInput => SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT,
+        EncoderError::NonSystematicInput => SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT,
+        // only produced by ReadOnlyEncoder, which is not used by the C API
+        EncoderError::WeightsTooShort => SSDV_FEC_ERR_INVALID_LENGTH,
+        // only produced by the byte buffer functions, which are not used by
+        // the C API
+        EncoderError::InvalidLength => SSDV_FEC_ERR_INVALID_LENGTH,
+        EncoderError::InvalidRange => SSDV_FEC_ENCODER_ERR_INVALID_RANGE,
+    }
+}
+
+fn decoder_error(err: DecoderError) -> c_int {
+    match err {
+        DecoderError::EoiOnFecPacket => SSDV_FEC_DECODER_ERR_EOI_ON_FEC_PACKET,
+        DecoderError::DuplicatedEoi => SSDV_FEC_DECODER_ERR_DUPLICATED_EOI,
+        DecoderError::NumSystematicMismatch => SSDV_FEC_DECODER_ERR_NUM_SYSTEMATIC_MISMATCH,
+        DecoderError::UnknownNumSystematic => SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC,
+        DecoderError::EoiFecMismatch => SSDV_FEC_DECODER_ERR_EOI_FEC_MISMATCH,
+        DecoderError::NotEnoughInput => SSDV_FEC_DECODER_ERR_NOT_ENOUGH_INPUT,
+        DecoderError::OutputTooShort => SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT,
+        DecoderError::WrongSystematicId => SSDV_FEC_DECODER_ERR_WRONG_SYSTEMATIC_ID,
+        DecoderError::MultipleImageIds => SSDV_FEC_DECODER_ERR_MULTIPLE_IMAGE_IDS,
+        DecoderError::InconsistentFlags => SSDV_FEC_DECODER_ERR_INCONSISTENT_FLAGS,
+        DecoderError::DimensionsMismatch => SSDV_FEC_DECODER_ERR_DIMENSIONS_MISMATCH,
+        DecoderError::NoSystematic => SSDV_FEC_DECODER_ERR_NO_SYSTEMATIC,
+        DecoderError::InvalidLength => SSDV_FEC_ERR_INVALID_LENGTH,
     }
 }
 
+// Maximum number of systematic packets of an image, which is set at build time
+// with the SSDV_FEC_MAX_K environment variable and defined in the header by the
+// build script.
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
+
+const _: () = assert!(MAX_K >= 1 && MAX_K <= u16::MAX as usize);
+
+// Packet sizes
+
+/// Length in bytes of an SSDV packet in the `SSDV_FEC_FORMAT_LJ2` format
+pub const SSDV_FEC_LJ2_PACKET_LEN: usize = 218;
+/// Length in bytes of the data field of an SSDV packet in the
+/// `SSDV_FEC_FORMAT_LJ2` format
+pub const SSDV_FEC_LJ2_DATA_LEN: usize = 208;
+/// Length in bytes of an SSDV packet in the `SSDV_FEC_FORMAT_NO_FEC` format
+pub const SSDV_FEC_NO_FEC_PACKET_LEN: usize = 256;
+/// Length in bytes of the data field of an SSDV packet in the
+/// `SSDV_FEC_FORMAT_NO_FEC` format
+pub const SSDV_FEC_NO_FEC_DATA_LEN: usize = 240;
+/// Length in bytes of an SSDV packet in the default `SSDV_FEC_FORMAT_LJ2`
+/// format
+pub const SSDV_FEC_PACKET_LEN: usize = SSDV_FEC_LJ2_PACKET_LEN;
+/// Length in bytes of the data field of an SSDV packet in the default
+/// `SSDV_FEC_FORMAT_LJ2` format
+pub const SSDV_FEC_DATA_LEN: usize = SSDV_FEC_LJ2_DATA_LEN;
+/// Maximum length in bytes of an SSDV packet in any of the formats
+pub const SSDV_FEC_MAX_PACKET_LEN: usize = SSDV_FEC_NO_FEC_PACKET_LEN;
+/// Length in bytes of the length word written by
+/// `ssdv_fec_encoder_encode_prefixed`
+pub const SSDV_FEC_LENGTH_PREFIX_LEN: usize = 2;
+
+// cbindgen can only export constants with literal values, so these are checked
+// against the constants of the ssdv_fec crate.
+const _: () = assert!(
+    SSDV_FEC_LJ2_PACKET_LEN == SSDV_PACKET_LEN
+        && SSDV_FEC_LJ2_DATA_LEN == SSDV_DATA_LEN
+        && SSDV_FEC_NO_FEC_PACKET_LEN == SSDV_NO_FEC_PACKET_LEN
+        && SSDV_FEC_NO_FEC_DATA_LEN == SSDV_NO_FEC_DATA_LEN
+        && SSDV_FEC_MAX_PACKET_LEN >= SSDV_PACKET_LEN
+);
+
+// Capabilities
+
+/// Support for `SSDV_FEC_FORMAT_LJ2`
+pub const SSDV_FEC_CAP_FORMAT_LJ2: u32 = 1 << 0;
+/// Support for `SSDV_FEC_FORMAT_NO_FEC`
+pub const SSDV_FEC_CAP_FORMAT_NO_FEC: u32 = 1 << 1;
+/// Support for lenient decoding
+pub const SSDV_FEC_CAP_LENIENT_DECODER: u32 = 1 << 2;
+/// Support for incremental decoding with `ssdv_fec_decoder_push`
+pub const SSDV_FEC_CAP_INCREMENTAL_DECODER: u32 = 1 << 3;
+/// Support for `ssdv_fec_encoder_encode_range`
+pub const SSDV_FEC_CAP_ENCODE_RANGE: u32 = 1 << 4;
+/// Support for the hardened `ssdv_fec2_*` API
+pub const SSDV_FEC_CAP_API_V2: u32 = 1 << 5;
+/// Support for partial decoding with `ssdv_fec_decoder_decode_partial`
+pub const SSDV_FEC_CAP_PARTIAL_DECODE: u32 = 1 << 6;
+/// Support for a log callback with `ssdv_fec_set_log_callback`
+pub const SSDV_FEC_CAP_LOG_CALLBACK: u32 = 1 << 7;
+/// Support for decoder scratch memory with `ssdv_fec_decoder_set_scratch`
+pub const SSDV_FEC_CAP_SCRATCH: u32 = 1 << 8;
+/// Support for a fault handler with `ssdv_fec_set_fault_handler`
+pub const SSDV_FEC_CAP_FAULT_HANDLER: u32 = 1 << 9;
+/// Support for strided packet buffers
+pub const SSDV_FEC_CAP_STRIDED_BUFFERS: u32 = 1 << 10;
+/// Support for the transmission scheduler with `ssdv_fec_scheduler_init`
+pub const SSDV_FEC_CAP_SCHEDULER: u32 = 1 << 11;
+/// Support for the `ssdv_fec_cs_*` functions, which are only built with the
+/// `critical-section` feature
+pub const SSDV_FEC_CAP_CRITICAL_SECTION: u32 = 1 << 12;
+/// Support for the per-format `ssdv_fec_lj2_*` and `ssdv_fec_nofec_*` functions
+pub const SSDV_FEC_CAP_FORMAT_SYMBOLS: u32 = 1 << 13;
+/// Support for decoder statistics with `ssdv_fec_decoder_stats`
+pub const SSDV_FEC_CAP_STATS: u32 = 1 << 14;
+/// Rejection of misaligned and overlapping buffers by the `ssdv_fec2_*`
+/// functions and the original functions that return an `int` with
+/// `SSDV_FEC_ERR_BAD_BUFFER`
+pub const SSDV_FEC_CAP_BUFFER_CHECKS: u32 = 1 << 15;
+/// Support for length-prefixed packets with `ssdv_fec_encoder_encode_prefixed`
+pub const SSDV_FEC_CAP_LENGTH_PREFIX: u32 = 1 << 16;
+/// Support for `ssdv_fec_encoder_is_ready` and `ssdv_fec_encoder_status`
+pub const SSDV_FEC_CAP_ENCODER_STATUS: u32 = 1 << 17;
+/// Support for `ssdv_fec_selftest`
+pub const SSDV_FEC_CAP_SELFTEST: u32 = 1 << 18;
+/// Support for `ssdv_fec_encoder_encode_chunks`
+pub const SSDV_FEC_CAP_CHUNKS: u32 = 1 << 19;
+/// Support for a progress hook with `ssdv_fec_set_progress_hook`
+pub const SSDV_FEC_CAP_PROGRESS_HOOK: u32 = 1 << 20;
+/// Generation of the tables in RAM by `ssdv_fec_init`, which is only done with
+/// the `ram-tables` feature
+pub const SSDV_FEC_CAP_RAM_TABLES: u32 = 1 << 21;
+/// Support for `ssdv_fec_encoder_encode_words`
+pub const SSDV_FEC_CAP_WORDS: u32 = 1 << 22;
+
+// Results of pushing a packet into a decoder
+
+/// The packet has been stored
+pub const SSDV_FEC_PUSH_STORED: c_int = 0;
+/// The packet is a duplicate of a packet already stored
+pub const SSDV_FEC_PUSH_DUPLICATE: c_int = 1;
+/// The packet has a wrong CRC
+pub const SSDV_FEC_PUSH_WRONG_CRC: c_int = 2;
+/// The packet belongs to a different image
+pub const SSDV_FEC_PUSH_OTHER_IMAGE: c_int = 3;
+/// The decoder buffer is full
+pub const SSDV_FEC_PUSH_BUFFER_FULL: c_int = 4;
+/// The packet belongs to an image with more than `SSDV_FEC_MAX_K` systematic
+/// packets
+pub const SSDV_FEC_PUSH_TOO_LARGE: c_int = 5;
+
 // Encoder error codes
 
 /// Encoder input is empty
@@ -140,6 +2501,10 @@ pub const SSDV_FEC_ENCODER_ERR_EMPTY_INPUT: c_int = -1;
 pub const SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT: c_int = -2;
 /// Non-systematic packet in encoder input
 pub const SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT: c_int = -3;
+/// Packet ID range out of bounds
+pub const SSDV_FEC_ENCODER_ERR_INVALID_RANGE: c_int = -4;
+/// Encoder not initialized
+pub const SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED: c_int = -5;
 
 // Decoder error codes
 
@@ -167,3 +2532,31 @@ pub const SSDV_FEC_DECODER_ERR_INCONSISTENT_FLAGS: c_int = -25;
 pub const SSDV_FEC_DECODER_ERR_DIMENSIONS_MISMATCH: c_int = -26;
 /// No systematic packets
 pub const SSDV_FEC_DECODER_ERR_NO_SYSTEMATIC: c_int = -27;
+
+// Packet validation error codes
+
+/// Wrong CRC-32
+pub const SSDV_FEC_PACKET_ERR_WRONG_CRC: c_int = -48;
+/// Wrong value in fixed field
+pub const SSDV_FEC_PACKET_ERR_WRONG_FIXED_FIELD: c_int = -49;
+/// EOI set on FEC packet
+pub const SSDV_FEC_PACKET_ERR_EOI_ON_FEC_PACKET: c_int = -50;
+/// FEC packet ID inconsistent with the number of systematic packets
+pub const SSDV_FEC_PACKET_ERR_WRONG_FEC_ID: c_int = -51;
+
+// Other error codes
+
+/// NULL pointer argument
+pub const SSDV_FEC_ERR_NULL_POINTER: c_int = -64;
+/// Invalid buffer length
+pub const SSDV_FEC_ERR_INVALID_LENGTH: c_int = -65;
+/// Output buffer is too short
+pub const SSDV_FEC_ERR_OUTPUT_TOO_SHORT: c_int = -66;
+/// Scratch memory is too short
+pub const SSDV_FEC_ERR_SCRATCH_TOO_SHORT: c_int = -67;
+/// Misaligned pointer or overlapping buffers
+pub const SSDV_FEC_ERR_BAD_BUFFER: c_int = -68;
+/// Self-test failed
+pub const SSDV_FEC_ERR_SELFTEST_FAILED: c_int = -69;
+/// Invalid packet format
+pub const SSDV_FEC_ERR_INVALID_FORMAT: c_int = -70;
@@ -0,0 +1,94 @@
+//! Log callback.
+//!
+//! The library reports notable events, such as dropped packets and the outcome
+//! of decoding, to a callback registered by the flight software, so that they
+//! can be integrated in its logging framework.
+
+use core::{
+    ffi::{c_char, c_int, c_void},
+    fmt::{self, Write},
+    mem, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// Log callback.
+///
+/// The callback receives one of the `SSDV_FEC_LOG_*` levels and a
+/// NUL-terminated message, which is only valid during the call.
+#[allow(non_camel_case_types)]
+pub type ssdv_fec_log_callback_t = Option<unsafe extern "C" fn(level: c_int, msg: *const c_char)>;
+
+// Callback registered with `ssdv_fec_set_log_callback`, stored as a data
+// pointer because there are no atomic function pointers. NULL if there is no
+// callback.
+static CALLBACK: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+// Messages longer than this are truncated.
+const MAX_MESSAGE_LEN: usize = 95;
+
+/// Registers a log callback.
+///
+/// The library calls `callback` to report notable events, such as packets
+/// dropped by the decoder because of a wrong CRC or because they are
+/// duplicates, and the outcome of encoder initialization and decoding. Passing
+/// `NULL` removes the callback. There is a single callback for all the encoder
+/// and decoder contexts. No messages are formatted while there is no callback.
+///
+/// The callback is called from the thread or interrupt handler that calls the
+/// library function that produces the message, so it must be safe to call from
+/// all of them. It must not call functions of this library on the same context.
+/// Messages are at most 95 characters long, and the pointer given to the
+/// callback is only valid during the call.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_set_log_callback(callback: ssdv_fec_log_callback_t) {
+    let callback = callback.map_or(ptr::null_mut(), |f| f as *mut c_void);
+    CALLBACK.store(callback, Ordering::Release);
+}
+
+// Formats a message and sends it to the log callback, if there is one.
+pub(crate) fn log(level: c_int, args: fmt::Arguments) {
+    let callback = CALLBACK.load(Ordering::Acquire);
+    if callback.is_null() {
+        return;
+    }
+    // SAFETY: the pointer was obtained from a callback in
+    // ssdv_fec_set_log_callback.
+    let callback = unsafe {
+        mem::transmute::<*mut c_void, unsafe extern "C" fn(c_int, *const c_char)>(callback)
+    };
+    let mut message = Message {
+        buffer: [0; MAX_MESSAGE_LEN + 1],
+        len: 0,
+    };
+    // Message never fails, but truncates long messages.
+    let _ = message.write_fmt(args);
+    message.buffer[message.len] = 0;
+    // SAFETY: the message is NUL-terminated.
+    unsafe { callback(level, message.buffer.as_ptr().cast()) };
+}
+
+// Fixed-size message buffer that truncates the text written to it.
+struct Message {
+    buffer: [u8; MAX_MESSAGE_LEN + 1],
+    len: usize,
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(MAX_MESSAGE_LEN - self.len);
+        self.buffer[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+// Log levels
+
+/// Debug messages, such as packets stored by a decoder
+pub const SSDV_FEC_LOG_DEBUG: c_int = 0;
+/// Informational messages, such as successful decoding
+pub const SSDV_FEC_LOG_INFO: c_int = 1;
+/// Warnings, such as packets dropped because of a wrong CRC
+pub const SSDV_FEC_LOG_WARNING: c_int = 2;
+/// Errors, such as failed decoding
+pub const SSDV_FEC_LOG_ERROR: c_int = 3;
@@ -0,0 +1,32 @@
+// Link-time check that the encoder and decoder cannot panic.
+//
+// The test builds the program in the panic-free directory for
+// thumbv7em-none-eabi. The program uses the encoder and decoder of the
+// ssdv_fec crate with the `panic-free` feature, and its panic handler calls a
+// function that is not defined anywhere, so it fails to link if any panicking
+// path, together with the panic formatting machinery, remains in the
+// Cortex-M build.
+//
+// The test builds the program, which takes a while, so it is ignored by
+// default. It is run with
+//
+//     cargo test --target x86_64-unknown-linux-gnu -- --ignored panic_free
+
+use std::{path::Path, process::Command};
+
+#[test]
+#[ignore]
+fn panic_free() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir.join("panic-free"))
+        .args(["build", "--release", "--target", "thumbv7em-none-eabi"])
+        .arg("--target-dir")
+        .arg(manifest_dir.join("target").join("panic-free"))
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "the panic-free program does not link, so some paths can panic"
+    );
+}
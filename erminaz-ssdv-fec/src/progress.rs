@@ -0,0 +1,76 @@
+//! Progress hook.
+//!
+//! Encoder initialization, FEC packet encoding and decoding can take a long
+//! time for large images. The library calls a hook registered by the flight
+//! software periodically during these computations, so that it can kick the
+//! watchdog or report progress.
+
+use core::{
+    ffi::{c_int, c_void},
+    mem, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+use ssdv_fec::{Operation, Progress};
+
+/// Progress hook.
+///
+/// The hook receives one of the `SSDV_FEC_PROGRESS_*` operations, the number
+/// of steps of the operation that have been done, and the total number of
+/// steps.
+#[allow(non_camel_case_types)]
+pub type ssdv_fec_progress_hook_t =
+    Option<unsafe extern "C" fn(operation: c_int, done: u32, total: u32)>;
+
+// Hook registered with `ssdv_fec_set_progress_hook`, stored as a data pointer
+// because there are no atomic function pointers. NULL if there is no hook.
+static HOOK: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers a progress hook.
+///
+/// The library calls `hook` each time that a bounded amount of work has been
+/// done during encoder initialization, FEC packet encoding and decoding, which
+/// is on the order of 1 ms on the Cortex-M4, so that the flight software can
+/// kick the watchdog during long computations. Passing `NULL` removes the hook.
+/// There is a single hook for all the encoder and decoder contexts.
+///
+/// The hook is called from the thread or interrupt handler that calls the
+/// library function, so it must be safe to call from all of them. It must not
+/// call functions of this library.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_set_progress_hook(hook: ssdv_fec_progress_hook_t) {
+    let hook = hook.map_or(ptr::null_mut(), |f| f as *mut c_void);
+    HOOK.store(hook, Ordering::Release);
+    ssdv_fec::set_progress_hook((!hook.is_null()).then_some(progress as fn(Progress)));
+}
+
+// Hook registered in the ssdv_fec crate, which calls the C hook.
+fn progress(progress: Progress) {
+    let hook = HOOK.load(Ordering::Acquire);
+    if hook.is_null() {
+        return;
+    }
+    // SAFETY: the pointer was obtained from a hook in
+    // ssdv_fec_set_progress_hook.
+    let hook =
+        unsafe { mem::transmute::<*mut c_void, unsafe extern "C" fn(c_int, u32, u32)>(hook) };
+    let operation = match progress.operation {
+        Operation::EncoderSetup => SSDV_FEC_PROGRESS_ENCODER_SETUP,
+        Operation::EncoderRelease => SSDV_FEC_PROGRESS_ENCODER_RELEASE,
+        Operation::Encode => SSDV_FEC_PROGRESS_ENCODE,
+        Operation::Decode => SSDV_FEC_PROGRESS_DECODE,
+    };
+    // SAFETY: the hook is a valid function according to the requirements of
+    // ssdv_fec_set_progress_hook.
+    unsafe { hook(operation, progress.done, progress.total) };
+}
+
+// Operations
+
+/// Initialization of an encoder
+pub const SSDV_FEC_PROGRESS_ENCODER_SETUP: c_int = 0;
+/// Restoration of the systematic packets when an encoder is released
+pub const SSDV_FEC_PROGRESS_ENCODER_RELEASE: c_int = 1;
+/// Encoding of a FEC packet
+pub const SSDV_FEC_PROGRESS_ENCODE: c_int = 2;
+/// Decoding of an image with missing systematic packets
+pub const SSDV_FEC_PROGRESS_DECODE: c_int = 3;
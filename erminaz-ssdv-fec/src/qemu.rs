@@ -0,0 +1,28 @@
+// Tests of the C API on the Cortex-M4 under QEMU.
+//
+// The test builds the program in the qemu directory for thumbv7em-none-eabi
+// and runs it on the Cortex-M4 of the Arm MPS2 AN386 board emulated by
+// qemu-system-arm, which must be installed. The program runs encode and decode
+// round trips through the C API of the library built for the target, and
+// reports the result through semihosting.
+//
+// The test builds the program and needs QEMU, so it is ignored by default. It
+// is run with
+//
+//     cargo test --target x86_64-unknown-linux-gnu -- --ignored qemu
+
+use std::{path::Path, process::Command};
+
+#[test]
+#[ignore]
+fn qemu() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir.join("qemu"))
+        .args(["run", "--release", "--target", "thumbv7em-none-eabi"])
+        .arg("--target-dir")
+        .arg(manifest_dir.join("target").join("qemu"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "the tests under QEMU failed");
+}
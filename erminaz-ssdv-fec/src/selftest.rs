@@ -0,0 +1,135 @@
+//! Built-in self-test.
+//!
+//! The self-test encodes a small synthetic image with each of the packet
+//! formats, decodes it from a set of packets where all but one of the
+//! systematic packets are missing, and checks that the decoded image is
+//! identical to the original. It is intended as the power-on built-in test of
+//! the library, so it only uses memory provided by the caller and takes a few
+//! milliseconds.
+
+use super::*;
+
+// Number of systematic packets of the synthetic image.
+const K: usize = 4;
+
+/// Minimum length in bytes of the scratch memory given to `ssdv_fec_selftest`
+pub const SSDV_FEC_SELFTEST_SCRATCH_LEN: usize = 3072;
+
+// The scratch memory holds the image, the received packets and the decoded
+// image.
+const _: () = assert!(SSDV_FEC_SELFTEST_SCRATCH_LEN == 3 * K * SSDV_FEC_MAX_PACKET_LEN);
+
+/// Runs the built-in self-test of the library.
+///
+/// The function encodes a small synthetic image, decodes it mostly from FEC
+/// packets, and checks that the decoded image matches the original, for each
+/// of the packet formats. All the work is done in the `scratch` memory, which
+/// must have a length `len` of at least `SSDV_FEC_SELFTEST_SCRATCH_LEN` bytes
+/// and does not need any alignment. Its contents are overwritten.
+///
+/// The function returns zero if the self-test passes,
+/// `SSDV_FEC_ERR_NULL_POINTER` if `scratch` is `NULL`,
+/// `SSDV_FEC_ERR_SCRATCH_TOO_SHORT` if `len` is too short, or
+/// `SSDV_FEC_ERR_SELFTEST_FAILED` if the self-test fails. The cause of a
+/// failure is reported to the log callback.
+///
+/// # Safety
+///
+/// The `scratch` buffer must have allocated storage for at least `len` bytes,
+/// and must not be used concurrently by other threads while this function
+/// runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_selftest(scratch: *mut c_void, len: usize) -> c_int {
+    if scratch.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if len < SSDV_FEC_SELFTEST_SCRATCH_LEN {
+        return SSDV_FEC_ERR_SCRATCH_TOO_SHORT;
+    }
+    let scratch = slice::from_raw_parts_mut(scratch.cast::<u8>(), SSDV_FEC_SELFTEST_SCRATCH_LEN);
+    for format in [
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2,
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC,
+    ] {
+        if let Err(cause) = with_format!(format, P => run::<P>(scratch)) {
+            log(
+                SSDV_FEC_LOG_ERROR,
+                format_args!("self-test failed with {:?}: {}", format, cause),
+            );
+            return SSDV_FEC_ERR_SELFTEST_FAILED;
+        }
+    }
+    log(SSDV_FEC_LOG_INFO, format_args!("self-test passed"));
+    0
+}
+
+// Runs the self-test with one packet format, returning the cause of the
+// failure if it fails.
+fn run<P: Packet>(scratch: &mut [u8]) -> Result<(), &'static str> {
+    let (image, rest) = scratch.split_at_mut(K * P::LEN);
+    let (received, rest) = rest.split_at_mut(K * P::LEN);
+    let image = as_packets::<P>(image);
+    let received = as_packets::<P>(received);
+    let output = as_packets::<P>(&mut rest[..K * P::LEN]);
+
+    synthetic_image(image);
+    let encoder = Encoder::new(image).map_err(|err| describe(encoder_error(err)))?;
+    // The last systematic packet, which carries the EOI flag, and FEC packets
+    // for the rest, so that K - 1 packets need to be recovered.
+    encoder.encode(K as u16 - 1, &mut received[0]);
+    encoder
+        .try_encode_range(K as u16 + 1, &mut received[1..])
+        .map_err(|err| describe(encoder_error(err)))?;
+    let image = encoder.release(false);
+
+    let (decoded, stats) = Decoder::decode_with_stats(received, output, &DecoderOptions::default())
+        .map_err(|err| describe(decoder_error(err)))?;
+    if stats.packets_recovered != K - 1 {
+        return Err("wrong number of recovered packets");
+    }
+    synthetic_image(image);
+    if decoded.len() != K
+        || decoded
+            .iter()
+            .zip(image.iter())
+            .any(|(a, b)| a.as_bytes() != b.as_bytes())
+    {
+        return Err("decoded image does not match");
+    }
+    Ok(())
+}
+
+// Views a byte slice whose length is a multiple of the packet size as packets.
+fn as_packets<P: Packet>(bytes: &mut [u8]) -> &mut [P] {
+    // SAFETY: the packet types are byte arrays, so they have no alignment
+    // requirements and any contents are valid.
+    unsafe { slice::from_raw_parts_mut(bytes.as_mut_ptr().cast::<P>(), bytes.len() / P::LEN) }
+}
+
+// Writes the packets of the synthetic image. The data is pseudo-random, so
+// that all the FEC symbols are exercised.
+fn synthetic_image<P: Packet>(packets: &mut [P]) {
+    let k = packets.len();
+    let mut state = 0x2545_f491_u32;
+    for (packet_id, packet) in packets.iter_mut().enumerate() {
+        *packet = P::zeroed();
+        if !packet.fixed_fields_ok() {
+            // sync byte and packet type of the no-FEC format
+            packet.as_bytes_mut()[..2].copy_from_slice(&[0x55, 0x67]);
+        }
+        packet.set_image_id(0xa5);
+        packet.set_packet_id(packet_id as u16);
+        packet.set_width(2);
+        packet.set_height(1);
+        packet.set_flags(0x1a);
+        packet.set_eoi(packet_id == k - 1);
+        for byte in packet.data_as_mut() {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        packet.set_crc32(packet.compute_crc32());
+    }
+}
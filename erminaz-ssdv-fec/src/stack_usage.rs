@@ -0,0 +1,240 @@
+// Stack usage of the C API on the target.
+//
+// The test builds the library for thumbv7em-none-eabi with the assembly output
+// enabled and obtains the size of the stack frame of each function from the ARM
+// EHABI unwinding directives (`.save`, `.vsave` and `.pad`). This gives the
+// same information as the `.stack_sizes` section emitted with
+// `-Z emit-stack-sizes`, which is not available in stable Rust. The stack usage
+// of each exported function is the largest sum of the stack frames along its
+// call chains. Indirect calls, which are calls through trait objects and
+// function pointers, are assumed to call any function of the library whose
+// address is taken, except for those listed in INDIRECT_TARGETS. Functions
+// outside the library, such as those of `core` and `compiler-builtins`, the log
+// callback, the progress hook and the fault handler, are not counted. The test
+// fails if any call chain is recursive, including a function that calls
+// itself, since then the stack usage would not be bounded.
+//
+// The test builds the library, which takes a while, so it is ignored by
+// default. It is run with
+//
+//     cargo test --target x86_64-unknown-linux-gnu -- --ignored stack_usage
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    process::Command,
+    string::{String, ToString},
+    time::SystemTime,
+    vec::Vec,
+};
+
+// Maximum stack usage of any function of the C API, in bytes. This is the
+// bound documented in the README.
+const MAX_STACK_USAGE: usize = 1024;
+
+// Functions whose indirect calls are known to call only some functions, given
+// as parts of their mangled names. The progress meter of the ssdv_fec crate
+// calls the hook registered by ssdv_fec_set_progress_hook, which calls the C
+// hook. Otherwise, the meter would be assumed to call the encoder, which calls
+// the meter.
+const INDIRECT_TARGETS: [(&str, &[&str]); 2] = [
+    (
+        "8ssdv_fec8progress9call_hook",
+        &["16erminaz_ssdv_fec8progress8progress"],
+    ),
+    ("16erminaz_ssdv_fec8progress8progress", &[]),
+];
+
+#[derive(Debug, Default)]
+struct Function {
+    // Size of the stack frame in bytes.
+    frame: usize,
+    // Functions called directly.
+    calls: HashSet<String>,
+    // Whether the function makes indirect calls.
+    indirect: bool,
+}
+
+#[derive(Debug, Default)]
+struct Program {
+    functions: HashMap<String, Function>,
+    address_taken: HashSet<String>,
+}
+
+impl Program {
+    fn parse(&mut self, asm: &str) {
+        let mut current = None;
+        let mut referenced = HashSet::new();
+        for line in asm.lines() {
+            if !line.starts_with(|c: char| c.is_whitespace() || c == '.') {
+                if let Some(label) = line.strip_suffix(':') {
+                    self.functions
+                        .insert(label.to_string(), Function::default());
+                    current = Some(label.to_string());
+                    continue;
+                }
+            }
+            let mut fields = line.split_whitespace();
+            let (Some(op), operands) = (fields.next(), fields.collect::<Vec<_>>().join(" ")) else {
+                continue;
+            };
+            if op == ".long" {
+                referenced.insert(operands);
+                continue;
+            }
+            let Some(function) = current.as_ref().and_then(|f| self.functions.get_mut(f)) else {
+                continue;
+            };
+            match op {
+                ".save" => function.frame += 4 * operands.split(',').count(),
+                ".vsave" => function.frame += 8 * operands.split(',').count(),
+                ".pad" => {
+                    function.frame += operands.trim_start_matches('#').parse::<usize>().unwrap()
+                }
+                _ if is_branch(op) => {
+                    if is_register(&operands) {
+                        function.indirect |= operands != "lr";
+                    } else if !operands.starts_with(".L") {
+                        function.calls.insert(operands);
+                    }
+                }
+                _ => {
+                    if let Some((_, symbol)) = operands.split_once(":lower16:") {
+                        referenced.insert(symbol.to_string());
+                    }
+                }
+            }
+        }
+        self.address_taken.extend(
+            referenced
+                .into_iter()
+                .filter(|symbol| self.functions.contains_key(symbol)),
+        );
+    }
+
+    // Returns the stack usage of a function, including the functions that it
+    // calls.
+    fn stack_usage(
+        &self,
+        name: &str,
+        usage: &mut HashMap<String, usize>,
+        path: &mut Vec<String>,
+    ) -> usize {
+        if let Some(&bytes) = usage.get(name) {
+            return bytes;
+        }
+        // functions outside the library
+        let Some(function) = self.functions.get(name) else {
+            return 0;
+        };
+        assert!(
+            !path.iter().any(|f| f == name),
+            "recursive call chain: {path:?} -> {name}"
+        );
+        path.push(name.to_string());
+        let mut callees = function.calls.iter().collect::<Vec<_>>();
+        if function.indirect {
+            match INDIRECT_TARGETS
+                .iter()
+                .find(|(caller, _)| name.contains(caller))
+            {
+                Some((_, targets)) => callees.extend(
+                    self.functions
+                        .keys()
+                        .filter(|f| targets.iter().any(|target| f.contains(target))),
+                ),
+                None => callees.extend(&self.address_taken),
+            }
+        }
+        let max_callee = callees
+            .into_iter()
+            .map(|callee| self.stack_usage(callee, usage, path))
+            .max()
+            .unwrap_or(0);
+        path.pop();
+        let bytes = function.frame + max_callee;
+        usage.insert(name.to_string(), bytes);
+        bytes
+    }
+}
+
+fn is_branch(op: &str) -> bool {
+    const CONDITIONS: [&str; 15] = [
+        "", "eq", "ne", "cs", "hs", "cc", "lo", "mi", "pl", "hi", "ls", "ge", "lt", "gt", "le",
+    ];
+    let op = op.trim_end_matches(".w").trim_end_matches(".n");
+    ["bl", "blx", "bx", "b"].iter().any(|branch| {
+        op.strip_prefix(branch)
+            .is_some_and(|cond| CONDITIONS.contains(&cond))
+    })
+}
+
+fn is_register(operand: &str) -> bool {
+    matches!(operand, "lr" | "ip" | "sp" | "pc")
+        || operand
+            .strip_prefix('r')
+            .is_some_and(|n| n.parse::<u8>().is_ok())
+}
+
+// Builds the library for the target with the assembly output and returns the
+// assembly of each crate.
+fn build_assembly() -> Vec<String> {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let target_dir = Path::new(manifest_dir).join("target").join("stack-usage");
+    let status = Command::new(env!("CARGO"))
+        .current_dir(manifest_dir)
+        .args(["build", "--release", "--target", "thumbv7em-none-eabi"])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .env(
+            "CARGO_TARGET_THUMBV7EM_NONE_EABI_RUSTFLAGS",
+            "-C target-cpu=cortex-m4 --emit=asm",
+        )
+        .status()
+        .unwrap();
+    assert!(status.success());
+    // Only the most recent assembly of each crate is used, since older builds
+    // leave their files behind.
+    let mut latest: HashMap<String, (SystemTime, String)> = HashMap::new();
+    for entry in fs::read_dir(target_dir.join("thumbv7em-none-eabi/release/deps")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "s") {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy();
+        let krate = name.split('-').next().unwrap().to_string();
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        if latest.get(&krate).is_none_or(|(time, _)| *time < modified) {
+            latest.insert(krate, (modified, fs::read_to_string(&path).unwrap()));
+        }
+    }
+    latest.into_values().map(|(_, asm)| asm).collect()
+}
+
+#[test]
+#[ignore]
+fn stack_usage() {
+    let mut program = Program::default();
+    for asm in build_assembly() {
+        program.parse(&asm);
+    }
+    let mut usage = HashMap::new();
+    let mut exported = program
+        .functions
+        .keys()
+        .filter(|name| name.starts_with("ssdv_fec"))
+        .map(|name| (program.stack_usage(name, &mut usage, &mut Vec::new()), name))
+        .collect::<Vec<_>>();
+    assert!(!exported.is_empty());
+    exported.sort();
+    for (bytes, name) in &exported {
+        println!("{bytes:5} {name}");
+    }
+    for (bytes, name) in &exported {
+        assert!(
+            *bytes <= MAX_STACK_USAGE,
+            "{name} uses {bytes} bytes of stack"
+        );
+    }
+}
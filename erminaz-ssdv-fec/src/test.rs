@@ -0,0 +1,1530 @@
+// Host tests of the C API.
+//
+// These tests call the exported functions through their C signatures, in the
+// same way as the flight software, so that regressions in the behaviour of the
+// C API are caught without running on the target. They are run on the host
+// with
+//
+//     cargo test --target x86_64-unknown-linux-gnu
+
+use super::*;
+use crate::{formats::*, log::ssdv_fec_set_log_callback, progress::*, selftest::*, v2::*};
+use core::{
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use ssdv_fec::FountainScheduler;
+use std::{ffi::CStr, vec, vec::Vec};
+
+static IMG_230_SSDV: &[u8; 14170] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+
+const LJ2: ssdv_fec_format_t = ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2;
+const NO_FEC: ssdv_fec_format_t = ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC;
+
+fn img_230(format: ssdv_fec_format_t) -> Vec<u8> {
+    match format {
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2 => IMG_230_SSDV.to_vec(),
+        ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC => IMG_230_SSDV
+            .chunks_exact(SSDV_PACKET_LEN)
+            .flat_map(|chunk| {
+                let lj2 = SSDVPacket(chunk.try_into().unwrap());
+                let mut packet = SSDVNoFecPacket([0; SSDV_NO_FEC_PACKET_LEN]);
+                packet.0[0] = 0x55;
+                packet.0[1] = 0x67;
+                packet.0[2..6].copy_from_slice(&[0x00, 0x0e, 0x72, 0x40]);
+                packet.0[6..12].copy_from_slice(&lj2.0[..6]);
+                packet.data_as_mut()[..SSDV_DATA_LEN].copy_from_slice(lj2.data());
+                packet.set_crc32(packet.compute_crc32());
+                packet.0
+            })
+            .collect(),
+    }
+}
+
+fn packet_len(format: ssdv_fec_format_t) -> usize {
+    unsafe { ssdv_fec_packet_len(format) as usize }
+}
+
+fn new_encoder() -> ssdv_fec_encoder_t {
+    // The flight software calls ssdv_fec_init at startup.
+    ssdv_fec_init();
+    // Contexts are usually zero-initialized static variables in C.
+    unsafe { MaybeUninit::zeroed().assume_init() }
+}
+
+fn new_decoder() -> ssdv_fec_decoder_t {
+    ssdv_fec_init();
+    unsafe { MaybeUninit::zeroed().assume_init() }
+}
+
+fn new_scheduler() -> ssdv_fec_scheduler_t {
+    unsafe { MaybeUninit::zeroed().assume_init() }
+}
+
+// Returns the FEC packets with IDs k..2k, replacing the first one by the last
+// systematic packet, which is enough to decode the image.
+fn fec_packets(encoder: &ssdv_fec_encoder_t, image: &[u8], format: ssdv_fec_format_t) -> Vec<u8> {
+    let len = packet_len(format);
+    let k = image.len() / len;
+    let mut packets = vec![0u8; k * len];
+    let ret = unsafe {
+        ssdv_fec_encoder_encode_range(encoder, k as u16, k as c_int, packets.as_mut_ptr().cast())
+    };
+    assert_eq!(ret, k as c_int);
+    packets[..len].copy_from_slice(&image[(k - 1) * len..]);
+    packets
+}
+
+#[test]
+fn encode_decode() {
+    for format in [LJ2, NO_FEC] {
+        let image = img_230(format);
+        let len = packet_len(format);
+        let k = image.len() / len;
+        let mut encoder_buffer = image.clone();
+        let mut encoder = new_encoder();
+        unsafe {
+            let ret = ssdv_fec_encoder_init(
+                &mut encoder,
+                format,
+                encoder_buffer.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            assert_eq!(ret, 0);
+            assert_eq!(ssdv_fec_encoder_num_systematic(&encoder), k as c_int);
+            assert_eq!(ssdv_fec_encoder_image_id(&encoder), 230);
+        }
+        let mut input = fec_packets(&encoder, &image, format);
+        let mut decoder = new_decoder();
+        let mut output = vec![0u8; image.len()];
+        unsafe {
+            ssdv_fec_decoder_init(&mut decoder, format, ptr::null_mut(), 0, false);
+            let ret = ssdv_fec_decoder_decode_packets(
+                &decoder,
+                input.as_mut_ptr().cast(),
+                k as c_int,
+                output.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            assert_eq!(ret, k as c_int);
+            assert_eq!(ssdv_fec_encoder_release(&mut encoder, true), 0);
+        }
+        assert_eq!(output, image);
+        assert_eq!(encoder_buffer, image);
+    }
+}
+
+#[test]
+fn push_decode_pushed() {
+    for format in [LJ2, NO_FEC] {
+        let image = img_230(format);
+        let len = packet_len(format);
+        let k = image.len() / len;
+        let mut encoder_buffer = image.clone();
+        let mut encoder = new_encoder();
+        let packets = unsafe {
+            ssdv_fec_encoder_init(
+                &mut encoder,
+                format,
+                encoder_buffer.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            fec_packets(&encoder, &image, format)
+        };
+        let mut buffer = vec![0u8; image.len()];
+        let mut output = vec![0u8; image.len()];
+        let mut decoder = new_decoder();
+        unsafe {
+            ssdv_fec_decoder_setup(
+                &mut decoder,
+                format,
+                buffer.as_mut_ptr().cast(),
+                k as c_int,
+                output.as_mut_ptr().cast(),
+                k as c_int,
+                false,
+            );
+            let status = ssdv_fec_decoder_status(&decoder);
+            assert_eq!(status.num_packets, 0);
+            assert_eq!(status.image_id, -1);
+            assert!(!status.ready);
+            assert_eq!(
+                ssdv_fec_decoder_decode_pushed(&mut decoder, ptr::null_mut(), 0),
+                SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC
+            );
+            for (j, packet) in packets.chunks_exact(len).enumerate() {
+                assert_eq!(
+                    ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+                    SSDV_FEC_PUSH_STORED
+                );
+                assert_eq!(ssdv_fec_decoder_status(&decoder).ready, j == k - 1);
+                // pushing the same packet again is detected
+                assert_eq!(
+                    ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+                    SSDV_FEC_PUSH_DUPLICATE
+                );
+            }
+            let status = ssdv_fec_decoder_status(&decoder);
+            assert_eq!(status.num_packets, k as c_int);
+            assert_eq!(status.num_systematic, k as c_int);
+            assert_eq!(status.image_id, 230);
+            // the buffer is full
+            let mut extra = vec![0u8; len];
+            ssdv_fec_encoder_encode_packet(&encoder, 3 * k as c_int, extra.as_mut_ptr().cast());
+            assert_eq!(
+                ssdv_fec_decoder_push(&mut decoder, extra.as_ptr().cast()),
+                SSDV_FEC_PUSH_BUFFER_FULL
+            );
+            assert_eq!(
+                ssdv_fec_decoder_decode_pushed(&mut decoder, ptr::null_mut(), 0),
+                k as c_int
+            );
+        }
+        assert_eq!(output, image);
+        let mut stats = MaybeUninit::<ssdv_fec_stats_t>::uninit();
+        let stats = unsafe {
+            ssdv_fec_decoder_stats(&decoder, stats.as_mut_ptr());
+            stats.assume_init()
+        };
+        assert_eq!(stats.wrong_crc, 0);
+        assert_eq!(stats.duplicates, k as u32);
+        assert_eq!(stats.fec_packets_used, k as u32 - 1);
+        assert_eq!(stats.packets_recovered, k as u32 - 1);
+        // the decoder is reset after decoding
+        assert_eq!(unsafe { ssdv_fec_decoder_status(&decoder) }.num_packets, 0);
+    }
+}
+
+#[test]
+fn per_format_symbols() {
+    let image = img_230(NO_FEC);
+    let k = image.len() / SSDV_NO_FEC_PACKET_LEN;
+    let mut encoder_buffer = image.clone();
+    let mut encoder = new_encoder();
+    let mut buffer = vec![0u8; image.len()];
+    let mut output = vec![0u8; image.len()];
+    let mut decoder = new_decoder();
+    unsafe {
+        let ret = ssdv_fec_nofec_encoder_init(
+            &mut encoder,
+            encoder_buffer.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(ret, 0);
+        let packets = fec_packets(&encoder, &image, NO_FEC);
+        ssdv_fec_nofec_decoder_setup(
+            &mut decoder,
+            buffer.as_mut_ptr().cast(),
+            k as c_int,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+            false,
+        );
+        for packet in packets.chunks_exact(SSDV_NO_FEC_PACKET_LEN) {
+            assert!(ssdv_fec_nofec_packet_crc_ok(packet.as_ptr().cast()));
+            assert_eq!(ssdv_fec_nofec_packet_validate(packet.as_ptr().cast()), 0);
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast());
+        }
+        let ret = ssdv_fec_decoder_decode_pushed(&mut decoder, ptr::null_mut(), 0);
+        assert_eq!(ret, k as c_int);
+    }
+    assert_eq!(output, image);
+
+    let image = img_230(LJ2);
+    let mut info = MaybeUninit::<ssdv_fec_packet_info_t>::uninit();
+    let info = unsafe {
+        assert_eq!(ssdv_fec_lj2_packet_validate(image.as_ptr().cast()), 0);
+        // a no-FEC packet is not valid as an LJ2 packet
+        assert!(!ssdv_fec_lj2_packet_crc_ok(output.as_ptr().cast()));
+        ssdv_fec_lj2_packet_info(image.as_ptr().cast(), info.as_mut_ptr());
+        info.assume_init()
+    };
+    assert_eq!(info.image_id, 230);
+    assert_eq!(info.packet_id, 0);
+}
+
+#[test]
+fn encode_prefixed() {
+    for format in [LJ2, NO_FEC] {
+        let image = img_230(format);
+        let len = packet_len(format);
+        let k = image.len() / len;
+        let mut encoder_buffer = image.clone();
+        let mut encoder = new_encoder();
+        let mut output = [0u8; SSDV_FEC_LENGTH_PREFIX_LEN + SSDV_FEC_MAX_PACKET_LEN];
+        let mut packet = vec![0u8; len];
+        unsafe {
+            ssdv_fec_encoder_init(
+                &mut encoder,
+                format,
+                encoder_buffer.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            let ret = ssdv_fec_encoder_encode_prefixed(
+                &encoder,
+                k as u16,
+                output.as_mut_ptr().cast(),
+                (len + 1) as c_int,
+            );
+            assert_eq!(ret, SSDV_FEC_ERR_OUTPUT_TOO_SHORT);
+            let ret = ssdv_fec_encoder_encode_prefixed(
+                &encoder,
+                u16::MAX,
+                output.as_mut_ptr().cast(),
+                output.len() as c_int,
+            );
+            assert_eq!(ret, SSDV_FEC_ENCODER_ERR_INVALID_RANGE);
+            let ret = ssdv_fec_encoder_encode_prefixed(
+                &encoder,
+                k as u16,
+                output.as_mut_ptr().cast(),
+                (len + 2) as c_int,
+            );
+            assert_eq!(ret, (len + 2) as c_int);
+            ssdv_fec_encoder_encode_packet(&encoder, k as c_int, packet.as_mut_ptr().cast());
+        }
+        assert_eq!(output[..2], (len as u16).to_be_bytes());
+        assert_eq!(output[2..len + 2], packet);
+    }
+}
+
+#[test]
+fn encode_chunks() {
+    for format in [LJ2, NO_FEC] {
+        let image = img_230(format);
+        let len = packet_len(format);
+        let k = image.len() / len;
+        let mut encoder_buffer = image.clone();
+        let mut encoder = new_encoder();
+        let mut chunks: ssdv_fec_chunks_t = unsafe { MaybeUninit::zeroed().assume_init() };
+        let mut packet = vec![0u8; len];
+        unsafe {
+            let ret = ssdv_fec_encoder_encode_chunks(&encoder, 0, &mut chunks);
+            assert_eq!(ret, SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED);
+            ssdv_fec_encoder_init(
+                &mut encoder,
+                format,
+                encoder_buffer.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            let ret = ssdv_fec_encoder_encode_chunks(&encoder, u16::MAX, &mut chunks);
+            assert_eq!(ret, SSDV_FEC_ENCODER_ERR_INVALID_RANGE);
+            for packet_id in [k - 1, k + 3] {
+                assert_eq!(
+                    ssdv_fec_encoder_encode_chunks(&encoder, packet_id as u16, &mut chunks),
+                    0
+                );
+                assert_eq!(ssdv_fec_chunks_remaining(&chunks), len as c_int);
+                let mut chunk = [0u8; 48];
+                let mut output = Vec::new();
+                loop {
+                    let ret = ssdv_fec_chunks_next(&mut chunks, chunk.as_mut_ptr().cast(), 48);
+                    assert!(ret >= 0);
+                    if ret == 0 {
+                        break;
+                    }
+                    output.extend_from_slice(&chunk[..ret as usize]);
+                }
+                assert_eq!(ssdv_fec_chunks_remaining(&chunks), 0);
+                assert_eq!(
+                    ssdv_fec_chunks_next(&mut chunks, chunk.as_mut_ptr().cast(), -1),
+                    SSDV_FEC_ERR_INVALID_LENGTH
+                );
+                ssdv_fec_encoder_encode_packet(
+                    &encoder,
+                    packet_id as c_int,
+                    packet.as_mut_ptr().cast(),
+                );
+                assert_eq!(output, packet);
+            }
+        }
+    }
+}
+
+#[test]
+fn encode_words() {
+    for format in [LJ2, NO_FEC] {
+        let image = img_230(format);
+        let len = packet_len(format);
+        let k = image.len() / len;
+        let mut encoder_buffer = image.clone();
+        let mut encoder = new_encoder();
+        let mut words: ssdv_fec_words_t = unsafe { MaybeUninit::zeroed().assume_init() };
+        let mut packet = vec![0u8; len];
+        unsafe {
+            let ret = ssdv_fec_encoder_encode_words(&encoder, 0, &mut words);
+            assert_eq!(ret, SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED);
+            ssdv_fec_encoder_init(
+                &mut encoder,
+                format,
+                encoder_buffer.as_mut_ptr().cast(),
+                k as c_int,
+            );
+            let ret = ssdv_fec_encoder_encode_words(&encoder, u16::MAX, &mut words);
+            assert_eq!(ret, SSDV_FEC_ENCODER_ERR_INVALID_RANGE);
+            for packet_id in [k - 1, k + 3] {
+                assert_eq!(
+                    ssdv_fec_encoder_encode_words(&encoder, packet_id as u16, &mut words),
+                    0
+                );
+                assert_eq!(ssdv_fec_words_remaining(&words), len as c_int);
+                let mut word = [0u8; 2];
+                let mut output = Vec::new();
+                while ssdv_fec_words_next(&mut words, word.as_mut_ptr().cast()) == 2 {
+                    output.extend_from_slice(&word);
+                }
+                assert_eq!(ssdv_fec_words_remaining(&words), 0);
+                assert_eq!(ssdv_fec_words_next(&mut words, word.as_mut_ptr().cast()), 0);
+                ssdv_fec_encoder_encode_packet(
+                    &encoder,
+                    packet_id as c_int,
+                    packet.as_mut_ptr().cast(),
+                );
+                assert_eq!(output, packet);
+            }
+        }
+    }
+}
+
+#[test]
+fn push_rejected_packets() {
+    let image = img_230(LJ2);
+    let mut buffer = vec![0u8; image.len()];
+    let mut decoder = new_decoder();
+    unsafe {
+        ssdv_fec_decoder_init(
+            &mut decoder,
+            LJ2,
+            buffer.as_mut_ptr().cast(),
+            (buffer.len() / SSDV_PACKET_LEN) as c_int,
+            false,
+        );
+        let mut packet = image[..SSDV_PACKET_LEN].to_vec();
+        packet[20] ^= 1;
+        assert!(!ssdv_fec_packet_crc_ok(LJ2, packet.as_ptr().cast()));
+        assert_eq!(
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+            SSDV_FEC_PUSH_WRONG_CRC
+        );
+        packet[20] ^= 1;
+        assert_eq!(
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+            SSDV_FEC_PUSH_STORED
+        );
+        // image ID is the first byte of the LJ2 format
+        packet[0] = 231;
+        let crc = ssdv_fec_crc32(packet.as_ptr().cast(), (SSDV_PACKET_LEN - 4) as c_int);
+        packet[SSDV_PACKET_LEN - 4..].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(ssdv_fec_packet_validate(LJ2, packet.as_ptr().cast()), 0);
+        assert_eq!(
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+            SSDV_FEC_PUSH_OTHER_IMAGE
+        );
+        ssdv_fec_decoder_reset(&mut decoder);
+        assert_eq!(ssdv_fec_decoder_status(&decoder).num_packets, 0);
+        let mut stats = MaybeUninit::<ssdv_fec_stats_t>::uninit();
+        ssdv_fec_decoder_stats(&decoder, stats.as_mut_ptr());
+        let stats = stats.assume_init();
+        // the counters are kept after a reset
+        assert_eq!(stats.wrong_crc, 1);
+        assert_eq!(stats.duplicates, 0);
+    }
+}
+
+#[test]
+fn encoder_errors() {
+    let mut image = img_230(LJ2);
+    let mut encoder = new_encoder();
+    unsafe {
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), 0),
+            SSDV_FEC_ENCODER_ERR_EMPTY_INPUT
+        );
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, ptr::null_mut(), 0),
+            SSDV_FEC_ENCODER_ERR_EMPTY_INPUT
+        );
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), -1),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, ptr::null_mut(), 1),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_encoder_init(ptr::null_mut(), LJ2, image.as_mut_ptr().cast(), 1),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_encoder_release(&mut encoder, false),
+            SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED
+        );
+        let k = image.len() / SSDV_PACKET_LEN;
+        let mut fec = vec![0u8; SSDV_PACKET_LEN];
+        assert_eq!(
+            ssdv_fec_encoder_encode_packet(&encoder, k as c_int, fec.as_mut_ptr().cast()),
+            SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_range(&encoder, 0, 1, fec.as_mut_ptr().cast()),
+            SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_range_strided(
+                &encoder,
+                0,
+                1,
+                fec.as_mut_ptr().cast(),
+                SSDV_PACKET_LEN as c_int
+            ),
+            SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED
+        );
+        assert_eq!(fec, [0; SSDV_PACKET_LEN]);
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), k as c_int),
+            0
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_packet(&encoder, k as c_int, fec.as_mut_ptr().cast()),
+            0
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_range(&encoder, u16::MAX, 1, fec.as_mut_ptr().cast()),
+            SSDV_FEC_ENCODER_ERR_INVALID_RANGE
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_range(&encoder, 0, -1, fec.as_mut_ptr().cast()),
+            SSDV_FEC_ENCODER_ERR_INVALID_RANGE
+        );
+        assert_eq!(ssdv_fec_encoder_release(&mut encoder, true), 0);
+        assert_eq!(
+            ssdv_fec_encoder_release(&mut encoder, true),
+            SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED
+        );
+        // a FEC packet in the input
+        image[..SSDV_PACKET_LEN].copy_from_slice(&fec);
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), k as c_int),
+            SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT
+        );
+    }
+}
+
+#[test]
+fn encoder_status() {
+    let mut image = img_230(NO_FEC);
+    let k = image.len() / SSDV_NO_FEC_PACKET_LEN;
+    let mut encoder = new_encoder();
+    unsafe {
+        assert!(!ssdv_fec_encoder_is_ready(&encoder));
+        let status = ssdv_fec_encoder_status(&encoder);
+        assert!(!status.ready);
+        assert!(status.buffer.is_null());
+        assert_eq!(status.num_systematic, -1);
+        assert_eq!(status.image_id, -1);
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, NO_FEC, image.as_mut_ptr().cast(), k as c_int),
+            0
+        );
+        assert!(ssdv_fec_encoder_is_ready(&encoder));
+        let status = ssdv_fec_encoder_status(&encoder);
+        assert!(status.ready);
+        assert_eq!(status.buffer, image.as_ptr().cast());
+        assert_eq!(status.num_systematic, k as c_int);
+        assert_eq!(status.image_id, ssdv_fec_encoder_image_id(&encoder));
+        assert_eq!(ssdv_fec_encoder_release(&mut encoder, false), 0);
+        assert!(!ssdv_fec_encoder_is_ready(&encoder));
+        assert!(ssdv_fec_encoder_status(&encoder).buffer.is_null());
+        // the getters do not use the storage of a released encoder
+        assert_eq!(ssdv_fec_encoder_num_systematic(&encoder), -1);
+        assert_eq!(ssdv_fec_encoder_image_id(&encoder), -1);
+        assert_eq!(ssdv_fec_encoder_width(&encoder), -1);
+        assert_eq!(ssdv_fec_encoder_height(&encoder), -1);
+        assert_eq!(ssdv_fec_encoder_flags(&encoder), -1);
+    }
+}
+
+#[test]
+fn decoder_errors() {
+    let image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut decoder = new_decoder();
+    let mut output = vec![0u8; image.len()];
+    unsafe {
+        ssdv_fec_decoder_init(&mut decoder, LJ2, ptr::null_mut(), 0, false);
+        let mut input = image[SSDV_PACKET_LEN..].to_vec();
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(
+                &decoder,
+                input.as_mut_ptr().cast(),
+                (k - 1) as c_int,
+                output.as_mut_ptr().cast(),
+                k as c_int,
+            ),
+            SSDV_FEC_DECODER_ERR_NOT_ENOUGH_INPUT
+        );
+        let mut input = image.clone();
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(
+                &decoder,
+                input.as_mut_ptr().cast(),
+                k as c_int,
+                output.as_mut_ptr().cast(),
+                (k - 1) as c_int,
+            ),
+            SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT
+        );
+        // the counts and pointers are checked before the buffers are used
+        let base = input.as_mut_ptr().cast::<c_char>();
+        let out = output.as_mut_ptr().cast::<c_char>();
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, base, -1, out, k as c_int),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, base, k as c_int, out, c_int::MIN),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, ptr::null_mut(), k as c_int, out, 0),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, base, k as c_int, ptr::null_mut(), 1),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(ptr::null(), base, k as c_int, out, k as c_int),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, ptr::null_mut(), 0, ptr::null_mut(), 0),
+            SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC
+        );
+        let stride = SSDV_PACKET_LEN as c_int;
+        assert_eq!(
+            ssdv_fec_decoder_decode_strided(&decoder, base, k as c_int, stride, out, -1),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_strided(&decoder, base, -1, stride, out, k as c_int),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_strided(&decoder, ptr::null(), 1, stride, out, k as c_int),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_strided(&decoder, base, 1, stride, ptr::null_mut(), 1),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(output, vec![0u8; image.len()]);
+    }
+}
+
+#[test]
+fn decode_with_scratch() {
+    let image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut encoder_buffer = image.clone();
+    let mut encoder = new_encoder();
+    let packets = unsafe {
+        ssdv_fec_encoder_init(
+            &mut encoder,
+            LJ2,
+            encoder_buffer.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        fec_packets(&encoder, &image, LJ2)
+    };
+    let mut decoder = new_decoder();
+    let mut scratch = vec![0u8; image.len() - 1];
+    let mut input = packets.clone();
+    let mut output = vec![0u8; image.len()];
+    unsafe {
+        ssdv_fec_decoder_init(&mut decoder, LJ2, ptr::null_mut(), 0, false);
+        ssdv_fec_decoder_set_scratch(&mut decoder, scratch.as_mut_ptr().cast(), scratch.len());
+        let ret = ssdv_fec_decoder_decode_packets(
+            &decoder,
+            input.as_mut_ptr().cast(),
+            k as c_int,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(ret, SSDV_FEC_ERR_SCRATCH_TOO_SHORT);
+        scratch.push(0);
+        ssdv_fec_decoder_set_scratch(&mut decoder, scratch.as_mut_ptr().cast(), scratch.len());
+        let ret = ssdv_fec_decoder_decode_packets(
+            &decoder,
+            input.as_mut_ptr().cast(),
+            k as c_int,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(ret, k as c_int);
+    }
+    assert_eq!(output, image);
+    // the input is not modified
+    assert_eq!(input, packets);
+}
+
+#[test]
+fn strided() {
+    const STRIDE: usize = SSDV_PACKET_LEN + 6;
+    let image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut encoder_buffer = image.clone();
+    let mut encoder = new_encoder();
+    let mut frames = vec![0xaau8; k * STRIDE];
+    unsafe {
+        ssdv_fec_encoder_init(
+            &mut encoder,
+            LJ2,
+            encoder_buffer.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_range_strided(
+                &encoder,
+                k as u16,
+                k as c_int,
+                frames.as_mut_ptr().add(6).cast(),
+                (SSDV_PACKET_LEN - 1) as c_int,
+            ),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            // the last systematic packet and k - 1 FEC packets
+            ssdv_fec_encoder_encode_range_strided(
+                &encoder,
+                (k - 1) as u16,
+                k as c_int,
+                frames.as_mut_ptr().add(6).cast(),
+                STRIDE as c_int,
+            ),
+            k as c_int
+        );
+    }
+    // the frame headers are not overwritten
+    assert!(frames.chunks_exact(STRIDE).all(|f| f[..6] == [0xaa; 6]));
+
+    let mut decoder = new_decoder();
+    let mut scratch = vec![0u8; image.len()];
+    let mut output = vec![0u8; image.len()];
+    unsafe {
+        ssdv_fec_decoder_init(&mut decoder, LJ2, ptr::null_mut(), 0, false);
+        let ret = ssdv_fec_decoder_decode_strided(
+            &decoder,
+            frames.as_ptr().add(6).cast(),
+            k as c_int,
+            STRIDE as c_int,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(ret, SSDV_FEC_ERR_SCRATCH_TOO_SHORT);
+        ssdv_fec_decoder_set_scratch(&mut decoder, scratch.as_mut_ptr().cast(), scratch.len());
+        let ret = ssdv_fec_decoder_decode_strided(
+            &decoder,
+            frames.as_ptr().add(6).cast(),
+            k as c_int,
+            STRIDE as c_int,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+        );
+        assert_eq!(ret, k as c_int);
+    }
+    assert_eq!(output, image);
+
+    let mut buffer = vec![0u8; image.len()];
+    unsafe {
+        ssdv_fec_decoder_init(
+            &mut decoder,
+            LJ2,
+            buffer.as_mut_ptr().cast(),
+            k as c_int,
+            false,
+        );
+        let ret = ssdv_fec_decoder_push_strided(
+            &mut decoder,
+            frames.as_ptr().add(6).cast(),
+            k as c_int,
+            STRIDE as c_int,
+        );
+        assert_eq!(ret, k as c_int);
+        assert!(ssdv_fec_decoder_status(&decoder).ready);
+    }
+}
+
+#[test]
+fn decode_partial() {
+    let image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut buffer = vec![0u8; image.len()];
+    let mut decoder = new_decoder();
+    let mut output = vec![0u8; image.len()];
+    let mut valid = vec![0xffu8; k.div_ceil(8)];
+    unsafe {
+        ssdv_fec_decoder_init(
+            &mut decoder,
+            LJ2,
+            buffer.as_mut_ptr().cast(),
+            k as c_int,
+            false,
+        );
+        for id in [0, 2, 7, 64] {
+            let packet = &image[id * SSDV_PACKET_LEN..(id + 1) * SSDV_PACKET_LEN];
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast());
+        }
+        let ret = ssdv_fec_decoder_decode_partial(
+            &decoder,
+            output.as_mut_ptr().cast(),
+            k as c_int,
+            valid.as_mut_ptr(),
+        );
+        assert_eq!(ret, 4);
+    }
+    assert_eq!(valid[0], 0x85);
+    assert!(valid[1..8].iter().all(|&b| b == 0));
+    assert_eq!(valid[8], 0x01);
+    assert_eq!(
+        output[7 * SSDV_PACKET_LEN..8 * SSDV_PACKET_LEN],
+        image[7 * SSDV_PACKET_LEN..8 * SSDV_PACKET_LEN]
+    );
+}
+
+#[test]
+fn bad_buffers() {
+    let mut image = img_230(LJ2);
+    let num_packets = (image.len() / SSDV_PACKET_LEN) as c_int;
+    let mut encoder = new_encoder();
+    let mut decoder = new_decoder();
+    let mut scheduler = new_scheduler();
+    let mut output = vec![0u8; image.len()];
+    let mut num_needed = [0 as c_int; 2];
+    unsafe {
+        let misaligned_encoder = ptr::addr_of_mut!(encoder)
+            .cast::<u8>()
+            .add(1)
+            .cast::<ssdv_fec_encoder_t>();
+        assert_eq!(
+            ssdv_fec_encoder_init(
+                misaligned_encoder,
+                LJ2,
+                image.as_mut_ptr().cast(),
+                num_packets
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // the packets overlap the encoder context
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, ptr::addr_of_mut!(encoder).cast(), 1),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert!(!ssdv_fec_encoder_is_ready(&encoder));
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), num_packets),
+            0
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_packet(misaligned_encoder, 0, output.as_mut_ptr().cast()),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert_eq!(ssdv_fec_encoder_image_id(misaligned_encoder), -1);
+        assert_eq!(
+            ssdv_fec_encoder_encode_range(&encoder, 0, 1, ptr::addr_of_mut!(encoder).cast()),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert_eq!(
+            ssdv_fec_encoder_release(misaligned_encoder, false),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert!(ssdv_fec_encoder_is_ready(&encoder));
+        assert_eq!(ssdv_fec_encoder_release(&mut encoder, true), 0);
+
+        ssdv_fec_decoder_init(&mut decoder, LJ2, ptr::null_mut(), 0, false);
+        let misaligned_decoder = ptr::addr_of_mut!(decoder)
+            .cast::<u8>()
+            .add(1)
+            .cast::<ssdv_fec_decoder_t>();
+        assert_eq!(
+            ssdv_fec_decoder_init(misaligned_decoder, LJ2, ptr::null_mut(), 0, false),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // the buffer overlaps the decoder context
+        assert_eq!(
+            ssdv_fec_decoder_init(
+                &mut decoder,
+                LJ2,
+                ptr::addr_of_mut!(decoder).cast(),
+                1,
+                false
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_reset(misaligned_decoder),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert!(!ssdv_fec_decoder_status(misaligned_decoder).ready);
+        assert_eq!(
+            ssdv_fec_decoder_push(misaligned_decoder, image.as_ptr().cast()),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        let misaligned_num_needed = num_needed.as_mut_ptr().cast::<u8>().add(1).cast::<c_int>();
+        assert_eq!(
+            ssdv_fec_decoder_missing_ids(&decoder, ptr::null_mut(), 0, misaligned_num_needed),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // input and output overlap by one byte
+        let half = num_packets / 2;
+        let base = image.as_mut_ptr().cast::<c_char>();
+        let half_len = half as usize * SSDV_PACKET_LEN;
+        assert_eq!(
+            ssdv_fec_decoder_decode_packets(&decoder, base, half, base.add(half_len - 1), half),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // adjacent buffers do not overlap
+        assert_ne!(
+            ssdv_fec_decoder_decode_packets(&decoder, base, half, base.add(half_len), half),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+
+        let misaligned_scheduler = ptr::addr_of_mut!(scheduler)
+            .cast::<u8>()
+            .add(1)
+            .cast::<ssdv_fec_scheduler_t>();
+        assert_eq!(
+            ssdv_fec_scheduler_init(misaligned_scheduler, 7, 65, 50),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+    }
+}
+
+#[test]
+fn null_pointers() {
+    let image = img_230(LJ2);
+    let num_packets = (image.len() / SSDV_PACKET_LEN) as c_int;
+    let mut buffer = vec![0u8; image.len()];
+    let mut output = vec![0u8; image.len()];
+    let mut decoder = new_decoder();
+    let null_decoder = ptr::null_mut::<ssdv_fec_decoder_t>();
+    let mut stats = MaybeUninit::<ssdv_fec_stats_t>::uninit();
+    let mut info = MaybeUninit::<ssdv_fec_packet_info_t>::uninit();
+    unsafe {
+        assert_eq!(
+            ssdv_fec_decoder_init(null_decoder, LJ2, ptr::null_mut(), 0, false),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_init(&mut decoder, LJ2, ptr::null_mut(), 1, false),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_init(&mut decoder, LJ2, buffer.as_mut_ptr().cast(), -1, false),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec_decoder_setup(
+                &mut decoder,
+                LJ2,
+                buffer.as_mut_ptr().cast(),
+                num_packets,
+                ptr::null_mut(),
+                num_packets,
+                false
+            ),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_setup(
+                &mut decoder,
+                LJ2,
+                buffer.as_mut_ptr().cast(),
+                num_packets,
+                output.as_mut_ptr().cast(),
+                -1,
+                false
+            ),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        // the output overlaps the buffer
+        assert_eq!(
+            ssdv_fec_decoder_setup(
+                &mut decoder,
+                LJ2,
+                buffer.as_mut_ptr().cast(),
+                num_packets,
+                buffer.as_mut_ptr().cast(),
+                1,
+                false
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_setup(
+                &mut decoder,
+                LJ2,
+                buffer.as_mut_ptr().cast(),
+                num_packets,
+                output.as_mut_ptr().cast(),
+                num_packets,
+                false
+            ),
+            0
+        );
+        let status = ssdv_fec_decoder_status(null_decoder);
+        assert!(!status.ready);
+        assert_eq!(status.num_packets, -1);
+        assert_eq!(
+            ssdv_fec_decoder_stats(null_decoder, stats.as_mut_ptr()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_stats(&decoder, ptr::null_mut()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_set_scratch(null_decoder, ptr::null_mut(), 0),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_reset(null_decoder),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_push(&mut decoder, ptr::null()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_push_strided(&mut decoder, ptr::null(), 1, SSDV_PACKET_LEN as c_int),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_missing_ids(&decoder, ptr::null_mut(), 1, ptr::null_mut()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_partial(&decoder, ptr::null_mut(), 0, ptr::null_mut()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_decoder_decode_pushed(null_decoder, ptr::null_mut(), 0),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        // the output given to ssdv_fec_decoder_setup is still used
+        assert_eq!(ssdv_fec_decoder_stats(&decoder, stats.as_mut_ptr()), 0);
+        assert_eq!(ssdv_fec_decoder_reset(&mut decoder), 0);
+
+        let null_encoder = ptr::null_mut::<ssdv_fec_encoder_t>();
+        assert!(!ssdv_fec_encoder_is_ready(null_encoder));
+        assert!(!ssdv_fec_encoder_status(null_encoder).ready);
+        assert_eq!(ssdv_fec_encoder_image_id(null_encoder), -1);
+        assert_eq!(
+            ssdv_fec_encoder_release(null_encoder, false),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_encoder_encode_packet(null_encoder, 0, output.as_mut_ptr().cast()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+
+        let null_scheduler = ptr::null_mut::<ssdv_fec_scheduler_t>();
+        assert_eq!(
+            ssdv_fec_scheduler_init(null_scheduler, 7, 65, 50),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(ssdv_fec_scheduler_next(null_scheduler), u16::MAX);
+        assert_eq!(ssdv_fec_scheduler_packets_per_round(null_scheduler), 0);
+
+        assert_eq!(
+            ssdv_fec_crc32(ptr::null(), 10),
+            ssdv_fec_crc32(ptr::null(), 0)
+        );
+        assert!(!ssdv_fec_packet_crc_ok(LJ2, ptr::null()));
+        assert_eq!(
+            ssdv_fec_packet_validate(LJ2, ptr::null()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_packet_info(LJ2, ptr::null(), info.as_mut_ptr()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec_packet_info(LJ2, image.as_ptr().cast(), ptr::null_mut()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+    }
+}
+
+#[test]
+fn v2_round_trip() {
+    let image = img_230(NO_FEC);
+    let len = ssdv_fec2_packet_len(NO_FEC as u32);
+    assert_eq!(len, SSDV_NO_FEC_PACKET_LEN);
+    let k = image.len() / len;
+    let mut encoder_buffer = image.clone();
+    let mut encoder = new_encoder();
+    let mut input = vec![0u8; k * len];
+    unsafe {
+        let ret = ssdv_fec2_encoder_init(
+            &mut encoder,
+            NO_FEC as u32,
+            encoder_buffer.as_mut_ptr(),
+            encoder_buffer.len(),
+        );
+        assert_eq!(ret, 0);
+        let ret =
+            ssdv_fec2_encoder_encode_range(&encoder, k as u16, k, input.as_mut_ptr(), input.len());
+        assert_eq!(ret, 0);
+    }
+    input[..len].copy_from_slice(&image[(k - 1) * len..]);
+    let mut decoder = new_decoder();
+    let mut output = vec![0u8; image.len()];
+    let mut image_len = 0;
+    unsafe {
+        assert_eq!(
+            ssdv_fec2_decoder_init(&mut decoder, NO_FEC as u32, ptr::null_mut(), 0, false),
+            0
+        );
+        let ret = ssdv_fec2_decoder_decode(
+            &decoder,
+            input.as_mut_ptr(),
+            input.len(),
+            output.as_mut_ptr(),
+            output.len(),
+            &mut image_len,
+        );
+        assert_eq!(ret, 0);
+    }
+    assert_eq!(image_len, image.len());
+    assert_eq!(output, image);
+}
+
+#[test]
+fn v2_edge_cases() {
+    let mut image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut encoder = new_encoder();
+    let mut decoder = new_decoder();
+    let mut output = vec![0u8; image.len()];
+    let mut image_len = 0;
+    let mut crc = 0;
+    unsafe {
+        // formats that do not exist
+        assert_eq!(
+            ssdv_fec2_encoder_init(&mut encoder, 7, image.as_mut_ptr(), image.len()),
+            SSDV_FEC_ERR_INVALID_FORMAT
+        );
+        assert!(!ssdv_fec_encoder_is_ready(&encoder));
+        assert_eq!(
+            ssdv_fec2_decoder_init(&mut decoder, 7, ptr::null_mut(), 0, false),
+            SSDV_FEC_ERR_INVALID_FORMAT
+        );
+        assert_eq!(
+            ssdv_fec2_packet_validate(7, image.as_ptr(), SSDV_PACKET_LEN),
+            SSDV_FEC_ERR_INVALID_FORMAT
+        );
+        assert_eq!(ssdv_fec2_packet_len(7), 0);
+        // NULL pointers
+        assert_eq!(
+            ssdv_fec2_encoder_init(ptr::null_mut(), LJ2 as u32, image.as_mut_ptr(), image.len()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_encoder_init(&mut encoder, LJ2 as u32, ptr::null_mut(), image.len()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_decoder_init(&mut decoder, LJ2 as u32, ptr::null_mut(), 1, false),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_decoder_push(ptr::null_mut(), image.as_ptr(), SSDV_PACKET_LEN),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_crc32(ptr::null(), 1, &mut crc),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_crc32(image.as_ptr(), 1, ptr::null_mut()),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        assert_eq!(
+            ssdv_fec2_packet_validate(LJ2 as u32, ptr::null(), SSDV_PACKET_LEN),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+        // an empty buffer has a zero CRC-32
+        assert_eq!(ssdv_fec2_crc32(ptr::null(), 0, &mut crc), 0);
+        assert_eq!(crc, ssdv_fec_crc32(ptr::null(), 0));
+
+        // lengths that are not a multiple of the packet size
+        assert_eq!(
+            ssdv_fec2_encoder_init(
+                &mut encoder,
+                LJ2 as u32,
+                image.as_mut_ptr(),
+                image.len() - 1
+            ),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec2_packet_validate(LJ2 as u32, image.as_ptr(), SSDV_PACKET_LEN + 1),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        assert_eq!(
+            ssdv_fec2_packet_validate(LJ2 as u32, image.as_ptr(), SSDV_PACKET_LEN),
+            0
+        );
+        assert_eq!(
+            ssdv_fec2_decoder_init(&mut decoder, LJ2 as u32, ptr::null_mut(), 0, false),
+            0
+        );
+        assert_eq!(
+            ssdv_fec2_decoder_push(&mut decoder, image.as_ptr(), SSDV_PACKET_LEN - 1),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+        let mut input = image.clone();
+        assert_eq!(
+            ssdv_fec2_decoder_decode(
+                &decoder,
+                input.as_mut_ptr(),
+                input.len() - 1,
+                output.as_mut_ptr(),
+                output.len(),
+                &mut image_len,
+            ),
+            SSDV_FEC_ERR_INVALID_LENGTH
+        );
+
+        // output buffers one byte too short
+        assert_eq!(
+            ssdv_fec2_encoder_init(&mut encoder, LJ2 as u32, image.as_mut_ptr(), image.len()),
+            0
+        );
+        assert_eq!(
+            ssdv_fec2_encoder_encode(&encoder, k as u16, output.as_mut_ptr(), SSDV_PACKET_LEN - 1),
+            SSDV_FEC_ERR_OUTPUT_TOO_SHORT
+        );
+        assert_eq!(
+            ssdv_fec2_encoder_encode_range(&encoder, 0, k, output.as_mut_ptr(), output.len() - 1),
+            SSDV_FEC_ERR_OUTPUT_TOO_SHORT
+        );
+        assert_eq!(
+            ssdv_fec2_encoder_encode_range(
+                &encoder,
+                1,
+                usize::MAX,
+                output.as_mut_ptr(),
+                output.len()
+            ),
+            SSDV_FEC_ENCODER_ERR_INVALID_RANGE
+        );
+        // the encoder has modified image
+        let mut input = img_230(LJ2);
+        assert_eq!(
+            ssdv_fec2_decoder_decode(
+                &decoder,
+                input.as_mut_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                output.len() - 1,
+                &mut image_len,
+            ),
+            SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT
+        );
+    }
+}
+
+#[test]
+fn v2_bad_buffers() {
+    let mut image = img_230(LJ2);
+    let mut decoder = new_decoder();
+    let mut output = vec![0u8; image.len()];
+    let mut image_len = [0usize; 2];
+    let mut crc = [0u32; 2];
+    unsafe {
+        let misaligned_crc = crc.as_mut_ptr().cast::<u8>().add(1).cast::<u32>();
+        assert_eq!(
+            ssdv_fec2_crc32(image.as_ptr(), image.len(), misaligned_crc),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        let misaligned_decoder = ptr::addr_of_mut!(decoder)
+            .cast::<u8>()
+            .add(1)
+            .cast::<ssdv_fec_decoder_t>();
+        assert_eq!(
+            ssdv_fec2_decoder_init(misaligned_decoder, LJ2 as u32, ptr::null_mut(), 0, false),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // the buffer overlaps the decoder context
+        assert_eq!(
+            ssdv_fec2_decoder_init(
+                &mut decoder,
+                LJ2 as u32,
+                ptr::addr_of_mut!(decoder).cast(),
+                size_of::<ssdv_fec_decoder_t>(),
+                false
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        assert_eq!(
+            ssdv_fec2_decoder_init(&mut decoder, LJ2 as u32, ptr::null_mut(), 0, false),
+            0
+        );
+        let misaligned_image_len = image_len.as_mut_ptr().cast::<u8>().add(1).cast::<usize>();
+        assert_eq!(
+            ssdv_fec2_decoder_decode(
+                &decoder,
+                image.as_mut_ptr(),
+                image.len(),
+                output.as_mut_ptr(),
+                output.len(),
+                misaligned_image_len,
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // input and output overlap by one byte
+        let half = image.len() / SSDV_PACKET_LEN / 2 * SSDV_PACKET_LEN;
+        let base = image.as_mut_ptr();
+        assert_eq!(
+            ssdv_fec2_decoder_decode(
+                &decoder,
+                base,
+                half,
+                base.add(half - 1),
+                half,
+                image_len.as_mut_ptr(),
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+        // adjacent buffers do not overlap
+        assert_ne!(
+            ssdv_fec2_decoder_decode(
+                &decoder,
+                base,
+                half,
+                base.add(half),
+                half,
+                image_len.as_mut_ptr(),
+            ),
+            SSDV_FEC_ERR_BAD_BUFFER
+        );
+    }
+}
+
+#[test]
+fn scheduler() {
+    let mut scheduler = new_scheduler();
+    unsafe {
+        assert_eq!(
+            ssdv_fec_scheduler_init(&mut scheduler, 7, 0, 50),
+            SSDV_FEC_ENCODER_ERR_EMPTY_INPUT
+        );
+        assert_eq!(ssdv_fec_scheduler_init(&mut scheduler, 7, 65, 50), 0);
+    }
+    let mut reference = FountainScheduler::with_overhead_percent(65, 50, 7);
+    assert_eq!(
+        unsafe { ssdv_fec_scheduler_packets_per_round(&scheduler) },
+        reference.packets_per_round()
+    );
+    for _ in 0..1000 {
+        assert_eq!(
+            unsafe { ssdv_fec_scheduler_next(&mut scheduler) },
+            reference.next_packet_id()
+        );
+    }
+}
+
+#[test]
+fn packet_info() {
+    let image = img_230(NO_FEC);
+    let mut info = MaybeUninit::<ssdv_fec_packet_info_t>::uninit();
+    let info = unsafe {
+        ssdv_fec_packet_info(
+            NO_FEC,
+            image[SSDV_NO_FEC_PACKET_LEN..].as_ptr().cast(),
+            info.as_mut_ptr(),
+        );
+        info.assume_init()
+    };
+    assert_eq!(info.image_id, 230);
+    assert_eq!(info.packet_id, 1);
+    assert!(!info.fec_packet);
+    assert!(!info.eoi);
+    assert_eq!(info.num_systematic, -1);
+    assert!(info.crc_ok);
+}
+
+#[test]
+fn strerror() {
+    let codes = [
+        SSDV_FEC_ENCODER_ERR_EMPTY_INPUT,
+        SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT,
+        SSDV_FEC_ENCODER_ERR_NON_SYSTEMATIC_INPUT,
+        SSDV_FEC_ENCODER_ERR_INVALID_RANGE,
+        SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED,
+        SSDV_FEC_DECODER_ERR_EOI_ON_FEC_PACKET,
+        SSDV_FEC_DECODER_ERR_DUPLICATED_EOI,
+        SSDV_FEC_DECODER_ERR_NUM_SYSTEMATIC_MISMATCH,
+        SSDV_FEC_DECODER_ERR_UNKNOWN_NUM_SYSTEMATIC,
+        SSDV_FEC_DECODER_ERR_EOI_FEC_MISMATCH,
+        SSDV_FEC_DECODER_ERR_NOT_ENOUGH_INPUT,
+        SSDV_FEC_DECODER_ERR_OUTPUT_TOO_SHORT,
+        SSDV_FEC_DECODER_ERR_WRONG_SYSTEMATIC_ID,
+        SSDV_FEC_DECODER_ERR_MULTIPLE_IMAGE_IDS,
+        SSDV_FEC_DECODER_ERR_INCONSISTENT_FLAGS,
+        SSDV_FEC_DECODER_ERR_DIMENSIONS_MISMATCH,
+        SSDV_FEC_DECODER_ERR_NO_SYSTEMATIC,
+        SSDV_FEC_PACKET_ERR_WRONG_CRC,
+        SSDV_FEC_PACKET_ERR_WRONG_FIXED_FIELD,
+        SSDV_FEC_PACKET_ERR_EOI_ON_FEC_PACKET,
+        SSDV_FEC_PACKET_ERR_WRONG_FEC_ID,
+        SSDV_FEC_ERR_NULL_POINTER,
+        SSDV_FEC_ERR_INVALID_LENGTH,
+        SSDV_FEC_ERR_OUTPUT_TOO_SHORT,
+        SSDV_FEC_ERR_SCRATCH_TOO_SHORT,
+        SSDV_FEC_ERR_BAD_BUFFER,
+        SSDV_FEC_ERR_SELFTEST_FAILED,
+        SSDV_FEC_ERR_INVALID_FORMAT,
+    ];
+    let unknown = unsafe { CStr::from_ptr(ssdv_fec_strerror(-1000)) };
+    for code in codes {
+        let description = unsafe { CStr::from_ptr(ssdv_fec_strerror(code)) };
+        assert_ne!(description, unknown, "code {code}");
+    }
+    let version = unsafe { CStr::from_ptr(ssdv_fec_version()) };
+    assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+    assert_eq!(ssdv_fec_max_k(), MAX_K as c_int);
+    assert_eq!(
+        ssdv_fec_capabilities() & SSDV_FEC_CAP_RAM_TABLES != 0,
+        cfg!(feature = "ram-tables")
+    );
+}
+
+#[test]
+fn selftest() {
+    ssdv_fec_init();
+    let mut scratch = vec![0u8; SSDV_FEC_SELFTEST_SCRATCH_LEN + 1];
+    unsafe {
+        // the scratch memory does not need to be aligned
+        assert_eq!(
+            ssdv_fec_selftest(
+                scratch[1..].as_mut_ptr().cast(),
+                SSDV_FEC_SELFTEST_SCRATCH_LEN
+            ),
+            0
+        );
+        assert_eq!(
+            ssdv_fec_selftest(
+                scratch.as_mut_ptr().cast(),
+                SSDV_FEC_SELFTEST_SCRATCH_LEN - 1
+            ),
+            SSDV_FEC_ERR_SCRATCH_TOO_SHORT
+        );
+        assert_eq!(
+            ssdv_fec_selftest(ptr::null_mut(), SSDV_FEC_SELFTEST_SCRATCH_LEN),
+            SSDV_FEC_ERR_NULL_POINTER
+        );
+    }
+}
+
+static LOG_MESSAGES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_log_messages(_level: c_int, msg: *const c_char) {
+    assert!(CStr::from_ptr(msg).to_bytes().len() <= 95);
+    LOG_MESSAGES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn log_callback() {
+    let image = img_230(LJ2);
+    let mut packet = image[..SSDV_PACKET_LEN].to_vec();
+    packet[20] ^= 1;
+    let mut decoder = new_decoder();
+    let mut buffer = vec![0u8; SSDV_PACKET_LEN];
+    unsafe {
+        ssdv_fec_decoder_init(&mut decoder, LJ2, buffer.as_mut_ptr().cast(), 1, false);
+        ssdv_fec_set_log_callback(Some(count_log_messages));
+        let before = LOG_MESSAGES.load(Ordering::Relaxed);
+        assert_eq!(
+            ssdv_fec_decoder_push(&mut decoder, packet.as_ptr().cast()),
+            SSDV_FEC_PUSH_WRONG_CRC
+        );
+        // other tests running concurrently can also produce messages
+        assert!(LOG_MESSAGES.load(Ordering::Relaxed) > before);
+        ssdv_fec_set_log_callback(None);
+    }
+}
+
+static PROGRESS_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn count_progress(operation: c_int, done: u32, total: u32) {
+    assert!((SSDV_FEC_PROGRESS_ENCODER_SETUP..=SSDV_FEC_PROGRESS_DECODE).contains(&operation));
+    assert!(done <= total);
+    PROGRESS_CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn progress_hook() {
+    let mut image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut encoder = new_encoder();
+    unsafe {
+        ssdv_fec_set_progress_hook(Some(count_progress));
+        let before = PROGRESS_CALLS.load(Ordering::Relaxed);
+        assert_eq!(
+            ssdv_fec_encoder_init(&mut encoder, LJ2, image.as_mut_ptr().cast(), k as c_int),
+            0
+        );
+        // other tests running concurrently can also report progress
+        assert!(PROGRESS_CALLS.load(Ordering::Relaxed) > before);
+        ssdv_fec_set_progress_hook(None);
+    }
+}
+
+#[cfg(feature = "critical-section")]
+#[test]
+fn critical_section_concurrent_push() {
+    use crate::cs::*;
+
+    struct SharedDecoder(*mut ssdv_fec_decoder_t);
+    unsafe impl Sync for SharedDecoder {}
+
+    let image = img_230(LJ2);
+    let k = image.len() / SSDV_PACKET_LEN;
+    let mut buffer = vec![0u8; image.len()];
+    let mut output = vec![0u8; image.len()];
+    let mut decoder = new_decoder();
+    unsafe {
+        ssdv_fec_cs_decoder_init(
+            &mut decoder,
+            LJ2,
+            buffer.as_mut_ptr().cast(),
+            k as c_int,
+            false,
+        );
+    }
+    let shared = SharedDecoder(&mut decoder);
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let shared = &shared;
+            let image = &image;
+            s.spawn(move || {
+                for packet in image.chunks_exact(SSDV_PACKET_LEN).skip(thread).step_by(4) {
+                    let ret = unsafe { ssdv_fec_cs_decoder_push(shared.0, packet.as_ptr().cast()) };
+                    assert_eq!(ret, SSDV_FEC_PUSH_STORED);
+                }
+            });
+        }
+    });
+    unsafe {
+        assert!(ssdv_fec_cs_decoder_status(&decoder).ready);
+        let ret =
+            ssdv_fec_cs_decoder_decode_pushed(&mut decoder, output.as_mut_ptr().cast(), k as c_int);
+        assert_eq!(ret, k as c_int);
+    }
+    assert_eq!(output, image);
+    assert_ne!(ssdv_fec_capabilities() & SSDV_FEC_CAP_CRITICAL_SECTION, 0);
+}
@@ -0,0 +1,447 @@
+//! Hardened C API.
+//!
+//! The `ssdv_fec2_*` functions are a parallel version of the `ssdv_fec_*`
+//! functions intended for flight software that follows coding standards such as
+//! MISRA C. Buffer lengths are given as `size_t` values measured in bytes,
+//! read-only buffers are `const uint8_t *`, and each output buffer comes with
+//! its capacity. All the pointers and lengths are checked before they are used,
+//! and the functions return an error code instead of writing out of bounds.
+//! Pointers to contexts and results that are not aligned for their type, and
+//! buffers that overlap a context or each other, are rejected with
+//! `SSDV_FEC_ERR_BAD_BUFFER`. The packet buffers are byte arrays, so they do
+//! not need any alignment. The contexts are the same as in the original API.
+//! The packet format is passed as a `uint32_t` holding one of the values of
+//! `ssdv_fec_format_t`, and other values are rejected with
+//! `SSDV_FEC_ERR_INVALID_FORMAT`.
+
+use super::*;
+
+// Converts a format given as an integer, which can hold any value, into a
+// `ssdv_fec_format_t`.
+fn format_from_u32(format: u32) -> Result<ssdv_fec_format_t, c_int> {
+    match format {
+        0 => Ok(ssdv_fec_format_t::SSDV_FEC_FORMAT_LJ2),
+        1 => Ok(ssdv_fec_format_t::SSDV_FEC_FORMAT_NO_FEC),
+        _ => Err(SSDV_FEC_ERR_INVALID_FORMAT),
+    }
+}
+
+// Converts the length in bytes of an input buffer into a number of packets.
+fn input_packets<P: Packet>(len: usize) -> Result<usize, c_int> {
+    if len.is_multiple_of(P::LEN) {
+        Ok(len / P::LEN)
+    } else {
+        Err(SSDV_FEC_ERR_INVALID_LENGTH)
+    }
+}
+
+/// Prepares an SSDV FEC encoder.
+///
+/// This is the hardened version of [`ssdv_fec_encoder_init`]. The
+/// `ssdv_packets` array contains `ssdv_packets_len` bytes, which must be a
+/// multiple of the packet size of the format.
+///
+/// The function returns zero on success, or a negative error code if there is
+/// an error. `SSDV_FEC_ERR_NULL_POINTER` is returned if `encoder` or
+/// `ssdv_packets` are `NULL`, and `SSDV_FEC_ERR_INVALID_LENGTH` if
+/// `ssdv_packets_len` is not a multiple of the packet size.
+/// `SSDV_FEC_ERR_BAD_BUFFER` is returned if `encoder` is misaligned or
+/// `ssdv_packets` overlaps it, and `SSDV_FEC_ERR_INVALID_FORMAT` if `format` is
+/// not a packet format. The encoder context is left uninitialized if there is
+/// an error, unless it is `NULL` or misaligned.
+///
+/// # Safety
+///
+/// The buffer pointed to by `ssdv_packets` must have allocated storage for at
+/// least `ssdv_packets_len` bytes. All the safety considerations of
+/// `ssdv_fec_encoder_init` also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_encoder_init(
+    encoder: *mut ssdv_fec_encoder_t,
+    format: u32,
+    ssdv_packets: *mut u8,
+    ssdv_packets_len: usize,
+) -> c_int {
+    if encoder.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    (*encoder).initialized = false;
+    let format = match format_from_u32(format) {
+        Ok(format) => format,
+        Err(err) => return err,
+    };
+    if ssdv_packets.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if overlaps_context(encoder, ssdv_packets, ssdv_packets_len) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let num_packets = match with_format!(format, P => input_packets::<P>(ssdv_packets_len)) {
+        Ok(num_packets) => num_packets,
+        Err(err) => return err,
+    };
+    // Encoder::new rejects inputs that do not fit in a 16-bit packet ID, so
+    // only the conversion to c_int needs to be checked.
+    let Ok(num_packets) = c_int::try_from(num_packets) else {
+        return SSDV_FEC_ENCODER_ERR_TOO_LONG_INPUT;
+    };
+    ssdv_fec_encoder_init(encoder, format, ssdv_packets.cast(), num_packets)
+}
+
+/// Generates a FEC encoded packet.
+///
+/// This is the hardened version of [`ssdv_fec_encoder_encode_packet`]. The
+/// packet is written to the `output` array, whose capacity in bytes is given by
+/// `output_len`.
+///
+/// The function returns zero on success, or a negative error code if there is
+/// an error. `SSDV_FEC_ERR_NULL_POINTER` is returned if `encoder` or `output`
+/// are `NULL`, `SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED` if the encoder is not
+/// initialized, `SSDV_FEC_ENCODER_ERR_INVALID_RANGE` if `packet_id` is
+/// `0xffff`, `SSDV_FEC_ERR_OUTPUT_TOO_SHORT` if the packet does not fit in
+/// `output`, and `SSDV_FEC_ERR_BAD_BUFFER` if `encoder` is misaligned or
+/// `output` overlaps it.
+///
+/// # Safety
+///
+/// The `encoder` parameter must be `NULL` or point to an encoder context that
+/// has been passed to `ssdv_fec_encoder_init` or `ssdv_fec2_encoder_init`. The
+/// `output` buffer must have allocated storage for at least `output_len` bytes.
+/// All the safety considerations of `ssdv_fec_encoder_encode_packet` also
+/// apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_encoder_encode(
+    encoder: *const ssdv_fec_encoder_t,
+    packet_id: u16,
+    output: *mut u8,
+    output_len: usize,
+) -> c_int {
+    ssdv_fec2_encoder_encode_range(encoder, packet_id, 1, output, output_len)
+}
+
+/// Generates a range of FEC encoded packets.
+///
+/// This is the hardened version of [`ssdv_fec_encoder_encode_range`]. The
+/// `count` packets are written to the `output` array, whose capacity in bytes
+/// is given by `output_len`.
+///
+/// The function returns zero on success, or a negative error code if there is
+/// an error. The errors are the same as for [`ssdv_fec2_encoder_encode`].
+///
+/// # Safety
+///
+/// The same safety considerations as for `ssdv_fec2_encoder_encode` apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_encoder_encode_range(
+    encoder: *const ssdv_fec_encoder_t,
+    first_id: u16,
+    count: usize,
+    output: *mut u8,
+    output_len: usize,
+) -> c_int {
+    if encoder.is_null() || output.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !encoder.is_aligned() || overlaps_context(encoder, output, output_len) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if !(*encoder).initialized {
+        return SSDV_FEC_ENCODER_ERR_NOT_INITIALIZED;
+    }
+    if usize::from(first_id)
+        .checked_add(count)
+        .is_none_or(|end| end > usize::from(u16::MAX))
+    {
+        return SSDV_FEC_ENCODER_ERR_INVALID_RANGE;
+    }
+    with_format!((*encoder).format, P => {
+        if count.checked_mul(P::LEN).is_none_or(|len| len > output_len) {
+            return SSDV_FEC_ERR_OUTPUT_TOO_SHORT;
+        }
+        let output = packets_mut::<P, _>(output, count);
+        if let Err(err) = (*encoder).encoder::<P>().try_encode_range(first_id, output) {
+            return encoder_error(err);
+        }
+    });
+    0
+}
+
+/// Prepares an SSDV FEC decoder.
+///
+/// This is the hardened version of [`ssdv_fec_decoder_init`]. The capacity of
+/// the `buffer` array is given in bytes by `buffer_len`. Only whole packets are
+/// stored in it, so any remaining bytes at the end are not used. If the decoder
+/// is only used with [`ssdv_fec2_decoder_decode`], `buffer` can be `NULL` and
+/// `buffer_len` can be zero.
+///
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if
+/// `decoder` is `NULL`, or `buffer` is `NULL` and `buffer_len` is not zero, or
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` is misaligned or `buffer` overlaps
+/// it, or `SSDV_FEC_ERR_INVALID_FORMAT` if `format` is not a packet format.
+///
+/// # Safety
+///
+/// The buffer pointed to by `buffer` must have allocated storage for at least
+/// `buffer_len` bytes. All the safety considerations of `ssdv_fec_decoder_init`
+/// also apply.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_decoder_init(
+    decoder: *mut ssdv_fec_decoder_t,
+    format: u32,
+    buffer: *mut u8,
+    buffer_len: usize,
+    lenient: bool,
+) -> c_int {
+    if decoder.is_null() || (buffer.is_null() && buffer_len != 0) {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() || overlaps_context(decoder, buffer, buffer_len) {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let format = match format_from_u32(format) {
+        Ok(format) => format,
+        Err(err) => return err,
+    };
+    with_format!(format, P => {
+        let buffer = packets_mut::<P, _>(buffer, buffer_len / P::LEN);
+        (*decoder)
+            .storage
+            .as_mut_ptr()
+            .cast::<StreamingDecoder<P>>()
+            .write(StreamingDecoder::with_options(
+                buffer,
+                DecoderOptions::new().lenient(lenient),
+            ));
+    });
+    (*decoder).format = format;
+    (*decoder).output = ptr::null_mut();
+    (*decoder).num_output_packets = 0;
+    (*decoder).scratch = ptr::null_mut();
+    (*decoder).scratch_len = 0;
+    (*decoder).stats = ssdv_fec_stats_t::default();
+    0
+}
+
+/// Pushes a received packet into an SSDV FEC decoder.
+///
+/// This is the hardened version of [`ssdv_fec_decoder_push`]. The `packet`
+/// array contains `packet_len` bytes, which must be the packet size of the
+/// format of the decoder.
+///
+/// The function returns one of the `SSDV_FEC_PUSH_*` values, or a negative
+/// error code if there is an error. `SSDV_FEC_ERR_NULL_POINTER` is returned if
+/// `decoder` or `packet` are `NULL`, `SSDV_FEC_ERR_INVALID_LENGTH` if
+/// `packet_len` is not the packet size, and `SSDV_FEC_ERR_BAD_BUFFER` if
+/// `decoder` is misaligned.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized. The `packet` parameter must point to at least
+/// `packet_len` bytes. The decoder context must not be used concurrently by
+/// other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_decoder_push(
+    decoder: *mut ssdv_fec_decoder_t,
+    packet: *const u8,
+    packet_len: usize,
+) -> c_int {
+    if decoder.is_null() || packet.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    if packet_len != with_format!((*decoder).format, P => P::LEN) {
+        return SSDV_FEC_ERR_INVALID_LENGTH;
+    }
+    ssdv_fec_decoder_push(decoder, packet.cast())
+}
+
+/// Decodes the SSDV image from the packets pushed into an SSDV FEC decoder.
+///
+/// This is the hardened version of [`ssdv_fec_decoder_decode_pushed`]. The
+/// decoded image is written to the `output` array, whose capacity in bytes is
+/// given by `output_len`. The output buffer given to [`ssdv_fec_decoder_setup`]
+/// is never used by this function.
+///
+/// The function returns zero if decoding is successful, in which case the
+/// length in bytes of the decoded image is written to `image_len`, or a
+/// negative error code otherwise. `SSDV_FEC_ERR_NULL_POINTER` is returned if
+/// `decoder`, `output` or `image_len` are `NULL`, and `SSDV_FEC_ERR_BAD_BUFFER`
+/// if `decoder` or `image_len` are misaligned or `output` overlaps the decoder
+/// context.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized. The `output` buffer must have allocated storage for
+/// at least `output_len` bytes. The `image_len` parameter must be `NULL` or
+/// point to valid storage. The decoder context must not be used concurrently
+/// by other threads while this function runs.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_decoder_decode_pushed(
+    decoder: *mut ssdv_fec_decoder_t,
+    output: *mut u8,
+    output_len: usize,
+    image_len: *mut usize,
+) -> c_int {
+    if decoder.is_null() || output.is_null() || image_len.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned()
+        || !image_len.is_aligned()
+        || overlaps_context(decoder, output, output_len)
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*decoder).format, P => {
+        let output = packets_mut::<P, _>(output, output_len / P::LEN);
+        match (*decoder).decode_stored(output) {
+            Ok(len) => {
+                *image_len = len * P::LEN;
+                0
+            }
+            Err(err) => err,
+        }
+    })
+}
+
+/// Decodes a FEC encoded SSDV image.
+///
+/// This is the hardened version of [`ssdv_fec_decoder_decode_packets`]. The
+/// `input` array contains `input_len` bytes, which must be a multiple of the
+/// packet size of the format of the decoder. The decoded image is written to
+/// the `output` array, whose capacity in bytes is given by `output_len`.
+///
+/// The function returns zero if decoding is successful, in which case the
+/// length in bytes of the decoded image is written to `image_len`, or a
+/// negative error code otherwise. `SSDV_FEC_ERR_NULL_POINTER` is returned if
+/// `decoder`, `input`, `output` or `image_len` are `NULL`,
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `input_len` is not a multiple of the
+/// packet size, and `SSDV_FEC_ERR_BAD_BUFFER` if `decoder` or `image_len` are
+/// misaligned, `input` and `output` overlap, or one of them overlaps the
+/// decoder context.
+///
+/// The function modifies the contents of the `input` array, unless scratch
+/// memory has been given to the decoder with [`ssdv_fec_decoder_set_scratch`].
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or point to a decoder context that
+/// has been initialized. The `input` and `output` buffers must have allocated
+/// storage for at least `input_len` and `output_len` bytes respectively, and
+/// must not overlap. The `image_len` parameter must be `NULL` or point to
+/// valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_decoder_decode(
+    decoder: *const ssdv_fec_decoder_t,
+    input: *mut u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+    image_len: *mut usize,
+) -> c_int {
+    if decoder.is_null() || input.is_null() || output.is_null() || image_len.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !decoder.is_aligned()
+        || !image_len.is_aligned()
+        || overlap(input, input_len, output, output_len)
+        || overlaps_context(decoder, input, input_len)
+        || overlaps_context(decoder, output, output_len)
+    {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    with_format!((*decoder).format, P => {
+        let num_input = match input_packets::<P>(input_len) {
+            Ok(num_input) => num_input,
+            Err(err) => return err,
+        };
+        let input = packets_mut::<P, _>(input, num_input);
+        let output = packets_mut::<P, _>(output, output_len / P::LEN);
+        match (*decoder).decode_input(input, output) {
+            Ok(len) => {
+                *image_len = len * P::LEN;
+                0
+            }
+            Err(err) => err,
+        }
+    })
+}
+
+/// Computes the CRC-32 used by SSDV packets in the Longjiang-2 format.
+///
+/// This is the hardened version of [`ssdv_fec_crc32`]. The CRC-32 of the
+/// `len` bytes of `data` is written to `crc`. `data` can be `NULL` if `len` is
+/// zero.
+///
+/// The function returns zero on success, `SSDV_FEC_ERR_NULL_POINTER` if `crc`
+/// is `NULL`, or `data` is `NULL` and `len` is not zero, or
+/// `SSDV_FEC_ERR_BAD_BUFFER` if `crc` is misaligned.
+///
+/// # Safety
+///
+/// The `data` buffer must have allocated storage for at least `len` bytes. The
+/// `crc` parameter must be `NULL` or point to valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_crc32(data: *const u8, len: usize, crc: *mut u32) -> c_int {
+    if crc.is_null() || (data.is_null() && len != 0) {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    if !crc.is_aligned() {
+        return SSDV_FEC_ERR_BAD_BUFFER;
+    }
+    let data = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    };
+    *crc = crc32(data.iter());
+    0
+}
+
+/// Validates a single SSDV packet.
+///
+/// This is the hardened version of [`ssdv_fec_packet_validate`]. The `packet`
+/// array contains `packet_len` bytes, which must be the packet size of the
+/// format.
+///
+/// The function returns zero if the packet is valid, or a negative error code
+/// otherwise. `SSDV_FEC_ERR_NULL_POINTER` is returned if `packet` is `NULL`,
+/// `SSDV_FEC_ERR_INVALID_FORMAT` if `format` is not a packet format, and
+/// `SSDV_FEC_ERR_INVALID_LENGTH` if `packet_len` is not the packet size.
+///
+/// # Safety
+///
+/// The `packet` parameter must point to at least `packet_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec2_packet_validate(
+    format: u32,
+    packet: *const u8,
+    packet_len: usize,
+) -> c_int {
+    if packet.is_null() {
+        return SSDV_FEC_ERR_NULL_POINTER;
+    }
+    let format = match format_from_u32(format) {
+        Ok(format) => format,
+        Err(err) => return err,
+    };
+    if packet_len != with_format!(format, P => P::LEN) {
+        return SSDV_FEC_ERR_INVALID_LENGTH;
+    }
+    ssdv_fec_packet_validate(format, packet.cast())
+}
+
+/// Returns the length in bytes of an SSDV packet in a format.
+///
+/// This is the hardened version of [`ssdv_fec_packet_len`]. The function
+/// returns zero if `format` is not a packet format.
+#[no_mangle]
+pub extern "C" fn ssdv_fec2_packet_len(format: u32) -> usize {
+    format_from_u32(format).map_or(0, |format| with_format!(format, P => P::LEN))
+}
@@ -0,0 +1,7 @@
+fn main() {
+    cxx_build::bridge("src/lib.rs")
+        .std("c++17")
+        .compile("ssdv-fec-cxx");
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+}
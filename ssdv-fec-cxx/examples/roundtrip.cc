@@ -0,0 +1,47 @@
+// Encodes an SSDV image and decodes it from a subset of the packets.
+//
+// Usage: roundtrip image.ssdv
+
+#include "rust/cxx.h"
+#include "ssdv-fec-cxx/src/lib.rs.h"
+
+#include <cstdint>
+#include <fstream>
+#include <iostream>
+#include <iterator>
+#include <vector>
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        std::cerr << "usage: " << argv[0] << " image.ssdv" << std::endl;
+        return 1;
+    }
+    std::ifstream file(argv[1], std::ios::binary);
+    std::vector<uint8_t> image((std::istreambuf_iterator<char>(file)),
+                               std::istreambuf_iterator<char>());
+
+    try {
+        const auto format = ssdv_fec::Format::Lj2;
+        const size_t packet_len = ssdv_fec::packet_len(format);
+        auto encoder = ssdv_fec::new_encoder(rust::Slice<const uint8_t>(image), format);
+        const uint16_t k = encoder->num_systematic();
+
+        // Only the last systematic packet and some FEC packets are received.
+        auto decoder = ssdv_fec::new_decoder(format, false);
+        const auto last = encoder->encode(k - 1);
+        decoder->push(rust::Slice<const uint8_t>(last.data(), last.size()));
+        const auto fec = encoder->encode_range(k, k - 1);
+        for (size_t j = 0; j < fec.size(); j += packet_len) {
+            decoder->push(rust::Slice<const uint8_t>(fec.data() + j, packet_len));
+        }
+        const auto decoded = decoder->decode();
+        const bool ok = std::vector<uint8_t>(decoded.begin(), decoded.end()) == image;
+        std::cout << "decoded image " << int(encoder->image_id()) << " from "
+                  << decoder->num_packets() << " packets: " << (ok ? "OK" : "mismatch")
+                  << std::endl;
+        return ok ? 0 : 1;
+    } catch (const rust::Error &err) {
+        std::cerr << "error: " << err.what() << std::endl;
+        return 1;
+    }
+}
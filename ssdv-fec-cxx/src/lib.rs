@@ -0,0 +1,354 @@
+//! # C++ bindings of the SSDV systematic erasure FEC
+//!
+//! This crate exposes the encoder, the decoder and the packet parser of the
+//! [`ssdv_fec`] crate to C++17 through [cxx](https://cxx.rs/). The encoders and
+//! decoders are owned by `rust::Box`, so their lifetime is managed by RAII in
+//! C++. Packets are passed as `rust::Slice<const uint8_t>`, which can be
+//! constructed from a pointer and a length, or from a contiguous container
+//! such as `std::vector` or a C++20 `std::span`, and contain one or several
+//! concatenated packets in the format selected with [`Format`]. Outputs are
+//! returned as `rust::Vec<uint8_t>`, and errors are thrown as `rust::Error`.
+
+use ssdv_fec::{format::AnyEncoder, DecoderOptions};
+
+#[cxx::bridge(namespace = "ssdv_fec")]
+mod ffi {
+    /// SSDV packet format.
+    #[derive(Debug)]
+    enum Format {
+        /// 218-byte format used by Longjiang-2, which omits the sync byte,
+        /// packet type and callsign fields.
+        Lj2,
+        /// Standard 256-byte no-FEC SSDV format.
+        NoFec,
+    }
+
+    /// Header fields of an SSDV packet.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    struct PacketInfo {
+        /// Image ID.
+        image_id: u8,
+        /// Packet ID.
+        packet_id: u16,
+        /// Image width in units of 16 pixels, or 0 in FEC packets.
+        width: u8,
+        /// Image height in units of 16 pixels, or 0 in FEC packets.
+        height: u8,
+        /// Number of systematic packets of the image, or 0 in systematic
+        /// packets.
+        num_systematic: u16,
+        /// Flags field.
+        flags: u8,
+        /// Whether the EOI flag is set.
+        eoi: bool,
+        /// Whether the packet is a FEC packet.
+        fec_packet: bool,
+        /// Whether the CRC-32 of the packet is correct.
+        crc_ok: bool,
+    }
+
+    extern "Rust" {
+        /// SSDV FEC encoder.
+        type Encoder;
+        /// SSDV FEC decoder.
+        type Decoder;
+
+        /// Returns the length in bytes of a packet in a format.
+        fn packet_len(format: Format) -> Result<usize>;
+        /// Returns the header fields of a single SSDV packet.
+        fn packet_info(packet: &[u8], format: Format) -> Result<PacketInfo>;
+        /// Decodes an SSDV image from received packets.
+        fn decode(packets: &[u8], format: Format, lenient: bool) -> Result<Vec<u8>>;
+
+        /// Creates an encoder for an image given by its systematic packets.
+        fn new_encoder(image: &[u8], format: Format) -> Result<Box<Encoder>>;
+        /// Generates the packet with a packet ID.
+        fn encode(self: &Encoder, packet_id: u16) -> Result<Vec<u8>>;
+        /// Generates packets with consecutive packet IDs, concatenated.
+        fn encode_range(self: &Encoder, first_id: u16, count: u16) -> Result<Vec<u8>>;
+        /// Returns the number of systematic packets of the image.
+        fn num_systematic(self: &Encoder) -> u16;
+        /// Returns the image ID of the image.
+        fn image_id(self: &Encoder) -> u8;
+
+        /// Creates an empty decoder.
+        fn new_decoder(format: Format, lenient: bool) -> Result<Box<Decoder>>;
+        /// Pushes a received packet.
+        fn push(self: &mut Decoder, packet: &[u8]) -> Result<bool>;
+        /// Returns the number of packets that have been pushed and kept.
+        fn num_packets(self: &Decoder) -> usize;
+        /// Decodes the image from the pushed packets.
+        #[cxx_name = "decode"]
+        fn decode_image(self: &Decoder) -> Result<Vec<u8>>;
+        /// Removes all the pushed packets.
+        fn reset(self: &mut Decoder);
+    }
+}
+
+pub use ffi::{Format, PacketInfo};
+
+impl TryFrom<Format> for ssdv_fec::format::Format {
+    type Error = Error;
+
+    fn try_from(format: Format) -> Result<ssdv_fec::format::Format, Error> {
+        match format {
+            Format::Lj2 => Ok(ssdv_fec::format::Format::Lj2),
+            Format::NoFec => Ok(ssdv_fec::format::Format::NoFec),
+            // C++ can pass values that are not in the enum
+            format => Err(Error::InvalidFormat(format.repr)),
+        }
+    }
+}
+
+/// Error produced by the bindings.
+///
+/// Errors are thrown in C++ as `rust::Error`, whose `what()` returns the
+/// error message.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The packet format is not valid.
+    #[error("invalid packet format {0}")]
+    InvalidFormat(u8),
+    /// The length of the input is not valid for the packet format.
+    #[error("invalid length {len} for packets of {packet_len} bytes")]
+    InvalidLength {
+        /// Length of the input.
+        len: usize,
+        /// Length of a packet in the selected format.
+        packet_len: usize,
+    },
+    /// The range of packet IDs to encode goes beyond 65534.
+    #[error("packet IDs out of range")]
+    InvalidRange,
+    /// Error produced by the encoder.
+    #[error(transparent)]
+    Encoder(#[from] ssdv_fec::EncoderError),
+    /// Error produced by the decoder.
+    #[error(transparent)]
+    Decoder(#[from] ssdv_fec::DecoderError),
+}
+
+/// Returns the length in bytes of a packet in a format.
+pub fn packet_len(format: Format) -> Result<usize, Error> {
+    Ok(ssdv_fec::format::Format::try_from(format)?.packet_len())
+}
+
+/// Returns the header fields of a single SSDV packet.
+pub fn packet_info(packet: &[u8], format: Format) -> Result<PacketInfo, Error> {
+    let info = ssdv_fec::format::Format::try_from(format)?
+        .packet_info(packet)
+        .ok_or_else(|| invalid_length(packet, format))?;
+    Ok(PacketInfo {
+        image_id: info.image_id,
+        packet_id: info.packet_id,
+        width: info.width.unwrap_or(0),
+        height: info.height.unwrap_or(0),
+        num_systematic: info.num_systematic.unwrap_or(0),
+        flags: info.flags,
+        eoi: info.eoi,
+        fec_packet: info.fec_packet,
+        crc_ok: info.crc_ok,
+    })
+}
+
+/// Decodes an SSDV image from received packets.
+///
+/// The packets can be in any order and contain duplicates. Packets with a
+/// wrong CRC-32 are ignored. If `lenient` is true, inconsistent headers are
+/// resolved by majority vote instead of failing. Returns the systematic
+/// packets of the image concatenated.
+pub fn decode(packets: &[u8], format: Format, lenient: bool) -> Result<Vec<u8>, Error> {
+    check_length(packets, format)?;
+    let options = DecoderOptions::new().lenient(lenient);
+    Ok(ssdv_fec::format::Format::try_from(format)?.decode(packets, &options)?)
+}
+
+/// SSDV FEC encoder.
+///
+/// The encoder is created with [`new_encoder`] from the systematic packets of
+/// an image and can generate any packet ID from 0 to 65534.
+pub struct Encoder {
+    inner: AnyEncoder,
+}
+
+/// Creates an encoder for an image given by its systematic packets.
+pub fn new_encoder(image: &[u8], format: Format) -> Result<Box<Encoder>, Error> {
+    check_length(image, format)?;
+    let inner = AnyEncoder::new(format.try_into()?, image)?;
+    Ok(Box::new(Encoder { inner }))
+}
+
+impl Encoder {
+    /// Generates the packet with a packet ID.
+    pub fn encode(&self, packet_id: u16) -> Result<Vec<u8>, Error> {
+        self.encode_range(packet_id, 1)
+    }
+
+    /// Generates `count` packets with consecutive packet IDs starting at
+    /// `first_id`, concatenated.
+    pub fn encode_range(&self, first_id: u16, count: u16) -> Result<Vec<u8>, Error> {
+        // Packet ID 0xffff cannot be encoded.
+        if u32::from(first_id) + u32::from(count) > u32::from(u16::MAX) {
+            return Err(Error::InvalidRange);
+        }
+        Ok(self.inner.encode_range(first_id, usize::from(count))?)
+    }
+
+    /// Returns the number of systematic packets of the image.
+    pub fn num_systematic(&self) -> u16 {
+        self.inner.num_systematic()
+    }
+
+    /// Returns the image ID of the image.
+    pub fn image_id(&self) -> u8 {
+        self.inner.image_id()
+    }
+}
+
+/// SSDV FEC decoder.
+///
+/// The decoder is created with [`new_decoder`]. Received packets are pushed
+/// into the decoder one by one as they arrive, and the image is decoded once
+/// enough packets have been pushed.
+pub struct Decoder {
+    format: Format,
+    lenient: bool,
+    // Concatenated packets with a correct CRC-32.
+    packets: Vec<u8>,
+}
+
+/// Creates an empty decoder.
+pub fn new_decoder(format: Format, lenient: bool) -> Result<Box<Decoder>, Error> {
+    // check that the format is valid
+    packet_len(format)?;
+    Ok(Box::new(Decoder {
+        format,
+        lenient,
+        packets: Vec::new(),
+    }))
+}
+
+impl Decoder {
+    /// Pushes a received packet.
+    ///
+    /// Returns false if the packet is dropped because its CRC-32 is wrong.
+    pub fn push(&mut self, packet: &[u8]) -> Result<bool, Error> {
+        if !packet_info(packet, self.format)?.crc_ok {
+            return Ok(false);
+        }
+        self.packets.extend_from_slice(packet);
+        Ok(true)
+    }
+
+    /// Returns the number of packets that have been pushed and kept.
+    pub fn num_packets(&self) -> usize {
+        // the format has been checked when creating the decoder
+        self.packets.len() / packet_len(self.format).unwrap()
+    }
+
+    /// Decodes the image from the pushed packets, returning its systematic
+    /// packets concatenated.
+    ///
+    /// This is called `decode` in C++.
+    pub fn decode_image(&self) -> Result<Vec<u8>, Error> {
+        decode(&self.packets, self.format, self.lenient)
+    }
+
+    /// Removes all the pushed packets.
+    pub fn reset(&mut self) {
+        self.packets.clear();
+    }
+}
+
+fn invalid_length(bytes: &[u8], format: Format) -> Error {
+    Error::InvalidLength {
+        len: bytes.len(),
+        packet_len: packet_len(format).unwrap_or(0),
+    }
+}
+
+// Checks that the input contains whole packets.
+fn check_length(bytes: &[u8], format: Format) -> Result<(), Error> {
+    if bytes.len().is_multiple_of(packet_len(format)?) {
+        Ok(())
+    } else {
+        Err(invalid_length(bytes, format))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static IMG_230_SSDV: &[u8] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+
+    // C++ can pass any value of the underlying integer type of the enum.
+    const BAD_FORMAT: Format = Format { repr: 7 };
+
+    #[test]
+    fn invalid_format() {
+        assert!(matches!(
+            packet_len(BAD_FORMAT),
+            Err(Error::InvalidFormat(7))
+        ));
+        assert!(matches!(
+            packet_info(&IMG_230_SSDV[..218], BAD_FORMAT),
+            Err(Error::InvalidFormat(7))
+        ));
+        assert!(matches!(
+            decode(IMG_230_SSDV, BAD_FORMAT, false),
+            Err(Error::InvalidFormat(7))
+        ));
+        assert!(matches!(
+            new_encoder(IMG_230_SSDV, BAD_FORMAT),
+            Err(Error::InvalidFormat(7))
+        ));
+        assert!(matches!(
+            new_decoder(BAD_FORMAT, false),
+            Err(Error::InvalidFormat(7))
+        ));
+        assert_eq!(
+            new_decoder(BAD_FORMAT, false).err().unwrap().to_string(),
+            "invalid packet format 7"
+        );
+    }
+
+    #[test]
+    fn boxed_encoder_owns_image() {
+        // the encoder copies the image, so the buffer given by C++ can be
+        // freed once the encoder has been created
+        let image = IMG_230_SSDV.to_vec();
+        let encoder = new_encoder(&image, Format::NoFec).err();
+        assert!(matches!(encoder, Some(Error::InvalidLength { .. })));
+        let encoder = new_encoder(&image, Format::Lj2).unwrap();
+        drop(image);
+        let k = encoder.num_systematic();
+        // the boxes can be moved around, as a rust::Box in C++
+        let encoders = vec![encoder];
+        let mut decoder = new_decoder(Format::Lj2, false).unwrap();
+        assert!(decoder.push(&encoders[0].encode(k - 1).unwrap()).unwrap());
+        let fec = encoders[0].encode_range(k + 1, k - 1).unwrap();
+        drop(encoders);
+        for packet in fec.chunks_exact(packet_len(Format::Lj2).unwrap()) {
+            assert!(decoder.push(packet).unwrap());
+        }
+        assert_eq!(decoder.num_packets(), usize::from(k));
+        assert_eq!(decoder.decode_image().unwrap(), IMG_230_SSDV);
+        decoder.reset();
+        assert_eq!(decoder.num_packets(), 0);
+    }
+
+    #[test]
+    fn packet_info_zero_fields() {
+        // C++ has no Option, so the missing fields are 0
+        let n = packet_len(Format::Lj2).unwrap();
+        let k = IMG_230_SSDV.len() / n;
+        let last = packet_info(&IMG_230_SSDV[(k - 1) * n..], Format::Lj2).unwrap();
+        assert_eq!(last.num_systematic, 0);
+        let encoder = new_encoder(IMG_230_SSDV, Format::Lj2).unwrap();
+        let fec = packet_info(&encoder.encode(k as u16).unwrap(), Format::Lj2).unwrap();
+        assert_eq!((fec.width, fec.height), (0, 0));
+        assert_eq!(fec.num_systematic, k as u16);
+        assert!(matches!(encoder.encode(u16::MAX), Err(Error::InvalidRange)));
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    let mut config = cbindgen::Config::default();
+    config.language = cbindgen::Language::C;
+    config.usize_is_size_t = true;
+
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate bindings")
+        .write_to_file("ssdv_fec_gr.h");
+}
@@ -0,0 +1,45 @@
+"""GNU Radio blocks using the ssdv-fec-gr shared library.
+
+The decoder block can be used directly as an Embedded Python Block in GNU
+Radio Companion. It receives PDUs with one or several concatenated packets in
+the 'in' message port, for instance from a 'Tagged Stream to PDU' block
+preceded by a 'Stream to Tagged Stream' block with a packet length of
+ssdv_fec_gr.packet_len(fmt), and sends each decoded image as a PDU with the
+systematic packets of the image in the 'out' message port.
+"""
+
+import numpy as np
+import pmt
+from gnuradio import gr
+
+import ssdv_fec_gr
+
+
+class ssdv_fec_decoder(gr.basic_block):
+    """SSDV FEC decoder
+
+    Parameters:
+        fmt: packet format (0 for Longjiang-2, 1 for no-FEC SSDV)
+        lenient: resolve inconsistent headers by majority vote
+    """
+
+    def __init__(self, fmt=ssdv_fec_gr.FORMAT_LJ2, lenient=False):
+        gr.basic_block.__init__(
+            self, name='SSDV FEC decoder', in_sig=None, out_sig=None)
+        self.decoder = ssdv_fec_gr.Decoder(fmt, lenient)
+        self.message_port_register_in(pmt.intern('in'))
+        self.set_msg_handler(pmt.intern('in'), self.handle_msg)
+        self.message_port_register_out(pmt.intern('out'))
+
+    def handle_msg(self, msg):
+        data = bytes(pmt.u8vector_elements(pmt.cdr(msg)))
+        try:
+            self.decoder.push(data)
+        except ValueError:
+            gr.log.warn(f'dropping PDU with invalid length {len(data)}')
+            return
+        while (image := self.decoder.pull()) is not None:
+            image = np.frombuffer(image, dtype=np.uint8)
+            self.message_port_pub(
+                pmt.intern('out'),
+                pmt.cons(pmt.PMT_NIL, pmt.init_u8vector(len(image), image)))
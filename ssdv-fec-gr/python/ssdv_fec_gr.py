@@ -0,0 +1,92 @@
+"""ctypes wrapper of the ssdv-fec-gr shared library.
+
+The library is loaded from the path in the SSDV_FEC_GR_LIB environment
+variable, or by name from the default library search path.
+"""
+
+import ctypes
+import ctypes.util
+import os
+
+ABI_VERSION = 1
+
+FORMAT_LJ2 = 0
+FORMAT_NO_FEC = 1
+
+ERR_NULL_POINTER = -1
+ERR_INVALID_LENGTH = -2
+ERR_OUTPUT_TOO_SHORT = -3
+
+
+def _load():
+    path = os.environ.get('SSDV_FEC_GR_LIB') or ctypes.util.find_library(
+        'ssdv_fec_gr')
+    if path is None:
+        raise OSError('ssdv-fec-gr library not found')
+    lib = ctypes.CDLL(path)
+    lib.ssdv_fec_gr_abi_version.restype = ctypes.c_uint
+    lib.ssdv_fec_gr_packet_len.argtypes = [ctypes.c_int]
+    lib.ssdv_fec_gr_packet_len.restype = ctypes.c_size_t
+    lib.ssdv_fec_gr_decoder_new.argtypes = [ctypes.c_int, ctypes.c_bool]
+    lib.ssdv_fec_gr_decoder_new.restype = ctypes.c_void_p
+    lib.ssdv_fec_gr_decoder_free.argtypes = [ctypes.c_void_p]
+    lib.ssdv_fec_gr_decoder_free.restype = None
+    lib.ssdv_fec_gr_decoder_push.argtypes = [
+        ctypes.c_void_p, ctypes.c_char_p, ctypes.c_size_t]
+    lib.ssdv_fec_gr_decoder_push.restype = ctypes.c_int
+    lib.ssdv_fec_gr_decoder_image_len.argtypes = [ctypes.c_void_p]
+    lib.ssdv_fec_gr_decoder_image_len.restype = ctypes.c_size_t
+    lib.ssdv_fec_gr_decoder_pull.argtypes = [
+        ctypes.c_void_p, ctypes.c_char_p, ctypes.c_size_t,
+        ctypes.POINTER(ctypes.c_size_t)]
+    lib.ssdv_fec_gr_decoder_pull.restype = ctypes.c_int
+    lib.ssdv_fec_gr_decoder_reset.argtypes = [ctypes.c_void_p]
+    lib.ssdv_fec_gr_decoder_reset.restype = None
+    version = lib.ssdv_fec_gr_abi_version()
+    if version != ABI_VERSION:
+        raise OSError(f'ssdv-fec-gr ABI version {version} is not supported')
+    return lib
+
+
+_lib = _load()
+
+
+def packet_len(fmt=FORMAT_LJ2):
+    """Returns the length in bytes of a packet in a format."""
+    return _lib.ssdv_fec_gr_packet_len(fmt)
+
+
+class Decoder:
+    """SSDV FEC decoder that follows the images of a downlink."""
+
+    def __init__(self, fmt=FORMAT_LJ2, lenient=False):
+        self._decoder = _lib.ssdv_fec_gr_decoder_new(fmt, lenient)
+
+    def __del__(self):
+        _lib.ssdv_fec_gr_decoder_free(self._decoder)
+
+    def push(self, data):
+        """Pushes one or several concatenated packets.
+
+        Returns the number of images that have been decoded.
+        """
+        data = bytes(data)
+        ret = _lib.ssdv_fec_gr_decoder_push(self._decoder, data, len(data))
+        if ret < 0:
+            raise ValueError(f'ssdv_fec_gr_decoder_push failed ({ret})')
+        return ret
+
+    def pull(self):
+        """Returns the next decoded image, or None if there are none."""
+        output = ctypes.create_string_buffer(
+            _lib.ssdv_fec_gr_decoder_image_len(self._decoder))
+        image_len = ctypes.c_size_t()
+        ret = _lib.ssdv_fec_gr_decoder_pull(
+            self._decoder, output, len(output), ctypes.byref(image_len))
+        if ret < 0:
+            raise ValueError(f'ssdv_fec_gr_decoder_pull failed ({ret})')
+        return output.raw[:image_len.value] if ret else None
+
+    def reset(self):
+        """Discards the received packets and the decoded images."""
+        _lib.ssdv_fec_gr_decoder_reset(self._decoder)
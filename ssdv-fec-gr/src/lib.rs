@@ -0,0 +1,481 @@
+//! # SSDV FEC shared library for GNU Radio
+//!
+//! This crate builds a shared library with a small C API to decode the SSDV
+//! FEC packets of a live downlink inside a GNU Radio flowgraph. Received
+//! packets are pushed into a decoder as they arrive, in chunks of one or
+//! several packets as produced by tagged streams or PDUs, and the images are
+//! pulled from the decoder once they have been decoded. The decoder follows
+//! the images transmitted one after another, so it does not need to be reset
+//! between images.
+//!
+//! The API is intended to be stable, so that it can be used through `ctypes`
+//! from Python blocks. A C header is generated using `cbindgen`.
+
+use ssdv_fec::{Decoder, DecoderOptions, Packet, SSDVNoFecPacket, SSDVPacket};
+use std::{
+    collections::VecDeque,
+    ffi::{c_int, c_uint},
+    ptr, slice,
+};
+
+/// SSDV packet format.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ssdv_fec_gr_format_t {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields
+    SSDV_FEC_GR_FORMAT_LJ2 = 0,
+    /// Standard 256-byte no-FEC SSDV format
+    SSDV_FEC_GR_FORMAT_NO_FEC = 1,
+}
+
+// Evaluates an expression that uses the packet type `$packet` corresponding to
+// a format.
+macro_rules! with_format {
+    ($format:expr, $packet:ident => $body:expr) => {
+        match $format {
+            ssdv_fec_gr_format_t::SSDV_FEC_GR_FORMAT_LJ2 => {
+                type $packet = SSDVPacket;
+                $body
+            }
+            ssdv_fec_gr_format_t::SSDV_FEC_GR_FORMAT_NO_FEC => {
+                type $packet = SSDVNoFecPacket;
+                $body
+            }
+        }
+    };
+}
+
+/// SSDV FEC decoder.
+///
+/// The decoder is created with [`ssdv_fec_gr_decoder_new`] and destroyed with
+/// [`ssdv_fec_gr_decoder_free`]. Its contents are private.
+#[allow(non_camel_case_types)]
+pub struct ssdv_fec_gr_decoder_t {
+    inner: Box<dyn AnyDecoder>,
+}
+
+/// Returns the version of the API of the library.
+///
+/// The version is increased when the API changes in an incompatible way, so
+/// that wrappers can check that they are using a compatible library.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_gr_abi_version() -> c_uint {
+    SSDV_FEC_GR_ABI_VERSION
+}
+
+/// Returns the length in bytes of an SSDV packet in a format.
+///
+/// Tagged streams and PDUs given to [`ssdv_fec_gr_decoder_push`] must have a
+/// length that is a multiple of this length.
+#[no_mangle]
+pub extern "C" fn ssdv_fec_gr_packet_len(format: ssdv_fec_gr_format_t) -> usize {
+    with_format!(format, P => P::LEN)
+}
+
+/// Creates a new SSDV FEC decoder.
+///
+/// If `lenient` is false, decoding fails if the headers of the packets of an
+/// image are inconsistent. If `lenient` is true, the inconsistencies are
+/// resolved by majority vote. The decoder must be destroyed with
+/// [`ssdv_fec_gr_decoder_free`].
+#[no_mangle]
+pub extern "C" fn ssdv_fec_gr_decoder_new(
+    format: ssdv_fec_gr_format_t,
+    lenient: bool,
+) -> *mut ssdv_fec_gr_decoder_t {
+    let inner: Box<dyn AnyDecoder> = with_format!(format, P => {
+        Box::new(ImageDecoder::<P>::new(lenient))
+    });
+    Box::into_raw(Box::new(ssdv_fec_gr_decoder_t { inner }))
+}
+
+/// Destroys an SSDV FEC decoder.
+///
+/// Passing `NULL` does nothing.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or a decoder returned by
+/// [`ssdv_fec_gr_decoder_new`] that has not been destroyed.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_gr_decoder_free(decoder: *mut ssdv_fec_gr_decoder_t) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Pushes received packets into an SSDV FEC decoder.
+///
+/// The `data` array contains `len` bytes formed by one or several
+/// concatenated packets. Packets with a wrong CRC-32 and duplicated packets are
+/// dropped. When a packet of a different image ID arrives, the packets of the
+/// previous image are discarded. An image is decoded as soon as enough packets
+/// have been pushed, and it is queued until it is pulled with
+/// [`ssdv_fec_gr_decoder_pull`].
+///
+/// The function returns the number of images that have been decoded, or a
+/// negative error code. `SSDV_FEC_GR_ERR_NULL_POINTER` is returned if
+/// `decoder` or `data` are `NULL`, and `SSDV_FEC_GR_ERR_INVALID_LENGTH` if
+/// `len` is not a multiple of the packet length, in which case no packets are
+/// pushed.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or a valid decoder, and `data` must
+/// point to at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_gr_decoder_push(
+    decoder: *mut ssdv_fec_gr_decoder_t,
+    data: *const u8,
+    len: usize,
+) -> c_int {
+    if decoder.is_null() || data.is_null() {
+        return SSDV_FEC_GR_ERR_NULL_POINTER;
+    }
+    let data = slice::from_raw_parts(data, len);
+    (*decoder).inner.push(data)
+}
+
+/// Returns the length in bytes of the next image queued in an SSDV FEC
+/// decoder, or zero if there are no images.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_gr_decoder_image_len(
+    decoder: *const ssdv_fec_gr_decoder_t,
+) -> usize {
+    if decoder.is_null() {
+        return 0;
+    }
+    (*decoder).inner.next_image().map_or(0, |image| image.len())
+}
+
+/// Pulls the next decoded image from an SSDV FEC decoder.
+///
+/// The systematic packets of the oldest queued image are written to the
+/// `output` array, whose capacity in bytes is `output_len`, and the length of
+/// the image is written to `image_len`. The image ID of the image is the image
+/// ID of its packets.
+///
+/// The function returns 1 if an image has been written, 0 if there are no
+/// queued images, or a negative error code. `SSDV_FEC_GR_ERR_NULL_POINTER` is
+/// returned if any of the pointers are `NULL`, and
+/// `SSDV_FEC_GR_ERR_OUTPUT_TOO_SHORT` if the image does not fit in `output`,
+/// in which case the length of the image is written to `image_len` and the
+/// image stays queued.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or a valid decoder, `output` must
+/// point to at least `output_len` bytes, and `image_len` must be `NULL` or
+/// point to valid storage.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_gr_decoder_pull(
+    decoder: *mut ssdv_fec_gr_decoder_t,
+    output: *mut u8,
+    output_len: usize,
+    image_len: *mut usize,
+) -> c_int {
+    if decoder.is_null() || output.is_null() || image_len.is_null() {
+        return SSDV_FEC_GR_ERR_NULL_POINTER;
+    }
+    let decoder = &mut (*decoder).inner;
+    let Some(image) = decoder.next_image() else {
+        *image_len = 0;
+        return 0;
+    };
+    *image_len = image.len();
+    if image.len() > output_len {
+        return SSDV_FEC_GR_ERR_OUTPUT_TOO_SHORT;
+    }
+    ptr::copy_nonoverlapping(image.as_ptr(), output, image.len());
+    decoder.pop_image();
+    1
+}
+
+/// Discards the received packets and the queued images of an SSDV FEC
+/// decoder.
+///
+/// # Safety
+///
+/// The `decoder` parameter must be `NULL` or a valid decoder.
+#[no_mangle]
+pub unsafe extern "C" fn ssdv_fec_gr_decoder_reset(decoder: *mut ssdv_fec_gr_decoder_t) {
+    if !decoder.is_null() {
+        (*decoder).inner.reset();
+    }
+}
+
+// Decoder for any packet format.
+trait AnyDecoder {
+    fn push(&mut self, data: &[u8]) -> c_int;
+    fn next_image(&self) -> Option<&[u8]>;
+    fn pop_image(&mut self);
+    fn reset(&mut self);
+}
+
+// Maximum number of decoded images that are queued. The oldest image is
+// dropped if a new one is decoded when the queue is full.
+const MAX_QUEUED_IMAGES: usize = 16;
+
+// Decoder that follows the images of a downlink.
+struct ImageDecoder<P> {
+    lenient: bool,
+    // Image ID of the image being received, if any.
+    image_id: Option<u8>,
+    // Distinct packets of the image being received.
+    packets: Vec<P>,
+    // Number of systematic packets of the image being received, if known.
+    num_systematic: Option<u16>,
+    // Whether the image being received has already been decoded.
+    decoded: bool,
+    images: VecDeque<Vec<u8>>,
+}
+
+impl<P: Packet + Clone> ImageDecoder<P> {
+    fn new(lenient: bool) -> ImageDecoder<P> {
+        ImageDecoder {
+            lenient,
+            image_id: None,
+            packets: Vec::new(),
+            num_systematic: None,
+            decoded: false,
+            images: VecDeque::new(),
+        }
+    }
+
+    // Pushes a single packet, returning true if an image has been decoded.
+    fn push_packet(&mut self, packet: P) -> bool {
+        if packet.compute_crc32() != packet.crc32() {
+            return false;
+        }
+        if self.image_id != Some(packet.image_id()) {
+            self.start_image();
+            self.image_id = Some(packet.image_id());
+        }
+        if self.decoded
+            || self
+                .packets
+                .iter()
+                .any(|p| p.packet_id() == packet.packet_id())
+        {
+            return false;
+        }
+        if let Some(k) = packet.number_systematic_packets() {
+            self.num_systematic = Some(k);
+        } else if packet.is_eoi() {
+            self.num_systematic = Some(packet.packet_id().saturating_add(1));
+        }
+        self.packets.push(packet);
+        match self.num_systematic {
+            Some(k) if self.packets.len() >= usize::from(k) => self.decode(),
+            _ => false,
+        }
+    }
+
+    // Attempts to decode the image being received, returning true if it has
+    // been decoded.
+    fn decode(&mut self) -> bool {
+        // The decoder modifies its input, so it works on a copy to be able to
+        // retry when more packets arrive.
+        let mut input = self.packets.clone();
+        let mut output = (0..input.len()).map(|_| P::zeroed()).collect::<Vec<_>>();
+        let options = DecoderOptions::new().lenient(self.lenient);
+        match Decoder::decode_with_options(&mut input, &mut output, &options) {
+            Ok((decoded, _)) => {
+                if self.images.len() == MAX_QUEUED_IMAGES {
+                    self.images.pop_front();
+                }
+                self.images.push_back(
+                    decoded
+                        .iter()
+                        .flat_map(|packet| packet.as_bytes())
+                        .copied()
+                        .collect(),
+                );
+                self.decoded = true;
+                // The packets are no longer needed, but the image ID is kept
+                // to drop the remaining packets of the image.
+                self.packets.clear();
+                true
+            }
+            // Decoding is retried when more packets arrive, for instance if
+            // there are no systematic packets yet.
+            Err(_) => false,
+        }
+    }
+
+    fn start_image(&mut self) {
+        self.image_id = None;
+        self.packets.clear();
+        self.num_systematic = None;
+        self.decoded = false;
+    }
+}
+
+impl<P: Packet + Clone> AnyDecoder for ImageDecoder<P> {
+    fn push(&mut self, data: &[u8]) -> c_int {
+        if !data.len().is_multiple_of(P::LEN) {
+            return SSDV_FEC_GR_ERR_INVALID_LENGTH;
+        }
+        let mut num_images = 0;
+        for chunk in data.chunks_exact(P::LEN) {
+            let mut packet = P::zeroed();
+            packet.as_bytes_mut().copy_from_slice(chunk);
+            if self.push_packet(packet) {
+                num_images += 1;
+            }
+        }
+        num_images
+    }
+
+    fn next_image(&self) -> Option<&[u8]> {
+        self.images.front().map(|image| &image[..])
+    }
+
+    fn pop_image(&mut self) {
+        self.images.pop_front();
+    }
+
+    fn reset(&mut self) {
+        self.start_image();
+        self.images.clear();
+    }
+}
+
+/// Version of the API of the library
+pub const SSDV_FEC_GR_ABI_VERSION: c_uint = 1;
+
+// Error codes
+
+/// NULL pointer argument
+pub const SSDV_FEC_GR_ERR_NULL_POINTER: c_int = -1;
+/// Length is not a multiple of the packet length
+pub const SSDV_FEC_GR_ERR_INVALID_LENGTH: c_int = -2;
+/// Output buffer is too short
+pub const SSDV_FEC_GR_ERR_OUTPUT_TOO_SHORT: c_int = -3;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ssdv_fec::{Encoder, SSDV_PACKET_LEN};
+
+    static IMG_230_SSDV: &[u8] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+    const LJ2: ssdv_fec_gr_format_t = ssdv_fec_gr_format_t::SSDV_FEC_GR_FORMAT_LJ2;
+
+    // Returns the last systematic packet of the test image followed by FEC
+    // packets for the rest of the image, with the image ID changed.
+    fn received(image_id: u8) -> Vec<u8> {
+        let mut image = IMG_230_SSDV
+            .chunks_exact(SSDV_PACKET_LEN)
+            .map(|chunk| {
+                let mut packet = SSDVPacket(chunk.try_into().unwrap());
+                packet.set_image_id(image_id);
+                packet.set_crc32(packet.compute_crc32());
+                packet
+            })
+            .collect::<Vec<_>>();
+        let k = image.len() as u16;
+        let encoder = Encoder::new(&mut image).unwrap();
+        let mut packets = vec![SSDVPacket::zeroed(); usize::from(k)];
+        encoder.encode(k - 1, &mut packets[0]);
+        encoder.encode_range(k + 1, &mut packets[1..]);
+        packets.iter().flat_map(|packet| packet.0).collect()
+    }
+
+    #[test]
+    fn push_pull() {
+        let len = ssdv_fec_gr_packet_len(LJ2);
+        let first = received(1);
+        let second = received(2);
+        unsafe {
+            let decoder = ssdv_fec_gr_decoder_new(LJ2, false);
+            let mut output = vec![0u8; IMG_230_SSDV.len()];
+            let mut image_len = 0;
+            // the first image is pushed in chunks of several packets
+            for chunk in first[..first.len() - len].chunks(4 * len) {
+                assert_eq!(
+                    ssdv_fec_gr_decoder_push(decoder, chunk.as_ptr(), chunk.len()),
+                    0
+                );
+            }
+            assert_eq!(ssdv_fec_gr_decoder_image_len(decoder), 0);
+            assert_eq!(
+                ssdv_fec_gr_decoder_pull(
+                    decoder,
+                    output.as_mut_ptr(),
+                    output.len(),
+                    &mut image_len
+                ),
+                0
+            );
+            let last = &first[first.len() - len..];
+            assert_eq!(ssdv_fec_gr_decoder_push(decoder, last.as_ptr(), len), 1);
+            // duplicates after decoding are ignored
+            assert_eq!(ssdv_fec_gr_decoder_push(decoder, last.as_ptr(), len), 0);
+            // the second image is pushed in one go
+            assert_eq!(
+                ssdv_fec_gr_decoder_push(decoder, second.as_ptr(), second.len()),
+                1
+            );
+            assert_eq!(ssdv_fec_gr_decoder_image_len(decoder), IMG_230_SSDV.len());
+            assert_eq!(
+                ssdv_fec_gr_decoder_pull(decoder, output.as_mut_ptr(), len, &mut image_len),
+                SSDV_FEC_GR_ERR_OUTPUT_TOO_SHORT
+            );
+            assert_eq!(image_len, IMG_230_SSDV.len());
+            for image_id in [1, 2] {
+                assert_eq!(
+                    ssdv_fec_gr_decoder_pull(
+                        decoder,
+                        output.as_mut_ptr(),
+                        output.len(),
+                        &mut image_len
+                    ),
+                    1
+                );
+                assert_eq!(image_len, IMG_230_SSDV.len());
+                assert!(output.chunks_exact(len).all(|p| p[0] == image_id));
+            }
+            assert_eq!(ssdv_fec_gr_decoder_image_len(decoder), 0);
+            ssdv_fec_gr_decoder_free(decoder);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let packets = received(1);
+        unsafe {
+            let decoder = ssdv_fec_gr_decoder_new(LJ2, true);
+            assert_eq!(
+                ssdv_fec_gr_decoder_push(decoder, packets.as_ptr(), 100),
+                SSDV_FEC_GR_ERR_INVALID_LENGTH
+            );
+            assert_eq!(
+                ssdv_fec_gr_decoder_push(ptr::null_mut(), packets.as_ptr(), 0),
+                SSDV_FEC_GR_ERR_NULL_POINTER
+            );
+            // corrupted packets are dropped
+            let mut corrupted = packets.clone();
+            corrupted[20] ^= 1;
+            assert_eq!(
+                ssdv_fec_gr_decoder_push(decoder, corrupted.as_ptr(), corrupted.len()),
+                0
+            );
+            ssdv_fec_gr_decoder_reset(decoder);
+            assert_eq!(
+                ssdv_fec_gr_decoder_push(decoder, packets.as_ptr(), packets.len()),
+                1
+            );
+            ssdv_fec_gr_decoder_reset(decoder);
+            assert_eq!(ssdv_fec_gr_decoder_image_len(decoder), 0);
+            ssdv_fec_gr_decoder_free(decoder);
+            ssdv_fec_gr_decoder_free(ptr::null_mut());
+        }
+        assert_eq!(ssdv_fec_gr_abi_version(), SSDV_FEC_GR_ABI_VERSION);
+    }
+}
@@ -0,0 +1,342 @@
+//! # Python bindings of the SSDV systematic erasure FEC
+//!
+//! This crate exposes the encoder, the decoder and the packet parser of the
+//! [`ssdv_fec`] crate as a Python extension module built with PyO3. Packets are
+//! passed as `bytes` objects containing one or several concatenated packets in
+//! the format selected with [`Format`], and the results are returned as
+//! `bytes`, which can be viewed as a NumPy array of packets with
+//! `numpy.frombuffer(data, dtype=numpy.uint8).reshape(-1, packet_len)`.
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+use ssdv_fec::{format::AnyEncoder, DecoderOptions};
+
+/// SSDV packet format.
+#[pyclass(eq, eq_int, frozen, from_py_object, module = "ssdv_fec")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Format {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields.
+    #[pyo3(name = "LJ2")]
+    Lj2,
+    /// Standard 256-byte no-FEC SSDV format.
+    #[pyo3(name = "NO_FEC")]
+    NoFec,
+}
+
+impl From<Format> for ssdv_fec::format::Format {
+    fn from(format: Format) -> ssdv_fec::format::Format {
+        match format {
+            Format::Lj2 => ssdv_fec::format::Format::Lj2,
+            Format::NoFec => ssdv_fec::format::Format::NoFec,
+        }
+    }
+}
+
+#[pymethods]
+impl Format {
+    /// Length in bytes of a packet in this format.
+    #[getter]
+    fn packet_len(&self) -> usize {
+        ssdv_fec::format::Format::from(*self).packet_len()
+    }
+}
+
+/// SSDV FEC encoder.
+///
+/// The encoder is created from the systematic packets of an image and can
+/// generate any packet ID from 0 to 65534.
+#[pyclass(frozen, module = "ssdv_fec")]
+pub struct Encoder {
+    inner: AnyEncoder,
+}
+
+#[pymethods]
+impl Encoder {
+    #[new]
+    #[pyo3(signature = (image, format = Format::Lj2))]
+    fn new(image: &[u8], format: Format) -> PyResult<Encoder> {
+        check_length(image, format)?;
+        let inner = AnyEncoder::new(format.into(), image).map_err(value_error)?;
+        Ok(Encoder { inner })
+    }
+
+    /// Generates the packet with a packet ID.
+    fn encode<'py>(&self, py: Python<'py>, packet_id: u16) -> PyResult<Bound<'py, PyBytes>> {
+        self.encode_range(py, packet_id, 1)
+    }
+
+    /// Generates `count` packets with consecutive packet IDs starting at
+    /// `first_id`, concatenated.
+    fn encode_range<'py>(
+        &self,
+        py: Python<'py>,
+        first_id: u16,
+        count: u16,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        // Packet ID 0xffff cannot be encoded.
+        if u32::from(first_id) + u32::from(count) > u32::from(u16::MAX) {
+            return Err(PyValueError::new_err("packet IDs out of range"));
+        }
+        let packets = self
+            .inner
+            .encode_range(first_id, usize::from(count))
+            .map_err(value_error)?;
+        Ok(PyBytes::new(py, &packets))
+    }
+
+    /// Number of systematic packets of the image.
+    #[getter]
+    fn num_systematic(&self) -> u16 {
+        self.inner.num_systematic()
+    }
+
+    /// Image ID of the image.
+    #[getter]
+    fn image_id(&self) -> u8 {
+        self.inner.image_id()
+    }
+}
+
+/// SSDV FEC decoder.
+///
+/// Received packets are pushed into the decoder one by one as they arrive,
+/// and the image is decoded once enough packets have been pushed.
+#[pyclass(module = "ssdv_fec")]
+pub struct Decoder {
+    format: Format,
+    lenient: bool,
+    // Concatenated packets with a correct CRC-32.
+    packets: Vec<u8>,
+}
+
+#[pymethods]
+impl Decoder {
+    #[new]
+    #[pyo3(signature = (format = Format::Lj2, lenient = false))]
+    fn new(format: Format, lenient: bool) -> Decoder {
+        Decoder {
+            format,
+            lenient,
+            packets: Vec::new(),
+        }
+    }
+
+    /// Pushes a received packet.
+    ///
+    /// Returns `False` if the packet is dropped because its CRC-32 is wrong.
+    fn push(&mut self, packet: &[u8]) -> PyResult<bool> {
+        if !packet_info(packet, self.format)?.crc_ok {
+            return Ok(false);
+        }
+        self.packets.extend_from_slice(packet);
+        Ok(true)
+    }
+
+    /// Number of packets that have been pushed and kept.
+    #[getter]
+    fn num_packets(&self) -> usize {
+        self.packets.len() / self.format.packet_len()
+    }
+
+    /// Decodes the image from the pushed packets, returning its systematic
+    /// packets concatenated.
+    fn decode<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        decode(py, &self.packets, self.format, self.lenient)
+    }
+
+    /// Removes all the pushed packets.
+    fn reset(&mut self) {
+        self.packets.clear();
+    }
+}
+
+/// Header fields of an SSDV packet.
+#[pyclass(frozen, get_all, skip_from_py_object, module = "ssdv_fec")]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PacketInfo {
+    /// Image ID.
+    pub image_id: u8,
+    /// Packet ID.
+    pub packet_id: u16,
+    /// Image width in units of 16 pixels, or `None` in FEC packets.
+    pub width: Option<u8>,
+    /// Image height in units of 16 pixels, or `None` in FEC packets.
+    pub height: Option<u8>,
+    /// Number of systematic packets of the image, or `None` in systematic
+    /// packets.
+    pub num_systematic: Option<u16>,
+    /// Flags field.
+    pub flags: u8,
+    /// Whether the EOI flag is set.
+    pub eoi: bool,
+    /// Whether the packet is a FEC packet.
+    pub fec_packet: bool,
+    /// Whether the CRC-32 of the packet is correct.
+    pub crc_ok: bool,
+}
+
+/// Returns the header fields of a single SSDV packet.
+#[pyfunction]
+#[pyo3(signature = (packet, format = Format::Lj2))]
+pub fn packet_info(packet: &[u8], format: Format) -> PyResult<PacketInfo> {
+    let info = ssdv_fec::format::Format::from(format)
+        .packet_info(packet)
+        .ok_or_else(|| invalid_length(packet, format))?;
+    Ok(PacketInfo {
+        image_id: info.image_id,
+        packet_id: info.packet_id,
+        width: info.width,
+        height: info.height,
+        num_systematic: info.num_systematic,
+        flags: info.flags,
+        eoi: info.eoi,
+        fec_packet: info.fec_packet,
+        crc_ok: info.crc_ok,
+    })
+}
+
+/// Decodes an SSDV image from received packets.
+///
+/// The packets can be in any order and contain duplicates. Packets with a
+/// wrong CRC-32 are ignored. Returns the systematic packets of the image
+/// concatenated.
+#[pyfunction]
+#[pyo3(signature = (packets, format = Format::Lj2, lenient = false))]
+pub fn decode<'py>(
+    py: Python<'py>,
+    packets: &[u8],
+    format: Format,
+    lenient: bool,
+) -> PyResult<Bound<'py, PyBytes>> {
+    check_length(packets, format)?;
+    let options = DecoderOptions::new().lenient(lenient);
+    let decoded = ssdv_fec::format::Format::from(format)
+        .decode(packets, &options)
+        .map_err(value_error)?;
+    Ok(PyBytes::new(py, &decoded))
+}
+
+fn value_error(err: impl std::error::Error) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
+fn invalid_length(bytes: &[u8], format: Format) -> PyErr {
+    PyValueError::new_err(format!(
+        "invalid length {} for packets of {} bytes",
+        bytes.len(),
+        format.packet_len()
+    ))
+}
+
+// Checks that the input contains whole packets.
+fn check_length(bytes: &[u8], format: Format) -> PyResult<()> {
+    if bytes.len().is_multiple_of(format.packet_len()) {
+        Ok(())
+    } else {
+        Err(invalid_length(bytes, format))
+    }
+}
+
+/// SSDV systematic erasure FEC.
+#[pymodule]
+#[pyo3(name = "ssdv_fec")]
+fn ssdv_fec_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Format>()?;
+    m.add_class::<Encoder>()?;
+    m.add_class::<Decoder>()?;
+    m.add_class::<PacketInfo>()?;
+    m.add_function(wrap_pyfunction!(packet_info, m)?)?;
+    m.add_function(wrap_pyfunction!(decode, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::ffi::c_str;
+
+    static IMG_230_SSDV: &[u8] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+
+    // Runs a Python script with the module imported as `ssdv_fec` and the test
+    // image as `image`.
+    fn run(script: &std::ffi::CStr) {
+        Python::attach(|py| {
+            let module = PyModule::new(py, "ssdv_fec").unwrap();
+            ssdv_fec_module(&module).unwrap();
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("ssdv_fec", module).unwrap();
+            globals
+                .set_item("image", PyBytes::new(py, IMG_230_SSDV))
+                .unwrap();
+            if let Err(err) = py.run(script, Some(&globals), None) {
+                err.print(py);
+                panic!("Python script failed");
+            }
+        });
+    }
+
+    #[test]
+    fn encode_decode() {
+        run(c_str!(
+            r#"
+fmt = ssdv_fec.Format.LJ2
+n = fmt.packet_len
+encoder = ssdv_fec.Encoder(image, fmt)
+k = encoder.num_systematic
+assert k * n == len(image)
+assert encoder.encode_range(0, k) == image
+assert encoder.image_id == ssdv_fec.packet_info(image[:n]).image_id
+decoder = ssdv_fec.Decoder(fmt)
+assert decoder.push(encoder.encode(k - 1))
+fec = encoder.encode_range(k + 1, k - 1)
+for j in range(k - 1):
+    assert decoder.push(fec[j * n:(j + 1) * n])
+corrupted = bytearray(fec[:n])
+corrupted[20] ^= 1
+assert not decoder.push(bytes(corrupted))
+assert decoder.num_packets == k
+assert decoder.decode() == image
+assert ssdv_fec.decode(encoder.encode(k - 1) + fec) == image
+decoder.reset()
+assert decoder.num_packets == 0
+"#
+        ));
+    }
+
+    #[test]
+    fn packet_info() {
+        run(c_str!(
+            r#"
+n = ssdv_fec.Format.LJ2.packet_len
+k = len(image) // n
+last = ssdv_fec.packet_info(image[-n:], ssdv_fec.Format.LJ2)
+assert last.packet_id == k - 1
+assert last.eoi and not last.fec_packet and last.crc_ok
+assert last.num_systematic is None
+fec = ssdv_fec.packet_info(ssdv_fec.Encoder(image).encode(k))
+assert fec.fec_packet and fec.num_systematic == k and fec.width is None
+assert ssdv_fec.Format.NO_FEC.packet_len == 256
+"#
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        run(c_str!(
+            r#"
+def raises(f):
+    try:
+        f()
+    except ValueError:
+        return True
+    return False
+
+assert raises(lambda: ssdv_fec.packet_info(image[:100]))
+assert raises(lambda: ssdv_fec.Encoder(image, ssdv_fec.Format.NO_FEC))
+assert raises(lambda: ssdv_fec.Encoder(b""))
+assert raises(lambda: ssdv_fec.Encoder(image).encode(65535))
+assert raises(lambda: ssdv_fec.decode(image[:218]))
+"#
+        ));
+    }
+}
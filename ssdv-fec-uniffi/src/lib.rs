@@ -0,0 +1,294 @@
+//! # Kotlin and Swift bindings of the SSDV systematic erasure FEC
+//!
+//! This crate exposes the encoder, the decoder and the packet parser of the
+//! [`ssdv_fec`] crate through [uniffi](https://mozilla.github.io/uniffi-rs/),
+//! so that Android and iOS applications can use them natively. Packets are
+//! passed as byte arrays containing one or several concatenated packets in the
+//! format selected with [`Format`].
+
+use ssdv_fec::{format::AnyEncoder, DecoderOptions};
+use std::sync::{Arc, Mutex};
+
+uniffi::setup_scaffolding!();
+
+/// SSDV packet format.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, uniffi::Enum)]
+pub enum Format {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields.
+    Lj2,
+    /// Standard 256-byte no-FEC SSDV format.
+    NoFec,
+}
+
+impl From<Format> for ssdv_fec::format::Format {
+    fn from(format: Format) -> ssdv_fec::format::Format {
+        match format {
+            Format::Lj2 => ssdv_fec::format::Format::Lj2,
+            Format::NoFec => ssdv_fec::format::Format::NoFec,
+        }
+    }
+}
+
+/// Error produced by the bindings.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum SsdvFecError {
+    /// The length of the input is not valid for the packet format.
+    #[error("invalid length {len} for packets of {packet_len} bytes")]
+    InvalidLength {
+        /// Length of the input.
+        len: usize,
+        /// Length of a packet in the selected format.
+        packet_len: usize,
+    },
+    /// The range of packet IDs to encode goes beyond 65534.
+    #[error("packet IDs out of range")]
+    InvalidRange,
+    /// Error produced by the encoder.
+    #[error(transparent)]
+    Encoder(#[from] ssdv_fec::EncoderError),
+    /// Error produced by the decoder.
+    #[error(transparent)]
+    Decoder(#[from] ssdv_fec::DecoderError),
+}
+
+/// Header fields of an SSDV packet.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, uniffi::Record)]
+pub struct PacketInfo {
+    /// Image ID.
+    pub image_id: u8,
+    /// Packet ID.
+    pub packet_id: u16,
+    /// Image width in units of 16 pixels, or null in FEC packets.
+    pub width: Option<u8>,
+    /// Image height in units of 16 pixels, or null in FEC packets.
+    pub height: Option<u8>,
+    /// Number of systematic packets of the image, or null in systematic
+    /// packets.
+    pub num_systematic: Option<u16>,
+    /// Flags field.
+    pub flags: u8,
+    /// Whether the EOI flag is set.
+    pub eoi: bool,
+    /// Whether the packet is a FEC packet.
+    pub fec_packet: bool,
+    /// Whether the CRC-32 of the packet is correct.
+    pub crc_ok: bool,
+}
+
+/// Returns the length in bytes of a packet in a format.
+#[uniffi::export]
+pub fn packet_len(format: Format) -> u32 {
+    ssdv_fec::format::Format::from(format).packet_len() as u32
+}
+
+/// Returns the header fields of a single SSDV packet.
+#[uniffi::export]
+pub fn packet_info(packet: &[u8], format: Format) -> Result<PacketInfo, SsdvFecError> {
+    let info = ssdv_fec::format::Format::from(format)
+        .packet_info(packet)
+        .ok_or_else(|| invalid_length(packet, format))?;
+    Ok(PacketInfo {
+        image_id: info.image_id,
+        packet_id: info.packet_id,
+        width: info.width,
+        height: info.height,
+        num_systematic: info.num_systematic,
+        flags: info.flags,
+        eoi: info.eoi,
+        fec_packet: info.fec_packet,
+        crc_ok: info.crc_ok,
+    })
+}
+
+/// Decodes an SSDV image from received packets.
+///
+/// The packets can be in any order and contain duplicates. Packets with a
+/// wrong CRC-32 are ignored. If `lenient` is true, inconsistent headers are
+/// resolved by majority vote instead of failing. Returns the systematic
+/// packets of the image concatenated.
+#[uniffi::export]
+pub fn decode(packets: &[u8], format: Format, lenient: bool) -> Result<Vec<u8>, SsdvFecError> {
+    check_length(packets, format)?;
+    let options = DecoderOptions::new().lenient(lenient);
+    Ok(ssdv_fec::format::Format::from(format).decode(packets, &options)?)
+}
+
+/// SSDV FEC encoder.
+///
+/// The encoder is created from the systematic packets of an image and can
+/// generate any packet ID from 0 to 65534.
+#[derive(uniffi::Object)]
+pub struct Encoder {
+    inner: AnyEncoder,
+}
+
+#[uniffi::export]
+impl Encoder {
+    /// Creates an encoder for an image given by its systematic packets.
+    #[uniffi::constructor]
+    pub fn new(image: &[u8], format: Format) -> Result<Arc<Encoder>, SsdvFecError> {
+        check_length(image, format)?;
+        let inner = AnyEncoder::new(format.into(), image)?;
+        Ok(Arc::new(Encoder { inner }))
+    }
+
+    /// Generates the packet with a packet ID.
+    pub fn encode(&self, packet_id: u16) -> Result<Vec<u8>, SsdvFecError> {
+        self.encode_range(packet_id, 1)
+    }
+
+    /// Generates `count` packets with consecutive packet IDs starting at
+    /// `first_id`, concatenated.
+    pub fn encode_range(&self, first_id: u16, count: u16) -> Result<Vec<u8>, SsdvFecError> {
+        // Packet ID 0xffff cannot be encoded.
+        if u32::from(first_id) + u32::from(count) > u32::from(u16::MAX) {
+            return Err(SsdvFecError::InvalidRange);
+        }
+        Ok(self.inner.encode_range(first_id, usize::from(count))?)
+    }
+
+    /// Returns the number of systematic packets of the image.
+    pub fn num_systematic(&self) -> u16 {
+        self.inner.num_systematic()
+    }
+
+    /// Returns the image ID of the image.
+    pub fn image_id(&self) -> u8 {
+        self.inner.image_id()
+    }
+}
+
+/// SSDV FEC decoder.
+///
+/// Received packets are pushed into the decoder one by one as they arrive,
+/// and the image is decoded once enough packets have been pushed. The decoder
+/// can be used from several threads.
+#[derive(uniffi::Object)]
+pub struct Decoder {
+    format: Format,
+    lenient: bool,
+    // Concatenated packets with a correct CRC-32.
+    packets: Mutex<Vec<u8>>,
+}
+
+#[uniffi::export]
+impl Decoder {
+    /// Creates an empty decoder.
+    #[uniffi::constructor]
+    pub fn new(format: Format, lenient: bool) -> Arc<Decoder> {
+        Arc::new(Decoder {
+            format,
+            lenient,
+            packets: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Pushes a received packet.
+    ///
+    /// Returns false if the packet is dropped because its CRC-32 is wrong.
+    pub fn push(&self, packet: &[u8]) -> Result<bool, SsdvFecError> {
+        if !packet_info(packet, self.format)?.crc_ok {
+            return Ok(false);
+        }
+        self.packets.lock().unwrap().extend_from_slice(packet);
+        Ok(true)
+    }
+
+    /// Returns the number of packets that have been pushed and kept.
+    pub fn num_packets(&self) -> u32 {
+        (self.packets.lock().unwrap().len() as u32) / packet_len(self.format)
+    }
+
+    /// Decodes the image from the pushed packets, returning its systematic
+    /// packets concatenated.
+    pub fn decode(&self) -> Result<Vec<u8>, SsdvFecError> {
+        decode(&self.packets.lock().unwrap(), self.format, self.lenient)
+    }
+
+    /// Removes all the pushed packets.
+    pub fn reset(&self) {
+        self.packets.lock().unwrap().clear();
+    }
+}
+
+fn invalid_length(bytes: &[u8], format: Format) -> SsdvFecError {
+    SsdvFecError::InvalidLength {
+        len: bytes.len(),
+        packet_len: packet_len(format) as usize,
+    }
+}
+
+// Checks that the input contains whole packets.
+fn check_length(bytes: &[u8], format: Format) -> Result<(), SsdvFecError> {
+    if bytes.len().is_multiple_of(packet_len(format) as usize) {
+        Ok(())
+    } else {
+        Err(invalid_length(bytes, format))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static IMG_230_SSDV: &[u8] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+
+    #[test]
+    fn encode_decode() {
+        let n = packet_len(Format::Lj2) as usize;
+        let encoder = Encoder::new(IMG_230_SSDV, Format::Lj2).unwrap();
+        let k = encoder.num_systematic();
+        assert_eq!(usize::from(k) * n, IMG_230_SSDV.len());
+        assert_eq!(encoder.encode_range(0, k).unwrap(), IMG_230_SSDV);
+        let decoder = Decoder::new(Format::Lj2, false);
+        assert!(decoder.push(&encoder.encode(k - 1).unwrap()).unwrap());
+        let fec = encoder.encode_range(k + 1, k - 1).unwrap();
+        for packet in fec.chunks_exact(n) {
+            assert!(decoder.push(packet).unwrap());
+        }
+        let mut corrupted = fec[..n].to_vec();
+        corrupted[20] ^= 1;
+        assert!(!decoder.push(&corrupted).unwrap());
+        assert_eq!(decoder.num_packets(), u32::from(k));
+        assert_eq!(decoder.decode().unwrap(), IMG_230_SSDV);
+        decoder.reset();
+        assert_eq!(decoder.num_packets(), 0);
+    }
+
+    #[test]
+    fn info() {
+        let n = packet_len(Format::Lj2) as usize;
+        let k = IMG_230_SSDV.len() / n;
+        let last = packet_info(&IMG_230_SSDV[(k - 1) * n..], Format::Lj2).unwrap();
+        assert_eq!(usize::from(last.packet_id), k - 1);
+        assert!(last.eoi && !last.fec_packet && last.crc_ok);
+        let encoder = Encoder::new(IMG_230_SSDV, Format::Lj2).unwrap();
+        let fec = packet_info(&encoder.encode(k as u16).unwrap(), Format::Lj2).unwrap();
+        assert!(fec.fec_packet && fec.crc_ok);
+        assert_eq!(fec.num_systematic, Some(k as u16));
+        assert_eq!(fec.image_id, encoder.image_id());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            packet_info(&IMG_230_SSDV[..100], Format::Lj2),
+            Err(SsdvFecError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            Encoder::new(IMG_230_SSDV, Format::NoFec),
+            Err(SsdvFecError::InvalidLength { .. })
+        ));
+        let encoder = Encoder::new(IMG_230_SSDV, Format::Lj2).unwrap();
+        assert!(matches!(
+            encoder.encode(u16::MAX),
+            Err(SsdvFecError::InvalidRange)
+        ));
+        assert!(matches!(
+            decode(&IMG_230_SSDV[..218], Format::Lj2, false),
+            Err(SsdvFecError::Decoder(_))
+        ));
+    }
+}
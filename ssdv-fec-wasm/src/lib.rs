@@ -0,0 +1,269 @@
+//! # WebAssembly bindings of the SSDV systematic erasure FEC
+//!
+//! This crate exposes the encoder, the decoder and the packet parser of the
+//! [`ssdv_fec`] crate to JavaScript using `wasm-bindgen`, so that web
+//! applications can encode and decode SSDV FEC packets in the browser. Packets
+//! are passed as `Uint8Array`s containing one or several concatenated packets
+//! in the format selected with [`Format`].
+
+use ssdv_fec::{
+    format::AnyEncoder,
+    transcode::{ssdv_to_jpeg, TranscodeError},
+    DecoderError, DecoderOptions, EncoderError, Packet, SSDVNoFecPacket, SSDVPacket,
+};
+use thiserror::Error;
+use wasm_bindgen::prelude::*;
+
+/// SSDV packet format.
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Format {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields.
+    Lj2 = 0,
+    /// Standard 256-byte no-FEC SSDV format.
+    NoFec = 1,
+}
+
+impl From<Format> for ssdv_fec::format::Format {
+    fn from(format: Format) -> ssdv_fec::format::Format {
+        match format {
+            Format::Lj2 => ssdv_fec::format::Format::Lj2,
+            Format::NoFec => ssdv_fec::format::Format::NoFec,
+        }
+    }
+}
+
+/// Error produced by the bindings.
+///
+/// This is converted to a JavaScript `Error` by the exported functions.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The length of the input is not valid for the packet format.
+    #[error("invalid length {len} for packets of {packet_len} bytes")]
+    InvalidLength {
+        /// Length of the input.
+        len: usize,
+        /// Length of a packet in the selected format.
+        packet_len: usize,
+    },
+    /// The range of packet IDs to encode goes beyond 65534.
+    #[error("packet IDs out of range")]
+    InvalidRange,
+    /// Error produced by the encoder.
+    #[error(transparent)]
+    Encoder(#[from] EncoderError),
+    /// Error produced by the decoder.
+    #[error(transparent)]
+    Decoder(#[from] DecoderError),
+    /// Error produced by the conversion to JPEG.
+    #[error(transparent)]
+    Transcode(#[from] TranscodeError),
+}
+
+/// Header fields of an SSDV packet.
+///
+/// This is returned by [`info`].
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct PacketInfo {
+    /// Image ID.
+    #[wasm_bindgen(js_name = imageId)]
+    pub image_id: u8,
+    /// Packet ID.
+    #[wasm_bindgen(js_name = packetId)]
+    pub packet_id: u16,
+    /// Image width in units of 16 pixels, or `undefined` in FEC packets.
+    pub width: Option<u8>,
+    /// Image height in units of 16 pixels, or `undefined` in FEC packets.
+    pub height: Option<u8>,
+    /// Number of systematic packets of the image, or `undefined` in
+    /// systematic packets.
+    #[wasm_bindgen(js_name = numSystematic)]
+    pub num_systematic: Option<u16>,
+    /// Flags field.
+    pub flags: u8,
+    /// Whether the EOI flag is set.
+    pub eoi: bool,
+    /// Whether the packet is a FEC packet.
+    #[wasm_bindgen(js_name = fecPacket)]
+    pub fec_packet: bool,
+    /// Whether the CRC-32 of the packet is correct.
+    #[wasm_bindgen(js_name = crcOk)]
+    pub crc_ok: bool,
+}
+
+/// Returns the length in bytes of a packet in a format.
+#[wasm_bindgen(js_name = packetLength)]
+pub fn packet_length(format: Format) -> usize {
+    ssdv_fec::format::Format::from(format).packet_len()
+}
+
+/// Generates FEC encoded packets for an SSDV image.
+///
+/// The `image` array contains the systematic packets of the image in order.
+/// The function returns the `count` packets with packet IDs starting at
+/// `first_id`, concatenated. Packet IDs smaller than the number of systematic
+/// packets give the systematic packets, and larger packet IDs give FEC packets.
+#[wasm_bindgen]
+pub fn encode(image: &[u8], format: Format, first_id: u16, count: u16) -> Result<Vec<u8>, JsError> {
+    Ok(try_encode(image, format, first_id, count)?)
+}
+
+/// Decodes an SSDV image from received packets.
+///
+/// The `packets` array contains the received packets, in any order and
+/// possibly with duplicates. Packets with a wrong CRC-32 are ignored. If
+/// `lenient` is true, inconsistent headers are resolved by majority vote
+/// instead of failing. The function returns the systematic packets of the
+/// image, concatenated.
+#[wasm_bindgen]
+pub fn decode(packets: &[u8], format: Format, lenient: bool) -> Result<Vec<u8>, JsError> {
+    Ok(try_decode(packets, format, lenient)?)
+}
+
+/// Returns the header fields of a single SSDV packet.
+#[wasm_bindgen]
+pub fn info(packet: &[u8], format: Format) -> Result<PacketInfo, JsError> {
+    Ok(try_info(packet, format)?)
+}
+
+/// Converts the systematic packets of an SSDV image to a JPEG image.
+///
+/// The `packets` array contains the packets, usually as returned by
+/// [`decode`]. Missing parts of the image are filled in gray.
+#[wasm_bindgen(js_name = toJpeg)]
+pub fn to_jpeg(packets: &[u8], format: Format) -> Result<Vec<u8>, JsError> {
+    Ok(try_to_jpeg(packets, format)?)
+}
+
+fn try_encode(image: &[u8], format: Format, first_id: u16, count: u16) -> Result<Vec<u8>, Error> {
+    // Packet ID 0xffff cannot be encoded.
+    if u32::from(first_id) + u32::from(count) > u32::from(u16::MAX) {
+        return Err(Error::InvalidRange);
+    }
+    check_length(image, format)?;
+    let encoder = AnyEncoder::new(format.into(), image)?;
+    Ok(encoder.encode_range(first_id, usize::from(count))?)
+}
+
+fn try_decode(packets: &[u8], format: Format, lenient: bool) -> Result<Vec<u8>, Error> {
+    check_length(packets, format)?;
+    let options = DecoderOptions::new().lenient(lenient);
+    Ok(ssdv_fec::format::Format::from(format).decode(packets, &options)?)
+}
+
+fn try_info(packet: &[u8], format: Format) -> Result<PacketInfo, Error> {
+    let info = ssdv_fec::format::Format::from(format)
+        .packet_info(packet)
+        .ok_or_else(|| invalid_length(packet, format))?;
+    Ok(PacketInfo {
+        image_id: info.image_id,
+        packet_id: info.packet_id,
+        width: info.width,
+        height: info.height,
+        num_systematic: info.num_systematic,
+        flags: info.flags,
+        eoi: info.eoi,
+        fec_packet: info.fec_packet,
+        crc_ok: info.crc_ok,
+    })
+}
+
+fn try_to_jpeg(packets: &[u8], format: Format) -> Result<Vec<u8>, Error> {
+    let jpeg = match format {
+        Format::Lj2 => SSDVPacket::slice_from_bytes(packets).map(ssdv_to_jpeg),
+        Format::NoFec => SSDVNoFecPacket::slice_from_bytes(packets).map(ssdv_to_jpeg),
+    }
+    .ok_or_else(|| invalid_length(packets, format))?;
+    Ok(jpeg?)
+}
+
+fn invalid_length(bytes: &[u8], format: Format) -> Error {
+    Error::InvalidLength {
+        len: bytes.len(),
+        packet_len: packet_length(format),
+    }
+}
+
+// Checks that the input contains whole packets.
+fn check_length(bytes: &[u8], format: Format) -> Result<(), Error> {
+    if bytes.len().is_multiple_of(packet_length(format)) {
+        Ok(())
+    } else {
+        Err(invalid_length(bytes, format))
+    }
+}
+
+// The exported functions can only be called from a WebAssembly host, so the
+// tests call the functions that they wrap.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static IMG_230_SSDV: &[u8] = include_bytes!("../../ssdv-fec/src/test_data/img_230.ssdv");
+
+    #[test]
+    fn encode_decode() {
+        let len = packet_length(Format::Lj2);
+        let k = IMG_230_SSDV.len() / len;
+        let systematic = try_encode(IMG_230_SSDV, Format::Lj2, 0, k as u16).unwrap();
+        assert_eq!(systematic, IMG_230_SSDV);
+        // the last systematic packet and FEC packets for the rest
+        let mut received = try_encode(IMG_230_SSDV, Format::Lj2, k as u16 - 1, 1).unwrap();
+        received.extend(try_encode(IMG_230_SSDV, Format::Lj2, k as u16 + 1, k as u16).unwrap());
+        // a corrupted packet is ignored
+        received[len + 20] ^= 1;
+        let decoded = try_decode(&received, Format::Lj2, false).unwrap();
+        assert_eq!(decoded, IMG_230_SSDV);
+        let jpeg = try_to_jpeg(&decoded, Format::Lj2).unwrap();
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+    }
+
+    #[test]
+    fn packet_info() {
+        let len = packet_length(Format::Lj2);
+        let k = IMG_230_SSDV.len() / len;
+        let last = try_info(&IMG_230_SSDV[(k - 1) * len..], Format::Lj2).unwrap();
+        assert_eq!(last.packet_id, k as u16 - 1);
+        assert!(last.eoi && !last.fec_packet && last.crc_ok);
+        assert_eq!(last.num_systematic, None);
+        let fec = try_encode(IMG_230_SSDV, Format::Lj2, k as u16, 1).unwrap();
+        let fec = try_info(&fec, Format::Lj2).unwrap();
+        assert!(fec.fec_packet && fec.crc_ok);
+        assert_eq!(fec.num_systematic, Some(k as u16));
+        assert_eq!(fec.width, None);
+        assert_eq!(fec.image_id, last.image_id);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            try_info(&IMG_230_SSDV[..100], Format::Lj2),
+            Err(Error::InvalidLength {
+                len: 100,
+                packet_len: 218
+            })
+        ));
+        assert!(matches!(
+            try_encode(IMG_230_SSDV, Format::NoFec, 0, 1),
+            Err(Error::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            try_encode(IMG_230_SSDV, Format::Lj2, u16::MAX - 1, 2),
+            Err(Error::InvalidRange)
+        ));
+        assert!(matches!(
+            try_encode(&[], Format::Lj2, 0, 1),
+            Err(Error::Encoder(EncoderError::EmptyInput))
+        ));
+        assert!(matches!(
+            try_decode(&IMG_230_SSDV[..218], Format::Lj2, false),
+            Err(Error::Decoder(_))
+        ));
+        assert!(matches!(
+            try_to_jpeg(&[], Format::Lj2),
+            Err(Error::Transcode(TranscodeError::NoPackets))
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ssdv-c")]
+    build_ssdv_c();
+}
+
+// Builds the ssdv C library from the source directory given in SSDV_SRC_DIR,
+// together with the shim through which the ssdv_c module calls it.
+#[cfg(feature = "ssdv-c")]
+fn build_ssdv_c() {
+    use std::{env, path::PathBuf};
+
+    println!("cargo:rerun-if-changed=csrc");
+    println!("cargo:rerun-if-env-changed=SSDV_SRC_DIR");
+    let src_dir = PathBuf::from(env::var_os("SSDV_SRC_DIR").unwrap_or_else(|| {
+        panic!("the ssdv-c feature requires SSDV_SRC_DIR to be set to the source directory of the ssdv C library")
+    }));
+    for file in ["ssdv.c", "ssdv.h", "rs8.c", "rs8.h"] {
+        let path = src_dir.join(file);
+        assert!(
+            path.exists(),
+            "{} not found in SSDV_SRC_DIR",
+            path.display()
+        );
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+    cc::Build::new()
+        .include(&src_dir)
+        .file(src_dir.join("ssdv.c"))
+        .file(src_dir.join("rs8.c"))
+        .file("csrc/ssdv_shim.c")
+        // the ssdv library is not warning-free with all compilers
+        .warnings(false)
+        .compile("ssdv");
+}
@@ -0,0 +1,104 @@
+/*
+ * Shim between the ssdv C library and the ssdv_c module of the ssdv-fec crate.
+ *
+ * The layout of ssdv_t depends on the version of the ssdv library, so it is
+ * only handled here. The functions in this file encode and decode whole images
+ * using plain buffers, which makes them easy to call from Rust.
+ */
+
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "ssdv.h"
+
+/* Return values, which must match those in src/ssdv_c.rs */
+#define SHIM_OK 0
+#define SHIM_NO_MEMORY (-1)
+#define SHIM_ENCODE_FAILED (-2)
+#define SHIM_OUTPUT_TOO_SHORT (-3)
+#define SHIM_DECODE_FAILED (-4)
+
+/* Size of the chunks in which the JPEG image is fed to the encoder */
+#define FEED_LEN 128
+
+int ssdv_fec_shim_jpeg_to_ssdv(const uint8_t *jpeg, size_t jpeg_len,
+                               uint8_t type, const char *callsign,
+                               uint8_t image_id, int8_t quality,
+                               uint8_t *packets, size_t max_packets,
+                               size_t *num_packets)
+{
+    ssdv_t *s = malloc(sizeof(*s));
+    uint8_t pkt[SSDV_PKT_SIZE];
+    size_t fed = 0;
+    int ret = SHIM_OK;
+    char c;
+
+    if (s == NULL)
+        return SHIM_NO_MEMORY;
+    *num_packets = 0;
+    /* the library does not modify the callsign or the JPEG data */
+    ssdv_enc_init(s, type, (char *)callsign, image_id, quality, SSDV_PKT_SIZE);
+    ssdv_enc_set_buffer(s, pkt);
+    for (;;) {
+        while ((c = ssdv_enc_get_packet(s)) == SSDV_FEED_ME) {
+            size_t len = jpeg_len - fed < FEED_LEN ? jpeg_len - fed : FEED_LEN;
+            if (len == 0)
+                break;
+            ssdv_enc_feed(s, (uint8_t *)jpeg + fed, len);
+            fed += len;
+        }
+        if (c == SSDV_EOI)
+            break;
+        if (c != SSDV_OK) {
+            /* this includes a JPEG file that ends prematurely */
+            ret = SHIM_ENCODE_FAILED;
+            break;
+        }
+        if (*num_packets == max_packets) {
+            ret = SHIM_OUTPUT_TOO_SHORT;
+            break;
+        }
+        memcpy(packets + *num_packets * SSDV_PKT_SIZE, pkt, SSDV_PKT_SIZE);
+        ++*num_packets;
+    }
+    free(s);
+    return ret;
+}
+
+int ssdv_fec_shim_ssdv_to_jpeg(const uint8_t *packets, size_t num_packets,
+                               uint8_t *jpeg, size_t jpeg_len,
+                               size_t *written)
+{
+    ssdv_t *s = malloc(sizeof(*s));
+    uint8_t pkt[SSDV_PKT_SIZE];
+    uint8_t *out;
+    size_t out_len;
+    size_t j;
+    int errors;
+    int ret = SHIM_OK;
+    char c = SSDV_FEED_ME;
+
+    if (s == NULL)
+        return SHIM_NO_MEMORY;
+    *written = 0;
+    ssdv_dec_init(s, SSDV_PKT_SIZE);
+    ssdv_dec_set_buffer(s, jpeg, jpeg_len);
+    for (j = 0; j < num_packets; ++j) {
+        /* the library works in-place on the packets */
+        memcpy(pkt, packets + j * SSDV_PKT_SIZE, SSDV_PKT_SIZE);
+        if (ssdv_dec_is_packet(pkt, SSDV_PKT_SIZE, &errors) != 0)
+            continue;
+        c = ssdv_dec_feed(s, pkt);
+        if (c != SSDV_FEED_ME)
+            break;
+    }
+    if (c == SSDV_BUFFER_FULL)
+        ret = SHIM_OUTPUT_TOO_SHORT;
+    else if (c == SSDV_ERROR || ssdv_dec_get_jpeg(s, &out, &out_len) != SSDV_OK)
+        ret = SHIM_DECODE_FAILED;
+    else
+        *written = out_len;
+    free(s);
+    return ret;
+}
@@ -0,0 +1,229 @@
+//! AGWPE protocol.
+//!
+//! This implements [`AgwpeClient`], a client for the TCP interface of AGW
+//! Packet Engine, which is also provided by other packet engines and software
+//! modems, such as Direwolf and UZ7HO's Soundmodem. The client receives the
+//! raw AX.25 frames decoded by the packet engine, and extracts the SSDV packets
+//! carried in UI frames.
+//!
+//! This module is only available with the `agwpe` feature.
+
+use crate::{encap::ax25::strip_ui_header, Packet};
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// Default TCP port of AGWPE.
+pub const AGWPE_PORT: u16 = 8000;
+
+// Length of the header of an AGWPE frame
+const HEADER_LEN: usize = 36;
+// Largest data length that is accepted. AX.25 frames are much shorter.
+const MAX_DATA_LEN: usize = 1 << 16;
+
+// Data kinds of AGWPE frames
+const KIND_RAW_FRAMES: u8 = b'k';
+const KIND_RAW_FRAME: u8 = b'K';
+
+/// AGWPE client.
+///
+/// The client enables the reception of raw frames from the packet engine and
+/// returns the AX.25 frames received on any of its radio ports, or only on the
+/// one given with [`AgwpeClient::with_port`]. The stream can be a TCP
+/// connection or any other byte stream implementing [`Read`] and [`Write`].
+#[derive(Debug)]
+pub struct AgwpeClient<T> {
+    stream: T,
+    port: Option<u8>,
+    buffer: Vec<u8>,
+}
+
+impl AgwpeClient<TcpStream> {
+    /// Connects to a packet engine through TCP.
+    ///
+    /// Packet engines listen on port [`AGWPE_PORT`] by default.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<AgwpeClient<TcpStream>> {
+        AgwpeClient::new(TcpStream::connect(addr)?)
+    }
+}
+
+impl<T: Read + Write> AgwpeClient<T> {
+    /// Creates a new client that uses a byte stream connected to a packet
+    /// engine.
+    ///
+    /// This requests the packet engine to send the raw frames that it
+    /// receives.
+    pub fn new(mut stream: T) -> io::Result<AgwpeClient<T>> {
+        stream.write_all(&header(0, KIND_RAW_FRAMES, 0))?;
+        stream.flush()?;
+        Ok(AgwpeClient {
+            stream,
+            port: None,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl<T> AgwpeClient<T> {
+    /// Only receives the frames of a radio port of the packet engine.
+    ///
+    /// The first radio port is port 0.
+    pub fn with_port(mut self, port: u8) -> AgwpeClient<T> {
+        self.port = Some(port);
+        self
+    }
+
+    /// Returns a reference to the byte stream.
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the byte stream.
+    ///
+    /// This can be used to set timeouts.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+
+    /// Returns the byte stream.
+    ///
+    /// Frames that have been received but not returned yet are lost.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+
+    // Removes the next AGWPE frame from the buffer and returns its radio port,
+    // data kind and data.
+    fn next_frame(&mut self) -> io::Result<Option<(u8, u8, Vec<u8>)>> {
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let len = u32::from_le_bytes(self.buffer[28..32].try_into().unwrap()) as usize;
+        if len > MAX_DATA_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "AGWPE frame too long",
+            ));
+        }
+        if self.buffer.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+        let frame = self.buffer.drain(..HEADER_LEN + len).collect::<Vec<u8>>();
+        Ok(Some((frame[0], frame[4], frame[HEADER_LEN..].to_vec())))
+    }
+}
+
+impl<T: Read> AgwpeClient<T> {
+    /// Receives an AX.25 frame.
+    ///
+    /// This blocks until a raw frame is received, and returns the radio port
+    /// in which it was received and the AX.25 frame, without the FCS. An error
+    /// with [`ErrorKind::UnexpectedEof`] is returned if the connection is
+    /// closed. If the byte stream has a timeout and the function fails because
+    /// of it, the partially received frame is kept, so the function can be
+    /// called again.
+    pub fn recv_frame(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut buffer = [0; 1024];
+        loop {
+            while let Some((port, kind, data)) = self.next_frame()? {
+                // The data of a raw frame starts with a KISS command byte.
+                if kind == KIND_RAW_FRAME && self.port.is_none_or(|p| p == port) && !data.is_empty()
+                {
+                    return Ok((port, data[1..].to_vec()));
+                }
+            }
+            let len = match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.buffer.extend_from_slice(&buffer[..len]);
+        }
+    }
+
+    /// Receives a packet.
+    ///
+    /// This blocks until a UI frame whose information field has the length of
+    /// a packet is received. Other frames are discarded. The CRC of the packet
+    /// is not checked.
+    pub fn recv_packet<P: Packet>(&mut self) -> io::Result<P> {
+        loop {
+            let (_, frame) = self.recv_frame()?;
+            if let Some(payload) = strip_ui_header(&frame).filter(|p| p.len() == P::LEN) {
+                let mut packet = P::zeroed();
+                packet.as_bytes_mut().copy_from_slice(payload);
+                return Ok(packet);
+            }
+        }
+    }
+}
+
+// Returns the header of an AGWPE frame without callsigns.
+fn header(port: u8, kind: u8, len: u32) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[0] = port;
+    header[4] = kind;
+    header[28..32].copy_from_slice(&len.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, SSDVPacket};
+    use std::{net::TcpListener, thread};
+
+    fn raw_frame(port: u8, ax25: &[u8]) -> Vec<u8> {
+        let mut frame = header(port, KIND_RAW_FRAME, ax25.len() as u32 + 1).to_vec();
+        frame.push(port << 4);
+        frame.extend_from_slice(ax25);
+        frame
+    }
+
+    fn ui_frame(payload: &[u8]) -> Vec<u8> {
+        let mut frame = b"\x82\xa0\xa4\xa6@@`\x8a\x82h\x8e\xa0\xb4a\x03\xf0".to_vec();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn engine_tcp() {
+        let packets = img_230_packets();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let sent = packets.clone();
+        let engine = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut request = [0; HEADER_LEN];
+            stream.read_exact(&mut request).unwrap();
+            assert_eq!(request, header(0, KIND_RAW_FRAMES, 0));
+            // a frame of another kind, a frame from another port and a frame
+            // that is not a packet
+            stream.write_all(&header(0, b'R', 0)).unwrap();
+            stream
+                .write_all(&raw_frame(0, &ui_frame(&sent[0].0)))
+                .unwrap();
+            stream.write_all(&raw_frame(1, &ui_frame(&[1, 2]))).unwrap();
+            // frames split across writes
+            for packet in &sent {
+                let frame = raw_frame(1, &ui_frame(&packet.0));
+                let (a, b) = frame.split_at(20);
+                stream.write_all(a).unwrap();
+                stream.write_all(b).unwrap();
+            }
+        });
+        let mut client = AgwpeClient::connect(addr).unwrap().with_port(1);
+        let mut received = Vec::new();
+        let err = loop {
+            match client.recv_packet::<SSDVPacket>() {
+                Ok(packet) => received.push(packet),
+                Err(err) => break err,
+            }
+        };
+        engine.join().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(received, packets);
+    }
+}
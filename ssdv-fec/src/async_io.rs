@@ -0,0 +1,315 @@
+//! Adapters for async applications.
+//!
+//! This module contains a [`Stream`] adapter that decodes images from a stream
+//! of received frames, and a [`Sink`] that encodes images and sends their
+//! packets to an inner sink at a paced rate. They allow async services, such
+//! as ground station servers based on tokio, to receive and transmit SSDV FEC
+//! images without blocking their worker threads.
+//!
+//! The adapters use the 218-byte packet format used by Longjiang-2, and the
+//! pacing uses the tokio timer, so they must be used within a tokio runtime
+//! with the time driver enabled.
+//!
+//! This module is only available with the `async` feature.
+
+use crate::{
+    DecodedImage, DecoderOptions, Encoder, EncoderError, ImageSet, Packet, PushResult, SSDVPacket,
+    SSDV_PACKET_LEN,
+};
+use bytes::Bytes;
+use futures_core::Stream;
+use futures_sink::Sink;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+use tokio::time::{sleep_until, Instant, Sleep};
+
+/// Stream of images decoded from a stream of received frames.
+///
+/// This adapter wraps a stream of frames, such as UDP datagrams or the
+/// payloads of a link-layer protocol, each containing one SSDV packet. The
+/// packets are accumulated in an [`ImageSet`], so packets from several images
+/// can be interleaved, and each image is yielded as soon as it can be decoded.
+/// Frames that do not have the length of an SSDV packet are discarded, as well
+/// as packets with a wrong CRC. The stream ends when the inner stream ends.
+#[derive(Debug)]
+pub struct ImageStream<S> {
+    frames: S,
+    images: ImageSet,
+    invalid_frames: usize,
+}
+
+impl<S> ImageStream<S> {
+    /// Creates a new stream of images from a stream of frames.
+    pub fn new(frames: S) -> ImageStream<S> {
+        ImageStream::with_options(frames, DecoderOptions::default())
+    }
+
+    /// Creates a new stream of images that are decoded with some options.
+    pub fn with_options(frames: S, options: DecoderOptions) -> ImageStream<S> {
+        ImageStream {
+            frames,
+            images: ImageSet::with_options(options),
+            invalid_frames: 0,
+        }
+    }
+
+    /// Returns the set of images being received.
+    ///
+    /// This can be used to obtain the reception statistics of each image.
+    pub fn images(&self) -> &ImageSet {
+        &self.images
+    }
+
+    /// Returns the number of frames discarded because of their length.
+    pub fn invalid_frames(&self) -> usize {
+        self.invalid_frames
+    }
+
+    /// Returns the inner stream of frames.
+    pub fn into_inner(self) -> S {
+        self.frames
+    }
+}
+
+impl<S, B> Stream for ImageStream<S>
+where
+    S: Stream<Item = B> + Unpin,
+    B: AsRef<[u8]>,
+{
+    type Item = DecodedImage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<DecodedImage>> {
+        let this = &mut *self;
+        while let Some(frame) = ready!(Pin::new(&mut this.frames).poll_next(cx)) {
+            let Ok(packet) = <[u8; SSDV_PACKET_LEN]>::try_from(frame.as_ref()) else {
+                this.invalid_frames += 1;
+                continue;
+            };
+            if let PushResult::Decoded(image) = this.images.push(SSDVPacket(packet)) {
+                return Poll::Ready(Some(image));
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// Error produced by an [`EncoderSink`].
+#[derive(Debug)]
+pub enum SinkError<E> {
+    /// The image could not be encoded.
+    Encoder(EncoderError),
+    /// The inner sink failed.
+    Sink(E),
+}
+
+impl<E: fmt::Display> fmt::Display for SinkError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SinkError::Encoder(err) => err.fmt(f),
+            SinkError::Sink(err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error> core::error::Error for SinkError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            SinkError::Encoder(err) => err.source(),
+            SinkError::Sink(err) => err.source(),
+        }
+    }
+}
+
+impl<E> From<EncoderError> for SinkError<E> {
+    fn from(err: EncoderError) -> SinkError<E> {
+        SinkError::Encoder(err)
+    }
+}
+
+/// Sink that encodes images and sends their packets at a paced rate.
+///
+/// The items of this sink are the systematic packets of SSDV images. Each image
+/// is encoded into its systematic packets followed by a number of FEC packets
+/// given by [`EncoderSink::with_overhead_percent`], and these are sent as
+/// frames to an inner sink, waiting [`EncoderSink::with_interval`] between
+/// consecutive packets. The sink only accepts a new image once all the packets
+/// of the previous image have been sent to the inner sink.
+#[derive(Debug)]
+pub struct EncoderSink<S> {
+    sink: S,
+    overhead_percent: u16,
+    interval: Option<Duration>,
+    queue: VecDeque<Bytes>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> EncoderSink<S> {
+    /// Creates a new encoder sink.
+    ///
+    /// By default, only the systematic packets of each image are sent, and
+    /// they are sent as fast as the inner sink accepts them.
+    pub fn new(sink: S) -> EncoderSink<S> {
+        EncoderSink {
+            sink,
+            overhead_percent: 0,
+            interval: None,
+            queue: VecDeque::new(),
+            delay: None,
+        }
+    }
+
+    /// Sets the number of FEC packets sent for each image.
+    ///
+    /// The number of FEC packets is given as a percentage of the number of
+    /// systematic packets, rounded up, as in
+    /// [`FountainScheduler::with_overhead_percent`](crate::FountainScheduler::with_overhead_percent).
+    pub fn with_overhead_percent(mut self, overhead_percent: u16) -> EncoderSink<S> {
+        self.overhead_percent = overhead_percent;
+        self
+    }
+
+    /// Sets the interval between consecutive packets.
+    pub fn with_interval(mut self, interval: Duration) -> EncoderSink<S> {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Returns the number of packets waiting to be sent to the inner sink.
+    pub fn pending_packets(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns the inner sink.
+    ///
+    /// Packets that have not been sent to the inner sink yet are lost.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: Sink<Bytes> + Unpin> EncoderSink<S> {
+    // Sends the queued packets to the inner sink, waiting for the pacing
+    // interval between them.
+    fn poll_send_queue(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        while !self.queue.is_empty() {
+            if let Some(delay) = &mut self.delay {
+                ready!(delay.as_mut().poll(cx));
+            }
+            ready!(Pin::new(&mut self.sink).poll_ready(cx))?;
+            let packet = self.queue.pop_front().unwrap();
+            Pin::new(&mut self.sink).start_send(packet)?;
+            if let Some(interval) = self.interval {
+                // The next deadline is computed from the previous one, so that
+                // the rate does not drift because of scheduling delays.
+                match &mut self.delay {
+                    Some(delay) => {
+                        let deadline = delay.deadline() + interval;
+                        delay.as_mut().reset(deadline);
+                    }
+                    None => self.delay = Some(Box::pin(sleep_until(Instant::now() + interval))),
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: Sink<Bytes> + Unpin> Sink<Vec<SSDVPacket>> for EncoderSink<S> {
+    type Error = SinkError<S::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_send_queue(cx).map_err(SinkError::Sink)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, mut image: Vec<SSDVPacket>) -> Result<(), Self::Error> {
+        let encoder = Encoder::new(&mut image)?;
+        let k = u32::from(encoder.num_systematic());
+        // Packet ID 0xffff cannot be encoded.
+        let npackets = (k + (k * u32::from(self.overhead_percent)).div_ceil(100))
+            .min(u32::from(u16::MAX)) as usize;
+        let mut packets = vec![SSDVPacket::zeroed(); npackets];
+        encoder.try_encode_range(0, &mut packets)?;
+        self.queue.extend(
+            packets
+                .iter()
+                .map(|packet| Bytes::copy_from_slice(packet.as_bytes())),
+        );
+        // Do not send a burst to catch up with the deadlines missed while
+        // there were no images to send.
+        let now = Instant::now();
+        if let Some(delay) = &mut self.delay {
+            if delay.deadline() < now {
+                delay.as_mut().reset(now);
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_send_queue(cx)).map_err(SinkError::Sink)?;
+        Pin::new(&mut self.sink)
+            .poll_flush(cx)
+            .map_err(SinkError::Sink)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_send_queue(cx)).map_err(SinkError::Sink)?;
+        Pin::new(&mut self.sink)
+            .poll_close(cx)
+            .map_err(SinkError::Sink)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+    use futures::{channel::mpsc, stream, SinkExt, StreamExt};
+
+    #[tokio::test(start_paused = true)]
+    async fn paced_round_trip() {
+        let image = img_230_packets();
+        let k = image.len();
+        let interval = Duration::from_millis(100);
+        let (tx, rx) = mpsc::unbounded::<Bytes>();
+        let mut sink = EncoderSink::new(tx)
+            .with_overhead_percent(100)
+            .with_interval(interval);
+        let start = Instant::now();
+        sink.send(image.clone()).await.unwrap();
+        let npackets = 2 * k;
+        assert_eq!(start.elapsed(), (npackets as u32 - 1) * interval);
+        assert_eq!(sink.pending_packets(), 0);
+        drop(sink);
+
+        // Drop most of the systematic packets and add a frame with a wrong
+        // length.
+        let frames = rx
+            .enumerate()
+            .filter(move |(j, _)| std::future::ready(*j >= k - 1))
+            .map(|(_, frame)| frame);
+        let frames = stream::iter([Bytes::from_static(&[0; 10])]).chain(frames);
+        let mut images = ImageStream::new(frames);
+        let decoded = images.next().await.unwrap();
+        assert_eq!(decoded.packets, image);
+        assert_eq!(decoded.num_systematic_received, 1);
+        assert!(images.next().await.is_none());
+        assert_eq!(images.invalid_frames(), 1);
+    }
+
+    #[tokio::test]
+    async fn empty_image() {
+        let (tx, _rx) = mpsc::unbounded::<Bytes>();
+        let mut sink = EncoderSink::new(tx);
+        assert!(matches!(
+            sink.send(Vec::new()).await,
+            Err(SinkError::Encoder(EncoderError::EmptyInput))
+        ));
+    }
+}
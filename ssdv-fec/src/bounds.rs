@@ -0,0 +1,61 @@
+// Bounds on the loops of the encoder and decoder.
+//
+// The encoder and decoder, and the GF(2¹⁶) arithmetic and CRC-32 that they use,
+// do not use recursion, so their stack usage does not depend on the input, as
+// checked for the Cortex-M4 by the stack usage test of the erminaz-ssdv-fec
+// crate. Their loops are `for` loops over ranges or slices, so the trip count
+// of each loop is bounded by the number of systematic packets k, the number of
+// input packets or the length of the packets. The only exception is
+// `PacketChunks::next_chunk_with_scratch`, which writes at least one byte in
+// each iteration, so it is bounded by the length of the chunk.
+//
+// The loops that do GF(2¹⁶) arithmetic for each systematic packet are the
+// long-running ones. They are counted in steps by a `Meter`, and each step runs
+// inner loops of at most k + DATA_LEN / 2 iterations. The functions in this
+// module give the number of steps of each operation as a function of k, which
+// is the total reported in its progress. The tests check that each operation
+// runs exactly this number of steps.
+
+use crate::ssdv::Packet;
+
+// Largest number of systematic packets of an image, which is limited by the
+// 16-bit packet IDs.
+const MAX_K: u64 = u16::MAX as u64;
+
+// Number of steps of the setup of an encoder and of its release, which is one
+// for each systematic packet.
+pub(crate) const fn encoder_steps(k: u16) -> u32 {
+    k as u32
+}
+
+// Number of steps of the encoding of a FEC packet, or of a block of its data
+// field, which is one for each systematic packet.
+pub(crate) const fn encode_steps(k: u16) -> u32 {
+    k as u32
+}
+
+// Number of steps of the decoding of an image with `missing` systematic
+// packets, which is one for each of the first k input packets and one for
+// each word of the missing packets.
+pub(crate) const fn decode_steps<P: Packet>(k: u16, missing: u16) -> u32 {
+    const {
+        assert!(
+            max_decode_steps(P::DATA_LEN) <= u32::MAX as u64,
+            "the number of decoding steps does not fit in the progress"
+        )
+    };
+    k as u32 + missing as u32 * (P::DATA_LEN / 2) as u32
+}
+
+// Largest number of steps of the decoding of an image with packets whose data
+// field has `data_len` bytes.
+const fn max_decode_steps(data_len: usize) -> u64 {
+    MAX_K + MAX_K * (data_len / 2) as u64
+}
+
+// The decoding steps of the largest images of the formats of this crate fit
+// in the progress.
+const _: () = assert!(
+    max_decode_steps(crate::SSDV_DATA_LEN) <= u32::MAX as u64
+        && max_decode_steps(crate::SSDV_NO_FEC_DATA_LEN) <= u32::MAX as u64
+);
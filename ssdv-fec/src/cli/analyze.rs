@@ -0,0 +1,141 @@
+//! Link budget analysis.
+//!
+//! This computes the number of packets that need to be transmitted so that an
+//! image can be decoded with some probability when packets are lost. The
+//! packets are assumed to be lost independently with a fixed probability.
+//!
+//! The SSDV FEC code can decode the image from any `k` distinct packets, where
+//! `k` is the number of systematic packets, as long as at least one of them is
+//! a systematic packet. Therefore, if `n` packets with IDs `0, ..., n - 1` are
+//! transmitted, the image can be decoded if at least `k` of them are received
+//! and not all the `k` systematic packets are lost.
+
+use anyhow::Result;
+use std::io::Write;
+
+/// Runs the analysis and writes the results to `out`.
+///
+/// The `loss` is the packet loss probability, and `probability` is the desired
+/// probability of decoding the image.
+pub fn analyze(k: u16, loss: f64, probability: f64, out: &mut dyn Write) -> Result<()> {
+    if k == 0 {
+        anyhow::bail!("the number of systematic packets must be positive");
+    }
+    if !(0.0..1.0).contains(&loss) {
+        anyhow::bail!("the packet loss rate must be in the interval [0, 1)");
+    }
+    if !(probability > 0.0 && probability <= 1.0) {
+        anyhow::bail!("the decode probability must be in the interval (0, 1]");
+    }
+    let Some(n) = required_packets(k, loss, probability) else {
+        anyhow::bail!(
+            "the decode probability cannot be reached with {} packets",
+            u16::MAX
+        );
+    };
+    writeln!(out, "image size: {k} packets")?;
+    writeln!(out, "packet loss rate: {:.1}%", loss * 100.0)?;
+    writeln!(
+        out,
+        "packets to transmit: {n} (rate {:.3}, overhead {:.1}%)",
+        f64::from(k) / f64::from(n),
+        f64::from(n - k) / f64::from(k) * 100.0
+    )?;
+    writeln!(
+        out,
+        "decode probability: {:.4}%",
+        decode_probability(k, n, loss) * 100.0
+    )?;
+    Ok(())
+}
+
+/// Returns the minimum number of packets to transmit to decode an image with
+/// at least the given probability.
+///
+/// `None` is returned if the probability cannot be reached with the 16-bit
+/// packet ID space.
+pub fn required_packets(k: u16, loss: f64, probability: f64) -> Option<u16> {
+    if decode_probability(k, u16::MAX, loss) < probability {
+        return None;
+    }
+    // the decode probability increases with the number of packets, so a
+    // binary search can be used
+    let (mut low, mut high) = (k, u16::MAX);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if decode_probability(k, mid, loss) >= probability {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Some(low)
+}
+
+/// Returns the probability of decoding an image of `k` systematic packets
+/// when the `n` packets with IDs `0, ..., n - 1` are transmitted.
+pub fn decode_probability(k: u16, n: u16, loss: f64) -> f64 {
+    let (k, n) = (u32::from(k), u32::from(n));
+    if n < k {
+        return 0.0;
+    }
+    let received = 1.0 - loss;
+    // at least k packets are received, but all of them are FEC packets
+    let no_systematic = loss.powi(k as i32) * binomial_tail(n - k, received, k);
+    (binomial_tail(n, received, k) - no_systematic).max(0.0)
+}
+
+// Returns the probability that a binomial random variable with n trials and
+// success probability p is at least k.
+fn binomial_tail(n: u32, p: f64, k: u32) -> f64 {
+    if k == 0 {
+        return 1.0;
+    }
+    if k > n || p <= 0.0 {
+        return 0.0;
+    }
+    if p >= 1.0 {
+        return 1.0;
+    }
+    // the terms of the probability mass function are computed recursively in
+    // the log domain to avoid underflow
+    let (log_p, log_q) = (p.ln(), (1.0 - p).ln());
+    let mut log_term = f64::from(n) * log_q;
+    let mut tail = 0.0;
+    for j in 0..=n {
+        if j >= k {
+            tail += log_term.exp();
+        }
+        if j < n {
+            log_term += (f64::from(n - j) / f64::from(j + 1)).ln() + log_p - log_q;
+        }
+    }
+    tail.min(1.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn small_codes() {
+        // k = 1: only the systematic packet can be used
+        assert!((decode_probability(1, 2, 0.5) - 0.5).abs() < 1e-12);
+        // k = 2, n = 3: at least 2 of 3 packets, and the 2 systematic packets
+        // cannot be both lost if 2 packets are received
+        assert!((decode_probability(2, 3, 0.5) - 0.5).abs() < 1e-12);
+        assert_eq!(decode_probability(10, 10, 0.0), 1.0);
+        assert_eq!(decode_probability(10, 9, 0.0), 0.0);
+    }
+
+    #[test]
+    fn required_packets_minimal() {
+        for (k, loss, probability) in [(69, 0.2, 0.99), (300, 0.05, 0.999), (10, 0.5, 0.9)] {
+            let n = required_packets(k, loss, probability).unwrap();
+            assert!(decode_probability(k, n, loss) >= probability);
+            assert!(decode_probability(k, n - 1, loss) < probability);
+        }
+        assert_eq!(required_packets(69, 0.0, 1.0), Some(69));
+        assert_eq!(required_packets(60000, 0.5, 0.9), None);
+    }
+}
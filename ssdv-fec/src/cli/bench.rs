@@ -0,0 +1,77 @@
+//! Benchmark of the encoder and decoder.
+
+use super::synthetic::{synthetic_image, Rng};
+use crate::{Decoder, Encoder, Packet};
+use anyhow::Result;
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// Runs the benchmark.
+///
+/// A synthetic image of `k` packets is used. The benchmark measures the time
+/// required to set up the encoder, to encode `npackets` FEC packets, and to
+/// decode the image from FEC packets and a single systematic packet (which is
+/// the worst case for the decoder, since it needs at least one systematic
+/// packet and all the other systematic packets need to be recovered). The
+/// packets are in the format `P`. The results are written to `out`.
+pub fn bench<P: Packet>(k: u16, npackets: u16, out: &mut dyn Write) -> Result<()> {
+    if k == 0 {
+        anyhow::bail!("the number of systematic packets must be positive");
+    }
+    if u32::from(k) + u32::from(npackets.max(k)) > u32::from(u16::MAX) {
+        anyhow::bail!("too many packets for the 16-bit packet ID space");
+    }
+    let mut rng = Rng::new(0);
+    let image = synthetic_image::<P>(k, 0, &mut rng);
+    writeln!(out, "image size: {k} packets")?;
+
+    let mut buffer = image.clone();
+    let start = Instant::now();
+    let encoder = Encoder::new(&mut buffer)?;
+    print_time::<P>(out, "encoder setup", start.elapsed(), None)?;
+
+    let mut encoded = vec![P::zeroed(); usize::from(npackets.max(k))];
+    let start = Instant::now();
+    for (j, packet) in encoded[..usize::from(npackets)].iter_mut().enumerate() {
+        encoder.encode(k + j as u16, packet);
+    }
+    print_time::<P>(out, "encode", start.elapsed(), Some(usize::from(npackets)))?;
+    // make sure that there are enough packets to decode
+    for (j, packet) in encoded.iter_mut().enumerate().skip(usize::from(npackets)) {
+        encoder.encode(k + j as u16, packet);
+    }
+
+    let mut input = encoded[..usize::from(k)].to_vec();
+    encoder.encode(0, &mut input[0]);
+    let mut output = vec![P::zeroed(); usize::from(k)];
+    let start = Instant::now();
+    let decoded = Decoder::decode(&mut input, &mut output)?;
+    print_time::<P>(out, "decode", start.elapsed(), Some(usize::from(k)))?;
+    if decoded != image.as_slice() {
+        anyhow::bail!("decoded image does not match the original");
+    }
+    Ok(())
+}
+
+fn print_time<P: Packet>(
+    out: &mut dyn Write,
+    operation: &str,
+    elapsed: Duration,
+    npackets: Option<usize>,
+) -> io::Result<()> {
+    let secs = elapsed.as_secs_f64();
+    match npackets {
+        Some(npackets) if npackets > 0 => {
+            let rate = npackets as f64 / secs;
+            let kbps = rate * (8 * P::LEN) as f64 / 1e3;
+            writeln!(
+                out,
+                "{operation}: {:.3} ms ({npackets} packets, {rate:.1} packets/s, {kbps:.1} kbps)",
+                secs * 1e3
+            )
+        }
+        _ => writeln!(out, "{operation}: {:.3} ms", secs * 1e3),
+    }
+}
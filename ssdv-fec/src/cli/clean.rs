@@ -0,0 +1,63 @@
+//! Normalization of packet files.
+
+use crate::{crc::crc32, SSDVPacket};
+use std::collections::BTreeMap;
+
+/// Result of cleaning a list of packets.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cleaned {
+    /// Packets kept, sorted by image ID and packet ID.
+    pub packets: Vec<SSDVPacket>,
+    /// Number of packets removed because of a wrong CRC.
+    pub wrong_crc: usize,
+    /// Number of duplicate packets removed.
+    pub duplicates: usize,
+}
+
+/// Cleans a list of packets.
+///
+/// Packets with a wrong CRC and duplicate packets are removed, and the
+/// remaining packets are sorted by image ID and then by packet ID, so that the
+/// result does not depend on the order in which the packets were received.
+/// Packets are considered duplicates if they have the same image ID and packet
+/// ID. Only the first of them is kept.
+pub fn clean(packets: &[SSDVPacket]) -> Cleaned {
+    let mut kept = BTreeMap::new();
+    let mut wrong_crc = 0;
+    let mut duplicates = 0;
+    for packet in packets {
+        if crc32(packet.crc32_data().iter()) != packet.crc32() {
+            wrong_crc += 1;
+        } else if kept
+            .insert((packet.image_id(), packet.packet_id()), *packet)
+            .is_some()
+        {
+            duplicates += 1;
+        }
+    }
+    Cleaned {
+        packets: kept.into_values().collect(),
+        wrong_crc,
+        duplicates,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+
+    #[test]
+    fn clean_shuffled() {
+        let packets = img_230_packets();
+        let mut input = packets.iter().rev().copied().collect::<Vec<_>>();
+        input.push(packets[3]);
+        let mut corrupted = packets[5];
+        corrupted.0[50] ^= 0xff;
+        input.push(corrupted);
+        let cleaned = clean(&input);
+        assert_eq!(cleaned.packets, packets);
+        assert_eq!(cleaned.wrong_crc, 1);
+        assert_eq!(cleaned.duplicates, 1);
+    }
+}
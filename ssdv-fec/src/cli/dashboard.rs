@@ -0,0 +1,118 @@
+//! Terminal dashboard showing the reception status of each image.
+//!
+//! The dashboard is drawn on stdout using ANSI escape codes. It is redrawn
+//! completely each time, which is simple and works in any terminal.
+
+use crate::ImageStats;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::{Duration, Instant, SystemTime},
+};
+
+// Minimum interval between redraws
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Terminal dashboard.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    images: BTreeMap<u8, ImageRow>,
+    wrong_crc: usize,
+    last_event: Option<String>,
+    last_draw: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct ImageRow {
+    received: usize,
+    stats: ImageStats,
+    last_packet: SystemTime,
+}
+
+impl Dashboard {
+    /// Creates a new dashboard.
+    pub fn new() -> Dashboard {
+        Dashboard::default()
+    }
+
+    /// Records the reception of a packet with a valid CRC.
+    ///
+    /// The `stats` are the statistics of the image after receiving the packet.
+    pub fn packet(&mut self, image_id: u8, stats: ImageStats) {
+        let row = self.images.entry(image_id).or_insert(ImageRow {
+            received: 0,
+            stats,
+            last_packet: SystemTime::now(),
+        });
+        row.received += 1;
+        row.stats = stats;
+        row.last_packet = SystemTime::now();
+    }
+
+    /// Records the reception of a packet with a wrong CRC.
+    pub fn wrong_crc(&mut self) {
+        self.wrong_crc += 1;
+    }
+
+    /// Records an event, which is shown below the table.
+    pub fn event(&mut self, event: String) {
+        self.last_event = Some(event);
+    }
+
+    /// Draws the dashboard on stdout.
+    ///
+    /// Unless `force` is true, the dashboard is only drawn if enough time has
+    /// elapsed since it was last drawn, to limit the redraw rate.
+    pub fn draw(&mut self, force: bool, out: &mut dyn Write) -> io::Result<()> {
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|last| last.elapsed() < REDRAW_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_draw = Some(Instant::now());
+        // move the cursor to the top left and clear the screen
+        write!(out, "\x1b[H\x1b[2J")?;
+        writeln!(
+            out,
+            "{:>5}  {:>8}  {:>6}  {:>6}  {:>8}  {:<9}  {:<8}",
+            "image", "received", "unique", "total", "complete", "decodable", "last"
+        )?;
+        for (image_id, row) in &self.images {
+            let stats = &row.stats;
+            let (total, complete) = match stats.num_systematic {
+                Some(k) => {
+                    let percent = (100 * stats.unique / usize::from(k).max(1)).min(100);
+                    (k.to_string(), format!("{percent}%"))
+                }
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let decodable = if stats.decoded {
+                "decoded"
+            } else if stats
+                .num_systematic
+                .is_some_and(|k| stats.unique >= usize::from(k))
+            {
+                // decoding has been attempted and failed
+                "failed"
+            } else {
+                "no"
+            };
+            // time of day in UTC
+            let last = humantime::format_rfc3339_seconds(row.last_packet).to_string();
+            let last = last.get(11..19).unwrap_or(&last);
+            writeln!(
+                out,
+                "{image_id:>5}  {:>8}  {:>6}  {total:>6}  {complete:>8}  {decodable:<9}  {last:<8}",
+                row.received, stats.unique
+            )?;
+        }
+        writeln!(out)?;
+        writeln!(out, "packets with wrong CRC: {}", self.wrong_crc)?;
+        if let Some(event) = &self.last_event {
+            writeln!(out, "{event}")?;
+        }
+        out.flush()
+    }
+}
@@ -0,0 +1,108 @@
+//! Exit codes of the CLI application.
+//!
+//! The exit code indicates the reason of a failure, so that scripts running
+//! the CLI application can act accordingly without parsing its messages.
+
+#[cfg(feature = "upload")]
+use crate::upload::UploadError;
+use crate::{DecoderError, EncoderError};
+
+/// Exit status of the CLI application.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum ExitStatus {
+    /// The command has been successful.
+    Success = 0,
+    /// Failure not covered by other exit codes.
+    Failure = 1,
+    /// The command line arguments are invalid.
+    ///
+    /// This is the exit code used by clap for usage errors.
+    Usage = 2,
+    /// There are not enough valid packets to decode the image.
+    NotEnoughInput = 3,
+    /// The packets are invalid or inconsistent.
+    InvalidInput = 4,
+    /// Error reading or writing files, network sockets or serial ports.
+    Io = 5,
+}
+
+/// Description of the exit codes, used in the CLI help.
+pub const EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  other failures
+  2  invalid command line arguments
+  3  not enough valid packets to decode the image
+  4  invalid or inconsistent packets
+  5  I/O or network error";
+
+impl ExitStatus {
+    /// Returns the exit status corresponding to an error.
+    ///
+    /// The chain of causes of the error is searched for errors of known types.
+    pub fn from_error(err: &anyhow::Error) -> ExitStatus {
+        for cause in err.chain() {
+            if let Some(err) = cause.downcast_ref::<DecoderError>() {
+                return ExitStatus::from(*err);
+            }
+            if cause.is::<EncoderError>() {
+                return ExitStatus::InvalidInput;
+            }
+            if cause.is::<clap::Error>() {
+                return ExitStatus::Usage;
+            }
+            if cause.is::<std::io::Error>() || cause.is::<serialport::Error>() {
+                return ExitStatus::Io;
+            }
+            #[cfg(feature = "upload")]
+            if cause.is::<UploadError>() {
+                return ExitStatus::Io;
+            }
+        }
+        ExitStatus::Failure
+    }
+}
+
+impl From<DecoderError> for ExitStatus {
+    fn from(err: DecoderError) -> ExitStatus {
+        match err {
+            // more packets would allow decoding
+            DecoderError::NotEnoughInput
+            | DecoderError::UnknownNumSystematic
+            | DecoderError::NoSystematic => ExitStatus::NotEnoughInput,
+            DecoderError::OutputTooShort | DecoderError::InvalidLength => ExitStatus::Failure,
+            DecoderError::EoiOnFecPacket
+            | DecoderError::DuplicatedEoi
+            | DecoderError::NumSystematicMismatch
+            | DecoderError::EoiFecMismatch
+            | DecoderError::WrongSystematicId
+            | DecoderError::MultipleImageIds
+            | DecoderError::InconsistentFlags
+            | DecoderError::DimensionsMismatch => ExitStatus::InvalidInput,
+        }
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> std::process::ExitCode {
+        std::process::ExitCode::from(status as u8)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_exit_status() {
+        let err = anyhow::Error::from(DecoderError::NotEnoughInput).context("decoding failed");
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::NotEnoughInput);
+        let err = anyhow::Error::from(EncoderError::EmptyInput);
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::InvalidInput);
+        let err = anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::Io);
+        let err = anyhow::anyhow!("other");
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::Failure);
+    }
+}
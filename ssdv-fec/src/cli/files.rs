@@ -0,0 +1,380 @@
+//! Reading and writing of packet files.
+//!
+//! Packet files contain SSDV packets back-to-back, or in KISS frames as in the
+//! files written by the KISS file sink of gr-satellites. The functions are
+//! generic over the packet format. They can be compressed
+//! with gzip or zstd. Compressed input files are detected automatically, and
+//! the compression of output files is chosen by their extension or explicitly.
+
+use crate::{
+    encap::ax25::strip_ui_header,
+    kiss::{read_kiss_file, TimestampedFrame},
+    Packet,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+    convert::AsRef,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Compression format of a packet file.
+#[derive(ValueEnum, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Compression {
+    /// No compression.
+    None,
+    /// gzip compression.
+    Gzip,
+    /// zstd compression.
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the compression format indicated by the extension of a path.
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Compression {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Returns the file extension suffix for the compression format.
+    ///
+    /// This includes the leading dot, and it is empty for no compression.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Path used to refer to stdin or stdout instead of a file.
+pub const STDIO_PATH: &str = "-";
+
+fn is_stdio<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new(STDIO_PATH)
+}
+
+/// Opens an input file, decompressing it if needed.
+///
+/// Compressed files are detected by their magic bytes, so this works regardless
+/// of the file extension.
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>> {
+    decompress(BufReader::new(File::open(path)?))
+}
+
+// Wraps a reader with a decompressor if its data is compressed.
+fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn Read + 'a>> {
+    let header = reader.fill_buf()?;
+    Ok(if header.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(reader))
+    } else if header.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::Decoder::with_buffer(reader)?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+// Expands glob patterns in a list of paths. This is needed because some shells
+// (such as the Windows shells) do not expand globs.
+pub fn expand_globs(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if !pattern.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+        let matches = glob::glob(&pattern)?.collect::<Result<Vec<PathBuf>, _>>()?;
+        if matches.is_empty() {
+            anyhow::bail!("no files match {pattern}");
+        }
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+/// Reads all the packets from a reader.
+///
+/// An incomplete packet at the end of the input is discarded.
+pub fn read_packets<T: Packet, R: Read>(mut reader: R) -> Result<Vec<T>> {
+    let mut packets = Vec::new();
+    loop {
+        let mut packet = T::zeroed();
+        match reader.read_exact(packet.as_bytes_mut()) {
+            Err(err) if matches!(err.kind(), ErrorKind::UnexpectedEof) => return Ok(packets),
+            Err(err) => Err(err)?,
+            Ok(()) => (),
+        }
+        packets.push(packet);
+    }
+}
+
+pub fn read_ssdv_to_vec<T: Packet, P: AsRef<Path>>(path: P) -> Result<Vec<T>> {
+    read_packets(open_input(path)?)
+}
+
+/// Reads the packets in an input file, or in `stdin` if the path is `-`.
+pub fn read_input<T: Packet, P: AsRef<Path>>(path: P, stdin: &mut dyn Read) -> Result<Vec<T>> {
+    read_packets(open_input_or_stdin(path, stdin)?)
+}
+
+/// Reads the packets in an input file, or in `stdin` if the path is `-`,
+/// resynchronizing the packet boundaries.
+///
+/// This is intended for raw captures that may contain garbage between packets
+/// or be misaligned. See [`resync_packets`].
+pub fn read_input_resync<T: Packet, P: AsRef<Path>>(
+    path: P,
+    stdin: &mut dyn Read,
+) -> Result<Vec<T>> {
+    let mut data = Vec::new();
+    open_input_or_stdin(&path, stdin)?.read_to_end(&mut data)?;
+    let (packets, skipped) = resync_packets(&data);
+    if skipped > 0 {
+        message!(
+            "{}: skipped {skipped} bytes to resynchronize packet boundaries",
+            path.as_ref().display()
+        );
+    }
+    Ok(packets)
+}
+
+/// Reads the packets in a KISS file, or in `stdin` if the path is `-`.
+///
+/// This accepts the files written by the KISS file sink of gr-satellites,
+/// whose timestamp frames are skipped. If `ax25` is true, an AX.25 UI frame
+/// header is stripped from each frame. Frames that do not contain a packet are
+/// ignored.
+pub fn read_input_kiss<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ax25: bool,
+    stdin: &mut dyn Read,
+) -> Result<Vec<T>> {
+    let mut data = Vec::new();
+    open_input_or_stdin(&path, stdin)?.read_to_end(&mut data)?;
+    let mut packets = Vec::new();
+    let mut ignored = 0;
+    for frame in read_kiss_file(&data) {
+        let payload = if ax25 {
+            strip_ui_header(&frame.payload)
+        } else {
+            Some(&frame.payload[..])
+        };
+        match payload.and_then(T::slice_from_bytes) {
+            Some([packet]) => packets.push(*packet),
+            _ => ignored += 1,
+        }
+    }
+    if ignored > 0 {
+        message!(
+            "{}: ignored {ignored} KISS frames that do not contain an SSDV packet",
+            path.as_ref().display()
+        );
+    }
+    Ok(packets)
+}
+
+fn open_input_or_stdin<'a, P: AsRef<Path>>(
+    path: P,
+    stdin: &'a mut dyn Read,
+) -> Result<Box<dyn Read + 'a>> {
+    if is_stdio(&path) {
+        decompress(BufReader::new(stdin))
+    } else {
+        open_input(path)
+    }
+}
+
+/// Extracts the packets with a valid CRC from a byte stream.
+///
+/// The packet boundaries are found by sliding a window of the size of a packet
+/// over the data one byte at a time until the window contains a packet with a
+/// valid CRC-32. This tolerates bytes that have been lost or inserted between
+/// packets, as happens in serial captures. Packets with a wrong CRC are
+/// discarded. The number of bytes that do not belong to any valid packet is
+/// returned together with the packets.
+pub fn resync_packets<T: Packet>(data: &[u8]) -> (Vec<T>, usize) {
+    let mut packets = Vec::new();
+    let mut skipped = 0;
+    let mut offset = 0;
+    while offset < data.len() {
+        let packet = data
+            .get(offset..offset + T::LEN)
+            .and_then(T::slice_from_bytes);
+        match packet {
+            Some([packet]) if packet.compute_crc32() == packet.crc32() => {
+                packets.push(*packet);
+                offset += T::LEN;
+            }
+            _ => {
+                skipped += 1;
+                offset += 1;
+            }
+        }
+    }
+    (packets, skipped)
+}
+
+/// Writes packets to a file.
+///
+/// The file is compressed with the format given in `compression`, or with the
+/// format indicated by its extension if `compression` is `None`.
+pub fn write_ssdv_slice<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ssdv_packets: &[T],
+    compression: Option<Compression>,
+) -> Result<()> {
+    write_file(path, ssdv_packets, compression, false)
+}
+
+fn write_file<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ssdv_packets: &[T],
+    compression: Option<Compression>,
+    kiss: bool,
+) -> Result<()> {
+    let compression = compression.unwrap_or_else(|| Compression::from_extension(&path));
+    let file = BufWriter::new(File::create(path)?);
+    let file = write_compressed(file, ssdv_packets, compression, kiss)?;
+    file.into_inner()?.sync_all()?;
+    Ok(())
+}
+
+/// Writes packets to an output file, or to `stdout` if the path is `-`.
+///
+/// The packets written to `stdout` are only compressed if `compression`
+/// indicates so.
+pub fn write_output<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ssdv_packets: &[T],
+    compression: Option<Compression>,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    write_output_framed(path, ssdv_packets, compression, false, stdout)
+}
+
+/// Writes packets in KISS frames to an output file, or to `stdout` if the path
+/// is `-`.
+///
+/// The output can be read by gr-satellites as a KISS file. No timestamp frames
+/// are written.
+pub fn write_output_kiss<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ssdv_packets: &[T],
+    compression: Option<Compression>,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    write_output_framed(path, ssdv_packets, compression, true, stdout)
+}
+
+fn write_output_framed<T: Packet, P: AsRef<Path>>(
+    path: P,
+    ssdv_packets: &[T],
+    compression: Option<Compression>,
+    kiss: bool,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    if is_stdio(&path) {
+        let compression = compression.unwrap_or(Compression::None);
+        write_compressed(stdout, ssdv_packets, compression, kiss)?.flush()?;
+        Ok(())
+    } else {
+        write_file(path, ssdv_packets, compression, kiss)
+    }
+}
+
+fn write_compressed<T: Packet, W: Write>(
+    writer: W,
+    ssdv_packets: &[T],
+    compression: Compression,
+    kiss: bool,
+) -> Result<W> {
+    Ok(match compression {
+        Compression::None => write_packets(writer, ssdv_packets, kiss)?,
+        Compression::Gzip => {
+            let encoder = GzEncoder::new(writer, flate2::Compression::default());
+            write_packets(encoder, ssdv_packets, kiss)?.finish()?
+        }
+        Compression::Zstd => {
+            let encoder = zstd::Encoder::new(writer, 0)?;
+            write_packets(encoder, ssdv_packets, kiss)?.finish()?
+        }
+    })
+}
+
+fn write_packets<T: Packet, W: Write>(mut writer: W, ssdv_packets: &[T], kiss: bool) -> Result<W> {
+    for packet in ssdv_packets {
+        if kiss {
+            let frame = TimestampedFrame {
+                timestamp: None,
+                payload: packet.as_bytes().to_vec(),
+            };
+            writer.write_all(&frame.to_kiss())?;
+        } else {
+            writer.write_all(packet.as_bytes())?;
+        }
+    }
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::IMG_230_SSDV, SSDVPacket, SSDV_PACKET_LEN};
+
+    #[test]
+    fn compressed_round_trip() {
+        let packets: Vec<SSDVPacket> = read_packets(&IMG_230_SSDV[..]).unwrap();
+        let dir = std::env::temp_dir().join(format!("ssdv-fec-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, compression) in [
+            ("a.ssdv", None),
+            ("b.ssdv.gz", None),
+            ("c.ssdv.zst", None),
+            ("d.ssdv", Some(Compression::Zstd)),
+        ] {
+            let path = dir.join(name);
+            write_ssdv_slice(&path, &packets, compression).unwrap();
+            assert_eq!(read_ssdv_to_vec::<SSDVPacket, _>(&path).unwrap(), packets);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn kiss_round_trip() {
+        let packets: Vec<SSDVPacket> = read_packets(&IMG_230_SSDV[..]).unwrap();
+        let mut kiss = Vec::new();
+        write_output_kiss(STDIO_PATH, &packets, Some(Compression::Gzip), &mut kiss).unwrap();
+        let read = read_input_kiss::<SSDVPacket, _>(STDIO_PATH, false, &mut &kiss[..]).unwrap();
+        assert_eq!(read, packets);
+    }
+
+    #[test]
+    fn resync() {
+        let packets: Vec<SSDVPacket> = read_packets(&IMG_230_SSDV[..]).unwrap();
+        let mut data = vec![0x55, 0x66, 0x00];
+        for (j, packet) in packets.iter().enumerate() {
+            let mut packet = *packet;
+            if j == 10 {
+                packet.0[100] ^= 1;
+            }
+            data.extend_from_slice(&packet.0);
+            data.extend(std::iter::repeat_n(0xaa, j % 3));
+        }
+        let (resynced, skipped) = resync_packets::<SSDVPacket>(&data);
+        let mut expected = packets.clone();
+        expected.remove(10);
+        assert_eq!(resynced, expected);
+        assert_eq!(skipped, data.len() - expected.len() * SSDV_PACKET_LEN);
+    }
+}
@@ -0,0 +1,341 @@
+//! Inspection of the images in packet files.
+//!
+//! The `check`, `info`, `missing` and `merge` commands push the packets of the
+//! input files into an [`ImageSet`], which decodes each image as soon as
+//! possible, and report the state of each image at the end of the input.
+
+use super::report::ImageReport;
+use crate::{DecodedImage, DecoderError, DecoderOptions, ImageSet, Packet, PushResult};
+use anyhow::Result;
+use std::{collections::BTreeMap, io::Write};
+
+/// Image found in the input packets.
+#[derive(Debug)]
+pub struct Image<P> {
+    /// Image ID.
+    pub image_id: u8,
+    /// Number of systematic packets of the image, if known.
+    pub num_systematic: Option<u16>,
+    /// Distinct packets with a valid CRC, sorted by packet ID.
+    pub packets: Vec<P>,
+    /// IDs of the systematic packets that have not been received.
+    ///
+    /// This is `None` if the number of systematic packets is not known, and
+    /// empty if the image has been decoded.
+    pub missing: Option<Vec<u16>>,
+    /// Result of decoding the image.
+    pub result: Result<DecodedImage<P>, DecoderError>,
+}
+
+/// Images found in the input packets.
+#[derive(Debug)]
+pub struct Inspection<P> {
+    /// Images, sorted by image ID.
+    pub images: Vec<Image<P>>,
+    /// Number of packets with a wrong CRC.
+    pub wrong_crc: usize,
+}
+
+/// Pushes some packets into an [`ImageSet`] and returns the resulting images.
+///
+/// An image that is not decoded because there are not enough packets has the
+/// error [`DecoderError::NotEnoughInput`], or
+/// [`DecoderError::UnknownNumSystematic`] if its number of systematic packets
+/// is not known.
+pub fn inspect<P: Packet>(packets: &[P], options: DecoderOptions) -> Inspection<P> {
+    let mut images = ImageSet::with_options(options);
+    let mut received = BTreeMap::<u8, BTreeMap<u16, P>>::new();
+    let mut results = BTreeMap::new();
+    let mut wrong_crc = 0;
+    for &packet in packets {
+        match images.push(packet) {
+            PushResult::WrongCrc => {
+                wrong_crc += 1;
+                continue;
+            }
+            PushResult::Duplicate | PushResult::Stored => (),
+            PushResult::Decoded(decoded) => {
+                results.insert(packet.image_id(), Ok(decoded));
+            }
+            PushResult::DecodeFailed(err) => {
+                results.insert(packet.image_id(), Err(err));
+            }
+        }
+        // the image set does not keep the packets of the decoded images
+        received
+            .entry(packet.image_id())
+            .or_default()
+            .entry(packet.packet_id())
+            .or_insert(packet);
+    }
+    let images = received
+        .into_iter()
+        .map(|(image_id, packets)| {
+            let num_systematic = images
+                .stats(image_id)
+                .and_then(|stats| stats.num_systematic);
+            let result = results
+                .remove(&image_id)
+                .unwrap_or(Err(match num_systematic {
+                    Some(_) => DecoderError::NotEnoughInput,
+                    None => DecoderError::UnknownNumSystematic,
+                }));
+            Image {
+                image_id,
+                num_systematic,
+                packets: packets.into_values().collect(),
+                missing: images.missing_systematic(image_id),
+                result,
+            }
+        })
+        .collect();
+    Inspection { images, wrong_crc }
+}
+
+impl<P: Packet> Image<P> {
+    /// Returns the number of distinct systematic packets received.
+    pub fn systematic_received(&self) -> usize {
+        self.packets
+            .iter()
+            .filter(|packet| {
+                !packet.is_fec_packet()
+                    && self.num_systematic.is_none_or(|k| packet.packet_id() < k)
+            })
+            .count()
+    }
+
+    /// Returns the number of distinct packets that are still needed to decode
+    /// the image, if the number of systematic packets is known.
+    pub fn packets_needed(&self) -> Option<usize> {
+        match &self.result {
+            Ok(_) => Some(0),
+            Err(_) => self
+                .num_systematic
+                .map(|k| usize::from(k).saturating_sub(self.packets.len())),
+        }
+    }
+
+    /// Returns the report of the image.
+    pub fn report(&self) -> ImageReport {
+        let mut report = match &self.result {
+            Ok(decoded) => ImageReport::decoded(
+                &decoded.packets,
+                self.packets.len(),
+                self.systematic_received(),
+                None,
+            ),
+            Err(err) => {
+                let mut report = ImageReport::failed(Some(self.image_id), err.to_string());
+                report.num_systematic = self.num_systematic.map(usize::from);
+                report.valid_packets = Some(self.packets.len());
+                report
+            }
+        };
+        report.packets_needed = self.packets_needed();
+        report
+    }
+}
+
+/// Prints whether each image can be decoded.
+///
+/// An error is returned if some image cannot be decoded. If `json` is true,
+/// the report of each image is printed as JSON instead.
+pub fn check<P: Packet>(
+    inspection: &Inspection<P>,
+    json: bool,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    for image in &inspection.images {
+        if json {
+            image.report().print(stdout)?;
+            continue;
+        }
+        match &image.result {
+            Ok(decoded) => writeln!(
+                stdout,
+                "image {}: decodable ({} packets, {} recovered)",
+                image.image_id,
+                image.packets.len(),
+                decoded.packets.len() - image.systematic_received()
+            )?,
+            Err(err) => writeln!(stdout, "image {}: not decodable: {err}", image.image_id)?,
+        }
+    }
+    match inspection
+        .images
+        .iter()
+        .find_map(|image| image.result.as_ref().err())
+    {
+        Some(&err) => Err(err.into()),
+        None if inspection.images.is_empty() => {
+            Err(anyhow::Error::from(DecoderError::NotEnoughInput).context("no valid packets found"))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Prints the number of packets received for each image and whether it can
+/// be decoded.
+///
+/// If `json` is true, the report of each image is printed as JSON instead.
+pub fn info<P: Packet>(
+    inspection: &Inspection<P>,
+    json: bool,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    for image in &inspection.images {
+        if json {
+            image.report().print(stdout)?;
+            continue;
+        }
+        let num_systematic = match image.num_systematic {
+            Some(k) => k.to_string(),
+            None => "unknown".to_string(),
+        };
+        let status = match &image.result {
+            Ok(_) => "decodable".to_string(),
+            Err(err) => format!("not decodable: {err}"),
+        };
+        let systematic = image.systematic_received();
+        writeln!(
+            stdout,
+            "image {}: {num_systematic} systematic packets, received {} systematic and {} FEC packets, {status}",
+            image.image_id,
+            systematic,
+            image.packets.len() - systematic,
+        )?;
+    }
+    message!("{} packets with wrong CRC", inspection.wrong_crc);
+    Ok(())
+}
+
+/// Prints the systematic packets that are missing for each image and the
+/// number of packets still needed to decode it.
+///
+/// If `json` is true, the report of each image, including the IDs of the
+/// missing systematic packets, is printed as JSON instead.
+pub fn missing<P: Packet>(
+    inspection: &Inspection<P>,
+    json: bool,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    for image in &inspection.images {
+        if json {
+            let mut report = image.report();
+            report.missing_packets = image.missing.clone();
+            report.print(stdout)?;
+            continue;
+        }
+        match (&image.result, &image.missing, image.packets_needed()) {
+            (Ok(_), _, _) => writeln!(stdout, "image {}: decodable", image.image_id)?,
+            (Err(_), Some(missing), Some(needed)) => writeln!(
+                stdout,
+                "image {}: {needed} more packets needed, missing systematic packets {}",
+                image.image_id,
+                format_ids(missing)
+            )?,
+            _ => writeln!(
+                stdout,
+                "image {}: unknown number of systematic packets",
+                image.image_id
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the packets of all the images.
+///
+/// The images that have been decoded are replaced by their systematic
+/// packets. For the other images, the distinct valid packets are kept. The
+/// packets are sorted by image ID and then by packet ID.
+pub fn merge<P: Packet>(inspection: Inspection<P>) -> Vec<P> {
+    inspection
+        .images
+        .into_iter()
+        .flat_map(|image| match image.result {
+            Ok(decoded) => decoded.packets,
+            Err(_) => image.packets,
+        })
+        .collect()
+}
+
+// Formats a sorted list of packet IDs, joining consecutive IDs in ranges.
+fn format_ids(ids: &[u16]) -> String {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for &id in ids {
+        match ranges.last_mut() {
+            Some((_, last)) if last.checked_add(1) == Some(id) => *last = id,
+            _ => ranges.push((id, id)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Encoder, SSDVPacket};
+
+    #[test]
+    fn partial_image() {
+        let mut packets = img_230_packets();
+        let k = packets.len();
+        let encoder = Encoder::new(&mut packets).unwrap();
+        let mut fec = SSDVPacket::zeroed();
+        // the systematic packets 1 to 4 are lost
+        let mut received = img_230_packets();
+        received.drain(1..5);
+        let mut corrupted = received[0];
+        corrupted.0[50] ^= 0xff;
+        received.push(corrupted);
+        let inspection = inspect(&received, DecoderOptions::default());
+        assert_eq!(inspection.wrong_crc, 1);
+        let image = &inspection.images[0];
+        assert_eq!(image.num_systematic, Some(k as u16));
+        assert_eq!(image.missing, Some(vec![1, 2, 3, 4]));
+        assert_eq!(image.packets_needed(), Some(4));
+        assert!(matches!(image.result, Err(DecoderError::NotEnoughInput)));
+        assert!(check(&inspection, false, &mut Vec::new()).is_err());
+        let mut output = Vec::new();
+        missing(&inspection, false, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "image 230: 4 more packets needed, missing systematic packets 1-4\n"
+        );
+
+        // with FEC packets the image is decoded, and merging gives all the
+        // systematic packets
+        for j in 0..4 {
+            encoder.encode(u16::try_from(k + j).unwrap(), &mut fec);
+            received.push(fec);
+        }
+        received.push(fec);
+        let inspection = inspect(&received, DecoderOptions::default());
+        let image = &inspection.images[0];
+        assert_eq!(image.missing, Some(Vec::new()));
+        assert_eq!(image.packets_needed(), Some(0));
+        let report = image.report();
+        assert_eq!(report.valid_packets, Some(k));
+        assert_eq!(report.recovered_packets, Some(4));
+        check(&inspection, false, &mut Vec::new()).unwrap();
+        assert_eq!(merge(inspection), img_230_packets());
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(format_ids(&[]), "");
+        assert_eq!(format_ids(&[3]), "3");
+        assert_eq!(format_ids(&[1, 2, 3, 5, 7, 8, 65535]), "1-3, 5, 7-8, 65535");
+    }
+}
@@ -0,0 +1,36 @@
+//! Block interleaving of packet sequences.
+
+// The block interleaver writes the packets row by row into a matrix with
+// `depth` columns and reads them column by column. The last row of the matrix
+// can be incomplete.
+//
+// There is no deinterleaver, because the decoder does not depend on the order
+// of the packets.
+pub fn interleave_packets<T: Copy>(packets: &[T], depth: usize) -> Vec<T> {
+    (0..depth)
+        .flat_map(|column| packets.iter().skip(column).step_by(depth).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interleave() {
+        for n in 0..50 {
+            let packets = (0..n).collect::<Vec<usize>>();
+            for depth in 1..=12 {
+                let interleaved = interleave_packets(&packets, depth);
+                assert_eq!(interleaved.len(), n);
+                let first_column = n.div_ceil(depth);
+                for (j, &packet) in interleaved[..first_column].iter().enumerate() {
+                    assert_eq!(packet, j * depth);
+                }
+                let mut sorted = interleaved.clone();
+                sorted.sort_unstable();
+                assert_eq!(sorted, packets);
+            }
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Live reception and decoding of SSDV FEC packets.
+
+#[cfg(feature = "metrics")]
+use super::metrics::{self, Metrics};
+#[cfg(feature = "mqtt")]
+use super::mqtt::Publisher;
+#[cfg(feature = "upload")]
+use super::upload::Uploader;
+use super::{
+    dashboard::Dashboard,
+    files::{write_ssdv_slice, Compression},
+    print_overrides,
+    report::{ImageReport, Sidecar},
+    template::{OutputTemplate, TemplateValues},
+    FilterArgs, ReceptionArgs,
+};
+use crate::{
+    agwpe::AgwpeClient,
+    net::PacketSocket,
+    transport::{ByteStream, PacketSource, Received},
+    ImageSet, Packet, PacketMetadata, PushResult, ReceivedPacket,
+};
+#[cfg(feature = "upload")]
+use crate::{format::Format, SSDVPacket};
+use anyhow::Result;
+use serialport::SerialPort;
+#[cfg(feature = "upload")]
+use std::any::Any;
+use std::{
+    fs,
+    io::Write,
+    net::{SocketAddr, TcpStream},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+#[cfg(feature = "metrics")]
+use std::{sync::Arc, time::Instant};
+
+// Timeout of the sources, which is used to refresh the dashboard periodically
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Opens a UDP source.
+///
+/// Each UDP datagram should contain one SSDV packet, or one or more KISS
+/// frames containing one SSDV packet each if KISS framing is used.
+pub fn udp_source(addr: SocketAddr) -> Result<PacketSocket> {
+    let socket = PacketSocket::bind(addr)?;
+    socket.get_ref().set_read_timeout(Some(REFRESH_INTERVAL))?;
+    message!("listening on UDP {}", socket.get_ref().local_addr()?);
+    Ok(socket)
+}
+
+/// Opens a serial port source.
+///
+/// The packets should be sent back-to-back in the byte stream, or each packet
+/// should be sent in a KISS frame if KISS framing is used.
+pub fn serial_source(path: &str, baud_rate: u32) -> Result<ByteStream<Box<dyn SerialPort>>> {
+    let port = serialport::new(path, baud_rate)
+        .timeout(REFRESH_INTERVAL)
+        .open()?;
+    message!("listening on serial port {path}");
+    Ok(ByteStream::new(port))
+}
+
+/// Opens a TCP source.
+///
+/// This is intended to receive from the KISS server of a software modem such
+/// as Direwolf, in which case KISS framing should be used. Otherwise, the
+/// packets are expected back-to-back in the byte stream.
+pub fn tcp_source(addr: &str) -> Result<ByteStream<TcpStream>> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(REFRESH_INTERVAL))?;
+    message!("connected to TCP {}", stream.peer_addr()?);
+    Ok(ByteStream::new(stream))
+}
+
+/// Opens an AGWPE packet engine source.
+///
+/// Each AX.25 UI frame should contain one SSDV packet. If `port` is given,
+/// only the frames received on this radio port are used. Other AX.25 frames
+/// are ignored.
+pub fn agwpe_source(addr: &str, port: Option<u8>) -> Result<AgwpeClient<TcpStream>> {
+    let mut client = AgwpeClient::connect(addr)?;
+    if let Some(port) = port {
+        client = client.with_port(port);
+    }
+    client.get_ref().set_read_timeout(Some(REFRESH_INTERVAL))?;
+    message!("connected to AGWPE {}", client.get_ref().peer_addr()?);
+    Ok(client)
+}
+
+/// Receives packets from a source and decodes images as they complete.
+///
+/// The packets are in the format given by `P`. This only returns if there is
+/// an error or the source is closed.
+pub fn listen<P: Packet + 'static, S: PacketSource>(
+    mut source: S,
+    args: &ReceptionArgs,
+    stdout: &mut dyn Write,
+) -> Result<()> {
+    let mut reception = Reception::<P>::new(args, stdout)?;
+    loop {
+        // The datagrams are validated by the reception, which reports the
+        // invalid ones.
+        match source.recv()? {
+            Received::Datagram(datagram, metadata) => {
+                reception.handle_frame(&datagram, metadata)?
+            }
+            Received::Stream(bytes) => reception.push_stream(&bytes)?,
+            Received::Timeout => reception.refresh_display()?,
+            Received::Closed => anyhow::bail!("the source was closed"),
+        }
+    }
+}
+
+/// Reception state.
+///
+/// This handles the accumulation and decoding of the received packets, which
+/// are in the format given by `P`. The JSON reports and the dashboard are
+/// written to stdout.
+pub struct Reception<'a, P: Packet> {
+    images: ImageSet<P>,
+    stream_buffer: Vec<u8>,
+    json: bool,
+    filter: &'a FilterArgs,
+    output_dir: PathBuf,
+    output_template: OutputTemplate,
+    sidecar: bool,
+    compress: Compression,
+    callsign: Option<String>,
+    #[cfg(feature = "upload")]
+    uploader: Option<Uploader>,
+    #[cfg(feature = "mqtt")]
+    publisher: Option<Publisher>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+    dashboard: Option<Dashboard>,
+    stdout: &'a mut dyn Write,
+}
+
+impl<'a, P: Packet + 'static> Reception<'a, P> {
+    /// Creates a new reception state with the configuration given by `args`.
+    pub fn new(args: &'a ReceptionArgs, stdout: &'a mut dyn Write) -> Result<Reception<'a, P>> {
+        args.filter.check(args.format)?;
+        #[cfg(feature = "upload")]
+        if args.upload.is_some() && args.format != Format::Lj2 {
+            // the SSDV server receives the packets in the standard format, to
+            // which only the lj2 packets are converted
+            anyhow::bail!("--upload requires --format lj2");
+        }
+        #[cfg(feature = "upload")]
+        let callsign = args.upload.clone();
+        #[cfg(not(feature = "upload"))]
+        let callsign = None;
+        if args.output_template.uses("callsign") && callsign.is_none() {
+            anyhow::bail!("the {{callsign}} placeholder of --output-template requires --upload");
+        }
+        if args.tui {
+            super::output::set_dashboard();
+        }
+        #[cfg(feature = "metrics")]
+        let metrics = match args.metrics {
+            Some(addr) => {
+                let metrics = Arc::new(Metrics::new());
+                metrics::spawn_exporter(addr, Arc::clone(&metrics))?;
+                Some(metrics)
+            }
+            None => None,
+        };
+        Ok(Reception {
+            images: ImageSet::with_options(args.decoder.options()),
+            stream_buffer: Vec::new(),
+            json: args.json,
+            filter: &args.filter,
+            output_dir: args.output_dir.clone(),
+            output_template: args.output_template.clone(),
+            sidecar: args.sidecar,
+            compress: args.compress,
+            callsign,
+            #[cfg(feature = "upload")]
+            uploader: args
+                .upload
+                .clone()
+                .map(|callsign| Uploader::new(args.upload_url.clone(), callsign)),
+            #[cfg(feature = "mqtt")]
+            publisher: args
+                .mqtt_publish
+                .as_deref()
+                .map(|broker| Publisher::new(broker, args.mqtt_prefix.clone()))
+                .transpose()?,
+            #[cfg(feature = "metrics")]
+            metrics,
+            dashboard: args.tui.then(Dashboard::new),
+            stdout,
+        })
+    }
+
+    /// Redraws the dashboard, if it is used.
+    pub fn refresh_display(&mut self) -> Result<()> {
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.draw(true, self.stdout)?;
+        }
+        Ok(())
+    }
+
+    // Reports an event, either in the dashboard or as a message in stderr
+    fn report(&mut self, event: String) {
+        match &mut self.dashboard {
+            Some(dashboard) => dashboard.event(event),
+            None => message!("{event}"),
+        }
+    }
+
+    // Handles a chunk of a byte stream, which contains back-to-back packets
+    fn push_stream(&mut self, bytes: &[u8]) -> Result<()> {
+        self.stream_buffer.extend_from_slice(bytes);
+        let len = self.stream_buffer.len() / P::LEN * P::LEN;
+        let packets = self.stream_buffer.drain(..len).collect::<Vec<u8>>();
+        for packet in packets.chunks_exact(P::LEN) {
+            self.handle_frame(packet, PacketMetadata::default())?;
+        }
+        Ok(())
+    }
+
+    /// Handles a received frame, which should contain a single packet,
+    /// together with the metadata given by the receiver.
+    pub fn handle_frame(&mut self, frame: &[u8], metadata: PacketMetadata) -> Result<()> {
+        let Some(&[packet]) = P::slice_from_bytes(frame) else {
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &self.metrics {
+                metrics.ignored_frame();
+            }
+            self.report(format!(
+                "ignoring frame of {} bytes (expected {} bytes)",
+                frame.len(),
+                P::LEN
+            ));
+            return Ok(());
+        };
+        let image_id = packet.image_id();
+        if !self.filter.matches(&packet) {
+            return Ok(());
+        }
+        #[cfg(feature = "metrics")]
+        let push_start = Instant::now();
+        let push_result = self
+            .images
+            .push_received(ReceivedPacket::new(packet).with_metadata(metadata));
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.push_result(&push_result, push_start.elapsed());
+        }
+        #[cfg(feature = "upload")]
+        if let Some(uploader) = &mut self.uploader {
+            // the uploader is only created for lj2 packets
+            let as_lj2 = |packet: &P| (packet as &dyn Any).downcast_ref::<SSDVPacket>().copied();
+            match &push_result {
+                PushResult::WrongCrc => (),
+                PushResult::Decoded(decoded) => {
+                    for packet in decoded.packets.iter().filter_map(as_lj2) {
+                        uploader.push(&packet);
+                    }
+                }
+                _ => {
+                    if let Some(packet) = as_lj2(&packet) {
+                        uploader.push(&packet);
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &mut self.publisher {
+            match &push_result {
+                PushResult::WrongCrc => (),
+                PushResult::Decoded(decoded) => {
+                    publisher.push_packet(&packet);
+                    publisher.push_image(image_id, &decoded.packets);
+                }
+                _ => publisher.push_packet(&packet),
+            }
+        }
+        if let Some(dashboard) = &mut self.dashboard {
+            match self.images.stats(image_id) {
+                Some(stats) if !matches!(push_result, PushResult::WrongCrc) => {
+                    dashboard.packet(image_id, stats)
+                }
+                _ => dashboard.wrong_crc(),
+            }
+        }
+        // events that change the state of an image are shown immediately
+        let redraw = !matches!(
+            push_result,
+            PushResult::WrongCrc | PushResult::Duplicate | PushResult::Stored
+        );
+        match push_result {
+            PushResult::WrongCrc => self.report("ignoring packet with wrong CRC".to_string()),
+            PushResult::Duplicate | PushResult::Stored => (),
+            PushResult::DecodeFailed(err) => {
+                self.report(format!("could not decode image {image_id}: {err}"));
+                if self.json {
+                    ImageReport::failed(Some(image_id), err.to_string()).print(self.stdout)?;
+                }
+            }
+            PushResult::Decoded(decoded) => {
+                print_overrides(Some(image_id), &decoded.overrides);
+                let decode_time = SystemTime::now();
+                let mut path = self
+                    .output_dir
+                    .join(self.output_template.expand(&TemplateValues {
+                        image_id,
+                        callsign: self.callsign.as_deref(),
+                        timestamp: decode_time,
+                    })?)
+                    .into_os_string();
+                path.push(self.compress.extension());
+                let path = PathBuf::from(path);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_ssdv_slice(&path, &decoded.packets, Some(self.compress))?;
+                self.report(format!(
+                    "decoded image {image_id} ({} packets) to {}",
+                    decoded.packets.len(),
+                    path.display()
+                ));
+                let report = ImageReport::decoded(
+                    &decoded.packets,
+                    decoded.num_received,
+                    decoded.num_systematic_received,
+                    Some(path.clone()),
+                );
+                #[cfg(feature = "image")]
+                if let Some(verdict) = report.image.as_ref().filter(|v| !v.is_clean()) {
+                    self.report(format!(
+                        "image {image_id} does not decode cleanly: {verdict}"
+                    ));
+                }
+                if self.sidecar {
+                    let mut sidecar_path = path.into_os_string();
+                    sidecar_path.push(".json");
+                    Sidecar::new(
+                        report.clone(),
+                        self.callsign.clone(),
+                        decoded.first_packet_time,
+                        decode_time,
+                    )
+                    .with_packet_metadata(&decoded.metadata)
+                    .write(sidecar_path.as_ref())?;
+                }
+                if self.json {
+                    report.print(self.stdout)?;
+                }
+            }
+        }
+        if let Some(dashboard) = &mut self.dashboard {
+            dashboard.draw(redraw, self.stdout)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+//! Prometheus metrics.
+//!
+//! The reception of packets is monitored with some counters and histograms,
+//! which are exported in the Prometheus text format at the `/metrics` endpoint
+//! of an HTTP server.
+
+use crate::PushResult;
+use anyhow::Result;
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{net::TcpListener, runtime::Builder};
+
+// Upper bounds of the buckets of the decode latency histogram, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0,
+];
+// Upper bounds of the buckets of the decoding duration histogram, in seconds
+const DURATION_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Prometheus histogram.
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    // cumulative counts of the buckets, excluding the +Inf bucket
+    buckets: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            buckets: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bucket, &bound) in self.buckets.iter_mut().zip(self.bounds) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn render(&self, name: &str, help: &str, out: &mut String) {
+        writeln!(out, "# HELP {name} {help}").unwrap();
+        writeln!(out, "# TYPE {name} histogram").unwrap();
+        for (bucket, bound) in self.buckets.iter().zip(self.bounds) {
+            writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {bucket}").unwrap();
+        }
+        writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count).unwrap();
+        writeln!(out, "{name}_sum {}", self.sum).unwrap();
+        writeln!(out, "{name}_count {}", self.count).unwrap();
+    }
+}
+
+/// Reception metrics.
+///
+/// The metrics can be updated and rendered concurrently.
+#[derive(Debug)]
+pub struct Metrics {
+    packets: AtomicU64,
+    wrong_crc: AtomicU64,
+    duplicates: AtomicU64,
+    ignored_frames: AtomicU64,
+    decoded: AtomicU64,
+    decode_failures: AtomicU64,
+    latency: Mutex<Histogram>,
+    duration: Mutex<Histogram>,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics {
+            packets: AtomicU64::new(0),
+            wrong_crc: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            ignored_frames: AtomicU64::new(0),
+            decoded: AtomicU64::new(0),
+            decode_failures: AtomicU64::new(0),
+            latency: Mutex::new(Histogram::new(LATENCY_BUCKETS)),
+            duration: Mutex::new(Histogram::new(DURATION_BUCKETS)),
+        }
+    }
+}
+
+impl Metrics {
+    /// Creates new metrics with all the counters set to zero.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Records a frame that has been ignored because it does not contain a
+    /// packet.
+    pub fn ignored_frame(&self) {
+        self.ignored_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the result of pushing a packet into an image set.
+    ///
+    /// The `duration` is the time taken by the push, which includes the
+    /// decoding of the image if it was attempted.
+    pub fn push_result<P>(&self, result: &PushResult<P>, duration: Duration) {
+        self.packets.fetch_add(1, Ordering::Relaxed);
+        match result {
+            PushResult::WrongCrc => {
+                self.wrong_crc.fetch_add(1, Ordering::Relaxed);
+            }
+            PushResult::Duplicate => {
+                self.duplicates.fetch_add(1, Ordering::Relaxed);
+            }
+            PushResult::Stored => (),
+            PushResult::DecodeFailed(_) => {
+                self.decode_failures.fetch_add(1, Ordering::Relaxed);
+                self.duration
+                    .lock()
+                    .unwrap()
+                    .observe(duration.as_secs_f64());
+            }
+            PushResult::Decoded(decoded) => {
+                self.decoded.fetch_add(1, Ordering::Relaxed);
+                self.duration
+                    .lock()
+                    .unwrap()
+                    .observe(duration.as_secs_f64());
+                // the clock may have gone backwards
+                let latency = SystemTime::now()
+                    .duration_since(decoded.first_packet_time)
+                    .unwrap_or_default();
+                self.latency.lock().unwrap().observe(latency.as_secs_f64());
+            }
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, help, counter) in [
+            (
+                "ssdv_fec_packets_received_total",
+                "Number of packets received.",
+                &self.packets,
+            ),
+            (
+                "ssdv_fec_packets_wrong_crc_total",
+                "Number of packets received with a wrong CRC.",
+                &self.wrong_crc,
+            ),
+            (
+                "ssdv_fec_packets_duplicate_total",
+                "Number of duplicate packets received.",
+                &self.duplicates,
+            ),
+            (
+                "ssdv_fec_frames_ignored_total",
+                "Number of frames ignored because they do not contain a packet.",
+                &self.ignored_frames,
+            ),
+            (
+                "ssdv_fec_images_decoded_total",
+                "Number of images decoded.",
+                &self.decoded,
+            ),
+            (
+                "ssdv_fec_decode_failures_total",
+                "Number of failed decoding attempts.",
+                &self.decode_failures,
+            ),
+        ] {
+            writeln!(out, "# HELP {name} {help}").unwrap();
+            writeln!(out, "# TYPE {name} counter").unwrap();
+            writeln!(out, "{name} {}", counter.load(Ordering::Relaxed)).unwrap();
+        }
+        self.latency.lock().unwrap().render(
+            "ssdv_fec_decode_latency_seconds",
+            "Time from the reception of the first packet of an image to its decoding.",
+            &mut out,
+        );
+        self.duration.lock().unwrap().render(
+            "ssdv_fec_decode_duration_seconds",
+            "Time spent in the FEC decoder for each decoding attempt.",
+            &mut out,
+        );
+        out
+    }
+}
+
+/// Returns a router that serves the metrics at `/metrics`.
+pub fn router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(render_metrics))
+        .with_state(metrics)
+}
+
+async fn render_metrics(State(metrics): State<Arc<Metrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+/// Serves the metrics over HTTP in a background thread.
+///
+/// The address is bound before returning, so that binding errors are
+/// reported.
+pub fn spawn_exporter(addr: SocketAddr, metrics: Arc<Metrics>) -> Result<()> {
+    let runtime = Builder::new_current_thread().enable_all().build()?;
+    let listener = runtime.block_on(TcpListener::bind(addr))?;
+    message!("serving Prometheus metrics on {}", listener.local_addr()?);
+    super::output::spawn(move || {
+        if let Err(err) = runtime.block_on(async { axum::serve(listener, router(metrics)).await }) {
+            message!("metrics exporter failed: {err}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        test_data::{img_230_packets, IMG_230_SSDV},
+        ImageSet, SSDVPacket, SSDV_PACKET_LEN,
+    };
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::new();
+        let mut images = ImageSet::new();
+        let mut corrupted = SSDVPacket(IMG_230_SSDV[..SSDV_PACKET_LEN].try_into().unwrap());
+        corrupted.0[20] ^= 1;
+        metrics.push_result(&images.push(corrupted), Duration::ZERO);
+        for packet in img_230_packets() {
+            metrics.push_result(&images.push(packet), Duration::from_millis(2));
+        }
+        metrics.ignored_frame();
+        let text = metrics.render();
+        let packets = IMG_230_SSDV.len() / SSDV_PACKET_LEN;
+        for line in [
+            format!("ssdv_fec_packets_received_total {}", packets + 1),
+            "ssdv_fec_packets_wrong_crc_total 1".to_string(),
+            "ssdv_fec_frames_ignored_total 1".to_string(),
+            "ssdv_fec_images_decoded_total 1".to_string(),
+            "# TYPE ssdv_fec_decode_latency_seconds histogram".to_string(),
+            "ssdv_fec_decode_latency_seconds_bucket{le=\"1\"} 1".to_string(),
+            "ssdv_fec_decode_duration_seconds_bucket{le=\"0.001\"} 0".to_string(),
+            "ssdv_fec_decode_duration_seconds_bucket{le=\"0.005\"} 1".to_string(),
+            "ssdv_fec_decode_duration_seconds_bucket{le=\"+Inf\"} 1".to_string(),
+            "ssdv_fec_decode_duration_seconds_sum 0.002".to_string(),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line:?} in\n{text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1641 @@
+//! CLI application.
+//!
+//! This module implements the CLI application for encoding and decoding with
+//! SSDV FEC.
+
+use crate::{
+    encode_callsign,
+    format::{with_format, Format},
+    transport::{Kiss, PacketSink, PacketSource},
+    Decoder, DecoderOptions, Encoder, FountainScheduler, HeaderOverrides, Packet, SSDVPacket,
+};
+use anyhow::Result;
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::{
+    ffi::OsString,
+    io::{Read, Write},
+    net::SocketAddr,
+    path::PathBuf,
+    time::Duration,
+};
+
+// Prints an informational message to stderr unless --quiet is used or the
+// dashboard is shown.
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if !$crate::cli::output::quiet() {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+mod analyze;
+mod bench;
+mod clean;
+mod dashboard;
+mod exit;
+pub use exit::ExitStatus;
+mod files;
+use files::{
+    expand_globs, read_input, read_input_kiss, read_input_resync, read_ssdv_to_vec, write_output,
+    write_output_kiss, Compression,
+};
+mod inspect;
+use inspect::Inspection;
+mod interleave;
+use interleave::interleave_packets;
+mod listen;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod output;
+use output::Output;
+mod pcap;
+use pcap::{read_pcap_to_vec, PayloadOptions};
+mod relay;
+mod replay;
+use replay::Timing;
+mod report;
+use report::ImageReport;
+mod selftest;
+#[cfg(feature = "serve")]
+mod serve;
+mod synthetic;
+mod template;
+use template::OutputTemplate;
+mod transmit;
+#[cfg(feature = "upload")]
+mod upload;
+use transmit::{Rate, Transmitter};
+mod watch;
+#[cfg(feature = "zmq")]
+mod zmq;
+
+/// SSDV FEC encoder and decoder.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(after_long_help = exit::EXIT_CODES_HELP)]
+struct Args {
+    /// Do not print messages to stderr.
+    ///
+    /// Errors are only indicated by the exit code. This is intended for
+    /// running the application from scripts.
+    #[arg(long, short, global = true)]
+    quiet: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Encode an SSDV FEC packet.
+    Encode {
+        /// First packet ID.
+        #[arg(long, default_value_t = 0, conflicts_with = "append")]
+        first: u16,
+        /// Append the packets to the existing output file.
+        ///
+        /// The packets are generated starting at the packet ID that follows
+        /// the highest packet ID in the output file, so that no duplicate
+        /// packets are generated. If the output file does not exist, it is
+        /// created as usual.
+        #[arg(long)]
+        append: bool,
+        /// Number of packets to encode.
+        #[arg(long)]
+        npackets: Option<u16>,
+        /// Coding rate to use.
+        ///
+        /// Mutually exclusive with npackets, overhead and extra.
+        ///
+        /// Chooses the number of packets as the number of packes in the input
+        /// divided by the rate.
+        #[arg(long)]
+        rate: Option<f64>,
+        /// Overhead of FEC packets.
+        ///
+        /// Mutually exclusive with npackets, rate and extra.
+        ///
+        /// This is given as a percentage of the number of packets in the input
+        /// (for example "20%"). Chooses the number of packets as the number of
+        /// packets in the input plus this percentage, rounded up.
+        #[arg(long, value_parser = parse_percent)]
+        overhead: Option<u16>,
+        /// Number of extra packets.
+        ///
+        /// Mutually exclusive with npackets, rate and overhead.
+        ///
+        /// Chooses the number of packets as the number of packets in the input
+        /// plus this number.
+        #[arg(long)]
+        extra: Option<u16>,
+        /// Interleaving depth.
+        ///
+        /// If given, the encoded packets are reordered with a block
+        /// interleaver of this depth before being written to the output, so
+        /// that packets which are consecutive in the output are `depth`
+        /// packet IDs apart.
+        #[arg(long)]
+        interleave: Option<usize>,
+        /// Packet format of the input and output files.
+        #[arg(long, value_enum, default_value_t = Format::Lj2)]
+        format: Format,
+        /// Compression of the output file.
+        ///
+        /// By default, the compression is chosen by the extension of the
+        /// output file (".gz" for gzip and ".zst" for zstd).
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Write the packets in KISS frames.
+        ///
+        /// The output file can be read by gr-satellites as a KISS file.
+        #[arg(long, conflicts_with = "append")]
+        kiss: bool,
+        /// Input file (original SSDV image), or "-" for stdin.
+        input: PathBuf,
+        /// Output file (encoded SSDV packet), or "-" for stdout.
+        output: PathBuf,
+    },
+    /// Decode an SSDV FEC image.
+    ///
+    /// The order of the received packets does not matter, so packets
+    /// interleaved by the encoder do not need to be deinterleaved.
+    Decode {
+        /// Print a JSON report of the decoding results to stdout.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        decoder: DecoderArgs,
+        /// Packet format of the input files and the output file.
+        #[arg(long, value_enum, default_value_t = Format::Lj2)]
+        format: Format,
+        /// Compression of the output file.
+        ///
+        /// By default, the compression is chosen by the extension of the
+        /// output file (".gz" for gzip and ".zst" for zstd).
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// The input files are pcap or pcapng captures.
+        ///
+        /// The packets are extracted from the payloads of the UDP datagrams in
+        /// the captures.
+        #[arg(long)]
+        pcap: bool,
+        /// Only use UDP datagrams sent to this port in pcap captures.
+        #[arg(long, requires = "pcap")]
+        port: Option<u16>,
+        /// The input files contain KISS frames.
+        ///
+        /// This reads the files written by the KISS file sink of
+        /// gr-satellites, skipping their timestamp frames. With --pcap, the UDP
+        /// payloads in the captures contain KISS frames.
+        #[arg(long)]
+        kiss: bool,
+        /// Strip an AX.25 UI frame header from the packets in pcap captures or
+        /// KISS files.
+        #[arg(long)]
+        ax25: bool,
+        /// Resynchronize the packet boundaries in the input files.
+        ///
+        /// The input files are scanned for packets with a valid CRC at any
+        /// byte offset, so that raw serial captures with garbage between
+        /// packets or misaligned packets can be used.
+        #[arg(long, conflicts_with_all = ["pcap", "kiss"])]
+        resync: bool,
+        /// Input files (received SSDV FEC packets).
+        ///
+        /// The packets of all the input files are merged. Glob patterns such
+        /// as "captures/*.bin" are expanded. The packets are read from stdin
+        /// for an input given as "-".
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Output file (recovered SSDV image), or "-" for stdout.
+        output: PathBuf,
+    },
+    /// Check whether the images in some packet files can be decoded.
+    ///
+    /// The exit code corresponds to the first image that cannot be decoded.
+    Check {
+        #[command(flatten)]
+        args: InspectArgs,
+    },
+    /// Show the packets received for each image in some packet files.
+    Info {
+        #[command(flatten)]
+        args: InspectArgs,
+    },
+    /// Show the systematic packets that are missing for each image in some
+    /// packet files.
+    Missing {
+        #[command(flatten)]
+        args: InspectArgs,
+    },
+    /// Receive SSDV FEC packets and decode images as they complete.
+    #[command(group(ArgGroup::new("source").required(true)))]
+    Listen {
+        /// UDP address to listen on.
+        ///
+        /// Each UDP datagram should contain a single SSDV FEC packet (or KISS
+        /// frames if --kiss is used).
+        #[arg(long, group = "source")]
+        udp: Option<SocketAddr>,
+        /// Serial port to receive from.
+        ///
+        /// The packets should be sent back-to-back (or in KISS frames if --kiss
+        /// is used).
+        #[arg(long, group = "source")]
+        serial: Option<String>,
+        /// Serial port baud rate.
+        #[arg(long, default_value_t = 115200)]
+        baud: u32,
+        /// TCP server to connect to, such as the KISS server of Direwolf
+        /// (which listens on port 8001 by default).
+        ///
+        /// The packets should be sent back-to-back (or in KISS frames if --kiss
+        /// is used).
+        #[arg(long, group = "source")]
+        tcp: Option<String>,
+        /// AGWPE packet engine to connect to, such as Direwolf or Soundmodem
+        /// (which listen on port 8000 by default).
+        ///
+        /// Each AX.25 UI frame received by the packet engine should contain a
+        /// single SSDV FEC packet.
+        #[arg(long, group = "source", value_name = "ADDR")]
+        agwpe: Option<String>,
+        /// Only receive from this radio port of the AGWPE packet engine.
+        ///
+        /// The first radio port is port 0.
+        #[arg(long, requires = "agwpe")]
+        agwpe_port: Option<u8>,
+        /// MQTT broker to receive from, given as HOST[:PORT].
+        ///
+        /// Each message published to the topic given by --mqtt-topic should
+        /// contain a single SSDV FEC packet (or KISS frames if --kiss is used).
+        #[cfg(feature = "mqtt")]
+        #[arg(long, group = "source", value_name = "BROKER")]
+        mqtt: Option<String>,
+        /// MQTT topic to subscribe to.
+        ///
+        /// Wildcards can be used to receive from several topics.
+        #[cfg(feature = "mqtt")]
+        #[arg(long, default_value = mqtt::DEFAULT_TOPIC)]
+        mqtt_topic: String,
+        /// ZeroMQ PUB socket to subscribe to, such as tcp://127.0.0.1:5555.
+        ///
+        /// Each message should contain a single SSDV FEC packet (or KISS frames
+        /// if --kiss is used), either as raw bytes or as a GNU Radio PDU.
+        #[cfg(feature = "zmq")]
+        #[arg(long, group = "source", value_name = "ENDPOINT")]
+        zmq: Option<String>,
+        /// Use KISS framing for the received packets.
+        #[arg(long, conflicts_with = "agwpe")]
+        kiss: bool,
+        #[command(flatten)]
+        reception: ReceptionArgs,
+    },
+    /// Upload the packets in some files to an SSDV server.
+    ///
+    /// The images are decoded when possible, so that the systematic packets
+    /// recovered with the FEC are also uploaded.
+    #[cfg(feature = "upload")]
+    Upload {
+        /// Receiver callsign.
+        #[arg(long)]
+        callsign: String,
+        /// URL of the SSDV server API.
+        #[arg(long, default_value = upload::DEFAULT_URL)]
+        url: String,
+        /// Input files (received SSDV FEC packets).
+        ///
+        /// Glob patterns such as "captures/*.bin" are expanded.
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+    },
+    /// Watch a directory for packet files and decode images as they complete.
+    Watch {
+        /// Interval between directory scans, in seconds.
+        #[arg(long, default_value_t = 1.0)]
+        interval: f64,
+        #[command(flatten)]
+        reception: ReceptionArgs,
+        /// Directory to watch.
+        dir: PathBuf,
+    },
+    /// Run an HTTP API server that decodes the packets submitted to it.
+    ///
+    /// Packets are submitted with POST /packets, with the packets back-to-back
+    /// in the request body. The status of the images can be queried with GET
+    /// /images and GET /images/<ID>, and the decoded images can be downloaded
+    /// with GET /images/<ID>/ssdv and GET /images/<ID>/jpeg.
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        #[command(flatten)]
+        decoder: DecoderArgs,
+    },
+    /// Remove invalid and duplicate packets and sort the packets.
+    ///
+    /// Packets with a wrong CRC and duplicate packets are removed, and the
+    /// remaining packets are sorted by image ID and then by packet ID. This
+    /// produces canonical files that can be compared between receivers.
+    Clean {
+        /// Compression of the output file.
+        ///
+        /// By default, the compression is chosen by the extension of the
+        /// output file (".gz" for gzip and ".zst" for zstd).
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Input files (received SSDV FEC packets).
+        ///
+        /// The packets of all the input files are merged. Glob patterns such
+        /// as "captures/*.bin" are expanded. The packets are read from stdin
+        /// for an input given as "-".
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Output file, or "-" for stdout.
+        output: PathBuf,
+    },
+    /// Merge packet files, replacing the images that can be decoded by their
+    /// systematic packets.
+    ///
+    /// This is intended to combine the packets received by several receivers.
+    /// Packets with a wrong CRC and duplicate packets are removed, and the
+    /// packets are sorted by image ID and then by packet ID. The images that
+    /// can be decoded are written complete, including the systematic packets
+    /// recovered by the FEC decoder and without FEC packets.
+    Merge {
+        /// Packet format of the input files and the output file.
+        #[arg(long, value_enum, default_value_t = Format::Lj2)]
+        format: Format,
+        #[command(flatten)]
+        filter: FilterArgs,
+        #[command(flatten)]
+        decoder: DecoderArgs,
+        /// Compression of the output file.
+        ///
+        /// By default, the compression is chosen by the extension of the
+        /// output file (".gz" for gzip and ".zst" for zstd).
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Input files (received SSDV FEC packets).
+        ///
+        /// Glob patterns such as "captures/*.bin" are expanded. The packets
+        /// are read from stdin for an input given as "-".
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Output file, or "-" for stdout.
+        output: PathBuf,
+    },
+    /// Compute the number of packets to transmit for a given packet loss rate.
+    ///
+    /// The packets are assumed to be lost independently. The number of
+    /// packets is the minimum that allows decoding the image with the desired
+    /// probability when the packets are encoded starting at packet ID 0.
+    #[command(group(ArgGroup::new("image").required(true)))]
+    Analyze {
+        /// Packet loss rate.
+        ///
+        /// This is given as a fraction (for example "0.2") or as a percentage
+        /// (for example "20%").
+        #[arg(long, value_parser = parse_fraction)]
+        loss: f64,
+        /// Desired probability of decoding the image.
+        ///
+        /// This is given as a fraction or as a percentage.
+        #[arg(long, default_value = "99%", value_parser = parse_fraction)]
+        probability: f64,
+        /// Number of systematic packets of the image.
+        #[arg(long, group = "image")]
+        k: Option<u16>,
+        /// Input file (original SSDV image), or "-" for stdin.
+        ///
+        /// The number of systematic packets is the number of packets in the
+        /// file.
+        #[arg(group = "image")]
+        input: Option<PathBuf>,
+    },
+    /// Measure the encoder and decoder performance with synthetic data.
+    Bench {
+        /// Number of systematic packets in the synthetic image.
+        #[arg(long, default_value_t = 300)]
+        k: u16,
+        /// Number of FEC packets to encode.
+        #[arg(long, default_value_t = 1000)]
+        packets: u16,
+        /// Packet format of the synthetic image.
+        #[arg(long, value_enum, default_value_t = Format::Lj2)]
+        format: Format,
+    },
+    /// Run a self-test with randomized encode and decode round trips.
+    Selftest {
+        /// Number of round trips.
+        #[arg(long, default_value_t = 100)]
+        iterations: u32,
+        /// Maximum number of systematic packets in the random images.
+        #[arg(long, default_value_t = 300)]
+        max_k: u16,
+        /// Seed for the pseudo-random number generator.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Packet format of the random images.
+        #[arg(long, value_enum, default_value_t = Format::Lj2)]
+        format: Format,
+    },
+    /// Transmit SSDV FEC packets to a UDP or TCP endpoint or a serial port.
+    Transmit {
+        #[command(flatten)]
+        transmit: TransmitArgs,
+        /// Transmit the input packets in a loop forever (carousel mode).
+        #[arg(long = "loop")]
+        carousel: bool,
+        /// Input file (SSDV FEC packets to transmit), or "-" for stdin.
+        input: PathBuf,
+    },
+    /// Transmit an SSDV image in a carousel of systematic and FEC packets.
+    ///
+    /// The packets are sent in rounds. Each round contains all the systematic
+    /// packets of the image followed by FEC packets that have not been sent in
+    /// previous rounds.
+    Carousel {
+        #[command(flatten)]
+        transmit: TransmitArgs,
+        /// Overhead of FEC packets in each round.
+        ///
+        /// This is given as a percentage of the number of systematic packets
+        /// (for example "25%").
+        #[arg(long, default_value = "25%", value_parser = parse_percent)]
+        overhead: u16,
+        /// Seed that selects the FEC packets to send.
+        ///
+        /// Transmitters using different seeds send different FEC packets.
+        #[arg(long, default_value_t = 0)]
+        seed: u32,
+        /// Transmit rounds in a loop forever.
+        ///
+        /// If this is not used, a single round is sent.
+        #[arg(long = "loop")]
+        carousel: bool,
+        /// Input file (original SSDV image), or "-" for stdin.
+        input: PathBuf,
+    },
+    /// Retransmit the SSDV FEC packets in a pcap or pcapng capture.
+    ///
+    /// This is useful to test live decoders with a recorded packet stream.
+    Replay {
+        #[command(flatten)]
+        transmit: TransmitArgs,
+        /// Timing of the transmitted packets.
+        #[arg(long, value_enum, default_value_t = Timing::FromPcap)]
+        timing: Timing,
+        /// Speed factor applied to the timing of the capture.
+        ///
+        /// For example, 2 replays the capture twice as fast.
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+        /// Only use UDP datagrams sent to this port.
+        #[arg(long)]
+        port: Option<u16>,
+        /// The UDP payloads in the capture contain KISS frames.
+        ///
+        /// The --kiss argument controls the framing of the transmitted
+        /// packets instead.
+        #[arg(long)]
+        input_kiss: bool,
+        /// Strip an AX.25 UI frame header from the packets in the capture.
+        #[arg(long)]
+        ax25: bool,
+        /// Input file (pcap or pcapng capture).
+        input: PathBuf,
+    },
+    /// Relay SSDV FEC packets, adding new FEC packets for the decoded images.
+    ///
+    /// The packets received from the input are retransmitted to the
+    /// destination. When an image is decoded, new FEC packets with packet IDs
+    /// that have not been received are generated and transmitted, so that the
+    /// receivers of the relay obtain new information even if they have also
+    /// received the original transmission.
+    #[command(group(ArgGroup::new("input").required(true)))]
+    Relay {
+        #[command(flatten)]
+        transmit: TransmitArgs,
+        /// UDP address to receive the input packets on.
+        #[arg(long, group = "input", value_name = "ADDR")]
+        input_udp: Option<SocketAddr>,
+        /// TCP server to receive the input packets from.
+        #[arg(long, group = "input", value_name = "ADDR")]
+        input_tcp: Option<String>,
+        /// Serial port to receive the input packets from.
+        #[arg(long, group = "input", value_name = "PATH")]
+        input_serial: Option<String>,
+        /// Baud rate of the input serial port.
+        #[arg(long, default_value_t = 115200)]
+        input_baud: u32,
+        /// ZeroMQ PUB socket to subscribe to for the input packets.
+        #[cfg(feature = "zmq")]
+        #[arg(long, group = "input", value_name = "ENDPOINT")]
+        input_zmq: Option<String>,
+        /// The input packets use KISS framing.
+        ///
+        /// The --kiss argument controls the framing of the transmitted
+        /// packets instead.
+        #[arg(long)]
+        input_kiss: bool,
+        /// Overhead of new FEC packets for each decoded image.
+        ///
+        /// This is given as a percentage of the number of systematic packets
+        /// (for example "25%").
+        #[arg(long, default_value = "25%", value_parser = parse_percent)]
+        overhead: u16,
+        /// Only transmit the new FEC packets, instead of also forwarding the
+        /// received packets.
+        #[arg(long)]
+        no_forward: bool,
+        #[command(flatten)]
+        decoder: DecoderArgs,
+    },
+    /// Generate shell completions.
+    ///
+    /// The completion script is written to stdout.
+    Completions {
+        /// Shell to generate completions for.
+        shell: Shell,
+    },
+}
+
+/// Arguments that configure the decoder.
+#[derive(clap::Args, Debug)]
+struct DecoderArgs {
+    /// Fail to decode if the packet headers are inconsistent (default).
+    #[arg(long, conflicts_with = "lenient")]
+    strict: bool,
+    /// Resolve inconsistencies in the packet headers by majority vote.
+    ///
+    /// The packets that disagree with the majority in some header field are
+    /// discarded, and the inconsistencies that have been resolved are
+    /// printed.
+    #[arg(long)]
+    lenient: bool,
+}
+
+impl DecoderArgs {
+    fn options(&self) -> DecoderOptions {
+        DecoderOptions::new().lenient(self.lenient)
+    }
+}
+
+/// Arguments that select the packets to use.
+#[derive(clap::Args, Debug)]
+struct FilterArgs {
+    /// Only use packets with this image ID.
+    #[arg(long)]
+    image_id: Option<u8>,
+    /// Only use packets with this callsign.
+    ///
+    /// Only the nofec format carries the callsign, so this requires
+    /// --format nofec.
+    #[arg(long, value_parser = parse_callsign)]
+    callsign: Option<u32>,
+}
+
+impl FilterArgs {
+    // Checks that the filters can be applied to packets in this format.
+    fn check(&self, format: Format) -> Result<()> {
+        if self.callsign.is_some() && format == Format::Lj2 {
+            anyhow::bail!(
+                "the lj2 format does not carry the callsign, so --callsign requires --format nofec"
+            );
+        }
+        Ok(())
+    }
+
+    // Returns true if the packet passes the filters.
+    fn matches<P: Packet>(&self, packet: &P) -> bool {
+        self.image_id.is_none_or(|id| packet.image_id() == id)
+            && self
+                .callsign
+                .is_none_or(|callsign| packet.callsign() == Some(callsign))
+    }
+}
+
+/// Arguments of the commands that inspect the images in some packet files.
+#[derive(clap::Args, Debug)]
+struct InspectArgs {
+    /// Print a JSON report of each image to stdout.
+    #[arg(long)]
+    json: bool,
+    /// Packet format of the input files.
+    #[arg(long, value_enum, default_value_t = Format::Lj2)]
+    format: Format,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    decoder: DecoderArgs,
+    /// Input files (received SSDV FEC packets).
+    ///
+    /// The packets of all the input files are merged. Glob patterns such as
+    /// "captures/*.bin" are expanded. The packets are read from stdin for an
+    /// input given as "-".
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+}
+
+impl InspectArgs {
+    // Reads the input files and inspects the images in them.
+    fn inspect<P: Packet>(&self, stdin: &mut dyn Read) -> Result<Inspection<P>> {
+        let packets = read_filtered::<P>(&self.inputs, self.format, &self.filter, stdin)?;
+        Ok(inspect::inspect(&packets, self.decoder.options()))
+    }
+}
+
+// Reads the packets of some input files that pass the filters.
+fn read_filtered<P: Packet>(
+    inputs: &[PathBuf],
+    format: Format,
+    filter: &FilterArgs,
+    stdin: &mut dyn Read,
+) -> Result<Vec<P>> {
+    filter.check(format)?;
+    let mut packets = Vec::new();
+    for path in expand_globs(inputs)? {
+        packets.extend(read_input::<P, _>(path, stdin)?);
+    }
+    packets.retain(|packet| filter.matches(packet));
+    Ok(packets)
+}
+
+// Parses a callsign given in the command line and encodes it in base 40.
+fn parse_callsign(callsign: &str) -> Result<u32, String> {
+    if callsign.is_empty()
+        || callsign.len() > 6
+        || !callsign.bytes().all(|c| c.is_ascii_alphanumeric())
+    {
+        return Err("the callsign must have between 1 and 6 letters and digits".to_string());
+    }
+    Ok(encode_callsign(callsign))
+}
+
+// Prints the header inconsistencies resolved by the decoder.
+fn print_overrides(image_id: Option<u8>, overrides: &HeaderOverrides) {
+    let image = match image_id {
+        Some(id) => format!("image {id}"),
+        None => "image".to_string(),
+    };
+    for (count, reason) in [
+        (overrides.image_id, "with a different image ID"),
+        (overrides.flags, "with different flags"),
+        (
+            overrides.num_systematic,
+            "inconsistent with the number of systematic packets",
+        ),
+        (overrides.dimensions, "with a different width or height"),
+    ] {
+        if count > 0 {
+            message!("{image}: discarded {count} packets {reason}");
+        }
+    }
+    if overrides.eoi_on_fec > 0 {
+        message!(
+            "{image}: ignored the EOI flag on {} FEC packets",
+            overrides.eoi_on_fec
+        );
+    }
+}
+
+/// Arguments that configure the live decoding of received packets.
+#[derive(clap::Args, Debug)]
+struct ReceptionArgs {
+    /// Print a JSON report to stdout for each decoded image.
+    #[arg(long)]
+    json: bool,
+    /// Show a dashboard with the reception status of each image.
+    ///
+    /// The dashboard is drawn in the terminal and replaces the messages
+    /// printed to stderr.
+    #[arg(long, conflicts_with = "json")]
+    tui: bool,
+    /// Packet format of the received packets and the decoded images.
+    #[arg(long, value_enum, default_value_t = Format::Lj2)]
+    format: Format,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    decoder: DecoderArgs,
+    /// Output directory for the decoded images.
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+    /// Template for the paths of the decoded images.
+    ///
+    /// The paths are relative to the output directory. The placeholders
+    /// {image_id}, {callsign} (the callsign given to --upload) and {timestamp}
+    /// (the decoding time in UTC) are replaced by their values. Directories
+    /// that do not exist are created.
+    #[arg(long, default_value = "img_{image_id}.ssdv")]
+    output_template: OutputTemplate,
+    /// Write a JSON sidecar file next to each decoded image.
+    ///
+    /// The sidecar contains the decoding statistics and the times at which
+    /// the first packet was received and the image was decoded. Its name is
+    /// the name of the image followed by ".json".
+    #[arg(long)]
+    sidecar: bool,
+    /// Compression of the decoded images.
+    ///
+    /// The corresponding extension (".gz" or ".zst") is added to the names of
+    /// the output files.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+    /// Upload the received packets to an SSDV server with this receiver
+    /// callsign.
+    #[cfg(feature = "upload")]
+    #[arg(long, value_name = "CALLSIGN")]
+    upload: Option<String>,
+    /// URL of the SSDV server API.
+    #[cfg(feature = "upload")]
+    #[arg(long, default_value = upload::DEFAULT_URL)]
+    upload_url: String,
+    /// Publish the received packets and the decoded images to an MQTT broker,
+    /// given as HOST[:PORT].
+    ///
+    /// The packets are published to the topic "<PREFIX>/packets" and the
+    /// decoded images are published to the topic "<PREFIX>/images/<IMAGE_ID>",
+    /// where the prefix is given by --mqtt-prefix.
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "BROKER")]
+    mqtt_publish: Option<String>,
+    /// Prefix of the MQTT topics to publish to.
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "PREFIX", default_value = mqtt::DEFAULT_PREFIX)]
+    mqtt_prefix: String,
+    /// Serve Prometheus metrics of the reception on this address.
+    ///
+    /// The metrics are served over HTTP at the /metrics path.
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDR")]
+    metrics: Option<SocketAddr>,
+}
+
+/// Arguments that configure how packets are transmitted.
+#[derive(clap::Args, Debug)]
+#[command(group(ArgGroup::new("destination").required(true)))]
+#[command(group(ArgGroup::new("rate")))]
+struct TransmitArgs {
+    /// UDP destination address.
+    ///
+    /// Each packet is sent in a separate UDP datagram.
+    #[arg(long, group = "destination")]
+    udp: Option<String>,
+    /// TCP destination address.
+    ///
+    /// The packets are sent back-to-back in the TCP stream, so using --kiss is
+    /// recommended to delimit them.
+    #[arg(long, group = "destination")]
+    tcp: Option<String>,
+    /// Serial port to transmit to.
+    ///
+    /// The packets are sent back-to-back, so using --kiss is recommended to
+    /// delimit them.
+    #[arg(long, group = "destination")]
+    serial: Option<String>,
+    /// Serial port baud rate.
+    #[arg(long, default_value_t = 115200)]
+    baud: u32,
+    /// ZeroMQ endpoint to bind a PUB socket to, such as tcp://0.0.0.0:5555.
+    ///
+    /// Each packet is sent in a separate message. Subscribers only receive the
+    /// packets sent after they connect.
+    #[cfg(feature = "zmq")]
+    #[arg(long, group = "destination", value_name = "ENDPOINT")]
+    zmq: Option<String>,
+    /// Send the ZeroMQ messages as GNU Radio PDUs instead of raw bytes.
+    #[cfg(feature = "zmq")]
+    #[arg(long, requires = "zmq")]
+    zmq_pdu: bool,
+    /// Transmission rate in packets per second.
+    #[arg(long, group = "rate")]
+    pps: Option<f64>,
+    /// Transmission rate in bits per second.
+    ///
+    /// Only the bits of the SSDV packets are taken into account to compute the
+    /// packet rate.
+    #[arg(long, group = "rate")]
+    bps: Option<f64>,
+    /// Use KISS framing for the transmitted packets.
+    #[arg(long)]
+    kiss: bool,
+}
+
+impl TransmitArgs {
+    fn transmitter(&self) -> Result<Transmitter> {
+        let rate = match (self.pps, self.bps) {
+            (Some(pps), _) if pps > 0.0 => Rate::PacketsPerSecond(pps),
+            (_, Some(bps)) if bps > 0.0 => Rate::BitsPerSecond(bps),
+            (None, None) => Rate::Unlimited,
+            _ => anyhow::bail!("the transmission rate must be positive"),
+        };
+        let sink: Box<dyn PacketSink> = match (&self.udp, &self.tcp, &self.serial) {
+            (Some(addr), _, _) => Box::new(transmit::udp_sink(addr)?),
+            (_, Some(addr), _) => Box::new(transmit::tcp_sink(addr)?),
+            (_, _, Some(path)) => Box::new(transmit::serial_sink(path, self.baud)?),
+            // the destination group requires --zmq if no other destination is
+            // given
+            #[cfg(feature = "zmq")]
+            _ => Box::new(zmq::Publisher::bind(
+                self.zmq.as_ref().unwrap(),
+                self.zmq_pdu,
+            )?),
+            #[cfg(not(feature = "zmq"))]
+            _ => unreachable!(),
+        };
+        Ok(Transmitter::new(sink, rate, self.kiss))
+    }
+}
+
+fn parse_percent(s: &str) -> Result<u16> {
+    Ok(s.strip_suffix('%').unwrap_or(s).trim().parse()?)
+}
+
+fn parse_fraction(s: &str) -> Result<f64> {
+    Ok(match s.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>()? / 100.0,
+        None => s.trim().parse()?,
+    })
+}
+
+/// Runs the CLI application as the `main` function of a binary.
+///
+/// Errors are printed to stderr (unless `--quiet` is used) and mapped to an
+/// exit code with [`ExitStatus::from_error`].
+pub fn main() -> std::process::ExitCode {
+    let args = Args::parse();
+    let quiet = args.quiet;
+    match run_args(
+        args,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout().lock(),
+    ) {
+        Ok(()) => ExitStatus::Success.into(),
+        Err(err) => {
+            if !quiet {
+                eprintln!("Error: {err:?}");
+            }
+            ExitStatus::from_error(&err).into()
+        }
+    }
+}
+
+/// Runs the CLI application.
+///
+/// The command line arguments of the process are used, and the process stdin
+/// and stdout are used for input and output.
+pub fn run() -> Result<()> {
+    run_args(
+        Args::parse(),
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout().lock(),
+    )
+}
+
+/// Runs the CLI application with the given command line arguments.
+///
+/// The first argument is the name of the binary, as in
+/// [`std::env::args_os`]. The output that the CLI application normally prints
+/// to stdout, including the help, is written to `stdout` instead, and input and
+/// output files given as "-" are read from `stdin` and written to `stdout`.
+/// Messages are still printed to stderr unless `--quiet` is used.
+///
+/// The output settings, such as `--quiet`, only apply to this invocation, so
+/// this function can be called several times, including concurrently from
+/// different threads, and each call prints messages according to its own
+/// arguments.
+///
+/// Invalid command line arguments are returned as a [`clap::Error`], which
+/// [`ExitStatus::from_error`] maps to [`ExitStatus::Usage`].
+pub fn run_with_args<I, T>(args: I, stdin: &mut dyn Read, stdout: &mut dyn Write) -> Result<()>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args = match Args::try_parse_from(args) {
+        Ok(args) => args,
+        Err(err)
+            if matches!(
+                err.kind(),
+                ErrorKind::DisplayHelp | ErrorKind::DisplayVersion
+            ) =>
+        {
+            write!(stdout, "{}", err.render())?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    run_args(args, stdin, stdout)
+}
+
+fn run_args(args: Args, stdin: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+    let _output = Output::new(args.quiet).enter();
+    match args.command {
+        Command::Encode {
+            mut first,
+            append,
+            npackets,
+            rate,
+            overhead,
+            extra,
+            interleave,
+            format,
+            compress,
+            kiss,
+            input,
+            output,
+        } => with_format!(format, P => {
+            let num_options = [
+                npackets.is_some(),
+                rate.is_some(),
+                overhead.is_some(),
+                extra.is_some(),
+            ]
+            .into_iter()
+            .filter(|&option| option)
+            .count();
+            match num_options {
+                0 => anyhow::bail!(
+                    "one of the --npackets, --rate, --overhead and --extra options must be used"
+                ),
+                1 => (),
+                _ => anyhow::bail!(
+                    "the --npackets, --rate, --overhead and --extra options are mutually exclusive"
+                ),
+            }
+            if let Some(rate) = rate {
+                if rate <= 0.0 || rate > 1.0 {
+                    anyhow::bail!("the coding rate must be in the interval (0, 1]");
+                }
+            }
+            check_interleave_depth(interleave)?;
+            let mut input = read_input::<P, _>(input, stdin)?;
+            let mut existing = Vec::<P>::new();
+            if append && output.exists() {
+                existing = read_ssdv_to_vec(&output)?;
+                if let (Some(image), Some(packet)) = (input.first(), existing.first()) {
+                    if image.image_id() != packet.image_id() {
+                        anyhow::bail!(
+                            "the output file contains packets of image {} instead of image {}",
+                            packet.image_id(),
+                            image.image_id()
+                        );
+                    }
+                }
+                if let Some(last) = existing.iter().map(|packet| packet.packet_id()).max() {
+                    first = last
+                        .checked_add(1)
+                        .ok_or_else(|| anyhow::anyhow!("all the packet IDs have been used"))?;
+                }
+            }
+            let input_len = input.len();
+            let encoder = Encoder::new(&mut input)?;
+            // the encoder has checked that the input length fits in a u16
+            let k = u32::try_from(input_len).unwrap();
+            // limits the number of packets to the available packet IDs
+            let clamp = |n: u32| u16::try_from(n.min(u32::from(u16::MAX - first))).unwrap();
+            let npackets = match (npackets, rate, overhead, extra) {
+                (Some(npackets), _, _, _) => clamp(u32::from(npackets)),
+                (_, Some(rate), _, _) => clamp((input_len as f64 / rate).round() as u32),
+                (_, _, Some(overhead), _) => clamp(k + (k * u32::from(overhead)).div_ceil(100)),
+                (_, _, _, Some(extra)) => clamp(k + u32::from(extra)),
+                _ => unreachable!(),
+            };
+            let mut encoded = vec![P::zeroed(); usize::from(npackets)];
+            for (j, packet) in encoded.iter_mut().enumerate() {
+                let packet_id = first + j as u16;
+                encoder.encode(packet_id, packet);
+            }
+            if let Some(depth) = interleave {
+                encoded = interleave_packets(&encoded, depth);
+            }
+            existing.extend_from_slice(&encoded);
+            if kiss {
+                write_output_kiss(output, &existing, compress, stdout)?;
+            } else {
+                write_output(output, &existing, compress, stdout)?;
+            }
+        }),
+        Command::Decode {
+            json,
+            filter,
+            decoder,
+            format,
+            compress,
+            pcap,
+            port,
+            kiss,
+            ax25,
+            resync,
+            inputs,
+            output,
+        } => with_format!(format, P => {
+            filter.check(format)?;
+            if json && output.as_os_str() == files::STDIO_PATH {
+                anyhow::bail!("the decoded image cannot be written to stdout with --json");
+            }
+            if ax25 && !pcap && !kiss {
+                anyhow::bail!("the --ax25 option can only be used with --pcap or --kiss");
+            }
+            let payload_options = PayloadOptions { port, kiss, ax25 };
+            let mut input = Vec::new();
+            for path in expand_globs(&inputs)? {
+                if pcap {
+                    input.extend(read_pcap_to_vec::<P, _>(path, &payload_options)?);
+                } else if kiss {
+                    input.extend(read_input_kiss::<P, _>(path, ax25, stdin)?);
+                } else if resync {
+                    input.extend(read_input_resync::<P, _>(path, stdin)?);
+                } else {
+                    input.extend(read_input::<P, _>(path, stdin)?);
+                }
+            }
+            input.retain(|packet| filter.matches(packet));
+            // The decoder modifies its input, so a copy is kept for the report
+            let received = input.clone();
+            let mut output_vec = vec![P::zeroed(); input.len()];
+            let decoded =
+                match Decoder::decode_with_options(&mut input, &mut output_vec, &decoder.options())
+                {
+                    Ok((decoded, overrides)) => {
+                        print_overrides(
+                            decoded.first().map(|packet| packet.image_id()),
+                            &overrides,
+                        );
+                        decoded
+                    }
+                    Err(err) => {
+                        if json {
+                            let image_id = received.first().map(|packet| packet.image_id());
+                            let mut report = ImageReport::failed(image_id, err.to_string());
+                            report.input_packets = Some(received.len());
+                            report.print(stdout)?;
+                        }
+                        return Err(err.into());
+                    }
+                };
+            write_output(&output, decoded, compress, stdout)?;
+            if json {
+                let (valid, systematic) = report::distinct_valid_packets(&received, decoded.len());
+                let mut report = ImageReport::decoded(decoded, valid, systematic, Some(output));
+                report.input_packets = Some(received.len());
+                report.print(stdout)?;
+            }
+        }),
+        Command::Check { args } => with_format!(args.format, P => {
+            inspect::check(&args.inspect::<P>(stdin)?, args.json, stdout)?
+        }),
+        Command::Info { args } => with_format!(args.format, P => {
+            inspect::info(&args.inspect::<P>(stdin)?, args.json, stdout)?
+        }),
+        Command::Missing { args } => with_format!(args.format, P => {
+            inspect::missing(&args.inspect::<P>(stdin)?, args.json, stdout)?
+        }),
+        Command::Listen {
+            udp,
+            serial,
+            baud,
+            tcp,
+            agwpe,
+            agwpe_port,
+            #[cfg(feature = "mqtt")]
+            mqtt,
+            #[cfg(feature = "mqtt")]
+            mqtt_topic,
+            #[cfg(feature = "zmq")]
+            zmq,
+            kiss,
+            reception,
+        } => {
+            let source: Option<Box<dyn PacketSource>> = match (udp, serial, tcp, agwpe) {
+                (Some(addr), _, _, _) => Some(Box::new(listen::udp_source(addr)?)),
+                (_, Some(path), _, _) => Some(Box::new(listen::serial_source(&path, baud)?)),
+                (_, _, Some(addr), _) => Some(Box::new(listen::tcp_source(&addr)?)),
+                (_, _, _, Some(addr)) => Some(Box::new(listen::agwpe_source(&addr, agwpe_port)?)),
+                _ => None,
+            };
+            #[cfg(feature = "mqtt")]
+            let source = match mqtt {
+                Some(broker) => Some(Box::new(mqtt::Subscriber::new(&broker, &mqtt_topic)?) as _),
+                None => source,
+            };
+            #[cfg(feature = "zmq")]
+            let source = match zmq {
+                Some(endpoint) => Some(Box::new(zmq::Subscriber::connect(&endpoint)?) as _),
+                None => source,
+            };
+            // the source group requires one of the sources
+            let source = source.unwrap();
+            with_format!(reception.format, P => {
+                if kiss {
+                    listen::listen::<P, _>(Kiss::new(source), &reception, stdout)?;
+                } else {
+                    listen::listen::<P, _>(source, &reception, stdout)?;
+                }
+            })
+        }
+        #[cfg(feature = "upload")]
+        Command::Upload {
+            callsign,
+            url,
+            inputs,
+        } => upload::upload(&inputs, &callsign, &url)?,
+        Command::Watch {
+            interval,
+            reception,
+            dir,
+        } => {
+            if !interval.is_finite() || interval <= 0.0 {
+                anyhow::bail!("the scan interval must be positive");
+            }
+            with_format!(reception.format, P => {
+                let watcher = watch::Watcher::new(&dir, Duration::from_secs_f64(interval), P::LEN);
+                listen::listen::<P, _>(watcher, &reception, stdout)?;
+            })
+        }
+        #[cfg(feature = "serve")]
+        Command::Serve { listen, decoder } => {
+            serve::serve(listen, crate::ImageSet::with_options(decoder.options()))?
+        }
+        Command::Clean {
+            compress,
+            inputs,
+            output,
+        } => {
+            let mut input = Vec::new();
+            for path in expand_globs(&inputs)? {
+                input.extend(read_input::<SSDVPacket, _>(path, stdin)?);
+            }
+            let cleaned = clean::clean(&input);
+            write_output(output, &cleaned.packets, compress, stdout)?;
+            message!(
+                "kept {} packets (removed {} with wrong CRC and {} duplicates)",
+                cleaned.packets.len(),
+                cleaned.wrong_crc,
+                cleaned.duplicates
+            );
+        }
+        Command::Merge {
+            format,
+            filter,
+            decoder,
+            compress,
+            inputs,
+            output,
+        } => with_format!(format, P => {
+            let packets = read_filtered::<P>(&inputs, format, &filter, stdin)?;
+            let inspection = inspect::inspect(&packets, decoder.options());
+            let num_images = inspection.images.len();
+            let num_decoded = inspection
+                .images
+                .iter()
+                .filter(|image| image.result.is_ok())
+                .count();
+            let merged = inspect::merge(inspection);
+            write_output(output, &merged, compress, stdout)?;
+            message!(
+                "merged {num_images} images ({num_decoded} decoded) in {} packets",
+                merged.len()
+            );
+        }),
+        Command::Analyze {
+            loss,
+            probability,
+            k,
+            input,
+        } => {
+            let k = match (k, input) {
+                (Some(k), _) => k,
+                (_, Some(input)) => u16::try_from(read_input::<SSDVPacket, _>(input, stdin)?.len())
+                    .map_err(|_| anyhow::anyhow!("the input file contains too many packets"))?,
+                _ => unreachable!(),
+            };
+            analyze::analyze(k, loss, probability, stdout)?;
+        }
+        Command::Bench { k, packets, format } => {
+            with_format!(format, P => bench::bench::<P>(k, packets, stdout))?
+        }
+        Command::Selftest {
+            iterations,
+            max_k,
+            seed,
+            format,
+        } => with_format!(format, P => selftest::selftest::<P>(iterations, max_k, seed, stdout))?,
+        Command::Transmit {
+            transmit,
+            carousel,
+            input,
+        } => {
+            let packets = read_input(input, stdin)?;
+            if packets.is_empty() {
+                anyhow::bail!("the input file contains no packets");
+            }
+            let mut transmitter = transmit.transmitter()?;
+            transmit::transmit(&packets, &mut transmitter, carousel)?;
+        }
+        Command::Carousel {
+            transmit,
+            overhead,
+            seed,
+            carousel,
+            input,
+        } => {
+            let mut input = read_input(input, stdin)?;
+            let num_systematic = input.len();
+            let encoder = Encoder::new(&mut input)?;
+            let mut transmitter = transmit.transmitter()?;
+            let mut scheduler = FountainScheduler::with_overhead_percent(
+                // the encoder has checked that the length fits in a u16
+                u16::try_from(num_systematic).unwrap(),
+                overhead,
+                seed,
+            );
+            let mut packet = SSDVPacket::zeroed();
+            loop {
+                for _ in 0..scheduler.packets_per_round() {
+                    encoder.encode(scheduler.next_packet_id(), &mut packet);
+                    transmitter.send(&packet)?;
+                }
+                if !carousel {
+                    break;
+                }
+            }
+        }
+        Command::Replay {
+            transmit,
+            timing,
+            speed,
+            port,
+            input_kiss,
+            ax25,
+            input,
+        } => {
+            if !speed.is_finite() || speed <= 0.0 {
+                anyhow::bail!("the speed factor must be positive");
+            }
+            if timing == Timing::FromPcap && (transmit.pps.is_some() || transmit.bps.is_some()) {
+                anyhow::bail!("the --pps and --bps options can only be used with --timing rate");
+            }
+            let payload_options = PayloadOptions {
+                port,
+                kiss: input_kiss,
+                ax25,
+            };
+            let mut transmitter = transmit.transmitter()?;
+            replay::replay(input, &payload_options, timing, speed, &mut transmitter)?;
+        }
+        Command::Relay {
+            transmit,
+            input_udp,
+            input_tcp,
+            input_serial,
+            input_baud,
+            #[cfg(feature = "zmq")]
+            input_zmq,
+            input_kiss,
+            overhead,
+            no_forward,
+            decoder,
+        } => {
+            let source: Option<Box<dyn PacketSource>> = match (input_udp, input_tcp, input_serial) {
+                (Some(addr), _, _) => Some(Box::new(listen::udp_source(addr)?)),
+                (_, Some(addr), _) => Some(Box::new(listen::tcp_source(&addr)?)),
+                (_, _, Some(path)) => Some(Box::new(listen::serial_source(&path, input_baud)?)),
+                _ => None,
+            };
+            #[cfg(feature = "zmq")]
+            let source = match input_zmq {
+                Some(endpoint) => Some(Box::new(zmq::Subscriber::connect(&endpoint)?) as _),
+                None => source,
+            };
+            // the input group requires one of the sources
+            let source = source.unwrap();
+            let mut transmitter = transmit.transmitter()?;
+            let mut relay = crate::relay::Relay::new(overhead)
+                .with_options(decoder.options())
+                .with_forwarding(!no_forward);
+            if input_kiss {
+                relay::relay(Kiss::new(source), &mut relay, &mut transmitter)?;
+            } else {
+                relay::relay(source, &mut relay, &mut transmitter)?;
+            }
+        }
+        Command::Completions { shell } => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, stdout);
+        }
+    }
+    Ok(())
+}
+
+fn check_interleave_depth(depth: Option<usize>) -> Result<()> {
+    if depth == Some(0) {
+        anyhow::bail!("the interleaving depth must be positive");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::IMG_230_SSDV, SSDVNoFecPacket};
+
+    #[test]
+    fn run_with_stdio() {
+        let mut encoded = Vec::new();
+        run_with_args(
+            [
+                "ssdv-fec", "encode", "--first", "5", "--rate", "0.5", "-", "-",
+            ],
+            &mut &IMG_230_SSDV[..],
+            &mut encoded,
+        )
+        .unwrap();
+        let mut decoded = Vec::new();
+        run_with_args(
+            ["ssdv-fec", "decode", "-", "-"],
+            &mut &encoded[..],
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, IMG_230_SSDV);
+    }
+
+    #[test]
+    fn quiet_per_invocation() {
+        run_with_args(
+            ["ssdv-fec", "--quiet", "decode", "-", "-"],
+            &mut &IMG_230_SSDV[..],
+            &mut Vec::new(),
+        )
+        .unwrap();
+        // --quiet does not apply to the later invocations
+        assert!(!output::quiet());
+        let _output = Output::new(true).enter();
+        run_with_args(
+            ["ssdv-fec", "decode", "-", "-"],
+            &mut &IMG_230_SSDV[..],
+            &mut Vec::new(),
+        )
+        .unwrap();
+        assert!(output::quiet());
+    }
+
+    #[test]
+    fn inspection_commands() {
+        let k = IMG_230_SSDV.len() / SSDVPacket::LEN;
+        let mut encoded = Vec::new();
+        run_with_args(
+            ["ssdv-fec", "encode", "--extra", "2", "-", "-"],
+            &mut &IMG_230_SSDV[..],
+            &mut encoded,
+        )
+        .unwrap();
+        // the systematic packets 3 and 4 are lost
+        let received = [
+            &encoded[..3 * SSDVPacket::LEN],
+            &encoded[5 * SSDVPacket::LEN..],
+        ]
+        .concat();
+        let partial = &received[..(k - 2) * SSDVPacket::LEN];
+        let run = |args: &[&str], input: &[u8]| {
+            let mut output = Vec::new();
+            let result = run_with_args(
+                ["ssdv-fec"].iter().chain(args),
+                &mut &input[..],
+                &mut output,
+            );
+            (result, String::from_utf8(output).unwrap())
+        };
+
+        let (result, output) = run(&["check", "--json", "-"], &received);
+        result.unwrap();
+        assert!(output.contains(r#""status":"decoded""#));
+        assert!(output.contains(r#""recovered_packets":2"#));
+        let (result, output) = run(&["check", "-"], partial);
+        assert_eq!(
+            ExitStatus::from_error(&result.unwrap_err()),
+            ExitStatus::NotEnoughInput
+        );
+        assert!(output.starts_with("image 230: not decodable"));
+
+        let (result, output) = run(&["info", "--image-id", "230", "-"], partial);
+        result.unwrap();
+        assert!(output.contains(&format!("{} systematic and 0 FEC packets", k - 2)));
+        let (result, output) = run(&["info", "--image-id", "231", "-"], partial);
+        result.unwrap();
+        assert!(output.is_empty());
+
+        let (result, output) = run(&["missing", "-"], partial);
+        result.unwrap();
+        assert_eq!(
+            output,
+            "image 230: 2 more packets needed, missing systematic packets 3-4\n"
+        );
+        let (result, output) = run(&["missing", "--json", "-"], partial);
+        result.unwrap();
+        assert!(output.contains(r#""packets_needed":2,"missing_packets":[3,4]"#));
+
+        let mut merged = Vec::new();
+        run_with_args(
+            ["ssdv-fec", "merge", "-", "-"],
+            &mut &received[..],
+            &mut merged,
+        )
+        .unwrap();
+        assert_eq!(merged, IMG_230_SSDV);
+    }
+
+    #[test]
+    fn nofec_format() {
+        let image =
+            synthetic::synthetic_image::<SSDVNoFecPacket>(20, 7, &mut synthetic::Rng::new(1));
+        let image = image
+            .iter()
+            .flat_map(|packet| packet.0)
+            .collect::<Vec<u8>>();
+        let mut encoded = Vec::new();
+        run_with_args(
+            [
+                "ssdv-fec", "encode", "--format", "nofec", "--first", "3", "--extra", "2", "-", "-",
+            ],
+            &mut &image[..],
+            &mut encoded,
+        )
+        .unwrap();
+        assert_eq!(encoded.len(), 22 * SSDVNoFecPacket::LEN);
+        let mut decoded = Vec::new();
+        run_with_args(
+            ["ssdv-fec", "decode", "--format", "nofec", "-", "-"],
+            &mut &encoded[..],
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, image);
+
+        let mut output = Vec::new();
+        run_with_args(
+            [
+                "ssdv-fec",
+                "selftest",
+                "--format",
+                "nofec",
+                "--iterations",
+                "10",
+                "--max-k",
+                "20",
+            ],
+            &mut std::io::empty(),
+            &mut output,
+        )
+        .unwrap();
+        assert!(String::from_utf8(output).unwrap().contains("0 failures"));
+    }
+
+    // Returns two no-FEC images with the same image ID sent by different
+    // callsigns.
+    fn images_by_callsign() -> [Vec<u8>; 2] {
+        [("EA4GPZ", 1), ("SORA", 2)].map(|(callsign, seed)| {
+            let mut image = synthetic::synthetic_image::<SSDVNoFecPacket>(
+                20,
+                7,
+                &mut synthetic::Rng::new(seed),
+            );
+            for packet in &mut image {
+                packet.0[2..6].copy_from_slice(&encode_callsign(callsign).to_be_bytes());
+                packet.set_crc32(packet.compute_crc32());
+            }
+            image
+                .iter()
+                .flat_map(|packet| packet.0)
+                .collect::<Vec<u8>>()
+        })
+    }
+
+    #[test]
+    fn callsign_filter() {
+        let images = images_by_callsign();
+        let mut decoded = Vec::new();
+        run_with_args(
+            [
+                "ssdv-fec",
+                "decode",
+                "--format",
+                "nofec",
+                "--callsign",
+                "sora",
+                "-",
+                "-",
+            ],
+            &mut &images.concat()[..],
+            &mut decoded,
+        )
+        .unwrap();
+        assert_eq!(decoded, images[1]);
+
+        let err = run_with_args(
+            ["ssdv-fec", "decode", "--callsign", "SORA", "-", "-"],
+            &mut &IMG_230_SSDV[..],
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("--format nofec"));
+        let err = run_with_args(
+            ["ssdv-fec", "decode", "--callsign", "EA4GPZ-1", "-", "-"],
+            &mut &IMG_230_SSDV[..],
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::Usage);
+    }
+
+    #[test]
+    fn reception_callsign_filter() {
+        let dir = std::env::temp_dir().join(format!("ssdv-fec-reception-{}", std::process::id()));
+        let args = Args::try_parse_from([
+            "ssdv-fec".as_ref(),
+            "watch".as_ref(),
+            "--format".as_ref(),
+            "nofec".as_ref(),
+            "--callsign".as_ref(),
+            "EA4GPZ".as_ref(),
+            "--output-dir".as_ref(),
+            dir.as_os_str(),
+            dir.as_os_str(),
+        ])
+        .unwrap();
+        let Command::Watch { reception, .. } = args.command else {
+            unreachable!()
+        };
+        let images = images_by_callsign();
+        let mut stdout = Vec::new();
+        let mut state = listen::Reception::<SSDVNoFecPacket>::new(&reception, &mut stdout).unwrap();
+        // the image from the other callsign is received first, and would
+        // otherwise be decoded to the same file
+        for frame in images[1].chunks(SSDVNoFecPacket::LEN) {
+            state.handle_frame(frame, Default::default()).unwrap();
+        }
+        assert!(!dir.join("img_7.ssdv").exists());
+        for frame in images[0].chunks(SSDVNoFecPacket::LEN) {
+            state.handle_frame(frame, Default::default()).unwrap();
+        }
+        assert_eq!(std::fs::read(dir.join("img_7.ssdv")).unwrap(), images[0]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn completions_and_help_formats() {
+        // the elvish and PowerShell completions do not complete values
+        for shell in ["bash", "zsh", "fish"] {
+            let mut script = Vec::new();
+            run_with_args(
+                ["ssdv-fec", "completions", shell],
+                &mut std::io::empty(),
+                &mut script,
+            )
+            .unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("format"), "{shell}");
+            assert!(
+                script.contains("lj2") && script.contains("nofec"),
+                "{shell}"
+            );
+        }
+        for command in ["encode", "decode", "bench", "selftest"] {
+            let mut help = Vec::new();
+            run_with_args(
+                ["ssdv-fec", command, "--help"],
+                &mut std::io::empty(),
+                &mut help,
+            )
+            .unwrap();
+            let help = String::from_utf8(help).unwrap();
+            assert!(help.contains("--format"), "{command}");
+            assert!(help.contains("lj2") && help.contains("nofec"), "{command}");
+        }
+    }
+
+    #[test]
+    fn run_with_invalid_args() {
+        let mut help = Vec::new();
+        run_with_args(["ssdv-fec", "--help"], &mut std::io::empty(), &mut help).unwrap();
+        assert!(String::from_utf8(help).unwrap().contains("Exit codes"));
+        let err = run_with_args(
+            ["ssdv-fec", "decode"],
+            &mut std::io::empty(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!(ExitStatus::from_error(&err), ExitStatus::Usage);
+    }
+}
@@ -0,0 +1,234 @@
+//! MQTT publication and subscription.
+//!
+//! Received packets and decoded images can be published to an MQTT broker,
+//! and packets can be received by subscribing to a topic of an MQTT broker.
+//! This allows the receivers of a distributed ground station network to share
+//! their packets. Each MQTT message contains a single SSDV FEC packet, or a
+//! complete image in the case of decoded images.
+
+use crate::{
+    transport::{PacketSink, PacketSource, Received},
+    PacketMetadata,
+};
+use anyhow::{Context, Result};
+use rumqttc::{Client, Connection, Event, MqttOptions, Packet, QoS, RecvTimeoutError};
+use std::{
+    collections::HashSet,
+    io,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Default MQTT port.
+const DEFAULT_PORT: u16 = 1883;
+
+/// Default prefix of the topics to which packets and images are published.
+pub const DEFAULT_PREFIX: &str = "ssdv-fec";
+
+/// Default topic to which the MQTT source subscribes.
+pub const DEFAULT_TOPIC: &str = "ssdv-fec/packets";
+
+// Maximum size of an MQTT message. This is large enough for complete images.
+const MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+// Timeout for the reception of a message
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Time to wait before reconnecting to the broker after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// Creates an MQTT client for a broker given as HOST[:PORT]. The role is
+// appended to the client ID, so that a publisher and a subscriber of the same
+// process can connect to the same broker.
+fn client(broker: &str, role: &str) -> Result<(Client, Connection)> {
+    let (host, port) = parse_broker(broker)?;
+    let mut options = MqttOptions::new(
+        format!("ssdv-fec-{}-{role}", std::process::id()),
+        host,
+        port,
+    );
+    options
+        .set_keep_alive(Duration::from_secs(30))
+        .set_max_packet_size(MAX_MESSAGE_SIZE, MAX_MESSAGE_SIZE);
+    Ok(Client::new(options, 64))
+}
+
+// Parses a broker address given as HOST[:PORT].
+fn parse_broker(broker: &str) -> Result<(&str, u16)> {
+    let (host, port) = match broker.rsplit_once(':') {
+        // IPv6 addresses must be given in brackets if a port is given
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => (
+            host,
+            port.parse()
+                .with_context(|| format!("invalid port in MQTT broker {broker}"))?,
+        ),
+        _ => (broker, DEFAULT_PORT),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        anyhow::bail!("invalid MQTT broker {broker}");
+    }
+    Ok((host, port))
+}
+
+/// Background MQTT publisher.
+///
+/// The packets and images pushed to the publisher are published by a
+/// background thread that maintains the connection to the broker, so that the
+/// reception of packets is not blocked by the network. Packets are published
+/// to the topic `<prefix>/packets` and the images are published to the topic
+/// `<prefix>/images/<image_id>` as the concatenation of their systematic
+/// packets. Connection errors are printed and otherwise ignored.
+pub struct Publisher {
+    client: Client,
+    prefix: String,
+    // Packets already published, identified by image ID, packet ID and CRC-32
+    published: HashSet<(u8, u16, u32)>,
+    _thread: JoinHandle<()>,
+}
+
+impl Publisher {
+    /// Creates a new publisher for an MQTT broker given as HOST[:PORT].
+    pub fn new(broker: &str, prefix: String) -> Result<Publisher> {
+        let (client, mut connection) = client(broker, "pub")?;
+        let broker = broker.to_string();
+        let thread = super::output::spawn(move || {
+            // The iterator reconnects to the broker after an error, and ends
+            // when the client is dropped.
+            for event in connection.iter() {
+                if let Err(err) = event {
+                    message!("MQTT connection to {broker} failed: {err}");
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        });
+        Ok(Publisher {
+            client,
+            prefix,
+            published: HashSet::new(),
+            _thread: thread,
+        })
+    }
+
+    /// Pushes a received packet to be published.
+    ///
+    /// Packets that have already been published are discarded. The packet
+    /// should have a valid CRC.
+    pub fn push_packet<P: crate::Packet>(&mut self, packet: &P) {
+        if !self
+            .published
+            .insert((packet.image_id(), packet.packet_id(), packet.crc32()))
+        {
+            return;
+        }
+        self.publish(
+            format!("{}/packets", self.prefix),
+            packet.as_bytes().to_vec(),
+        );
+    }
+
+    /// Pushes a decoded image to be published.
+    pub fn push_image<P: crate::Packet>(&mut self, image_id: u8, packets: &[P]) {
+        let payload = packets
+            .iter()
+            .flat_map(|packet| packet.as_bytes())
+            .copied()
+            .collect::<Vec<u8>>();
+        self.publish(format!("{}/images/{image_id}", self.prefix), payload);
+    }
+
+    fn publish(&self, topic: String, payload: Vec<u8>) {
+        // Messages are dropped instead of blocking the reception if the
+        // connection cannot keep up.
+        if let Err(err) = self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, false, payload)
+        {
+            message!("could not publish to MQTT: {err}");
+        }
+    }
+}
+
+/// Each datagram is published to the topic `<prefix>/packets`, without
+/// discarding the datagrams that have already been published.
+impl PacketSink for Publisher {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.publish(format!("{}/packets", self.prefix), datagram.to_vec());
+        Ok(())
+    }
+}
+
+/// MQTT subscriber.
+///
+/// Each MQTT message published to the topic should contain one SSDV packet, or
+/// one or more KISS frames containing one SSDV packet each if KISS framing is
+/// used.
+pub struct Subscriber {
+    client: Client,
+    connection: Connection,
+    broker: String,
+    topic: String,
+}
+
+impl Subscriber {
+    /// Creates a new subscriber to a topic of an MQTT broker given as
+    /// HOST[:PORT].
+    pub fn new(broker: &str, topic: &str) -> Result<Subscriber> {
+        let (client, connection) = client(broker, "sub")?;
+        message!("subscribing to MQTT topic {topic} on {broker}");
+        Ok(Subscriber {
+            client,
+            connection,
+            broker: broker.to_string(),
+            topic: topic.to_string(),
+        })
+    }
+}
+
+impl PacketSource for Subscriber {
+    fn recv(&mut self) -> io::Result<Received> {
+        loop {
+            // the timeout is used to refresh the dashboard periodically
+            match self.connection.recv_timeout(RECV_TIMEOUT) {
+                Ok(Ok(Event::Incoming(Packet::Publish(publish)))) => {
+                    return Ok(Received::Datagram(
+                        publish.payload.to_vec(),
+                        PacketMetadata::default(),
+                    ))
+                }
+                // The subscription is made on each connection, since the broker
+                // forgets it when the client reconnects with a clean session.
+                Ok(Ok(Event::Incoming(Packet::ConnAck(_)))) => self
+                    .client
+                    .try_subscribe(&self.topic, QoS::AtLeastOnce)
+                    .map_err(io::Error::other)?,
+                Ok(Ok(_)) => (),
+                Ok(Err(err)) => {
+                    message!("MQTT connection to {} failed: {err}", self.broker);
+                    thread::sleep(RECONNECT_DELAY);
+                    return Ok(Received::Timeout);
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(Received::Timeout),
+                Err(RecvTimeoutError::Disconnected) => return Ok(Received::Closed),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn broker_address() {
+        assert_eq!(parse_broker("localhost").unwrap(), ("localhost", 1883));
+        assert_eq!(
+            parse_broker("mqtt.example.com:8883").unwrap(),
+            ("mqtt.example.com", 8883)
+        );
+        assert_eq!(parse_broker("[::1]:1884").unwrap(), ("::1", 1884));
+        assert_eq!(parse_broker("::1").unwrap(), ("::1", 1883));
+        assert!(parse_broker("localhost:port").is_err());
+        assert!(parse_broker(":1883").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! Output settings of an invocation of the CLI application.
+//!
+//! Informational messages are printed to stderr unless `--quiet` is used or
+//! the dashboard is shown. These settings belong to each invocation of
+//! [`run_with_args`](super::run_with_args), which can be called several times
+//! in the same process, and even concurrently in different threads. They are
+//! kept in a thread-local variable that is set for the duration of the
+//! invocation, and the threads spawned by the invocation inherit them.
+
+#[cfg(any(feature = "metrics", feature = "mqtt", feature = "upload"))]
+use std::thread::{self, JoinHandle};
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Output settings.
+#[derive(Debug, Clone, Default)]
+pub struct Output {
+    quiet: bool,
+    // This is shared with the threads spawned by the invocation, since the
+    // dashboard is shown once the reception starts.
+    dashboard: Arc<AtomicBool>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Output> = RefCell::default();
+}
+
+/// Guard that restores the previous output settings when dropped.
+#[derive(Debug)]
+pub struct OutputGuard {
+    previous: Option<Output>,
+}
+
+impl Output {
+    /// Creates the output settings of an invocation.
+    pub fn new(quiet: bool) -> Output {
+        Output {
+            quiet,
+            dashboard: Arc::default(),
+        }
+    }
+
+    /// Uses these settings in the current thread until the guard is dropped.
+    pub fn enter(self) -> OutputGuard {
+        OutputGuard {
+            previous: Some(CURRENT.replace(self)),
+        }
+    }
+}
+
+impl Drop for OutputGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            CURRENT.set(previous);
+        }
+    }
+}
+
+/// Returns true if messages should not be printed.
+pub fn quiet() -> bool {
+    CURRENT.with_borrow(|output| output.quiet || output.dashboard.load(Ordering::Relaxed))
+}
+
+/// Suppresses the messages of the current invocation, which is showing the
+/// dashboard.
+pub fn set_dashboard() {
+    CURRENT.with_borrow(|output| output.dashboard.store(true, Ordering::Relaxed));
+}
+
+/// Spawns a thread that uses the output settings of the current thread.
+#[cfg(any(feature = "metrics", feature = "mqtt", feature = "upload"))]
+pub fn spawn<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let output = CURRENT.with_borrow(Output::clone);
+    thread::spawn(move || {
+        let _output = output.enter();
+        f()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scoped_settings() {
+        assert!(!quiet());
+        {
+            let _output = Output::new(true).enter();
+            assert!(quiet());
+            // other threads are not affected
+            assert!(!std::thread::spawn(quiet).join().unwrap());
+            let _inner = Output::new(false).enter();
+            assert!(!quiet());
+        }
+        assert!(!quiet());
+    }
+
+    #[test]
+    #[cfg(any(feature = "metrics", feature = "mqtt", feature = "upload"))]
+    fn spawned_threads() {
+        let _output = Output::new(false).enter();
+        let thread = spawn(|| {
+            thread::park();
+            quiet()
+        });
+        assert!(!quiet());
+        set_dashboard();
+        assert!(quiet());
+        thread.thread().unpark();
+        assert!(thread.join().unwrap());
+    }
+}
@@ -0,0 +1,242 @@
+//! Extraction of SSDV packets from pcap captures.
+//!
+//! Both the pcap and pcapng formats are supported. The UDP datagrams in the
+//! capture are extracted by parsing the link layer, IPv4 or IPv6 and UDP
+//! headers, so that captures of a ground station network taken with Wireshark
+//! or tcpdump can be used directly.
+
+use super::files::open_input;
+use crate::{encap::ax25::strip_ui_header, kiss::KissDeframer, Packet};
+use anyhow::Result;
+use pcap_file::{
+    pcap::PcapReader,
+    pcapng::{Block, PcapNgReader},
+    DataLink,
+};
+use std::{io::Read, path::Path, time::Duration};
+
+/// UDP datagram extracted from a capture.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Datagram {
+    /// Capture timestamp.
+    pub timestamp: Duration,
+    /// UDP payload.
+    pub payload: Vec<u8>,
+}
+
+/// Options for the extraction of SSDV packets from UDP payloads.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct PayloadOptions {
+    /// Only use datagrams sent to this UDP port.
+    pub port: Option<u16>,
+    /// The UDP payloads contain KISS frames.
+    pub kiss: bool,
+    /// The packets are prefixed by an AX.25 UI frame header.
+    pub ax25: bool,
+}
+
+const PCAPNG_MAGIC: &[u8] = &[0x0a, 0x0d, 0x0d, 0x0a];
+
+/// Reads the UDP datagrams in a pcap or pcapng file.
+///
+/// Only datagrams sent to `port` are returned if it is not `None`. Frames that
+/// do not contain UDP datagrams, as well as fragmented IP packets, are skipped.
+pub fn read_udp_datagrams<P: AsRef<Path>>(path: P, port: Option<u16>) -> Result<Vec<Datagram>> {
+    let mut data = Vec::new();
+    open_input(path)?.read_to_end(&mut data)?;
+    let mut datagrams = Vec::new();
+    let mut push = |timestamp, linktype, frame: &[u8]| {
+        if let Some(payload) = udp_payload(linktype, frame, port) {
+            datagrams.push(Datagram {
+                timestamp,
+                payload: payload.to_vec(),
+            });
+        }
+    };
+    if data.starts_with(PCAPNG_MAGIC) {
+        let mut reader = PcapNgReader::new(&data[..])?;
+        // Link types of the interfaces in the current section
+        let mut linktypes = Vec::new();
+        let linktype = |linktypes: &[DataLink], id: u32| {
+            usize::try_from(id)
+                .ok()
+                .and_then(|id| linktypes.get(id).copied())
+                .ok_or_else(|| anyhow::anyhow!("packet with unknown interface {id}"))
+        };
+        while let Some(block) = reader.next_block() {
+            match block? {
+                Block::SectionHeader(_) => linktypes.clear(),
+                Block::InterfaceDescription(interface) => linktypes.push(interface.linktype),
+                Block::EnhancedPacket(packet) => push(
+                    packet.timestamp,
+                    linktype(&linktypes, packet.interface_id)?,
+                    &packet.data,
+                ),
+                Block::SimplePacket(packet) => {
+                    push(Duration::ZERO, linktype(&linktypes, 0)?, &packet.data)
+                }
+                _ => (),
+            }
+        }
+    } else {
+        let mut reader = PcapReader::new(&data[..])?;
+        let linktype = reader.header().datalink;
+        while let Some(packet) = reader.next_packet() {
+            let packet = packet?;
+            push(packet.timestamp, linktype, &packet.data);
+        }
+    }
+    Ok(datagrams)
+}
+
+/// Extracts the SSDV packets contained in the UDP payload of a datagram.
+///
+/// The payload can contain a packet or some KISS frames, each containing a
+/// packet. Payloads and frames of the wrong size are ignored. The number of
+/// ignored payloads or frames is returned together with the packets.
+pub fn payload_packets<T: Packet>(payload: &[u8], options: &PayloadOptions) -> (Vec<T>, usize) {
+    let frames = if options.kiss {
+        let mut deframer = KissDeframer::new();
+        deframer.push(payload)
+    } else {
+        vec![payload.to_vec()]
+    };
+    let mut packets = Vec::new();
+    let mut ignored = 0;
+    for frame in &frames {
+        let frame = if options.ax25 {
+            strip_ui_header(frame)
+        } else {
+            Some(&frame[..])
+        };
+        match frame.and_then(T::slice_from_bytes) {
+            Some([packet]) => packets.push(*packet),
+            _ => ignored += 1,
+        }
+    }
+    (packets, ignored)
+}
+
+/// Reads the SSDV packets contained in the UDP datagrams of a capture.
+pub fn read_pcap_to_vec<T: Packet, P: AsRef<Path>>(
+    path: P,
+    options: &PayloadOptions,
+) -> Result<Vec<T>> {
+    let mut packets = Vec::new();
+    let mut ignored = 0;
+    for datagram in read_udp_datagrams(&path, options.port)? {
+        let (datagram_packets, datagram_ignored) = payload_packets::<T>(&datagram.payload, options);
+        packets.extend(datagram_packets);
+        ignored += datagram_ignored;
+    }
+    if ignored > 0 {
+        message!(
+            "{}: ignored {ignored} UDP payloads or frames that do not contain an SSDV packet",
+            path.as_ref().display()
+        );
+    }
+    Ok(packets)
+}
+
+// Returns the payload of a UDP datagram contained in a link layer frame.
+fn udp_payload(linktype: DataLink, frame: &[u8], port: Option<u16>) -> Option<&[u8]> {
+    let (ethertype, packet) = match linktype {
+        DataLink::ETHERNET => {
+            let mut ethertype = u16::from_be_bytes(frame.get(12..14)?.try_into().ok()?);
+            let mut offset = 14;
+            // skip 802.1Q and 802.1ad VLAN tags
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                ethertype = u16::from_be_bytes(frame.get(offset + 2..offset + 4)?.try_into().ok()?);
+                offset += 4;
+            }
+            (Some(ethertype), frame.get(offset..)?)
+        }
+        DataLink::LINUX_SLL => (
+            Some(u16::from_be_bytes(frame.get(14..16)?.try_into().ok()?)),
+            frame.get(16..)?,
+        ),
+        DataLink::LINUX_SLL2 => (
+            Some(u16::from_be_bytes(frame.get(0..2)?.try_into().ok()?)),
+            frame.get(20..)?,
+        ),
+        // The 4-byte header contains the address family in host byte order,
+        // so the IP version is used instead.
+        DataLink::NULL | DataLink::LOOP => (None, frame.get(4..)?),
+        DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => (None, frame),
+        _ => return None,
+    };
+    let udp = match (ethertype, packet.first()? >> 4) {
+        (Some(0x0800) | None, 4) => ipv4_udp(packet)?,
+        (Some(0x86dd) | None, 6) => ipv6_udp(packet)?,
+        _ => return None,
+    };
+    let dst_port = u16::from_be_bytes(udp.get(2..4)?.try_into().ok()?);
+    if port.is_some_and(|port| port != dst_port) {
+        return None;
+    }
+    let len = usize::from(u16::from_be_bytes(udp.get(4..6)?.try_into().ok()?));
+    udp.get(8..len)
+}
+
+// Returns the UDP datagram contained in an IPv4 packet.
+fn ipv4_udp(packet: &[u8]) -> Option<&[u8]> {
+    let header_len = usize::from(packet.first()? & 0xf) * 4;
+    let total_len = usize::from(u16::from_be_bytes(packet.get(2..4)?.try_into().ok()?));
+    let flags_fragment = u16::from_be_bytes(packet.get(6..8)?.try_into().ok()?);
+    // fragments (MF flag set or non-zero fragment offset) are not reassembled
+    if flags_fragment & 0x3fff != 0 || *packet.get(9)? != 17 {
+        return None;
+    }
+    packet.get(header_len..total_len)
+}
+
+// Returns the UDP datagram contained in an IPv6 packet. Extension headers are
+// not supported.
+fn ipv6_udp(packet: &[u8]) -> Option<&[u8]> {
+    let payload_len = usize::from(u16::from_be_bytes(packet.get(4..6)?.try_into().ok()?));
+    if *packet.get(6)? != 17 {
+        return None;
+    }
+    packet.get(40..40 + payload_len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ethernet_ipv4_udp(port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 14];
+        frame[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+        let udp_len = 8 + payload.len() as u16;
+        let mut ip = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 17, 0, 0];
+        ip[2..4].copy_from_slice(&(20 + udp_len).to_be_bytes());
+        ip.extend_from_slice(&[127, 0, 0, 1, 127, 0, 0, 1]);
+        frame.extend_from_slice(&ip);
+        frame.extend_from_slice(&1234u16.to_be_bytes());
+        frame.extend_from_slice(&port.to_be_bytes());
+        frame.extend_from_slice(&udp_len.to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(payload);
+        // Ethernet padding
+        frame.extend_from_slice(&[0; 4]);
+        frame
+    }
+
+    #[test]
+    fn udp_payload_ethernet() {
+        let frame = ethernet_ipv4_udp(7000, b"hello");
+        assert_eq!(
+            udp_payload(DataLink::ETHERNET, &frame, None),
+            Some(&b"hello"[..])
+        );
+        assert_eq!(
+            udp_payload(DataLink::ETHERNET, &frame, Some(7000)),
+            Some(&b"hello"[..])
+        );
+        assert_eq!(udp_payload(DataLink::ETHERNET, &frame, Some(7001)), None);
+        assert_eq!(
+            udp_payload(DataLink::RAW, &frame[14..], None),
+            Some(&b"hello"[..])
+        );
+    }
+}
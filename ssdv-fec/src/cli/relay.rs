@@ -0,0 +1,71 @@
+//! Relaying of SSDV FEC packets.
+
+use super::{print_overrides, transmit::Transmitter};
+use crate::{
+    relay::Relay,
+    transport::{PacketSource, Received},
+    PushResult, SSDVPacket, SSDV_PACKET_LEN,
+};
+use anyhow::Result;
+
+/// Receives packets from a source and relays them with a transmitter.
+///
+/// The transmission blocks the reception as required to maintain the
+/// transmission rate, so the source should buffer the packets received in the
+/// meantime, as UDP sockets and byte streams do. This only returns if there is
+/// an error or the source is closed.
+pub fn relay<S: PacketSource>(
+    mut source: S,
+    relay: &mut Relay,
+    transmitter: &mut Transmitter,
+) -> Result<()> {
+    let mut stream_buffer = Vec::new();
+    loop {
+        let frames = match source.recv()? {
+            Received::Datagram(datagram, _) => vec![datagram],
+            Received::Stream(bytes) => {
+                stream_buffer.extend_from_slice(&bytes);
+                let len = stream_buffer.len() / SSDV_PACKET_LEN * SSDV_PACKET_LEN;
+                stream_buffer
+                    .drain(..len)
+                    .collect::<Vec<u8>>()
+                    .chunks_exact(SSDV_PACKET_LEN)
+                    .map(<[u8]>::to_vec)
+                    .collect()
+            }
+            Received::Timeout => continue,
+            Received::Closed => anyhow::bail!("the source was closed"),
+        };
+        for frame in frames {
+            let Ok(packet) = <[u8; SSDV_PACKET_LEN]>::try_from(&frame[..]) else {
+                message!(
+                    "ignoring frame of {} bytes (expected {SSDV_PACKET_LEN} bytes)",
+                    frame.len()
+                );
+                continue;
+            };
+            let packet = SSDVPacket(packet);
+            let image_id = packet.image_id();
+            let result = relay.push(packet);
+            match &result.push_result {
+                PushResult::WrongCrc => message!("ignoring packet with wrong CRC"),
+                PushResult::Duplicate | PushResult::Stored => (),
+                PushResult::DecodeFailed(err) => {
+                    message!("could not decode image {image_id}: {err}")
+                }
+                PushResult::Decoded(decoded) => {
+                    print_overrides(Some(image_id), &decoded.overrides);
+                    let forwarded = result.packets.first() == Some(&packet);
+                    message!(
+                        "decoded image {image_id} ({} packets), relaying {} new FEC packets",
+                        decoded.packets.len(),
+                        result.packets.len() - usize::from(forwarded)
+                    );
+                }
+            }
+            for packet in &result.packets {
+                transmitter.send(packet)?;
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Replay of captured packet streams.
+
+use super::{
+    pcap::{payload_packets, read_udp_datagrams, PayloadOptions},
+    transmit::Transmitter,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use std::{
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Timing used to replay a capture.
+#[derive(ValueEnum, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Timing {
+    /// Use the timestamps of the capture.
+    FromPcap,
+    /// Use the transmission rate given by --pps or --bps.
+    Rate,
+}
+
+/// Retransmits the packets in a pcap or pcapng capture.
+///
+/// With [`Timing::FromPcap`], each UDP datagram is sent at the same time
+/// relative to the start of the replay as in the capture, with the time scaled
+/// by `1 / speed`. The packets contained in a single datagram are sent
+/// back-to-back. With [`Timing::Rate`], the packets are paced by the
+/// transmitter.
+pub fn replay<P: AsRef<Path>>(
+    path: P,
+    options: &PayloadOptions,
+    timing: Timing,
+    speed: f64,
+    transmitter: &mut Transmitter,
+) -> Result<()> {
+    let datagrams = read_udp_datagrams(&path, options.port)?;
+    let Some(first) = datagrams.first().map(|datagram| datagram.timestamp) else {
+        anyhow::bail!("the capture contains no UDP datagrams");
+    };
+    let start = Instant::now();
+    let mut sent = 0;
+    let mut ignored = 0;
+    for datagram in &datagrams {
+        let (packets, datagram_ignored) = payload_packets(&datagram.payload, options);
+        ignored += datagram_ignored;
+        if timing == Timing::FromPcap {
+            // Timestamps can go backwards in captures merged from several
+            // interfaces. Those datagrams are sent immediately.
+            let deadline = start + timestamp_offset(datagram.timestamp, first, speed);
+            let now = Instant::now();
+            if deadline > now {
+                thread::sleep(deadline - now);
+            }
+        }
+        for packet in &packets {
+            transmitter.send(packet)?;
+        }
+        sent += packets.len();
+    }
+    if ignored > 0 {
+        message!(
+            "{}: ignored {ignored} UDP payloads or frames that do not contain an SSDV packet",
+            path.as_ref().display()
+        );
+    }
+    message!("replayed {sent} packets");
+    Ok(())
+}
+
+// Returns the time at which a datagram is sent relative to the start of the
+// replay.
+fn timestamp_offset(timestamp: Duration, first: Duration, speed: f64) -> Duration {
+    timestamp.saturating_sub(first).div_f64(speed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scaled_offsets() {
+        let first = Duration::from_secs(1000);
+        assert_eq!(
+            timestamp_offset(Duration::from_millis(1_002_000), first, 1.0),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            timestamp_offset(Duration::from_millis(1_002_000), first, 4.0),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            timestamp_offset(Duration::from_secs(999), first, 1.0),
+            Duration::ZERO
+        );
+    }
+}
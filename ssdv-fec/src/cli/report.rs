@@ -0,0 +1,203 @@
+//! Machine-readable reports.
+//!
+//! These reports are printed to stdout as JSON when the `--json` argument is
+//! used.
+
+#[cfg(feature = "image")]
+use crate::verify::{verify_packets, ImageVerdict};
+use crate::{Packet, PacketMetadata};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+/// Decoding status of an image.
+#[derive(Serialize, Debug, Copy, Clone, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// The image has been decoded.
+    Decoded,
+    /// The image could not be decoded.
+    Failed,
+}
+
+/// Report about the decoding of an image.
+#[derive(Serialize, Debug, Clone)]
+pub struct ImageReport {
+    /// Decoding status.
+    pub status: Status,
+    /// Image ID.
+    pub image_id: Option<u8>,
+    /// Number of systematic packets of the image.
+    pub num_systematic: Option<usize>,
+    /// Number of input packets, including invalid packets and duplicates.
+    pub input_packets: Option<usize>,
+    /// Number of distinct packets with a valid CRC.
+    pub valid_packets: Option<usize>,
+    /// Number of systematic packets recovered by the FEC decoder.
+    pub recovered_packets: Option<usize>,
+    /// Error that caused decoding to fail.
+    pub error: Option<String>,
+    /// File where the decoded image has been written.
+    pub output: Option<PathBuf>,
+    /// Number of distinct packets still needed to decode the image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packets_needed: Option<usize>,
+    /// IDs of the systematic packets that have not been received.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_packets: Option<Vec<u16>>,
+    /// Result of the validation of the decoded image.
+    #[cfg(feature = "image")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<ImageVerdict>,
+}
+
+impl ImageReport {
+    /// Creates a report for an image that has been decoded.
+    ///
+    /// With the `image` feature, the decoded image is validated.
+    ///
+    /// The `decoded` slice contains the packets of the decoded image. The
+    /// `valid_packets` and `systematic_received` parameters indicate the number
+    /// of distinct valid packets and distinct systematic packets received. The
+    /// `output` is the file where the image has been written, if any.
+    pub fn decoded<P: Packet>(
+        decoded: &[P],
+        valid_packets: usize,
+        systematic_received: usize,
+        output: Option<PathBuf>,
+    ) -> ImageReport {
+        ImageReport {
+            status: Status::Decoded,
+            image_id: decoded.first().map(|packet| packet.image_id()),
+            num_systematic: Some(decoded.len()),
+            input_packets: None,
+            valid_packets: Some(valid_packets),
+            recovered_packets: Some(decoded.len() - systematic_received),
+            error: None,
+            output,
+            packets_needed: None,
+            missing_packets: None,
+            #[cfg(feature = "image")]
+            image: Some(verify_packets(decoded)),
+        }
+    }
+
+    /// Creates a report for an image that could not be decoded.
+    pub fn failed(image_id: Option<u8>, error: String) -> ImageReport {
+        ImageReport {
+            status: Status::Failed,
+            image_id,
+            num_systematic: None,
+            input_packets: None,
+            valid_packets: None,
+            recovered_packets: None,
+            error: Some(error),
+            output: None,
+            packets_needed: None,
+            missing_packets: None,
+            #[cfg(feature = "image")]
+            image: None,
+        }
+    }
+
+    /// Writes the report as a line of JSON to stdout.
+    pub fn print(&self, stdout: &mut dyn Write) -> io::Result<()> {
+        writeln!(stdout, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+/// Metadata sidecar of a decoded image.
+///
+/// This is written as a JSON file next to each decoded image when the
+/// `--sidecar` argument is used, so that archival pipelines can index the
+/// received images.
+#[derive(Serialize, Debug, Clone)]
+pub struct Sidecar {
+    /// Decoding report.
+    #[serde(flatten)]
+    pub report: ImageReport,
+    /// Receiver callsign.
+    pub callsign: Option<String>,
+    /// Time at which the first packet of the image was received (RFC 3339).
+    pub first_packet_time: String,
+    /// Time at which the image was decoded (RFC 3339).
+    pub decode_time: String,
+    /// Metadata given by the receiver for the packets that have it.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub packets: Vec<PacketMetadataReport>,
+}
+
+/// Metadata of a received packet in a sidecar.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PacketMetadataReport {
+    /// Packet ID.
+    pub packet_id: u16,
+    /// Reception time (RFC 3339).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// Reception frequency in Hz.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f64>,
+    /// Signal to noise ratio in dB.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snr: Option<f64>,
+}
+
+impl Sidecar {
+    /// Creates the sidecar of an image.
+    pub fn new(
+        report: ImageReport,
+        callsign: Option<String>,
+        first_packet_time: SystemTime,
+        decode_time: SystemTime,
+    ) -> Sidecar {
+        Sidecar {
+            report,
+            callsign,
+            first_packet_time: humantime::format_rfc3339_seconds(first_packet_time).to_string(),
+            decode_time: humantime::format_rfc3339_seconds(decode_time).to_string(),
+            packets: Vec::new(),
+        }
+    }
+
+    /// Adds the metadata of the received packets, by packet ID.
+    pub fn with_packet_metadata(mut self, metadata: &BTreeMap<u16, PacketMetadata>) -> Sidecar {
+        self.packets = metadata
+            .iter()
+            .map(|(&packet_id, metadata)| PacketMetadataReport {
+                packet_id,
+                time: metadata
+                    .timestamp
+                    .map(|time| humantime::format_rfc3339_millis(time).to_string()),
+                frequency: metadata.frequency,
+                snr: metadata.snr,
+            })
+            .collect();
+        self
+    }
+
+    /// Writes the sidecar to a file.
+    pub fn write(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+}
+
+/// Returns the number of distinct packets with valid CRC, and how many of
+/// these are systematic packets.
+pub fn distinct_valid_packets<P: Packet>(packets: &[P], num_systematic: usize) -> (usize, usize) {
+    let ids = packets
+        .iter()
+        .filter(|packet| packet.compute_crc32() == packet.crc32())
+        .map(|packet| packet.packet_id())
+        .collect::<BTreeSet<u16>>();
+    let systematic = ids
+        .iter()
+        .filter(|&&id| usize::from(id) < num_systematic)
+        .count();
+    (ids.len(), systematic)
+}
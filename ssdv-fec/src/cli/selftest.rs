@@ -0,0 +1,112 @@
+//! Self-test with randomized round trips.
+
+use super::synthetic::{synthetic_image, Rng};
+use crate::{Decoder, DecoderError, Encoder, Packet};
+use anyhow::Result;
+use std::io::Write;
+
+/// Runs the self-test.
+///
+/// Each iteration generates a random image, encodes it, erases packets with a
+/// random loss pattern, and checks that decoding gives back the original
+/// image when enough packets have been received, or that it fails with
+/// [`DecoderError::NotEnoughInput`] otherwise. The packets are in the format
+/// `P`. A summary of the results is written to `out`.
+pub fn selftest<P: Packet>(
+    iterations: u32,
+    max_k: u16,
+    seed: u64,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if max_k == 0 || max_k > u16::MAX / 4 {
+        anyhow::bail!(
+            "the maximum image size must be between 1 and {}",
+            u16::MAX / 4
+        );
+    }
+    let mut rng = Rng::new(seed);
+    let mut decoded = 0;
+    let mut not_enough = 0;
+    let mut failures = 0;
+    for iteration in 0..iterations {
+        match round_trip::<P>(&mut rng, max_k) {
+            Ok(true) => decoded += 1,
+            Ok(false) => not_enough += 1,
+            Err(err) => {
+                message!("iteration {iteration}: {err}");
+                failures += 1;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "{iterations} round trips: {decoded} decoded, {not_enough} not enough packets, \
+         {failures} failures"
+    )?;
+    if failures > 0 {
+        anyhow::bail!("self-test failed");
+    }
+    Ok(())
+}
+
+// Performs a random round trip. Returns whether there were enough packets to
+// decode.
+fn round_trip<P: Packet>(rng: &mut Rng, max_k: u16) -> Result<bool> {
+    let k = 1 + rng.below(usize::from(max_k)) as u16;
+    let image_id = rng.below(256) as u8;
+    let image = synthetic_image::<P>(k, image_id, rng);
+    let mut buffer = image.clone();
+    let encoder = Encoder::new(&mut buffer)?;
+
+    // Encode up to 4k packets, starting at a random packet ID, and erase each
+    // of them with a random probability
+    let npackets = usize::from(k) * (1 + rng.below(4));
+    let first = rng.below(usize::from(k) + 1) as u16;
+    let loss_probability = rng.uniform();
+    let mut received = Vec::new();
+    for j in 0..npackets {
+        if rng.uniform() < loss_probability {
+            continue;
+        }
+        let mut packet = P::zeroed();
+        encoder.encode(first + j as u16, &mut packet);
+        received.push(packet);
+    }
+    // Shuffle the received packets and add some duplicates
+    for j in (1..received.len()).rev() {
+        received.swap(j, rng.below(j + 1));
+    }
+    if !received.is_empty() {
+        for _ in 0..rng.below(4) {
+            received.push(received[rng.below(received.len())]);
+        }
+    }
+
+    let has_systematic = received.iter().any(|packet| !packet.is_fec_packet());
+    let distinct = received
+        .iter()
+        .map(|packet| packet.packet_id())
+        .collect::<std::collections::BTreeSet<u16>>()
+        .len();
+    let decodable = has_systematic && distinct >= usize::from(k);
+
+    let mut output = vec![P::zeroed(); usize::from(k)];
+    match Decoder::decode(&mut received, &mut output) {
+        Ok(decoded) if decodable => {
+            if decoded != image.as_slice() {
+                anyhow::bail!("decoded image does not match the original (k = {k})");
+            }
+            Ok(true)
+        }
+        Ok(_) => anyhow::bail!("decoding succeeded with not enough packets (k = {k})"),
+        Err(err) if decodable => anyhow::bail!("decoding failed (k = {k}): {err}"),
+        // The decoder can report several errors when there are not enough
+        // packets, depending on which packets are missing
+        Err(
+            DecoderError::NotEnoughInput
+            | DecoderError::NoSystematic
+            | DecoderError::UnknownNumSystematic,
+        ) => Ok(false),
+        Err(err) => anyhow::bail!("unexpected decoding error (k = {k}): {err}"),
+    }
+}
@@ -0,0 +1,270 @@
+//! HTTP API server.
+//!
+//! The server decodes the packets submitted to it, so that web interfaces and
+//! other services can use the decoder without linking to this crate. It has
+//! the following endpoints:
+//!
+//! - `POST /packets` submits some SSDV FEC packets, given back-to-back in the
+//!   request body, and returns a summary of their reception as JSON.
+//! - `GET /images` returns the status of all the images as a JSON array.
+//! - `GET /images/<image_id>` returns the status of an image as JSON,
+//!   including the IDs of the missing systematic packets.
+//! - `GET /images/<image_id>/ssdv` downloads a decoded image as SSDV packets.
+//! - `GET /images/<image_id>/jpeg` downloads a decoded image as JPEG.
+//! - `GET /metrics` returns Prometheus metrics, if the `metrics` feature is
+//!   enabled.
+
+#[cfg(feature = "metrics")]
+use super::metrics::{self, Metrics};
+use crate::{
+    transcode::ssdv_to_jpeg, DecodedImage, ImageSet, PushResult, SSDVPacket, SSDV_PACKET_LEN,
+};
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{net::TcpListener, runtime::Builder};
+
+#[derive(Debug, Default)]
+struct Images {
+    images: ImageSet,
+    decoded: BTreeMap<u8, DecodedImage>,
+    // error of the last decoding attempt of each image
+    errors: BTreeMap<u8, String>,
+    #[cfg(feature = "metrics")]
+    metrics: Arc<Metrics>,
+}
+
+type SharedImages = Arc<Mutex<Images>>;
+
+/// Summary of the reception of submitted packets.
+#[derive(Serialize, Debug, Default)]
+struct Submission {
+    /// Number of packets submitted.
+    packets: usize,
+    /// Number of packets with a wrong CRC.
+    wrong_crc: usize,
+    /// Number of packets already received.
+    duplicates: usize,
+    /// IDs of the images decoded with these packets.
+    decoded: Vec<u8>,
+}
+
+/// Status of an image.
+#[derive(Serialize, Debug)]
+struct ImageStatus {
+    image_id: u8,
+    /// Number of distinct valid packets received.
+    received: usize,
+    /// Number of systematic packets, if known.
+    num_systematic: Option<u16>,
+    /// IDs of the systematic packets not received, if they are known.
+    missing: Option<Vec<u16>>,
+    decoded: bool,
+    /// Error of the last decoding attempt.
+    error: Option<String>,
+}
+
+impl Images {
+    fn status(&self, image_id: u8) -> Option<ImageStatus> {
+        let stats = self.images.stats(image_id)?;
+        Some(ImageStatus {
+            image_id,
+            received: stats.unique,
+            num_systematic: stats.num_systematic,
+            missing: self.images.missing_systematic(image_id),
+            decoded: stats.decoded,
+            error: self.errors.get(&image_id).cloned(),
+        })
+    }
+}
+
+fn router(images: SharedImages) -> Router {
+    #[cfg(feature = "metrics")]
+    let metrics = Arc::clone(&images.lock().unwrap().metrics);
+    let router = Router::new()
+        .route("/packets", post(submit_packets))
+        .route("/images", get(list_images))
+        .route("/images/:image_id", get(image_status))
+        .route("/images/:image_id/ssdv", get(image_ssdv))
+        .route("/images/:image_id/jpeg", get(image_jpeg))
+        .with_state(images);
+    #[cfg(feature = "metrics")]
+    let router = router.merge(metrics::router(metrics));
+    router
+}
+
+async fn submit_packets(
+    State(images): State<SharedImages>,
+    body: Bytes,
+) -> Result<Json<Submission>, (StatusCode, String)> {
+    if !body.len().is_multiple_of(SSDV_PACKET_LEN) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("the body length is not a multiple of {SSDV_PACKET_LEN} bytes"),
+        ));
+    }
+    let mut images = images.lock().unwrap();
+    let mut submission = Submission::default();
+    for chunk in body.chunks_exact(SSDV_PACKET_LEN) {
+        submission.packets += 1;
+        let packet = SSDVPacket(chunk.try_into().unwrap());
+        let image_id = packet.image_id();
+        #[cfg(feature = "metrics")]
+        let push_start = std::time::Instant::now();
+        let push_result = images.images.push(packet);
+        #[cfg(feature = "metrics")]
+        images
+            .metrics
+            .push_result(&push_result, push_start.elapsed());
+        match push_result {
+            PushResult::WrongCrc => submission.wrong_crc += 1,
+            PushResult::Duplicate => submission.duplicates += 1,
+            PushResult::Stored => (),
+            PushResult::Decoded(decoded) => {
+                message!(
+                    "decoded image {image_id} ({} packets)",
+                    decoded.packets.len()
+                );
+                images.errors.remove(&image_id);
+                images.decoded.insert(image_id, decoded);
+                submission.decoded.push(image_id);
+            }
+            PushResult::DecodeFailed(err) => {
+                images.errors.insert(image_id, err.to_string());
+            }
+        }
+    }
+    Ok(Json(submission))
+}
+
+async fn list_images(State(images): State<SharedImages>) -> Json<Vec<ImageStatus>> {
+    let images = images.lock().unwrap();
+    Json(
+        images
+            .images
+            .image_ids()
+            .filter_map(|image_id| images.status(image_id))
+            .collect(),
+    )
+}
+
+async fn image_status(
+    State(images): State<SharedImages>,
+    Path(image_id): Path<u8>,
+) -> Result<Json<ImageStatus>, StatusCode> {
+    images
+        .lock()
+        .unwrap()
+        .status(image_id)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn image_ssdv(
+    State(images): State<SharedImages>,
+    Path(image_id): Path<u8>,
+) -> Result<Response, StatusCode> {
+    let images = images.lock().unwrap();
+    let decoded = images.decoded.get(&image_id).ok_or(StatusCode::NOT_FOUND)?;
+    let ssdv = decoded
+        .packets
+        .iter()
+        .flat_map(|packet| packet.0)
+        .collect::<Vec<u8>>();
+    Ok(([(header::CONTENT_TYPE, "application/octet-stream")], ssdv).into_response())
+}
+
+async fn image_jpeg(
+    State(images): State<SharedImages>,
+    Path(image_id): Path<u8>,
+) -> Result<Response, StatusCode> {
+    let images = images.lock().unwrap();
+    let decoded = images.decoded.get(&image_id).ok_or(StatusCode::NOT_FOUND)?;
+    let jpeg = ssdv_to_jpeg(&decoded.packets).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], jpeg).into_response())
+}
+
+/// Runs the HTTP API server.
+///
+/// The submitted packets are pushed into `images`.
+pub fn serve(addr: SocketAddr, images: ImageSet) -> Result<()> {
+    let images = Arc::new(Mutex::new(Images {
+        images,
+        ..Images::default()
+    }));
+    Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            let listener = TcpListener::bind(addr).await?;
+            message!("serving HTTP API on {}", listener.local_addr()?);
+            axum::serve(listener, router(images)).await
+        })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::IMG_230_SSDV;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn api() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(SharedImages::default());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let client = reqwest::Client::new();
+        let submit = |body: Vec<u8>| client.post(format!("{url}/packets")).body(body).send();
+        let get = |path: &str| client.get(format!("{url}{path}")).send();
+        let json = |response: reqwest::Response| async move {
+            serde_json::from_slice::<Value>(&response.bytes().await.unwrap()).unwrap()
+        };
+
+        // all the packets except the first, and a corrupted packet
+        let mut body = IMG_230_SSDV[SSDV_PACKET_LEN..].to_vec();
+        body.extend_from_slice(&[0; SSDV_PACKET_LEN]);
+        let submission = submit(body).await.unwrap();
+        let submission = json(submission).await;
+        assert_eq!(submission["wrong_crc"], 1);
+        assert_eq!(submission["decoded"], serde_json::json!([]));
+        let status = get("/images/230").await.unwrap();
+        let status = json(status).await;
+        assert_eq!(status["missing"], serde_json::json!([0]));
+        assert_eq!(get("/images/230/jpeg").await.unwrap().status(), 404);
+        assert_eq!(get("/images/1").await.unwrap().status(), 404);
+        let response = submit(vec![0; 10]).await.unwrap();
+        assert_eq!(response.status(), 400);
+
+        let submission = submit(IMG_230_SSDV[..SSDV_PACKET_LEN].to_vec())
+            .await
+            .unwrap();
+        let submission = json(submission).await;
+        assert_eq!(submission["decoded"], serde_json::json!([230]));
+        let list = json(get("/images").await.unwrap()).await;
+        assert_eq!(list[0]["decoded"], true);
+        let ssdv = get("/images/230/ssdv")
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(ssdv[..], IMG_230_SSDV[..]);
+        let jpeg = get("/images/230/jpeg").await.unwrap();
+        assert_eq!(jpeg.headers()[header::CONTENT_TYPE], "image/jpeg");
+        assert_eq!(jpeg.bytes().await.unwrap()[..2], [0xff, 0xd8]);
+    }
+}
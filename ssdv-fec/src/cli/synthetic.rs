@@ -0,0 +1,74 @@
+//! Generation of synthetic SSDV images.
+//!
+//! The images generated here have valid SSDV headers and CRCs, but their data
+//! is random, so they do not correspond to a JPEG image. They are used for
+//! benchmarking and testing.
+
+use crate::Packet;
+
+/// Simple pseudo-random number generator.
+///
+/// This implements the xorshift64* generator, which is good enough for
+/// generating test data and loss patterns.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a new generator with a given seed.
+    pub fn new(seed: u64) -> Rng {
+        // the state must be non-zero
+        Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    /// Returns a pseudo-random number uniformly distributed in `[0, n)`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a pseudo-random number uniformly distributed in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Fills a slice with pseudo-random bytes.
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let x = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&x[..chunk.len()]);
+        }
+    }
+}
+
+/// Generates a synthetic SSDV image formed by `k` systematic packets.
+///
+/// The packets are in the format `P`. If the format has a prefix before the
+/// image ID, the prefix starts with the sync byte and the packet type of the
+/// standard no-FEC packets, and the callsign that follows is left empty.
+pub fn synthetic_image<P: Packet>(k: u16, image_id: u8, rng: &mut Rng) -> Vec<P> {
+    (0..k)
+        .map(|packet_id| {
+            let mut packet = P::zeroed();
+            let prefix = &mut packet.as_bytes_mut()[..P::HEADER_OFFSET];
+            if let Some(sync_type) = prefix.get_mut(..2) {
+                sync_type.copy_from_slice(&[0x55, 0x67]);
+            }
+            packet.set_image_id(image_id);
+            packet.set_packet_id(packet_id);
+            packet.set_width(40);
+            packet.set_height(30);
+            packet.set_flags(0x1a);
+            packet.set_eoi(packet_id == k - 1);
+            rng.fill(packet.data_as_mut());
+            packet.set_crc32(packet.compute_crc32());
+            packet
+        })
+        .collect()
+}
@@ -0,0 +1,143 @@
+//! Templates for the paths of decoded images.
+//!
+//! A template is a path containing placeholders between braces, such as
+//! `out/{callsign}_img{image_id}.ssdv`, which are replaced by the values
+//! corresponding to each decoded image. Literal braces are written as `{{` and
+//! `}}`.
+
+use anyhow::Result;
+use std::{path::PathBuf, str::FromStr, time::SystemTime};
+
+/// Placeholders supported in templates.
+pub const PLACEHOLDERS: &[&str] = &["image_id", "callsign", "timestamp"];
+
+/// Output path template.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct OutputTemplate(Vec<Part>);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// Values used to fill in the placeholders of a template.
+#[derive(Debug, Clone)]
+pub struct TemplateValues<'a> {
+    /// Image ID.
+    pub image_id: u8,
+    /// Receiver callsign, if known.
+    pub callsign: Option<&'a str>,
+    /// Time at which the image was decoded.
+    pub timestamp: SystemTime,
+}
+
+impl FromStr for OutputTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<OutputTemplate> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        anyhow::bail!("unterminated placeholder in template {s}");
+                    };
+                    let name = &rest[..end];
+                    if !PLACEHOLDERS.contains(&name) {
+                        anyhow::bail!(
+                            "unknown placeholder {{{name}}} in template (supported: {})",
+                            PLACEHOLDERS.join(", ")
+                        );
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(name.to_string()));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => anyhow::bail!("unmatched }} in template {s}"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(OutputTemplate(parts))
+    }
+}
+
+impl OutputTemplate {
+    /// Returns whether the template uses a placeholder.
+    pub fn uses(&self, placeholder: &str) -> bool {
+        self.0
+            .iter()
+            .any(|part| matches!(part, Part::Placeholder(name) if name == placeholder))
+    }
+
+    /// Expands the template with some values.
+    ///
+    /// The timestamp is formatted as `YYYYMMDDTHHMMSSZ` in UTC, which is valid
+    /// in file names on all platforms. An error is returned if the template
+    /// uses the callsign and it is not known.
+    pub fn expand(&self, values: &TemplateValues) -> Result<PathBuf> {
+        let mut path = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(literal) => path.push_str(literal),
+                Part::Placeholder(name) => match name.as_str() {
+                    "image_id" => path.push_str(&values.image_id.to_string()),
+                    "callsign" => path.push_str(values.callsign.ok_or_else(|| {
+                        anyhow::anyhow!("the output template uses the callsign, but it is unknown")
+                    })?),
+                    "timestamp" => path.extend(
+                        humantime::format_rfc3339_seconds(values.timestamp)
+                            .to_string()
+                            .chars()
+                            .filter(|&c| c != '-' && c != ':'),
+                    ),
+                    _ => unreachable!(),
+                },
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn expand_template() {
+        let template = "out/{callsign}_(img{image_id})_{timestamp}{{}}.ssdv"
+            .parse::<OutputTemplate>()
+            .unwrap();
+        assert!(template.uses("callsign"));
+        let mut values = TemplateValues {
+            image_id: 42,
+            callsign: Some("EA4GPZ"),
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        };
+        assert_eq!(
+            template.expand(&values).unwrap(),
+            PathBuf::from("out/EA4GPZ_(img42)_20231114T221320Z{}.ssdv")
+        );
+        values.callsign = None;
+        assert!(template.expand(&values).is_err());
+        assert!("img{id}.ssdv".parse::<OutputTemplate>().is_err());
+        assert!("img{image_id.ssdv".parse::<OutputTemplate>().is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Paced transmission of SSDV FEC packets.
+
+use crate::{
+    net::PacketSocket,
+    transport::{ByteStream, Kiss, PacketSink},
+    SSDVPacket, SSDV_PACKET_LEN,
+};
+use anyhow::Result;
+use serialport::SerialPort;
+use std::{
+    fmt,
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Opens a UDP destination.
+///
+/// Each packet is sent in a separate datagram.
+pub fn udp_sink(addr: &str) -> Result<PacketSocket> {
+    let socket = PacketSocket::bind("0.0.0.0:0")?;
+    socket.connect(addr)?;
+    Ok(socket)
+}
+
+/// Opens a TCP destination.
+///
+/// Packets are sent back-to-back.
+pub fn tcp_sink(addr: &str) -> Result<ByteStream<TcpStream>> {
+    Ok(ByteStream::new(TcpStream::connect(addr)?))
+}
+
+/// Opens a serial port destination.
+///
+/// Packets are sent back-to-back.
+pub fn serial_sink(path: &str, baud_rate: u32) -> Result<ByteStream<Box<dyn SerialPort>>> {
+    Ok(ByteStream::new(serialport::new(path, baud_rate).open()?))
+}
+
+/// Transmission rate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rate {
+    /// Send packets as fast as possible.
+    Unlimited,
+    /// Rate in packets per second.
+    PacketsPerSecond(f64),
+    /// Rate in bits per second, counting only the SSDV packet bytes.
+    BitsPerSecond(f64),
+}
+
+impl Rate {
+    fn packet_interval(&self) -> Option<Duration> {
+        match *self {
+            Rate::Unlimited => None,
+            Rate::PacketsPerSecond(pps) => Some(Duration::from_secs_f64(1.0 / pps)),
+            Rate::BitsPerSecond(bps) => {
+                Some(Duration::from_secs_f64((8 * SSDV_PACKET_LEN) as f64 / bps))
+            }
+        }
+    }
+}
+
+/// Paced packet transmitter.
+pub struct Transmitter {
+    sink: Box<dyn PacketSink>,
+    interval: Option<Duration>,
+    deadline: Instant,
+}
+
+impl fmt::Debug for Transmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transmitter")
+            .field("interval", &self.interval)
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Transmitter {
+    /// Creates a new transmitter.
+    ///
+    /// The packets are sent to `sink` at the indicated `rate`. If `kiss` is
+    /// true, each packet is sent in a KISS frame.
+    pub fn new(sink: Box<dyn PacketSink>, rate: Rate, kiss: bool) -> Transmitter {
+        Transmitter {
+            sink: if kiss {
+                Box::new(Kiss::new(sink))
+            } else {
+                sink
+            },
+            interval: rate.packet_interval(),
+            deadline: Instant::now(),
+        }
+    }
+
+    /// Sends a packet.
+    ///
+    /// This function blocks as required to maintain the transmission rate.
+    pub fn send(&mut self, packet: &SSDVPacket) -> Result<()> {
+        if let Some(interval) = self.interval {
+            // Sleeping until an absolute deadline prevents the rate from
+            // drifting because of the time spent sending.
+            let now = Instant::now();
+            if self.deadline > now {
+                thread::sleep(self.deadline - now);
+            }
+            self.deadline += interval;
+        }
+        self.sink.send(&packet.0)?;
+        Ok(())
+    }
+}
+
+/// Transmits `packets` with a transmitter.
+///
+/// If `carousel` is true, the packets are sent in a loop forever.
+pub fn transmit(
+    packets: &[SSDVPacket],
+    transmitter: &mut Transmitter,
+    carousel: bool,
+) -> Result<()> {
+    loop {
+        for packet in packets {
+            transmitter.send(packet)?;
+        }
+        if !carousel {
+            return Ok(());
+        }
+    }
+}
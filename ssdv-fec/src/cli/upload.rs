@@ -0,0 +1,123 @@
+//! Upload of received packets to an SSDV server.
+//!
+//! Packets are uploaded to a server implementing the ssdv.habhub.org REST API
+//! using the [`UploadClient`] of the library. The CLI application is
+//! synchronous, so the uploads are run in a single-threaded tokio runtime.
+
+use super::files::{expand_globs, read_ssdv_to_vec};
+use crate::{
+    upload::{UploadClient, DEFAULT_MAX_BATCH},
+    ImageSet, PushResult, SSDVPacket,
+};
+use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+    sync::mpsc,
+    thread::JoinHandle,
+    time::SystemTime,
+};
+use tokio::runtime::{Builder, Runtime};
+
+pub use crate::upload::DEFAULT_URL;
+
+// Creates the runtime in which the uploads are run.
+fn runtime() -> std::io::Result<Runtime> {
+    Builder::new_current_thread().enable_all().build()
+}
+
+/// Uploads the packets in some files to an SSDV server.
+///
+/// The images are decoded when possible, so that the systematic packets
+/// recovered with the FEC are also uploaded. FEC packets are not uploaded,
+/// since they are not understood by the server.
+pub fn upload(inputs: &[PathBuf], callsign: &str, url: &str) -> Result<()> {
+    let mut images = ImageSet::new();
+    let mut packets = BTreeMap::new();
+    for path in expand_globs(inputs)? {
+        for packet in read_ssdv_to_vec::<SSDVPacket, _>(path)? {
+            let key = (packet.image_id(), packet.packet_id());
+            match images.push(packet) {
+                PushResult::WrongCrc => (),
+                PushResult::Decoded(decoded) => {
+                    for packet in decoded.packets {
+                        packets.insert((packet.image_id(), packet.packet_id()), packet);
+                    }
+                }
+                _ if packet.is_fec_packet() => (),
+                _ => {
+                    packets.insert(key, packet);
+                }
+            }
+        }
+    }
+    let received = SystemTime::now();
+    let packets = packets
+        .into_values()
+        .map(|packet| (packet, received))
+        .collect::<Vec<_>>();
+    let client = UploadClient::new(callsign).with_url(url);
+    runtime()?.block_on(client.upload(&packets))?;
+    message!("uploaded {} packets", packets.len());
+    Ok(())
+}
+
+/// Background uploader.
+///
+/// The packets pushed to the uploader are uploaded by a background thread, so
+/// that the reception of packets is not blocked by the network. Upload errors
+/// are printed and otherwise ignored.
+#[derive(Debug)]
+pub struct Uploader {
+    sender: mpsc::Sender<(SSDVPacket, SystemTime)>,
+    // Packets already uploaded, identified by image ID, packet ID and CRC-32,
+    // so that images reusing an image ID are also uploaded
+    uploaded: HashSet<(u8, u16, u32)>,
+    _thread: JoinHandle<()>,
+}
+
+impl Uploader {
+    /// Creates a new uploader for an SSDV server.
+    pub fn new(url: String, callsign: String) -> Uploader {
+        let (sender, receiver) = mpsc::channel::<(SSDVPacket, SystemTime)>();
+        let thread = super::output::spawn(move || {
+            let runtime = match runtime() {
+                Ok(runtime) => runtime,
+                Err(err) => {
+                    message!("could not start the uploader: {err}");
+                    return;
+                }
+            };
+            let client = UploadClient::new(&callsign).with_url(&url);
+            while let Ok(packet) = receiver.recv() {
+                // upload the packets that have accumulated in a single request
+                let mut batch = vec![packet];
+                batch.extend(receiver.try_iter().take(DEFAULT_MAX_BATCH - 1));
+                if let Err(err) = runtime.block_on(client.upload(&batch)) {
+                    message!("could not upload {} packets: {err}", batch.len());
+                }
+            }
+        });
+        Uploader {
+            sender,
+            uploaded: HashSet::new(),
+            _thread: thread,
+        }
+    }
+
+    /// Pushes a packet to be uploaded.
+    ///
+    /// FEC packets and packets that have already been uploaded are discarded.
+    /// The packet should have a valid CRC.
+    pub fn push(&mut self, packet: &SSDVPacket) {
+        if packet.is_fec_packet()
+            || !self
+                .uploaded
+                .insert((packet.image_id(), packet.packet_id(), packet.crc32()))
+        {
+            return;
+        }
+        // the thread only ends if the channel is disconnected
+        let _ = self.sender.send((*packet, SystemTime::now()));
+    }
+}
@@ -0,0 +1,110 @@
+//! Monitoring of a directory for new packet files.
+
+use super::files::open_input;
+use crate::{
+    transport::{PacketSource, Received},
+    PacketMetadata,
+};
+use anyhow::Result;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, ErrorKind, Read},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Directory of packet files.
+///
+/// As a source, the directory is scanned periodically with the indicated
+/// interval. The packets of new files, as well as new packets appended to files
+/// that have already been seen, are received as datagrams. The files should
+/// contain packets of `packet_len` bytes back-to-back. All the packets are
+/// merged regardless of the file they come from.
+#[derive(Debug)]
+pub struct Watcher {
+    dir: PathBuf,
+    interval: Duration,
+    packet_len: usize,
+    // Number of bytes already processed for each file
+    offsets: HashMap<PathBuf, u64>,
+    // Packets found in the last scan that have not been received yet
+    packets: VecDeque<Vec<u8>>,
+    scanned: bool,
+}
+
+impl Watcher {
+    /// Creates a new watcher for a directory.
+    pub fn new(dir: &Path, interval: Duration, packet_len: usize) -> Watcher {
+        message!("watching directory {}", dir.display());
+        Watcher {
+            dir: dir.to_path_buf(),
+            interval,
+            packet_len,
+            offsets: HashMap::new(),
+            packets: VecDeque::new(),
+            scanned: false,
+        }
+    }
+
+    // Scans the directory for new packets.
+    fn scan(&mut self) -> io::Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let path = entry.path();
+            let offset = self.offsets.entry(path.clone()).or_default();
+            match read_file(&path, *offset, self.packet_len, &mut self.packets) {
+                Ok(new_offset) => *offset = new_offset,
+                Err(err) => message!("could not read {}: {err}", path.display()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PacketSource for Watcher {
+    fn recv(&mut self) -> io::Result<Received> {
+        if self.packets.is_empty() {
+            if self.scanned {
+                thread::sleep(self.interval);
+            }
+            self.scan()?;
+            self.scanned = true;
+        }
+        Ok(match self.packets.pop_front() {
+            Some(packet) => Received::Datagram(packet, PacketMetadata::default()),
+            None => Received::Timeout,
+        })
+    }
+}
+
+// Reads the complete packets in a file starting at a given offset and returns
+// the offset after the last complete packet. Offsets are counted in
+// decompressed bytes for compressed files.
+fn read_file(
+    path: &Path,
+    mut offset: u64,
+    packet_len: usize,
+    packets: &mut VecDeque<Vec<u8>>,
+) -> Result<u64> {
+    let mut data = Vec::new();
+    // A compressed file that is still being written ends abruptly, so an
+    // unexpected EOF only means that there is no more data available yet.
+    match open_input(path)?.read_to_end(&mut data) {
+        Err(err) if err.kind() != ErrorKind::UnexpectedEof => return Err(err.into()),
+        _ => (),
+    }
+    if (data.len() as u64) < offset {
+        // the file has been truncated or replaced, so we read it again
+        offset = 0;
+    }
+    for packet in data[offset as usize..].chunks_exact(packet_len) {
+        packets.push_back(packet.to_vec());
+        offset += packet_len as u64;
+    }
+    Ok(offset)
+}
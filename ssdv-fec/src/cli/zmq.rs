@@ -0,0 +1,169 @@
+//! ZeroMQ transport.
+//!
+//! Packets can be received by subscribing to a ZeroMQ PUB socket, and
+//! transmitted through a ZeroMQ PUB socket. Many SDR demodulator chains, such
+//! as the deframers of gr-satellites, emit their frames in this way. Each
+//! message contains a single SSDV FEC packet (or KISS frames), either as raw
+//! bytes or as a PDU serialized in the PMT format of GNU Radio, which is used
+//! by the ZMQ message blocks of GNU Radio.
+
+use crate::{
+    pmt::Pdu,
+    transport::{PacketSink, PacketSource, Received},
+};
+use anyhow::Result;
+use std::{fmt, io, time::Duration};
+use tokio::runtime::{Builder, Runtime};
+use zeromq::{PubSocket, Socket, SocketRecv, SocketSend, SubSocket, ZmqMessage};
+
+// Timeout for the reception of a message
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Time to wait before retrying to connect to a PUB socket
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+// Creates the runtime in which the ZeroMQ sockets are run. The sockets accept
+// connections and perform handshakes in background tasks, which need a worker
+// thread to run while the CLI application is blocked in other tasks.
+fn runtime() -> std::io::Result<Runtime> {
+    Builder::new_multi_thread()
+        .worker_threads(1)
+        .enable_all()
+        .build()
+}
+
+/// Returns the PDU contained in a message.
+///
+/// If the message is not a serialized PDU, it is returned as the data of a PDU
+/// without metadata.
+fn message_pdu(message: &[u8]) -> Pdu {
+    Pdu::parse(message).unwrap_or_else(|_| Pdu::new(message.to_vec()))
+}
+
+/// ZeroMQ PUB socket.
+///
+/// Messages sent before a subscriber has connected are not received by it.
+pub struct Publisher {
+    runtime: Runtime,
+    socket: PubSocket,
+    pdu: bool,
+}
+
+impl fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Publisher")
+            .field("pdu", &self.pdu)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Publisher {
+    /// Binds a PUB socket to an endpoint, such as `tcp://127.0.0.1:5555`.
+    ///
+    /// If `pdu` is true, each message is sent as a serialized PDU.
+    pub fn bind(endpoint: &str, pdu: bool) -> Result<Publisher> {
+        let runtime = runtime()?;
+        let mut socket = PubSocket::new();
+        let endpoint = runtime.block_on(socket.bind(endpoint))?;
+        message!("publishing on ZeroMQ {endpoint}");
+        Ok(Publisher {
+            runtime,
+            socket,
+            pdu,
+        })
+    }
+}
+
+impl PacketSink for Publisher {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        let message = if self.pdu {
+            Pdu::new(datagram.to_vec()).serialize()
+        } else {
+            datagram.to_vec()
+        };
+        self.runtime
+            .block_on(self.socket.send(ZmqMessage::from(message)))
+            .map_err(io::Error::other)
+    }
+}
+
+/// ZeroMQ SUB socket.
+///
+/// Each message should contain one SSDV packet, or one or more KISS frames
+/// containing one SSDV packet each if KISS framing is used, either as raw bytes
+/// or as a serialized PDU. The reception time, frequency and SNR given in the
+/// metadata of the PDUs are kept with the packets. In multipart messages, only
+/// the last part is used, since the first parts usually contain a topic.
+pub struct Subscriber {
+    runtime: Runtime,
+    socket: SubSocket,
+}
+
+impl fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriber").finish_non_exhaustive()
+    }
+}
+
+impl Subscriber {
+    /// Subscribes to a PUB socket bound to an endpoint, such as
+    /// `tcp://127.0.0.1:5555`.
+    pub fn connect(endpoint: &str) -> Result<Subscriber> {
+        let runtime = runtime()?;
+        let mut socket = SubSocket::new();
+        // The zeromq crate waits until the PUB socket is bound, but gives up if
+        // the handshake fails, so the connection is retried.
+        while let Err(err) = runtime.block_on(socket.connect(endpoint)) {
+            message!("could not connect to ZeroMQ {endpoint}: {err}");
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+        runtime.block_on(socket.subscribe(""))?;
+        message!("subscribed to ZeroMQ {endpoint}");
+        Ok(Subscriber { runtime, socket })
+    }
+}
+
+impl PacketSource for Subscriber {
+    fn recv(&mut self) -> io::Result<Received> {
+        // the timeout is used to refresh the dashboard periodically
+        let message = self
+            .runtime
+            .block_on(async { tokio::time::timeout(RECV_TIMEOUT, self.socket.recv()).await });
+        let Ok(message) = message else {
+            return Ok(Received::Timeout);
+        };
+        match message.map_err(io::Error::other)?.into_vec().last() {
+            Some(part) => {
+                let pdu = message_pdu(part);
+                let metadata = pdu.packet_metadata();
+                Ok(Received::Datagram(pdu.data, metadata))
+            }
+            None => Ok(Received::Timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pdu_payload() {
+        let payload = (0..=255).collect::<Vec<u8>>();
+        let pdu = Pdu::new(payload.clone());
+        assert_eq!(message_pdu(&pdu.serialize()), pdu);
+        // a PDU with metadata, as sent by gr-satellites
+        let mut message = vec![
+            0x07, 0x09, 0x07, 0x02, 0x00, 0x04, b'n', b'a', b'm', b'e', 0x02, 0x00, 0x02, b'S',
+            b'2', 0x06,
+        ];
+        message.extend_from_slice(&pdu.serialize()[2..]);
+        let received = message_pdu(&message);
+        assert_eq!(received.data, payload);
+        assert_eq!(received.metadata.len(), 1);
+        // raw packets are not modified
+        assert_eq!(message_pdu(&payload), pdu);
+        let raw = [0x07, 1, 2, 3];
+        assert_eq!(message_pdu(&raw).data, raw);
+    }
+}
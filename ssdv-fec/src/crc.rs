@@ -1,15 +1,61 @@
 use core::borrow::Borrow;
 
-const CRC32_DSLWP_MAGIC_VALUE: u32 = 0x4EE4FDE1;
+pub(crate) const CRC32_DSLWP_MAGIC_VALUE: u32 = 0x4EE4FDE1;
 
+pub(crate) const CRC32_INIT: u32 = 0xFFFFFFFF;
+
+/// Computes the CRC-32 used by the packets in the Longjiang-2 format.
+///
+/// This is the CRC-32 used by standard SSDV packets, but initialized as if the
+/// packet type and callsign fields that Longjiang-2 packets omit had been
+/// processed first. It is also known as the DSLWP CRC-32.
 pub fn crc32<I, T>(data: I) -> u32
 where
     I: Iterator<Item = T>,
     T: Borrow<u8>,
 {
-    let mut crc = CRC32_DSLWP_MAGIC_VALUE;
+    crc32_from(CRC32_DSLWP_MAGIC_VALUE, data)
+}
+
+/// Computes the CRC-32 used by standard SSDV packets.
+pub fn crc32_standard<I, T>(data: I) -> u32
+where
+    I: Iterator<Item = T>,
+    T: Borrow<u8>,
+{
+    crc32_from(CRC32_INIT, data)
+}
+
+fn crc32_from<I, T>(init: u32, data: I) -> u32
+where
+    I: Iterator<Item = T>,
+    T: Borrow<u8>,
+{
+    let mut crc = Crc32::new(init);
     for d in data {
-        let mut x = (crc ^ *d.borrow() as u32) & 0xff;
+        crc.update_byte(*d.borrow());
+    }
+    crc.finish()
+}
+
+// CRC-32 computed incrementally, for packets that are generated in pieces.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    // Creates a CRC-32 with an initial value of the register.
+    pub(crate) fn new(init: u32) -> Crc32 {
+        Crc32(init)
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        for &d in data {
+            self.update_byte(d);
+        }
+    }
+
+    fn update_byte(&mut self, d: u8) {
+        let mut x = (self.0 ^ d as u32) & 0xff;
         for _ in 0..8 {
             if x & 1 != 0 {
                 x = (x >> 1) ^ 0xEDB88320;
@@ -17,26 +63,35 @@ where
                 x >>= 1;
             }
         }
-        crc = (crc >> 8) ^ x;
+        self.0 = (self.0 >> 8) ^ x;
+    }
+
+    pub(crate) fn finish(self) -> u32 {
+        self.0 ^ 0xFFFFFFFF
     }
-    crc ^ 0xFFFFFFFF
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{
-        ssdv::{SSDVPacket, SSDV_PACKET_LEN},
-        test_data::IMG_230_SSDV,
-    };
+    use crate::test_data::img_230_packets;
 
     #[test]
     fn check_img_230_crcs() {
-        for packet in IMG_230_SSDV.chunks_exact(SSDV_PACKET_LEN) {
-            let packet = SSDVPacket(packet.try_into().unwrap());
+        for packet in img_230_packets() {
             let crc_calc = crc32(packet.crc32_data().iter());
             let crc_packet = packet.crc32();
             assert_eq!(crc_calc, crc_packet);
         }
     }
+
+    #[test]
+    fn magic_value() {
+        // packet type 0x66 and callsign "SORA"
+        let implicit = [0x66, 0x00, 0x0e, 0x72, 0x40];
+        for packet in img_230_packets() {
+            let crc_calc = crc32_standard(implicit.iter().chain(packet.crc32_data().iter()));
+            assert_eq!(crc_calc, packet.crc32());
+        }
+    }
 }
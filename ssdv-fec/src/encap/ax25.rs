@@ -0,0 +1,45 @@
+//! AX.25 encapsulation.
+//!
+//! Packets are often transmitted in the information field of AX.25 UI frames,
+//! which is how they are delivered by TNCs and by packet engines such as
+//! AGWPE. This module removes the header of these frames.
+
+/// Strips the header of an AX.25 UI frame.
+///
+/// The header is formed by the address field, whose end is marked by the
+/// extension bit, and the control and PID fields. The frame should not
+/// contain the FCS. `None` is returned if the frame is not a UI frame.
+pub fn strip_ui_header(frame: &[u8]) -> Option<&[u8]> {
+    // Each address is 7 bytes long. The extension bit is the LSB of the last
+    // byte of an address, and it is set in the last address.
+    let mut offset = 0;
+    loop {
+        let ssid = *frame.get(offset + 6)?;
+        offset += 7;
+        if ssid & 1 != 0 {
+            break;
+        }
+    }
+    // control field of a UI frame (ignoring the P/F bit)
+    if frame.get(offset)? & !0x10 != 0x03 {
+        return None;
+    }
+    // skip control and PID
+    frame.get(offset + 2..)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ui_header() {
+        let mut frame = vec![0x60; 14];
+        frame[13] = 0x61;
+        frame.extend_from_slice(&[0x03, 0xf0]);
+        frame.extend_from_slice(b"payload");
+        assert_eq!(strip_ui_header(&frame), Some(&b"payload"[..]));
+        frame[14] = 0x00;
+        assert_eq!(strip_ui_header(&frame), None);
+    }
+}
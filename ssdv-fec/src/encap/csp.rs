@@ -0,0 +1,411 @@
+//! CubeSat Space Protocol encapsulation.
+//!
+//! This module wraps packets into [CSP](https://github.com/libcsp/libcsp)
+//! frames and unwraps them on reception. Each frame contains a CSP header,
+//! which can use the format of CSP 1 or CSP 2, followed by a single packet and
+//! optionally by the CRC-32C that libcsp appends when the CRC flag is set.
+
+use crate::Packet;
+use core::fmt;
+
+/// CSP version, which determines the header format.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CspVersion {
+    /// CSP 1, with a 4-byte header, 5-bit addresses and 8-bit flags.
+    #[default]
+    V1,
+    /// CSP 2, with a 6-byte header, 14-bit addresses and 6-bit flags.
+    V2,
+}
+
+impl CspVersion {
+    /// Returns the length of the CSP header in bytes.
+    pub fn header_len(self) -> usize {
+        match self {
+            CspVersion::V1 => 4,
+            CspVersion::V2 => 6,
+        }
+    }
+
+    fn max_address(self) -> u16 {
+        match self {
+            CspVersion::V1 => (1 << 5) - 1,
+            CspVersion::V2 => (1 << 14) - 1,
+        }
+    }
+}
+
+/// CSP header flag indicating that the frame ends with a CRC-32C.
+pub const CSP_FLAG_CRC32: u8 = 0x01;
+
+/// Length of the CRC-32C appended to frames with the CRC flag.
+pub const CSP_CRC32_LEN: usize = 4;
+
+/// CSP header.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct CspHeader {
+    /// Priority, between 0 (critical) and 3 (low).
+    pub priority: u8,
+    /// Source address.
+    pub source: u16,
+    /// Destination address.
+    pub destination: u16,
+    /// Source port.
+    pub source_port: u8,
+    /// Destination port.
+    pub destination_port: u8,
+    /// Flags.
+    pub flags: u8,
+}
+
+impl CspHeader {
+    /// Parses a CSP header from the beginning of a frame.
+    ///
+    /// Returns `None` if the frame is shorter than the header.
+    pub fn parse(version: CspVersion, frame: &[u8]) -> Option<CspHeader> {
+        let header = frame.get(..version.header_len())?;
+        Some(match version {
+            CspVersion::V1 => {
+                let id = u32::from_be_bytes(header.try_into().unwrap());
+                CspHeader {
+                    priority: (id >> 30) as u8,
+                    source: ((id >> 25) & 0x1f) as u16,
+                    destination: ((id >> 20) & 0x1f) as u16,
+                    destination_port: ((id >> 14) & 0x3f) as u8,
+                    source_port: ((id >> 8) & 0x3f) as u8,
+                    flags: id as u8,
+                }
+            }
+            CspVersion::V2 => {
+                let mut bytes = [0; 8];
+                bytes[2..].copy_from_slice(header);
+                let id = u64::from_be_bytes(bytes);
+                CspHeader {
+                    priority: (id >> 46) as u8,
+                    destination: ((id >> 32) & 0x3fff) as u16,
+                    source: ((id >> 18) & 0x3fff) as u16,
+                    destination_port: ((id >> 12) & 0x3f) as u8,
+                    source_port: ((id >> 6) & 0x3f) as u8,
+                    flags: (id & 0x3f) as u8,
+                }
+            }
+        })
+    }
+
+    // Writes the header, whose fields must be in range, to the beginning of a
+    // buffer long enough.
+    fn write(&self, version: CspVersion, buffer: &mut [u8]) {
+        match version {
+            CspVersion::V1 => {
+                let id = (u32::from(self.priority) << 30)
+                    | (u32::from(self.source) << 25)
+                    | (u32::from(self.destination) << 20)
+                    | (u32::from(self.destination_port) << 14)
+                    | (u32::from(self.source_port) << 8)
+                    | u32::from(self.flags);
+                buffer[..4].copy_from_slice(&id.to_be_bytes());
+            }
+            CspVersion::V2 => {
+                let id = (u64::from(self.priority) << 46)
+                    | (u64::from(self.destination) << 32)
+                    | (u64::from(self.source) << 18)
+                    | (u64::from(self.destination_port) << 12)
+                    | (u64::from(self.source_port) << 6)
+                    | u64::from(self.flags);
+                buffer[..6].copy_from_slice(&id.to_be_bytes()[2..]);
+            }
+        }
+    }
+}
+
+/// Error produced by [`Csp`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CspError {
+    /// The buffer is too short for the frame.
+    BufferTooShort,
+    /// The frame length, given in this variant, does not correspond to a
+    /// packet.
+    WrongLength(usize),
+    /// The addresses of the frame are not the configured ones.
+    WrongAddress,
+    /// The ports of the frame are not the configured ones.
+    WrongPort,
+    /// The CRC-32C of the frame is wrong.
+    WrongCrc,
+}
+
+impl fmt::Display for CspError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CspError::BufferTooShort => f.write_str("buffer too short for CSP frame"),
+            CspError::WrongLength(len) => write!(f, "wrong CSP frame length {len}"),
+            CspError::WrongAddress => f.write_str("wrong CSP addresses"),
+            CspError::WrongPort => f.write_str("wrong CSP ports"),
+            CspError::WrongCrc => f.write_str("wrong CSP CRC-32C"),
+        }
+    }
+}
+
+impl core::error::Error for CspError {}
+
+/// CSP encapsulation.
+///
+/// This contains the CSP addresses and ports of the connection that carries
+/// the packets, and wraps and unwraps frames with them. The same configuration
+/// should be used by the transmitter and the receiver: frames are only
+/// unwrapped if their addresses and ports are the configured ones.
+///
+/// By default, CSP 1 is used, all the addresses and ports are zero, the
+/// priority is 2 (normal) and no CRC-32C is appended.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Csp {
+    version: CspVersion,
+    header: CspHeader,
+}
+
+impl Default for Csp {
+    fn default() -> Csp {
+        Csp::new(CspVersion::default())
+    }
+}
+
+impl Csp {
+    /// Creates a new CSP encapsulation using a version of the protocol.
+    pub fn new(version: CspVersion) -> Csp {
+        Csp {
+            version,
+            header: CspHeader {
+                priority: 2,
+                ..CspHeader::default()
+            },
+        }
+    }
+
+    /// Sets the priority of the transmitted frames.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `priority` is larger than 3.
+    pub fn priority(mut self, priority: u8) -> Csp {
+        assert!(priority < 4, "the CSP priority must be smaller than 4");
+        self.header.priority = priority;
+        self
+    }
+
+    /// Sets the source address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address does not fit in the header of the CSP version.
+    pub fn source(mut self, address: u16) -> Csp {
+        assert!(
+            address <= self.version.max_address(),
+            "CSP address too large"
+        );
+        self.header.source = address;
+        self
+    }
+
+    /// Sets the destination address.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address does not fit in the header of the CSP version.
+    pub fn destination(mut self, address: u16) -> Csp {
+        assert!(
+            address <= self.version.max_address(),
+            "CSP address too large"
+        );
+        self.header.destination = address;
+        self
+    }
+
+    /// Sets the source port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` is larger than 63.
+    pub fn source_port(mut self, port: u8) -> Csp {
+        assert!(port < 64, "the CSP port must be smaller than 64");
+        self.header.source_port = port;
+        self
+    }
+
+    /// Sets the destination port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` is larger than 63.
+    pub fn destination_port(mut self, port: u8) -> Csp {
+        assert!(port < 64, "the CSP port must be smaller than 64");
+        self.header.destination_port = port;
+        self
+    }
+
+    /// Sets whether a CRC-32C is appended to the transmitted frames.
+    pub fn crc(mut self, crc: bool) -> Csp {
+        if crc {
+            self.header.flags |= CSP_FLAG_CRC32;
+        } else {
+            self.header.flags &= !CSP_FLAG_CRC32;
+        }
+        self
+    }
+
+    /// Returns the header of the transmitted frames.
+    pub fn header(&self) -> CspHeader {
+        self.header
+    }
+
+    /// Returns the length of the frames that carry packets of type `P`.
+    pub fn frame_len<P: Packet>(&self) -> usize {
+        let crc_len = if self.header.flags & CSP_FLAG_CRC32 != 0 {
+            CSP_CRC32_LEN
+        } else {
+            0
+        };
+        self.version.header_len() + P::LEN + crc_len
+    }
+
+    /// Wraps a packet into a CSP frame.
+    ///
+    /// The frame is written to the beginning of `buffer`, and its length is
+    /// returned.
+    pub fn wrap<P: Packet>(&self, packet: &P, buffer: &mut [u8]) -> Result<usize, CspError> {
+        let len = self.frame_len::<P>();
+        let frame = buffer.get_mut(..len).ok_or(CspError::BufferTooShort)?;
+        let header_len = self.version.header_len();
+        self.header.write(self.version, frame);
+        frame[header_len..header_len + P::LEN].copy_from_slice(packet.as_bytes());
+        if self.header.flags & CSP_FLAG_CRC32 != 0 {
+            let crc = crc32c(packet.as_bytes());
+            frame[header_len + P::LEN..].copy_from_slice(&crc.to_be_bytes());
+        }
+        Ok(len)
+    }
+
+    /// Unwraps the packet contained in a CSP frame.
+    ///
+    /// The CRC-32C of the frame is checked if its CRC flag is set. The CRC-32
+    /// of the packet is not checked.
+    pub fn unwrap<P: Packet>(&self, frame: &[u8]) -> Result<P, CspError> {
+        let header =
+            CspHeader::parse(self.version, frame).ok_or(CspError::WrongLength(frame.len()))?;
+        if header.source != self.header.source || header.destination != self.header.destination {
+            return Err(CspError::WrongAddress);
+        }
+        if header.source_port != self.header.source_port
+            || header.destination_port != self.header.destination_port
+        {
+            return Err(CspError::WrongPort);
+        }
+        let mut data = &frame[self.version.header_len()..];
+        if header.flags & CSP_FLAG_CRC32 != 0 {
+            let Some(crc_start) = data.len().checked_sub(CSP_CRC32_LEN) else {
+                return Err(CspError::WrongLength(frame.len()));
+            };
+            let (payload, crc) = data.split_at(crc_start);
+            if crc32c(payload).to_be_bytes() != crc {
+                return Err(CspError::WrongCrc);
+            }
+            data = payload;
+        }
+        if data.len() != P::LEN {
+            return Err(CspError::WrongLength(frame.len()));
+        }
+        let mut packet = P::zeroed();
+        packet.as_bytes_mut().copy_from_slice(data);
+        Ok(packet)
+    }
+}
+
+// Computes the CRC-32C (Castagnoli) used by libcsp.
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f63b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc ^ 0xffffffff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::IMG_230_SSDV, SSDVPacket, SSDV_PACKET_LEN};
+
+    #[test]
+    fn crc32c_check() {
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+    }
+
+    #[test]
+    fn header_v1() {
+        // libcsp 1.x: priority 2, source 1, destination 10, destination port
+        // 15, source port 40, CRC flag
+        let header = CspHeader {
+            priority: 2,
+            source: 1,
+            destination: 10,
+            source_port: 40,
+            destination_port: 15,
+            flags: CSP_FLAG_CRC32,
+        };
+        let mut bytes = [0; 4];
+        header.write(CspVersion::V1, &mut bytes);
+        assert_eq!(bytes, [0x82, 0xa3, 0xe8, 0x01]);
+        assert_eq!(CspHeader::parse(CspVersion::V1, &bytes), Some(header));
+    }
+
+    #[test]
+    fn round_trip() {
+        let packet = SSDVPacket(IMG_230_SSDV[..SSDV_PACKET_LEN].try_into().unwrap());
+        for version in [CspVersion::V1, CspVersion::V2] {
+            for crc in [false, true] {
+                let csp = Csp::new(version)
+                    .source(3)
+                    .destination(9)
+                    .source_port(20)
+                    .destination_port(21)
+                    .crc(crc);
+                let mut buffer = [0; 256];
+                let len = csp.wrap(&packet, &mut buffer).unwrap();
+                assert_eq!(len, csp.frame_len::<SSDVPacket>());
+                let frame = &mut buffer[..len];
+                assert_eq!(CspHeader::parse(version, frame).unwrap(), csp.header());
+                assert_eq!(csp.unwrap::<SSDVPacket>(frame), Ok(packet));
+                assert_eq!(
+                    csp.destination(8).unwrap::<SSDVPacket>(frame),
+                    Err(CspError::WrongAddress)
+                );
+                assert_eq!(
+                    csp.source_port(19).unwrap::<SSDVPacket>(frame),
+                    Err(CspError::WrongPort)
+                );
+                assert_eq!(
+                    csp.unwrap::<SSDVPacket>(&frame[..len - 1]),
+                    Err(if crc {
+                        CspError::WrongCrc
+                    } else {
+                        CspError::WrongLength(len - 1)
+                    })
+                );
+                frame[20] ^= 1;
+                if crc {
+                    assert_eq!(csp.unwrap::<SSDVPacket>(frame), Err(CspError::WrongCrc));
+                }
+                assert_eq!(
+                    csp.wrap(&packet, &mut [0; 100]),
+                    Err(CspError::BufferTooShort)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Encapsulation of packets in other protocols.
+//!
+//! The modules in this module wrap SSDV FEC packets into the frames of
+//! protocols that are used to carry them, and unwrap them on reception. They
+//! work on caller-provided buffers, so they can be used in `no_std`
+//! environments.
+
+pub mod ax25;
+pub mod csp;
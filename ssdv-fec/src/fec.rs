@@ -1,6 +1,15 @@
-use crate::{crc::crc32, SSDVPacket, GF64K, SSDV_DATA_LEN};
-#[cfg(feature = "std")]
-use thiserror::Error;
+#[cfg(feature = "async-embedded")]
+use crate::yield_now::Yielder;
+use crate::{
+    bounds::{self, encode_steps, encoder_steps},
+    crc::Crc32,
+    progress::{Meter, Operation},
+    timing::Stopwatch,
+    Packet, SSDVPacket, GF64K,
+};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
 
 /// SSDV FEC encoder.
 ///
@@ -12,31 +21,544 @@ use thiserror::Error;
 ///
 /// The struct contains a mutable reference to a slice containing the SSDV
 /// packets of the image. The lifetime of this slice is given by the lifetime
-/// parameter `'a`.
+/// parameter `'a`. The packet format is given by the type parameter `P`, which
+/// defaults to the 218-byte format used by Longjiang-2.
 #[derive(Debug)]
-pub struct Encoder<'a> {
-    buffer: &'a mut [SSDVPacket],
+pub struct Encoder<'a, P: Packet = SSDVPacket> {
+    buffer: &'a mut [P],
+}
+
+/// SSDV FEC encoder for read-only packets.
+///
+/// This struct works as [`Encoder`], but the systematic packets of the image
+/// are given in a read-only slice, which is not modified. This allows the
+/// packets to be stored in flash or in memory-mapped storage. Instead of
+/// modifying the packets, the encoder stores a weight of 2 bytes for each
+/// systematic packet in a slice given by the caller, which is the only RAM
+/// needed besides the packet being encoded.
+///
+/// Encoding FEC packets takes one more GF(2¹⁶) multiplication per systematic
+/// packet than with [`Encoder`], which is negligible compared to the work done
+/// for each data word.
+#[derive(Debug)]
+pub struct ReadOnlyEncoder<'a, P: Packet = SSDVPacket> {
+    packets: &'a [P],
+    weights: &'a mut [u16],
+}
+
+/// SSDV FEC encoder that owns the packets of the image.
+///
+/// This struct works as [`Encoder`], but the systematic packets of the image
+/// are moved into the encoder in a `Vec`, so that it has no lifetime
+/// parameter. It is intended for applications and language bindings where
+/// the encoder must be stored without borrowing a buffer.
+///
+/// This struct is only available with the `alloc` feature.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct OwnedEncoder<P: Packet = SSDVPacket> {
+    packets: Vec<P>,
 }
 
 /// Error produced by the SSDV FEC encoder.
 ///
-/// This enum lists the errors that can be produced by [`Encoder`].
-#[allow(clippy::enum_variant_names)] // this is triggered because all the variants end in Input
+/// This enum lists the errors that can be produced by [`Encoder`] and
+/// [`ReadOnlyEncoder`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum EncoderError {
     /// The encoder input is empty.
-    #[cfg_attr(feature = "std", error("encoder input is empty"))]
     EmptyInput,
     /// The encoder input is too long.
-    #[cfg_attr(feature = "std", error("encoder input is too long"))]
     TooLongInput,
     /// There is a non-systematic packet in the encoder input.
-    #[cfg_attr(feature = "std", error("non-systematic packet in encoder input"))]
     NonSystematicInput,
+    /// The slice for the weights of a [`ReadOnlyEncoder`] is shorter than the
+    /// number of systematic packets.
+    WeightsTooShort,
+    /// A byte buffer given to the encoder has an invalid length.
+    ///
+    /// This is returned by [`Encoder::from_bytes`] if the length of the
+    /// buffer is not a multiple of the packet length, and by
+    /// [`Encoder::encode_into`] if the output buffer is shorter than a packet.
+    InvalidLength,
+    /// The packet ID of some of the packets to generate is larger than
+    /// `u16::MAX`.
+    ///
+    /// This is returned by [`Encoder::try_encode_range`].
+    InvalidRange,
+}
+
+impl fmt::Display for EncoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EncoderError::EmptyInput => "encoder input is empty",
+            EncoderError::TooLongInput => "encoder input is too long",
+            EncoderError::NonSystematicInput => "non-systematic packet in encoder input",
+            EncoderError::WeightsTooShort => "encoder weights slice is too short",
+            EncoderError::InvalidLength => "encoder buffer has an invalid length",
+            EncoderError::InvalidRange => "packet IDs out of range",
+        })
+    }
 }
 
-impl<'a> Encoder<'a> {
+impl core::error::Error for EncoderError {}
+
+// Checks the systematic packets given to an encoder.
+fn check_input<P: Packet>(systematic_packets: &[P]) -> Result<(), EncoderError> {
+    if systematic_packets.is_empty() {
+        return Err(EncoderError::EmptyInput);
+    }
+    if systematic_packets.len() > usize::from(u16::MAX) {
+        return Err(EncoderError::TooLongInput);
+    }
+    // only check the first packet for efficiency
+    if systematic_packets[0].is_fec_packet() {
+        return Err(EncoderError::NonSystematicInput);
+    }
+    Ok(())
+}
+
+// Computes
+// w_j^{-1} = \prod_{m \neq j} (x_j - x_m).
+fn wj_inv(j: u16, k: u16) -> GF64K {
+    let xj = GF64K::from(j);
+    let mut ret = GF64K::from(1);
+    for m in 0..k {
+        if m != j {
+            let xm = GF64K::from(m);
+            ret *= xj - xm;
+        }
+    }
+    ret
+}
+
+// Multiplies each of the GF(2¹⁶) words of a data field by a factor.
+fn scale_data(data: &mut [u8], factor: GF64K) {
+    for word in data.as_chunks_mut::<2>().0 {
+        let value = GF64K::from(u16::from_be_bytes(*word));
+        *word = u16::from(value * factor).to_be_bytes();
+    }
+}
+
+// Systematic packets of an image, as stored by an encoder.
+//
+// The Lagrange polynomial L(x) that interpolates
+// L(x_j) = y_j
+// can be computed as
+// L(x) = l(x) \sum_{j=0}^{k-1} w_j y_j / (x - x_j),
+// where
+// l(x) = \prod_{j=0}^{k-1} (x - x_j),
+// and
+// w_j = \prod_{m \neq j} (x_j - x_m)^{-1}.
+//
+// The data field of each stored packet contains either the values y_j or the
+// terms w_j y_j. The factors returned by the methods of this trait convert the
+// stored data into each of them.
+trait Systematic<P: Packet>: Debug {
+    // Returns the stored packets.
+    fn packets(&self) -> &[P];
+
+    // Returns the factor by which the data of packet j is multiplied to
+    // obtain w_j y_j.
+    fn lagrange_factor(&self, j: u16) -> GF64K;
+
+    // Returns the factor by which the data of packet j is multiplied to
+    // obtain y_j.
+    fn value_factor(&self, j: u16) -> GF64K;
+
+    fn num_systematic(&self) -> u16 {
+        self.packets().len() as u16
+    }
+
+    // Returns the first packet. The encoders always have at least one packet,
+    // but None is handled instead of panicking.
+    fn first(&self) -> Option<&P> {
+        self.packets().first()
+    }
+
+    fn encode_packet(&self, packet_id: u16, output: &mut P) {
+        self.encode_header(packet_id, output);
+        self.encode_data(packet_id, 0, output.data_as_mut());
+        output.set_crc32(output.compute_crc32());
+    }
+
+    // Writes the fields of a packet that come before the data field.
+    fn encode_header(&self, packet_id: u16, output: &mut P) {
+        let (prefix, rest) = output.as_bytes_mut().split_at_mut(P::HEADER_OFFSET);
+        if let Some(first) = self.first() {
+            prefix.copy_from_slice(&first.as_bytes()[..P::HEADER_OFFSET]);
+        }
+        for (byte, header) in rest.iter_mut().zip(self.header(packet_id)) {
+            *byte = header;
+        }
+    }
+
+    // Returns the image ID, packet ID, width, height and flags fields of a
+    // packet.
+    fn header(&self, packet_id: u16) -> [u8; HEADER_LEN] {
+        let Some(first) = self.first() else {
+            return [0; HEADER_LEN];
+        };
+        let k = self.num_systematic();
+        let is_fec = packet_id >= k;
+        let [packet_id_hi, packet_id_lo] = packet_id.to_be_bytes();
+        let [size_hi, size_lo] = if is_fec {
+            k.to_be_bytes()
+        } else {
+            // the first packet is systematic, so it has a width and height
+            [
+                first.width().unwrap_or_default(),
+                first.height().unwrap_or_default(),
+            ]
+        };
+        let mut flags = first.flags() & !(FLAG_EOI | FLAG_FEC);
+        if packet_id.wrapping_add(1) == k {
+            flags |= FLAG_EOI;
+        }
+        if is_fec {
+            flags |= FLAG_FEC;
+        }
+        [
+            first.image_id(),
+            packet_id_hi,
+            packet_id_lo,
+            size_hi,
+            size_lo,
+            flags,
+        ]
+    }
+
+    // Generates the data field of a packet, starting at the byte `offset` of
+    // the data field. The offset and the length of `data` must be even.
+    fn encode_data(&self, packet_id: u16, offset: usize, data: &mut [u8]) {
+        if packet_id >= self.num_systematic() {
+            self.encode_fec_data(packet_id, offset, data);
+        } else {
+            self.encode_systematic_data(packet_id, offset, data);
+        }
+    }
+
+    fn encode_fec_data(&self, packet_id: u16, offset: usize, data: &mut [u8]) {
+        // Compute l(x) \sum_{j=0}^{k-1} w_j y_j / (x - x_j) for each word in
+        // the output data. The sum is accumulated in the output data, so that
+        // only one division is needed for each term j.
+        let k = self.num_systematic();
+        let mut meter = Meter::new(Operation::Encode, encode_steps(k));
+        let lx = self.lx(packet_id);
+        meter.work(usize::from(k));
+        data.fill(0);
+        for (j, packet) in (0..k).zip(self.packets()) {
+            self.add_fec_term(packet_id, lx, j, packet, offset, data);
+            meter.step(data.len() / 2);
+        }
+    }
+
+    // Computes l(x) for x = packet_id.
+    fn lx(&self, packet_id: u16) -> GF64K {
+        let x = GF64K::from(packet_id);
+        let mut lx = GF64K::from(1);
+        for j in 0..self.num_systematic() {
+            let xj = GF64K::from(j);
+            lx *= x - xj;
+        }
+        lx
+    }
+
+    // Adds the term l(x) w_j y_j / (x - x_j) to the words of `data`, where
+    // `packet` is the stored packet j.
+    fn add_fec_term(
+        &self,
+        packet_id: u16,
+        lx: GF64K,
+        j: u16,
+        packet: &P,
+        offset: usize,
+        data: &mut [u8],
+    ) {
+        let x = GF64K::from(packet_id);
+        let xj = GF64K::from(j);
+        let coeff = (lx * self.lagrange_factor(j)).div_nonzero(x - xj);
+        let stored = packet.data().get(offset..).unwrap_or_default();
+        for (word_in, word_out) in stored
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .zip(data.as_chunks_mut::<2>().0)
+        {
+            let word = GF64K::from(u16::from_be_bytes(*word_in));
+            let sum = GF64K::from(u16::from_be_bytes(*word_out)) + word * coeff;
+            *word_out = u16::from(sum).to_be_bytes();
+        }
+    }
+
+    fn encode_systematic_data(&self, packet_id: u16, offset: usize, data: &mut [u8]) {
+        // The algorithm in encode_fec_data is not valid for systematic packets,
+        // because both l(x) and one of the terms 1 / (x - x_j) vanish. In the
+        // systematic case we convert the stored data into y_j.
+        let factor = self.value_factor(packet_id);
+        let stored = self
+            .packets()
+            .get(usize::from(packet_id))
+            .and_then(|packet| packet.data().get(offset..))
+            .unwrap_or_default();
+        for (word_in, word_out) in stored
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .zip(data.as_chunks_mut::<2>().0)
+        {
+            let yj = GF64K::from(u16::from_be_bytes(*word_in)) * factor;
+            *word_out = u16::from(yj).to_be_bytes();
+        }
+    }
+}
+
+impl<P: Packet> Systematic<P> for Encoder<'_, P> {
+    fn packets(&self) -> &[P] {
+        self.buffer
+    }
+
+    fn lagrange_factor(&self, _j: u16) -> GF64K {
+        // the stored data is already w_j y_j
+        GF64K::from(1)
+    }
+
+    fn value_factor(&self, j: u16) -> GF64K {
+        // undo what we did in values_to_lagrange
+        wj_inv(j, self.num_systematic())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<P: Packet> Systematic<P> for OwnedEncoder<P> {
+    fn packets(&self) -> &[P] {
+        &self.packets
+    }
+
+    fn lagrange_factor(&self, _j: u16) -> GF64K {
+        // the stored data is already w_j y_j, as in Encoder
+        GF64K::from(1)
+    }
+
+    fn value_factor(&self, j: u16) -> GF64K {
+        wj_inv(j, self.num_systematic())
+    }
+}
+
+impl<P: Packet> Systematic<P> for ReadOnlyEncoder<'_, P> {
+    fn packets(&self) -> &[P] {
+        self.packets
+    }
+
+    fn lagrange_factor(&self, j: u16) -> GF64K {
+        GF64K::from(
+            self.weights
+                .get(usize::from(j))
+                .copied()
+                .unwrap_or_default(),
+        )
+    }
+
+    fn value_factor(&self, _j: u16) -> GF64K {
+        // the stored data is already y_j
+        GF64K::from(1)
+    }
+}
+
+// Implements the methods that are common to all the encoders.
+macro_rules! impl_encode {
+    ($encoder:ident $(<$lifetime:lifetime>)?) => {
+        impl<$($lifetime,)? P: Packet> $encoder<$($lifetime,)? P> {
+            /// Generate the packet with a corresponding `packet_id`.
+            ///
+            /// If the `packet_id` is smaller than the number of systematic
+            /// packets in the image, the corresponding systematic packet given
+            /// to the encoder is generated. Otherwise, a FEC packet is
+            /// generated. The packet is written to `output`.
+            pub fn encode(&self, packet_id: u16, output: &mut P) {
+                let stopwatch = Stopwatch::start(Operation::Encode);
+                self.encode_packet(packet_id, output);
+                stopwatch.stop();
+            }
+
+            /// Generates the packet with a corresponding `packet_id` into a byte
+            /// buffer.
+            ///
+            /// This works as [`Self::encode`], but the packet is written to the
+            /// beginning of `output`, which can be any byte buffer. The function
+            /// returns the length of the packet, or
+            /// [`EncoderError::InvalidLength`] if `output` is shorter than a
+            /// packet.
+            pub fn encode_into(
+                &self,
+                packet_id: u16,
+                output: &mut [u8],
+            ) -> Result<usize, EncoderError> {
+                let packet = output
+                    .get_mut(..P::LEN)
+                    .and_then(P::slice_from_bytes_mut)
+                    .and_then(|packet| packet.first_mut())
+                    .ok_or(EncoderError::InvalidLength)?;
+                self.encode(packet_id, packet);
+                Ok(P::LEN)
+            }
+
+            /// Generate the packet with a corresponding `packet_id`,
+            /// yielding to the async executor periodically.
+            ///
+            /// This generates the same packet as [`Self::encode`], but it
+            /// yields each time that a bounded amount of work has been done,
+            /// so that a single-core async firmware can run other tasks while
+            /// FEC packets are encoded in the background.
+            #[cfg(feature = "async-embedded")]
+            pub async fn encode_async(&self, packet_id: u16, output: &mut P) {
+                let k = self.num_systematic();
+                if packet_id < k {
+                    // systematic packets take very little work
+                    self.encode_packet(packet_id, output);
+                    return;
+                }
+                let mut yielder = Yielder::new(Operation::Encode, encode_steps(k));
+                self.encode_header(packet_id, output);
+                let lx = self.lx(packet_id);
+                yielder.work(usize::from(k)).await;
+                let data = output.data_as_mut();
+                data.fill(0);
+                for (j, packet) in (0..k).zip(self.packets()) {
+                    self.add_fec_term(packet_id, lx, j, packet, 0, data);
+                    yielder.step(P::DATA_LEN / 2).await;
+                }
+                output.set_crc32(output.compute_crc32());
+            }
+
+            /// Generates the packet with a corresponding `packet_id` in chunks.
+            ///
+            /// This generates the same packet as [`Self::encode`], but instead
+            /// of writing it to a packet buffer, it returns a [`PacketChunks`]
+            /// that writes the bytes of the packet in chunks of any size chosen
+            /// by the caller. This allows writing the packet directly into a
+            /// radio FIFO or a chain of DMA descriptors without holding a buffer
+            /// for the whole packet.
+            pub fn encode_chunks(&self, packet_id: u16) -> PacketChunks<'_, P> {
+                PacketChunks {
+                    encoder: self,
+                    packet_id,
+                    header: self.header(packet_id),
+                    position: 0,
+                    crc: Crc32::new(P::CRC32_INIT),
+                }
+            }
+
+            /// Generates the packet with a corresponding `packet_id` word by
+            /// word.
+            ///
+            /// This generates the same packet as [`Self::encode`], but it
+            /// returns a [`PacketWords`] that computes each 2-byte word of the
+            /// packet on demand, for instance when the interrupt handler of the
+            /// radio requests the next word to transmit. No buffer is needed
+            /// for the packet, not even for a chunk of it.
+            pub fn encode_words(&self, packet_id: u16) -> PacketWords<'_, P> {
+                let lx = if packet_id >= self.num_systematic() {
+                    self.lx(packet_id)
+                } else {
+                    GF64K::from(0)
+                };
+                PacketWords {
+                    encoder: self,
+                    packet_id,
+                    header: self.header(packet_id),
+                    lx,
+                    position: 0,
+                    crc: Crc32::new(P::CRC32_INIT),
+                    word_offset: usize::MAX,
+                    word: [0; 2],
+                }
+            }
+
+            /// Generates the packets with consecutive packet IDs starting at
+            /// `first_packet_id`.
+            ///
+            /// This works as [`Self::try_encode_range`], but panics instead of
+            /// returning an error.
+            ///
+            /// # Panics
+            ///
+            /// This function panics if the packet ID of some of the packets is
+            /// larger than `u16::MAX`.
+            pub fn encode_range(&self, first_packet_id: u16, output: &mut [P]) {
+                if self.try_encode_range(first_packet_id, output).is_err() {
+                    panic!("packet IDs out of range");
+                }
+            }
+
+            /// Generates the packets with consecutive packet IDs starting at
+            /// `first_packet_id`.
+            ///
+            /// The number of packets generated is given by the length of
+            /// `output`, where the packets are written. This is equivalent to
+            /// calling [`Self::encode`] for each packet. If the packet ID of
+            /// some of the packets would be larger than `u16::MAX`,
+            /// [`EncoderError::InvalidRange`] is returned and `output` is not
+            /// modified.
+            pub fn try_encode_range(
+                &self,
+                first_packet_id: u16,
+                output: &mut [P],
+            ) -> Result<(), EncoderError> {
+                if output.len() > usize::from(u16::MAX - first_packet_id) + 1 {
+                    return Err(EncoderError::InvalidRange);
+                }
+                for (packet_id, packet) in (first_packet_id..=u16::MAX).zip(output.iter_mut()) {
+                    self.encode(packet_id, packet);
+                }
+                Ok(())
+            }
+
+            /// Returns the number of systematic packets of the image.
+            pub fn num_systematic(&self) -> u16 {
+                Systematic::num_systematic(self)
+            }
+
+            /// Returns the image ID of the image.
+            pub fn image_id(&self) -> u8 {
+                self.first().map_or(0, |first| first.image_id())
+            }
+
+            /// Returns the value of the width field of the image.
+            ///
+            /// As in the SSDV header, the width is measured in units of 16
+            /// pixels.
+            pub fn image_width(&self) -> u8 {
+                self.first()
+                    .and_then(|first| first.width())
+                    .unwrap_or_default()
+            }
+
+            /// Returns the value of the height field of the image.
+            ///
+            /// As in the SSDV header, the height is measured in units of 16
+            /// pixels.
+            pub fn image_height(&self) -> u8 {
+                self.first()
+                    .and_then(|first| first.height())
+                    .unwrap_or_default()
+            }
+
+            /// Returns the value of the flags field of the image.
+            pub fn flags(&self) -> u8 {
+                self.first().map_or(0, |first| first.flags())
+            }
+        }
+    };
+}
+
+impl_encode!(Encoder<'a>);
+impl_encode!(ReadOnlyEncoder<'a>);
+#[cfg(feature = "alloc")]
+impl_encode!(OwnedEncoder);
+
+impl<'a, P: Packet> Encoder<'a, P> {
     /// Creates a new FEC encoder for an SSDV image.
     ///
     /// The systematic packets for the image are given in the slice
@@ -46,176 +568,605 @@ impl<'a> Encoder<'a> {
     /// If there is a problem with the input contents, this function returns an
     /// error. Otherwise, an [`Encoder`] struct on which
     /// [`encode`](`Encoder::encode`) can be called is returned.
-    pub fn new(systematic_packets: &mut [SSDVPacket]) -> Result<Encoder, EncoderError> {
-        if systematic_packets.is_empty() {
-            return Err(EncoderError::EmptyInput);
-        }
-        if systematic_packets.len() > usize::from(u16::MAX) {
-            return Err(EncoderError::TooLongInput);
-        }
-        // only check the first packet for efficiency
-        if systematic_packets[0].is_fec_packet() {
-            return Err(EncoderError::NonSystematicInput);
-        }
+    pub fn new(systematic_packets: &mut [P]) -> Result<Encoder<'_, P>, EncoderError> {
+        let stopwatch = Stopwatch::start(Operation::EncoderSetup);
+        check_input(systematic_packets)?;
         let mut encoder = Encoder {
             buffer: systematic_packets,
         };
         encoder.values_to_lagrange();
+        stopwatch.stop();
         Ok(encoder)
     }
 
-    // Computes
-    // w_j^{-1} = \prod_{m \neq j} (x_j - x_m).
-    fn wj_inv(j: u16, k: u16) -> GF64K {
-        let xj = GF64K::from(j);
-        let mut ret = GF64K::from(1);
-        for m in 0..k {
-            if m != j {
-                let xm = GF64K::from(m);
-                ret *= xj - xm;
-            }
+    /// Creates a new FEC encoder for an SSDV image, yielding to the async
+    /// executor periodically.
+    ///
+    /// This function works as [`Encoder::new`], but it yields each time that
+    /// a bounded amount of work has been done.
+    #[cfg(feature = "async-embedded")]
+    pub async fn new_async(systematic_packets: &mut [P]) -> Result<Encoder<'_, P>, EncoderError> {
+        check_input(systematic_packets)?;
+        let mut encoder = Encoder {
+            buffer: systematic_packets,
+        };
+        let k = Systematic::num_systematic(&encoder);
+        let mut yielder = Yielder::new(Operation::EncoderSetup, encoder_steps(k));
+        for j in 0..k {
+            encoder.value_to_lagrange(j);
+            yielder.step(usize::from(k) + P::DATA_LEN / 2).await;
+        }
+        Ok(encoder)
+    }
+
+    /// Creates a new FEC encoder for an SSDV image stored in a byte buffer.
+    ///
+    /// This works as [`Encoder::new`], but the systematic packets are given
+    /// as the bytes of the packets one after another, for applications that
+    /// work with byte buffers instead of with packet types. The packet format
+    /// is given by the type parameter `P`. If the length of the buffer is not
+    /// a multiple of the packet length, [`EncoderError::InvalidLength`] is
+    /// returned.
+    pub fn from_bytes(systematic_packets: &mut [u8]) -> Result<Encoder<'_, P>, EncoderError> {
+        let packets =
+            P::slice_from_bytes_mut(systematic_packets).ok_or(EncoderError::InvalidLength)?;
+        Encoder::new(packets)
+    }
+
+    // Creates an encoder for a slice that already contains the terms w_j y_j,
+    // because it was given to an encoder that was not restored when released.
+    #[cfg(any(feature = "alloc", feature = "static-cell"))]
+    pub(crate) fn from_lagrange_terms(systematic_packets: &mut [P]) -> Encoder<'_, P> {
+        Encoder {
+            buffer: systematic_packets,
         }
-        ret
     }
 
     fn values_to_lagrange(&mut self) {
-        // The Lagrange polynomial L(x) that interpolates
-        // L(x_j) = y_j
-        // can be computed as
-        // L(x) = l(x) \sum_{j=0}^{k-1} w_j y_j / (x - x_j),
-        // where
-        // l(x) = \prod_{j=0}^{k-1} (x - x_j),
-        // and
-        // w_j = \prod_{m \neq j} (x_j - x_m)^{-1}.
-        //
         // This function replaces in-place in self.buffer the values y_j by the
-        // terms w_j y_j. This speeds up evaluation of the L(x) for encoding
-        // each FEC packet.
-        let k = self.num_systematic();
+        // terms w_j y_j (see Systematic for the formulas). This speeds up
+        // evaluation of the L(x) for encoding each FEC packet.
+        let k = Systematic::num_systematic(self);
+        let mut meter = Meter::new(Operation::EncoderSetup, encoder_steps(k));
         for j in 0..k {
-            // Compute w_j
-            let wj = GF64K::from(1) / Self::wj_inv(j, k);
-            // Multiply each y_j by w_j
-            let data = self.buffer[usize::from(j)].data_as_mut();
-            for word in data.chunks_exact_mut(2) {
-                let word: &mut [u8; 2] = word.try_into().unwrap();
-                let yj = GF64K::from(u16::from_be_bytes(*word));
-                let yj_wj = yj * wj;
-                *word = u16::from(yj_wj).to_be_bytes();
-            }
+            self.value_to_lagrange(j);
+            meter.step(usize::from(k) + P::DATA_LEN / 2);
         }
     }
 
-    /// Generate the packet with a corresponding `packet_id`.
-    ///
-    /// If the `packet_id` is smaller than the number of systematic packets in
-    /// the image, the corresponding systematic packet give to [`Encoder::new`]
-    /// is generated. Otherwise, a FEC packet is generated. The packet is
-    /// written to `output`.
-    pub fn encode(&self, packet_id: u16, output: &mut SSDVPacket) {
-        self.encode_header(packet_id, output);
-        if output.is_fec_packet() {
-            self.encode_fec_data(packet_id, output.data_as_mut());
-        } else {
-            self.encode_systematic_data(packet_id, output.data_as_mut());
+    // Replaces the values y_j of packet j by the terms w_j y_j.
+    fn value_to_lagrange(&mut self, j: u16) {
+        let k = Systematic::num_systematic(self);
+        // Compute w_j
+        let wj = GF64K::from(1).div_nonzero(wj_inv(j, k));
+        // Multiply each y_j by w_j
+        if let Some(packet) = self.buffer.get_mut(usize::from(j)) {
+            scale_data(packet.data_as_mut(), wj);
         }
-        output.set_crc32(crc32(output.crc32_data().iter()));
     }
 
-    fn encode_header(&self, packet_id: u16, output: &mut SSDVPacket) {
-        output.set_image_id(self.image_id());
-        output.set_packet_id(packet_id);
-        let is_fec = packet_id >= self.num_systematic();
-        if is_fec {
-            output.set_number_systematic_packets(self.num_systematic());
-        } else {
-            output.set_width(self.image_width());
-            output.set_height(self.image_height());
+    /// Releases the encoder, returning the slice given to [`Encoder::new`].
+    ///
+    /// The encoder modifies the contents of the slice. If `restore` is true,
+    /// the original contents of the slice, which are the systematic packets of
+    /// the image, are restored. This takes about as long as [`Encoder::new`].
+    pub fn release(mut self, restore: bool) -> &'a mut [P] {
+        if restore {
+            let stopwatch = Stopwatch::start(Operation::EncoderRelease);
+            self.lagrange_to_values();
+            stopwatch.stop();
         }
-        output.set_flags(self.flags());
-        output.set_eoi(packet_id == self.num_systematic() - 1);
-        output.set_fec_packet(is_fec);
+        self.buffer
     }
 
-    fn encode_fec_data(&self, packet_id: u16, data: &mut [u8; SSDV_DATA_LEN]) {
-        // See values_to_lagrange for the formulas
-        let x = GF64K::from(packet_id);
-        let k = self.num_systematic();
-        // Compute l(x)
-        let mut lx = GF64K::from(1);
+    fn lagrange_to_values(&mut self) {
+        // Undoes values_to_lagrange, replacing in-place in self.buffer the
+        // terms w_j y_j by the values y_j.
+        let k = Systematic::num_systematic(self);
+        let mut meter = Meter::new(Operation::EncoderRelease, encoder_steps(k));
         for j in 0..k {
-            let xj = GF64K::from(j);
-            lx *= x - xj;
+            if let Some(packet) = self.buffer.get_mut(usize::from(j)) {
+                scale_data(packet.data_as_mut(), wj_inv(j, k));
+            }
+            meter.step(usize::from(k) + P::DATA_LEN / 2);
+        }
+    }
+
+    /// Returns a pointer to the slice given to [`Encoder::new`].
+    ///
+    /// This can be used to check which buffer the encoder is using without
+    /// releasing it.
+    pub fn as_ptr(&self) -> *const P {
+        self.buffer.as_ptr()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<P: Packet> OwnedEncoder<P> {
+    /// Creates a new FEC encoder that owns the systematic packets of an SSDV
+    /// image.
+    ///
+    /// The packets must be in order and without repetitions, as in
+    /// [`Encoder::new`].
+    pub fn new(mut systematic_packets: Vec<P>) -> Result<OwnedEncoder<P>, EncoderError> {
+        // the encoder leaves the terms w_j y_j in the packets when dropped
+        Encoder::new(&mut systematic_packets)?;
+        Ok(OwnedEncoder {
+            packets: systematic_packets,
+        })
+    }
+
+    /// Creates a new FEC encoder for an SSDV image given as bytes.
+    ///
+    /// This works as [`Encoder::from_bytes`], but the bytes are copied into
+    /// the encoder. If the length of `systematic_packets` is not a multiple of
+    /// the packet length, [`EncoderError::InvalidLength`] is returned.
+    pub fn from_bytes(systematic_packets: &[u8]) -> Result<OwnedEncoder<P>, EncoderError> {
+        let packets = P::slice_from_bytes(systematic_packets).ok_or(EncoderError::InvalidLength)?;
+        OwnedEncoder::new(packets.to_vec())
+    }
+
+    /// Releases the encoder, returning the systematic packets of the image.
+    ///
+    /// The packets are restored to their original contents, which takes about
+    /// as long as [`OwnedEncoder::new`].
+    pub fn into_packets(mut self) -> Vec<P> {
+        Encoder::from_lagrange_terms(&mut self.packets).release(true);
+        self.packets
+    }
+}
+
+impl<'a, P: Packet> ReadOnlyEncoder<'a, P> {
+    /// Creates a new FEC encoder for an SSDV image stored in read-only memory.
+    ///
+    /// The systematic packets for the image are given in the slice
+    /// `systematic_packets`, which is not modified. They must be in order and
+    /// without repetitions. The encoder computes a weight for each systematic
+    /// packet and stores it in `weights`, which must have at least as many
+    /// elements as systematic packets. Otherwise,
+    /// [`EncoderError::WeightsTooShort`] is returned.
+    ///
+    /// Computing the weights takes about as long as [`Encoder::new`].
+    pub fn new(
+        systematic_packets: &'a [P],
+        weights: &'a mut [u16],
+    ) -> Result<ReadOnlyEncoder<'a, P>, EncoderError> {
+        let stopwatch = Stopwatch::start(Operation::EncoderSetup);
+        check_input(systematic_packets)?;
+        let k = systematic_packets.len() as u16;
+        let Some(weights) = weights.get_mut(..usize::from(k)) else {
+            return Err(EncoderError::WeightsTooShort);
+        };
+        let mut meter = Meter::new(Operation::EncoderSetup, encoder_steps(k));
+        for (j, weight) in (0..k).zip(weights.iter_mut()) {
+            *weight = u16::from(GF64K::from(1).div_nonzero(wj_inv(j, k)));
+            meter.step(usize::from(k));
+        }
+        stopwatch.stop();
+        Ok(ReadOnlyEncoder {
+            packets: systematic_packets,
+            weights,
+        })
+    }
+
+    /// Creates a new FEC encoder for an SSDV image stored in read-only memory,
+    /// yielding to the async executor periodically.
+    ///
+    /// This function works as [`ReadOnlyEncoder::new`], but it yields each
+    /// time that a bounded amount of work has been done.
+    #[cfg(feature = "async-embedded")]
+    pub async fn new_async(
+        systematic_packets: &'a [P],
+        weights: &'a mut [u16],
+    ) -> Result<ReadOnlyEncoder<'a, P>, EncoderError> {
+        check_input(systematic_packets)?;
+        let k = systematic_packets.len() as u16;
+        let Some(weights) = weights.get_mut(..usize::from(k)) else {
+            return Err(EncoderError::WeightsTooShort);
+        };
+        let mut yielder = Yielder::new(Operation::EncoderSetup, encoder_steps(k));
+        for (j, weight) in (0..k).zip(weights.iter_mut()) {
+            *weight = u16::from(GF64K::from(1).div_nonzero(wj_inv(j, k)));
+            yielder.step(usize::from(k)).await;
         }
+        Ok(ReadOnlyEncoder {
+            packets: systematic_packets,
+            weights,
+        })
+    }
+
+    /// Releases the encoder, returning the slice of weights given to
+    /// [`ReadOnlyEncoder::new`].
+    pub fn release(self) -> &'a mut [u16] {
+        self.weights
+    }
+
+    /// Returns a pointer to the slice of packets given to
+    /// [`ReadOnlyEncoder::new`].
+    pub fn as_ptr(&self) -> *const P {
+        self.packets.as_ptr()
+    }
+}
 
-        // Compute \sum_{j=0}^{k-1} w_j y_j / (x - x_j) for each word in the
-        // output data
-        for (r, word) in data.chunks_exact_mut(2).enumerate() {
-            let mut sum = GF64K::from(0);
-            for (j, wj_yj_s) in self.buffer.iter().map(|packet| packet.data()).enumerate() {
-                let wj_yj = GF64K::from(u16::from_be_bytes(
-                    wj_yj_s[2 * r..2 * r + 2].try_into().unwrap(),
-                ));
-                let xj = GF64K::from(j as u16);
-                sum += wj_yj / (x - xj);
+// Length of the image ID, packet ID, width, height and flags fields.
+const HEADER_LEN: usize = 6;
+
+const FLAG_EOI: u8 = 0x4;
+const FLAG_FEC: u8 = 0x40;
+
+// Size of the blocks in which PacketChunks generates the data field. Each block
+// takes one division per systematic packet, in addition to the multiplications
+// needed for each word, so larger blocks are slightly faster.
+const CHUNK_BLOCK_LEN: usize = 64;
+
+/// SSDV FEC packet generated in chunks.
+///
+/// This struct is returned by [`Encoder::encode_chunks`] and
+/// [`ReadOnlyEncoder::encode_chunks`]. Each call to
+/// [`PacketChunks::next_chunk`] writes the next bytes of the packet. Only a few
+/// bytes of state are kept between calls, regardless of the packet size.
+///
+/// Generating the data field of a FEC packet takes one division for each
+/// systematic packet and for each 64-byte block of the data field that a chunk
+/// overlaps, in addition to the work done by [`Encoder::encode`]. Chunks that
+/// are a multiple of 64 bytes and aligned with the data field are the most
+/// efficient, but in practice the overhead is small for any chunk size of a
+/// few tens of bytes.
+///
+/// The blocks are generated in a 64-byte buffer on the stack. When the stack is
+/// very small, [`PacketChunks::next_chunk_with_scratch`] can be used instead
+/// to generate them in a [`ChunkScratch`] given by the caller.
+#[derive(Debug)]
+pub struct PacketChunks<'e, P: Packet = SSDVPacket> {
+    encoder: &'e dyn Systematic<P>,
+    packet_id: u16,
+    header: [u8; HEADER_LEN],
+    // Number of bytes already written.
+    position: usize,
+    // CRC-32 of the bytes already written.
+    crc: Crc32,
+}
+
+impl<P: Packet> PacketChunks<'_, P> {
+    /// Writes the next bytes of the packet.
+    ///
+    /// The bytes are written to the beginning of `chunk`. The function returns
+    /// the number of bytes written, which is the length of `chunk` unless the
+    /// end of the packet is reached. Once the whole packet has been written,
+    /// the function returns zero.
+    pub fn next_chunk(&mut self, chunk: &mut [u8]) -> usize {
+        self.next_chunk_with_scratch(chunk, &mut ChunkScratch::new())
+    }
+
+    /// Writes the next bytes of the packet using scratch memory given by the
+    /// caller.
+    ///
+    /// This function works as [`PacketChunks::next_chunk`], but the blocks of
+    /// the data field are generated in `scratch` instead of in a buffer on the
+    /// stack. The contents of `scratch` are not used between calls, so the
+    /// same scratch can be shared by several packets.
+    pub fn next_chunk_with_scratch(
+        &mut self,
+        chunk: &mut [u8],
+        scratch: &mut ChunkScratch,
+    ) -> usize {
+        let len = chunk.len().min(self.remaining());
+        let data_start = P::HEADER_OFFSET + HEADER_LEN;
+        let crc_start = P::LEN - 4;
+        let mut written = 0;
+        // each iteration writes at least one byte
+        for _ in 0..len {
+            let Some(output) = chunk.get_mut(written..len).filter(|o| !o.is_empty()) else {
+                break;
+            };
+            let position = self.position;
+            let n = if position < P::HEADER_OFFSET {
+                let prefix = self
+                    .encoder
+                    .first()
+                    .and_then(|first| first.as_bytes().get(position..P::HEADER_OFFSET));
+                copy_prefix(output, prefix.unwrap_or_default())
+            } else if position < data_start {
+                let header = self.header.get(position - P::HEADER_OFFSET..);
+                copy_prefix(output, header.unwrap_or_default())
+            } else if position < crc_start {
+                self.data(position - data_start, output, scratch)
+            } else {
+                let crc = self.crc.finish().to_be_bytes();
+                copy_prefix(output, crc.get(position - crc_start..).unwrap_or_default())
+            };
+            if n == 0 {
+                // this does not happen
+                break;
+            }
+            if position < crc_start {
+                let start = position.max(P::CRC32_OFFSET);
+                if let Some(bytes) = output.get(start - position..n) {
+                    self.crc.update(bytes);
+                }
             }
-            let word: &mut [u8; 2] = word.try_into().unwrap();
-            let result = lx * sum;
-            *word = u16::from(result).to_be_bytes();
+            self.position += n;
+            written += n;
         }
+        written
     }
 
-    fn encode_systematic_data(&self, packet_id: u16, data: &mut [u8; SSDV_DATA_LEN]) {
-        // The algorithm in encode_fec_data is not valid for systematic packets,
-        // because both l(x) and one of the terms 1 / (x - x_j) vanish. In the
-        // systematic case we compute w_j again and divide, undoing what we did
-        // in values_to_lagrange.
-        let wjinv = Self::wj_inv(packet_id, self.num_systematic());
-        for (word_in, word_out) in self.buffer[usize::from(packet_id)]
-            .data()
-            .chunks_exact(2)
-            .zip(data.chunks_exact_mut(2))
-        {
-            let wj_yj = GF64K::from(u16::from_be_bytes(word_in.try_into().unwrap()));
-            let yj = wj_yj * wjinv;
-            let word_out: &mut [u8; 2] = word_out.try_into().unwrap();
-            *word_out = u16::from(yj).to_be_bytes();
+    // Writes the data field starting at `offset`, up to the end of the block
+    // that contains `offset`, and returns the number of bytes written.
+    fn data(&self, offset: usize, output: &mut [u8], scratch: &mut ChunkScratch) -> usize {
+        // The FEC works with 16-bit words, so whole words are generated in a
+        // block and then the requested bytes are copied.
+        let block_start = offset & !1;
+        let end = (offset + output.len()).min(P::DATA_LEN);
+        let block_end = ((end + 1) & !1).min(block_start + CHUNK_BLOCK_LEN);
+        let Some(block) = scratch.block.get_mut(..block_end - block_start) else {
+            return 0;
+        };
+        self.encoder.encode_data(self.packet_id, block_start, block);
+        copy_prefix(
+            output,
+            block.get(offset - block_start..).unwrap_or_default(),
+        )
+    }
+
+    /// Returns the packet ID of the packet.
+    pub fn packet_id(&self) -> u16 {
+        self.packet_id
+    }
+
+    /// Returns the number of bytes of the packet that have not been written
+    /// yet.
+    pub fn remaining(&self) -> usize {
+        P::LEN - self.position
+    }
+
+    /// Returns true if the whole packet has been written.
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+/// SSDV FEC packet generated word by word.
+///
+/// This struct is returned by [`Encoder::encode_words`] and
+/// [`ReadOnlyEncoder::encode_words`]. Each call to [`PacketWords::next_word`]
+/// computes the next 2 bytes of the packet, which makes it suitable for a
+/// radio interrupt handler that transmits packets without any packet buffer.
+/// The value of l(x) for the packet is computed when the struct is created, and
+/// the encoder already stores the terms w_j y_j, so each word of the data field
+/// of a FEC packet takes one division and one multiplication for each
+/// systematic packet. This is more work than [`PacketChunks`], which does the
+/// divisions once per 64-byte block, so this should only be used when not even
+/// a small chunk buffer can be afforded.
+#[derive(Debug)]
+pub struct PacketWords<'e, P: Packet = SSDVPacket> {
+    encoder: &'e dyn Systematic<P>,
+    packet_id: u16,
+    header: [u8; HEADER_LEN],
+    // l(x) for x = packet_id. This is only used for FEC packets.
+    lx: GF64K,
+    // Number of bytes already written.
+    position: usize,
+    // CRC-32 of the bytes already written.
+    crc: Crc32,
+    // Offset in the data field of the last data word computed, and its value.
+    word_offset: usize,
+    word: [u8; 2],
+}
+
+impl<P: Packet> PacketWords<'_, P> {
+    /// Returns the next 2 bytes of the packet.
+    ///
+    /// Once the whole packet has been written, the function returns `None`.
+    /// The supported packet formats have an even length, so the packet is
+    /// always written in whole words.
+    pub fn next_word(&mut self) -> Option<[u8; 2]> {
+        if self.remaining() < 2 {
+            return None;
         }
+        Some([self.next_byte()?, self.next_byte()?])
     }
 
-    fn num_systematic(&self) -> u16 {
-        self.buffer.len() as u16
+    /// Returns the next byte of the packet.
+    ///
+    /// This can be mixed with calls to [`PacketWords::next_word`]. Once the
+    /// whole packet has been written, the function returns `None`.
+    pub fn next_byte(&mut self) -> Option<u8> {
+        let position = self.position;
+        let data_start = P::HEADER_OFFSET + HEADER_LEN;
+        let crc_start = P::LEN - 4;
+        let byte = if position < P::HEADER_OFFSET {
+            self.encoder
+                .first()
+                .and_then(|first| first.as_bytes().get(position))
+                .copied()
+                .unwrap_or_default()
+        } else if position < data_start {
+            self.header
+                .get(position - P::HEADER_OFFSET)
+                .copied()
+                .unwrap_or_default()
+        } else if position < crc_start {
+            let offset = position - data_start;
+            let word = self.data_word(offset & !1);
+            word[offset & 1]
+        } else if position < P::LEN {
+            let crc = self.crc.finish().to_be_bytes();
+            crc.get(position - crc_start).copied().unwrap_or_default()
+        } else {
+            return None;
+        };
+        if (P::CRC32_OFFSET..crc_start).contains(&position) {
+            self.crc.update(&[byte]);
+        }
+        self.position += 1;
+        Some(byte)
+    }
+
+    // Returns the word of the data field at `offset`, which must be even.
+    fn data_word(&mut self, offset: usize) -> [u8; 2] {
+        if offset == self.word_offset {
+            return self.word;
+        }
+        let mut word = [0; 2];
+        let k = self.encoder.num_systematic();
+        if self.packet_id >= k {
+            for (j, packet) in (0..k).zip(self.encoder.packets()) {
+                self.encoder
+                    .add_fec_term(self.packet_id, self.lx, j, packet, offset, &mut word);
+            }
+        } else {
+            self.encoder
+                .encode_systematic_data(self.packet_id, offset, &mut word);
+        }
+        self.word_offset = offset;
+        self.word = word;
+        word
+    }
+
+    /// Returns the packet ID of the packet.
+    pub fn packet_id(&self) -> u16 {
+        self.packet_id
     }
 
-    fn image_id(&self) -> u8 {
-        self.buffer[0].image_id()
+    /// Returns the number of bytes of the packet that have not been written
+    /// yet.
+    pub fn remaining(&self) -> usize {
+        P::LEN - self.position
     }
 
-    fn image_width(&self) -> u8 {
-        self.buffer[0].width().unwrap()
+    /// Returns true if the whole packet has been written.
+    pub fn is_finished(&self) -> bool {
+        self.remaining() == 0
     }
+}
+
+// Copies the beginning of `source` to the beginning of `destination`, and
+// returns the number of bytes copied.
+fn copy_prefix(destination: &mut [u8], source: &[u8]) -> usize {
+    let n = destination.len().min(source.len());
+    destination[..n].copy_from_slice(&source[..n]);
+    n
+}
 
-    fn image_height(&self) -> u8 {
-        self.buffer[0].height().unwrap()
+/// Scratch memory for [`PacketChunks::next_chunk_with_scratch`].
+#[derive(Debug, Clone)]
+pub struct ChunkScratch {
+    block: [u8; CHUNK_BLOCK_LEN],
+}
+
+impl ChunkScratch {
+    /// Creates a new scratch.
+    ///
+    /// This function is `const`, so the scratch can be a static variable.
+    pub const fn new() -> ChunkScratch {
+        ChunkScratch {
+            block: [0; CHUNK_BLOCK_LEN],
+        }
     }
+}
 
-    fn flags(&self) -> u8 {
-        self.buffer[0].flags()
+impl Default for ChunkScratch {
+    fn default() -> ChunkScratch {
+        ChunkScratch::new()
     }
 }
 
 /// SSDV FEC decoder.
 ///
 /// This struct represents the FEC decoder. The way to use the FEC decoder is
-/// through the [`Decoder::decode`] associated function. The struct only exists
-/// for namespacing this function.
+/// through the [`Decoder::decode`] and [`Decoder::decode_with_options`]
+/// associated functions. The struct only exists for namespacing these
+/// functions.
 #[derive(Debug)]
 pub struct Decoder {}
 
+/// Options for the SSDV FEC decoder.
+///
+/// By default, the decoder is strict: any inconsistency in the headers of the
+/// input packets causes decoding to fail. A lenient decoder instead resolves
+/// inconsistencies by majority vote, discarding the packets that disagree with
+/// the majority. This allows decoding in the presence of packets from other
+/// images or with corrupted headers that still have a valid CRC. The
+/// inconsistencies that have been resolved are reported in
+/// [`HeaderOverrides`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecoderOptions {
+    lenient: bool,
+}
+
+impl DecoderOptions {
+    /// Creates the default decoder options.
+    pub fn new() -> DecoderOptions {
+        DecoderOptions::default()
+    }
+
+    /// Selects whether the decoder is lenient.
+    pub fn lenient(mut self, lenient: bool) -> DecoderOptions {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Returns `true` if the decoder is lenient.
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+}
+
+/// Header inconsistencies resolved by a lenient decoder.
+///
+/// Each field counts the number of input packets that disagreed with the
+/// majority in some header field and were discarded (or, in the case of
+/// `eoi_on_fec`, corrected).
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HeaderOverrides {
+    /// Packets discarded because of a different image ID.
+    pub image_id: usize,
+    /// Packets discarded because of different flags.
+    pub flags: usize,
+    /// Packets discarded because they were inconsistent with the number of
+    /// systematic packets of the image.
+    pub num_systematic: usize,
+    /// Packets discarded because of a different image width or height.
+    pub dimensions: usize,
+    /// FEC packets on which the EOI flag was set and has been ignored.
+    pub eoi_on_fec: usize,
+}
+
+impl HeaderOverrides {
+    /// Returns `true` if no inconsistencies have been resolved.
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Returns the total number of packets discarded or corrected.
+    pub fn total(&self) -> usize {
+        self.image_id + self.flags + self.num_systematic + self.dimensions + self.eoi_on_fec
+    }
+}
+
+/// Statistics of a successful decoding.
+///
+/// This is returned by [`Decoder::decode_with_stats`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DecodeStats {
+    /// Header inconsistencies resolved by a lenient decoder.
+    pub overrides: HeaderOverrides,
+    /// Number of FEC packets used to recover the missing systematic packets.
+    pub fec_packets_used: usize,
+    /// Number of systematic packets missing from the input that have been
+    /// recovered.
+    pub packets_recovered: usize,
+}
+
 #[derive(Debug)]
-struct DecoderHelper<'a, 'b> {
-    input: &'a mut [SSDVPacket],
-    output: &'b mut [SSDVPacket],
+struct DecoderHelper<'a, 'b, P: Packet> {
+    input: &'a mut [P],
+    output: &'b mut [P],
     num_systematic: u16,
     image_id: u8,
     image_width: u8,
@@ -227,73 +1178,80 @@ struct DecoderHelper<'a, 'b> {
 ///
 /// This enum lists the errors that can be produced by [`Decoder`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "std", derive(Error))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DecoderError {
     /// The EOI flag is set on a FEC packet.
-    #[cfg_attr(feature = "std", error("EOI set on FEC packet"))]
     EoiOnFecPacket,
     /// The EOI flag is set on several different systematic packets.
-    #[cfg_attr(feature = "std", error("EOI set on several different packets"))]
     DuplicatedEoi,
     /// There are different FEC packets containing a different value in the
     /// number of systematic packets field.
-    #[cfg_attr(
-        feature = "std",
-        error("mismatched number of systematic packets on different FEC packets")
-    )]
     NumSystematicMismatch,
     /// The number of systematic packets in the image could not be determined.
     ///
     /// This happens if the last systematic packet (carrying the EOI flag) is
     /// missing and there are no FEC packets.
-    #[cfg_attr(
-        feature = "std",
-        error("could not determine number of systematic packets")
-    )]
     UnknownNumSystematic,
     /// There is a mismatch between the packet ID of the packet carrying the EOI
     /// flag and the number of systematic packets field in the FEC packets.
-    #[cfg_attr(
-        feature = "std",
-        error("mismatch between EOI and number of systematic packets")
-    )]
     EoiFecMismatch,
     /// There are not enough input packets for decoding.
     ///
     /// The decoder needs as least as many distinct input packets as systematic
     /// packets are there in the image.
-    #[cfg_attr(feature = "std", error("not enough input packets"))]
     NotEnoughInput,
     /// The output buffer is too short.
     ///
     /// The length of the output buffer must be greater or equal than the number
     /// of systematic packets in the image.
-    #[cfg_attr(feature = "std", error("output buffer is too short"))]
     OutputTooShort,
     /// A systematic packet has a wrong packet ID.
-    #[cfg_attr(feature = "std", error("wrong packet ID on systematic packet"))]
     WrongSystematicId,
     /// There are multiple image IDs in the packets.
-    #[cfg_attr(feature = "std", error("multiple image IDs"))]
     MultipleImageIds,
     /// There are different packets with inconsistent values of the flags field.
-    #[cfg_attr(feature = "std", error("inconsistent flags on different packets"))]
     InconsistentFlags,
     /// There are systematic packets with different values of the image width or
     /// height.
-    #[cfg_attr(
-        feature = "std",
-        error("mismatched width or height on different systematic packets")
-    )]
     DimensionsMismatch,
     /// There are no systematic packets.
     ///
     /// At least one systematic packet is required to obtain the image width and
     /// height.
-    #[cfg_attr(feature = "std", error("no systematic packets"))]
     NoSystematic,
+    /// The length of the input buffer given to [`Decoder::decode_from`] is not
+    /// a multiple of the packet length.
+    InvalidLength,
+}
+
+impl fmt::Display for DecoderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DecoderError::EoiOnFecPacket => "EOI set on FEC packet",
+            DecoderError::DuplicatedEoi => "EOI set on several different packets",
+            DecoderError::NumSystematicMismatch => {
+                "mismatched number of systematic packets on different FEC packets"
+            }
+            DecoderError::UnknownNumSystematic => {
+                "could not determine number of systematic packets"
+            }
+            DecoderError::EoiFecMismatch => "mismatch between EOI and number of systematic packets",
+            DecoderError::NotEnoughInput => "not enough input packets",
+            DecoderError::OutputTooShort => "output buffer is too short",
+            DecoderError::WrongSystematicId => "wrong packet ID on systematic packet",
+            DecoderError::MultipleImageIds => "multiple image IDs",
+            DecoderError::InconsistentFlags => "inconsistent flags on different packets",
+            DecoderError::DimensionsMismatch => {
+                "mismatched width or height on different systematic packets"
+            }
+            DecoderError::NoSystematic => "no systematic packets",
+            DecoderError::InvalidLength => "input buffer has an invalid length",
+        })
+    }
 }
 
+impl core::error::Error for DecoderError {}
+
 impl Decoder {
     /// Decodes a list of SSDV packets to obtain the original SSDV image.
     ///
@@ -305,34 +1263,186 @@ impl Decoder {
     ///
     /// The packets in `input` can be in any order and can have duplicates. The
     /// function works in-place in the `input` slice, modifying its contents.
-    pub fn decode<'a>(
-        input: &mut [SSDVPacket],
-        output: &'a mut [SSDVPacket],
-    ) -> Result<&'a mut [SSDVPacket], DecoderError> {
-        let mut decoder = DecoderHelper::new(input, output)?;
-        decoder.init_output();
-        decoder.copy_systematic();
-        if !decoder.all_systematic_obtained() {
-            decoder.values_to_lagrange();
-            decoder.interpolate_missing();
+    pub fn decode<'a, P: Packet>(
+        input: &mut [P],
+        output: &'a mut [P],
+    ) -> Result<&'a mut [P], DecoderError> {
+        Self::decode_with_options(input, output, &DecoderOptions::default())
+            .map(|(decoded, _)| decoded)
+    }
+
+    /// Decodes SSDV packets stored in a byte buffer.
+    ///
+    /// This works as [`Decoder::decode`], but the packets are given as the
+    /// bytes of the packets one after another, and the decoded image is written
+    /// to the beginning of the byte buffer `output`, returning the subslice
+    /// that contains the bytes of the image packets. The packet format is given
+    /// by the type parameter `P`, as in
+    /// `Decoder::decode_from::<SSDVPacket>(input, output)`.
+    ///
+    /// If the length of `input` is not a multiple of the packet length,
+    /// [`DecoderError::InvalidLength`] is returned. The bytes at the end of
+    /// `output` that do not fill a whole packet are not used.
+    pub fn decode_from<'a, P: Packet>(
+        input: &mut [u8],
+        output: &'a mut [u8],
+    ) -> Result<&'a mut [u8], DecoderError> {
+        let input = P::slice_from_bytes_mut(input).ok_or(DecoderError::InvalidLength)?;
+        let packets_len = output.len() / P::LEN * P::LEN;
+        let packets = output
+            .get_mut(..packets_len)
+            .and_then(P::slice_from_bytes_mut)
+            .ok_or(DecoderError::OutputTooShort)?;
+        let decoded_len = Self::decode(input, packets)?.len() * P::LEN;
+        output
+            .get_mut(..decoded_len)
+            .ok_or(DecoderError::OutputTooShort)
+    }
+
+    /// Decodes a list of SSDV packets using some decoder options.
+    ///
+    /// This function works as [`Decoder::decode`], but the behaviour of the
+    /// decoder can be configured with `options`. Together with the decoded
+    /// image, it returns the header inconsistencies that have been resolved,
+    /// which can only be non-empty if the decoder is lenient.
+    pub fn decode_with_options<'a, P: Packet>(
+        input: &mut [P],
+        output: &'a mut [P],
+        options: &DecoderOptions,
+    ) -> Result<(&'a mut [P], HeaderOverrides), DecoderError> {
+        Self::decode_with_stats(input, output, options)
+            .map(|(decoded, stats)| (decoded, stats.overrides))
+    }
+
+    /// Decodes a list of SSDV packets and reports statistics of the decoding.
+    ///
+    /// This function works as [`Decoder::decode_with_options`], but together
+    /// with the decoded image it returns [`DecodeStats`], which include the
+    /// header inconsistencies that have been resolved.
+    pub fn decode_with_stats<'a, P: Packet>(
+        input: &mut [P],
+        output: &'a mut [P],
+        options: &DecoderOptions,
+    ) -> Result<(&'a mut [P], DecodeStats), DecoderError> {
+        let stopwatch = Stopwatch::start(Operation::Decode);
+        let (mut decoder, stats) = DecoderHelper::start(input, output, options)?;
+        if stats.packets_recovered != 0 {
+            let mut meter = Meter::new(
+                Operation::Decode,
+                decoder.decode_steps(stats.packets_recovered),
+            );
+            decoder.values_to_lagrange(&mut meter);
+            decoder.interpolate_missing(&mut meter);
+        }
+        stopwatch.stop();
+        Ok((decoder.into_output(), stats))
+    }
+
+    /// Decodes a list of SSDV packets, yielding to the async executor
+    /// periodically.
+    ///
+    /// This function works as [`Decoder::decode_with_stats`], but it yields
+    /// each time that a bounded amount of work has been done during the
+    /// recovery of the missing systematic packets, which is where most of the
+    /// time is spent. The checks of the input packets that are done before are
+    /// not interrupted, but they do not perform GF(2¹⁶) arithmetic.
+    #[cfg(feature = "async-embedded")]
+    pub async fn decode_with_stats_async<'a, P: Packet>(
+        input: &mut [P],
+        output: &'a mut [P],
+        options: &DecoderOptions,
+    ) -> Result<(&'a mut [P], DecodeStats), DecoderError> {
+        let (mut decoder, stats) = DecoderHelper::start(input, output, options)?;
+        if stats.packets_recovered != 0 {
+            let k = usize::from(decoder.num_systematic);
+            let mut yielder = Yielder::new(
+                Operation::Decode,
+                decoder.decode_steps(stats.packets_recovered),
+            );
+            for j in 0..k {
+                decoder.value_to_lagrange(j);
+                yielder.step(k + P::DATA_LEN / 2).await;
+            }
+            for j in 0..k {
+                if decoder.is_missing(j) {
+                    let lx = decoder.lx(j);
+                    yielder.work(k).await;
+                    for r in 0..P::DATA_LEN / 2 {
+                        decoder.interpolate_word(j, lx, r);
+                        yielder.step(k).await;
+                    }
+                    decoder.finish_packet(j);
+                }
+            }
+        }
+        Ok((decoder.into_output(), stats))
+    }
+}
+
+// Returns the value that appears in more than half of the items of an
+// iterator, if there is such a value.
+//
+// This uses the Boyer-Moore majority vote algorithm, which runs in linear time
+// and constant memory.
+fn majority<T: Copy + Eq, I: Iterator<Item = T> + Clone>(values: I) -> Option<T> {
+    let mut candidate = None;
+    let mut count = 0usize;
+    for value in values.clone() {
+        if count == 0 {
+            candidate = Some(value);
+            count = 1;
+        } else if candidate == Some(value) {
+            count += 1;
+        } else {
+            count -= 1;
         }
-        Ok(&mut decoder.output[..usize::from(decoder.num_systematic)])
     }
+    let candidate = candidate?;
+    let (total, votes) = values.fold((0usize, 0usize), |(total, votes), value| {
+        (total + 1, votes + usize::from(value == candidate))
+    });
+    (2 * votes > total).then_some(candidate)
 }
 
-impl<'a, 'b> DecoderHelper<'a, 'b> {
+// Keeps only the packets for which f returns true, preserving their order,
+// and returns the number of packets removed.
+fn retain_packets<P: Packet, F: FnMut(&P) -> bool>(input: &mut &mut [P], mut f: F) -> usize {
+    let mut len = 0;
+    for j in 0..input.len() {
+        let packet = input[j];
+        if f(&packet) {
+            // len <= j, so the slot always exists
+            if let Some(slot) = input.get_mut(len) {
+                *slot = packet;
+            }
+            len += 1;
+        }
+    }
+    let removed = input.len() - len;
+    let slice = core::mem::take(input);
+    *input = slice.get_mut(..len).unwrap_or_default();
+    removed
+}
+
+impl<'a, 'b, P: Packet> DecoderHelper<'a, 'b, P> {
     fn new(
-        input: &'a mut [SSDVPacket],
-        output: &'b mut [SSDVPacket],
+        input: &'a mut [P],
+        output: &'b mut [P],
+        options: &DecoderOptions,
+        overrides: &mut HeaderOverrides,
     ) -> Result<Self, DecoderError> {
-        let input = Self::remove_duplicates_and_wrong_crcs(input);
+        let mut input = Self::remove_wrong_crcs(input);
+        if options.lenient {
+            Self::resolve_inconsistencies(&mut input, overrides);
+        }
+        let input = Self::remove_duplicates(input);
         let num_systematic = Self::find_num_systematic(input)?;
         if input.len() < usize::from(num_systematic) {
             return Err(DecoderError::NotEnoughInput);
         }
-        if output.len() < usize::from(num_systematic) {
+        let Some(output) = output.get_mut(..usize::from(num_systematic)) else {
             return Err(DecoderError::OutputTooShort);
-        }
+        };
         Self::check_systematic_ids(input, num_systematic)?;
         let (image_id, flags) = Self::find_image_id_flags(input)?;
         let (image_width, image_height) = Self::find_image_dimensions(input)?;
@@ -347,33 +1457,123 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         })
     }
 
-    fn remove_duplicates_and_wrong_crcs(input: &mut [SSDVPacket]) -> &mut [SSDVPacket] {
-        let mut len = input.len();
-        let mut j = 0;
-        while j < len {
-            if input[j].crc32() != crc32(input[j].crc32_data().iter()) {
-                // remove wrong CRC
-                input.copy_within(j + 1..len, j);
-                len -= 1;
-                continue;
-            }
-            let id = input[j].packet_id();
-            let mut k = j + 1;
-            while k < len {
-                if input[k].packet_id() == id {
-                    // remove duplicate
-                    input.copy_within(k + 1..len, k);
-                    len -= 1;
-                } else {
-                    k += 1;
+    // Checks the input and copies the systematic packets to the output,
+    // returning the helper and the statistics of the decoding.
+    fn start(
+        input: &'a mut [P],
+        output: &'b mut [P],
+        options: &DecoderOptions,
+    ) -> Result<(Self, DecodeStats), DecoderError> {
+        let mut stats = DecodeStats::default();
+        let mut decoder = DecoderHelper::new(input, output, options, &mut stats.overrides)?;
+        decoder.init_output();
+        decoder.copy_systematic();
+        stats.packets_recovered = decoder.num_missing_systematic();
+        if stats.packets_recovered != 0 {
+            // the interpolation uses the first k packets of the input
+            stats.fec_packets_used = decoder
+                .interpolation_input()
+                .iter()
+                .filter(|packet| packet.is_fec_packet())
+                .count();
+        }
+        Ok((decoder, stats))
+    }
+
+    // Returns the decoded packets. The output has been truncated to the k
+    // packets of the image by DecoderHelper::new.
+    fn into_output(self) -> &'b mut [P] {
+        self.output
+    }
+
+    // Returns the first k packets of the input, which are used for the
+    // interpolation. DecoderHelper::new checks that there are at least k
+    // packets.
+    fn interpolation_input(&self) -> &[P] {
+        self.input
+            .get(..usize::from(self.num_systematic))
+            .unwrap_or_default()
+    }
+
+    // Keeps the first packet with each packet ID, preserving their order.
+    fn remove_duplicates(input: &mut [P]) -> &mut [P] {
+        let mut len = 0;
+        for j in 0..input.len() {
+            let packet = input[j];
+            let kept = input.get(..len).unwrap_or_default();
+            if kept.iter().all(|p| p.packet_id() != packet.packet_id()) {
+                // len <= j, so the slot always exists
+                if let Some(slot) = input.get_mut(len) {
+                    *slot = packet;
                 }
+                len += 1;
             }
-            j += 1;
         }
-        &mut input[..len]
+        input.get_mut(..len).unwrap_or_default()
+    }
+
+    fn remove_wrong_crcs(mut input: &mut [P]) -> &mut [P] {
+        retain_packets(&mut input, |packet| {
+            packet.crc32() == packet.compute_crc32()
+        });
+        input
     }
 
-    fn find_num_systematic(input: &[SSDVPacket]) -> Result<u16, DecoderError> {
+    // Discards the packets that disagree with the majority of the packets in
+    // some header field. This is done before removing duplicates, so that the
+    // packets discarded do not replace packets with the same packet ID.
+    fn resolve_inconsistencies(input: &mut &mut [P], overrides: &mut HeaderOverrides) {
+        fn clean_flags(flags: u8) -> u8 {
+            // remove EOI and FEC packet flags
+            flags & !0x44
+        }
+
+        if let Some(image_id) = majority(input.iter().map(|packet| packet.image_id())) {
+            overrides.image_id = retain_packets(input, |packet| packet.image_id() == image_id);
+        }
+        if let Some(flags) = majority(input.iter().map(|packet| clean_flags(packet.flags()))) {
+            overrides.flags = retain_packets(input, |packet| clean_flags(packet.flags()) == flags);
+        }
+        for packet in input.iter_mut() {
+            if packet.is_fec_packet() && packet.is_eoi() {
+                // the CRC has already been checked, so it is fine to modify
+                // the packet
+                packet.set_eoi(false);
+                overrides.eoi_on_fec += 1;
+            }
+        }
+        // Each FEC packet votes for the value of its number of systematic
+        // packets field, and each packet with the EOI flag votes for its packet
+        // ID plus one.
+        let votes = input.iter().filter_map(|packet| {
+            packet
+                .number_systematic_packets()
+                .or_else(|| packet.is_eoi().then(|| packet.packet_id().wrapping_add(1)))
+        });
+        if let Some(k) = majority(votes) {
+            overrides.num_systematic =
+                retain_packets(input, |packet| match packet.number_systematic_packets() {
+                    Some(n) => n == k,
+                    None => {
+                        packet.packet_id() < k
+                            && (!packet.is_eoi()
+                                || u32::from(packet.packet_id()) + 1 == u32::from(k))
+                    }
+                });
+        }
+        let dimensions = input
+            .iter()
+            .filter_map(|packet| Some((packet.width()?, packet.height()?)));
+        if let Some(dimensions) = majority(dimensions) {
+            overrides.dimensions =
+                retain_packets(input, |packet| match (packet.width(), packet.height()) {
+                    (Some(width), Some(height)) => (width, height) == dimensions,
+                    _ => true,
+                });
+        }
+    }
+
+    fn find_num_systematic(input: &[P]) -> Result<u16, DecoderError> {
         let mut id_eoi = None;
         let mut from_fec_packets = None;
         for packet in input {
@@ -398,10 +1598,11 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         }
         match (id_eoi, from_fec_packets) {
             (None, None) => Err(DecoderError::UnknownNumSystematic),
-            (Some(k), None) => Ok(k + 1),
+            // k is at most 0xffff, so an EOI on packet ID 0xffff is invalid
+            (Some(k), None) => k.checked_add(1).ok_or(DecoderError::WrongSystematicId),
             (None, Some(k)) => Ok(k),
             (Some(k), Some(k2)) => {
-                if k + 1 == k2 {
+                if k.checked_add(1) == Some(k2) {
                     Ok(k2)
                 } else {
                     Err(DecoderError::EoiFecMismatch)
@@ -410,7 +1611,7 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         }
     }
 
-    fn check_systematic_ids(input: &[SSDVPacket], num_systematic: u16) -> Result<(), DecoderError> {
+    fn check_systematic_ids(input: &[P], num_systematic: u16) -> Result<(), DecoderError> {
         for packet in input {
             if !packet.is_fec_packet() && packet.packet_id() >= num_systematic {
                 return Err(DecoderError::WrongSystematicId);
@@ -419,15 +1620,18 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         Ok(())
     }
 
-    fn find_image_id_flags(input: &[SSDVPacket]) -> Result<(u8, u8), DecoderError> {
-        let image_id = input[0].image_id();
+    fn find_image_id_flags(input: &[P]) -> Result<(u8, u8), DecoderError> {
+        let Some(first) = input.first() else {
+            return Err(DecoderError::NotEnoughInput);
+        };
+        let image_id = first.image_id();
 
         fn clean_flags(flags: u8) -> u8 {
             // remove EOI and FEC packet flags
             flags & !0x44
         }
 
-        let flags = clean_flags(input[0].flags());
+        let flags = clean_flags(first.flags());
 
         for packet in input {
             if packet.image_id() != image_id {
@@ -440,12 +1644,11 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         Ok((image_id, flags))
     }
 
-    fn find_image_dimensions(input: &[SSDVPacket]) -> Result<(u8, u8), DecoderError> {
+    fn find_image_dimensions(input: &[P]) -> Result<(u8, u8), DecoderError> {
         let mut dimensions = None;
         for packet in input {
-            if let Some(width) = packet.width() {
-                // if width is present, then height is also present
-                let height = packet.height().unwrap();
+            // width and height are either both present or both absent
+            if let (Some(width), Some(height)) = (packet.width(), packet.height()) {
                 if let Some((w, h)) = dimensions {
                     if w != width || h != height {
                         return Err(DecoderError::DimensionsMismatch);
@@ -471,15 +1674,19 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         for packet in self.input.iter() {
             if !packet.is_fec_packet() {
                 let id = packet.packet_id();
-                self.output[usize::from(id)].clone_from(packet);
+                // check_systematic_ids has checked that id < k
+                if let Some(slot) = self.output.get_mut(usize::from(id)) {
+                    slot.clone_from(packet);
+                }
             }
         }
     }
 
-    fn all_systematic_obtained(&self) -> bool {
-        !self.output[..usize::from(self.num_systematic)]
+    fn num_missing_systematic(&self) -> usize {
+        self.output
             .iter()
-            .any(|&packet| packet.packet_id() == Self::INVALID_PACKET_ID)
+            .filter(|&packet| packet.packet_id() == Self::INVALID_PACKET_ID)
+            .count()
     }
 
     // Computes
@@ -488,12 +1695,10 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
     // This is different from Encoder::wj_inv because the packet_id's of the
     // first k packets in the input buffer are not sequential.
     fn wj_inv(&self, j: usize) -> GF64K {
-        let xj = GF64K::from(self.input[j].packet_id());
+        let input = self.interpolation_input();
+        let xj = GF64K::from(input.get(j).map_or(0, |p| p.packet_id()));
         let mut ret = GF64K::from(1);
-        for (m, p) in self.input[0..usize::from(self.num_systematic)]
-            .iter()
-            .enumerate()
-        {
+        for (m, p) in input.iter().enumerate() {
             if m != j {
                 let xm = GF64K::from(p.packet_id());
                 ret *= xj - xm;
@@ -502,85 +1707,135 @@ impl<'a, 'b> DecoderHelper<'a, 'b> {
         ret
     }
 
-    fn values_to_lagrange(&mut self) {
-        // See Encoder::values_to_lagrange
-        for j in 0..usize::from(self.num_systematic) {
-            let wj = GF64K::from(1) / self.wj_inv(j);
-            let data = self.input[j].data_as_mut();
-            for word in data.chunks_exact_mut(2) {
-                let word: &mut [u8; 2] = word.try_into().unwrap();
-                let yj = GF64K::from(u16::from_be_bytes(*word));
-                let yj_wj = yj * wj;
-                *word = u16::from(yj_wj).to_be_bytes();
-            }
+    // Returns the number of steps reported in the progress of the decoding.
+    fn decode_steps(&self, missing: usize) -> u32 {
+        // there are at most k missing packets
+        bounds::decode_steps::<P>(self.num_systematic, missing as u16)
+    }
+
+    fn values_to_lagrange(&mut self, meter: &mut Meter) {
+        let k = usize::from(self.num_systematic);
+        for j in 0..k {
+            self.value_to_lagrange(j);
+            meter.step(k + P::DATA_LEN / 2);
         }
     }
 
-    fn interpolate_missing(&mut self) {
-        // See Encoder::encode_fec_data
+    fn value_to_lagrange(&mut self, j: usize) {
+        // See Encoder::value_to_lagrange
+        let wj = GF64K::from(1).div_nonzero(self.wj_inv(j));
+        if let Some(packet) = self.input.get_mut(j) {
+            scale_data(packet.data_as_mut(), wj);
+        }
+    }
+
+    fn is_missing(&self, j: usize) -> bool {
+        self.output
+            .get(j)
+            .is_some_and(|packet| packet.packet_id() == Self::INVALID_PACKET_ID)
+    }
+
+    fn interpolate_missing(&mut self, meter: &mut Meter) {
+        // See Encoder::encode_fec_data. Only scalar temporaries are used, so
+        // the stack usage does not depend on k or on the packet length.
         let k = usize::from(self.num_systematic);
-        for (j, packet) in self.output[..k]
-            .iter_mut()
-            .enumerate()
-            .filter(|(_, packet)| packet.packet_id() == Self::INVALID_PACKET_ID)
-        {
-            // Compute l(x)
-            let x = GF64K::from(j as u16);
-            let mut lx = GF64K::from(1);
-            for p in &self.input[..k] {
-                let xj = GF64K::from(p.packet_id());
-                lx *= x - xj;
-            }
-
-            // Compute \sum_{j=0}^{k-1} w_j y_j / (x - x_j) for each word in the
-            // output data
-            let data = packet.data_as_mut();
-            for (r, word) in data.chunks_exact_mut(2).enumerate() {
-                let mut sum = GF64K::from(0);
-                for p in &self.input[..k] {
-                    let wj_yj_s = p.data();
-                    let wj_yj = GF64K::from(u16::from_be_bytes(
-                        wj_yj_s[2 * r..2 * r + 2].try_into().unwrap(),
-                    ));
-                    let xj = GF64K::from(p.packet_id());
-                    sum += wj_yj / (x - xj);
+        for j in 0..k {
+            if self.is_missing(j) {
+                let lx = self.lx(j);
+                meter.work(k);
+                for r in 0..P::DATA_LEN / 2 {
+                    self.interpolate_word(j, lx, r);
+                    meter.step(k);
                 }
-                let word: &mut [u8; 2] = word.try_into().unwrap();
-                let result = lx * sum;
-                *word = u16::from(result).to_be_bytes();
+                self.finish_packet(j);
             }
+        }
+    }
 
-            // Fill header
-            packet.set_image_id(self.image_id);
-            packet.set_packet_id(j as u16);
-            packet.set_width(self.image_width);
-            packet.set_height(self.image_height);
-            packet.set_flags(self.flags);
-            packet.set_eoi(j == k - 1);
-            packet.set_fec_packet(false);
+    // Computes l(x) for x = j.
+    fn lx(&self, j: usize) -> GF64K {
+        let x = GF64K::from(j as u16);
+        let mut lx = GF64K::from(1);
+        for p in self.interpolation_input() {
+            let xj = GF64K::from(p.packet_id());
+            lx *= x - xj;
+        }
+        lx
+    }
 
-            // Fill CRC32
-            packet.set_crc32(crc32(packet.crc32_data().iter()));
+    // Computes l(x) \sum_{j=0}^{k-1} w_j y_j / (x - x_j) for the word r of the
+    // data of the missing packet j.
+    fn interpolate_word(&mut self, j: usize, lx: GF64K, r: usize) {
+        let x = GF64K::from(j as u16);
+        let mut sum = GF64K::from(0);
+        for p in self.interpolation_input() {
+            let wj_yj = p.data().as_chunks::<2>().0.get(r).copied();
+            let wj_yj = GF64K::from(u16::from_be_bytes(wj_yj.unwrap_or_default()));
+            let xj = GF64K::from(p.packet_id());
+            sum += wj_yj.div_nonzero(x - xj);
+        }
+        let word = self
+            .output
+            .get_mut(j)
+            .and_then(|packet| packet.data_as_mut().as_chunks_mut::<2>().0.get_mut(r));
+        if let Some(word) = word {
+            *word = u16::from(lx * sum).to_be_bytes();
+        }
+    }
+
+    // Fills the header and CRC-32 of the missing packet j, once its data has
+    // been interpolated.
+    fn finish_packet(&mut self, j: usize) {
+        let k = usize::from(self.num_systematic);
+        let Some(packet) = self.output.get_mut(j) else {
+            return;
+        };
+        // Fill header
+        if let Some(first) = self.input.first() {
+            copy_prefix(
+                packet.as_bytes_mut(),
+                first.as_bytes().get(..P::HEADER_OFFSET).unwrap_or_default(),
+            );
         }
+        packet.set_image_id(self.image_id);
+        packet.set_packet_id(j as u16);
+        packet.set_width(self.image_width);
+        packet.set_height(self.image_height);
+        packet.set_flags(self.flags);
+        packet.set_eoi(j == k - 1);
+        packet.set_fec_packet(false);
+
+        // Fill CRC32
+        packet.set_crc32(packet.compute_crc32());
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{ssdv::SSDV_PACKET_LEN, test_data::IMG_230_SSDV};
+    use crate::{
+        crc::crc32,
+        ssdv::SSDV_PACKET_LEN,
+        test_data::{img_230_no_fec_packets, img_230_packets, IMG_230_SSDV},
+        SSDVNoFecPacket,
+    };
 
     #[test]
     fn encode_img_230_systematic() {
-        let mut ssdv = IMG_230_SSDV
-            .chunks_exact(SSDV_PACKET_LEN)
-            .map(|chunk| SSDVPacket(chunk.try_into().unwrap()))
-            .collect::<Vec<SSDVPacket>>();
+        let mut ssdv = img_230_packets();
+        let first = ssdv[0];
         let encoder = Encoder::new(&mut ssdv).unwrap();
+        assert_eq!(
+            usize::from(encoder.num_systematic()),
+            IMG_230_SSDV.len() / SSDV_PACKET_LEN
+        );
+        assert_eq!(encoder.image_id(), 230);
+        assert_eq!(Some(encoder.image_width()), first.width());
+        assert_eq!(Some(encoder.image_height()), first.height());
+        assert_eq!(encoder.flags(), first.flags());
 
         let mut encoded_packet = SSDVPacket::zeroed();
-        for (j, packet) in IMG_230_SSDV.chunks_exact(SSDV_PACKET_LEN).enumerate() {
-            let original_packet = SSDVPacket(packet.try_into().unwrap());
+        for (j, original_packet) in img_230_packets().into_iter().enumerate() {
             encoder.encode(u16::try_from(j).unwrap(), &mut encoded_packet);
             assert_eq!(&encoded_packet, &original_packet);
         }
@@ -588,10 +1843,7 @@ mod test {
 
     #[test]
     fn encode_decode_img_230_one_every_n() {
-        let ssdv = IMG_230_SSDV
-            .chunks_exact(SSDV_PACKET_LEN)
-            .map(|chunk| SSDVPacket(chunk.try_into().unwrap()))
-            .collect::<Vec<SSDVPacket>>();
+        let ssdv = img_230_packets();
         let k = ssdv.len();
         // Do a copy to keep ssdv as a reference (since the encoder destroys the input)
         let mut ssdv_copy = ssdv.clone();
@@ -618,4 +1870,412 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn lenient_decode_img_230() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut input = (0..2 * k)
+            .step_by(2)
+            .map(|j| {
+                let mut packet = SSDVPacket::zeroed();
+                encoder.encode(u16::try_from(j).unwrap(), &mut packet);
+                packet
+            })
+            .collect::<Vec<SSDVPacket>>();
+        let extra_packet = |packet_id: usize| {
+            let mut packet = SSDVPacket::zeroed();
+            encoder.encode(u16::try_from(packet_id).unwrap(), &mut packet);
+            packet
+        };
+        // FEC packets from another image with a valid CRC
+        for j in 0..5 {
+            let mut packet = extra_packet(2 * k + j);
+            packet.set_image_id(packet.image_id().wrapping_add(1));
+            packet.set_crc32(crc32(packet.crc32_data().iter()));
+            input.push(packet);
+        }
+        // FEC packet with a wrong number of systematic packets
+        let mut packet = extra_packet(3 * k);
+        packet.set_number_systematic_packets(u16::try_from(k).unwrap() + 1);
+        packet.set_crc32(crc32(packet.crc32_data().iter()));
+        input.push(packet);
+
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        assert_eq!(
+            Decoder::decode(&mut input.clone(), &mut output).unwrap_err(),
+            DecoderError::NumSystematicMismatch
+        );
+        let options = DecoderOptions::new().lenient(true);
+        let (decoded, overrides) =
+            Decoder::decode_with_options(&mut input, &mut output, &options).unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+        assert_eq!(
+            overrides,
+            HeaderOverrides {
+                image_id: 5,
+                num_systematic: 1,
+                ..HeaderOverrides::default()
+            }
+        );
+    }
+
+    #[test]
+    fn majority_vote() {
+        assert_eq!(majority([1, 2, 1, 3, 1].into_iter()), Some(1));
+        assert_eq!(majority([1, 2, 1, 2].into_iter()), None);
+        assert_eq!(majority(core::iter::empty::<u8>()), None);
+    }
+
+    #[test]
+    fn encode_decode_no_fec_format() {
+        let ssdv = img_230_no_fec_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        assert_eq!(usize::from(encoder.num_systematic()), k);
+        assert_eq!(encoder.image_id(), 230);
+
+        // decode only from FEC packets and one systematic packet
+        let mut encoded_packets = (k..2 * k)
+            .map(|j| {
+                let mut encoded_packet = SSDVNoFecPacket::zeroed();
+                encoder.encode(u16::try_from(j).unwrap(), &mut encoded_packet);
+                assert_eq!(&encoded_packet.0[..6], &ssdv[0].0[..6]);
+                encoded_packet
+            })
+            .collect::<Vec<SSDVNoFecPacket>>();
+        encoded_packets[0] = ssdv[k - 1];
+        let mut output = vec![SSDVNoFecPacket::zeroed(); k];
+        let decoded = Decoder::decode(&mut encoded_packets[..], &mut output[..]).unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+    }
+
+    #[test]
+    fn decode_with_stats() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut output = vec![SSDVPacket::zeroed(); k];
+
+        // all the systematic packets
+        let mut input = ssdv.clone();
+        let (_, stats) =
+            Decoder::decode_with_stats(&mut input, &mut output, &DecoderOptions::default())
+                .unwrap();
+        assert_eq!(stats, DecodeStats::default());
+
+        // 10 systematic packets missing, replaced by FEC packets
+        let mut input = ssdv.clone();
+        for (j, packet) in input[..10].iter_mut().enumerate() {
+            encoder.encode(u16::try_from(k + j).unwrap(), packet);
+        }
+        let (decoded, stats) =
+            Decoder::decode_with_stats(&mut input, &mut output, &DecoderOptions::default())
+                .unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+        assert_eq!(stats.packets_recovered, 10);
+        assert_eq!(stats.fec_packets_used, 10);
+        assert!(stats.overrides.is_empty());
+    }
+
+    #[test]
+    fn encode_range_img_230() {
+        let mut ssdv = img_230_packets();
+        let k = ssdv.len();
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        let mut range = vec![SSDVPacket::zeroed(); 2 * k];
+        encoder.try_encode_range(10, &mut range).unwrap();
+        for (j, packet) in range.iter().enumerate() {
+            let mut expected = SSDVPacket::zeroed();
+            encoder.encode(u16::try_from(10 + j).unwrap(), &mut expected);
+            assert_eq!(packet, &expected);
+        }
+        encoder.try_encode_range(u16::MAX, &mut range[..1]).unwrap();
+        let before = range.clone();
+        assert_eq!(
+            encoder.try_encode_range(u16::MAX, &mut range[..2]),
+            Err(EncoderError::InvalidRange)
+        );
+        assert_eq!(range, before);
+    }
+
+    #[test]
+    #[should_panic(expected = "packet IDs out of range")]
+    fn encode_range_out_of_range() {
+        let mut ssdv = img_230_packets();
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        encoder.encode_range(u16::MAX, &mut [SSDVPacket::zeroed(); 2]);
+    }
+
+    // Checks that a packet generated in chunks of each size is the same as the
+    // packet generated by Encoder::encode.
+    fn check_chunks<P: Packet>(encoder: &Encoder<P>, packet_id: u16) {
+        let mut expected = P::zeroed();
+        encoder.encode(packet_id, &mut expected);
+        for chunk_len in [1, 3, 32, 63, 64, 100, P::LEN, P::LEN + 10] {
+            assert_eq!(
+                collect_chunks(encoder.encode_chunks(packet_id), chunk_len),
+                expected.as_bytes(),
+                "chunk length {chunk_len}"
+            );
+        }
+    }
+
+    fn collect_chunks<P: Packet>(mut chunks: PacketChunks<P>, chunk_len: usize) -> Vec<u8> {
+        let mut packet = Vec::new();
+        let mut chunk = vec![0; chunk_len];
+        loop {
+            let n = chunks.next_chunk(&mut chunk);
+            if n == 0 {
+                break;
+            }
+            packet.extend_from_slice(&chunk[..n]);
+        }
+        assert!(chunks.is_finished());
+        packet
+    }
+
+    #[test]
+    fn encode_chunks() {
+        let mut ssdv = img_230_packets();
+        let k = u16::try_from(ssdv.len()).unwrap();
+        let mut no_fec = img_230_no_fec_packets();
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        for packet_id in [0, k - 1, k, 2 * k + 7] {
+            check_chunks(&encoder, packet_id);
+        }
+        let encoder = Encoder::new(&mut no_fec).unwrap();
+        for packet_id in [3, k + 1] {
+            check_chunks(&encoder, packet_id);
+        }
+    }
+
+    #[test]
+    fn encode_chunks_with_scratch() {
+        let mut ssdv = img_230_packets();
+        let k = u16::try_from(ssdv.len()).unwrap();
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        // the same scratch is used for all the packets
+        let mut scratch = ChunkScratch::new();
+        for packet_id in [1, k, k + 1] {
+            let mut expected = SSDVPacket::zeroed();
+            encoder.encode(packet_id, &mut expected);
+            let mut chunks = encoder.encode_chunks(packet_id);
+            let mut packet = Vec::new();
+            let mut chunk = [0; 37];
+            loop {
+                let n = chunks.next_chunk_with_scratch(&mut chunk, &mut scratch);
+                if n == 0 {
+                    break;
+                }
+                packet.extend_from_slice(&chunk[..n]);
+            }
+            assert_eq!(packet, expected.as_bytes());
+        }
+    }
+
+    // Checks that a packet generated word by word is the same as the packet
+    // generated by Encoder::encode.
+    fn check_words<P: Packet>(encoder: &Encoder<P>, packet_id: u16) {
+        let mut expected = P::zeroed();
+        encoder.encode(packet_id, &mut expected);
+        let mut words = encoder.encode_words(packet_id);
+        let mut packet = Vec::new();
+        while let Some(word) = words.next_word() {
+            packet.extend_from_slice(&word);
+        }
+        assert!(words.is_finished());
+        assert_eq!(words.next_byte(), None);
+        assert_eq!(packet, expected.as_bytes());
+    }
+
+    #[test]
+    fn encode_words() {
+        let mut ssdv = img_230_packets();
+        let k = u16::try_from(ssdv.len()).unwrap();
+        let mut no_fec = img_230_no_fec_packets();
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        for packet_id in [0, k - 1, k, 2 * k + 7] {
+            check_words(&encoder, packet_id);
+        }
+        // bytes and words can be mixed, which misaligns the words of the
+        // packet with the words of the data field
+        let mut expected = SSDVPacket::zeroed();
+        encoder.encode(k + 5, &mut expected);
+        let mut words = encoder.encode_words(k + 5);
+        let mut packet = vec![words.next_byte().unwrap()];
+        while let Some(word) = words.next_word() {
+            packet.extend_from_slice(&word);
+        }
+        packet.push(words.next_byte().unwrap());
+        assert_eq!(packet, expected.as_bytes());
+        let encoder = Encoder::new(&mut no_fec).unwrap();
+        for packet_id in [3, k + 1] {
+            check_words(&encoder, packet_id);
+        }
+    }
+
+    #[test]
+    fn encode_decode_bytes() {
+        let mut encoder_buffer = IMG_230_SSDV.to_vec();
+        let k = encoder_buffer.len() / SSDV_PACKET_LEN;
+        assert_eq!(
+            Encoder::<SSDVPacket>::from_bytes(&mut encoder_buffer[1..]).unwrap_err(),
+            EncoderError::InvalidLength
+        );
+        let encoder = Encoder::<SSDVPacket>::from_bytes(&mut encoder_buffer).unwrap();
+        // the image is decoded from the last systematic packet and FEC
+        // packets
+        let mut input = vec![0; k * SSDV_PACKET_LEN];
+        for (j, packet) in input.chunks_exact_mut(SSDV_PACKET_LEN).enumerate() {
+            let packet_id = u16::try_from(if j == 0 { k - 1 } else { k + j }).unwrap();
+            assert_eq!(encoder.encode_into(packet_id, packet), Ok(SSDV_PACKET_LEN));
+        }
+        let mut short = [0; SSDV_PACKET_LEN - 1];
+        assert_eq!(
+            encoder.encode_into(0, &mut short),
+            Err(EncoderError::InvalidLength)
+        );
+        assert_eq!(
+            Decoder::decode_from::<SSDVPacket>(&mut input.clone()[1..], &mut []),
+            Err(DecoderError::InvalidLength)
+        );
+        // the output has room for one more packet and a few bytes
+        let mut output = vec![0; (k + 1) * SSDV_PACKET_LEN + 5];
+        let decoded = Decoder::decode_from::<SSDVPacket>(&mut input, &mut output).unwrap();
+        assert_eq!(decoded, IMG_230_SSDV);
+    }
+
+    #[test]
+    fn read_only_encoder() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut weights = vec![0; k];
+        assert_eq!(
+            ReadOnlyEncoder::new(&ssdv, &mut weights[..k - 1]).unwrap_err(),
+            EncoderError::WeightsTooShort
+        );
+        let read_only = ReadOnlyEncoder::new(&ssdv, &mut weights).unwrap();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        assert_eq!(read_only.num_systematic(), encoder.num_systematic());
+        assert_eq!(read_only.image_id(), 230);
+        let mut expected = vec![SSDVPacket::zeroed(); 2 * k];
+        let mut packets = vec![SSDVPacket::zeroed(); 2 * k];
+        encoder.try_encode_range(0, &mut expected).unwrap();
+        read_only.try_encode_range(0, &mut packets).unwrap();
+        assert_eq!(packets, expected);
+        assert_eq!(
+            collect_chunks(read_only.encode_chunks(u16::try_from(k).unwrap()), 40),
+            expected[k].as_bytes()
+        );
+    }
+
+    #[test]
+    fn release_img_230() {
+        let ssdv = img_230_packets();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        assert_ne!(encoder.release(false), &ssdv[..]);
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        assert_eq!(encoder.release(true), &ssdv[..]);
+    }
+
+    // Polls a future to completion, returning its output and the number of
+    // times that it has yielded.
+    #[cfg(feature = "async-embedded")]
+    fn poll_counting_yields<F: core::future::Future>(future: F) -> (F::Output, usize) {
+        use core::task::{Context, Poll};
+        let mut future = core::pin::pin!(future);
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut yields = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, yields),
+                Poll::Pending => yields += 1,
+            }
+        }
+    }
+
+    #[cfg(feature = "async-embedded")]
+    #[test]
+    fn encode_decode_async() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut expected = vec![SSDVPacket::zeroed(); 2 * k];
+        encoder.try_encode_range(0, &mut expected).unwrap();
+
+        let mut ssdv_copy = ssdv.clone();
+        let (encoder, yields) = poll_counting_yields(Encoder::new_async(&mut ssdv_copy));
+        let encoder = encoder.unwrap();
+        assert!(yields > 0);
+        let mut weights = vec![0; k];
+        let (read_only, _) = poll_counting_yields(ReadOnlyEncoder::new_async(&ssdv, &mut weights));
+        let read_only = read_only.unwrap();
+        let mut packets = vec![SSDVPacket::zeroed(); 2 * k];
+        let mut read_only_packets = vec![SSDVPacket::zeroed(); 2 * k];
+        for (packet_id, (packet, read_only_packet)) in
+            (0..).zip(packets.iter_mut().zip(read_only_packets.iter_mut()))
+        {
+            let ((), yields) = poll_counting_yields(encoder.encode_async(packet_id, packet));
+            if packet_id >= encoder.num_systematic() {
+                assert!(yields > 0);
+            }
+            poll_counting_yields(read_only.encode_async(packet_id, read_only_packet));
+        }
+        assert_eq!(packets, expected);
+        assert_eq!(read_only_packets, expected);
+
+        // the first half of the systematic packets is replaced by FEC packets
+        let half = k / 2;
+        let mut input = packets[half..half + k].to_vec();
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        let (decoded, yields) = poll_counting_yields(Decoder::decode_with_stats_async(
+            &mut input,
+            &mut output,
+            &DecoderOptions::default(),
+        ));
+        let (decoded, stats) = decoded.unwrap();
+        assert!(yields > 0);
+        assert_eq!(decoded, &ssdv[..]);
+        assert_eq!(stats.packets_recovered, half);
+        assert_eq!(stats.fec_packets_used, half);
+    }
+
+    #[test]
+    fn error_messages() {
+        use core::fmt::Write;
+
+        // Display does not need std, so this formats into a fixed buffer as
+        // no_std firmware would.
+        struct Buffer([u8; 64], usize);
+
+        impl Write for Buffer {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                let end = self.1 + s.len();
+                self.0
+                    .get_mut(self.1..end)
+                    .ok_or(core::fmt::Error)?
+                    .copy_from_slice(s.as_bytes());
+                self.1 = end;
+                Ok(())
+            }
+        }
+
+        let mut buffer = Buffer([0; 64], 0);
+        write!(buffer, "decoding failed: {}", DecoderError::NotEnoughInput).unwrap();
+        assert_eq!(
+            &buffer.0[..buffer.1],
+            b"decoding failed: not enough input packets"
+        );
+        let err: &dyn core::error::Error = &EncoderError::EmptyInput;
+        assert_eq!(err.to_string(), "encoder input is empty");
+    }
 }
@@ -0,0 +1,293 @@
+//! Packet formats chosen at runtime.
+//!
+//! The encoder and decoder select the packet format with the type parameter
+//! `P`. Applications and language bindings that choose the format at runtime
+//! and work with the bytes of the packets can use [`Format`] and
+//! [`AnyEncoder`] instead, which dispatch to the corresponding packet type.
+//!
+//! This module is only available with the `alloc` feature.
+
+use crate::{
+    Decoder, DecoderError, DecoderOptions, EncoderError, OwnedEncoder, Packet, SSDVNoFecPacket,
+    SSDVPacket,
+};
+use alloc::vec::Vec;
+
+/// SSDV packet format.
+///
+/// With the `cli` feature, this implements `clap::ValueEnum`, so that the CLI
+/// application accepts the formats as `lj2` and `nofec`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Format {
+    /// 218-byte format used by Longjiang-2, which omits the sync byte, packet
+    /// type and callsign fields. This corresponds to [`SSDVPacket`].
+    #[cfg_attr(feature = "cli", value(help = "218-byte format used by Longjiang-2"))]
+    Lj2,
+    /// Standard 256-byte no-FEC SSDV format. This corresponds to
+    /// [`SSDVNoFecPacket`].
+    #[cfg_attr(
+        feature = "cli",
+        value(name = "nofec", help = "Standard 256-byte no-FEC SSDV format")
+    )]
+    NoFec,
+}
+
+// Evaluates an expression that uses the packet type `$packet` corresponding to
+// a format.
+macro_rules! with_format {
+    ($format:expr, $packet:ident => $body:expr) => {
+        match $format {
+            $crate::format::Format::Lj2 => {
+                type $packet = $crate::SSDVPacket;
+                $body
+            }
+            $crate::format::Format::NoFec => {
+                type $packet = $crate::SSDVNoFecPacket;
+                $body
+            }
+        }
+    };
+}
+#[cfg(feature = "cli")]
+pub(crate) use with_format;
+
+/// Header fields of an SSDV packet.
+///
+/// This is returned by [`Format::packet_info`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PacketInfo {
+    /// Image ID.
+    pub image_id: u8,
+    /// Packet ID.
+    pub packet_id: u16,
+    /// Image width in units of 16 pixels, or `None` in FEC packets.
+    pub width: Option<u8>,
+    /// Image height in units of 16 pixels, or `None` in FEC packets.
+    pub height: Option<u8>,
+    /// Number of systematic packets of the image, or `None` in systematic
+    /// packets.
+    pub num_systematic: Option<u16>,
+    /// Flags field.
+    pub flags: u8,
+    /// Whether the EOI flag is set.
+    pub eoi: bool,
+    /// Whether the packet is a FEC packet.
+    pub fec_packet: bool,
+    /// Whether the CRC-32 of the packet is correct.
+    pub crc_ok: bool,
+}
+
+impl Format {
+    /// Returns the length in bytes of a packet in this format.
+    pub fn packet_len(self) -> usize {
+        with_format!(self, P => P::LEN)
+    }
+
+    /// Returns the header fields of a single packet given as bytes.
+    ///
+    /// If the length of `packet` is not the packet length, `None` is returned.
+    pub fn packet_info(self, packet: &[u8]) -> Option<PacketInfo> {
+        with_format!(self, P => {
+            let [packet] = P::slice_from_bytes(packet)? else {
+                return None;
+            };
+            Some(PacketInfo {
+                image_id: packet.image_id(),
+                packet_id: packet.packet_id(),
+                width: packet.width(),
+                height: packet.height(),
+                num_systematic: packet.number_systematic_packets(),
+                flags: packet.flags(),
+                eoi: packet.is_eoi(),
+                fec_packet: packet.is_fec_packet(),
+                crc_ok: packet.compute_crc32() == packet.crc32(),
+            })
+        })
+    }
+
+    /// Decodes an SSDV image from the bytes of the received packets.
+    ///
+    /// This works as [`Decoder::decode_with_options`], but the packets are
+    /// given one after another in `packets`, which is not modified, and the
+    /// systematic packets of the image are returned in the same way. If the
+    /// length of `packets` is not a multiple of the packet length,
+    /// [`DecoderError::InvalidLength`] is returned.
+    pub fn decode(self, packets: &[u8], options: &DecoderOptions) -> Result<Vec<u8>, DecoderError> {
+        with_format!(self, P => {
+            let mut input = P::slice_from_bytes(packets)
+                .ok_or(DecoderError::InvalidLength)?
+                .to_vec();
+            let mut output = alloc::vec![P::zeroed(); input.len()];
+            let (decoded, _) = Decoder::decode_with_options(&mut input, &mut output, options)?;
+            Ok(to_bytes(decoded))
+        })
+    }
+}
+
+/// SSDV FEC encoder for a packet format chosen at runtime.
+///
+/// This wraps an [`OwnedEncoder`] for the packet type of a [`Format`], and
+/// takes and returns the packets as bytes.
+#[derive(Debug)]
+pub enum AnyEncoder {
+    /// Encoder for [`Format::Lj2`].
+    Lj2(OwnedEncoder<SSDVPacket>),
+    /// Encoder for [`Format::NoFec`].
+    NoFec(OwnedEncoder<SSDVNoFecPacket>),
+}
+
+// Evaluates an expression that uses the encoder `$encoder` wrapped by an
+// AnyEncoder.
+macro_rules! with_encoder {
+    ($any:expr, $encoder:ident => $body:expr) => {
+        match $any {
+            AnyEncoder::Lj2($encoder) => $body,
+            AnyEncoder::NoFec($encoder) => $body,
+        }
+    };
+}
+
+impl AnyEncoder {
+    /// Creates a new FEC encoder for an SSDV image given as bytes.
+    ///
+    /// The systematic packets of the image are given one after another in
+    /// `systematic_packets`, in the packet format `format`. This works as
+    /// [`OwnedEncoder::from_bytes`].
+    pub fn new(format: Format, systematic_packets: &[u8]) -> Result<AnyEncoder, EncoderError> {
+        Ok(match format {
+            Format::Lj2 => AnyEncoder::Lj2(OwnedEncoder::from_bytes(systematic_packets)?),
+            Format::NoFec => AnyEncoder::NoFec(OwnedEncoder::from_bytes(systematic_packets)?),
+        })
+    }
+
+    /// Returns the packet format of the encoder.
+    pub fn format(&self) -> Format {
+        match self {
+            AnyEncoder::Lj2(_) => Format::Lj2,
+            AnyEncoder::NoFec(_) => Format::NoFec,
+        }
+    }
+
+    /// Generates the packet with a corresponding `packet_id`, returning its
+    /// bytes.
+    ///
+    /// This works as [`OwnedEncoder::encode`].
+    pub fn encode(&self, packet_id: u16) -> Vec<u8> {
+        with_encoder!(self, encoder => {
+            let mut packet = Packet::zeroed();
+            encoder.encode(packet_id, &mut packet);
+            to_bytes(&[packet])
+        })
+    }
+
+    /// Generates `count` packets with consecutive packet IDs starting at
+    /// `first_packet_id`, returning their bytes one after another.
+    ///
+    /// This works as [`OwnedEncoder::try_encode_range`]. If the packet ID of
+    /// some of the packets would be larger than `u16::MAX`,
+    /// [`EncoderError::InvalidRange`] is returned.
+    pub fn encode_range(
+        &self,
+        first_packet_id: u16,
+        count: usize,
+    ) -> Result<Vec<u8>, EncoderError> {
+        with_encoder!(self, encoder => {
+            if count > usize::from(u16::MAX - first_packet_id) + 1 {
+                return Err(EncoderError::InvalidRange);
+            }
+            let mut output = alloc::vec![Packet::zeroed(); count];
+            encoder.try_encode_range(first_packet_id, &mut output)?;
+            Ok(to_bytes(&output))
+        })
+    }
+
+    /// Returns the number of systematic packets of the image.
+    pub fn num_systematic(&self) -> u16 {
+        with_encoder!(self, encoder => encoder.num_systematic())
+    }
+
+    /// Returns the image ID of the image.
+    pub fn image_id(&self) -> u8 {
+        with_encoder!(self, encoder => encoder.image_id())
+    }
+
+    /// Releases the encoder, returning the bytes of the systematic packets of
+    /// the image.
+    pub fn into_bytes(self) -> Vec<u8> {
+        with_encoder!(self, encoder => to_bytes(&encoder.into_packets()))
+    }
+}
+
+// Concatenates packets.
+fn to_bytes<P: Packet>(packets: &[P]) -> Vec<u8> {
+    packets
+        .iter()
+        .flat_map(|packet| packet.as_bytes())
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::IMG_230_SSDV;
+
+    #[test]
+    fn encode_decode() {
+        let n = Format::Lj2.packet_len();
+        let encoder = AnyEncoder::new(Format::Lj2, IMG_230_SSDV).unwrap();
+        assert_eq!(encoder.format(), Format::Lj2);
+        let k = encoder.num_systematic();
+        assert_eq!(usize::from(k) * n, IMG_230_SSDV.len());
+        assert_eq!(
+            encoder.encode_range(0, usize::from(k)).unwrap(),
+            IMG_230_SSDV
+        );
+        let mut received = encoder.encode(k - 1);
+        received.extend(encoder.encode_range(k + 1, usize::from(k) - 1).unwrap());
+        let decoded = Format::Lj2
+            .decode(&received, &DecoderOptions::default())
+            .unwrap();
+        assert_eq!(decoded, IMG_230_SSDV);
+        assert_eq!(encoder.into_bytes(), IMG_230_SSDV);
+    }
+
+    #[test]
+    fn packet_info() {
+        let n = Format::Lj2.packet_len();
+        let k = IMG_230_SSDV.len() / n;
+        let last = Format::Lj2
+            .packet_info(&IMG_230_SSDV[(k - 1) * n..])
+            .unwrap();
+        assert_eq!(usize::from(last.packet_id), k - 1);
+        assert!(last.eoi && !last.fec_packet && last.crc_ok);
+        assert_eq!(last.num_systematic, None);
+        let encoder = AnyEncoder::new(Format::Lj2, IMG_230_SSDV).unwrap();
+        let fec = Format::Lj2.packet_info(&encoder.encode(k as u16)).unwrap();
+        assert!(fec.fec_packet && fec.crc_ok);
+        assert_eq!(fec.num_systematic, Some(k as u16));
+        assert_eq!(fec.image_id, encoder.image_id());
+        assert_eq!(Format::Lj2.packet_info(&IMG_230_SSDV[..2 * n]), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            AnyEncoder::new(Format::NoFec, IMG_230_SSDV).unwrap_err(),
+            EncoderError::InvalidLength
+        );
+        let encoder = AnyEncoder::new(Format::Lj2, IMG_230_SSDV).unwrap();
+        assert_eq!(
+            encoder.encode_range(u16::MAX, 2),
+            Err(EncoderError::InvalidRange)
+        );
+        assert_eq!(encoder.encode_range(u16::MAX, 1).unwrap().len(), 218);
+        assert_eq!(
+            Format::Lj2.decode(&IMG_230_SSDV[..100], &DecoderOptions::default()),
+            Err(DecoderError::InvalidLength)
+        );
+    }
+}
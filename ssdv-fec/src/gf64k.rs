@@ -1,4 +1,7 @@
 use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+#[cfg(feature = "ram-tables")]
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+#[cfg(not(feature = "ram-tables"))]
 use ssdv_fec_gf_tables::{gf256_exp_table, gf256_log_table};
 
 /// GF(2¹⁶) field element.
@@ -9,6 +12,9 @@ use ssdv_fec_gf_tables::{gf256_exp_table, gf256_log_table};
 /// where x denotes the generator of GF(2⁸). Arithmetic in this
 /// field extension is implemented using simple ad-hoc formulas for a field
 /// extension of degree two.
+///
+/// Division with the `/` operator panics if the divisor is zero.
+/// [`Self::checked_div`] returns `None` instead.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
 pub struct GF64K(GF256, GF256);
 
@@ -19,6 +25,9 @@ pub struct GF64K(GF256, GF256);
 /// GF(2)\[x\] / (x⁸ + x⁴ + x³ + x² + 1).
 /// Its arithmetic is implemented
 /// using tables of exponentials and logarithms.
+///
+/// Division with the `/` operator panics if the divisor is zero.
+/// [`Self::checked_div`] returns `None` instead.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
 pub struct GF256(u8);
 
@@ -156,11 +165,11 @@ impl Mul for GF256 {
         if self.0 == 0 || rhs.0 == 0 {
             GF256(0)
         } else {
-            let a = GF256_LOG_TABLE[self.0 as usize];
-            let b = GF256_LOG_TABLE[rhs.0 as usize];
+            let a = log(self.0);
+            let b = log(rhs.0);
             let c = a as u32 + b as u32;
             let c = if c >= 255 { c - 255 } else { c };
-            GF256(GF256_EXP_TABLE[c as usize])
+            GF256(exp(c as usize))
         }
     }
 }
@@ -171,55 +180,188 @@ impl MulAssign for GF256 {
     }
 }
 
-impl Div for GF64K {
-    type Output = GF64K;
-    fn div(self, rhs: GF64K) -> GF64K {
-        assert_ne!(rhs, GF64K(GF256(0), GF256(0)));
+impl GF64K {
+    /// Divides by `rhs`, returning `None` if `rhs` is zero.
+    pub fn checked_div(self, rhs: GF64K) -> Option<GF64K> {
+        (rhs != GF64K::default()).then(|| self.div_nonzero(rhs))
+    }
+
+    // Divides by `rhs`, which must be nonzero. The encoder and decoder only
+    // divide by nonzero elements, and they use this instead of the `/`
+    // operator so that they have no panicking path. A zero `rhs` gives zero.
+    pub(crate) fn div_nonzero(self, rhs: GF64K) -> GF64K {
         // Compute the inverse by solving a 2x2 linear system over GF(2^8) using
-        // Cramer's rule.
+        // Cramer's rule. The discriminant is only zero if rhs is zero, because
+        // y^2 + x^3 y + 1 is irreducible over GF(2^8).
         let discr = rhs.1 * rhs.1 + GF64K_POLY_XCOEFF * rhs.0 * rhs.1 + rhs.0 * rhs.0;
         GF64K(
-            (self.0 * rhs.1 + self.1 * rhs.0) / discr,
-            (self.1 * (rhs.1 + GF64K_POLY_XCOEFF * rhs.0) + self.0 * rhs.0) / discr,
+            (self.0 * rhs.1 + self.1 * rhs.0).div_nonzero(discr),
+            (self.1 * (rhs.1 + GF64K_POLY_XCOEFF * rhs.0) + self.0 * rhs.0).div_nonzero(discr),
         )
     }
 }
 
+impl Div for GF64K {
+    type Output = GF64K;
+    fn div(self, rhs: GF64K) -> GF64K {
+        assert_ne!(rhs, GF64K(GF256(0), GF256(0)));
+        self.div_nonzero(rhs)
+    }
+}
+
 impl DivAssign for GF64K {
     fn div_assign(&mut self, rhs: GF64K) {
         *self = *self / rhs;
     }
 }
 
-impl Div for GF256 {
-    type Output = GF256;
-    fn div(self, rhs: GF256) -> GF256 {
-        assert_ne!(rhs, GF256(0));
-        if self.0 == 0 {
+impl GF256 {
+    /// Divides by `rhs`, returning `None` if `rhs` is zero.
+    pub fn checked_div(self, rhs: GF256) -> Option<GF256> {
+        (rhs.0 != 0).then(|| self.div_nonzero(rhs))
+    }
+
+    // Divides by `rhs`, which must be nonzero. A zero `rhs` gives zero.
+    fn div_nonzero(self, rhs: GF256) -> GF256 {
+        if self.0 == 0 || rhs.0 == 0 {
             GF256(0)
         } else {
-            let a = GF256_LOG_TABLE[self.0 as usize];
-            let b = GF256_LOG_TABLE[rhs.0 as usize];
+            let a = log(self.0);
+            let b = log(rhs.0);
             let c = 255 + a as u32 - b as u32;
             let c = if c >= 255 { c - 255 } else { c };
-            GF256(GF256_EXP_TABLE[c as usize])
+            GF256(exp(c as usize))
         }
     }
 }
 
+impl Div for GF256 {
+    type Output = GF256;
+    fn div(self, rhs: GF256) -> GF256 {
+        assert_ne!(rhs, GF256(0));
+        self.div_nonzero(rhs)
+    }
+}
+
 impl DivAssign for GF256 {
     fn div_assign(&mut self, rhs: GF256) {
         *self = *self / rhs;
     }
 }
 
+#[cfg(all(feature = "tables-ccmram", feature = "tables-dtcm"))]
+compile_error!("the tables-ccmram and tables-dtcm features cannot be enabled together");
+
+// The tables are accessed for each GF(2¹⁶) multiplication and division, so
+// the `tables-ccmram` and `tables-dtcm` features place them in the
+// tightly-coupled memory of the microcontroller, which is faster than flash.
+// The linker script must copy their initial contents from flash at startup,
+// as for `.data`.
+#[cfg(not(feature = "ram-tables"))]
+#[cfg_attr(feature = "tables-ccmram", link_section = ".ccmram")]
+#[cfg_attr(feature = "tables-dtcm", link_section = ".dtcm")]
 static GF256_EXP_TABLE: [u8; 256] = gf256_exp_table!();
+#[cfg(not(feature = "ram-tables"))]
+#[cfg_attr(feature = "tables-ccmram", link_section = ".ccmram")]
+#[cfg_attr(feature = "tables-dtcm", link_section = ".dtcm")]
 static GF256_LOG_TABLE: [u8; 256] = gf256_log_table!();
 
+#[cfg(not(feature = "ram-tables"))]
+fn exp(power: usize) -> u8 {
+    GF256_EXP_TABLE[power]
+}
+
+#[cfg(not(feature = "ram-tables"))]
+fn log(a: u8) -> u8 {
+    GF256_LOG_TABLE[usize::from(a)]
+}
+
+// With the `ram-tables` feature, the tables are zero-initialized, so that they
+// take no space in flash, and they are generated by `init`. Atomics are used so
+// that the tables can be written safely, but the relaxed loads compile to
+// ordinary loads.
+#[cfg(feature = "ram-tables")]
+#[cfg_attr(feature = "tables-ccmram", link_section = ".ccmram")]
+#[cfg_attr(feature = "tables-dtcm", link_section = ".dtcm")]
+static GF256_EXP_TABLE: [AtomicU8; 256] = [const { AtomicU8::new(0) }; 256];
+#[cfg(feature = "ram-tables")]
+#[cfg_attr(feature = "tables-ccmram", link_section = ".ccmram")]
+#[cfg_attr(feature = "tables-dtcm", link_section = ".dtcm")]
+static GF256_LOG_TABLE: [AtomicU8; 256] = [const { AtomicU8::new(0) }; 256];
+#[cfg(feature = "ram-tables")]
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+// Primitive polynomial defining GF(2⁸), without the x⁸ term. This is the same
+// as in the ssdv-fec-gf-tables crate.
+#[cfg(feature = "ram-tables")]
+const GF256_POLY: u8 = 0b11101;
+
+/// Generates the tables used for the arithmetic of GF(2⁸).
+///
+/// With the `ram-tables` feature, the 512 bytes of tables used by [`GF256`]
+/// and [`GF64K`], and hence by the encoder and decoder, are not included in
+/// the binary, but generated in RAM by this function. It must be called before
+/// doing any arithmetic, for instance at the start of the firmware. Calling it
+/// again does nothing. In debug builds, using the arithmetic before calling
+/// this function panics. In release builds, it gives wrong results.
+#[cfg(feature = "ram-tables")]
+pub fn init() {
+    if INITIALIZED.load(Ordering::Acquire) {
+        return;
+    }
+    let mut a = 1u8;
+    for (power, exp) in (0..255).zip(GF256_EXP_TABLE.iter()) {
+        exp.store(a, Ordering::Relaxed);
+        GF256_LOG_TABLE[usize::from(a)].store(power, Ordering::Relaxed);
+        a = if a & 0x80 != 0 {
+            (a << 1) ^ GF256_POLY
+        } else {
+            a << 1
+        };
+    }
+    INITIALIZED.store(true, Ordering::Release);
+}
+
+#[cfg(feature = "ram-tables")]
+fn check_initialized() {
+    // The unit tests do not call init, so the tables are generated on first
+    // use.
+    #[cfg(test)]
+    init();
+    debug_assert!(
+        INITIALIZED.load(Ordering::Relaxed),
+        "ssdv_fec::init has not been called"
+    );
+}
+
+#[cfg(feature = "ram-tables")]
+fn exp(power: usize) -> u8 {
+    check_initialized();
+    GF256_EXP_TABLE[power].load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "ram-tables")]
+fn log(a: u8) -> u8 {
+    check_initialized();
+    GF256_LOG_TABLE[usize::from(a)].load(Ordering::Relaxed)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[cfg(feature = "ram-tables")]
+    #[test]
+    fn ram_tables() {
+        init();
+        let exp_table: [u8; 256] = ssdv_fec_gf_tables::gf256_exp_table!();
+        let log_table: [u8; 256] = ssdv_fec_gf_tables::gf256_log_table!();
+        for j in 0..256 {
+            assert_eq!(GF256_EXP_TABLE[j].load(Ordering::Relaxed), exp_table[j]);
+            assert_eq!(GF256_LOG_TABLE[j].load(Ordering::Relaxed), log_table[j]);
+        }
+    }
+
     #[test]
     fn powers_gf256() {
         let mut a = GF256(1);
@@ -252,6 +394,19 @@ mod test {
         assert_eq!(c * b, a);
     }
 
+    #[test]
+    fn div_by_zero() {
+        let a = GF64K(GF256(87), GF256(34));
+        assert_eq!(a.checked_div(GF64K::default()), None);
+        assert_eq!(GF256(123).checked_div(GF256(0)), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn div_operator_by_zero() {
+        let _ = GF64K(GF256(87), GF256(34)) / GF64K::default();
+    }
+
     #[test]
     fn gf64k_poly_root() {
         let y = GF64K(GF256(1), GF256(0));
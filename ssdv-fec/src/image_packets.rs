@@ -0,0 +1,89 @@
+// Accumulation of the received packets of a single image.
+//
+// This is shared by ImageSet and Receiver, which track several images by their
+// image ID. The packets of an image are stored as they arrive, without
+// duplicates, and the image is decoded as soon as there are as many distinct
+// packets as systematic packets. If decoding fails, for instance because of
+// inconsistent headers, the packets are kept and decoding is attempted again
+// with each new packet.
+
+use crate::{DecodeStats, Decoder, DecoderError, DecoderOptions, Packet};
+use alloc::{collections::BTreeSet, vec, vec::Vec};
+
+#[derive(Debug)]
+pub(crate) struct ImagePackets<P> {
+    // Packets received, in order of arrival. This is emptied once the image is
+    // decoded.
+    pub(crate) packets: Vec<P>,
+    pub(crate) packet_ids: BTreeSet<u16>,
+    pub(crate) num_systematic: Option<u16>,
+    pub(crate) decoded: bool,
+}
+
+// Result of pushing a packet into an ImagePackets.
+#[derive(Debug)]
+pub(crate) enum Push<P> {
+    // The packet has already been received or the image has been decoded.
+    Duplicate,
+    // The packet has been stored, but there are not enough packets to decode.
+    Stored,
+    // The image has been decoded with this packet.
+    Decoded(Vec<P>, DecodeStats),
+    // Decoding was attempted but failed.
+    DecodeFailed(DecoderError),
+}
+
+impl<P: Packet> ImagePackets<P> {
+    pub(crate) fn new() -> ImagePackets<P> {
+        ImagePackets {
+            packets: Vec::new(),
+            packet_ids: BTreeSet::new(),
+            num_systematic: None,
+            decoded: false,
+        }
+    }
+
+    // Returns the number of distinct packets received.
+    pub(crate) fn num_received(&self) -> usize {
+        self.packet_ids.len()
+    }
+
+    // Returns the number of packets still needed to decode the image, if the
+    // number of systematic packets is known.
+    pub(crate) fn num_missing(&self) -> Option<usize> {
+        self.num_systematic
+            .map(|k| usize::from(k).saturating_sub(self.num_received()))
+    }
+
+    // Stores a packet whose CRC has already been checked, and decodes the
+    // image if there are enough distinct packets.
+    pub(crate) fn push(&mut self, packet: P, options: &DecoderOptions) -> Push<P> {
+        if self.decoded || !self.packet_ids.insert(packet.packet_id()) {
+            return Push::Duplicate;
+        }
+        self.packets.push(packet);
+        if let Some(k) = packet.number_systematic_packets() {
+            self.num_systematic = Some(k);
+        } else if packet.is_eoi() {
+            // an EOI on packet ID 0xffff is invalid, since k would be 2¹⁶
+            if let Some(k) = packet.packet_id().checked_add(1) {
+                self.num_systematic = Some(k);
+            }
+        }
+        if self.num_missing() != Some(0) {
+            return Push::Stored;
+        }
+        // The decoder works in-place, so a copy of the packets is needed in
+        // case decoding fails and more packets are needed.
+        let mut input = self.packets.clone();
+        let mut output = vec![P::zeroed(); input.len()];
+        match Decoder::decode_with_stats(&mut input, &mut output, options) {
+            Ok((decoded, stats)) => {
+                self.decoded = true;
+                self.packets = Vec::new();
+                Push::Decoded(decoded.to_vec(), stats)
+            }
+            Err(err) => Push::DecodeFailed(err),
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Per-image accumulation of received packets.
+//!
+//! This module is used by receivers that get packets as they arrive, possibly
+//! from several images interleaved, and decode each image as soon as enough
+//! packets are available, such as the CLI `listen` command and the
+//! [`async_io`](crate::async_io) adapters.
+//!
+//! Packets can be pushed together with the metadata given by the receiver,
+//! such as the reception time, frequency and SNR, as a [`ReceivedPacket`]. The
+//! metadata of the packets used to decode an image is kept in the
+//! [`DecodedImage`] for later analysis.
+//!
+//! Images for which no packets have been received during a timeout are
+//! expired and forgotten, so that their image ID can be reused by a later
+//! image.
+
+use crate::{
+    image_packets::{ImagePackets, Push},
+    DecoderError, DecoderOptions, HeaderOverrides, Packet, SSDVPacket,
+};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Collection of images being received.
+///
+/// Images for which no packets have been received during the timeout set
+/// with [`ImageSet::timeout`], which is [`ImageSet::DEFAULT_TIMEOUT`] by
+/// default, are expired and forgotten. This includes the images that have been
+/// decoded, so that a later image that reuses their image ID, since it is only
+/// 8 bits long, can be decoded, as well as the images that cannot be decoded,
+/// so that their packets are not kept forever. Expiry is checked when a packet
+/// is pushed and when [`ImageSet::expire`] is called.
+///
+/// The packets are in the format used by Longjiang-2 by default, but any type
+/// implementing [`Packet`] can be used.
+#[derive(Debug)]
+pub struct ImageSet<P: Packet = SSDVPacket> {
+    images: BTreeMap<u8, ImageState<P>>,
+    options: DecoderOptions,
+    timeout: Duration,
+}
+
+/// Metadata given by a receiver for a packet.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PacketMetadata {
+    /// Reception time.
+    pub timestamp: Option<SystemTime>,
+    /// Reception frequency in Hz.
+    pub frequency: Option<f64>,
+    /// Signal to noise ratio in dB.
+    pub snr: Option<f64>,
+}
+
+impl PacketMetadata {
+    /// Returns true if no metadata is present.
+    pub fn is_empty(&self) -> bool {
+        self.timestamp.is_none() && self.frequency.is_none() && self.snr.is_none()
+    }
+}
+
+/// Packet received together with its metadata.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceivedPacket<P = SSDVPacket> {
+    /// Received packet.
+    pub packet: P,
+    /// Metadata given by the receiver.
+    pub metadata: PacketMetadata,
+}
+
+impl<P> ReceivedPacket<P> {
+    /// Creates a received packet without metadata.
+    pub fn new(packet: P) -> ReceivedPacket<P> {
+        ReceivedPacket {
+            packet,
+            metadata: PacketMetadata::default(),
+        }
+    }
+
+    /// Sets the metadata of the packet.
+    pub fn with_metadata(mut self, metadata: PacketMetadata) -> ReceivedPacket<P> {
+        self.metadata = metadata;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ImageState<P: Packet> {
+    received: ImagePackets<P>,
+    // metadata of the packets that have it, by packet ID
+    metadata: BTreeMap<u16, PacketMetadata>,
+    first_packet_time: SystemTime,
+    last_packet_time: Instant,
+}
+
+/// Result of pushing a packet into an [`ImageSet`].
+#[derive(Debug)]
+pub enum PushResult<P = SSDVPacket> {
+    /// The packet has a wrong CRC and has been discarded.
+    WrongCrc,
+    /// The packet has already been received or belongs to an image that has
+    /// already been decoded.
+    Duplicate,
+    /// The packet has been stored, but its image cannot be decoded yet.
+    Stored,
+    /// The image has been decoded with this packet.
+    Decoded(DecodedImage<P>),
+    /// Decoding of the image was attempted but failed.
+    DecodeFailed(DecoderError),
+}
+
+/// Image decoded by an [`ImageSet`].
+#[derive(Debug)]
+pub struct DecodedImage<P = SSDVPacket> {
+    /// Packets of the decoded image.
+    pub packets: Vec<P>,
+    /// Number of distinct valid packets used for decoding.
+    pub num_received: usize,
+    /// Number of systematic packets that were received.
+    ///
+    /// The remaining systematic packets have been recovered by the FEC decoder.
+    pub num_systematic_received: usize,
+    /// Header inconsistencies resolved by the decoder.
+    pub overrides: HeaderOverrides,
+    /// Time at which the first valid packet of the image was received.
+    pub first_packet_time: SystemTime,
+    /// Metadata of the received packets that have metadata, by packet ID.
+    pub metadata: BTreeMap<u16, PacketMetadata>,
+}
+
+/// Reception statistics of an image.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ImageStats {
+    /// Number of distinct valid packets received.
+    pub unique: usize,
+    /// Number of systematic packets of the image, if known.
+    pub num_systematic: Option<u16>,
+    /// Whether the image has been decoded.
+    pub decoded: bool,
+}
+
+impl<P: Packet> Default for ImageSet<P> {
+    fn default() -> ImageSet<P> {
+        ImageSet {
+            images: BTreeMap::new(),
+            options: DecoderOptions::default(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+impl ImageSet {
+    /// Default time after the last received packet at which an image expires.
+    pub const DEFAULT_TIMEOUT: Duration = DEFAULT_TIMEOUT;
+}
+
+impl<P: Packet> ImageSet<P> {
+    /// Creates an empty set of images.
+    pub fn new() -> ImageSet<P> {
+        ImageSet::default()
+    }
+
+    /// Creates an empty set of images that are decoded with some options.
+    pub fn with_options(options: DecoderOptions) -> ImageSet<P> {
+        ImageSet {
+            options,
+            ..ImageSet::default()
+        }
+    }
+
+    /// Sets the time after the last received packet at which an image expires.
+    pub fn timeout(mut self, timeout: Duration) -> ImageSet<P> {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Expires the images whose timeout has elapsed by `now`.
+    ///
+    /// This is done each time that a packet is pushed, but it can also be
+    /// called periodically to release the memory used by the images that are
+    /// no longer being received. The function returns the IDs of the expired
+    /// images.
+    pub fn expire(&mut self, now: Instant) -> Vec<u8> {
+        let mut expired = Vec::new();
+        self.images.retain(|&image_id, image| {
+            let keep = image
+                .last_packet_time
+                .checked_add(self.timeout)
+                .is_none_or(|expiry| now < expiry);
+            if !keep {
+                expired.push(image_id);
+            }
+            keep
+        });
+        expired
+    }
+
+    /// Returns the reception statistics of an image.
+    pub fn stats(&self, image_id: u8) -> Option<ImageStats> {
+        self.images.get(&image_id).map(|image| ImageStats {
+            unique: image.received.num_received(),
+            num_systematic: image.received.num_systematic,
+            decoded: image.received.decoded,
+        })
+    }
+
+    /// Returns the IDs of the images that have been received, in increasing
+    /// order.
+    pub fn image_ids(&self) -> impl Iterator<Item = u8> + '_ {
+        self.images.keys().copied()
+    }
+
+    /// Returns the IDs of the systematic packets of an image that have not
+    /// been received.
+    ///
+    /// `None` is returned if no packets of the image have been received or if
+    /// its number of systematic packets is not known yet. Once the image has
+    /// been decoded, no packets are missing.
+    pub fn missing_systematic(&self, image_id: u8) -> Option<Vec<u16>> {
+        let image = &self.images.get(&image_id)?.received;
+        let k = image.num_systematic?;
+        if image.decoded {
+            return Some(Vec::new());
+        }
+        Some((0..k).filter(|id| !image.packet_ids.contains(id)).collect())
+    }
+
+    /// Adds a received packet.
+    ///
+    /// Decoding of the image that the packet belongs to is attempted if there
+    /// are enough distinct packets for this image.
+    pub fn push(&mut self, packet: P) -> PushResult<P> {
+        self.push_received(ReceivedPacket::new(packet))
+    }
+
+    /// Adds a received packet with its metadata.
+    ///
+    /// This works as [`ImageSet::push`], and the metadata is returned in the
+    /// [`DecodedImage`] when the image is decoded.
+    pub fn push_received(&mut self, received: ReceivedPacket<P>) -> PushResult<P> {
+        let packet = received.packet;
+        if packet.compute_crc32() != packet.crc32() {
+            return PushResult::WrongCrc;
+        }
+        let now = Instant::now();
+        self.expire(now);
+        let image = self
+            .images
+            .entry(packet.image_id())
+            .or_insert_with(|| ImageState {
+                received: ImagePackets::new(),
+                metadata: BTreeMap::new(),
+                first_packet_time: SystemTime::now(),
+                last_packet_time: now,
+            });
+        image.last_packet_time = now;
+        let result = image.received.push(packet, &self.options);
+        if !matches!(result, Push::Duplicate) && !received.metadata.is_empty() {
+            image.metadata.insert(packet.packet_id(), received.metadata);
+        }
+        match result {
+            Push::Duplicate => PushResult::Duplicate,
+            Push::Stored => PushResult::Stored,
+            Push::Decoded(packets, stats) => {
+                let packet_ids = &image.received.packet_ids;
+                PushResult::Decoded(DecodedImage {
+                    num_received: packet_ids.len(),
+                    num_systematic_received: packet_ids.range(..packets.len() as u16).count(),
+                    packets,
+                    overrides: stats.overrides,
+                    first_packet_time: image.first_packet_time,
+                    metadata: core::mem::take(&mut image.metadata),
+                })
+            }
+            Push::DecodeFailed(err) => PushResult::DecodeFailed(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Encoder, Packet};
+
+    #[test]
+    fn decode_when_enough_packets() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut images = ImageSet::new();
+        assert_eq!(images.missing_systematic(230), None);
+        for j in 0..k {
+            if j == k - 1 {
+                // the odd packet IDs have been received
+                let missing = (0..k as u16).step_by(2).collect::<Vec<_>>();
+                assert_eq!(images.missing_systematic(230), Some(missing));
+            }
+            let mut packet = SSDVPacket::zeroed();
+            encoder.encode(u16::try_from(2 * j + 1).unwrap(), &mut packet);
+            match images.push(packet) {
+                PushResult::Stored => assert!(j < k - 1),
+                PushResult::Decoded(decoded) => {
+                    assert_eq!(j, k - 1);
+                    assert_eq!(decoded.packets, ssdv);
+                    assert_eq!(decoded.num_received, k);
+                    assert_eq!(decoded.num_systematic_received, k / 2);
+                }
+                result => panic!("unexpected result {result:?}"),
+            }
+        }
+        assert!(matches!(images.push(ssdv[0]), PushResult::Duplicate));
+        assert_eq!(images.image_ids().collect::<Vec<_>>(), [230]);
+        assert_eq!(images.missing_systematic(230), Some(Vec::new()));
+    }
+
+    #[test]
+    fn image_id_reuse() {
+        let ssdv = img_230_packets();
+        let mut images = ImageSet::new();
+        for _ in 0..2 {
+            let results = ssdv
+                .iter()
+                .map(|&packet| images.push(packet))
+                .collect::<Vec<_>>();
+            assert!(matches!(results.last(), Some(PushResult::Decoded(_))));
+            assert!(matches!(images.push(ssdv[0]), PushResult::Duplicate));
+            // a later image with the same image ID is decoded once the image
+            // has expired
+            assert!(images.expire(Instant::now()).is_empty());
+            assert_eq!(
+                images.expire(Instant::now() + ImageSet::DEFAULT_TIMEOUT),
+                [230]
+            );
+            assert_eq!(images.stats(230), None);
+        }
+    }
+
+    #[test]
+    fn eoi_on_last_packet_id() {
+        let mut ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut packet = ssdv[k - 1];
+        assert!(packet.is_eoi());
+        packet.set_packet_id(0xffff);
+        packet.set_crc32(packet.compute_crc32());
+        let mut images = ImageSet::new();
+        assert!(matches!(images.push(packet), PushResult::Stored));
+        assert_eq!(images.stats(230).unwrap().num_systematic, None);
+        // the FEC packets give the number of systematic packets, which is
+        // inconsistent with the EOI
+        let encoder = Encoder::new(&mut ssdv).unwrap();
+        let mut packet = SSDVPacket::zeroed();
+        for j in 1..k {
+            encoder.encode(u16::try_from(k + j).unwrap(), &mut packet);
+            let result = images.push(packet);
+            if j < k - 1 {
+                assert!(matches!(result, PushResult::Stored));
+            } else {
+                assert!(matches!(
+                    result,
+                    PushResult::DecodeFailed(DecoderError::EoiFecMismatch)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn metadata() {
+        let packets = img_230_packets();
+        let metadata = PacketMetadata {
+            timestamp: Some(SystemTime::UNIX_EPOCH),
+            frequency: Some(435.5e6),
+            snr: Some(12.5),
+        };
+        let mut images = ImageSet::new();
+        let mut decoded = None;
+        for packet in packets {
+            let received = if packet.packet_id() == 3 {
+                ReceivedPacket::new(packet).with_metadata(metadata)
+            } else {
+                ReceivedPacket::new(packet)
+            };
+            if let PushResult::Decoded(image) = images.push_received(received) {
+                decoded = Some(image);
+            }
+        }
+        let decoded = decoded.unwrap();
+        assert_eq!(decoded.metadata, BTreeMap::from([(3, metadata)]));
+    }
+}
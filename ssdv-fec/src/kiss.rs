@@ -0,0 +1,414 @@
+//! KISS protocol.
+//!
+//! This implements the framing and deframing of the KISS protocol, which is
+//! commonly used by TNCs and software modems such as Direwolf to exchange
+//! packets, and [`KissTnc`], a client that sends and receives SSDV packets
+//! through a TNC connected by a serial port or TCP.
+//!
+//! It also supports the KISS files written by the KISS file sink of
+//! [gr-satellites](https://github.com/daniestevez/gr-satellites), in which
+//! each frame can be preceded by a timestamp frame. See [`read_kiss_file`] and
+//! [`TimestampedFrame`].
+//!
+//! This module is only available with the `kiss` feature.
+
+use crate::Packet;
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+const FEND: u8 = 0xc0;
+const FESC: u8 = 0xdb;
+const TFEND: u8 = 0xdc;
+const TFESC: u8 = 0xdd;
+
+// KISS command of the timestamp frames written by gr-satellites
+const TIMESTAMP: u8 = 0x09;
+
+/// Encodes a KISS data frame for port 0 containing `payload`.
+pub fn kiss_frame(payload: &[u8]) -> Vec<u8> {
+    kiss_frame_with_port(0, payload)
+}
+
+/// Encodes a KISS data frame for a TNC port containing `payload`.
+///
+/// # Panics
+///
+/// Panics if `port` is larger than 15.
+pub fn kiss_frame_with_port(port: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(port < 16, "the KISS port must be smaller than 16");
+    let mut frame = Vec::with_capacity(payload.len() + 3);
+    frame.push(FEND);
+    frame.push(port << 4);
+    for &byte in payload {
+        match byte {
+            FEND => frame.extend_from_slice(&[FESC, TFEND]),
+            FESC => frame.extend_from_slice(&[FESC, TFESC]),
+            _ => frame.push(byte),
+        }
+    }
+    frame.push(FEND);
+    frame
+}
+
+/// KISS deframer.
+///
+/// The deframer is fed with bytes as they are received and returns the data
+/// frames that are completed. Since it keeps state between calls, a frame can
+/// be split across several calls to [`KissDeframer::push`].
+#[derive(Debug, Default)]
+pub struct KissDeframer {
+    buffer: Vec<u8>,
+    in_frame: bool,
+    escape: bool,
+    port: u8,
+}
+
+impl KissDeframer {
+    /// Creates a new KISS deframer for port 0.
+    pub fn new() -> KissDeframer {
+        KissDeframer::default()
+    }
+
+    /// Creates a new KISS deframer for a TNC port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` is larger than 15.
+    pub fn with_port(port: u8) -> KissDeframer {
+        assert!(port < 16, "the KISS port must be smaller than 16");
+        KissDeframer {
+            port,
+            ..KissDeframer::default()
+        }
+    }
+
+    /// Pushes some bytes into the deframer.
+    ///
+    /// The payloads of the data frames for the port of the deframer that are
+    /// completed with these bytes are returned. Non-data frames (KISS
+    /// commands) and frames for other ports are discarded.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        self.push_commands(bytes)
+            .into_iter()
+            .filter(|(command, _)| *command == 0)
+            .map(|(_, frame)| frame)
+            .collect()
+    }
+
+    // Pushes some bytes into the deframer and returns the frames for the port
+    // of the deframer that are completed, with their command (the lower nibble
+    // of the command byte).
+    fn push_commands(&mut self, bytes: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        for &byte in bytes {
+            if byte == FEND {
+                if self.in_frame && !self.buffer.is_empty() {
+                    // The first byte of the frame is the KISS command byte,
+                    // with the port number in its upper nibble.
+                    if self.buffer[0] >> 4 == self.port {
+                        frames.push((self.buffer[0] & 0xf, self.buffer[1..].to_vec()));
+                    }
+                }
+                self.buffer.clear();
+                self.in_frame = true;
+                self.escape = false;
+                continue;
+            }
+            if !self.in_frame {
+                continue;
+            }
+            if self.escape {
+                self.buffer.push(match byte {
+                    TFEND => FEND,
+                    TFESC => FESC,
+                    // protocol violation; keep the byte as is
+                    _ => byte,
+                });
+                self.escape = false;
+            } else if byte == FESC {
+                self.escape = true;
+            } else {
+                self.buffer.push(byte);
+            }
+        }
+        frames
+    }
+}
+
+/// Frame of a KISS file written by gr-satellites.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TimestampedFrame {
+    /// Reception time, as the time elapsed since the UNIX epoch.
+    ///
+    /// This is `None` if the frame is not preceded by a timestamp frame.
+    pub timestamp: Option<Duration>,
+    /// Frame payload.
+    pub payload: Vec<u8>,
+}
+
+impl TimestampedFrame {
+    /// Encodes the frame in the format written by gr-satellites.
+    ///
+    /// A data frame for port 0 is returned, preceded by a timestamp frame if
+    /// the frame has a timestamp. Timestamps are given with millisecond
+    /// resolution.
+    pub fn to_kiss(&self) -> Vec<u8> {
+        let mut kiss = Vec::new();
+        if let Some(timestamp) = self.timestamp {
+            let millis = u64::try_from(timestamp.as_millis()).unwrap_or(u64::MAX);
+            let mut frame = kiss_frame(&millis.to_be_bytes());
+            frame[1] = TIMESTAMP;
+            kiss.extend_from_slice(&frame);
+        }
+        kiss.extend_from_slice(&kiss_frame(&self.payload));
+        kiss
+    }
+}
+
+/// Reads the frames of a KISS file written by gr-satellites.
+///
+/// The data frames for port 0 are returned. In these files, each data frame
+/// can be preceded by a timestamp frame, which uses the KISS command 0x09 and
+/// contains the reception time as a big-endian 64-bit number of milliseconds
+/// since the UNIX epoch. Timestamps are optional, so files containing only
+/// data frames, such as those written by other applications, are also
+/// accepted.
+pub fn read_kiss_file(data: &[u8]) -> Vec<TimestampedFrame> {
+    let mut frames = Vec::new();
+    let mut timestamp = None;
+    for (command, frame) in KissDeframer::new().push_commands(data) {
+        match command {
+            0 => frames.push(TimestampedFrame {
+                timestamp: timestamp.take(),
+                payload: frame,
+            }),
+            TIMESTAMP => {
+                timestamp = <[u8; 8]>::try_from(&frame[..])
+                    .ok()
+                    .map(|millis| Duration::from_millis(u64::from_be_bytes(millis)));
+            }
+            _ => (),
+        }
+    }
+    frames
+}
+
+/// Default TCP port of the KISS server of Direwolf.
+pub const DIREWOLF_PORT: u16 = 8001;
+
+/// KISS TNC client.
+///
+/// The client sends and receives SSDV packets in KISS data frames through a
+/// connection to a TNC, which can be a TCP connection, such as the KISS server
+/// of Direwolf, or any other byte stream implementing [`Read`] and [`Write`],
+/// such as a serial port connected to a hardware TNC. Packets received with
+/// [`KissTnc::recv_packet`] can be given to an [`ImageSet`](crate::ImageSet) or
+/// a decoder, and the packets produced by an [`Encoder`](crate::Encoder) can
+/// be sent with [`KissTnc::send_packet`].
+#[derive(Debug)]
+pub struct KissTnc<T> {
+    stream: T,
+    port: u8,
+    deframer: KissDeframer,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl KissTnc<TcpStream> {
+    /// Connects to a TNC through TCP.
+    ///
+    /// The KISS server of Direwolf listens on port [`DIREWOLF_PORT`] by
+    /// default.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<KissTnc<TcpStream>> {
+        let stream = TcpStream::connect(addr)?;
+        // frames are small, so they should not be delayed
+        stream.set_nodelay(true)?;
+        Ok(KissTnc::new(stream))
+    }
+}
+
+impl<T> KissTnc<T> {
+    /// Creates a new client that uses a byte stream connected to a TNC.
+    ///
+    /// The client uses port 0 of the TNC.
+    pub fn new(stream: T) -> KissTnc<T> {
+        KissTnc::with_port(stream, 0)
+    }
+
+    /// Creates a new client that uses a port of the TNC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` is larger than 15.
+    pub fn with_port(stream: T, port: u8) -> KissTnc<T> {
+        KissTnc {
+            stream,
+            port,
+            deframer: KissDeframer::with_port(port),
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Returns a reference to the byte stream.
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the byte stream.
+    ///
+    /// This can be used to set timeouts.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+
+    /// Returns the byte stream.
+    ///
+    /// Frames that have been received but not returned yet are lost.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+impl<T: Write> KissTnc<T> {
+    /// Sends a frame containing `payload`.
+    pub fn send_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.stream
+            .write_all(&kiss_frame_with_port(self.port, payload))?;
+        self.stream.flush()
+    }
+
+    /// Sends a frame containing a packet.
+    pub fn send_packet<P: Packet>(&mut self, packet: &P) -> io::Result<()> {
+        self.send_frame(packet.as_bytes())
+    }
+}
+
+impl<T: Read> KissTnc<T> {
+    /// Receives a frame and returns its payload.
+    ///
+    /// This blocks until a data frame for the port of the client is received.
+    /// An error with [`ErrorKind::UnexpectedEof`] is returned if the
+    /// connection is closed. If the byte stream has a timeout and the function
+    /// fails because of it, the partially received frame is kept, so the
+    /// function can be called again.
+    pub fn recv_frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut buffer = [0; 1024];
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Ok(frame);
+            }
+            let len = match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.frames.extend(self.deframer.push(&buffer[..len]));
+        }
+    }
+
+    /// Receives a packet.
+    ///
+    /// This blocks until a frame with the length of a packet is received.
+    /// Frames with a different length are discarded. The CRC of the packet is
+    /// not checked.
+    pub fn recv_packet<P: Packet>(&mut self) -> io::Result<P> {
+        loop {
+            let frame = self.recv_frame()?;
+            if frame.len() == P::LEN {
+                let mut packet = P::zeroed();
+                packet.as_bytes_mut().copy_from_slice(&frame);
+                return Ok(packet);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deframe() {
+        let mut deframer = KissDeframer::new();
+        let frames = deframer.push(&[
+            0xaa, FEND, 0x00, 1, 2, FESC, TFEND, 3, FESC, TFESC, FEND, FEND, 0x00, 4,
+        ]);
+        assert_eq!(frames, vec![vec![1, 2, FEND, 3, FESC]]);
+        let frames = deframer.push(&[5, FEND, 0x06, 7, FEND]);
+        assert_eq!(frames, vec![vec![4, 5]]);
+        let mut deframer = KissDeframer::with_port(2);
+        let frames = deframer.push(&[FEND, 0x00, 1, FEND, 0x20, 2, FEND, 0x21, 3, FEND]);
+        assert_eq!(frames, vec![vec![2]]);
+    }
+
+    #[test]
+    fn frame_deframe() {
+        let payload = (0..=255).collect::<Vec<u8>>();
+        let mut deframer = KissDeframer::new();
+        assert_eq!(deframer.push(&kiss_frame(&payload)), vec![payload]);
+    }
+
+    #[test]
+    fn kiss_file() {
+        let frames = [
+            TimestampedFrame {
+                timestamp: Some(Duration::from_millis(1_700_000_000_123)),
+                payload: vec![1, FEND, 2],
+            },
+            TimestampedFrame {
+                timestamp: None,
+                payload: vec![3],
+            },
+        ];
+        let mut data = frames
+            .iter()
+            .flat_map(|frame| frame.to_kiss())
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            data[..11],
+            [FEND, TIMESTAMP, 0, 0, 1, 0x8b, 0xcf, 0xe5, 0x68, 0x7b, FEND]
+        );
+        // a frame for another port and a command frame are ignored
+        data.extend_from_slice(&kiss_frame_with_port(1, &[4]));
+        data.extend_from_slice(&[FEND, 0x01, 50, FEND]);
+        assert_eq!(read_kiss_file(&data), frames);
+    }
+
+    #[test]
+    fn tnc_tcp() {
+        use crate::{test_data::img_230_packets, SSDVPacket};
+        use std::{net::TcpListener, thread};
+
+        let packets = img_230_packets();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let sent = packets.clone();
+        let tnc = thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            // a frame for another port and a frame that is not a packet
+            stream
+                .write_all(&kiss_frame_with_port(1, &sent[0].0))
+                .unwrap();
+            let mut tnc = KissTnc::new(stream);
+            tnc.send_frame(&[1, 2, 3]).unwrap();
+            for packet in &sent {
+                tnc.send_packet(packet).unwrap();
+            }
+        });
+        let mut client = KissTnc::connect(addr).unwrap();
+        let mut received = Vec::new();
+        let err = loop {
+            match client.recv_packet::<SSDVPacket>() {
+                Ok(packet) => received.push(packet),
+                Err(err) => break err,
+            }
+        };
+        tnc.join().unwrap();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(received, packets);
+    }
+}
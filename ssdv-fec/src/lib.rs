@@ -17,40 +17,199 @@
 //! of packets. The receiver can recover the original SSDV image from any set of
 //! k distinct packets.
 //!
-//! This implementation of the FEC scheme uses 218-byte SSDV packets following
-//! the format used by Longjiang-2, which omits the sync byte, packet type and
-//! callsign fields (but includes them implicitly in the generation of the
-//! CRC-32).
+//! This implementation of the FEC scheme uses by default 218-byte SSDV packets
+//! following the format used by Longjiang-2, which omits the sync byte, packet
+//! type and callsign fields (but includes them implicitly in the generation of
+//! the CRC-32). The standard 256-byte no-FEC SSDV packet format is also
+//! supported. The packet format is selected with the [`Packet`] type used by
+//! the encoder and decoder.
 //!
 //! The crate supports `no_std` and the implementation is designed with small
 //! microcontrollers in mind. The GF(2¹⁶) arithmetic only uses two tables of 256
-//! bytes each that are included in the `.rodata` section. The FEC encoder and
-//! decoder work with externally provided slices, giving freedom as to how to
-//! perform memory allocation, and do the computations in-place when
-//! possible. The memory required for encoding corresponds to a buffer
-//! containing the k SSDV packets of the original image, and a buffer containing
-//! the packet being encoded. The memory required for decoding corresponds to a
-//! buffer containing at least k distinct received SSDV packets, and another
-//! buffer where the k SSDV packets that compose the original image can be
-//! written. Besides these buffers, the algorithms use only a small amount of
-//! stack space.
+//! bytes each that are included in the `.rodata` section, or in the `.ccmram`
+//! or `.dtcm` sections with the `tables-ccmram` and `tables-dtcm` features.
+//! With the `ram-tables` feature, the tables are not included in the binary,
+//! but generated in RAM by `init`, which must be called before using the
+//! encoder and decoder. The FEC encoder and decoder work with externally
+//! provided slices, giving freedom as to how to perform memory allocation, and
+//! do the computations in-place when possible. The memory required for encoding
+//! corresponds to a buffer containing the k SSDV packets of the original image,
+//! and a buffer containing the packet being encoded. The memory required for
+//! decoding corresponds to a buffer containing at least k distinct received
+//! SSDV packets, and another buffer where the k SSDV packets that compose the
+//! original image can be written. These buffers can also be given as byte
+//! buffers with [`Encoder::from_bytes`], [`Encoder::encode_into`] and
+//! [`Decoder::decode_from`]. Besides these buffers, the algorithms use only a
+//! small amount of stack space. The hook registered with [`set_progress_hook`]
+//! is called periodically during long computations, for instance to kick a
+//! watchdog.
 //!
 //! A simple CLI application that can perform encoding and decoding can be built
 //! with the `cli` feature, which is enabled by default.
+//!
+//! The `alloc` feature enables the `receiver` module, which contains a sans-IO
+//! state machine for the reception of several images, and the `transcode`
+//! module, which converts SSDV packets to JPEG images in pure Rust. It also
+//! enables [`OwnedEncoder`], which owns the packets of the image, and the
+//! `format` module, which encodes and decodes packets given as bytes in a
+//! format chosen at runtime.
+//!
+//! The `embedded-io` feature enables the `packet_io` module, which reads and
+//! writes packets using the traits of the `embedded-io` crate, with
+//! resynchronization of the packet boundaries.
+//!
+//! The `defmt` feature implements `defmt::Format` for the errors, the decoding
+//! statistics and the packets, which are formatted as a summary of their
+//! header, for logging in firmware.
+//!
+//! The `async-embedded` feature adds async variants of the encoding and
+//! decoding functions that yield to the executor periodically, so that
+//! single-core async firmware, such as that based on Embassy or RTIC, can
+//! service other tasks while they run. This feature does not require `std` or
+//! any particular executor.
+//!
+//! The `heapless` feature adds `PacketQueue`, a fixed-capacity queue of packets
+//! that is split into a producer and a consumer, to pass packets between
+//! interrupt handlers and tasks without locking.
+//!
+//! The `static-cell` feature adds `StaticEncoder` and `StaticDecoder`, which
+//! own `'static` buffers, such as those of a `static_cell::StaticCell`, so that
+//! they can be stored in RTIC resources or Embassy tasks without lifetimes.
+//!
+//! The `panic-free` feature marks builds that must not contain panicking
+//! paths. It does not remove any API. The functions that panic on invalid
+//! arguments, which are `encode_range`, `StreamingDecoder::decode_partial` and
+//! the `/` operator of the Galois fields, have the fallible counterparts
+//! `try_encode_range`, `try_decode_partial` and `checked_div`, which panic-free
+//! firmware should use instead. Together with `panic = "abort"`, this keeps the
+//! panic formatting machinery out of firmware builds.
+//!
+//! The `cycle-count` feature measures the execution time of the encoder setup,
+//! the encoding of each packet and the decoding, using the DWT cycle counter on
+//! Cortex-M and `std::time::Instant` on other targets. The last and maximum
+//! times of each operation are given by `execution_time`, to check the
+//! worst-case execution times against the schedule of the firmware.
+//!
+//! The `serde` feature implements the `serde` traits for the packets and the
+//! decoder options. The `postcard` feature uses them to save and restore the
+//! state of a `StreamingDecoder` or a `Receiver` with partially received
+//! images.
+//!
+//! The `ssdv-c` feature enables the `ssdv_c` module, which converts between
+//! JPEG images and SSDV packets using the ssdv C library.
+//!
+//! The `async` feature enables the `async_io` module, which contains a
+//! `Stream` adapter that decodes images from a stream of received frames and a
+//! `Sink` that encodes images and sends their packets at a paced rate, for
+//! integration in async applications based on tokio. It also enables the async
+//! flavors of the packet sources and sinks of the `transport` module.
+//!
+//! The `transport` feature enables the `transport` module, with the packet
+//! sources and sinks, together with the `kiss`, `net` and `agwpe` modules,
+//! which also have their own features. The `relay` feature enables the
+//! `relay` module. The CLI application enables all of them.
+//!
+//! The `upload` feature enables the `upload` module, which contains an async
+//! client that uploads received packets to an SSDV server.
+//!
+//! The `pmt` feature enables the `pmt` module, which parses the PDUs emitted by
+//! GNU Radio deframers, such as those of gr-satellites, and maps them to
+//! packets with the reception time, frequency and SNR given in their metadata.
+//!
+//! The `image` feature enables the `verify` module, which checks whether
+//! decoded images can be parsed by the `image` crate. With this feature, the
+//! reports of the CLI include this verdict.
 
 #![warn(missing_docs)]
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "agwpe")]
+pub mod agwpe;
+#[cfg(feature = "async")]
+pub mod async_io;
+mod bounds;
 #[cfg(feature = "cli")]
 pub mod cli;
 
 mod crc;
+pub use crc::{crc32, crc32_standard};
+pub mod encap;
 mod fec;
-pub use fec::{Decoder, DecoderError, Encoder, EncoderError};
+#[cfg(feature = "alloc")]
+pub use fec::OwnedEncoder;
+pub use fec::{
+    ChunkScratch, DecodeStats, Decoder, DecoderError, DecoderOptions, Encoder, EncoderError,
+    HeaderOverrides, PacketChunks, PacketWords, ReadOnlyEncoder,
+};
+#[cfg(feature = "alloc")]
+pub mod format;
 mod gf64k;
+#[cfg(feature = "alloc")]
+mod image_packets;
+#[cfg(feature = "ram-tables")]
+pub use gf64k::init;
 pub use gf64k::{GF256, GF64K};
+#[cfg(feature = "std")]
+mod images;
+#[cfg(feature = "std")]
+pub use images::{DecodedImage, ImageSet, ImageStats, PacketMetadata, PushResult, ReceivedPacket};
+#[cfg(feature = "kiss")]
+pub mod kiss;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "embedded-io")]
+pub mod packet_io;
+#[cfg(feature = "postcard")]
+mod persist;
+#[cfg(feature = "postcard")]
+pub use persist::StateError;
+#[cfg(feature = "pmt")]
+pub mod pmt;
+mod progress;
+pub use progress::{set_progress_hook, Operation, Progress};
+#[cfg(feature = "heapless")]
+mod queue;
+#[cfg(feature = "heapless")]
+pub use queue::{PacketConsumer, PacketProducer, PacketQueue};
+#[cfg(feature = "alloc")]
+pub mod receiver;
+#[cfg(feature = "relay")]
+pub mod relay;
+#[cfg(any(feature = "ssdv-c", feature = "upload"))]
+mod rs8;
+mod scheduler;
+pub use scheduler::FountainScheduler;
+#[cfg(feature = "static-cell")]
+mod singleton;
+#[cfg(feature = "static-cell")]
+pub use singleton::{StaticDecoder, StaticEncoder};
 mod ssdv;
-pub use ssdv::{SSDVPacket, SSDV_DATA_LEN, SSDV_PACKET_LEN};
+pub use ssdv::{
+    encode_callsign, Packet, PacketError, SSDVNoFecPacket, SSDVPacket, SSDV_DATA_LEN,
+    SSDV_NO_FEC_DATA_LEN, SSDV_NO_FEC_PACKET_LEN, SSDV_PACKET_LEN,
+};
+#[cfg(feature = "ssdv-c")]
+pub mod ssdv_c;
+#[cfg(any(feature = "ssdv-c", feature = "upload"))]
+mod standard;
+mod stream;
+pub use stream::{PushStatus, StreamingDecoder};
+mod timing;
+#[cfg(feature = "cycle-count")]
+pub use timing::{execution_time, reset_execution_times, ExecutionTime};
+#[cfg(feature = "alloc")]
+pub mod transcode;
+#[cfg(feature = "transport")]
+pub mod transport;
+#[cfg(feature = "upload")]
+pub mod upload;
+#[cfg(feature = "image")]
+pub mod verify;
+#[cfg(feature = "async-embedded")]
+mod yield_now;
 
 #[cfg(test)]
 mod test_data;
@@ -1,5 +1,5 @@
-use anyhow::Result;
+use std::process::ExitCode;
 
-pub fn main() -> Result<()> {
-    ssdv_fec::cli::run()
+pub fn main() -> ExitCode {
+    ssdv_fec::cli::main()
 }
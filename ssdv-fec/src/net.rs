@@ -0,0 +1,201 @@
+//! UDP transport of packets.
+//!
+//! This module contains [`PacketSocket`], a UDP socket that sends and receives
+//! one SSDV packet per datagram, which is the format used by the `listen` and
+//! `transmit` subcommands of the CLI, and by many software modems and ground
+//! station applications.
+//!
+//! This module is only available with the `net` feature.
+
+use crate::Packet;
+use std::{
+    fmt,
+    io::{self, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+// Maximum size of a UDP datagram
+const MAX_DATAGRAM_LEN: usize = 65536;
+
+/// UDP socket for SSDV packets.
+///
+/// Each packet is sent in a separate datagram. Received datagrams that do not
+/// have the length of a packet are discarded by [`PacketSocket::recv_packet`].
+/// Optionally, the socket can also validate the packets with
+/// [`Packet::validate`], discarding received packets that are not valid and
+/// refusing to send them.
+///
+/// Datagrams containing other formats, such as KISS frames, can be sent and
+/// received with [`PacketSocket::send_datagram`] and
+/// [`PacketSocket::recv_datagram`].
+pub struct PacketSocket {
+    socket: UdpSocket,
+    validation: bool,
+    discarded: usize,
+    buffer: Box<[u8]>,
+}
+
+impl fmt::Debug for PacketSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketSocket")
+            .field("socket", &self.socket)
+            .field("validation", &self.validation)
+            .field("discarded", &self.discarded)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PacketSocket {
+    /// Creates a socket bound to an address.
+    ///
+    /// The address `0.0.0.0:0` can be used to send packets from an arbitrary
+    /// port.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<PacketSocket> {
+        Ok(PacketSocket::new(UdpSocket::bind(addr)?))
+    }
+
+    /// Creates a packet socket from a UDP socket.
+    ///
+    /// Packet validation is disabled.
+    pub fn new(socket: UdpSocket) -> PacketSocket {
+        PacketSocket {
+            socket,
+            validation: false,
+            discarded: 0,
+            buffer: vec![0; MAX_DATAGRAM_LEN].into_boxed_slice(),
+        }
+    }
+
+    /// Enables or disables packet validation.
+    pub fn with_validation(mut self, validation: bool) -> PacketSocket {
+        self.validation = validation;
+        self
+    }
+
+    /// Connects the socket to a remote address.
+    ///
+    /// The socket then sends packets to this address and only receives packets
+    /// from it.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        self.socket.connect(addr)
+    }
+
+    /// Returns a reference to the UDP socket.
+    ///
+    /// This can be used to set timeouts or to get the local address.
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the UDP socket.
+    pub fn into_inner(self) -> UdpSocket {
+        self.socket
+    }
+
+    /// Returns the number of received datagrams that have been discarded by
+    /// [`PacketSocket::recv_packet`].
+    pub fn discarded(&self) -> usize {
+        self.discarded
+    }
+
+    /// Sends a datagram to the address to which the socket is connected.
+    pub fn send_datagram(&self, datagram: &[u8]) -> io::Result<()> {
+        self.socket.send(datagram)?;
+        Ok(())
+    }
+
+    /// Sends a packet to the address to which the socket is connected.
+    ///
+    /// If validation is enabled and the packet is not valid, an error with
+    /// [`ErrorKind::InvalidInput`] is returned.
+    pub fn send_packet<P: Packet>(&self, packet: &P) -> io::Result<()> {
+        self.check(packet)?;
+        self.send_datagram(packet.as_bytes())
+    }
+
+    /// Sends a packet to an address.
+    ///
+    /// If validation is enabled and the packet is not valid, an error with
+    /// [`ErrorKind::InvalidInput`] is returned.
+    pub fn send_packet_to<P: Packet, A: ToSocketAddrs>(
+        &self,
+        packet: &P,
+        addr: A,
+    ) -> io::Result<()> {
+        self.check(packet)?;
+        self.socket.send_to(packet.as_bytes(), addr)?;
+        Ok(())
+    }
+
+    fn check<P: Packet>(&self, packet: &P) -> io::Result<()> {
+        if self.validation {
+            packet
+                .validate()
+                .map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+        }
+        Ok(())
+    }
+
+    /// Receives a datagram.
+    ///
+    /// Returns the contents of the datagram and the address that sent it.
+    pub fn recv_datagram(&mut self) -> io::Result<(&[u8], SocketAddr)> {
+        let (len, addr) = self.socket.recv_from(&mut self.buffer)?;
+        Ok((&self.buffer[..len], addr))
+    }
+
+    /// Receives a packet.
+    ///
+    /// This blocks until a datagram containing a packet is received, and
+    /// returns the packet and the address that sent it. Datagrams that do not
+    /// have the length of a packet, or that contain an invalid packet if
+    /// validation is enabled, are discarded. If the socket has a read timeout,
+    /// the timeout applies to each datagram.
+    pub fn recv_packet<P: Packet>(&mut self) -> io::Result<(P, SocketAddr)> {
+        loop {
+            let validation = self.validation;
+            let (datagram, addr) = self.recv_datagram()?;
+            if datagram.len() == P::LEN {
+                let mut packet = P::zeroed();
+                packet.as_bytes_mut().copy_from_slice(datagram);
+                if !validation || packet.validate().is_ok() {
+                    return Ok((packet, addr));
+                }
+            }
+            self.discarded += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, SSDVPacket};
+
+    #[test]
+    fn send_recv() {
+        let packets = img_230_packets();
+        let mut receiver = PacketSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .with_validation(true);
+        let addr = receiver.get_ref().local_addr().unwrap();
+        let sender = PacketSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(addr).unwrap();
+        let mut corrupted = packets[1];
+        corrupted.0[20] ^= 1;
+
+        sender.send_datagram(&[1, 2, 3]).unwrap();
+        sender.send_packet(&corrupted).unwrap();
+        sender.send_packet(&packets[0]).unwrap();
+        let (packet, from) = receiver.recv_packet::<SSDVPacket>().unwrap();
+        assert_eq!(packet, packets[0]);
+        assert_eq!(from, sender.get_ref().local_addr().unwrap());
+        assert_eq!(receiver.discarded(), 2);
+
+        let sender = sender.with_validation(true);
+        let err = sender.send_packet(&corrupted).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        sender.send_packet_to(&packets[1], addr).unwrap();
+        assert_eq!(receiver.recv_packet::<SSDVPacket>().unwrap().0, packets[1]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Packet I/O over `embedded-io`.
+//!
+//! This module contains helpers to read and write whole packets using the
+//! [`Read`] and [`Write`] traits of the `embedded-io` crate, which are
+//! implemented by the UARTs of many embedded HAL crates. It allows firmware to
+//! stream packets to and from the encoder and decoder without using `std` or an
+//! allocator.
+//!
+//! This module is only available with the `embedded-io` feature.
+
+use crate::Packet;
+use embedded_io::{Read, ReadExactError, Write};
+
+/// Writes a packet.
+pub fn write_packet<W: Write, P: Packet>(writer: &mut W, packet: &P) -> Result<(), W::Error> {
+    writer.write_all(packet.as_bytes())
+}
+
+/// Reads a packet.
+///
+/// This reads exactly the length of a packet, without checking whether the
+/// packet is valid. [`PacketReader`] should be used if the packet boundaries
+/// can be lost.
+pub fn read_packet<R: Read, P: Packet>(reader: &mut R) -> Result<P, ReadExactError<R::Error>> {
+    let mut packet = P::zeroed();
+    reader.read_exact(packet.as_bytes_mut())?;
+    Ok(packet)
+}
+
+/// Reader of packets that resynchronizes the packet boundaries.
+///
+/// Bytes can be lost or inserted between packets in a serial link, so the
+/// reader looks for the packet boundaries by sliding a window of the size of a
+/// packet over the received bytes one byte at a time, until the window
+/// contains a packet with the correct fixed fields (such as the sync byte of
+/// the no-FEC format) and a valid CRC-32. Packets with a wrong CRC-32 are
+/// therefore discarded.
+///
+/// The reader only stores one packet, so it does not need an allocator.
+#[derive(Debug)]
+pub struct PacketReader<R, P> {
+    reader: R,
+    window: P,
+    filled: usize,
+    skipped: usize,
+}
+
+impl<R, P: Packet> PacketReader<R, P> {
+    /// Creates a new packet reader.
+    pub fn new(reader: R) -> PacketReader<R, P> {
+        PacketReader {
+            reader,
+            window: P::zeroed(),
+            filled: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Returns the number of bytes that have been discarded because they do not
+    /// belong to any valid packet.
+    pub fn skipped_bytes(&self) -> usize {
+        self.skipped
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the inner reader.
+    ///
+    /// The bytes that have been read but do not form a packet yet are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read, P: Packet> PacketReader<R, P> {
+    /// Reads the next valid packet.
+    ///
+    /// If the inner reader returns an error, the bytes read so far are kept,
+    /// so this function can be called again to continue reading the packet.
+    pub fn read_packet(&mut self) -> Result<P, ReadExactError<R::Error>> {
+        loop {
+            while self.filled < P::LEN {
+                let len = self
+                    .reader
+                    .read(&mut self.window.as_bytes_mut()[self.filled..])
+                    .map_err(ReadExactError::Other)?;
+                if len == 0 {
+                    return Err(ReadExactError::UnexpectedEof);
+                }
+                self.filled += len;
+            }
+            // The fixed fields are cheaper to check than the CRC-32.
+            if self.window.fixed_fields_ok() && self.window.compute_crc32() == self.window.crc32() {
+                self.filled = 0;
+                return Ok(self.window);
+            }
+            self.window.as_bytes_mut().copy_within(1.., 0);
+            self.filled -= 1;
+            self.skipped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        test_data::{img_230_packets, IMG_230_SSDV},
+        SSDVNoFecPacket, SSDVPacket,
+    };
+
+    #[test]
+    fn resync() {
+        let packets = img_230_packets();
+        let mut data = vec![0; IMG_230_SSDV.len() + 10];
+        let mut writer = &mut data[..];
+        writer.write_all(&[1, 2, 3]).unwrap();
+        for (j, packet) in packets.iter().enumerate() {
+            write_packet(&mut writer, packet).unwrap();
+            if j == 10 {
+                writer.write_all(&[4; 7]).unwrap();
+            }
+        }
+        // a truncated packet at the end
+        data.truncate(data.len() - 5);
+
+        let mut reader = PacketReader::<_, SSDVPacket>::new(&data[..]);
+        for packet in &packets[..packets.len() - 1] {
+            assert_eq!(reader.read_packet().unwrap(), *packet);
+        }
+        assert!(matches!(
+            reader.read_packet(),
+            Err(ReadExactError::UnexpectedEof)
+        ));
+        assert_eq!(reader.skipped_bytes(), 10);
+        assert_eq!(
+            read_packet::<_, SSDVPacket>(&mut &data[3..]).unwrap(),
+            packets[0]
+        );
+    }
+
+    #[test]
+    fn resync_no_fec() {
+        let mut packet = SSDVNoFecPacket::zeroed();
+        packet.0[..2].copy_from_slice(&[0x55, 0x67]);
+        packet.0[10..20].fill(0x55);
+        packet.set_crc32(packet.compute_crc32());
+        let mut data = [0x55; 3 + 2 * SSDVNoFecPacket::LEN];
+        data[3..3 + SSDVNoFecPacket::LEN].copy_from_slice(&packet.0);
+        data[3 + SSDVNoFecPacket::LEN..].copy_from_slice(&packet.0);
+        let mut reader = PacketReader::<_, SSDVNoFecPacket>::new(&data[..]);
+        assert_eq!(reader.read_packet().unwrap(), packet);
+        assert_eq!(reader.read_packet().unwrap(), packet);
+        assert_eq!(reader.skipped_bytes(), 3);
+    }
+}
@@ -0,0 +1,54 @@
+//! Persistence of the decoder state.
+//!
+//! The state of a [`StreamingDecoder`](crate::StreamingDecoder) and of a
+//! [`Receiver`](crate::receiver::Receiver) can be saved using postcard, so that
+//! the partially received images survive a restart of the application. The
+//! state starts with a format version, so that the state saved by an
+//! incompatible version of this crate is rejected.
+
+use core::fmt;
+
+// Version of the format of the saved state.
+pub(crate) const STATE_VERSION: u8 = 1;
+
+/// Error produced when saving or restoring the decoder state.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StateError {
+    /// The state could not be serialized or deserialized.
+    Postcard(postcard::Error),
+    /// The state was saved with an incompatible format version.
+    UnsupportedVersion(u8),
+    /// The buffer is too small for the packets in the state.
+    BufferTooSmall,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Postcard(err) => {
+                write!(f, "could not serialize or deserialize the state: {err}")
+            }
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported state version {version}")
+            }
+            StateError::BufferTooSmall => f.write_str("buffer too small for the saved packets"),
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+impl From<postcard::Error> for StateError {
+    fn from(err: postcard::Error) -> StateError {
+        StateError::Postcard(err)
+    }
+}
+
+// Checks the format version of a saved state.
+pub(crate) fn check_version(version: u8) -> Result<(), StateError> {
+    if version == STATE_VERSION {
+        Ok(())
+    } else {
+        Err(StateError::UnsupportedVersion(version))
+    }
+}
@@ -0,0 +1,516 @@
+//! GNU Radio PDUs.
+//!
+//! The deframers of [gr-satellites](https://github.com/daniestevez/gr-satellites)
+//! and other GNU Radio flowgraphs emit the received frames as PDUs, which are
+//! pairs of a metadata dictionary and a vector of bytes. When the PDUs leave
+//! the flowgraph, for instance through the ZMQ message blocks, they are
+//! serialized in the PMT format of GNU Radio. This module parses and
+//! serializes these PDUs, and maps them to [`ReceivedPacket`]s, keeping the
+//! reception time, frequency and SNR given in the metadata.
+//!
+//! This module is only available with the `pmt` feature.
+
+use crate::{PacketMetadata, ReceivedPacket, SSDVPacket, SSDV_PACKET_LEN};
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+// Tags of the PMT serialization format
+const PST_TRUE: u8 = 0x00;
+const PST_FALSE: u8 = 0x01;
+const PST_SYMBOL: u8 = 0x02;
+const PST_INT32: u8 = 0x03;
+const PST_DOUBLE: u8 = 0x04;
+const PST_COMPLEX: u8 = 0x05;
+const PST_NULL: u8 = 0x06;
+const PST_PAIR: u8 = 0x07;
+const PST_VECTOR: u8 = 0x08;
+const PST_DICT: u8 = 0x09;
+const PST_UNIFORM_VECTOR: u8 = 0x0a;
+const PST_UINT64: u8 = 0x0b;
+const PST_TUPLE: u8 = 0x0c;
+const PST_INT64: u8 = 0x0d;
+
+/// Item type of uniform vectors of bytes.
+pub const UVI_U8: u8 = 0x00;
+
+// Keys of the metadata entries used for the packet metadata, in order of
+// preference
+const TIMESTAMP_KEYS: &[&str] = &["time", "rx_time", "timestamp"];
+const FREQUENCY_KEYS: &[&str] = &["frequency", "freq"];
+const SNR_KEYS: &[&str] = &["snr"];
+
+/// Polymorphic type (PMT) value.
+///
+/// This supports the PMT types that can appear in the PDUs emitted by
+/// deframers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pmt {
+    /// Boolean.
+    Bool(bool),
+    /// Symbol (string).
+    Symbol(String),
+    /// 32-bit integer.
+    Int32(i32),
+    /// 64-bit integer.
+    Int64(i64),
+    /// 64-bit unsigned integer.
+    Uint64(u64),
+    /// Double precision floating point number.
+    Double(f64),
+    /// Complex number, given by its real and imaginary parts.
+    Complex(f64, f64),
+    /// Empty list.
+    Null,
+    /// Pair.
+    Pair(Box<Pmt>, Box<Pmt>),
+    /// Vector of PMTs.
+    Vector(Vec<Pmt>),
+    /// Tuple of PMTs.
+    Tuple(Vec<Pmt>),
+    /// Dictionary, given by its key-value pairs.
+    Dict(Vec<(Pmt, Pmt)>),
+    /// Uniform vector.
+    UniformVector {
+        /// Item type, such as [`UVI_U8`].
+        item_type: u8,
+        /// Items in big-endian byte order.
+        data: Vec<u8>,
+    },
+}
+
+/// Error produced when parsing a PMT.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PmtError {
+    /// The serialized PMT is truncated.
+    Truncated,
+    /// The serialized PMT has a tag that is not supported.
+    UnsupportedTag(u8),
+    /// The uniform vector has an item type that is not supported.
+    UnsupportedItemType(u8),
+    /// The dictionary contains an entry that is not a pair.
+    InvalidDict,
+    /// The symbol is not valid UTF-8.
+    InvalidSymbol,
+    /// There is data after the end of the PMT.
+    TrailingData,
+    /// The PMT is not a PDU with a vector of bytes.
+    NotPdu,
+    /// The PDU does not have the length of a packet.
+    WrongLength(usize),
+}
+
+impl fmt::Display for PmtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PmtError::Truncated => f.write_str("truncated PMT"),
+            PmtError::UnsupportedTag(tag) => write!(f, "unsupported PMT tag {tag:#04x}"),
+            PmtError::UnsupportedItemType(item_type) => {
+                write!(f, "unsupported uniform vector item type {item_type:#04x}")
+            }
+            PmtError::InvalidDict => f.write_str("invalid PMT dictionary"),
+            PmtError::InvalidSymbol => f.write_str("invalid PMT symbol"),
+            PmtError::TrailingData => f.write_str("trailing data after the PMT"),
+            PmtError::NotPdu => f.write_str("the PMT is not a PDU"),
+            PmtError::WrongLength(len) => {
+                write!(f, "PDU of {len} bytes (expected {SSDV_PACKET_LEN} bytes)")
+            }
+        }
+    }
+}
+
+impl core::error::Error for PmtError {}
+
+impl Pmt {
+    /// Parses a serialized PMT.
+    pub fn parse(data: &[u8]) -> Result<Pmt, PmtError> {
+        let mut reader = Reader(data);
+        let pmt = reader.pmt()?;
+        if !reader.0.is_empty() {
+            return Err(PmtError::TrailingData);
+        }
+        Ok(pmt)
+    }
+
+    /// Serializes the PMT.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.serialize_into(&mut out);
+        out
+    }
+
+    fn serialize_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Pmt::Bool(true) => out.push(PST_TRUE),
+            Pmt::Bool(false) => out.push(PST_FALSE),
+            Pmt::Symbol(symbol) => {
+                out.push(PST_SYMBOL);
+                out.extend_from_slice(&(symbol.len() as u16).to_be_bytes());
+                out.extend_from_slice(symbol.as_bytes());
+            }
+            Pmt::Int32(x) => {
+                out.push(PST_INT32);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            Pmt::Int64(x) => {
+                out.push(PST_INT64);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            Pmt::Uint64(x) => {
+                out.push(PST_UINT64);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            Pmt::Double(x) => {
+                out.push(PST_DOUBLE);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            Pmt::Complex(re, im) => {
+                out.push(PST_COMPLEX);
+                out.extend_from_slice(&re.to_be_bytes());
+                out.extend_from_slice(&im.to_be_bytes());
+            }
+            Pmt::Null => out.push(PST_NULL),
+            Pmt::Pair(car, cdr) => {
+                out.push(PST_PAIR);
+                car.serialize_into(out);
+                cdr.serialize_into(out);
+            }
+            Pmt::Vector(items) | Pmt::Tuple(items) => {
+                out.push(if matches!(self, Pmt::Vector(_)) {
+                    PST_VECTOR
+                } else {
+                    PST_TUPLE
+                });
+                out.extend_from_slice(&(items.len() as u32).to_be_bytes());
+                for item in items {
+                    item.serialize_into(out);
+                }
+            }
+            Pmt::Dict(entries) => {
+                // A dictionary is a list of key-value pairs, in which each
+                // node is tagged as a dictionary.
+                for (key, value) in entries {
+                    out.push(PST_DICT);
+                    out.push(PST_PAIR);
+                    key.serialize_into(out);
+                    value.serialize_into(out);
+                }
+                out.push(PST_NULL);
+            }
+            Pmt::UniformVector { item_type, data } => {
+                out.push(PST_UNIFORM_VECTOR);
+                out.push(*item_type);
+                // the item size has been checked when parsing, so the length
+                // is exact for valid uniform vectors
+                let len = data.len() / item_size(*item_type).unwrap_or(1);
+                out.extend_from_slice(&(len as u32).to_be_bytes());
+                // one byte of padding, as GNU Radio does
+                out.extend_from_slice(&[1, 0]);
+                out.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+// Returns the size in bytes of the items of a uniform vector.
+fn item_size(item_type: u8) -> Option<usize> {
+    match item_type {
+        // u8, s8
+        0x00 | 0x01 => Some(1),
+        // u16, s16
+        0x02 | 0x03 => Some(2),
+        // u32, s32, f32
+        0x04 | 0x05 | 0x08 => Some(4),
+        // u64, s64, f64, c32
+        0x06 | 0x07 | 0x09 | 0x0a => Some(8),
+        // c64
+        0x0b => Some(16),
+        _ => None,
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], PmtError> {
+        if self.0.len() < len {
+            return Err(PmtError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], PmtError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, PmtError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, PmtError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> Result<f64, PmtError> {
+        Ok(f64::from_be_bytes(self.array()?))
+    }
+
+    fn pmt(&mut self) -> Result<Pmt, PmtError> {
+        Ok(match self.u8()? {
+            PST_TRUE => Pmt::Bool(true),
+            PST_FALSE => Pmt::Bool(false),
+            PST_SYMBOL => {
+                let len = u16::from_be_bytes(self.array()?);
+                let symbol = self.take(usize::from(len))?;
+                Pmt::Symbol(
+                    String::from_utf8(symbol.to_vec()).map_err(|_| PmtError::InvalidSymbol)?,
+                )
+            }
+            PST_INT32 => Pmt::Int32(i32::from_be_bytes(self.array()?)),
+            PST_INT64 => Pmt::Int64(i64::from_be_bytes(self.array()?)),
+            PST_UINT64 => Pmt::Uint64(u64::from_be_bytes(self.array()?)),
+            PST_DOUBLE => Pmt::Double(self.f64()?),
+            PST_COMPLEX => Pmt::Complex(self.f64()?, self.f64()?),
+            PST_NULL => Pmt::Null,
+            PST_PAIR => Pmt::Pair(Box::new(self.pmt()?), Box::new(self.pmt()?)),
+            tag @ (PST_VECTOR | PST_TUPLE) => {
+                let len = self.u32()?;
+                // the length is not trusted to preallocate
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.pmt()?);
+                }
+                if tag == PST_VECTOR {
+                    Pmt::Vector(items)
+                } else {
+                    Pmt::Tuple(items)
+                }
+            }
+            PST_DICT => {
+                let mut entries = Vec::new();
+                loop {
+                    match self.pmt()? {
+                        Pmt::Pair(key, value) => entries.push((*key, *value)),
+                        _ => return Err(PmtError::InvalidDict),
+                    }
+                    match self.u8()? {
+                        PST_DICT => (),
+                        PST_NULL => break,
+                        _ => return Err(PmtError::InvalidDict),
+                    }
+                }
+                Pmt::Dict(entries)
+            }
+            PST_UNIFORM_VECTOR => {
+                let item_type = self.u8()?;
+                let size = item_size(item_type).ok_or(PmtError::UnsupportedItemType(item_type))?;
+                let len = usize::try_from(self.u32()?).unwrap();
+                let padding = self.u8()?;
+                self.take(usize::from(padding))?;
+                let data = self
+                    .take(len.checked_mul(size).ok_or(PmtError::Truncated)?)?
+                    .to_vec();
+                Pmt::UniformVector { item_type, data }
+            }
+            tag => return Err(PmtError::UnsupportedTag(tag)),
+        })
+    }
+}
+
+/// PDU, formed by a metadata dictionary and a vector of bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pdu {
+    /// Metadata entries.
+    pub metadata: Vec<(Pmt, Pmt)>,
+    /// Data.
+    pub data: Vec<u8>,
+}
+
+impl Pdu {
+    /// Creates a PDU without metadata.
+    pub fn new(data: Vec<u8>) -> Pdu {
+        Pdu {
+            metadata: Vec::new(),
+            data,
+        }
+    }
+
+    /// Parses a serialized PDU.
+    pub fn parse(data: &[u8]) -> Result<Pdu, PmtError> {
+        let Pmt::Pair(car, cdr) = Pmt::parse(data)? else {
+            return Err(PmtError::NotPdu);
+        };
+        let metadata = match *car {
+            Pmt::Dict(entries) => entries,
+            Pmt::Null => Vec::new(),
+            _ => return Err(PmtError::NotPdu),
+        };
+        match *cdr {
+            Pmt::UniformVector {
+                item_type: UVI_U8,
+                data,
+            } => Ok(Pdu { metadata, data }),
+            _ => Err(PmtError::NotPdu),
+        }
+    }
+
+    /// Serializes the PDU.
+    pub fn serialize(&self) -> Vec<u8> {
+        let metadata = if self.metadata.is_empty() {
+            Pmt::Null
+        } else {
+            Pmt::Dict(self.metadata.clone())
+        };
+        let data = Pmt::UniformVector {
+            item_type: UVI_U8,
+            data: self.data.clone(),
+        };
+        Pmt::Pair(Box::new(metadata), Box::new(data)).serialize()
+    }
+
+    /// Returns the value of the metadata entry whose key is a symbol.
+    pub fn get(&self, key: &str) -> Option<&Pmt> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| matches!(k, Pmt::Symbol(k) if k == key))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the packet metadata given in the metadata dictionary.
+    ///
+    /// The reception time is taken from the `time`, `rx_time` or `timestamp`
+    /// entries, which can contain the seconds since the UNIX epoch, a UHD
+    /// timestamp (a tuple of the integer and fractional seconds) or an RFC 3339
+    /// time. The frequency in Hz is taken from the `frequency` or `freq`
+    /// entries, and the SNR in dB from the `snr` entry. Entries with other
+    /// types are ignored.
+    pub fn packet_metadata(&self) -> PacketMetadata {
+        let find = |keys: &[&str]| keys.iter().find_map(|key| self.get(key));
+        PacketMetadata {
+            timestamp: find(TIMESTAMP_KEYS).and_then(pmt_time),
+            frequency: find(FREQUENCY_KEYS).and_then(pmt_number),
+            snr: find(SNR_KEYS).and_then(pmt_number),
+        }
+    }
+
+    /// Returns the packet contained in the PDU, together with its metadata.
+    pub fn received_packet(&self) -> Result<ReceivedPacket, PmtError> {
+        let packet = <[u8; SSDV_PACKET_LEN]>::try_from(&self.data[..])
+            .map_err(|_| PmtError::WrongLength(self.data.len()))?;
+        Ok(ReceivedPacket::new(SSDVPacket(packet)).with_metadata(self.packet_metadata()))
+    }
+}
+
+// Returns the value of a numeric PMT.
+fn pmt_number(pmt: &Pmt) -> Option<f64> {
+    match *pmt {
+        Pmt::Double(x) => Some(x),
+        Pmt::Int32(x) => Some(x.into()),
+        Pmt::Int64(x) => Some(x as f64),
+        Pmt::Uint64(x) => Some(x as f64),
+        _ => None,
+    }
+}
+
+// Returns the time given by a PMT.
+fn pmt_time(pmt: &Pmt) -> Option<SystemTime> {
+    let seconds = match pmt {
+        Pmt::Symbol(time) => return humantime::parse_rfc3339_weak(time).ok(),
+        Pmt::Tuple(items) => match &items[..] {
+            [secs, frac] => pmt_number(secs)? + pmt_number(frac)?,
+            _ => return None,
+        },
+        pmt => pmt_number(pmt)?,
+    };
+    SystemTime::UNIX_EPOCH.checked_add(Duration::try_from_secs_f64(seconds).ok()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::IMG_230_SSDV;
+
+    fn symbol(s: &str) -> Pmt {
+        Pmt::Symbol(s.to_string())
+    }
+
+    #[test]
+    fn round_trip() {
+        let pmt = Pmt::Pair(
+            Box::new(Pmt::Dict(vec![
+                (symbol("a"), Pmt::Bool(true)),
+                (symbol("b"), Pmt::Int32(-3)),
+                (symbol("c"), Pmt::Uint64(1 << 40)),
+                (symbol("d"), Pmt::Complex(1.0, -2.0)),
+                (symbol("e"), Pmt::Vector(vec![Pmt::Int64(5), Pmt::Null])),
+                (
+                    symbol("f"),
+                    Pmt::UniformVector {
+                        item_type: 0x08,
+                        data: vec![0; 12],
+                    },
+                ),
+            ])),
+            Box::new(Pmt::Tuple(vec![Pmt::Double(0.5), symbol("g")])),
+        );
+        assert_eq!(Pmt::parse(&pmt.serialize()), Ok(pmt));
+        assert_eq!(Pmt::parse(&[PST_PAIR, PST_NULL]), Err(PmtError::Truncated));
+        assert_eq!(
+            Pmt::parse(&[PST_NULL, PST_NULL]),
+            Err(PmtError::TrailingData)
+        );
+    }
+
+    #[test]
+    fn gr_satellites_pdu() {
+        let packet = &IMG_230_SSDV[..SSDV_PACKET_LEN];
+        // PDU serialized by GNU Radio with the metadata of a gr-satellites
+        // deframer
+        let mut message = vec![
+            PST_PAIR, PST_DICT, PST_PAIR, PST_SYMBOL, 0, 4, b't', b'i', b'm', b'e', PST_SYMBOL, 0,
+            20,
+        ];
+        message.extend_from_slice(b"2024-05-01T12:00:00Z");
+        message.extend_from_slice(&[PST_DICT, PST_PAIR, PST_SYMBOL, 0, 9]);
+        message.extend_from_slice(b"frequency");
+        message.push(PST_DOUBLE);
+        message.extend_from_slice(&435.6e6_f64.to_be_bytes());
+        message.extend_from_slice(&[PST_DICT, PST_PAIR, PST_SYMBOL, 0, 3, b's', b'n', b'r']);
+        message.push(PST_DOUBLE);
+        message.extend_from_slice(&9.5_f64.to_be_bytes());
+        message.extend_from_slice(&[PST_NULL, PST_UNIFORM_VECTOR, UVI_U8, 0, 0, 0, 218, 1, 0]);
+        message.extend_from_slice(packet);
+
+        let pdu = Pdu::parse(&message).unwrap();
+        assert_eq!(pdu.serialize(), message);
+        let received = pdu.received_packet().unwrap();
+        assert_eq!(received.packet.0, packet);
+        assert_eq!(
+            received.metadata,
+            PacketMetadata {
+                timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_564_800)),
+                frequency: Some(435.6e6),
+                snr: Some(9.5),
+            }
+        );
+
+        // UHD timestamp and no metadata
+        let mut pdu = Pdu::new(packet.to_vec());
+        assert!(pdu.received_packet().unwrap().metadata.is_empty());
+        pdu.metadata.push((
+            symbol("rx_time"),
+            Pmt::Tuple(vec![Pmt::Uint64(100), Pmt::Double(0.25)]),
+        ));
+        assert_eq!(
+            pdu.packet_metadata().timestamp,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_millis(100_250))
+        );
+        assert_eq!(
+            Pdu::new(vec![1, 2]).received_packet(),
+            Err(PmtError::WrongLength(2))
+        );
+    }
+}
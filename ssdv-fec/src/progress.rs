@@ -0,0 +1,177 @@
+use core::{
+    mem, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// Long-running operation reported in a [`Progress`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Operation {
+    /// Initialization of an [`Encoder`](crate::Encoder) or a
+    /// [`ReadOnlyEncoder`](crate::ReadOnlyEncoder).
+    EncoderSetup,
+    /// Restoration of the systematic packets by
+    /// [`Encoder::release`](crate::Encoder::release).
+    EncoderRelease,
+    /// Encoding of a FEC packet.
+    Encode,
+    /// Decoding of an image with missing systematic packets.
+    Decode,
+}
+
+/// Progress of a long-running operation.
+///
+/// This is given to the hook registered with [`set_progress_hook`]. The
+/// progress is measured in steps, which take a similar amount of work, so
+/// `done / total` is the fraction of the operation that has been done.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Progress {
+    /// Operation in progress.
+    pub operation: Operation,
+    /// Number of steps done.
+    pub done: u32,
+    /// Total number of steps of the operation.
+    pub total: u32,
+}
+
+// Hook registered with `set_progress_hook`, stored as a data pointer because
+// there are no atomic function pointers. NULL if there is no hook.
+static HOOK: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+
+// Amount of work done between calls to the hook, measured in GF(2¹⁶)
+// operations. On a Cortex-M4 at 80 MHz, this is on the order of 1 ms.
+pub(crate) const PERIOD_WORK: usize = 4096;
+
+/// Registers a hook that is called periodically during long computations.
+///
+/// The encoder and the decoder call `hook` each time that a bounded amount of
+/// work has been done, on the order of 1 ms on a Cortex-M4, so that bare-metal
+/// applications can kick a watchdog or report progress without restructuring
+/// their calls. Passing `None` removes the hook, which is the default. There
+/// is a single hook for all the encoders and decoders.
+///
+/// The hook is called from the thread or interrupt handler that calls the
+/// encoder or decoder, so it must be safe to call from all of them. It must not
+/// encode or decode.
+pub fn set_progress_hook(hook: Option<fn(Progress)>) {
+    let hook = hook.map_or(ptr::null_mut(), |f| f as *mut ());
+    HOOK.store(hook, Ordering::Release);
+}
+
+// Counts the steps and the work done by a long computation and calls the hook
+// each time that PERIOD_WORK operations have been done.
+#[derive(Debug)]
+pub(crate) struct Meter {
+    operation: Operation,
+    done: u32,
+    total: u32,
+    work: usize,
+}
+
+impl Meter {
+    pub(crate) fn new(operation: Operation, total: u32) -> Meter {
+        Meter {
+            operation,
+            done: 0,
+            total,
+            work: 0,
+        }
+    }
+
+    // Accounts for a step that has taken `operations` GF(2¹⁶) operations.
+    // Returns true if the period has elapsed.
+    pub(crate) fn step(&mut self, operations: usize) -> bool {
+        self.done += 1;
+        self.work(operations)
+    }
+
+    // Accounts for `operations` GF(2¹⁶) operations that are not part of a
+    // step. Returns true if the period has elapsed.
+    pub(crate) fn work(&mut self, operations: usize) -> bool {
+        self.work += operations;
+        if self.work < PERIOD_WORK {
+            return false;
+        }
+        self.work = 0;
+        let hook = HOOK.load(Ordering::Acquire);
+        if !hook.is_null() {
+            call_hook(
+                hook,
+                Progress {
+                    operation: self.operation,
+                    done: self.done,
+                    total: self.total,
+                },
+            );
+        }
+        true
+    }
+}
+
+// Checks that each operation runs the number of steps given as its total,
+// which is the bound on its loops given in the bounds module.
+#[cfg(test)]
+impl Drop for Meter {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            assert_eq!(
+                self.done, self.total,
+                "wrong number of steps of {:?}",
+                self.operation
+            );
+        }
+    }
+}
+
+// Calls the hook. This is kept out of line so that the loops that report
+// progress stay small, and so that the stack usage analysis of the
+// erminaz-ssdv-fec crate can identify the only indirect call of the meter.
+#[inline(never)]
+fn call_hook(hook: *mut (), progress: Progress) {
+    // SAFETY: the pointer was obtained from a hook in set_progress_hook.
+    let hook = unsafe { mem::transmute::<*mut (), fn(Progress)>(hook) };
+    hook(progress);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Decoder, Encoder, SSDVPacket};
+    use std::sync::atomic::AtomicUsize;
+
+    static ENCODER_SETUP_CALLS: AtomicUsize = AtomicUsize::new(0);
+    static DECODE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn hook(progress: Progress) {
+        // other tests can run while the hook is registered, so this only
+        // checks that the progress is consistent
+        assert!(progress.done <= progress.total);
+        match progress.operation {
+            Operation::EncoderSetup => ENCODER_SETUP_CALLS.fetch_add(1, Ordering::Relaxed),
+            Operation::Decode => DECODE_CALLS.fetch_add(1, Ordering::Relaxed),
+            _ => 0,
+        };
+    }
+
+    #[test]
+    fn progress_hook() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        set_progress_hook(Some(hook));
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        assert!(ENCODER_SETUP_CALLS.load(Ordering::Relaxed) > 0);
+        let mut packets = vec![SSDVPacket::zeroed(); k];
+        encoder
+            .try_encode_range(u16::try_from(k).unwrap(), &mut packets)
+            .unwrap();
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        // only the last systematic packet is given, to find the image size
+        packets[0] = ssdv[k - 1];
+        Decoder::decode(&mut packets, &mut output).unwrap();
+        assert!(DECODE_CALLS.load(Ordering::Relaxed) > 0);
+        set_progress_hook(None);
+        assert_eq!(output, ssdv);
+    }
+}
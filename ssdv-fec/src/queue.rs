@@ -0,0 +1,200 @@
+use crate::{Packet, PushStatus, StreamingDecoder};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+/// Fixed-capacity queue of packets.
+///
+/// This is a single-producer single-consumer queue based on
+/// [`heapless::spsc::Queue`], which can hold up to `N - 1` packets without
+/// an allocator. It is used to pass packets between an interrupt handler and
+/// a task: the queue is split into a [`PacketProducer`] and a
+/// [`PacketConsumer`], which can be used concurrently from different
+/// execution contexts without locking.
+///
+/// In a receiver, the interrupt handler of the radio enqueues the received
+/// packets, and a task feeds them to a [`StreamingDecoder`] with
+/// [`PacketConsumer::feed`]. In a transmitter, a task encodes packets into the
+/// queue with [`PacketProducer::fill`], and the interrupt handler of the radio
+/// dequeues them as it sends them.
+///
+/// To use the producer and the consumer in an interrupt handler, the queue is
+/// usually stored in a `static_cell::StaticCell` or in an RTIC local
+/// resource, so that splitting it gives a `'static` producer and consumer.
+#[derive(Debug)]
+pub struct PacketQueue<P: Packet, const N: usize> {
+    queue: Queue<P, N>,
+}
+
+/// Producer of a [`PacketQueue`].
+///
+/// This is obtained with [`PacketQueue::split`].
+pub struct PacketProducer<'a, P: Packet> {
+    producer: Producer<'a, P>,
+}
+
+/// Consumer of a [`PacketQueue`].
+///
+/// This is obtained with [`PacketQueue::split`].
+pub struct PacketConsumer<'a, P: Packet> {
+    consumer: Consumer<'a, P>,
+}
+
+impl<P: Packet, const N: usize> PacketQueue<P, N> {
+    /// Creates an empty queue.
+    ///
+    /// This function is `const`, so the queue can be a static variable. `N`
+    /// must be at least 2.
+    pub const fn new() -> PacketQueue<P, N> {
+        PacketQueue {
+            queue: Queue::new(),
+        }
+    }
+
+    /// Splits the queue into a producer and a consumer.
+    pub fn split(&mut self) -> (PacketProducer<'_, P>, PacketConsumer<'_, P>) {
+        let (producer, consumer) = self.queue.split();
+        (PacketProducer { producer }, PacketConsumer { consumer })
+    }
+
+    /// Returns the maximum number of packets that the queue can hold, which is
+    /// `N - 1`.
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+
+    /// Returns the number of packets in the queue.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl<P: Packet, const N: usize> Default for PacketQueue<P, N> {
+    fn default() -> PacketQueue<P, N> {
+        PacketQueue::new()
+    }
+}
+
+impl<P: Packet> PacketProducer<'_, P> {
+    /// Adds a packet to the queue.
+    ///
+    /// If the queue is full, the packet is given back as an error.
+    pub fn enqueue(&mut self, packet: P) -> Result<(), P> {
+        self.producer.enqueue(packet)
+    }
+
+    /// Returns true if there is room for a packet in the queue.
+    pub fn ready(&self) -> bool {
+        self.producer.ready()
+    }
+
+    /// Encodes packets into the queue until it is full.
+    ///
+    /// The packet IDs are taken from `packet_ids`, such as a
+    /// [`FountainScheduler`](crate::FountainScheduler), and each packet is
+    /// generated by calling `encode` with its packet ID, for instance
+    /// `|id, packet| encoder.encode(id, packet)`. No packet IDs are taken from
+    /// the iterator when the queue is full, so the iterator can be used to
+    /// resume the filling later. The function returns the number of packets
+    /// added to the queue.
+    pub fn fill<I, F>(&mut self, packet_ids: &mut I, mut encode: F) -> usize
+    where
+        I: Iterator<Item = u16>,
+        F: FnMut(u16, &mut P),
+    {
+        let mut added = 0;
+        while self.producer.ready() {
+            let Some(packet_id) = packet_ids.next() else {
+                break;
+            };
+            let mut packet = P::zeroed();
+            encode(packet_id, &mut packet);
+            // This cannot fail, because this is the only producer and the
+            // queue was not full.
+            if self.producer.enqueue(packet).is_err() {
+                break;
+            }
+            added += 1;
+        }
+        added
+    }
+}
+
+impl<P: Packet> PacketConsumer<'_, P> {
+    /// Removes the oldest packet from the queue.
+    ///
+    /// If the queue is empty, `None` is returned.
+    pub fn dequeue(&mut self) -> Option<P> {
+        self.consumer.dequeue()
+    }
+
+    /// Returns the number of packets in the queue.
+    pub fn len(&self) -> usize {
+        self.consumer.len()
+    }
+
+    /// Returns true if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        !self.consumer.ready()
+    }
+
+    /// Pushes all the packets in the queue to a streaming decoder.
+    ///
+    /// The function returns the number of packets that have been stored by the
+    /// decoder. The other packets, such as duplicates or packets with a wrong
+    /// CRC, are discarded as in [`StreamingDecoder::push`].
+    pub fn feed(&mut self, decoder: &mut StreamingDecoder<'_, P>) -> usize {
+        let mut stored = 0;
+        while let Some(packet) = self.consumer.dequeue() {
+            if decoder.push(&packet) == PushStatus::Stored {
+                stored += 1;
+            }
+        }
+        stored
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Encoder, FountainScheduler, SSDVPacket};
+
+    #[test]
+    fn encode_queue_decode() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut scheduler = FountainScheduler::new(u16::try_from(k).unwrap(), 8, 0);
+
+        let mut tx_queue = PacketQueue::<SSDVPacket, 16>::new();
+        let mut rx_queue = PacketQueue::<SSDVPacket, 16>::new();
+        assert_eq!(tx_queue.capacity(), 15);
+        let (mut tx_producer, mut tx_consumer) = tx_queue.split();
+        let (mut rx_producer, mut rx_consumer) = rx_queue.split();
+        let mut buffer = vec![SSDVPacket::zeroed(); k];
+        let mut decoder = StreamingDecoder::new(&mut buffer);
+        let mut stored = 0;
+        while !decoder.is_ready() {
+            let added = tx_producer.fill(&mut scheduler, |id, packet| encoder.encode(id, packet));
+            assert_eq!(added, 15);
+            assert!(!tx_producer.ready());
+            assert_eq!(tx_producer.fill(&mut scheduler, |_, _| unreachable!()), 0);
+            // the radio sends the packets, which are received by the other
+            // radio
+            while let Some(packet) = tx_consumer.dequeue() {
+                rx_producer.enqueue(packet).unwrap();
+            }
+            assert_eq!(rx_consumer.len(), 15);
+            stored += rx_consumer.feed(&mut decoder);
+            assert!(rx_consumer.is_empty());
+        }
+        assert_eq!(stored, decoder.num_packets());
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        let (decoded, _) = decoder.decode(&mut output).unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+    }
+}
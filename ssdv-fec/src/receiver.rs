@@ -0,0 +1,460 @@
+//! Sans-IO receiver.
+//!
+//! This module contains [`Receiver`], a state machine that implements the
+//! reception of SSDV FEC images independently of how the frames are received,
+//! so that it can be shared by applications using blocking I/O, async I/O or
+//! running on a microcontroller.
+//!
+//! This module is only available with the `alloc` feature.
+
+#[cfg(feature = "postcard")]
+use crate::persist::{check_version, StateError, STATE_VERSION};
+use crate::{
+    image_packets::{ImagePackets, Push},
+    DecodeStats, DecoderError, DecoderOptions, Packet, PacketError, SSDVPacket,
+};
+#[cfg(feature = "postcard")]
+use alloc::collections::BTreeSet;
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+#[cfg(feature = "postcard")]
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Sans-IO receiver of SSDV FEC images.
+///
+/// This is a state machine that implements the reception logic of a ground
+/// station without doing any I/O. The application gives each received frame to
+/// [`Receiver::handle_frame`] together with the current time, and obtains a
+/// list of [`Event`]s that describe what has happened. The receiver tracks the
+/// reception of several images, which can be interleaved, and decodes each
+/// image as soon as enough distinct packets have been received.
+///
+/// Images for which no packets have been received during the timeout set in
+/// [`ReceiverOptions::timeout`] are expired and forgotten, so that their image
+/// ID can be reused by a later image. Since the receiver does no I/O, expiry is
+/// checked when a frame is handled and when [`Receiver::handle_timeout`] is
+/// called. [`Receiver::next_timeout`] gives the time at which the application
+/// should call [`Receiver::handle_timeout`] if no frames are received.
+///
+/// Times are given as a [`Duration`] since an arbitrary epoch chosen by the
+/// application, which should be monotonic.
+///
+/// The packet format is given by the type parameter `P`, which defaults to the
+/// 218-byte format used by Longjiang-2.
+#[derive(Debug)]
+pub struct Receiver<P: Packet = SSDVPacket> {
+    images: BTreeMap<u8, ImageState<P>>,
+    options: ReceiverOptions,
+}
+
+/// Options of a [`Receiver`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReceiverOptions {
+    decoder: DecoderOptions,
+    timeout: Option<Duration>,
+}
+
+impl ReceiverOptions {
+    /// Creates the default receiver options.
+    ///
+    /// By default images never expire and the default decoder options are
+    /// used.
+    pub fn new() -> ReceiverOptions {
+        ReceiverOptions::default()
+    }
+
+    /// Sets the options used to decode the images.
+    ///
+    /// If the decoder is lenient, packets that fail [`Packet::validate`] for
+    /// reasons other than a wrong CRC are not discarded, since the decoder can
+    /// deal with them.
+    pub fn decoder(mut self, decoder: DecoderOptions) -> ReceiverOptions {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Sets the time after the last received packet at which an image expires.
+    pub fn timeout(mut self, timeout: Duration) -> ReceiverOptions {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct ImageState<P> {
+    received: ImagePackets<P>,
+    last_packet_time: Duration,
+}
+
+/// Event produced by a [`Receiver`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Event<P: Packet = SSDVPacket> {
+    /// A frame has been discarded.
+    FrameDiscarded(DiscardReason),
+    /// An image has been decoded.
+    ImageComplete(CompletedImage<P>),
+    /// A packet has been stored, but more packets are needed to decode its
+    /// image.
+    NeedMorePackets {
+        /// Image ID.
+        image_id: u8,
+        /// Number of distinct packets received.
+        received: usize,
+        /// Number of additional packets needed, if the number of systematic
+        /// packets of the image is known.
+        missing: Option<usize>,
+    },
+    /// Decoding of an image was attempted but failed.
+    ///
+    /// The image is kept, and decoding is attempted again when more packets
+    /// are received.
+    DecodeFailed {
+        /// Image ID.
+        image_id: u8,
+        /// Decoder error.
+        error: DecoderError,
+    },
+    /// An image has expired because no packets have been received for it
+    /// during the timeout.
+    ImageExpired {
+        /// Image ID.
+        image_id: u8,
+        /// Number of distinct packets received.
+        received: usize,
+        /// Whether the image had been decoded.
+        decoded: bool,
+    },
+}
+
+/// Reason why a [`Receiver`] discards a frame.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DiscardReason {
+    /// The frame length, given in this variant, is not the packet length.
+    InvalidLength(usize),
+    /// The packet is not valid.
+    InvalidPacket(PacketError),
+    /// The packet has already been received or belongs to an image that has
+    /// already been decoded.
+    Duplicate,
+}
+
+/// Image decoded by a [`Receiver`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CompletedImage<P: Packet = SSDVPacket> {
+    /// Image ID.
+    pub image_id: u8,
+    /// Packets of the decoded image.
+    pub packets: Vec<P>,
+    /// Number of distinct packets used for decoding.
+    pub num_received: usize,
+    /// Statistics of the decoding.
+    pub stats: DecodeStats,
+}
+
+impl<P: Packet> Default for Receiver<P> {
+    fn default() -> Receiver<P> {
+        Receiver::new(ReceiverOptions::default())
+    }
+}
+
+impl<P: Packet> Receiver<P> {
+    /// Creates a new receiver.
+    pub fn new(options: ReceiverOptions) -> Receiver<P> {
+        Receiver {
+            images: BTreeMap::new(),
+            options,
+        }
+    }
+
+    /// Returns the number of images being tracked.
+    ///
+    /// This includes the images that have been decoded but have not expired.
+    pub fn num_images(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Handles a received frame.
+    ///
+    /// The frame must contain a single packet. The function returns the events
+    /// caused by the frame, preceded by the expiry of any images whose timeout
+    /// has elapsed by `now`.
+    pub fn handle_frame(&mut self, frame: &[u8], now: Duration) -> Vec<Event<P>> {
+        let mut events = self.handle_timeout(now);
+        events.push(self.handle_packet(frame, now));
+        events
+    }
+
+    fn handle_packet(&mut self, frame: &[u8], now: Duration) -> Event<P> {
+        if frame.len() != P::LEN {
+            return Event::FrameDiscarded(DiscardReason::InvalidLength(frame.len()));
+        }
+        let mut packet = P::zeroed();
+        packet.as_bytes_mut().copy_from_slice(frame);
+        if let Err(err) = packet.validate() {
+            if err == PacketError::WrongCrc || !self.options.decoder.is_lenient() {
+                return Event::FrameDiscarded(DiscardReason::InvalidPacket(err));
+            }
+        }
+
+        let image_id = packet.image_id();
+        let image = self.images.entry(image_id).or_insert_with(|| ImageState {
+            received: ImagePackets::new(),
+            last_packet_time: now,
+        });
+        image.last_packet_time = now;
+        let image = &mut image.received;
+        match image.push(packet, &self.options.decoder) {
+            Push::Duplicate => Event::FrameDiscarded(DiscardReason::Duplicate),
+            Push::Stored => Event::NeedMorePackets {
+                image_id,
+                received: image.num_received(),
+                missing: image.num_missing(),
+            },
+            Push::Decoded(packets, stats) => Event::ImageComplete(CompletedImage {
+                image_id,
+                packets,
+                num_received: image.num_received(),
+                stats,
+            }),
+            Push::DecodeFailed(error) => Event::DecodeFailed { image_id, error },
+        }
+    }
+
+    /// Expires the images whose timeout has elapsed by `now`.
+    ///
+    /// The function returns an [`Event::ImageExpired`] for each expired image.
+    pub fn handle_timeout(&mut self, now: Duration) -> Vec<Event<P>> {
+        let Some(timeout) = self.options.timeout else {
+            return Vec::new();
+        };
+        let mut events = Vec::new();
+        self.images.retain(|&image_id, image| {
+            let expired = now >= image.last_packet_time + timeout;
+            if expired {
+                events.push(Event::ImageExpired {
+                    image_id,
+                    received: image.received.num_received(),
+                    decoded: image.received.decoded,
+                });
+            }
+            !expired
+        });
+        events
+    }
+
+    /// Returns the time at which the next image will expire.
+    ///
+    /// This returns `None` if there are no images or the receiver has no
+    /// timeout.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        let timeout = self.options.timeout?;
+        self.images
+            .values()
+            .map(|image| image.last_packet_time + timeout)
+            .min()
+    }
+}
+
+// Saved state of a Receiver. The time of the last packet of each image is
+// saved as the time elapsed since then, since the epoch of the times given to
+// the receiver may change when the application restarts.
+#[cfg(feature = "postcard")]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "P: Serialize + DeserializeOwned")]
+struct SavedState<P> {
+    version: u8,
+    options: ReceiverOptions,
+    images: Vec<SavedImage<P>>,
+}
+
+#[cfg(feature = "postcard")]
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "P: Serialize + DeserializeOwned")]
+struct SavedImage<P> {
+    image_id: u8,
+    packets: Vec<P>,
+    packet_ids: BTreeSet<u16>,
+    num_systematic: Option<u16>,
+    decoded: bool,
+    elapsed: Duration,
+}
+
+#[cfg(feature = "postcard")]
+impl<P: Packet + Serialize + DeserializeOwned> Receiver<P> {
+    /// Saves the state of the receiver, including the received packets.
+    ///
+    /// The state is serialized with postcard. The current time `now` is used
+    /// to save how long ago the last packet of each image was received, so
+    /// that the timeouts continue when the state is restored.
+    pub fn save(&self, now: Duration) -> Result<Vec<u8>, StateError> {
+        let state = SavedState {
+            version: STATE_VERSION,
+            options: self.options,
+            images: self
+                .images
+                .iter()
+                .map(|(&image_id, image)| SavedImage {
+                    image_id,
+                    packets: image.received.packets.clone(),
+                    packet_ids: image.received.packet_ids.clone(),
+                    num_systematic: image.received.num_systematic,
+                    decoded: image.received.decoded,
+                    elapsed: now.saturating_sub(image.last_packet_time),
+                })
+                .collect(),
+        };
+        Ok(postcard::to_allocvec(&state)?)
+    }
+
+    /// Restores a receiver from a state saved by [`Receiver::save`].
+    ///
+    /// The current time `now` may use a different epoch than the times used
+    /// when the state was saved.
+    pub fn restore(state: &[u8], now: Duration) -> Result<Receiver<P>, StateError> {
+        let state = postcard::from_bytes::<SavedState<P>>(state)?;
+        check_version(state.version)?;
+        Ok(Receiver {
+            images: state
+                .images
+                .into_iter()
+                .map(|image| {
+                    (
+                        image.image_id,
+                        ImageState {
+                            received: ImagePackets {
+                                packets: image.packets,
+                                packet_ids: image.packet_ids,
+                                num_systematic: image.num_systematic,
+                                decoded: image.decoded,
+                            },
+                            last_packet_time: now.saturating_sub(image.elapsed),
+                        },
+                    )
+                })
+                .collect(),
+            options: state.options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Encoder};
+
+    #[test]
+    fn receive_and_expire() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let timeout = Duration::from_secs(10);
+        let mut receiver = Receiver::<SSDVPacket>::new(ReceiverOptions::new().timeout(timeout));
+        let mut packet = SSDVPacket::zeroed();
+        let mut now = Duration::ZERO;
+
+        assert_eq!(
+            receiver.handle_frame(&[0; 10], now),
+            [Event::FrameDiscarded(DiscardReason::InvalidLength(10))]
+        );
+        // the last systematic packet and FEC packets for the rest
+        for j in 0..k {
+            let packet_id = if j == 0 { k - 1 } else { k + j };
+            encoder.encode(packet_id as u16, &mut packet);
+            now += Duration::from_secs(1);
+            let events = receiver.handle_frame(&packet.0, now);
+            assert_eq!(events.len(), 1);
+            if j < k - 1 {
+                assert_eq!(
+                    events[0],
+                    Event::NeedMorePackets {
+                        image_id: 230,
+                        received: j + 1,
+                        missing: Some(k - j - 1),
+                    }
+                );
+            } else {
+                let Event::ImageComplete(image) = &events[0] else {
+                    panic!("unexpected event {:?}", events[0]);
+                };
+                assert_eq!(image.packets, ssdv);
+                assert_eq!(image.num_received, k);
+                assert_eq!(image.stats.packets_recovered, k - 1);
+            }
+        }
+        assert_eq!(
+            receiver.handle_frame(&packet.0, now),
+            [Event::FrameDiscarded(DiscardReason::Duplicate)]
+        );
+        packet.0[20] ^= 1;
+        assert_eq!(
+            receiver.handle_frame(&packet.0, now),
+            [Event::FrameDiscarded(DiscardReason::InvalidPacket(
+                PacketError::WrongCrc
+            ))]
+        );
+
+        assert_eq!(receiver.next_timeout(), Some(now + timeout));
+        assert!(receiver.handle_timeout(now + timeout / 2).is_empty());
+        assert_eq!(
+            receiver.handle_timeout(now + timeout),
+            [Event::ImageExpired {
+                image_id: 230,
+                received: k,
+                decoded: true,
+            }]
+        );
+        assert_eq!(receiver.num_images(), 0);
+        assert_eq!(receiver.next_timeout(), None);
+    }
+
+    #[test]
+    fn eoi_on_last_packet_id() {
+        let mut packet = img_230_packets()[0];
+        packet.set_eoi(true);
+        packet.set_packet_id(0xffff);
+        packet.set_crc32(packet.compute_crc32());
+        let mut receiver = Receiver::<SSDVPacket>::default();
+        // the EOI does not give the number of systematic packets, which would
+        // be 2¹⁶
+        assert_eq!(
+            receiver.handle_frame(&packet.0, Duration::ZERO),
+            [Event::NeedMorePackets {
+                image_id: 230,
+                received: 1,
+                missing: None,
+            }]
+        );
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn save_and_restore() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let timeout = Duration::from_secs(10);
+        let mut receiver = Receiver::<SSDVPacket>::new(ReceiverOptions::new().timeout(timeout));
+        for (j, packet) in ssdv.iter().enumerate().skip(1) {
+            receiver.handle_frame(&packet.0, Duration::from_secs(100 + j as u64));
+        }
+        let now = Duration::from_secs(100 + k as u64);
+        let state = receiver.save(now).unwrap();
+
+        // the application restarts with a clock starting at zero
+        let now = Duration::from_secs(1);
+        let mut restored = Receiver::<SSDVPacket>::restore(&state, now).unwrap();
+        assert_eq!(restored.num_images(), 1);
+        assert_eq!(restored.next_timeout(), Some(Duration::from_secs(10)));
+        assert_eq!(
+            restored.handle_frame(&ssdv[1].0, now),
+            [Event::FrameDiscarded(DiscardReason::Duplicate)]
+        );
+        let events = restored.handle_frame(&ssdv[0].0, now);
+        let [Event::ImageComplete(image)] = &events[..] else {
+            panic!("unexpected events {events:?}");
+        };
+        assert_eq!(image.packets, ssdv);
+    }
+}
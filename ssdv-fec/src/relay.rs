@@ -0,0 +1,180 @@
+//! Relaying of SSDV FEC images.
+//!
+//! A relay, such as a digipeater in a network of balloons, receives the
+//! packets of some images and retransmits them through another transport.
+//! Since any k distinct packets are enough to decode an image, a [`Relay`] can
+//! do better than retransmitting the packets that it receives: once it decodes
+//! an image, it generates FEC packets with packet IDs that it has not received,
+//! so that the receivers downstream obtain new information even if they have
+//! also received the original transmission.
+//!
+//! This module is only available with the `relay` feature.
+
+use crate::{DecoderOptions, Encoder, ImageSet, PushResult, SSDVPacket};
+use std::collections::BTreeMap;
+
+/// Relay of SSDV FEC images.
+///
+/// The received packets are accumulated in an [`ImageSet`], and the packets
+/// that are new are forwarded unless forwarding is disabled. When an image is
+/// decoded, new FEC packets are generated for it. Their packet IDs follow the
+/// largest packet ID received for the image, which are the IDs that the
+/// original transmitter is least likely to have sent.
+#[derive(Debug)]
+pub struct Relay {
+    images: ImageSet,
+    overhead_percent: u16,
+    forward: bool,
+    // largest packet ID received for each image
+    max_packet_ids: BTreeMap<u8, u16>,
+}
+
+/// Result of pushing a packet into a [`Relay`].
+#[derive(Debug)]
+pub struct RelayResult {
+    /// Result of adding the packet to the images being received.
+    pub push_result: PushResult,
+    /// Packets to retransmit, in transmission order.
+    pub packets: Vec<SSDVPacket>,
+}
+
+impl Relay {
+    /// Creates a new relay.
+    ///
+    /// The number of new FEC packets generated for each decoded image is the
+    /// overhead times the number of systematic packets, rounded up. Forwarding
+    /// is enabled.
+    pub fn new(overhead_percent: u16) -> Relay {
+        Relay {
+            images: ImageSet::new(),
+            overhead_percent,
+            forward: true,
+            max_packet_ids: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the options used to decode the images.
+    ///
+    /// This should be called before any packets are pushed, since the images
+    /// being received are discarded.
+    pub fn with_options(mut self, options: DecoderOptions) -> Relay {
+        self.images = ImageSet::with_options(options);
+        self
+    }
+
+    /// Enables or disables the forwarding of the received packets.
+    ///
+    /// If forwarding is enabled, each packet with a valid CRC that has not been
+    /// received before is retransmitted, as long as its image has not been
+    /// decoded yet.
+    pub fn with_forwarding(mut self, forward: bool) -> Relay {
+        self.forward = forward;
+        self
+    }
+
+    /// Returns the images being received.
+    pub fn images(&self) -> &ImageSet {
+        &self.images
+    }
+
+    /// Adds a received packet.
+    ///
+    /// Returns the result of adding the packet to the images being received
+    /// and the packets that should be retransmitted.
+    pub fn push(&mut self, packet: SSDVPacket) -> RelayResult {
+        let push_result = self.images.push(packet);
+        if matches!(push_result, PushResult::WrongCrc) {
+            return RelayResult {
+                push_result,
+                packets: Vec::new(),
+            };
+        }
+        let max_packet_id = self
+            .max_packet_ids
+            .entry(packet.image_id())
+            .or_insert(packet.packet_id());
+        *max_packet_id = (*max_packet_id).max(packet.packet_id());
+        let first_packet_id = max_packet_id.checked_add(1);
+        let mut packets = Vec::new();
+        if self.forward && !matches!(push_result, PushResult::Duplicate) {
+            packets.push(packet);
+        }
+        if let (PushResult::Decoded(decoded), Some(first_packet_id)) =
+            (&push_result, first_packet_id)
+        {
+            let mut systematic = decoded.packets.clone();
+            // the decoder returns the systematic packets in order, which is
+            // what the encoder needs
+            if let Ok(encoder) = Encoder::new(&mut systematic) {
+                let count = (usize::from(encoder.num_systematic())
+                    * usize::from(self.overhead_percent))
+                .div_ceil(100);
+                // the packet IDs are limited by u16::MAX, so encoding cannot fail
+                let count = count.min(usize::from(u16::MAX - first_packet_id) + 1);
+                let start = packets.len();
+                packets.resize(start + count, SSDVPacket::zeroed());
+                encoder
+                    .try_encode_range(first_packet_id, &mut packets[start..])
+                    .unwrap();
+                // these IDs are no longer available for new FEC packets
+                if count > 0 {
+                    *max_packet_id = first_packet_id + u16::try_from(count - 1).unwrap();
+                }
+            }
+        }
+        RelayResult {
+            push_result,
+            packets,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Decoder};
+
+    #[test]
+    fn relay() {
+        let systematic = img_230_packets();
+        let k = u16::try_from(systematic.len()).unwrap();
+        let mut input = systematic.clone();
+        let encoder = Encoder::new(&mut input).unwrap();
+        // the decoder needs the last systematic packet to know the number of
+        // systematic packets
+        let mut transmitted = vec![SSDVPacket::zeroed(); usize::from(k)];
+        transmitted[0] = systematic[usize::from(k) - 1];
+        encoder.try_encode_range(k, &mut transmitted[1..]).unwrap();
+
+        // the image has 65 packets, so an overhead of 15% gives 10 packets
+        assert_eq!(k, 65);
+        let mut relay = Relay::new(15);
+        let mut relayed = Vec::new();
+        let mut decoded = false;
+        for packet in &transmitted {
+            let result = relay.push(*packet);
+            decoded |= matches!(result.push_result, PushResult::Decoded(_));
+            relayed.extend(result.packets);
+        }
+        assert!(decoded);
+        // all the packets are forwarded, followed by 10 new FEC packets
+        assert_eq!(relayed.len(), usize::from(k) + 10);
+        assert_eq!(relayed[..usize::from(k)], transmitted[..]);
+        let new_ids = relayed[usize::from(k)..]
+            .iter()
+            .map(|packet| packet.packet_id())
+            .collect::<Vec<_>>();
+        assert_eq!(new_ids, (2 * k - 1..2 * k + 9).collect::<Vec<_>>());
+
+        // the image can be decoded from the new FEC packets together with
+        // other packets
+        let mut received = relayed[usize::from(k)..].to_vec();
+        received.extend_from_slice(&transmitted[..usize::from(k) - 10]);
+        let mut output = vec![SSDVPacket::zeroed(); usize::from(k)];
+        let decoded = Decoder::decode(&mut received, &mut output).unwrap();
+        assert_eq!(decoded, &systematic[..]);
+
+        // packets received after the image is decoded are not relayed
+        assert!(relay.push(transmitted[0]).packets.is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+//! Reed-Solomon (255, 223) encoder.
+//!
+//! This is the CCSDS Reed-Solomon code (in conventional basis) used by the
+//! standard SSDV packet format to protect each packet. It uses the field
+//! GF(2⁸) defined by the polynomial x⁸ + x⁷ + x² + x + 1, and the roots of the
+//! generator polynomial are α^(11·(112 + j)) for j = 0, ..., 31.
+
+use std::sync::OnceLock;
+
+/// Number of parity bytes.
+pub const NROOTS: usize = 32;
+
+const NN: usize = 255;
+const GFPOLY: u16 = 0x187;
+const FCR: usize = 112;
+const PRIM: usize = 11;
+// Index form of zero
+const A0: u8 = 255;
+
+struct Tables {
+    alpha_to: [u8; 256],
+    index_of: [u8; 256],
+    // generator polynomial in index form
+    genpoly: [u8; NROOTS + 1],
+}
+
+impl Tables {
+    fn new() -> Tables {
+        let mut alpha_to = [0; 256];
+        let mut index_of = [0; 256];
+        index_of[0] = A0;
+        let mut x: u16 = 1;
+        for (j, alpha) in alpha_to.iter_mut().enumerate().take(NN) {
+            *alpha = x as u8;
+            index_of[usize::from(x)] = j as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= GFPOLY;
+            }
+        }
+        // generator polynomial in polynomial form, computed as the product of
+        // (x - root) for each of the roots
+        let mut genpoly = [0u8; NROOTS + 1];
+        genpoly[0] = 1;
+        let mut root = FCR * PRIM;
+        for i in 0..NROOTS {
+            genpoly[i + 1] = 1;
+            for j in (1..=i).rev() {
+                genpoly[j] = if genpoly[j] != 0 {
+                    genpoly[j - 1]
+                        ^ alpha_to[(usize::from(index_of[usize::from(genpoly[j])]) + root) % NN]
+                } else {
+                    genpoly[j - 1]
+                };
+            }
+            genpoly[0] = alpha_to[(usize::from(index_of[usize::from(genpoly[0])]) + root) % NN];
+            root += PRIM;
+        }
+        for g in genpoly.iter_mut() {
+            *g = index_of[usize::from(*g)];
+        }
+        Tables {
+            alpha_to,
+            index_of,
+            genpoly,
+        }
+    }
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(Tables::new)
+}
+
+/// Computes the parity bytes of a codeword.
+///
+/// The code is shortened if `data` is shorter than 223 bytes.
+///
+/// # Panics
+///
+/// This function panics if `data` is longer than 223 bytes.
+pub fn encode(data: &[u8]) -> [u8; NROOTS] {
+    assert!(data.len() <= NN - NROOTS);
+    let t = tables();
+    let mut parity = [0u8; NROOTS];
+    for &d in data {
+        let feedback = t.index_of[usize::from(d ^ parity[0])];
+        parity.copy_within(1.., 0);
+        parity[NROOTS - 1] = 0;
+        if feedback != A0 {
+            for (j, p) in parity.iter_mut().enumerate() {
+                let g = t.genpoly[NROOTS - 1 - j];
+                *p ^= t.alpha_to[(usize::from(feedback) + usize::from(g)) % NN];
+            }
+        }
+    }
+    parity
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Evaluates the codeword polynomial at each of the roots of the generator
+    // polynomial. All the syndromes are zero for a valid codeword.
+    fn syndromes(codeword: &[u8]) -> Vec<u8> {
+        let t = tables();
+        (0..NROOTS)
+            .map(|j| {
+                let root = ((FCR + j) * PRIM) % NN;
+                codeword.iter().fold(0u8, |acc, &c| {
+                    let acc = if acc == 0 {
+                        0
+                    } else {
+                        t.alpha_to[(usize::from(t.index_of[usize::from(acc)]) + root) % NN]
+                    };
+                    acc ^ c
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn valid_codewords() {
+        for len in [1, 100, NN - NROOTS] {
+            let data = (0..len).map(|j| (j * 37 + 11) as u8).collect::<Vec<u8>>();
+            let mut codeword = data.clone();
+            codeword.extend_from_slice(&encode(&data));
+            assert!(syndromes(&codeword).iter().all(|&s| s == 0));
+        }
+    }
+}
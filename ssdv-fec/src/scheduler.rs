@@ -0,0 +1,149 @@
+/// Fountain-code transmission scheduler.
+///
+/// This struct generates the sequence of packet IDs that a transmitter should
+/// encode and send in order to transmit an SSDV image in a carousel. The
+/// sequence is formed by rounds. In each round, the `k` systematic packets of
+/// the image are sent, followed by a number of FEC packets. The FEC packets in
+/// each round are fresh, meaning that they have not been sent in previous
+/// rounds (until the 2¹⁶ packet ID space is exhausted and the FEC packet IDs
+/// wrap around). This ensures that a receiver that listens to several rounds
+/// collects distinct packets.
+///
+/// The scheduler is deterministic, so a transmitter and a receiver (or
+/// different tools) that use the same parameters obtain the same sequence. The
+/// `seed` parameter selects the packet ID of the first FEC packet, so that
+/// transmitters using different seeds can send different FEC packets for the
+/// same image.
+///
+/// The scheduler implements [`Iterator`], returning packet IDs forever.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FountainScheduler {
+    num_systematic: u16,
+    fec_per_round: u16,
+    next_fec: u16,
+    position: u32,
+}
+
+impl FountainScheduler {
+    /// Creates a new scheduler.
+    ///
+    /// The number of systematic packets in the image is `num_systematic`, and
+    /// the number of FEC packets sent in each round is `fec_per_round`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `num_systematic` is zero.
+    pub fn new(num_systematic: u16, fec_per_round: u16, seed: u32) -> FountainScheduler {
+        assert!(num_systematic > 0);
+        let fec_space = 0x10000 - u32::from(num_systematic);
+        // Multiplicative hashing spreads consecutive seeds over the FEC
+        // packet ID space
+        let offset = seed.wrapping_mul(0x9e37_79b9) % fec_space;
+        FountainScheduler {
+            num_systematic,
+            fec_per_round,
+            next_fec: (u32::from(num_systematic) + offset) as u16,
+            position: 0,
+        }
+    }
+
+    /// Creates a new scheduler with a given overhead.
+    ///
+    /// The overhead is given as a percentage of the number of systematic
+    /// packets. The number of FEC packets sent in each round is the overhead
+    /// times the number of systematic packets, rounded up. For example, an
+    /// overhead of 25% in an image of 10 systematic packets gives 3 FEC packets
+    /// in each round.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `num_systematic` is zero.
+    pub fn with_overhead_percent(
+        num_systematic: u16,
+        overhead_percent: u16,
+        seed: u32,
+    ) -> FountainScheduler {
+        let fec = (u32::from(num_systematic) * u32::from(overhead_percent)).div_ceil(100);
+        Self::new(num_systematic, u16::try_from(fec).unwrap_or(u16::MAX), seed)
+    }
+
+    /// Returns the number of systematic packets of the image.
+    pub fn num_systematic(&self) -> u16 {
+        self.num_systematic
+    }
+
+    /// Returns the number of FEC packets sent in each round.
+    pub fn fec_per_round(&self) -> u16 {
+        self.fec_per_round
+    }
+
+    /// Returns the total number of packets sent in each round.
+    pub fn packets_per_round(&self) -> u32 {
+        u32::from(self.num_systematic) + u32::from(self.fec_per_round)
+    }
+
+    /// Returns the next packet ID to send.
+    pub fn next_packet_id(&mut self) -> u16 {
+        let position = self.position;
+        self.position += 1;
+        if self.position == self.packets_per_round() {
+            self.position = 0;
+        }
+        if position < u32::from(self.num_systematic) {
+            return position as u16;
+        }
+        let packet_id = self.next_fec;
+        self.next_fec = if self.next_fec == u16::MAX {
+            self.num_systematic
+        } else {
+            self.next_fec + 1
+        };
+        packet_id
+    }
+}
+
+impl Iterator for FountainScheduler {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        Some(self.next_packet_id())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rounds() {
+        let k = 10;
+        let scheduler = FountainScheduler::with_overhead_percent(k, 25, 0);
+        assert_eq!(scheduler.fec_per_round(), 3);
+        let ids = scheduler.take(3 * 13).collect::<Vec<u16>>();
+        let mut fec_ids = Vec::new();
+        for round in ids.chunks_exact(13) {
+            assert_eq!(&round[..10], &(0..10).collect::<Vec<u16>>());
+            fec_ids.extend_from_slice(&round[10..]);
+        }
+        assert_eq!(fec_ids, (10..19).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn fec_ids_wrap_around() {
+        let k = 100;
+        let mut scheduler = FountainScheduler::new(k, 1, 0);
+        for _ in 0..0x10000 - u32::from(k) {
+            scheduler.nth(usize::from(k)).unwrap();
+        }
+        assert_eq!(scheduler.nth(usize::from(k)), Some(k));
+    }
+
+    #[test]
+    fn seed_changes_fec_ids() {
+        let a = FountainScheduler::new(50, 5, 1).nth(50).unwrap();
+        let b = FountainScheduler::new(50, 5, 2).nth(50).unwrap();
+        assert!(a >= 50);
+        assert!(b >= 50);
+        assert_ne!(a, b);
+    }
+}
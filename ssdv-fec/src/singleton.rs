@@ -0,0 +1,234 @@
+use crate::{
+    DecodeStats, DecoderError, DecoderOptions, Encoder, EncoderError, Packet, PushStatus,
+    SSDVPacket, StreamingDecoder,
+};
+use static_cell::StaticCell;
+
+/// Encoder that owns a `'static` buffer.
+///
+/// This wraps an [`Encoder`] together with a buffer that lives for the whole
+/// execution of the firmware, such as one obtained from a [`StaticCell`], the
+/// `cortex_m::singleton!` macro, or a local resource of an RTIC `init` task.
+/// Since it has no lifetime parameter, a `StaticEncoder` can be stored in a
+/// `StaticCell` or in an RTIC resource and passed to the task that transmits
+/// the images.
+///
+/// The buffer is used for the images one after another. The systematic
+/// packets of an image are written with [`StaticEncoder::packets_mut`], and
+/// [`StaticEncoder::setup`] prepares the encoder for them. The encoder is
+/// then obtained as many times as needed with [`StaticEncoder::encoder`],
+/// without repeating the setup. The borrow checker ensures that the packets
+/// are not modified while an encoder is in use.
+#[derive(Debug)]
+pub struct StaticEncoder<P: Packet + 'static = SSDVPacket> {
+    buffer: &'static mut [P],
+    // Number of systematic packets of the image that has been set up, or zero
+    // if there is none.
+    num_systematic: usize,
+}
+
+impl<P: Packet> StaticEncoder<P> {
+    /// Creates a new encoder that uses a `'static` buffer.
+    ///
+    /// The length of the buffer limits the number of systematic packets of the
+    /// images.
+    pub fn new(buffer: &'static mut [P]) -> StaticEncoder<P> {
+        StaticEncoder {
+            buffer,
+            num_systematic: 0,
+        }
+    }
+
+    /// Creates a new encoder that uses the buffer of a [`StaticCell`].
+    ///
+    /// The cell is initialized with zeroed packets. If the cell has already
+    /// been initialized, for instance because this function has already been
+    /// called with it, `None` is returned, so that the buffer is never shared
+    /// by two encoders.
+    pub fn from_cell<const N: usize>(
+        cell: &'static StaticCell<[P; N]>,
+    ) -> Option<StaticEncoder<P>> {
+        let buffer = cell.try_init_with(|| [P::zeroed(); N])?;
+        Some(StaticEncoder::new(buffer))
+    }
+
+    /// Returns the whole buffer, to write the systematic packets of an image.
+    ///
+    /// The packets of the image must be written to the beginning of the buffer,
+    /// in order. This discards the image that has been set up, if any, whose
+    /// packets have been modified by the encoder unless they were restored
+    /// with [`StaticEncoder::release`].
+    pub fn packets_mut(&mut self) -> &mut [P] {
+        self.num_systematic = 0;
+        self.buffer
+    }
+
+    /// Sets up the encoder for an image.
+    ///
+    /// The `num_systematic` systematic packets of the image must have been
+    /// written to the beginning of the buffer. The encoder works in-place in
+    /// the buffer, as [`Encoder::new`]. If the number of packets is larger
+    /// than the buffer, [`EncoderError::TooLongInput`] is returned.
+    pub fn setup(&mut self, num_systematic: usize) -> Result<Encoder<'_, P>, EncoderError> {
+        self.num_systematic = 0;
+        let packets = self
+            .buffer
+            .get_mut(..num_systematic)
+            .ok_or(EncoderError::TooLongInput)?;
+        let encoder = Encoder::new(packets)?;
+        self.num_systematic = num_systematic;
+        Ok(encoder)
+    }
+
+    /// Returns the encoder for the image that has been set up.
+    ///
+    /// This does not repeat the computations done by [`StaticEncoder::setup`].
+    /// If no image has been set up, `None` is returned.
+    pub fn encoder(&mut self) -> Option<Encoder<'_, P>> {
+        if self.num_systematic == 0 {
+            return None;
+        }
+        let packets = self.buffer.get_mut(..self.num_systematic)?;
+        Some(Encoder::from_lagrange_terms(packets))
+    }
+
+    /// Releases the image that has been set up.
+    ///
+    /// If `restore` is true, the systematic packets of the image are restored,
+    /// as in [`Encoder::release`]. The packets of the image are returned, or
+    /// an empty slice if no image has been set up.
+    pub fn release(&mut self, restore: bool) -> &mut [P] {
+        let num_systematic = core::mem::take(&mut self.num_systematic);
+        let packets = self.buffer.get_mut(..num_systematic).unwrap_or_default();
+        if restore && num_systematic != 0 {
+            Encoder::from_lagrange_terms(packets).release(true)
+        } else {
+            packets
+        }
+    }
+}
+
+/// Streaming decoder that owns `'static` buffers.
+///
+/// This wraps a [`StreamingDecoder`] that stores the received packets in a
+/// `'static` buffer, together with a `'static` buffer where the decoded images
+/// are written. As with [`StaticEncoder`], the buffers can be obtained from
+/// [`StaticCell`]s or from the local resources of an RTIC `init` task, and the
+/// `StaticDecoder` can be stored in an RTIC resource and passed to the task
+/// that receives the packets.
+#[derive(Debug)]
+pub struct StaticDecoder<P: Packet + 'static = SSDVPacket> {
+    decoder: StreamingDecoder<'static, P>,
+    output: &'static mut [P],
+}
+
+impl<P: Packet> StaticDecoder<P> {
+    /// Creates a new decoder that uses `'static` buffers.
+    ///
+    /// The received packets are stored in `buffer`, as in
+    /// [`StreamingDecoder::with_options`], and the decoded images are written
+    /// to `output`. Both buffers should be large enough to hold the systematic
+    /// packets of the largest image that is expected.
+    pub fn new(
+        buffer: &'static mut [P],
+        output: &'static mut [P],
+        options: DecoderOptions,
+    ) -> StaticDecoder<P> {
+        StaticDecoder {
+            decoder: StreamingDecoder::with_options(buffer, options),
+            output,
+        }
+    }
+
+    /// Creates a new decoder that uses the buffers of two [`StaticCell`]s.
+    ///
+    /// The cells are initialized with zeroed packets. If any of the cells has
+    /// already been initialized, `None` is returned, so that the buffers are
+    /// never shared by two decoders.
+    pub fn from_cells<const N: usize, const M: usize>(
+        buffer: &'static StaticCell<[P; N]>,
+        output: &'static StaticCell<[P; M]>,
+        options: DecoderOptions,
+    ) -> Option<StaticDecoder<P>> {
+        let buffer = buffer.try_init_with(|| [P::zeroed(); N])?;
+        let output = output.try_init_with(|| [P::zeroed(); M])?;
+        Some(StaticDecoder::new(buffer, output, options))
+    }
+
+    /// Returns the streaming decoder.
+    ///
+    /// This can be used to query the state of the reception of the image.
+    pub fn decoder(&self) -> &StreamingDecoder<'static, P> {
+        &self.decoder
+    }
+
+    /// Returns the streaming decoder as a mutable reference.
+    pub fn decoder_mut(&mut self) -> &mut StreamingDecoder<'static, P> {
+        &mut self.decoder
+    }
+
+    /// Adds a received packet.
+    ///
+    /// See [`StreamingDecoder::push`].
+    pub fn push(&mut self, packet: &P) -> PushStatus {
+        self.decoder.push(packet)
+    }
+
+    /// Decodes the image from the packets that have been stored.
+    ///
+    /// This works as [`StreamingDecoder::decode_with_stats`], writing the
+    /// decoded image to the output buffer. The decoded image can be used until
+    /// the next call to a method of the decoder.
+    pub fn decode(&mut self) -> Result<(&mut [P], DecodeStats), DecoderError> {
+        self.decoder.decode_with_stats(self.output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+
+    const MAX_PACKETS: usize = 100;
+
+    #[test]
+    fn encode_decode_static() {
+        static ENCODER_BUFFER: StaticCell<[SSDVPacket; MAX_PACKETS]> = StaticCell::new();
+        static DECODER_BUFFER: StaticCell<[SSDVPacket; MAX_PACKETS]> = StaticCell::new();
+        static DECODER_OUTPUT: StaticCell<[SSDVPacket; MAX_PACKETS]> = StaticCell::new();
+
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut encoder = StaticEncoder::from_cell(&ENCODER_BUFFER).unwrap();
+        assert!(StaticEncoder::from_cell(&ENCODER_BUFFER).is_none());
+        assert!(encoder.encoder().is_none());
+        encoder.packets_mut()[..k].copy_from_slice(&ssdv);
+        assert_eq!(
+            encoder.setup(MAX_PACKETS + 1).unwrap_err(),
+            EncoderError::TooLongInput
+        );
+        encoder.setup(k).unwrap();
+
+        let mut decoder =
+            StaticDecoder::from_cells(&DECODER_BUFFER, &DECODER_OUTPUT, DecoderOptions::default())
+                .unwrap();
+        // only the last systematic packet is given, to find the image size
+        assert_eq!(decoder.push(&ssdv[k - 1]), PushStatus::Stored);
+        for j in 1..k {
+            let mut packet = SSDVPacket::zeroed();
+            encoder
+                .encoder()
+                .unwrap()
+                .encode(u16::try_from(k + j).unwrap(), &mut packet);
+            assert_eq!(decoder.push(&packet), PushStatus::Stored);
+        }
+        assert!(decoder.decoder().is_ready());
+        let (decoded, stats) = decoder.decode().unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+        assert_eq!(stats.packets_recovered, k - 1);
+
+        assert_eq!(encoder.release(true), &ssdv[..]);
+        assert!(encoder.encoder().is_none());
+        assert!(encoder.release(true).is_empty());
+    }
+}
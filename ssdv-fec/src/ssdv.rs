@@ -1,7 +1,11 @@
+use crate::crc::{Crc32, CRC32_DSLWP_MAGIC_VALUE, CRC32_INIT};
+use core::fmt::{self, Debug};
+
 /// SSDV packet.
 ///
-/// This struct wraps an array containing an SSDV packet and provides some
-/// convenience methods for accessing the fields of the packet.
+/// This struct wraps an array containing an SSDV packet in the 218-byte format
+/// used by Longjiang-2 and provides some convenience methods for accessing the
+/// fields of the packet.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 #[repr(transparent)]
 pub struct SSDVPacket(pub [u8; SSDV_PACKET_LEN]);
@@ -155,3 +159,543 @@ impl SSDVPacket {
         self.0[SSDV_PACKET_LEN - 4..].copy_from_slice(&crc32.to_be_bytes());
     }
 }
+
+/// SSDV packet in the standard no-FEC format.
+///
+/// This struct wraps an array containing a 256-byte standard SSDV packet of
+/// the no-FEC type, which does not contain a Reed-Solomon parity. The fields
+/// of the packet are accessed through the [`Packet`] trait.
+///
+/// The sync byte, packet type and callsign fields are not used by the encoder
+/// and decoder, but they are copied from the other packets of the image into
+/// the FEC packets and the packets recovered by the decoder.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(transparent)]
+pub struct SSDVNoFecPacket(pub [u8; SSDV_NO_FEC_PACKET_LEN]);
+
+/// Length of a standard no-FEC SSDV packet in bytes.
+pub const SSDV_NO_FEC_PACKET_LEN: usize = 256;
+
+/// Length of the data field of a standard no-FEC SSDV packet.
+pub const SSDV_NO_FEC_DATA_LEN: usize = 240;
+
+/// Error found when validating a single SSDV packet.
+///
+/// This enum lists the errors that can be returned by [`Packet::validate`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PacketError {
+    /// The CRC-32 of the packet is wrong.
+    WrongCrc,
+    /// A field that has a fixed value in the packet format has a different
+    /// value.
+    WrongFixedField,
+    /// The EOI flag is set on a FEC packet.
+    EoiOnFecPacket,
+    /// The packet ID of a FEC packet is smaller than its number of systematic
+    /// packets field, or this field is zero.
+    WrongFecId,
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PacketError::WrongCrc => "wrong CRC-32",
+            PacketError::WrongFixedField => "wrong value in fixed field",
+            PacketError::EoiOnFecPacket => "EOI set on FEC packet",
+            PacketError::WrongFecId => {
+                "FEC packet ID inconsistent with the number of systematic packets"
+            }
+        })
+    }
+}
+
+impl core::error::Error for PacketError {}
+
+/// Encodes a callsign in the base-40 format used by SSDV.
+///
+/// Only the first 6 characters of the callsign are encoded. Letters are
+/// case-insensitive, and characters other than letters and digits are encoded
+/// as zero.
+pub fn encode_callsign(callsign: &str) -> u32 {
+    callsign.bytes().take(6).rev().fold(0, |x, c| {
+        x * 40
+            + match c {
+                b'A'..=b'Z' => u32::from(c - b'A') + 14,
+                b'a'..=b'z' => u32::from(c - b'a') + 14,
+                b'0'..=b'9' => u32::from(c - b'0') + 1,
+                _ => 0,
+            }
+    })
+}
+
+/// SSDV packet format.
+///
+/// This trait gives access to the fields of an SSDV packet, so that the FEC
+/// encoder and decoder can work with different packet formats. A packet starts
+/// with a prefix of [`Packet::HEADER_OFFSET`] bytes that is the same for all
+/// the packets of an image, followed by the image ID, packet ID, width, height
+/// and flags fields, the data field, whose length must be even, and the CRC-32
+/// in the last 4 bytes. In FEC packets, the width and height fields contain
+/// the number of systematic packets instead.
+///
+/// The trait is implemented by [`SSDVPacket`], for the 218-byte format used by
+/// Longjiang-2, and by [`SSDVNoFecPacket`], for the standard 256-byte no-FEC
+/// format.
+pub trait Packet: Debug + Copy + Eq {
+    /// Length of the packet in bytes.
+    const LEN: usize;
+
+    /// Offset of the image ID field in the packet.
+    const HEADER_OFFSET: usize;
+
+    /// Length of the data field of the packet.
+    const DATA_LEN: usize = Self::LEN - Self::HEADER_OFFSET - 10;
+
+    /// Offset of the first byte covered by the CRC-32.
+    ///
+    /// The CRC-32 covers the bytes from this offset up to the CRC-32 field.
+    const CRC32_OFFSET: usize;
+
+    /// Initial value of the CRC-32 register.
+    ///
+    /// The CRC-32 is the one used by standard SSDV packets, but the register
+    /// can be initialized to a different value, as if some bytes that are not
+    /// present in the packet had been processed first.
+    const CRC32_INIT: u32;
+
+    /// Returns a new packet full of zeros.
+    fn zeroed() -> Self;
+
+    /// Returns the bytes of the packet.
+    fn as_bytes(&self) -> &[u8];
+
+    /// Returns the bytes of the packet as a mutable slice.
+    fn as_bytes_mut(&mut self) -> &mut [u8];
+
+    /// Reinterprets a byte buffer as a slice of packets, without copying.
+    ///
+    /// If the length of `bytes` is not a multiple of [`Packet::LEN`], `None` is
+    /// returned.
+    fn slice_from_bytes(bytes: &[u8]) -> Option<&[Self]>;
+
+    /// Reinterprets a mutable byte buffer as a mutable slice of packets,
+    /// without copying.
+    ///
+    /// If the length of `bytes` is not a multiple of [`Packet::LEN`], `None` is
+    /// returned.
+    fn slice_from_bytes_mut(bytes: &mut [u8]) -> Option<&mut [Self]>;
+
+    /// Computes the CRC-32 of the packet.
+    ///
+    /// This can be compared with [`Packet::crc32`] to check the packet.
+    fn compute_crc32(&self) -> u32 {
+        let mut crc = Crc32::new(Self::CRC32_INIT);
+        crc.update(&self.as_bytes()[Self::CRC32_OFFSET..Self::LEN - 4]);
+        crc.finish()
+    }
+
+    /// Returns true if the fields that have a fixed value in the packet format
+    /// have the correct value.
+    fn fixed_fields_ok(&self) -> bool {
+        true
+    }
+
+    /// Returns the value of the callsign field, encoded in base 40.
+    ///
+    /// The callsign field is only present in the packet formats that carry the
+    /// callsign. For other formats this function returns `None`. The value can
+    /// be compared with the result of [`encode_callsign`].
+    fn callsign(&self) -> Option<u32> {
+        None
+    }
+
+    /// Checks whether a single packet is valid.
+    ///
+    /// This checks the CRC-32, the fields with a fixed value, and the
+    /// consistency of the header fields that can be checked without other
+    /// packets of the image.
+    fn validate(&self) -> Result<(), PacketError> {
+        if self.compute_crc32() != self.crc32() {
+            return Err(PacketError::WrongCrc);
+        }
+        if !self.fixed_fields_ok() {
+            return Err(PacketError::WrongFixedField);
+        }
+        if self.is_fec_packet() && self.is_eoi() {
+            return Err(PacketError::EoiOnFecPacket);
+        }
+        if let Some(k) = self.number_systematic_packets() {
+            if k == 0 || self.packet_id() < k {
+                return Err(PacketError::WrongFecId);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the value of the image ID field.
+    fn image_id(&self) -> u8 {
+        self.as_bytes()[Self::HEADER_OFFSET]
+    }
+
+    /// Sets the value of the image ID field.
+    fn set_image_id(&mut self, image_id: u8) {
+        self.as_bytes_mut()[Self::HEADER_OFFSET] = image_id;
+    }
+
+    /// Returns the value of the packet ID field.
+    fn packet_id(&self) -> u16 {
+        let offset = Self::HEADER_OFFSET + 1;
+        u16::from_be_bytes(self.as_bytes()[offset..offset + 2].try_into().unwrap())
+    }
+
+    /// Sets the value of the packet ID field.
+    fn set_packet_id(&mut self, packet_id: u16) {
+        let offset = Self::HEADER_OFFSET + 1;
+        self.as_bytes_mut()[offset..offset + 2].copy_from_slice(&packet_id.to_be_bytes());
+    }
+
+    /// Returns the value of the width field.
+    ///
+    /// The width field is only present in systematic packets. If this function
+    /// is called on a FEC packet it returns `None`.
+    fn width(&self) -> Option<u8> {
+        (!self.is_fec_packet()).then(|| self.as_bytes()[Self::HEADER_OFFSET + 3])
+    }
+
+    /// Sets the value of the width field.
+    ///
+    /// The width field is only present in systematic packets. This function
+    /// should only be called for systematic packets.
+    fn set_width(&mut self, width: u8) {
+        self.as_bytes_mut()[Self::HEADER_OFFSET + 3] = width;
+    }
+
+    /// Returns the value of the height field.
+    ///
+    /// The height field is only present in systematic packets. If this
+    /// function is called on a FEC packet it returns `None`.
+    fn height(&self) -> Option<u8> {
+        (!self.is_fec_packet()).then(|| self.as_bytes()[Self::HEADER_OFFSET + 4])
+    }
+
+    /// Sets the value of the height field.
+    ///
+    /// The height field is only present in systematic packets. This function
+    /// should only be called for systematic packets.
+    fn set_height(&mut self, height: u8) {
+        self.as_bytes_mut()[Self::HEADER_OFFSET + 4] = height;
+    }
+
+    /// Returns the value of the number of systematic packets field.
+    ///
+    /// This field is only present in FEC packets. If this function is called
+    /// on a systematic packet it returns `None`.
+    fn number_systematic_packets(&self) -> Option<u16> {
+        let offset = Self::HEADER_OFFSET + 3;
+        self.is_fec_packet()
+            .then(|| u16::from_be_bytes(self.as_bytes()[offset..offset + 2].try_into().unwrap()))
+    }
+
+    /// Sets the value of the number of systematic packets field.
+    ///
+    /// This field is only present in FEC packets. This function should only be
+    /// called for FEC packets.
+    fn set_number_systematic_packets(&mut self, number_systematic_packets: u16) {
+        let offset = Self::HEADER_OFFSET + 3;
+        self.as_bytes_mut()[offset..offset + 2]
+            .copy_from_slice(&number_systematic_packets.to_be_bytes());
+    }
+
+    /// Returns the value of the flags field.
+    fn flags(&self) -> u8 {
+        self.as_bytes()[Self::HEADER_OFFSET + 5]
+    }
+
+    /// Sets the value of the flags field.
+    fn set_flags(&mut self, flags: u8) {
+        self.as_bytes_mut()[Self::HEADER_OFFSET + 5] = flags;
+    }
+
+    /// Returns true if the packet has the EOI flag set.
+    fn is_eoi(&self) -> bool {
+        self.flags() & 0x4 != 0
+    }
+
+    /// Sets the value of the EOI flag.
+    fn set_eoi(&mut self, eoi: bool) {
+        self.set_flags((self.flags() & !0x4) | (u8::from(eoi) << 2));
+    }
+
+    /// Returns true if the packet has the FEC packet flag set.
+    fn is_fec_packet(&self) -> bool {
+        self.flags() & 0x40 != 0
+    }
+
+    /// Sets the value of the FEC packet flag.
+    fn set_fec_packet(&mut self, fec_packet: bool) {
+        self.set_flags((self.flags() & !0x40) | (u8::from(fec_packet) << 6));
+    }
+
+    /// Returns a reference to the sub-slice that contains the packet data.
+    fn data(&self) -> &[u8] {
+        let offset = Self::HEADER_OFFSET + 6;
+        &self.as_bytes()[offset..offset + Self::DATA_LEN]
+    }
+
+    /// Returns a mutable reference to the sub-slice that contains the packet
+    /// data.
+    fn data_as_mut(&mut self) -> &mut [u8] {
+        let offset = Self::HEADER_OFFSET + 6;
+        &mut self.as_bytes_mut()[offset..offset + Self::DATA_LEN]
+    }
+
+    /// Returns the value of the CRC-32 field of the packet.
+    fn crc32(&self) -> u32 {
+        u32::from_be_bytes(self.as_bytes()[Self::LEN - 4..].try_into().unwrap())
+    }
+
+    /// Sets the value of the CRC-32 field of the packet.
+    fn set_crc32(&mut self, crc32: u32) {
+        self.as_bytes_mut()[Self::LEN - 4..].copy_from_slice(&crc32.to_be_bytes());
+    }
+}
+
+impl Packet for SSDVPacket {
+    const LEN: usize = SSDV_PACKET_LEN;
+    const HEADER_OFFSET: usize = 0;
+    const CRC32_OFFSET: usize = 0;
+    const CRC32_INIT: u32 = CRC32_DSLWP_MAGIC_VALUE;
+
+    fn zeroed() -> SSDVPacket {
+        SSDVPacket::zeroed()
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    fn slice_from_bytes(bytes: &[u8]) -> Option<&[SSDVPacket]> {
+        let (packets, []) = bytes.as_chunks::<SSDV_PACKET_LEN>() else {
+            return None;
+        };
+        // SAFETY: SSDVPacket is repr(transparent) over [u8; SSDV_PACKET_LEN].
+        Some(unsafe { &*(packets as *const [[u8; SSDV_PACKET_LEN]] as *const [SSDVPacket]) })
+    }
+
+    fn slice_from_bytes_mut(bytes: &mut [u8]) -> Option<&mut [SSDVPacket]> {
+        let (packets, []) = bytes.as_chunks_mut::<SSDV_PACKET_LEN>() else {
+            return None;
+        };
+        // SAFETY: SSDVPacket is repr(transparent) over [u8; SSDV_PACKET_LEN].
+        Some(unsafe { &mut *(packets as *mut [[u8; SSDV_PACKET_LEN]] as *mut [SSDVPacket]) })
+    }
+}
+
+impl Packet for SSDVNoFecPacket {
+    const LEN: usize = SSDV_NO_FEC_PACKET_LEN;
+    const HEADER_OFFSET: usize = 6;
+    // the sync byte is not included in the CRC
+    const CRC32_OFFSET: usize = 1;
+    const CRC32_INIT: u32 = CRC32_INIT;
+
+    fn zeroed() -> SSDVNoFecPacket {
+        SSDVNoFecPacket([0u8; SSDV_NO_FEC_PACKET_LEN])
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    fn slice_from_bytes(bytes: &[u8]) -> Option<&[SSDVNoFecPacket]> {
+        let (packets, []) = bytes.as_chunks::<SSDV_NO_FEC_PACKET_LEN>() else {
+            return None;
+        };
+        // SAFETY: SSDVNoFecPacket is repr(transparent) over [u8; SSDV_NO_FEC_PACKET_LEN].
+        Some(unsafe {
+            &*(packets as *const [[u8; SSDV_NO_FEC_PACKET_LEN]] as *const [SSDVNoFecPacket])
+        })
+    }
+
+    fn slice_from_bytes_mut(bytes: &mut [u8]) -> Option<&mut [SSDVNoFecPacket]> {
+        let (packets, []) = bytes.as_chunks_mut::<SSDV_NO_FEC_PACKET_LEN>() else {
+            return None;
+        };
+        // SAFETY: SSDVNoFecPacket is repr(transparent) over [u8; SSDV_NO_FEC_PACKET_LEN].
+        Some(unsafe {
+            &mut *(packets as *mut [[u8; SSDV_NO_FEC_PACKET_LEN]] as *mut [SSDVNoFecPacket])
+        })
+    }
+
+    fn fixed_fields_ok(&self) -> bool {
+        // sync byte and no-FEC packet type
+        self.0[0] == 0x55 && self.0[1] == 0x67
+    }
+
+    fn callsign(&self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.0[2..6].try_into().unwrap()))
+    }
+}
+
+// The FEC works with 16-bit words of the data field.
+const _: () = assert!(SSDVPacket::DATA_LEN == SSDV_DATA_LEN && SSDV_DATA_LEN.is_multiple_of(2));
+const _: () = assert!(
+    SSDVNoFecPacket::DATA_LEN == SSDV_NO_FEC_DATA_LEN && SSDV_NO_FEC_DATA_LEN.is_multiple_of(2)
+);
+
+// Packets are formatted with defmt as a compact summary of their header, which
+// is more useful in logs than the raw bytes.
+#[cfg(feature = "defmt")]
+mod defmt_impls {
+    use super::{Packet, SSDVNoFecPacket, SSDVPacket};
+    use defmt::{write, Format, Formatter};
+
+    fn format_header<P: Packet>(packet: &P, fmt: Formatter) {
+        write!(
+            fmt,
+            "image {=u8} packet {=u16}",
+            packet.image_id(),
+            packet.packet_id()
+        );
+        match (
+            packet.number_systematic_packets(),
+            packet.width(),
+            packet.height(),
+        ) {
+            (Some(k), _, _) => write!(fmt, " FEC k={=u16}", k),
+            (None, Some(width), Some(height)) => {
+                write!(fmt, " width {=u8} height {=u8}", width, height)
+            }
+            _ => (),
+        }
+        if packet.is_eoi() {
+            write!(fmt, " EOI");
+        }
+    }
+
+    impl Format for SSDVPacket {
+        fn format(&self, fmt: Formatter) {
+            format_header(self, fmt);
+        }
+    }
+
+    impl Format for SSDVNoFecPacket {
+        fn format(&self, fmt: Formatter) {
+            format_header(self, fmt);
+        }
+    }
+}
+
+// Packets are serialized as byte strings, which serde does not support for
+// arrays longer than 32 bytes.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::{Packet, SSDVNoFecPacket, SSDVPacket};
+    use core::{fmt, marker::PhantomData};
+    use serde::{
+        de::{self, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    struct PacketVisitor<P>(PhantomData<P>);
+
+    impl<'de, P: Packet> Visitor<'de> for PacketVisitor<P> {
+        type Value = P;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "{} bytes", P::LEN)
+        }
+
+        fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<P, E> {
+            if bytes.len() != P::LEN {
+                return Err(E::invalid_length(bytes.len(), &self));
+            }
+            let mut packet = P::zeroed();
+            packet.as_bytes_mut().copy_from_slice(bytes);
+            Ok(packet)
+        }
+
+        // used by formats that do not support byte strings, such as JSON
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<P, A::Error> {
+            let mut packet = P::zeroed();
+            for (j, byte) in packet.as_bytes_mut().iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(j, &self))?;
+            }
+            if seq.next_element::<u8>()?.is_some() {
+                return Err(de::Error::invalid_length(P::LEN + 1, &self));
+            }
+            Ok(packet)
+        }
+    }
+
+    macro_rules! impl_serde {
+        ($packet:ty) => {
+            impl Serialize for $packet {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_bytes(&self.0)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $packet {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<$packet, D::Error> {
+                    deserializer.deserialize_bytes(PacketVisitor(PhantomData))
+                }
+            }
+        };
+    }
+
+    impl_serde!(SSDVPacket);
+    impl_serde!(SSDVNoFecPacket);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::IMG_230_SSDV;
+
+    #[test]
+    fn validate() {
+        let mut packet = SSDVPacket(IMG_230_SSDV[..SSDV_PACKET_LEN].try_into().unwrap());
+        assert_eq!(packet.validate(), Ok(()));
+        packet.set_eoi(true);
+        assert_eq!(packet.validate(), Err(PacketError::WrongCrc));
+        packet.set_fec_packet(true);
+        packet.set_number_systematic_packets(10);
+        packet.set_packet_id(10);
+        packet.set_crc32(packet.compute_crc32());
+        assert_eq!(packet.validate(), Err(PacketError::EoiOnFecPacket));
+        packet.set_eoi(false);
+        packet.set_packet_id(9);
+        packet.set_crc32(packet.compute_crc32());
+        assert_eq!(packet.validate(), Err(PacketError::WrongFecId));
+
+        let mut packet = SSDVNoFecPacket::zeroed();
+        packet.set_crc32(packet.compute_crc32());
+        assert_eq!(packet.validate(), Err(PacketError::WrongFixedField));
+        packet.0[..2].copy_from_slice(&[0x55, 0x67]);
+        packet.set_crc32(packet.compute_crc32());
+        assert_eq!(packet.validate(), Ok(()));
+    }
+
+    #[test]
+    fn callsign() {
+        assert_eq!(encode_callsign("SORA"), encode_callsign("sora"));
+        assert_eq!(encode_callsign("EA4GPZ"), encode_callsign("EA4GPZ-1"));
+        assert_ne!(encode_callsign("EA4GPZ"), encode_callsign("EA4GP"));
+        let mut packet = SSDVNoFecPacket::zeroed();
+        packet.0[2..6].copy_from_slice(&encode_callsign("EA4GPZ").to_be_bytes());
+        assert_eq!(packet.callsign(), Some(encode_callsign("EA4GPZ")));
+        let packet = SSDVPacket::zeroed();
+        assert_eq!(Packet::callsign(&packet), None);
+    }
+}
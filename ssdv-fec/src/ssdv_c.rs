@@ -0,0 +1,355 @@
+//! Bridge to the ssdv C library.
+//!
+//! This module converts between JPEG images and SSDV packets by calling
+//! [fsphil's ssdv](https://github.com/fsphil/ssdv) C library, or a fork with
+//! the same API. Together with the FEC encoder and decoder, this gives complete
+//! image-level round trips: a JPEG image can be converted to SSDV packets with
+//! [`jpeg_to_ssdv`], FEC encoded, transmitted, decoded, and converted back to a
+//! JPEG image with [`ssdv_to_jpeg`].
+//!
+//! This module is only available with the `ssdv-c` feature. The ssdv library is
+//! not distributed with this crate, so the `SSDV_SRC_DIR` environment variable
+//! must be set at build time to a directory containing its source, which is
+//! compiled and linked statically. The ssdv library is licensed under the GPL,
+//! so binaries built with this feature are subject to the terms of the GPL.
+//! The [`transcode`](crate::transcode) module can convert SSDV packets to JPEG
+//! images without this dependency.
+
+use crate::{
+    standard::{self, STANDARD_PACKET_LEN},
+    Packet, SSDVNoFecPacket, SSDVPacket,
+};
+use std::{
+    ffi::{c_char, c_int, CString},
+    fmt,
+};
+
+// Return values of the shim functions, which must match those in
+// csrc/ssdv_shim.c.
+const SHIM_OK: c_int = 0;
+const SHIM_NO_MEMORY: c_int = -1;
+const SHIM_ENCODE_FAILED: c_int = -2;
+const SHIM_OUTPUT_TOO_SHORT: c_int = -3;
+const SHIM_DECODE_FAILED: c_int = -4;
+
+// Packet types of the ssdv library.
+const SSDV_TYPE_NORMAL: u8 = 0;
+const SSDV_TYPE_NOFEC: u8 = 1;
+
+// Maximum number of packets in an image.
+const MAX_PACKETS: usize = 1 << 16;
+// Maximum length of a decoded JPEG image. Decoding gives up if the image does
+// not fit in this length.
+const MAX_JPEG_LEN: usize = 64 << 20;
+// Length reserved for the JPEG headers, which are not transmitted in the SSDV
+// packets.
+const JPEG_HEADERS_LEN: usize = 4096;
+
+extern "C" {
+    fn ssdv_fec_shim_jpeg_to_ssdv(
+        jpeg: *const u8,
+        jpeg_len: usize,
+        ssdv_type: u8,
+        callsign: *const c_char,
+        image_id: u8,
+        quality: i8,
+        packets: *mut u8,
+        max_packets: usize,
+        num_packets: *mut usize,
+    ) -> c_int;
+
+    fn ssdv_fec_shim_ssdv_to_jpeg(
+        packets: *const u8,
+        num_packets: usize,
+        jpeg: *mut u8,
+        jpeg_len: usize,
+        written: *mut usize,
+    ) -> c_int;
+}
+
+/// Error produced by the ssdv C library bridge.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SsdvCError {
+    /// The ssdv library could not encode the JPEG image.
+    ///
+    /// The ssdv library only supports baseline JPEG images with dimensions
+    /// that are multiples of 16 pixels.
+    EncodeFailed,
+    /// The ssdv library could not decode the packets.
+    DecodeFailed,
+    /// There are no valid systematic packets to decode.
+    NoPackets,
+    /// The image needs more than 65536 packets.
+    ImageTooLarge,
+    /// The callsign contains a NUL character.
+    InvalidCallsign,
+    /// The ssdv library could not allocate memory.
+    OutOfMemory,
+}
+
+impl fmt::Display for SsdvCError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SsdvCError::EncodeFailed => "the ssdv library could not encode the JPEG image",
+            SsdvCError::DecodeFailed => "the ssdv library could not decode the packets",
+            SsdvCError::NoPackets => "no valid systematic packets",
+            SsdvCError::ImageTooLarge => "image too large",
+            SsdvCError::InvalidCallsign => "invalid callsign",
+            SsdvCError::OutOfMemory => "out of memory",
+        })
+    }
+}
+
+impl core::error::Error for SsdvCError {}
+
+/// Options for [`jpeg_to_ssdv`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct JpegOptions {
+    callsign: String,
+    quality: u8,
+}
+
+impl Default for JpegOptions {
+    fn default() -> JpegOptions {
+        JpegOptions {
+            callsign: standard::implicit_callsign().to_string(),
+            quality: 4,
+        }
+    }
+}
+
+impl JpegOptions {
+    /// Creates the default options.
+    ///
+    /// The default quality is 4, which is also the default of the ssdv
+    /// library.
+    pub fn new() -> JpegOptions {
+        JpegOptions::default()
+    }
+
+    /// Sets the callsign of the packets.
+    ///
+    /// The callsign is only used with the standard no-FEC format. The format
+    /// used by Longjiang-2 always uses an implicit callsign.
+    pub fn callsign(mut self, callsign: &str) -> JpegOptions {
+        self.callsign = callsign.to_string();
+        self
+    }
+
+    /// Sets the JPEG quality level used by the ssdv library.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `quality` is larger than 7.
+    pub fn quality(mut self, quality: u8) -> JpegOptions {
+        assert!(quality <= 7, "the JPEG quality must be between 0 and 7");
+        self.quality = quality;
+        self
+    }
+}
+
+mod sealed {
+    use super::*;
+
+    pub trait Sealed: Sized {
+        const SSDV_TYPE: u8;
+        fn to_standard(&self) -> [u8; STANDARD_PACKET_LEN];
+        fn from_standard(standard: &[u8; STANDARD_PACKET_LEN]) -> Self;
+    }
+
+    impl Sealed for SSDVPacket {
+        const SSDV_TYPE: u8 = SSDV_TYPE_NORMAL;
+
+        fn to_standard(&self) -> [u8; STANDARD_PACKET_LEN] {
+            standard::standard_packet(self)
+        }
+
+        fn from_standard(standard: &[u8; STANDARD_PACKET_LEN]) -> SSDVPacket {
+            standard::from_standard_packet(standard)
+        }
+    }
+
+    impl Sealed for SSDVNoFecPacket {
+        const SSDV_TYPE: u8 = SSDV_TYPE_NOFEC;
+
+        fn to_standard(&self) -> [u8; STANDARD_PACKET_LEN] {
+            self.0
+        }
+
+        fn from_standard(standard: &[u8; STANDARD_PACKET_LEN]) -> SSDVNoFecPacket {
+            SSDVNoFecPacket(*standard)
+        }
+    }
+}
+
+/// Packet format supported by the ssdv C library bridge.
+///
+/// Packets in the format used by Longjiang-2 are handled by the ssdv library
+/// as standard packets with Reed-Solomon parity.
+pub trait SsdvCPacket: Packet + sealed::Sealed {}
+
+impl SsdvCPacket for SSDVPacket {}
+
+impl SsdvCPacket for SSDVNoFecPacket {}
+
+/// Converts a JPEG image to SSDV packets.
+///
+/// The image is re-encoded by the ssdv library according to the quality
+/// selected in `options`, and the systematic packets of the image are returned
+/// in order. They can be given to [`Encoder::new`](crate::Encoder::new) to
+/// generate FEC packets.
+pub fn jpeg_to_ssdv<P: SsdvCPacket>(
+    jpeg: &[u8],
+    image_id: u8,
+    options: &JpegOptions,
+) -> Result<Vec<P>, SsdvCError> {
+    // The CRC-32 of packets in the format used by Longjiang-2 is only valid
+    // with the implicit callsign.
+    let callsign = if P::SSDV_TYPE == SSDV_TYPE_NORMAL {
+        standard::implicit_callsign()
+    } else {
+        &options.callsign
+    };
+    let callsign = CString::new(callsign).map_err(|_| SsdvCError::InvalidCallsign)?;
+    // The output usually has a size similar to the JPEG image. The buffer is
+    // enlarged if it is too short.
+    let mut max_packets = (jpeg.len() / (STANDARD_PACKET_LEN / 2) + 16).min(MAX_PACKETS);
+    loop {
+        let mut packets = vec![[0u8; STANDARD_PACKET_LEN]; max_packets];
+        let mut num_packets = 0;
+        // SAFETY: the buffers are valid for the lengths given to the function.
+        let ret = unsafe {
+            ssdv_fec_shim_jpeg_to_ssdv(
+                jpeg.as_ptr(),
+                jpeg.len(),
+                P::SSDV_TYPE,
+                callsign.as_ptr(),
+                image_id,
+                options.quality as i8,
+                packets.as_mut_ptr().cast::<u8>(),
+                max_packets,
+                &mut num_packets,
+            )
+        };
+        match ret {
+            SHIM_OK => {
+                return Ok(packets[..num_packets]
+                    .iter()
+                    .map(P::from_standard)
+                    .collect())
+            }
+            SHIM_OUTPUT_TOO_SHORT if max_packets < MAX_PACKETS => {
+                max_packets = (2 * max_packets).min(MAX_PACKETS);
+            }
+            SHIM_OUTPUT_TOO_SHORT => return Err(SsdvCError::ImageTooLarge),
+            err => return Err(shim_error(err)),
+        }
+    }
+}
+
+/// Converts SSDV packets to a JPEG image.
+///
+/// The packets can be given in any order and can contain duplicates. Packets
+/// with a wrong CRC-32 and FEC packets are ignored, so this function is
+/// usually called with the output of the FEC decoder. If some systematic
+/// packets are missing, the ssdv library fills the missing parts of the image.
+pub fn ssdv_to_jpeg<P: SsdvCPacket>(packets: &[P]) -> Result<Vec<u8>, SsdvCError> {
+    // The ssdv library needs the packets in order.
+    let mut packets = packets
+        .iter()
+        .filter(|packet| packet.compute_crc32() == packet.crc32() && !packet.is_fec_packet())
+        .copied()
+        .collect::<Vec<P>>();
+    packets.sort_by_key(|packet| packet.packet_id());
+    packets.dedup_by_key(|packet| packet.packet_id());
+    if packets.is_empty() {
+        return Err(SsdvCError::NoPackets);
+    }
+    let standard = packets
+        .iter()
+        .map(|packet| packet.to_standard())
+        .collect::<Vec<_>>();
+
+    let mut jpeg_len = standard.len() * STANDARD_PACKET_LEN + JPEG_HEADERS_LEN;
+    loop {
+        let mut jpeg = vec![0u8; jpeg_len];
+        let mut written = 0;
+        // SAFETY: the buffers are valid for the lengths given to the function.
+        let ret = unsafe {
+            ssdv_fec_shim_ssdv_to_jpeg(
+                standard.as_ptr().cast::<u8>(),
+                standard.len(),
+                jpeg.as_mut_ptr(),
+                jpeg.len(),
+                &mut written,
+            )
+        };
+        match ret {
+            SHIM_OK => {
+                jpeg.truncate(written);
+                return Ok(jpeg);
+            }
+            SHIM_OUTPUT_TOO_SHORT if jpeg_len < MAX_JPEG_LEN => {
+                jpeg_len = (2 * jpeg_len).min(MAX_JPEG_LEN);
+            }
+            SHIM_OUTPUT_TOO_SHORT => return Err(SsdvCError::ImageTooLarge),
+            err => return Err(shim_error(err)),
+        }
+    }
+}
+
+fn shim_error(ret: c_int) -> SsdvCError {
+    match ret {
+        SHIM_NO_MEMORY => SsdvCError::OutOfMemory,
+        SHIM_ENCODE_FAILED => SsdvCError::EncodeFailed,
+        SHIM_DECODE_FAILED => SsdvCError::DecodeFailed,
+        _ => unreachable!("unexpected return value {ret} from the ssdv shim"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+
+    #[test]
+    fn round_trip() {
+        let packets = img_230_packets();
+        let jpeg = ssdv_to_jpeg(&packets).unwrap();
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(jpeg[jpeg.len() - 2..], [0xff, 0xd9]);
+
+        let reencoded = jpeg_to_ssdv::<SSDVPacket>(&jpeg, 230, &JpegOptions::new()).unwrap();
+        assert!(!reencoded.is_empty());
+        for (packet_id, packet) in reencoded.iter().enumerate() {
+            assert_eq!(packet.validate(), Ok(()));
+            assert_eq!(packet.image_id(), 230);
+            assert_eq!(usize::from(packet.packet_id()), packet_id);
+            assert_eq!(packet.is_eoi(), packet_id == reencoded.len() - 1);
+        }
+        let jpeg = ssdv_to_jpeg(&reencoded).unwrap();
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+
+        let no_fec = jpeg_to_ssdv::<SSDVNoFecPacket>(
+            &jpeg,
+            1,
+            &JpegOptions::new().callsign("EA4GPZ").quality(6),
+        )
+        .unwrap();
+        assert!(no_fec.iter().all(|packet| packet.validate().is_ok()));
+        assert_eq!(ssdv_to_jpeg(&no_fec).unwrap()[..2], [0xff, 0xd8]);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(ssdv_to_jpeg::<SSDVPacket>(&[]), Err(SsdvCError::NoPackets));
+        assert_eq!(
+            jpeg_to_ssdv::<SSDVPacket>(&[0; 100], 0, &JpegOptions::new()),
+            Err(SsdvCError::EncodeFailed)
+        );
+        assert_eq!(
+            jpeg_to_ssdv::<SSDVNoFecPacket>(&[], 0, &JpegOptions::new().callsign("A\0B")),
+            Err(SsdvCError::InvalidCallsign)
+        );
+    }
+}
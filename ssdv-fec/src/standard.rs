@@ -0,0 +1,79 @@
+//! Conversion to the standard SSDV packet format.
+//!
+//! SSDV FEC packets in the format used by Longjiang-2 are standard SSDV packets
+//! with the sync byte, packet type, callsign and Reed-Solomon parity removed.
+//! This module restores these fields, so that the packets can be handled by
+//! tools that use the standard format.
+
+use crate::{encode_callsign, rs8, SSDVPacket, SSDV_PACKET_LEN};
+
+/// Length of a standard SSDV packet.
+pub const STANDARD_PACKET_LEN: usize = 256;
+
+// The CRC-32 of SSDV FEC packets is initialized as if the packet type byte of
+// standard SSDV packets (0x66) and this callsign had been processed first.
+const IMPLICIT_CALLSIGN: &str = "SORA";
+
+/// Converts a packet to the standard SSDV packet format.
+///
+/// The sync byte, packet type and callsign fields, which are implicit in SSDV
+/// FEC packets, are added, and the Reed-Solomon parity is computed. The CRC-32
+/// of the packet remains valid.
+pub fn standard_packet(packet: &SSDVPacket) -> [u8; STANDARD_PACKET_LEN] {
+    let mut standard = [0; STANDARD_PACKET_LEN];
+    standard[0] = 0x55;
+    standard[1] = 0x66;
+    standard[2..6].copy_from_slice(&encode_callsign(IMPLICIT_CALLSIGN).to_be_bytes());
+    standard[6..6 + SSDV_PACKET_LEN].copy_from_slice(&packet.0);
+    let parity = rs8::encode(&standard[1..6 + SSDV_PACKET_LEN]);
+    standard[6 + SSDV_PACKET_LEN..].copy_from_slice(&parity);
+    standard
+}
+
+/// Converts a standard SSDV packet to an SSDV FEC packet.
+///
+/// The fields that are implicit in SSDV FEC packets and the Reed-Solomon parity
+/// are removed. The CRC-32 of the packet only remains valid if the packet type
+/// and callsign are the implicit ones.
+#[cfg_attr(not(feature = "ssdv-c"), allow(dead_code))]
+pub fn from_standard_packet(standard: &[u8; STANDARD_PACKET_LEN]) -> SSDVPacket {
+    SSDVPacket(standard[6..6 + SSDV_PACKET_LEN].try_into().unwrap())
+}
+
+/// Returns the callsign that is implicit in SSDV FEC packets.
+#[cfg_attr(not(feature = "ssdv-c"), allow(dead_code))]
+pub fn implicit_callsign() -> &'static str {
+    IMPLICIT_CALLSIGN
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+
+    // Standard CRC-32 used by SSDV packets
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xffffffff;
+        for &d in data {
+            crc ^= u32::from(d);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xedb88320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn standard_packet_crc() {
+        for packet in img_230_packets() {
+            let standard = standard_packet(&packet);
+            let crc = u32::from_be_bytes(standard[220..224].try_into().unwrap());
+            assert_eq!(crc32(&standard[1..220]), crc);
+            assert_eq!(from_standard_packet(&standard), packet);
+        }
+    }
+}
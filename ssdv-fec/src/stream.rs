@@ -0,0 +1,451 @@
+#[cfg(feature = "postcard")]
+use crate::persist::{check_version, StateError, STATE_VERSION};
+use crate::{
+    DecodeStats, Decoder, DecoderError, DecoderOptions, HeaderOverrides, Packet, SSDVPacket,
+};
+#[cfg(feature = "postcard")]
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Incremental SSDV FEC decoder.
+///
+/// This decoder receives packets one by one as they arrive, and stores them in
+/// an externally provided buffer. Packets with a wrong CRC and duplicate
+/// packets are discarded as soon as they are pushed, so the buffer only
+/// contains distinct valid packets. The decoder keeps track of whether enough
+/// packets have been received, so that the computationally expensive decoding
+/// is only attempted when it can succeed.
+///
+/// Unless the decoder is lenient, the decoder locks onto the image ID of the
+/// first valid packet, and packets from other images are discarded.
+///
+/// The packet format is given by the type parameter `P`, which defaults to the
+/// 218-byte format used by Longjiang-2.
+#[derive(Debug)]
+pub struct StreamingDecoder<'a, P: Packet = SSDVPacket> {
+    buffer: &'a mut [P],
+    len: usize,
+    image_id: Option<u8>,
+    num_systematic: Option<u16>,
+    systematic_received: usize,
+    options: DecoderOptions,
+}
+
+/// Result of pushing a packet into a [`StreamingDecoder`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PushStatus {
+    /// The packet has been stored.
+    Stored,
+    /// A packet with the same packet ID has already been stored.
+    Duplicate,
+    /// The packet has a wrong CRC.
+    WrongCrc,
+    /// The packet belongs to a different image.
+    OtherImage,
+    /// The buffer is full.
+    BufferFull,
+}
+
+impl<'a, P: Packet> StreamingDecoder<'a, P> {
+    /// Creates a new streaming decoder.
+    ///
+    /// The received packets are stored in `buffer`, which limits the number of
+    /// packets that can be stored. The buffer should be large enough to hold
+    /// the `k` systematic packets of the largest image that is expected.
+    pub fn new(buffer: &'a mut [P]) -> StreamingDecoder<'a, P> {
+        StreamingDecoder::with_options(buffer, DecoderOptions::default())
+    }
+
+    /// Creates a new streaming decoder that decodes with some options.
+    pub fn with_options(buffer: &'a mut [P], options: DecoderOptions) -> StreamingDecoder<'a, P> {
+        StreamingDecoder {
+            buffer,
+            len: 0,
+            image_id: None,
+            num_systematic: None,
+            systematic_received: 0,
+            options,
+        }
+    }
+
+    /// Returns the options of the decoder.
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Adds a received packet.
+    pub fn push(&mut self, packet: &P) -> PushStatus {
+        if packet.compute_crc32() != packet.crc32() {
+            return PushStatus::WrongCrc;
+        }
+        if !self.options.is_lenient() && self.image_id.is_some_and(|id| id != packet.image_id()) {
+            return PushStatus::OtherImage;
+        }
+        if self
+            .packets()
+            .iter()
+            .any(|stored| stored.packet_id() == packet.packet_id())
+        {
+            return PushStatus::Duplicate;
+        }
+        let Some(slot) = self.buffer.get_mut(self.len) else {
+            return PushStatus::BufferFull;
+        };
+        *slot = *packet;
+        self.len += 1;
+        self.image_id.get_or_insert(packet.image_id());
+        if let Some(k) = packet.number_systematic_packets() {
+            self.num_systematic = Some(k);
+        } else if packet.is_eoi() {
+            self.num_systematic = Some(packet.packet_id().wrapping_add(1));
+        }
+        if !packet.is_fec_packet() {
+            self.systematic_received += 1;
+        }
+        PushStatus::Stored
+    }
+
+    /// Returns the packets that have been stored.
+    pub fn packets(&self) -> &[P] {
+        // len never exceeds the length of the buffer
+        self.buffer.get(..self.len).unwrap_or_default()
+    }
+
+    /// Returns the number of distinct packets that have been stored.
+    pub fn num_packets(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the image ID of the first packet stored.
+    pub fn image_id(&self) -> Option<u8> {
+        self.image_id
+    }
+
+    /// Returns the number of systematic packets of the image, if it is already
+    /// known.
+    ///
+    /// This is known once a FEC packet or the systematic packet with the EOI
+    /// flag has been received.
+    pub fn num_systematic(&self) -> Option<u16> {
+        self.num_systematic
+    }
+
+    /// Returns the number of systematic packets that have been stored.
+    pub fn num_systematic_received(&self) -> usize {
+        self.systematic_received
+    }
+
+    /// Returns the IDs of the systematic packets that have not been stored.
+    ///
+    /// The IDs are returned in increasing order. Nothing is returned if the
+    /// number of systematic packets is not known yet.
+    pub fn missing_ids(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.num_systematic.unwrap_or(0))
+            .filter(|&id| !self.packets().iter().any(|packet| packet.packet_id() == id))
+    }
+
+    /// Returns the number of additional distinct packets needed to decode the
+    /// image, if the number of systematic packets is already known.
+    ///
+    /// If no systematic packets have been stored yet, at least one of the
+    /// additional packets must be a systematic packet.
+    pub fn num_packets_needed(&self) -> Option<usize> {
+        let k = usize::from(self.num_systematic?);
+        Some(
+            k.saturating_sub(self.len)
+                .max(usize::from(self.systematic_received == 0)),
+        )
+    }
+
+    /// Returns whether enough packets have been stored to decode the image.
+    ///
+    /// Decoding can still fail if the headers of the packets are inconsistent.
+    pub fn is_ready(&self) -> bool {
+        self.not_ready_error().is_none()
+    }
+
+    fn not_ready_error(&self) -> Option<DecoderError> {
+        match self.num_systematic {
+            None => Some(DecoderError::UnknownNumSystematic),
+            Some(_) if self.systematic_received == 0 => Some(DecoderError::NoSystematic),
+            Some(k) if self.len < usize::from(k) => Some(DecoderError::NotEnoughInput),
+            Some(_) => None,
+        }
+    }
+
+    /// Writes the systematic packets that have been stored to their slots in
+    /// `output`.
+    ///
+    /// This works as [`StreamingDecoder::try_decode_partial`], but panics
+    /// instead of returning an error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `valid` is shorter than `output.len().div_ceil(8)` bytes.
+    pub fn decode_partial(&self, output: &mut [P], valid: &mut [u8]) -> usize {
+        match self.try_decode_partial(output, valid) {
+            Ok(num_valid) => num_valid,
+            Err(_) => panic!("valid is too short"),
+        }
+    }
+
+    /// Writes the systematic packets that have been stored to their slots in
+    /// `output`.
+    ///
+    /// This can be used to show an incomplete image while packets are being
+    /// received. The systematic packet with packet ID `i` is written to
+    /// `output[i]`, and bit `i % 8` of `valid[i / 8]` is set to indicate that
+    /// the slot is valid. The bits of the other slots are cleared, and the
+    /// slots themselves are not modified. Packets whose ID does not fit in
+    /// `output` are ignored. The function returns the number of valid slots,
+    /// or [`DecoderError::OutputTooShort`] if `valid` is shorter than
+    /// `output.len().div_ceil(8)` bytes, in which case neither `output` nor
+    /// `valid` are modified.
+    ///
+    /// Missing systematic packets cannot be recovered until enough packets
+    /// have been stored, so this function does not use FEC packets. The stored
+    /// packets are kept, so more packets can be pushed afterwards. Once the
+    /// decoder is ready, [`StreamingDecoder::decode`] should be used to obtain
+    /// the complete image.
+    pub fn try_decode_partial(
+        &self,
+        output: &mut [P],
+        valid: &mut [u8],
+    ) -> Result<usize, DecoderError> {
+        let valid = valid
+            .get_mut(..output.len().div_ceil(8))
+            .ok_or(DecoderError::OutputTooShort)?;
+        valid.fill(0);
+        let mut num_valid = 0;
+        for packet in self.packets() {
+            // Lenient decoders can store packets from other images.
+            if packet.is_fec_packet() || Some(packet.image_id()) != self.image_id {
+                continue;
+            }
+            let id = usize::from(packet.packet_id());
+            if let (Some(slot), Some(bits)) = (output.get_mut(id), valid.get_mut(id / 8)) {
+                *slot = *packet;
+                *bits |= 1 << (id % 8);
+                num_valid += 1;
+            }
+        }
+        Ok(num_valid)
+    }
+
+    /// Removes all the packets that have been stored.
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.image_id = None;
+        self.num_systematic = None;
+        self.systematic_received = 0;
+    }
+
+    /// Decodes the image from the packets that have been stored.
+    ///
+    /// The decoded image is written to the beginning of `output`, as in
+    /// [`Decoder::decode_with_options`]. If there are not enough packets to
+    /// decode the image, an error is returned and the stored packets are kept,
+    /// so that more packets can be pushed. Otherwise the stored packets are
+    /// consumed by the decoder, which works in-place, and the decoder is reset
+    /// regardless of whether decoding succeeds.
+    pub fn decode<'b>(
+        &mut self,
+        output: &'b mut [P],
+    ) -> Result<(&'b mut [P], HeaderOverrides), DecoderError> {
+        self.decode_with_stats(output)
+            .map(|(decoded, stats)| (decoded, stats.overrides))
+    }
+
+    /// Decodes the image from the packets that have been stored and reports
+    /// statistics of the decoding.
+    ///
+    /// This works as [`StreamingDecoder::decode`], but returns the statistics
+    /// given by [`Decoder::decode_with_stats`].
+    pub fn decode_with_stats<'b>(
+        &mut self,
+        output: &'b mut [P],
+    ) -> Result<(&'b mut [P], DecodeStats), DecoderError> {
+        if let Some(err) = self.not_ready_error() {
+            return Err(err);
+        }
+        let packets = self.buffer.get_mut(..self.len).unwrap_or_default();
+        let result = Decoder::decode_with_stats(packets, output, &self.options);
+        self.reset();
+        result
+    }
+}
+
+// Saved state of a StreamingDecoder, which is followed by the stored packets.
+#[cfg(feature = "postcard")]
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    version: u8,
+    len: usize,
+    image_id: Option<u8>,
+    num_systematic: Option<u16>,
+    systematic_received: usize,
+    options: DecoderOptions,
+}
+
+#[cfg(feature = "postcard")]
+impl<'a, P: Packet + Serialize + DeserializeOwned> StreamingDecoder<'a, P> {
+    /// Saves the state of the decoder, including the stored packets.
+    ///
+    /// The state is serialized with postcard into the beginning of `buffer`,
+    /// and the part of the buffer that has been used is returned. The state
+    /// occupies slightly more than the stored packets, so a buffer of
+    /// `(P::LEN + 3) * n + 16` bytes is large enough for `n` packets.
+    pub fn save<'b>(&self, buffer: &'b mut [u8]) -> Result<&'b mut [u8], StateError> {
+        let state = SavedState {
+            version: STATE_VERSION,
+            len: self.len,
+            image_id: self.image_id,
+            num_systematic: self.num_systematic,
+            systematic_received: self.systematic_received,
+            options: self.options,
+        };
+        let mut used = postcard::to_slice(&state, buffer)?.len();
+        for packet in self.packets() {
+            used += postcard::to_slice(packet, &mut buffer[used..])?.len();
+        }
+        Ok(&mut buffer[..used])
+    }
+
+    /// Restores a decoder from a state saved by [`StreamingDecoder::save`].
+    ///
+    /// The stored packets are written to the beginning of `buffer`, which is
+    /// used by the restored decoder.
+    pub fn restore(
+        state: &[u8],
+        buffer: &'a mut [P],
+    ) -> Result<StreamingDecoder<'a, P>, StateError> {
+        let (saved, mut rest) = postcard::take_from_bytes::<SavedState>(state)?;
+        check_version(saved.version)?;
+        let slots = buffer
+            .get_mut(..saved.len)
+            .ok_or(StateError::BufferTooSmall)?;
+        for slot in slots {
+            (*slot, rest) = postcard::take_from_bytes(rest)?;
+        }
+        Ok(StreamingDecoder {
+            buffer,
+            len: saved.len,
+            image_id: saved.image_id,
+            num_systematic: saved.num_systematic,
+            systematic_received: saved.systematic_received,
+            options: saved.options,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{crc::crc32, test_data::img_230_packets, Encoder};
+
+    #[test]
+    fn push_and_decode() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut buffer = vec![SSDVPacket::zeroed(); k];
+        let mut decoder = StreamingDecoder::new(&mut buffer);
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        assert_eq!(
+            decoder.decode(&mut output).unwrap_err(),
+            DecoderError::UnknownNumSystematic
+        );
+        assert_eq!(decoder.num_packets_needed(), None);
+        for j in 0..k {
+            let mut packet = SSDVPacket::zeroed();
+            encoder.encode(u16::try_from(2 * j + 1).unwrap(), &mut packet);
+            assert!(!decoder.is_ready());
+            assert_eq!(decoder.push(&packet), PushStatus::Stored);
+            assert_eq!(decoder.push(&packet), PushStatus::Duplicate);
+        }
+        assert_eq!(
+            decoder.missing_ids().collect::<Vec<u16>>(),
+            (0..k as u16).filter(|id| id % 2 == 0).collect::<Vec<u16>>()
+        );
+        assert_eq!(decoder.num_packets_needed(), Some(0));
+        assert!(decoder.is_ready());
+        assert_eq!(decoder.num_systematic(), Some(k as u16));
+        assert_eq!(decoder.num_systematic_received(), k / 2);
+        assert_eq!(decoder.push(&ssdv[0]), PushStatus::BufferFull);
+        let mut other = ssdv[0];
+        other.set_image_id(other.image_id() + 1);
+        assert_eq!(decoder.push(&other), PushStatus::WrongCrc);
+        other.set_crc32(crc32(other.crc32_data().iter()));
+        assert_eq!(decoder.push(&other), PushStatus::OtherImage);
+        let (decoded, _) = decoder.decode(&mut output).unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+        assert_eq!(decoder.num_packets(), 0);
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn save_and_restore() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut buffer = vec![SSDVPacket::zeroed(); k];
+        let mut decoder = StreamingDecoder::new(&mut buffer);
+        for packet in ssdv.iter().skip(1) {
+            decoder.push(packet);
+        }
+        let mut state = vec![0; (SSDVPacket::LEN + 3) * k + 16];
+        let state = decoder.save(&mut state).unwrap();
+
+        let mut small = vec![SSDVPacket::zeroed(); k - 2];
+        assert_eq!(
+            StreamingDecoder::restore(state, &mut small).unwrap_err(),
+            StateError::BufferTooSmall
+        );
+        state[0] = STATE_VERSION + 1;
+        assert_eq!(
+            StreamingDecoder::<SSDVPacket>::restore(state, &mut small).unwrap_err(),
+            StateError::UnsupportedVersion(STATE_VERSION + 1)
+        );
+        state[0] = STATE_VERSION;
+        let mut restored_buffer = vec![SSDVPacket::zeroed(); k];
+        let mut restored = StreamingDecoder::restore(state, &mut restored_buffer).unwrap();
+        assert_eq!(restored.packets(), &ssdv[1..]);
+        assert_eq!(restored.num_systematic(), Some(k as u16));
+        assert_eq!(restored.num_systematic_received(), k - 1);
+        assert_eq!(restored.missing_ids().collect::<Vec<u16>>(), [0]);
+        assert_eq!(restored.push(&ssdv[1]), PushStatus::Duplicate);
+        assert_eq!(restored.push(&ssdv[0]), PushStatus::Stored);
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        let (decoded, _) = restored.decode(&mut output).unwrap();
+        assert_eq!(decoded, &ssdv[..]);
+    }
+
+    #[test]
+    fn decode_partial() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut buffer = vec![SSDVPacket::zeroed(); k];
+        let mut decoder = StreamingDecoder::new(&mut buffer);
+        for id in [0, 3, 9, 10, k as u16 - 1, k as u16 + 5] {
+            let mut packet = SSDVPacket::zeroed();
+            encoder.encode(id, &mut packet);
+            assert_eq!(decoder.push(&packet), PushStatus::Stored);
+        }
+        let mut output = vec![SSDVPacket::zeroed(); 10];
+        let mut valid = [0xff; 2];
+        assert_eq!(
+            decoder.try_decode_partial(&mut output, &mut valid[..1]),
+            Err(DecoderError::OutputTooShort)
+        );
+        assert_eq!(valid, [0xff; 2]);
+        assert_eq!(decoder.try_decode_partial(&mut output, &mut valid), Ok(3));
+        assert_eq!(valid, [0b0000_1001, 0b10]);
+        assert_eq!(output[0], ssdv[0]);
+        assert_eq!(output[3], ssdv[3]);
+        assert_eq!(output[9], ssdv[9]);
+        assert_eq!(output[1], SSDVPacket::zeroed());
+        assert_eq!(decoder.num_packets(), 6);
+    }
+}
@@ -1 +1,29 @@
+use crate::{Packet, SSDVNoFecPacket, SSDVPacket, SSDV_DATA_LEN, SSDV_PACKET_LEN};
+
 pub static IMG_230_SSDV: &[u8; 14170] = include_bytes!("img_230.ssdv");
+
+// Returns the packets of IMG_230_SSDV.
+pub fn img_230_packets() -> Vec<SSDVPacket> {
+    IMG_230_SSDV
+        .chunks_exact(SSDV_PACKET_LEN)
+        .map(|chunk| SSDVPacket(chunk.try_into().unwrap()))
+        .collect()
+}
+
+// Returns the packets of IMG_230_SSDV converted to the no-FEC format, with a
+// fixed callsign and a valid CRC-32.
+pub fn img_230_no_fec_packets() -> Vec<SSDVNoFecPacket> {
+    img_230_packets()
+        .iter()
+        .map(|lj2| {
+            let mut packet = SSDVNoFecPacket::zeroed();
+            packet.0[0] = 0x55;
+            packet.0[1] = 0x67;
+            packet.0[2..6].copy_from_slice(&[0x00, 0x0e, 0x72, 0x40]);
+            packet.0[6..12].copy_from_slice(&lj2.0[..6]);
+            packet.data_as_mut()[..SSDV_DATA_LEN].copy_from_slice(lj2.data());
+            packet.set_crc32(packet.compute_crc32());
+            packet
+        })
+        .collect()
+}
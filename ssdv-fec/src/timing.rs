@@ -0,0 +1,144 @@
+use crate::progress::Operation;
+#[cfg(feature = "cycle-count")]
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(all(
+    feature = "cycle-count",
+    not(all(target_arch = "arm", target_os = "none")),
+    not(feature = "std")
+))]
+compile_error!("the cycle-count feature needs a Cortex-M target or the std feature");
+
+/// Execution time of an operation.
+///
+/// This is returned by [`execution_time`]. The times are measured in ticks,
+/// which are CPU cycles counted by the DWT cycle counter on Cortex-M, and
+/// microseconds on other targets, which need the `std` feature. On Cortex-M,
+/// the firmware must enable the cycle counter with
+/// `DCB::enable_trace` and `DWT::enable_cycle_counter` before the times are
+/// measured, and an operation must not take longer than 2³² cycles.
+#[cfg(feature = "cycle-count")]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ExecutionTime {
+    /// Execution time of the last run of the operation, in ticks.
+    pub last: u32,
+    /// Maximum execution time of the operation, in ticks.
+    pub max: u32,
+    /// Number of runs of the operation.
+    pub count: u32,
+}
+
+// Execution times of each operation, indexed by the Operation.
+#[cfg(feature = "cycle-count")]
+static LAST: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+#[cfg(feature = "cycle-count")]
+static MAX: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+#[cfg(feature = "cycle-count")]
+static COUNT: [AtomicU32; 4] = [const { AtomicU32::new(0) }; 4];
+
+/// Returns the execution time of an operation.
+///
+/// With the `cycle-count` feature, the encoder and decoder measure the
+/// execution time of each encoder setup, encoder release with restore, packet
+/// encoding and decoding, so that the worst-case execution times can be
+/// checked against the schedule of the firmware. The maximum is taken over
+/// all the runs since the start or the last call to
+/// [`reset_execution_times`]. The async variants of these functions are not
+/// measured, since their execution time includes that of other tasks.
+#[cfg(feature = "cycle-count")]
+pub fn execution_time(operation: Operation) -> ExecutionTime {
+    let index = operation as usize;
+    ExecutionTime {
+        last: LAST[index].load(Ordering::Relaxed),
+        max: MAX[index].load(Ordering::Relaxed),
+        count: COUNT[index].load(Ordering::Relaxed),
+    }
+}
+
+/// Resets the execution times of all the operations.
+#[cfg(feature = "cycle-count")]
+pub fn reset_execution_times() {
+    for times in [&LAST, &MAX, &COUNT] {
+        for time in times {
+            time.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// Measures the execution time of an operation. Without the `cycle-count`
+// feature, this does nothing.
+#[derive(Debug)]
+pub(crate) struct Stopwatch {
+    #[cfg(feature = "cycle-count")]
+    operation: Operation,
+    #[cfg(all(feature = "cycle-count", target_arch = "arm", target_os = "none"))]
+    start: u32,
+    #[cfg(all(
+        feature = "cycle-count",
+        not(all(target_arch = "arm", target_os = "none"))
+    ))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    #[cfg_attr(not(feature = "cycle-count"), allow(unused_variables))]
+    pub(crate) fn start(operation: Operation) -> Stopwatch {
+        Stopwatch {
+            #[cfg(feature = "cycle-count")]
+            operation,
+            #[cfg(all(feature = "cycle-count", target_arch = "arm", target_os = "none"))]
+            start: cortex_m::peripheral::DWT::cycle_count(),
+            #[cfg(all(
+                feature = "cycle-count",
+                not(all(target_arch = "arm", target_os = "none"))
+            ))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    // Records the execution time of the operation.
+    pub(crate) fn stop(self) {
+        #[cfg(feature = "cycle-count")]
+        {
+            #[cfg(all(target_arch = "arm", target_os = "none"))]
+            let ticks = cortex_m::peripheral::DWT::cycle_count().wrapping_sub(self.start);
+            #[cfg(not(all(target_arch = "arm", target_os = "none")))]
+            let ticks = u32::try_from(self.start.elapsed().as_micros()).unwrap_or(u32::MAX);
+            let index = self.operation as usize;
+            LAST[index].store(ticks, Ordering::Relaxed);
+            MAX[index].fetch_max(ticks, Ordering::Relaxed);
+            COUNT[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "cycle-count"))]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, Decoder, Encoder, SSDVPacket};
+
+    #[test]
+    fn execution_times() {
+        let ssdv = img_230_packets();
+        let k = ssdv.len();
+        let mut ssdv_copy = ssdv.clone();
+        let encoder = Encoder::new(&mut ssdv_copy).unwrap();
+        let mut packets = vec![SSDVPacket::zeroed(); k];
+        encoder
+            .try_encode_range(u16::try_from(k).unwrap(), &mut packets)
+            .unwrap();
+        packets[0] = ssdv[k - 1];
+        let mut output = vec![SSDVPacket::zeroed(); k];
+        Decoder::decode(&mut packets, &mut output).unwrap();
+        // other tests can run concurrently, so this only checks that the
+        // times have been recorded
+        let decode = execution_time(Operation::Decode);
+        assert!(decode.count >= 1);
+        assert!(decode.max >= decode.last);
+        let encode = execution_time(Operation::Encode);
+        assert!(encode.count >= k as u32);
+        assert!(encode.max >= encode.last);
+        assert!(execution_time(Operation::EncoderSetup).count >= 1);
+    }
+}
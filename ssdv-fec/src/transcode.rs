@@ -0,0 +1,691 @@
+//! Conversion of SSDV packets to JPEG images.
+//!
+//! The payload of the systematic SSDV packets of an image is a baseline JPEG
+//! entropy-coded bitstream that uses the standard Huffman tables of the JPEG
+//! specification. The JPEG headers are not transmitted. Instead, they are
+//! regenerated from the image dimensions, the chroma subsampling mode and the
+//! quality level given in the packet headers. To make each packet decodable
+//! independently, the first MCU that begins in each packet starts at a byte
+//! boundary whose offset is given in the packet header, together with the MCU
+//! index, and its DC coefficients are coded as absolute values instead of as
+//! differences with the previous MCU.
+//!
+//! [`ssdv_to_jpeg`] parses the packets, restores the differential coding of the
+//! DC coefficients and writes a JPEG image. The MCUs that are lost because of
+//! missing packets are filled in gray. This module is written in pure Rust, so
+//! it can be used in `no_std` environments with an allocator and in
+//! WebAssembly, where the ssdv C library is not available.
+//!
+//! This module is only available with the `alloc` feature.
+
+use crate::Packet;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Error produced by [`ssdv_to_jpeg`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TranscodeError {
+    /// There are no valid systematic packets.
+    NoPackets,
+    /// The image width or height is zero.
+    InvalidDimensions,
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TranscodeError::NoPackets => "no valid systematic packets",
+            TranscodeError::InvalidDimensions => "invalid image dimensions",
+        })
+    }
+}
+
+impl core::error::Error for TranscodeError {}
+
+// Length of the header fields of a packet that precede the payload: image ID,
+// packet ID, width, height, flags, MCU offset and MCU index.
+const HEADER_LEN: usize = 9;
+// MCU offset indicating that no MCU begins in a packet.
+const NO_MCU: u8 = 0xff;
+
+/// Converts the systematic packets of an SSDV image to a JPEG image.
+///
+/// The packets can be given in any order and can contain duplicates. Packets
+/// with a wrong CRC-32, FEC packets and packets from an image different from
+/// the one of the first valid packet are ignored, so this function is usually
+/// called with the output of the FEC decoder. If some systematic packets are
+/// missing, the MCUs that cannot be decoded are filled in gray.
+pub fn ssdv_to_jpeg<P: Packet>(packets: &[P]) -> Result<Vec<u8>, TranscodeError> {
+    let mut packets = packets
+        .iter()
+        .filter(|packet| packet.compute_crc32() == packet.crc32() && !packet.is_fec_packet())
+        .collect::<Vec<&P>>();
+    let first = packets.first().ok_or(TranscodeError::NoPackets)?;
+    let image_id = first.image_id();
+    packets.retain(|packet| packet.image_id() == image_id);
+    packets.sort_by_key(|packet| packet.packet_id());
+    packets.dedup_by_key(|packet| packet.packet_id());
+
+    let first = packets[0];
+    // FEC packets have been discarded, so the dimensions are present.
+    let width = first.width().unwrap_or(0);
+    let height = first.height().unwrap_or(0);
+    if width == 0 || height == 0 {
+        return Err(TranscodeError::InvalidDimensions);
+    }
+    let flags = first.flags();
+    let format = Format {
+        width: u16::from(width) * 16,
+        height: u16::from(height) * 16,
+        // Only the 2x2, 1x2, 2x1 and 1x1 luma sampling factors exist, so they
+        // are encoded as their logarithm.
+        sampling: match flags & 0x3 {
+            0 => (2, 2),
+            1 => (1, 2),
+            2 => (2, 1),
+            _ => (1, 1),
+        },
+        // The quality level is XORed with 4 so that packets produced before
+        // the quality level was introduced have the default level.
+        quality: ((flags >> 3) & 0x7) ^ 4,
+    };
+
+    let mut transcoder = Transcoder::new(&format);
+    // Packets with consecutive packet IDs form a continuous bitstream, since
+    // the MCUs can span several packets.
+    let mut start = 0;
+    while start < packets.len() {
+        let mut end = start + 1;
+        while end < packets.len()
+            && u32::from(packets[end].packet_id()) == u32::from(packets[end - 1].packet_id()) + 1
+        {
+            end += 1;
+        }
+        transcoder.transcode_run(&packets[start..end]);
+        start = end;
+    }
+    Ok(transcoder.finish())
+}
+
+#[derive(Debug)]
+struct Format {
+    width: u16,
+    height: u16,
+    // horizontal and vertical luma sampling factors
+    sampling: (u8, u8),
+    quality: u8,
+}
+
+impl Format {
+    fn num_mcus(&self) -> usize {
+        let (h, v) = self.sampling;
+        usize::from(self.width / (8 * u16::from(h))) * usize::from(self.height / (8 * u16::from(v)))
+    }
+
+    fn luma_blocks(&self) -> usize {
+        usize::from(self.sampling.0 * self.sampling.1)
+    }
+}
+
+// Point in the bitstream of a run of packets where an MCU begins.
+#[derive(Debug, Copy, Clone)]
+struct McuStart {
+    bit_pos: usize,
+    mcu: usize,
+}
+
+#[derive(Debug)]
+struct Transcoder {
+    output: BitWriter,
+    num_mcus: usize,
+    luma_blocks: usize,
+    // next MCU to be written
+    next_mcu: usize,
+    // DC predictors of the output for the luma and the two chroma components
+    dc: [i32; 3],
+}
+
+impl Transcoder {
+    fn new(format: &Format) -> Transcoder {
+        let mut output = BitWriter::new();
+        write_headers(&mut output.bytes, format);
+        Transcoder {
+            output,
+            num_mcus: format.num_mcus(),
+            luma_blocks: format.luma_blocks(),
+            next_mcu: 0,
+            dc: [0; 3],
+        }
+    }
+
+    // Transcodes the MCUs contained in a run of packets with consecutive
+    // packet IDs.
+    fn transcode_run<P: Packet>(&mut self, packets: &[&P]) {
+        let mut payload = Vec::new();
+        let mut starts = Vec::new();
+        for packet in packets {
+            let bytes = &packet.as_bytes()[P::HEADER_OFFSET..P::LEN - 4];
+            let offset = bytes[6];
+            let payload_len = bytes.len() - HEADER_LEN;
+            if offset != NO_MCU && usize::from(offset) < payload_len {
+                starts.push(McuStart {
+                    bit_pos: 8 * (payload.len() + usize::from(offset)),
+                    mcu: usize::from(u16::from_be_bytes([bytes[7], bytes[8]])),
+                });
+            }
+            payload.extend_from_slice(&bytes[HEADER_LEN..]);
+        }
+        // The data before the first MCU start belongs to an MCU that begins in
+        // a missing packet, so it is discarded.
+        for (j, start) in starts.iter().enumerate() {
+            let end = starts.get(j + 1).map(|next| next.mcu);
+            self.transcode_segment(&payload, *start, end);
+        }
+    }
+
+    // Transcodes the MCUs between an MCU start and the MCU of the next MCU
+    // start, or the end of the data.
+    fn transcode_segment(&mut self, payload: &[u8], start: McuStart, end: Option<usize>) {
+        // Inconsistent MCU indices, which are not possible unless a packet
+        // with a valid CRC is corrupted, are ignored.
+        if start.mcu < self.next_mcu || start.mcu >= self.num_mcus {
+            return;
+        }
+        let end = end.unwrap_or(self.num_mcus).min(self.num_mcus);
+        self.fill_gray(start.mcu);
+        let mut reader = BitReader {
+            data: payload,
+            pos: start.bit_pos,
+        };
+        // The DC coefficients of the first MCU are absolute.
+        let mut dc = [0; 3];
+        let mut blocks = [[0i32; 64]; 6];
+        while self.next_mcu < end {
+            let nblocks = self.luma_blocks + 2;
+            let mut dc_mcu = dc;
+            for (j, block) in blocks[..nblocks].iter_mut().enumerate() {
+                let component = j.saturating_sub(self.luma_blocks - 1).min(2);
+                if read_block(&mut reader, component, &mut dc_mcu[component], block).is_none() {
+                    // The rest of the MCUs of the segment are lost.
+                    return;
+                }
+            }
+            dc = dc_mcu;
+            for (j, block) in blocks[..nblocks].iter().enumerate() {
+                let component = j.saturating_sub(self.luma_blocks - 1).min(2);
+                self.write_block(component, block);
+            }
+            self.next_mcu += 1;
+        }
+    }
+
+    // Writes gray MCUs until the MCU with index `mcu`.
+    fn fill_gray(&mut self, mcu: usize) {
+        let gray = [0; 64];
+        while self.next_mcu < mcu {
+            for component in 0..self.luma_blocks + 2 {
+                self.write_block(component.saturating_sub(self.luma_blocks - 1).min(2), &gray);
+            }
+            self.next_mcu += 1;
+        }
+    }
+
+    // Writes a block given its absolute DC coefficient and its AC coefficients
+    // in zigzag order.
+    fn write_block(&mut self, component: usize, block: &[i32; 64]) {
+        let (dc_table, ac_table) = tables(component);
+        let diff = block[0] - self.dc[component];
+        self.dc[component] = block[0];
+        let (size, bits) = magnitude(diff);
+        self.output.write_symbol(dc_table, size);
+        self.output.write_bits(bits, size);
+        let mut run = 0;
+        for &coefficient in &block[1..] {
+            if coefficient == 0 {
+                run += 1;
+                continue;
+            }
+            while run >= 16 {
+                // ZRL
+                self.output.write_symbol(ac_table, 0xf0);
+                run -= 16;
+            }
+            let (size, bits) = magnitude(coefficient);
+            self.output.write_symbol(ac_table, (run << 4) | size);
+            self.output.write_bits(bits, size);
+            run = 0;
+        }
+        if run > 0 {
+            // EOB
+            self.output.write_symbol(ac_table, 0x00);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.fill_gray(self.num_mcus);
+        self.output.flush();
+        let mut jpeg = self.output.bytes;
+        // EOI
+        jpeg.extend_from_slice(&[0xff, 0xd9]);
+        jpeg
+    }
+}
+
+// Reads a block, updating the DC predictor of its component. The AC
+// coefficients are stored in zigzag order. Returns `None` if the data ends or
+// contains an invalid Huffman code.
+fn read_block(
+    reader: &mut BitReader,
+    component: usize,
+    dc: &mut i32,
+    block: &mut [i32; 64],
+) -> Option<()> {
+    let (dc_table, ac_table) = tables(component);
+    *block = [0; 64];
+    let size = reader.read_symbol(dc_table)?;
+    if size > 11 {
+        return None;
+    }
+    *dc += extend(reader.read_bits(size)?, size);
+    block[0] = *dc;
+    let mut k = 1;
+    while k < 64 {
+        let symbol = reader.read_symbol(ac_table)?;
+        let (run, size) = (usize::from(symbol >> 4), symbol & 0xf);
+        if size == 0 {
+            if run == 15 {
+                // ZRL
+                k += 16;
+                continue;
+            }
+            // EOB
+            break;
+        }
+        k += run;
+        if k >= 64 || size > 10 {
+            return None;
+        }
+        block[k] = extend(reader.read_bits(size)?, size);
+        k += 1;
+    }
+    Some(())
+}
+
+// Converts the additional bits of a coefficient to its value.
+fn extend(bits: u32, size: u8) -> i32 {
+    if size == 0 {
+        0
+    } else if bits < 1 << (size - 1) {
+        bits as i32 - (1 << size) + 1
+    } else {
+        bits as i32
+    }
+}
+
+// Returns the size category and the additional bits of a coefficient.
+fn magnitude(value: i32) -> (u8, u32) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (size, bits as u32 & ((1 << size) - 1))
+}
+
+#[derive(Debug)]
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn read_bit(&mut self) -> Option<u32> {
+        let byte = self.data.get(self.pos / 8)?;
+        let bit = (byte >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        Some(u32::from(bit))
+    }
+
+    fn read_bits(&mut self, len: u8) -> Option<u32> {
+        (0..len).try_fold(0, |value, _| Some((value << 1) | self.read_bit()?))
+    }
+
+    fn read_symbol(&mut self, table: &HuffmanTable) -> Option<u8> {
+        // Canonical Huffman decoding: the codes of each length are
+        // consecutive, starting after the codes of the previous length.
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in table.counts.iter() {
+            code |= self.read_bit()?;
+            let count = u32::from(count);
+            if code < first + count {
+                return Some(table.symbols[index + (code - first) as usize]);
+            }
+            index += count as usize;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    nbits: u8,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    fn write_bits(&mut self, bits: u32, len: u8) {
+        for j in (0..len).rev() {
+            self.acc = (self.acc << 1) | ((bits >> j) & 1);
+            self.nbits += 1;
+            if self.nbits == 8 {
+                let byte = self.acc as u8;
+                self.bytes.push(byte);
+                // byte stuffing
+                if byte == 0xff {
+                    self.bytes.push(0x00);
+                }
+                self.acc = 0;
+                self.nbits = 0;
+            }
+        }
+    }
+
+    fn write_symbol(&mut self, table: &HuffmanTable, symbol: u8) {
+        let mut code = 0;
+        let mut index = 0;
+        for (len, &count) in (1..).zip(table.counts.iter()) {
+            let count = usize::from(count);
+            if let Some(j) = table.symbols[index..index + count]
+                .iter()
+                .position(|&s| s == symbol)
+            {
+                self.write_bits(code + j as u32, len);
+                return;
+            }
+            index += count;
+            code = (code + count as u32) << 1;
+        }
+        unreachable!("symbol {symbol:#04x} not in Huffman table");
+    }
+
+    // Pads the last byte with ones.
+    fn flush(&mut self) {
+        if self.nbits > 0 {
+            self.write_bits(0xff, 8 - self.nbits);
+        }
+    }
+}
+
+fn write_headers(jpeg: &mut Vec<u8>, format: &Format) {
+    // SOI
+    jpeg.extend_from_slice(&[0xff, 0xd8]);
+    // APP0 (JFIF 1.01, no density, no thumbnail)
+    write_segment(
+        jpeg,
+        0xe0,
+        &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0],
+    );
+    for (id, table) in [(0, &LUMA_DQT), (1, &CHROMA_DQT)] {
+        let mut dqt = [0; 65];
+        dqt[0] = id;
+        for (j, q) in dqt[1..].iter_mut().enumerate() {
+            *q = scale_quantizer(table[usize::from(ZIGZAG[j])], format.quality);
+        }
+        write_segment(jpeg, 0xdb, &dqt);
+    }
+    let [h0, h1] = format.height.to_be_bytes();
+    let [w0, w1] = format.width.to_be_bytes();
+    let (h, v) = format.sampling;
+    #[rustfmt::skip]
+    let sof0 = [
+        8, h0, h1, w0, w1, 3,
+        // component ID, sampling factors and quantization table
+        1, (h << 4) | v, 0,
+        2, 0x11, 1,
+        3, 0x11, 1,
+    ];
+    write_segment(jpeg, 0xc0, &sof0);
+    for (class_id, table) in [
+        (0x00, &LUMA_DC),
+        (0x10, &LUMA_AC),
+        (0x01, &CHROMA_DC),
+        (0x11, &CHROMA_AC),
+    ] {
+        let mut dht = Vec::with_capacity(1 + 16 + table.symbols.len());
+        dht.push(class_id);
+        dht.extend_from_slice(&table.counts);
+        dht.extend_from_slice(table.symbols);
+        write_segment(jpeg, 0xc4, &dht);
+    }
+    // SOS with the component IDs and their Huffman tables, and the spectral
+    // selection and successive approximation of a baseline scan
+    write_segment(jpeg, 0xda, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+}
+
+fn write_segment(jpeg: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    jpeg.extend_from_slice(&[0xff, marker]);
+    jpeg.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+    jpeg.extend_from_slice(data);
+}
+
+// Scales an entry of a standard quantization table to a quality level, as
+// done by the IJG JPEG library for the JPEG quality factor corresponding to
+// the level. The default level 4 gives the standard tables.
+fn scale_quantizer(value: u8, level: u8) -> u8 {
+    const QUALITY: [u32; 8] = [13, 18, 29, 43, 50, 71, 86, 100];
+    let quality = QUALITY[usize::from(level)];
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+    ((u32::from(value) * scale + 50) / 100).clamp(1, 255) as u8
+}
+
+// Returns the DC and AC Huffman tables of a component.
+fn tables(component: usize) -> (&'static HuffmanTable, &'static HuffmanTable) {
+    if component == 0 {
+        (&LUMA_DC, &LUMA_AC)
+    } else {
+        (&CHROMA_DC, &CHROMA_AC)
+    }
+}
+
+#[derive(Debug)]
+struct HuffmanTable {
+    // number of codes of each length from 1 to 16 bits
+    counts: [u8; 16],
+    // symbols in order of increasing code
+    symbols: &'static [u8],
+}
+
+// Zigzag order of the coefficients of a block, as indices in natural order.
+#[rustfmt::skip]
+const ZIGZAG: [u8; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Standard quantization tables in natural order (JPEG specification, Annex K).
+#[rustfmt::skip]
+const LUMA_DQT: [u8; 64] = [
+    16, 11, 10, 16,  24,  40,  51,  61,
+    12, 12, 14, 19,  26,  58,  60,  55,
+    14, 13, 16, 24,  40,  57,  69,  56,
+    14, 17, 22, 29,  51,  87,  80,  62,
+    18, 22, 37, 56,  68, 109, 103,  77,
+    24, 35, 55, 64,  81, 104, 113,  92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103,  99,
+];
+
+#[rustfmt::skip]
+const CHROMA_DQT: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+// Standard Huffman tables (JPEG specification, Annex K).
+static LUMA_DC: HuffmanTable = HuffmanTable {
+    counts: [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0],
+    symbols: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+
+static CHROMA_DC: HuffmanTable = HuffmanTable {
+    counts: [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+    symbols: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+
+static LUMA_AC: HuffmanTable = HuffmanTable {
+    counts: [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d],
+    symbols: &[
+        0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61,
+        0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52,
+        0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25,
+        0x26, 0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45,
+        0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64,
+        0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83,
+        0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99,
+        0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6,
+        0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3,
+        0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8,
+        0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ],
+};
+
+static CHROMA_AC: HuffmanTable = HuffmanTable {
+    counts: [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77],
+    symbols: &[
+        0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61,
+        0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33,
+        0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18,
+        0x19, 0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44,
+        0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63,
+        0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a,
+        0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97,
+        0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4,
+        0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca,
+        0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7,
+        0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ],
+};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, SSDVPacket};
+
+    #[test]
+    fn huffman_tables() {
+        for table in [&LUMA_DC, &CHROMA_DC, &LUMA_AC, &CHROMA_AC] {
+            let count = table.counts.iter().map(|&c| usize::from(c)).sum::<usize>();
+            assert_eq!(count, table.symbols.len());
+            // every symbol round-trips through the encoder and decoder
+            for &symbol in table.symbols {
+                let mut writer = BitWriter::new();
+                writer.write_symbol(table, symbol);
+                writer.flush();
+                // SSDV packets do not use byte stuffing
+                let mut data = writer.bytes.clone();
+                if data.starts_with(&[0xff, 0x00]) {
+                    data.remove(1);
+                }
+                let mut reader = BitReader {
+                    data: &data,
+                    pos: 0,
+                };
+                assert_eq!(reader.read_symbol(table), Some(symbol));
+            }
+        }
+    }
+
+    #[test]
+    fn coefficient_magnitude() {
+        for value in -2047..=2047 {
+            let (size, bits) = magnitude(value);
+            assert_eq!(extend(bits, size), value);
+        }
+    }
+
+    // Splits a JPEG image into its markers, checking its structure.
+    fn markers(jpeg: &[u8]) -> Vec<u8> {
+        assert_eq!(jpeg[..2], [0xff, 0xd8]);
+        assert_eq!(jpeg[jpeg.len() - 2..], [0xff, 0xd9]);
+        let mut markers = Vec::new();
+        let mut pos = 2;
+        loop {
+            assert_eq!(jpeg[pos], 0xff);
+            let marker = jpeg[pos + 1];
+            markers.push(marker);
+            let len = usize::from(u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]));
+            pos += 2 + len;
+            if marker == 0xda {
+                break;
+            }
+        }
+        // The entropy-coded data does not contain markers.
+        let data = &jpeg[pos..jpeg.len() - 2];
+        assert!(data.windows(2).all(|w| w[0] != 0xff || w[1] == 0x00));
+        markers
+    }
+
+    #[test]
+    fn transcode_image() {
+        let packets = img_230_packets();
+        let jpeg = ssdv_to_jpeg(&packets).unwrap();
+        assert_eq!(
+            markers(&jpeg),
+            [0xe0, 0xdb, 0xdb, 0xc0, 0xc4, 0xc4, 0xc4, 0xc4, 0xda]
+        );
+        // order and duplicates do not matter
+        let mut shuffled = packets.clone();
+        shuffled.reverse();
+        shuffled.push(packets[3]);
+        assert_eq!(ssdv_to_jpeg(&shuffled).unwrap(), jpeg);
+
+        // With missing packets, the image is still complete.
+        let partial = packets
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| j % 5 != 2)
+            .map(|(_, packet)| *packet)
+            .collect::<Vec<_>>();
+        let partial_jpeg = ssdv_to_jpeg(&partial).unwrap();
+        markers(&partial_jpeg);
+        // all MCUs are present in the output, so it has a similar size
+        assert!(partial_jpeg.len() > jpeg.len() / 2);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            ssdv_to_jpeg::<SSDVPacket>(&[]),
+            Err(TranscodeError::NoPackets)
+        );
+        let mut packet = img_230_packets()[0];
+        packet.0[20] ^= 1;
+        assert_eq!(ssdv_to_jpeg(&[packet]), Err(TranscodeError::NoPackets));
+    }
+}
@@ -0,0 +1,467 @@
+//! Packet sources and sinks.
+//!
+//! The [`PacketSource`] and [`PacketSink`] traits abstract the transports
+//! through which packets are received and transmitted, so that applications
+//! can be written once for all of them. They are implemented by
+//! [`PacketSocket`] (UDP), [`ByteStream`] (TCP connections, serial ports and
+//! files), [`KissTnc`] and [`AgwpeClient`], as well as by the ZeroMQ and MQTT
+//! transports of the CLI application. The [`Kiss`] adapter adds KISS framing to
+//! any source or sink.
+//!
+//! With the `async` feature, the async flavors of the traits,
+//! `AsyncPacketSource` and `AsyncPacketSink`, are also available, and they are
+//! implemented for the tokio UDP socket, [`ByteStream`] and [`Kiss`].
+//!
+//! This module is only available with the `transport` feature.
+
+use crate::{
+    agwpe::AgwpeClient,
+    encap::ax25::strip_ui_header,
+    kiss::{kiss_frame, KissDeframer, KissTnc},
+    net::PacketSocket,
+    PacketMetadata,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Read, Write},
+};
+
+// Size of the buffer used to read from byte streams
+const READ_BUFFER_LEN: usize = 4096;
+
+/// Data received from a [`PacketSource`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Received {
+    /// Datagram, together with the metadata given by the receiver.
+    ///
+    /// The datagram should contain one packet, or some KISS frames if KISS
+    /// framing is used.
+    Datagram(Vec<u8>, PacketMetadata),
+    /// Chunk of a byte stream.
+    ///
+    /// The packets are sent back-to-back in the byte stream, or in KISS frames
+    /// if KISS framing is used.
+    Stream(Vec<u8>),
+    /// Nothing has been received before the timeout of the source expired.
+    ///
+    /// This allows applications to perform periodic tasks while they wait for
+    /// packets.
+    Timeout,
+    /// The source has been closed, so nothing else will be received.
+    Closed,
+}
+
+/// Source of received packets.
+pub trait PacketSource {
+    /// Receives data from the source.
+    ///
+    /// This blocks until some data is received, the timeout of the source
+    /// expires, or the source is closed.
+    fn recv(&mut self) -> io::Result<Received>;
+}
+
+/// Sink for transmitted packets.
+pub trait PacketSink {
+    /// Sends a datagram, which contains a packet or a KISS frame.
+    ///
+    /// Sinks that use a byte stream send the datagrams back-to-back.
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()>;
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for &mut S {
+    fn recv(&mut self) -> io::Result<Received> {
+        (**self).recv()
+    }
+}
+
+impl<S: PacketSource + ?Sized> PacketSource for Box<S> {
+    fn recv(&mut self) -> io::Result<Received> {
+        (**self).recv()
+    }
+}
+
+impl<S: PacketSink + ?Sized> PacketSink for &mut S {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        (**self).send(datagram)
+    }
+}
+
+impl<S: PacketSink + ?Sized> PacketSink for Box<S> {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        (**self).send(datagram)
+    }
+}
+
+// Returns true if the error has been caused by the timeout of a read.
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+impl PacketSource for PacketSocket {
+    fn recv(&mut self) -> io::Result<Received> {
+        match self.recv_datagram() {
+            Ok((datagram, _)) => Ok(Received::Datagram(
+                datagram.to_vec(),
+                PacketMetadata::default(),
+            )),
+            Err(err) if is_timeout(&err) => Ok(Received::Timeout),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl PacketSink for PacketSocket {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.send_datagram(datagram)
+    }
+}
+
+impl<T: Read> PacketSource for KissTnc<T> {
+    fn recv(&mut self) -> io::Result<Received> {
+        match self.recv_frame() {
+            Ok(frame) => Ok(Received::Datagram(frame, PacketMetadata::default())),
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(Received::Closed),
+            Err(err) if is_timeout(&err) => Ok(Received::Timeout),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<T: Write> PacketSink for KissTnc<T> {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.send_frame(datagram)
+    }
+}
+
+/// The information field of each AX.25 UI frame is received as a datagram.
+/// Other AX.25 frames are discarded.
+impl<T: Read> PacketSource for AgwpeClient<T> {
+    fn recv(&mut self) -> io::Result<Received> {
+        loop {
+            match self.recv_frame() {
+                Ok((_, frame)) => {
+                    if let Some(payload) = strip_ui_header(&frame) {
+                        return Ok(Received::Datagram(
+                            payload.to_vec(),
+                            PacketMetadata::default(),
+                        ));
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(Received::Closed),
+                Err(err) if is_timeout(&err) => return Ok(Received::Timeout),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Byte stream transport.
+///
+/// This wraps a byte stream, such as a TCP connection, a serial port or a file.
+/// The end of the stream closes the source, and the timeouts of the stream are
+/// returned as [`Received::Timeout`].
+#[derive(Debug)]
+pub struct ByteStream<T> {
+    stream: T,
+    buffer: Box<[u8]>,
+}
+
+impl<T> ByteStream<T> {
+    /// Creates a new byte stream transport.
+    pub fn new(stream: T) -> ByteStream<T> {
+        ByteStream {
+            stream,
+            buffer: vec![0; READ_BUFFER_LEN].into_boxed_slice(),
+        }
+    }
+
+    /// Returns a reference to the byte stream.
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the byte stream.
+    ///
+    /// This can be used to set timeouts.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.stream
+    }
+
+    /// Returns the byte stream.
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+}
+
+impl<T: Read> PacketSource for ByteStream<T> {
+    fn recv(&mut self) -> io::Result<Received> {
+        loop {
+            match self.stream.read(&mut self.buffer) {
+                Ok(0) => return Ok(Received::Closed),
+                Ok(len) => return Ok(Received::Stream(self.buffer[..len].to_vec())),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) if is_timeout(&err) => return Ok(Received::Timeout),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl<T: Write> PacketSink for ByteStream<T> {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.stream.write_all(datagram)
+    }
+}
+
+/// KISS framing adapter.
+///
+/// As a source, the data received from the inner source, either datagrams or
+/// chunks of a byte stream, is deframed, and the payload of each KISS data
+/// frame is returned as a datagram, with the metadata of the datagram that
+/// completed the frame. As a sink, each datagram is sent to the inner sink in a
+/// KISS data frame.
+#[derive(Debug)]
+pub struct Kiss<S> {
+    inner: S,
+    deframer: KissDeframer,
+    frames: VecDeque<(Vec<u8>, PacketMetadata)>,
+}
+
+impl<S> Kiss<S> {
+    /// Creates a new KISS adapter that uses port 0.
+    pub fn new(inner: S) -> Kiss<S> {
+        Kiss {
+            inner,
+            deframer: KissDeframer::new(),
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// Returns a reference to the inner source or sink.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner source or sink.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns the inner source or sink.
+    ///
+    /// Frames that have been received but not returned yet are lost.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    // Returns the next deframed frame.
+    fn next_frame(&mut self) -> Option<Received> {
+        self.frames
+            .pop_front()
+            .map(|(frame, metadata)| Received::Datagram(frame, metadata))
+    }
+
+    // Deframes the data received from the inner source. Received events that
+    // do not contain data are returned.
+    fn deframe(&mut self, received: Received) -> Option<Received> {
+        let (data, metadata) = match received {
+            Received::Datagram(data, metadata) => (data, metadata),
+            Received::Stream(data) => (data, PacketMetadata::default()),
+            received => return Some(received),
+        };
+        self.frames.extend(
+            self.deframer
+                .push(&data)
+                .into_iter()
+                .map(|frame| (frame, metadata)),
+        );
+        None
+    }
+}
+
+impl<S: PacketSource> PacketSource for Kiss<S> {
+    fn recv(&mut self) -> io::Result<Received> {
+        loop {
+            if let Some(frame) = self.next_frame() {
+                return Ok(frame);
+            }
+            let received = self.inner.recv()?;
+            if let Some(received) = self.deframe(received) {
+                return Ok(received);
+            }
+        }
+    }
+}
+
+impl<S: PacketSink> PacketSink for Kiss<S> {
+    fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+        self.inner.send(&kiss_frame(datagram))
+    }
+}
+
+#[cfg(feature = "async")]
+pub use async_transport::{AsyncPacketSink, AsyncPacketSource};
+
+#[cfg(feature = "async")]
+mod async_transport {
+    use super::*;
+    use std::future::Future;
+    use tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+        net::UdpSocket,
+    };
+
+    // Maximum size of a UDP datagram
+    const MAX_DATAGRAM_LEN: usize = 65536;
+
+    /// Async source of received packets.
+    ///
+    /// This is the async flavor of [`PacketSource`]. Async sources do not have
+    /// timeouts, so they never return [`Received::Timeout`]. Timeouts can be
+    /// implemented with `tokio::time::timeout` instead.
+    pub trait AsyncPacketSource {
+        /// Receives data from the source.
+        fn recv(&mut self) -> impl Future<Output = io::Result<Received>> + Send;
+    }
+
+    /// Async sink for transmitted packets.
+    ///
+    /// This is the async flavor of [`PacketSink`].
+    pub trait AsyncPacketSink {
+        /// Sends a datagram, which contains a packet or a KISS frame.
+        fn send(&mut self, datagram: &[u8]) -> impl Future<Output = io::Result<()>> + Send;
+    }
+
+    /// The datagrams are received from any address.
+    impl AsyncPacketSource for UdpSocket {
+        async fn recv(&mut self) -> io::Result<Received> {
+            let mut buffer = vec![0; MAX_DATAGRAM_LEN];
+            let (len, _) = self.recv_from(&mut buffer).await?;
+            buffer.truncate(len);
+            Ok(Received::Datagram(buffer, PacketMetadata::default()))
+        }
+    }
+
+    /// The datagrams are sent to the address to which the socket is
+    /// connected.
+    impl AsyncPacketSink for UdpSocket {
+        async fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+            UdpSocket::send(self, datagram).await?;
+            Ok(())
+        }
+    }
+
+    impl<T: AsyncRead + Unpin + Send> AsyncPacketSource for ByteStream<T> {
+        async fn recv(&mut self) -> io::Result<Received> {
+            match AsyncReadExt::read(&mut self.stream, &mut self.buffer).await? {
+                0 => Ok(Received::Closed),
+                len => Ok(Received::Stream(self.buffer[..len].to_vec())),
+            }
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin + Send> AsyncPacketSink for ByteStream<T> {
+        async fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+            self.stream.write_all(datagram).await
+        }
+    }
+
+    impl<S: AsyncPacketSource + Send> AsyncPacketSource for Kiss<S> {
+        async fn recv(&mut self) -> io::Result<Received> {
+            loop {
+                if let Some(frame) = self.next_frame() {
+                    return Ok(frame);
+                }
+                let received = self.inner.recv().await?;
+                if let Some(received) = self.deframe(received) {
+                    return Ok(received);
+                }
+            }
+        }
+    }
+
+    impl<S: AsyncPacketSink + Send> AsyncPacketSink for Kiss<S> {
+        async fn send(&mut self, datagram: &[u8]) -> io::Result<()> {
+            self.inner.send(&kiss_frame(datagram)).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::IMG_230_SSDV, SSDV_PACKET_LEN};
+    use std::{io::Cursor, time::Duration};
+
+    #[test]
+    fn byte_stream() {
+        // the transports are used as trait objects, since they also implement
+        // the async traits
+        let packets = &IMG_230_SSDV[..2 * SSDV_PACKET_LEN];
+        let mut framed = Vec::new();
+        let sink: &mut dyn PacketSink = &mut Kiss::new(ByteStream::new(&mut framed));
+        for packet in packets.chunks_exact(SSDV_PACKET_LEN) {
+            sink.send(packet).unwrap();
+        }
+        assert_eq!(
+            framed,
+            packets
+                .chunks_exact(SSDV_PACKET_LEN)
+                .flat_map(kiss_frame)
+                .collect::<Vec<u8>>()
+        );
+
+        let source: &mut dyn PacketSource = &mut ByteStream::new(Cursor::new(packets));
+        assert_eq!(source.recv().unwrap(), Received::Stream(packets.to_vec()));
+        assert_eq!(source.recv().unwrap(), Received::Closed);
+
+        let source: &mut dyn PacketSource = &mut Kiss::new(ByteStream::new(Cursor::new(framed)));
+        for packet in packets.chunks_exact(SSDV_PACKET_LEN) {
+            assert_eq!(
+                source.recv().unwrap(),
+                Received::Datagram(packet.to_vec(), PacketMetadata::default())
+            );
+        }
+        assert_eq!(source.recv().unwrap(), Received::Closed);
+    }
+
+    #[test]
+    fn udp() {
+        let packet = &IMG_230_SSDV[..SSDV_PACKET_LEN];
+        let mut source = Kiss::new(PacketSocket::bind("127.0.0.1:0").unwrap());
+        source
+            .get_ref()
+            .get_ref()
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let sink = PacketSocket::bind("127.0.0.1:0").unwrap();
+        sink.connect(source.get_ref().get_ref().local_addr().unwrap())
+            .unwrap();
+        let mut sink: Box<dyn PacketSink> = Box::new(Kiss::new(sink));
+        sink.send(packet).unwrap();
+        assert_eq!(
+            source.recv().unwrap(),
+            Received::Datagram(packet.to_vec(), PacketMetadata::default())
+        );
+        assert_eq!(source.recv().unwrap(), Received::Timeout);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_udp() {
+        use tokio::net::UdpSocket;
+
+        let packet = &IMG_230_SSDV[..SSDV_PACKET_LEN];
+        let mut source = Kiss::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let sink = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sink.connect(source.get_ref().local_addr().unwrap())
+            .await
+            .unwrap();
+        let mut sink = Kiss::new(sink);
+        AsyncPacketSink::send(&mut sink, packet).await.unwrap();
+        assert_eq!(
+            AsyncPacketSource::recv(&mut source).await.unwrap(),
+            Received::Datagram(packet.to_vec(), PacketMetadata::default())
+        );
+    }
+}
@@ -0,0 +1,322 @@
+//! Upload of received packets to an SSDV server.
+//!
+//! This module contains [`UploadClient`], an async client for servers
+//! implementing the [ssdv.habhub.org](https://ssdv.habhub.org/) packet upload
+//! API, which assemble the images received by many receivers. The server uses
+//! the standard SSDV packet format, so the packets are converted to this format
+//! before being uploaded. Uploads are split into batches, and failed requests
+//! are retried with an exponential backoff.
+//!
+//! This module is only available with the `upload` feature.
+
+use crate::{standard::standard_packet, SSDVPacket};
+use base64::Engine;
+use serde::Serialize;
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+/// Default URL of the SSDV server API.
+pub const DEFAULT_URL: &str = "https://ssdv.habhub.org/api/v0/packets";
+
+/// Default maximum number of packets uploaded in a single request.
+pub const DEFAULT_MAX_BATCH: usize = 64;
+
+#[derive(Serialize, Debug)]
+struct PacketUpload {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    packet: String,
+    encoding: &'static str,
+    received: String,
+    receiver: String,
+}
+
+#[derive(Serialize, Debug)]
+struct PacketsUpload {
+    #[serde(rename = "type")]
+    type_: &'static str,
+    packets: Vec<PacketUpload>,
+}
+
+/// Upload error.
+#[derive(Debug)]
+pub enum UploadError {
+    /// The request could not be sent or the server returned an error status.
+    Http(reqwest::Error),
+    /// The request body could not be serialized.
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::Http(err) => write!(f, "upload request failed: {err}"),
+            UploadError::Json(err) => write!(f, "could not serialize upload request: {err}"),
+        }
+    }
+}
+
+impl core::error::Error for UploadError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            UploadError::Http(err) => Some(err),
+            UploadError::Json(err) => Some(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for UploadError {
+    fn from(err: reqwest::Error) -> UploadError {
+        UploadError::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for UploadError {
+    fn from(err: serde_json::Error) -> UploadError {
+        UploadError::Json(err)
+    }
+}
+
+/// Async client for an SSDV server.
+///
+/// The client uploads packets together with the time at which they were
+/// received and the callsign of the receiver. Since the server only
+/// understands systematic packets, FEC packets should not be uploaded.
+#[derive(Debug, Clone)]
+pub struct UploadClient {
+    client: reqwest::Client,
+    url: String,
+    callsign: String,
+    max_batch: usize,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl UploadClient {
+    /// Creates a new client that uploads packets with a receiver callsign.
+    ///
+    /// By default, the client uses [`DEFAULT_URL`], uploads at most
+    /// [`DEFAULT_MAX_BATCH`] packets in each request, and retries each failed
+    /// request 3 times, waiting 1 second before the first retry.
+    pub fn new(callsign: &str) -> UploadClient {
+        UploadClient {
+            client: reqwest::Client::builder()
+                .user_agent(concat!("ssdv-fec/", env!("CARGO_PKG_VERSION")))
+                .timeout(Duration::from_secs(30))
+                .build()
+                // this only fails if the TLS backend cannot be initialized
+                .expect("could not create HTTP client"),
+            url: DEFAULT_URL.to_string(),
+            callsign: callsign.to_string(),
+            max_batch: DEFAULT_MAX_BATCH,
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the URL of the SSDV server API.
+    pub fn with_url(mut self, url: &str) -> UploadClient {
+        self.url = url.to_string();
+        self
+    }
+
+    /// Sets the maximum number of packets uploaded in a single request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_batch` is zero.
+    pub fn with_max_batch(mut self, max_batch: usize) -> UploadClient {
+        assert!(max_batch > 0, "the maximum batch size must be positive");
+        self.max_batch = max_batch;
+        self
+    }
+
+    /// Sets the number of times that a failed request is retried and the
+    /// delay before the first retry.
+    ///
+    /// The delay is doubled for each subsequent retry. Requests rejected by
+    /// the server with a client error status other than 429 (Too Many
+    /// Requests) are not retried, since they would fail again.
+    pub fn with_retries(mut self, retries: u32, delay: Duration) -> UploadClient {
+        self.retries = retries;
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Returns the receiver callsign.
+    pub fn callsign(&self) -> &str {
+        &self.callsign
+    }
+
+    /// Uploads some packets.
+    ///
+    /// Each packet is accompanied by the time at which it was received. The
+    /// packets are uploaded in batches, in order. If a batch fails after all
+    /// its retries, the function returns the error without uploading the
+    /// remaining batches.
+    pub async fn upload(&self, packets: &[(SSDVPacket, SystemTime)]) -> Result<(), UploadError> {
+        for batch in packets.chunks(self.max_batch) {
+            self.upload_batch(batch).await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_batch(&self, packets: &[(SSDVPacket, SystemTime)]) -> Result<(), UploadError> {
+        let body = serde_json::to_string(&PacketsUpload {
+            type_: "packets",
+            packets: packets
+                .iter()
+                .map(|(packet, received)| PacketUpload {
+                    type_: "packet",
+                    packet: base64::engine::general_purpose::STANDARD
+                        .encode(standard_packet(packet)),
+                    encoding: "base64",
+                    received: humantime::format_rfc3339_seconds(*received).to_string(),
+                    receiver: self.callsign.clone(),
+                })
+                .collect(),
+        })?;
+        let mut delay = self.retry_delay;
+        let mut retries = self.retries;
+        loop {
+            match self.post(body.clone()).await {
+                Err(err) if retries > 0 && is_transient(&err) => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    retries -= 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn post(&self, body: String) -> Result<(), UploadError> {
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+// Returns true if a failed request may succeed if it is retried.
+fn is_transient(err: &UploadError) -> bool {
+    match err {
+        UploadError::Http(err) => match err.status() {
+            Some(status) => {
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            None => true,
+        },
+        UploadError::Json(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::img_230_packets;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    // Reads an HTTP request and returns its body.
+    async fn read_request(stream: &mut TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let len = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..len]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                let content_length = headers
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(|len| len.parse::<usize>().unwrap())
+                    })
+                    .unwrap();
+                if body.len() == content_length {
+                    return body.to_string();
+                }
+            }
+        }
+    }
+
+    // Runs a server that answers each request with the next status and
+    // returns the bodies of the requests.
+    async fn server(statuses: &[u16]) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/v0/packets", listener.local_addr().unwrap());
+        let statuses = statuses.to_vec();
+        let handle = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                bodies.push(read_request(&mut stream).await);
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    fn packets() -> Vec<(SSDVPacket, SystemTime)> {
+        img_230_packets()
+            .into_iter()
+            .map(|packet| (packet, SystemTime::UNIX_EPOCH))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn batches_and_retries() {
+        let packets = packets();
+        let batch = 50;
+        assert!(packets.len() > batch && packets.len() <= 2 * batch);
+        let (url, server) = server(&[503, 200, 200]).await;
+        let client = UploadClient::new("EA4GPZ")
+            .with_url(&url)
+            .with_max_batch(batch)
+            .with_retries(1, Duration::from_millis(1));
+        client.upload(&packets).await.unwrap();
+        let bodies = server.await.unwrap();
+        // the first batch is sent twice
+        assert_eq!(bodies[0], bodies[1]);
+        let body: serde_json::Value = serde_json::from_str(&bodies[0]).unwrap();
+        assert_eq!(body["type"], "packets");
+        let uploaded = body["packets"].as_array().unwrap();
+        assert_eq!(uploaded.len(), batch);
+        assert_eq!(uploaded[0]["receiver"], "EA4GPZ");
+        assert_eq!(uploaded[0]["received"], "1970-01-01T00:00:00Z");
+        assert_eq!(
+            uploaded[0]["packet"],
+            base64::engine::general_purpose::STANDARD.encode(standard_packet(&packets[0].0))
+        );
+        let body: serde_json::Value = serde_json::from_str(&bodies[2]).unwrap();
+        assert_eq!(
+            body["packets"].as_array().unwrap().len(),
+            packets.len() - batch
+        );
+    }
+
+    #[tokio::test]
+    async fn client_error_is_not_retried() {
+        let (url, server) = server(&[400]).await;
+        let client = UploadClient::new("EA4GPZ")
+            .with_url(&url)
+            .with_retries(3, Duration::from_millis(1));
+        let err = client.upload(&packets()[..1]).await.unwrap_err();
+        assert!(matches!(&err, UploadError::Http(err) if err.status().unwrap().as_u16() == 400));
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,194 @@
+//! Validation of decoded images.
+//!
+//! The FEC decoder only guarantees that the systematic packets of an image
+//! have been recovered. This module checks that the image they contain can
+//! actually be decoded, by converting the packets to a JPEG image with
+//! [`ssdv_to_jpeg`](crate::transcode::ssdv_to_jpeg) and parsing the result with
+//! the [image](https://crates.io/crates/image) crate. JPEG and PNG images
+//! obtained by other means can also be checked with [`verify_image`].
+//!
+//! This module is only available with the `image` feature.
+
+use crate::{transcode::ssdv_to_jpeg, Packet};
+use image::ImageFormat;
+use std::{collections::BTreeSet, fmt};
+
+// JPEG end of image marker
+const JPEG_EOI: [u8; 2] = [0xff, 0xd9];
+
+/// Result of the validation of an image.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "verdict", rename_all = "snake_case")
+)]
+pub enum ImageVerdict {
+    /// The image decodes cleanly.
+    Clean {
+        /// Image width in pixels.
+        width: u32,
+        /// Image height in pixels.
+        height: u32,
+    },
+    /// The image decodes, but some parts of it are missing.
+    ///
+    /// For SSDV packets, this means that some systematic packets are missing,
+    /// so that the corresponding parts of the image are filled in gray. For
+    /// JPEG images, it means that the image is truncated.
+    Partial {
+        /// Image width in pixels.
+        width: u32,
+        /// Image height in pixels.
+        height: u32,
+    },
+    /// The image cannot be decoded.
+    Invalid {
+        /// Description of the error.
+        error: String,
+    },
+}
+
+impl ImageVerdict {
+    /// Returns true if the image decodes cleanly.
+    pub fn is_clean(&self) -> bool {
+        matches!(self, ImageVerdict::Clean { .. })
+    }
+}
+
+impl fmt::Display for ImageVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageVerdict::Clean { width, height } => write!(f, "clean ({width}x{height})"),
+            ImageVerdict::Partial { width, height } => write!(f, "partial ({width}x{height})"),
+            ImageVerdict::Invalid { error } => write!(f, "invalid ({error})"),
+        }
+    }
+}
+
+/// Checks whether a JPEG or PNG image decodes cleanly.
+pub fn verify_image(data: &[u8]) -> ImageVerdict {
+    let image = match image::load_from_memory(data) {
+        Ok(image) => image,
+        Err(err) => {
+            return ImageVerdict::Invalid {
+                error: err.to_string(),
+            }
+        }
+    };
+    let (width, height) = (image.width(), image.height());
+    // The JPEG decoder of the image crate fills the missing part of truncated
+    // images, so truncation is detected by the absence of the EOI marker.
+    let truncated =
+        matches!(image::guess_format(data), Ok(ImageFormat::Jpeg)) && !data.ends_with(&JPEG_EOI);
+    if !truncated {
+        ImageVerdict::Clean { width, height }
+    } else {
+        ImageVerdict::Partial { width, height }
+    }
+}
+
+/// Checks whether the systematic packets of an SSDV image decode cleanly.
+///
+/// The packets can be given in any order and can contain duplicates and FEC
+/// packets, so this function is usually called with the output of the FEC
+/// decoder. As in [`ssdv_to_jpeg`](crate::transcode::ssdv_to_jpeg), packets
+/// with a wrong CRC-32 and packets from an image different from the one of the
+/// first valid packet are ignored.
+pub fn verify_packets<P: Packet>(packets: &[P]) -> ImageVerdict {
+    let jpeg = match ssdv_to_jpeg(packets) {
+        Ok(jpeg) => jpeg,
+        Err(err) => {
+            return ImageVerdict::Invalid {
+                error: err.to_string(),
+            }
+        }
+    };
+    match verify_image(&jpeg) {
+        ImageVerdict::Clean { width, height } if !all_systematic(packets) => {
+            ImageVerdict::Partial { width, height }
+        }
+        verdict => verdict,
+    }
+}
+
+// Returns true if all the systematic packets of the image are present, up to
+// the packet with the EOI flag.
+fn all_systematic<P: Packet>(packets: &[P]) -> bool {
+    let mut valid = packets
+        .iter()
+        .filter(|packet| packet.compute_crc32() == packet.crc32() && !packet.is_fec_packet());
+    let Some(first) = valid.next() else {
+        return false;
+    };
+    let image_id = first.image_id();
+    let mut eoi = None;
+    let ids = core::iter::once(first)
+        .chain(valid)
+        .filter(|packet| packet.image_id() == image_id)
+        .inspect(|packet| {
+            if packet.is_eoi() {
+                eoi = Some(packet.packet_id());
+            }
+        })
+        .map(|packet| packet.packet_id())
+        .collect::<BTreeSet<u16>>();
+    eoi.is_some_and(|eoi| ids.len() == usize::from(eoi) + 1 && ids.last() == Some(&eoi))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_data::img_230_packets, SSDVPacket};
+    use image::{DynamicImage, ImageFormat};
+    use std::io::Cursor;
+
+    #[test]
+    fn packets() {
+        let mut packets = img_230_packets();
+        let verdict = verify_packets(&packets);
+        assert!(verdict.is_clean());
+        let ImageVerdict::Clean { width, height } = verdict else {
+            unreachable!()
+        };
+        let first = packets[0];
+        assert_eq!(width, u32::from(first.width().unwrap()) * 16);
+        assert_eq!(height, u32::from(first.height().unwrap()) * 16);
+
+        packets.remove(5);
+        assert_eq!(
+            verify_packets(&packets),
+            ImageVerdict::Partial { width, height }
+        );
+        assert!(matches!(
+            verify_packets::<SSDVPacket>(&[]),
+            ImageVerdict::Invalid { .. }
+        ));
+
+        let jpeg = ssdv_to_jpeg(&packets).unwrap();
+        assert_eq!(verify_image(&jpeg), ImageVerdict::Clean { width, height });
+        assert_eq!(
+            verify_image(&jpeg[..jpeg.len() / 2]),
+            ImageVerdict::Partial { width, height }
+        );
+    }
+
+    #[test]
+    fn png() {
+        let mut png = Vec::new();
+        DynamicImage::new_luma8(32, 16)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        assert_eq!(
+            verify_image(&png),
+            ImageVerdict::Clean {
+                width: 32,
+                height: 16
+            }
+        );
+        assert!(matches!(
+            verify_image(&png[..png.len() / 2]),
+            ImageVerdict::Invalid { .. }
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::progress::{Meter, Operation};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+// Future that returns `Pending` once, waking its task, so that the executor
+// can run other tasks before polling it again.
+//
+// This is the same as `embassy_futures::yield_now`, and works with any
+// executor, including those of Embassy and RTIC.
+#[derive(Debug, Default)]
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+// Meter that also yields to the executor each time that its period elapses,
+// which is on the order of 1 ms on a Cortex-M4 at 80 MHz.
+#[derive(Debug)]
+pub(crate) struct Yielder {
+    meter: Meter,
+}
+
+impl Yielder {
+    pub(crate) fn new(operation: Operation, total: u32) -> Yielder {
+        Yielder {
+            meter: Meter::new(operation, total),
+        }
+    }
+
+    // See Meter::step.
+    pub(crate) async fn step(&mut self, operations: usize) {
+        if self.meter.step(operations) {
+            YieldNow::default().await;
+        }
+    }
+
+    // See Meter::work.
+    pub(crate) async fn work(&mut self, operations: usize) {
+        if self.meter.work(operations) {
+            YieldNow::default().await;
+        }
+    }
+}